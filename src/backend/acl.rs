@@ -0,0 +1,559 @@
+use crate::{glob_match, Backend, BackendError};
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+
+/// The user connections are authenticated as until they call AUTH with another.
+pub const DEFAULT_USER: &str = "default";
+
+/// The categories of commands ACL rules name, as `+@read`.
+pub const ACL_CATEGORIES: &[&str] = &["all", "read", "write", "admin"];
+
+/// The users clients authenticate as, and the user each connection is authenticated as.
+#[derive(Debug)]
+pub struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+    // the connections authenticated, by client ID
+    clients: RwLock<HashMap<u64, String>>,
+    // where ACL SAVE and ACL LOAD keep the users, none if empty
+    file: RwLock<String>,
+}
+
+/// A user: whether it may be authenticated as, the SHA-256 of its passwords, and the
+/// commands and keys it's allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct User {
+    pub enabled: bool,
+    // any password authenticates
+    pub nopass: bool,
+    pub passwords: BTreeSet<String>,
+    // in order, the last matching a command decides, none allowing any
+    pub commands: Vec<CommandRule>,
+    // the glob patterns of the keys allowed
+    pub keys: Vec<String>,
+}
+
+/// A command, or a category of them as `@read`, allowed or denied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandRule {
+    pub allow: bool,
+    pub target: String,
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        let default = User {
+            enabled: true,
+            nopass: true,
+            passwords: BTreeSet::new(),
+            commands: vec![CommandRule {
+                allow: true,
+                target: "@all".to_string(),
+            }],
+            keys: vec!["*".to_string()],
+        };
+        Acl {
+            users: RwLock::new(BTreeMap::from([(DEFAULT_USER.to_string(), default)])),
+            clients: RwLock::new(HashMap::new()),
+            file: RwLock::new(String::new()),
+        }
+    }
+}
+
+impl User {
+    /// Apply an ACL rule, as ACL SETUSER takes them: on, off, >password, <password, #hash,
+    /// !hash, nopass, resetpass, +command, -command, +@category, -@category, allcommands,
+    /// nocommands, ~pattern, allkeys, resetkeys or reset. Returns why it couldn't be.
+    pub fn apply_rule(&mut self, rule: &str) -> Result<(), String> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "allcommands" => return self.apply_rule("+@all"),
+            "nocommands" => return self.apply_rule("-@all"),
+            "allkeys" => self.keys = vec!["*".to_string()],
+            "resetkeys" => self.keys.clear(),
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "reset" => *self = User::default(),
+            _ => match rule.split_at(rule.chars().next().map_or(0, char::len_utf8)) {
+                (">", password) => {
+                    self.nopass = false;
+                    self.passwords.insert(hash_password(password));
+                }
+                ("<", password) => {
+                    if !self.passwords.remove(&hash_password(password)) {
+                        return Err("no such password".to_string());
+                    }
+                }
+                ("#", hash) => {
+                    check_hash(hash)?;
+                    self.nopass = false;
+                    self.passwords.insert(hash.to_string());
+                }
+                ("!", hash) => {
+                    check_hash(hash)?;
+                    if !self.passwords.remove(hash) {
+                        return Err("no such password".to_string());
+                    }
+                }
+                (sign @ ("+" | "-"), target) => {
+                    let target = target.to_ascii_lowercase();
+                    match target.strip_prefix('@') {
+                        Some(category) if !ACL_CATEGORIES.contains(&category) => {
+                            return Err("Unknown command or category name in ACL".to_string())
+                        }
+                        // the rules before it no longer matter
+                        Some("all") => self.commands.clear(),
+                        _ if target.is_empty() => return Err("Syntax error".to_string()),
+                        _ => {}
+                    }
+                    self.commands.push(CommandRule {
+                        allow: sign == "+",
+                        target,
+                    });
+                }
+                ("~", pattern) => self.keys.push(pattern.to_string()),
+                _ => return Err("Syntax error".to_string()),
+            },
+        }
+        Ok(())
+    }
+
+    /// The rules that make the user, as ACL LIST lists them.
+    pub fn rules(&self) -> String {
+        let mut rules = vec![if self.enabled { "on" } else { "off" }.to_string()];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        rules.extend(self.passwords.iter().map(|hash| format!("#{}", hash)));
+        rules.extend(self.keys.iter().map(|pattern| format!("~{}", pattern)));
+        rules.push(self.command_rules());
+        rules.join(" ")
+    }
+
+    /// The command rules of the user, `-@all` if it has none.
+    pub fn command_rules(&self) -> String {
+        match self.commands.is_empty() {
+            true => "-@all".to_string(),
+            false => self
+                .commands
+                .iter()
+                .map(|rule| format!("{}{}", if rule.allow { '+' } else { '-' }, rule.target))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    // whether the user may run the command named name, which is in the categories for
+    // which in_category is true
+    fn allows_command(&self, name: &str, in_category: impl Fn(&str) -> bool) -> bool {
+        self.commands
+            .iter()
+            .rev()
+            .find(|rule| match rule.target.strip_prefix('@') {
+                Some(category) => in_category(category),
+                None => rule.target == name,
+            })
+            .is_some_and(|rule| rule.allow)
+    }
+
+    fn allows_key(&self, key: &str) -> bool {
+        self.keys
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), key.as_bytes()))
+    }
+
+    // whether password authenticates as the user
+    fn accepts(&self, password: &str) -> bool {
+        self.enabled && (self.nopass || self.passwords.contains(&hash_password(password)))
+    }
+}
+
+/// The SHA-256 of password in lowercase hex, how the passwords of the users are kept.
+pub fn hash_password(password: &str) -> String {
+    format!("{:x}", Sha256::digest(password.as_bytes()))
+}
+
+fn check_hash(hash: &str) -> Result<(), String> {
+    match hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        true => Ok(()),
+        false => Err("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters".to_string()),
+    }
+}
+
+impl Backend {
+    /// Check the credentials of AUTH, the default user's when there's no username. The
+    /// default user takes any password until it's given one, by `requirepass` or ACL SETUSER.
+    pub fn authenticate(&self, username: Option<&str>, password: &str) -> Result<(), BackendError> {
+        let users = self.acl.users.read();
+        let user = users.get(username.unwrap_or(DEFAULT_USER));
+        match user {
+            Some(user) if username.is_none() && user.nopass => Err(BackendError::NoPassword),
+            Some(user) if user.accepts(password) => Ok(()),
+            _ => Err(BackendError::WrongPass),
+        }
+    }
+
+    /// Authenticate the connection of ID id as username, the default user if it's None.
+    pub fn auth_client(
+        &self,
+        id: u64,
+        username: Option<&str>,
+        password: &str,
+    ) -> Result<(), BackendError> {
+        self.authenticate(username, password)?;
+        let username = username.unwrap_or(DEFAULT_USER).to_string();
+        self.acl.clients.write().insert(id, username);
+        Ok(())
+    }
+
+    /// Authenticate a new connection as the default user, if it needs no password. RESET
+    /// brings the connection back to it.
+    pub fn acl_connect(&self, id: u64) {
+        let users = self.acl.users.read();
+        let mut clients = self.acl.clients.write();
+        match users.get(DEFAULT_USER) {
+            Some(user) if user.enabled && user.nopass => {
+                clients.insert(id, DEFAULT_USER.to_string());
+            }
+            _ => {
+                clients.remove(&id);
+            }
+        }
+    }
+
+    /// Authenticate the connection of ID id as the user named by the common name of the
+    /// certificate its client presented over TLS, if it's an enabled user. It's left as
+    /// acl_connect has it otherwise.
+    pub fn auth_client_certificate(&self, id: u64, common_name: &str) -> bool {
+        let users = self.acl.users.read();
+        match users.get(common_name) {
+            Some(user) if user.enabled => {
+                let mut clients = self.acl.clients.write();
+                clients.insert(id, common_name.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn acl_disconnect(&self, id: u64) {
+        self.acl.clients.write().remove(&id);
+    }
+
+    /// Whether the connection of ID id is authenticated, as a user that still exists.
+    pub fn is_authenticated(&self, id: u64) -> bool {
+        self.acl.clients.read().contains_key(&id)
+    }
+
+    /// The user the connection of ID id is authenticated as.
+    pub fn client_user(&self, id: u64) -> Option<String> {
+        self.acl.clients.read().get(&id).cloned()
+    }
+
+    /// Check the connection of ID id may run the command named name, in the categories for
+    /// which in_category is true, on keys: it must be authenticated, and its user allowed the
+    /// command and every key.
+    pub fn acl_check(
+        &self,
+        id: u64,
+        name: &str,
+        in_category: impl Fn(&str) -> bool,
+        keys: &[String],
+    ) -> Result<(), BackendError> {
+        let users = self.acl.users.read();
+        let clients = self.acl.clients.read();
+        let Some((username, user)) = clients
+            .get(&id)
+            .and_then(|username| Some((username, users.get(username)?)))
+        else {
+            return Err(BackendError::NoAuth);
+        };
+        if !user.allows_command(name, in_category) {
+            return Err(BackendError::NoPermCommand(
+                username.clone(),
+                name.to_string(),
+            ));
+        }
+        if !keys.iter().all(|key| user.allows_key(key)) {
+            return Err(BackendError::NoPermKey);
+        }
+        Ok(())
+    }
+
+    /// Give the default user the password of `requirepass`, or none if it's empty.
+    pub(crate) fn set_default_password(&self, password: &str) {
+        let mut users = self.acl.users.write();
+        let user = users.entry(DEFAULT_USER.to_string()).or_default();
+        match password {
+            "" => user.apply_rule("nopass"),
+            password => user
+                .apply_rule("resetpass")
+                .and_then(|()| user.apply_rule(&format!(">{}", password))),
+        }
+        .expect("password rules are valid");
+    }
+
+    /// Create the user if it doesn't exist, disabled and without password, then apply the
+    /// rules, all or none of them.
+    pub fn acl_setuser(&self, username: &str, rules: &[String]) -> Result<(), BackendError> {
+        let mut users = self.acl.users.write();
+        let mut user = users.get(username).cloned().unwrap_or_default();
+        for rule in rules {
+            user.apply_rule(rule)
+                .map_err(|reason| BackendError::AclRule(rule.clone(), reason))?;
+        }
+        users.insert(username.to_string(), user);
+        Ok(())
+    }
+
+    pub fn acl_getuser(&self, username: &str) -> Option<User> {
+        self.acl.users.read().get(username).cloned()
+    }
+
+    /// Every user with its rules, as `user <name> <rules>` lines sorted by name.
+    pub fn acl_list(&self) -> Vec<String> {
+        self.acl
+            .users
+            .read()
+            .iter()
+            .map(|(name, user)| format!("user {} {}", name, user.rules()))
+            .collect()
+    }
+
+    /// Delete the users, the connections authenticated as them having to authenticate again.
+    /// Returns how many existed.
+    pub fn acl_deluser(&self, usernames: &[String]) -> Result<usize, BackendError> {
+        if usernames.iter().any(|name| name == DEFAULT_USER) {
+            return Err(BackendError::DefaultUserRemoved);
+        }
+        let mut users = self.acl.users.write();
+        let deleted = usernames
+            .iter()
+            .filter(|name| users.remove(name.as_str()).is_some())
+            .count();
+        self.acl
+            .clients
+            .write()
+            .retain(|_, name| users.contains_key(name));
+        Ok(deleted)
+    }
+
+    /// The file the users are saved to and loaded from, none if it's empty.
+    pub fn aclfile(&self) -> String {
+        self.acl.file.read().clone()
+    }
+
+    pub fn set_aclfile(&self, path: impl Into<String>) {
+        *self.acl.file.write() = path.into();
+    }
+
+    /// Write the users to the ACL file, in the format of ACL LIST.
+    pub fn acl_save(&self) -> Result<(), BackendError> {
+        let path = self.aclfile();
+        if path.is_empty() {
+            return Err(BackendError::NoAclFile);
+        }
+        let mut content = self.acl_list().join("\n");
+        content.push('\n');
+        // written aside then renamed, a crash leaves one file or the other
+        let temp = format!("{}.save-{}", path, std::process::id());
+        fs::write(&temp, content)
+            .and_then(|()| fs::rename(&temp, &path))
+            .map_err(|e| {
+                BackendError::AclFile(format!("There was an error trying to save the ACLs: {}", e))
+            })
+    }
+
+    /// Replace the users by those of the ACL file, all or none of them. The default user is
+    /// added if the file doesn't have it, as it is when the server starts.
+    pub fn acl_load(&self) -> Result<(), BackendError> {
+        let path = self.aclfile();
+        if path.is_empty() {
+            return Err(BackendError::NoAclFile);
+        }
+        let content = fs::read_to_string(&path).map_err(|e| {
+            BackendError::AclFile(format!(
+                "Error loading ACLs, opening file '{}': {}",
+                path, e
+            ))
+        })?;
+        let loaded =
+            parse_acl(&content).map_err(|e| BackendError::AclFile(format!("{}:{}", path, e)))?;
+        let mut users = self.acl.users.write();
+        *users = loaded;
+        self.acl
+            .clients
+            .write()
+            .retain(|_, name| users.contains_key(name));
+        Ok(())
+    }
+}
+
+// the users of an ACL file, a `user <name> <rules>` line each
+fn parse_acl(content: &str) -> Result<BTreeMap<String, User>, String> {
+    let mut users = Acl::default().users.into_inner();
+    for (i, line) in content.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => continue,
+            (Some("user"), Some(name)) => {
+                let mut user = User::default();
+                for rule in words {
+                    user.apply_rule(rule).map_err(|reason| {
+                        format!(
+                            "{}: Error in user declaration '{}': {}",
+                            i + 1,
+                            rule,
+                            reason
+                        )
+                    })?;
+                }
+                users.insert(name.to_string(), user);
+            }
+            _ => return Err(format!("{}: should start with user keyword", i + 1)),
+        }
+    }
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acl_users() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        backend.acl_connect(1);
+        assert_eq!(backend.client_user(1).as_deref(), Some(DEFAULT_USER));
+        assert_eq!(
+            backend.authenticate(None, "any"),
+            Err(BackendError::NoPassword)
+        );
+
+        let rules = ["on", ">secret", ">other"].map(String::from);
+        backend.acl_setuser("alice", &rules)?;
+        assert_eq!(backend.authenticate(Some("alice"), "other"), Ok(()));
+        assert_eq!(
+            backend.authenticate(Some("alice"), "wrong"),
+            Err(BackendError::WrongPass)
+        );
+        backend.auth_client(2, Some("alice"), "secret")?;
+        assert_eq!(backend.client_user(2).as_deref(), Some("alice"));
+
+        // all the rules or none
+        let rules = ["off", "bogus"].map(String::from);
+        assert!(backend.acl_setuser("alice", &rules).is_err());
+        assert!(backend.acl_getuser("alice").unwrap().enabled);
+        backend.acl_setuser("alice", &["<other".to_string()])?;
+        assert_eq!(
+            backend.acl_list(),
+            vec![
+                format!("user alice on #{} -@all", hash_password("secret")),
+                "user default on nopass ~* +@all".to_string(),
+            ]
+        );
+
+        // requirepass is the default user's password
+        backend.set_requirepass("pass");
+        assert_eq!(backend.authenticate(None, "pass"), Ok(()));
+        backend.acl_connect(3);
+        assert!(!backend.is_authenticated(3));
+
+        assert_eq!(
+            backend.acl_deluser(&[DEFAULT_USER.to_string()]),
+            Err(BackendError::DefaultUserRemoved)
+        );
+        assert_eq!(
+            backend.acl_deluser(&["alice".to_string(), "bob".to_string()]),
+            Ok(1)
+        );
+        assert!(!backend.is_authenticated(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_permissions() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        let rules = ["on", "nopass", "+@read", "-hget", "+set", "~cache:*"].map(String::from);
+        backend.acl_setuser("reader", &rules)?;
+        backend.auth_client(1, Some("reader"), "")?;
+        let category = |categories: &'static [&'static str]| {
+            move |category: &str| category == "all" || categories.contains(&category)
+        };
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            backend.acl_check(1, "get", category(&["read"]), &keys(&["cache:1"])),
+            Ok(())
+        );
+        assert_eq!(
+            backend.acl_check(1, "get", category(&["read"]), &keys(&["user:1"])),
+            Err(BackendError::NoPermKey)
+        );
+        assert_eq!(
+            backend.acl_check(1, "hget", category(&["read"]), &keys(&["cache:1"])),
+            Err(BackendError::NoPermCommand(
+                "reader".to_string(),
+                "hget".to_string()
+            ))
+        );
+        assert_eq!(
+            backend.acl_check(1, "set", category(&["write"]), &keys(&["cache:1"])),
+            Ok(())
+        );
+        assert!(backend
+            .acl_check(1, "del", category(&["write"]), &keys(&["cache:1"]))
+            .is_err());
+        assert_eq!(
+            backend.acl_check(2, "get", category(&["read"]), &[]),
+            Err(BackendError::NoAuth)
+        );
+        assert_eq!(
+            backend.acl_getuser("reader").unwrap().rules(),
+            "on nopass ~cache:* +@read -hget +set"
+        );
+
+        // +@all drops the rules before it
+        backend.acl_setuser(
+            "reader",
+            &["allcommands".to_string(), "allkeys".to_string()],
+        )?;
+        assert_eq!(
+            backend.acl_getuser("reader").unwrap().rules(),
+            "on nopass ~* +@all"
+        );
+        assert!(backend
+            .acl_setuser("reader", &["+@bogus".to_string()])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_file() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        assert_eq!(backend.acl_save(), Err(BackendError::NoAclFile));
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.acl", std::process::id()));
+        backend.set_aclfile(path.display().to_string());
+        backend.acl_setuser("alice", &["on".to_string(), ">secret".to_string()])?;
+        backend.acl_save()?;
+
+        let loaded = Backend::new();
+        loaded.set_aclfile(path.display().to_string());
+        loaded.acl_load()?;
+        assert_eq!(loaded.acl_list(), backend.acl_list());
+
+        fs::write(&path, "user bob on\nuser carol bogus\n")?;
+        assert!(loaded.acl_load().is_err());
+        assert!(loaded.acl_getuser("alice").is_some());
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
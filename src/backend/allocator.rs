@@ -0,0 +1,137 @@
+use crate::{Backend, BackendError};
+
+/// What the allocator reports about the memory of the process, in bytes, as INFO memory and
+/// MEMORY STATS do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// The bytes allocated by the application.
+    pub allocated: u64,
+    /// The bytes of the pages holding those allocations, free space within them included.
+    pub active: u64,
+    /// The bytes of the pages mapped and resident in memory.
+    pub resident: u64,
+    /// The bytes of the pages kept mapped but not resident, for the allocator to reuse.
+    pub retained: u64,
+}
+
+impl AllocatorStats {
+    /// How much more the pages in use take than the allocations in them.
+    pub fn frag_ratio(&self) -> f64 {
+        ratio(self.active, self.allocated)
+    }
+
+    pub fn frag_bytes(&self) -> i64 {
+        self.active as i64 - self.allocated as i64
+    }
+
+    /// How much more the resident pages take than those in use, what MEMORY PURGE releases.
+    pub fn rss_ratio(&self) -> f64 {
+        ratio(self.resident, self.active)
+    }
+
+    pub fn rss_bytes(&self) -> i64 {
+        self.resident as i64 - self.active as i64
+    }
+}
+
+impl Backend {
+    /// The allocator in use, as INFO memory reports it.
+    pub fn allocator_name(&self) -> String {
+        #[cfg(feature = "jemalloc")]
+        {
+            let version = tikv_jemalloc_ctl::version::read().unwrap_or("unknown");
+            format!("jemalloc-{}", version.split('-').next().unwrap_or(version))
+        }
+        #[cfg(not(feature = "jemalloc"))]
+        {
+            "libc".to_string()
+        }
+    }
+
+    /// The stats of the allocator, refreshed first, none unless it's jemalloc.
+    pub fn allocator_stats(&self) -> Option<AllocatorStats> {
+        #[cfg(feature = "jemalloc")]
+        {
+            use tikv_jemalloc_ctl::{epoch, stats};
+
+            epoch::advance().ok()?;
+            Some(AllocatorStats {
+                allocated: stats::allocated::read().ok()? as u64,
+                active: stats::active::read().ok()? as u64,
+                resident: stats::resident::read().ok()? as u64,
+                retained: stats::retained::read().ok()? as u64,
+            })
+        }
+        #[cfg(not(feature = "jemalloc"))]
+        {
+            None
+        }
+    }
+
+    /// Release the dirty pages of every arena of the allocator back to the OS, which MEMORY
+    /// PURGE does. Nothing to release unless it's jemalloc.
+    pub fn purge_allocator(&self) -> Result<(), BackendError> {
+        #[cfg(feature = "jemalloc")]
+        {
+            // MALLCTL_ARENAS_ALL, a void control: neither read nor written
+            const PURGE: &[u8] = b"arena.4096.purge\0";
+            let ret = unsafe {
+                tikv_jemalloc_sys::mallctl(
+                    PURGE.as_ptr().cast(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    0,
+                )
+            };
+            if ret != 0 {
+                return Err(BackendError::PurgeFailed);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    if b == 0 {
+        return 0.0;
+    }
+    a as f64 / b as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocator_stats() {
+        let stats = AllocatorStats {
+            allocated: 1000,
+            active: 1500,
+            resident: 3000,
+            retained: 0,
+        };
+        assert_eq!((stats.frag_ratio(), stats.frag_bytes()), (1.5, 500));
+        assert_eq!((stats.rss_ratio(), stats.rss_bytes()), (2.0, 1500));
+        assert_eq!(AllocatorStats::default().frag_ratio(), 0.0);
+    }
+
+    #[cfg(feature = "jemalloc")]
+    #[test]
+    fn test_jemalloc() {
+        let backend = Backend::new();
+        assert!(backend.allocator_name().starts_with("jemalloc-5."));
+        let stats = backend.allocator_stats().unwrap();
+        assert!(stats.allocated > 0 && stats.active >= stats.allocated);
+        assert_eq!(backend.purge_allocator(), Ok(()));
+    }
+
+    #[cfg(not(feature = "jemalloc"))]
+    #[test]
+    fn test_libc() {
+        let backend = Backend::new();
+        assert_eq!(backend.allocator_name(), "libc");
+        assert_eq!(backend.allocator_stats(), None);
+        assert_eq!(backend.purge_allocator(), Ok(()));
+    }
+}
@@ -0,0 +1,1348 @@
+use crate::backend::{elapsed_sec, now_ms, string_bytes, AcksWaker};
+use crate::cmd::{Command, CommandError, CommandExecutor};
+use crate::{
+    decode_dump_prefix, encode_dump, is_dump, Backend, BackendError, BulkString, HashTable,
+    LatencyEvent, Library, QuickList, RespArray, RespDecode, RespEncode, RespError, RespFrame,
+    SetMembers, Snapshot, SnapshotEntry, Stream, StreamId, Value, DEFAULT_LIST_MAX_LISTPACK_SIZE,
+    DEFAULT_SET_MAX_INTSET_ENTRIES,
+};
+use bytes::BytesMut;
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+/// The default auto-aof-rewrite-percentage.
+pub const DEFAULT_AUTO_AOF_REWRITE_PERCENTAGE: u64 = 100;
+
+/// The default auto-aof-rewrite-min-size, in bytes.
+pub const DEFAULT_AUTO_AOF_REWRITE_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// XCLAIM with this min-idle claims nothing: it only creates the consumer, moves the last
+/// delivered ID and drops the deleted entries.
+pub(crate) const XCLAIM_NO_CLAIM: &str = "9223372036854775807";
+
+// how many elements a rewritten command adds at most
+const REWRITE_ITEMS_PER_COMMAND: usize = 64;
+
+/// When the AOF is fsynced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// After every write, which is replied once fsynced.
+    Always,
+    /// Once a second, so a crash loses at most the last second of writes.
+    #[default]
+    EverySec,
+    /// Whenever the OS flushes the file.
+    No,
+}
+
+impl FromStr for AppendFsync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(format!("unknown appendfsync policy: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        };
+        write!(f, "{}", policy)
+    }
+}
+
+/// The append only file: the writes, in the order they took effect, appended by a task of
+/// its own.
+#[derive(Debug)]
+pub struct Aof {
+    enabled: AtomicBool,
+    // the channel to the writing task, locked by a write from when it runs until it's
+    // appended
+    writer: Mutex<Option<AofWriter>>,
+    // how many appends were sent to the writing task, and how many of them are fsynced
+    appended: AtomicU64,
+    synced: Arc<AtomicU64>,
+    rewriting: AtomicBool,
+    // the size of the file, and its size after the last rewrite, in bytes
+    size: AtomicU64,
+    base_size: AtomicU64,
+    // whether the AOF is started with the server, as configured
+    appendonly: AtomicBool,
+    // that of the writer, read without waiting for the writes
+    fsync: Mutex<AppendFsync>,
+    auto_rewrite_percentage: AtomicU64,
+    auto_rewrite_min_size: AtomicU64,
+    // whether a rewrite starts with the keyspace as a dump, as aof-use-rdb-preamble
+    use_rdb_preamble: AtomicBool,
+    // whether the AOF is being replayed
+    loading: AtomicBool,
+    // unix time in milliseconds the running rewrite started, how long the last one took in
+    // seconds, -1 before any, and whether it succeeded
+    rewrite_started: AtomicI64,
+    last_rewrite_duration: AtomicI64,
+    last_rewrite_ok: AtomicBool,
+    // whether the last write or fsync of the writing task succeeded
+    write_ok: Arc<AtomicBool>,
+    // what was propagated outside of a logged write, to go ahead of the next one
+    pending: Mutex<Vec<RespArray>>,
+}
+
+/// Where WAITAOF waits the AOF to be fsynced up to: the writes appended to the local one, and
+/// the offset of the stream of writes for those of the replicas.
+#[derive(Debug, Clone, Copy)]
+pub struct AofTarget {
+    appended: u64,
+    offset: u64,
+}
+
+/// What `check_aof` found in an append only file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AofCheck {
+    /// How many keys the RDB preamble the file starts with holds, if it has one.
+    pub preamble_keys: Option<usize>,
+    /// How many times each command of the valid part is replayed, by lowercased name.
+    pub commands: BTreeMap<String, usize>,
+    /// The length of the part that replays, the whole file if it's valid.
+    pub valid_len: usize,
+    /// Why what follows doesn't: a bad command, or a command or a transaction cut short.
+    pub error: Option<String>,
+}
+
+/// The state of the AOF, as INFO reports it. Durations are in seconds, -1 if there's none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AofReport {
+    pub enabled: bool,
+    pub rewrite_in_progress: bool,
+    pub last_rewrite_time_sec: i64,
+    pub current_rewrite_time_sec: i64,
+    pub last_bgrewrite_ok: bool,
+    pub last_write_ok: bool,
+    pub current_size: u64,
+    pub base_size: u64,
+}
+
+impl Default for Aof {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            writer: Mutex::new(None),
+            appended: AtomicU64::new(0),
+            synced: Arc::new(AtomicU64::new(0)),
+            rewriting: AtomicBool::new(false),
+            size: AtomicU64::new(0),
+            base_size: AtomicU64::new(0),
+            appendonly: AtomicBool::new(false),
+            fsync: Mutex::new(AppendFsync::default()),
+            auto_rewrite_percentage: AtomicU64::new(DEFAULT_AUTO_AOF_REWRITE_PERCENTAGE),
+            auto_rewrite_min_size: AtomicU64::new(DEFAULT_AUTO_AOF_REWRITE_MIN_SIZE),
+            use_rdb_preamble: AtomicBool::new(true),
+            loading: AtomicBool::new(false),
+            rewrite_started: AtomicI64::new(0),
+            last_rewrite_duration: AtomicI64::new(-1),
+            last_rewrite_ok: AtomicBool::new(true),
+            write_ok: Arc::new(AtomicBool::new(true)),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AofWriter {
+    tx: mpsc::UnboundedSender<AofRequest>,
+    fsync: AppendFsync,
+    // what was appended since a rewrite started, for the rewritten file
+    rewrite_buffer: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+enum AofRequest {
+    Append(Vec<u8>),
+    // answered once everything appended before is fsynced
+    Sync(oneshot::Sender<()>),
+    // append to the rewritten file from now on
+    Reopen(fs::File),
+}
+
+thread_local! {
+    // the commands propagated by the write running on this thread, a list per nested capture
+    static PROPAGATED: RefCell<Vec<Vec<RespArray>>> = const { RefCell::new(Vec::new()) };
+    // what goes ahead of everything the write running on this thread propagates
+    static AHEAD: RefCell<Vec<RespArray>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Backend {
+    pub fn is_aof_enabled(&self) -> bool {
+        self.aof.enabled.load(Ordering::Acquire)
+    }
+
+    /// Whether the writes are propagated, to the AOF or to replicas.
+    pub fn is_propagating(&self) -> bool {
+        self.is_aof_enabled() || self.is_replication_enabled()
+    }
+
+    /// Append the writes to the AOF from now on, with the task writing it spawned on handle.
+    pub fn start_aof(&self, fsync: AppendFsync, handle: &Handle) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.aof_path())?;
+        let size = file.metadata()?.len();
+        self.aof.size.store(size, Ordering::Relaxed);
+        self.aof.base_size.store(size, Ordering::Relaxed);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let synced = self.aof.synced.clone();
+        synced.store(self.aof.appended.load(Ordering::Acquire), Ordering::Release);
+        let synced = Synced {
+            count: synced,
+            acks: self.acks_waker(),
+        };
+        let write_ok = self.aof.write_ok.clone();
+        *self.aof.fsync.lock() = fsync;
+        handle.spawn(write_aof(File::from_std(file), rx, fsync, synced, write_ok));
+        *self.aof.writer.lock() = Some(AofWriter {
+            tx,
+            fsync,
+            rewrite_buffer: None,
+        });
+        self.aof.appendonly.store(true, Ordering::Relaxed);
+        self.aof.enabled.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Whether the AOF is on, or is to be started with the server.
+    pub fn appendonly(&self) -> bool {
+        self.aof.appendonly.load(Ordering::Relaxed) || self.is_aof_enabled()
+    }
+
+    pub fn set_appendonly(&self, appendonly: bool) {
+        self.aof.appendonly.store(appendonly, Ordering::Relaxed);
+    }
+
+    /// Rewrite the AOF once it grew by percentage percent since the last rewrite, if it's at
+    /// least min_size bytes. A percentage of 0 turns automatic rewrites off.
+    pub fn set_auto_aof_rewrite(&self, percentage: u64, min_size: u64) {
+        let aof = &self.aof;
+        aof.auto_rewrite_percentage
+            .store(percentage, Ordering::Relaxed);
+        aof.auto_rewrite_min_size.store(min_size, Ordering::Relaxed);
+    }
+
+    /// The percentage and the minimum size of the automatic rewrites.
+    pub fn auto_aof_rewrite(&self) -> (u64, u64) {
+        let aof = &self.aof;
+        (
+            aof.auto_rewrite_percentage.load(Ordering::Relaxed),
+            aof.auto_rewrite_min_size.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Whether the AOF is being replayed, the keyspace being loaded as it was written.
+    pub fn is_loading(&self) -> bool {
+        self.aof.loading.load(Ordering::Acquire)
+    }
+
+    /// Whether a rewrite writes the keyspace as a dump, in the RDB format, the commands that
+    /// follow being appended to it, rather than as the commands rebuilding it.
+    pub fn aof_use_rdb_preamble(&self) -> bool {
+        self.aof.use_rdb_preamble.load(Ordering::Relaxed)
+    }
+
+    pub fn set_aof_use_rdb_preamble(&self, preamble: bool) {
+        self.aof.use_rdb_preamble.store(preamble, Ordering::Relaxed);
+    }
+
+    /// When the AOF is fsynced, or would be once started.
+    pub fn appendfsync(&self) -> AppendFsync {
+        *self.aof.fsync.lock()
+    }
+
+    /// Set the policy the AOF is to be started with.
+    pub fn set_appendfsync(&self, fsync: AppendFsync) {
+        *self.aof.fsync.lock() = fsync;
+    }
+
+    /// Whether a BGREWRITEAOF is in progress.
+    pub fn is_aof_rewriting(&self) -> bool {
+        self.aof.rewriting.load(Ordering::Acquire)
+    }
+
+    pub fn aof_report(&self) -> AofReport {
+        let aof = &self.aof;
+        let rewriting = self.is_aof_rewriting();
+        AofReport {
+            enabled: self.is_aof_enabled(),
+            rewrite_in_progress: rewriting,
+            last_rewrite_time_sec: aof.last_rewrite_duration.load(Ordering::Relaxed),
+            current_rewrite_time_sec: match rewriting {
+                true => elapsed_sec(aof.rewrite_started.load(Ordering::Relaxed)),
+                false => -1,
+            },
+            last_bgrewrite_ok: aof.last_rewrite_ok.load(Ordering::Relaxed),
+            last_write_ok: aof.write_ok.load(Ordering::Relaxed),
+            current_size: aof.size.load(Ordering::Relaxed),
+            base_size: aof.base_size.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Rewrite the AOF in the background as the shortest commands rebuilding the keyspace, or
+    /// as a dump of it with aof-use-rdb-preamble. The keyspace is copied right away and
+    /// written by another thread while the writes that follow are buffered, then appended to
+    /// the new file before it replaces the old one.
+    pub fn bgrewriteaof(&self) -> Result<(), BackendError> {
+        if self.aof.rewriting.swap(true, Ordering::AcqRel) {
+            return Err(BackendError::RewriteInProgress);
+        }
+        self.aof.rewrite_started.store(now_ms(), Ordering::Relaxed);
+        // no write runs while the AOF is held, the copy is where the buffer starts
+        let (snapshot, libraries) = {
+            let mut writer = self.aof.writer.lock();
+            if let Some(writer) = &mut *writer {
+                writer.rewrite_buffer = Some(Vec::new());
+            }
+            let started = Instant::now();
+            let snapshot = self.snapshot();
+            self.latency_sample(LatencyEvent::Fork, started.elapsed());
+            (snapshot, self.function_list(None))
+        };
+        let preamble = self.aof_use_rdb_preamble();
+        let backend = self.clone();
+        thread::spawn(move || {
+            let path = backend.aof_path();
+            let temp = path.with_file_name(format!("temp-rewriteaof-{}.aof", std::process::id()));
+            let rewritten = write_rewrite(&temp, &snapshot, &libraries, preamble)
+                .and_then(|()| backend.finish_rewrite(&temp, &path));
+            match &rewritten {
+                Ok(()) => info!("background AOF rewrite terminated with success"),
+                Err(e) => {
+                    warn!("background AOF rewrite failed: {}", e);
+                    let _ = fs::remove_file(&temp);
+                    if let Some(writer) = &mut *backend.aof.writer.lock() {
+                        writer.rewrite_buffer = None;
+                    }
+                }
+            }
+            let aof = &backend.aof;
+            let started = aof.rewrite_started.load(Ordering::Relaxed);
+            aof.last_rewrite_duration
+                .store(elapsed_sec(started), Ordering::Relaxed);
+            aof.last_rewrite_ok
+                .store(rewritten.is_ok(), Ordering::Relaxed);
+            aof.rewriting.store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+
+    // append what was buffered to the rewritten file and put it in place of the AOF, the
+    // writes that follow being appended to it
+    fn finish_rewrite(&self, temp: &Path, path: &Path) -> io::Result<()> {
+        let mut writer = self.aof.writer.lock();
+        let mut file = OpenOptions::new().append(true).open(temp)?;
+        if let Some(buffer) = writer.as_ref().and_then(|w| w.rewrite_buffer.as_ref()) {
+            file.write_all(buffer)?;
+        }
+        file.sync_all()?;
+        fs::rename(temp, path)?;
+
+        let size = file.metadata()?.len();
+        self.aof.size.store(size, Ordering::Relaxed);
+        self.aof.base_size.store(size, Ordering::Relaxed);
+        if let Some(writer) = &mut *writer {
+            writer.rewrite_buffer = None;
+            let _ = writer.tx.send(AofRequest::Reopen(file));
+        }
+        Ok(())
+    }
+
+    // whether the AOF grew enough since the last rewrite to be rewritten again
+    fn needs_rewrite(&self) -> bool {
+        let aof = &self.aof;
+        let percentage = aof.auto_rewrite_percentage.load(Ordering::Relaxed);
+        let size = aof.size.load(Ordering::Relaxed);
+        let base = aof.base_size.load(Ordering::Relaxed).max(1);
+        percentage > 0
+            && !self.is_aof_rewriting()
+            && size >= aof.auto_rewrite_min_size.load(Ordering::Relaxed)
+            && (size.saturating_sub(base)) * 100 / base >= percentage
+    }
+
+    /// Replay the commands of the AOF, if there is one, through the command path, after
+    /// loading the keys of its RDB preamble if it starts with one. What a crash cut short at
+    /// the end of the file, a command or a transaction, is truncated away. Returns how many
+    /// commands were replayed.
+    pub fn load_aof(&self) -> io::Result<usize> {
+        let path = self.aof_path();
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        // done loading even if it fails
+        struct Loading<'a>(&'a AtomicBool);
+        impl Drop for Loading<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::Release);
+            }
+        }
+        self.aof.loading.store(true, Ordering::Release);
+        let _loading = Loading(&self.aof.loading);
+
+        let mut start = 0;
+        if is_dump(&data) {
+            let (entries, len) = decode_dump_prefix(
+                &data,
+                || self.new_list(),
+                || self.new_set(),
+                || self.new_hash(),
+            )?;
+            let loaded = self.insert_entries(entries);
+            info!(
+                "loaded {} keys from the RDB preamble of {}",
+                loaded,
+                path.display()
+            );
+            start = len;
+        }
+        let mut replayed = 0;
+        let parse = |frame| self.parse_command(frame);
+        let (valid, bad) = read_aof(&data, start, parse, |_, cmd| {
+            cmd.execute(self);
+            replayed += 1;
+        });
+        if let Some(bad) = bad {
+            return Err(invalid(bad));
+        }
+        if valid < data.len() {
+            warn!(
+                "truncating {} bytes of an incomplete command at the end of {}",
+                data.len() - valid,
+                path.display()
+            );
+            OpenOptions::new()
+                .write(true)
+                .open(&path)?
+                .set_len(valid as u64)?;
+        }
+        self.clear_dirty();
+        Ok(replayed)
+    }
+
+    /// Run f, a write, then append to the AOF and send to the replicas the commands it
+    /// propagated. The AOF is held in between so writes are propagated in the order they took
+    /// effect. Several commands are propagated as a MULTI/EXEC transaction, so they're
+    /// replayed all or none. The keys f created without a TTL are given their default one.
+    pub(crate) fn run_logged<T>(&self, f: impl FnOnce() -> T) -> T {
+        // nested in another logged write, which appends what this one propagates
+        if !self.is_propagating() || PROPAGATED.with(|p| !p.borrow().is_empty()) {
+            return self.with_default_ttl(f);
+        }
+        let mut writer = self.aof.writer.lock();
+        AHEAD.with(|a| a.borrow_mut().clear());
+        let (result, commands) = self.capture_propagated(|| self.with_default_ttl(f));
+        let ahead = AHEAD.with(|a| std::mem::take(&mut *a.borrow_mut()));
+        // taken after f, as what f changed may have been deleted by a read meanwhile
+        let pending = std::mem::take(&mut *self.aof.pending.lock());
+        let data = encode_propagated(pending.into_iter().chain(ahead).chain(commands).collect());
+        self.feed_replicas(&data);
+        let appended = match &mut *writer {
+            Some(writer) => writer.append(data),
+            None => 0,
+        };
+        drop(writer);
+        if appended > 0 {
+            self.aof.appended.fetch_add(1, Ordering::AcqRel);
+            self.aof.size.fetch_add(appended as u64, Ordering::Relaxed);
+            if self.needs_rewrite() {
+                info!("starting an automatic rewrite of the AOF");
+                let _ = self.bgrewriteaof();
+            }
+        }
+        result
+    }
+
+    /// Run f, returning the commands it propagated instead of propagating them.
+    pub(crate) fn capture_propagated<T>(&self, f: impl FnOnce() -> T) -> (T, Vec<RespArray>) {
+        // dropped even if f panics, not to leave the thread capturing
+        struct Capture;
+        impl Drop for Capture {
+            fn drop(&mut self) {
+                PROPAGATED.with(|p| p.borrow_mut().pop());
+            }
+        }
+
+        PROPAGATED.with(|p| p.borrow_mut().push(Vec::new()));
+        let capture = Capture;
+        let result = f();
+        let commands = PROPAGATED.with(|p| p.borrow_mut().last_mut().map(std::mem::take));
+        drop(capture);
+        (result, commands.unwrap_or_default())
+    }
+
+    /// Run f, propagating the commands standing for it, from its result, ahead of those it
+    /// propagated itself, such as the pops of the clients its pushes served.
+    pub(crate) fn propagate_effects<T>(
+        &self,
+        f: impl FnOnce() -> T,
+        commands: impl FnOnce(&T) -> Vec<RespArray>,
+    ) -> T {
+        let (result, propagated) = self.capture_propagated(f);
+        for command in commands(&result).into_iter().chain(propagated) {
+            self.propagate(command);
+        }
+        result
+    }
+
+    /// Propagate command as an effect of the logged write running on this thread. Outside of
+    /// one, such as for a key a read expired, it goes ahead of the next write: the caller holds
+    /// the lock of what it changed meanwhile, so no write to it can be propagated first.
+    pub(crate) fn propagate(&self, command: RespArray) {
+        let uncaptured = PROPAGATED.with(|p| match p.borrow_mut().last_mut() {
+            Some(commands) => {
+                commands.push(command);
+                None
+            }
+            None => Some(command),
+        });
+        if let Some(command) = uncaptured {
+            if self.is_propagating() {
+                self.aof.pending.lock().push(command);
+            }
+        }
+    }
+
+    /// Propagate command ahead of everything the logged write running on this thread
+    /// propagates, such as the deletion of a key it found expired.
+    pub(crate) fn propagate_ahead(&self, command: RespArray) {
+        if PROPAGATED.with(|p| p.borrow().is_empty()) {
+            return self.propagate(command);
+        }
+        AHEAD.with(|a| a.borrow_mut().push(command));
+    }
+
+    /// Propagate what was propagated outside of a logged write, if anything was.
+    pub(crate) fn flush_propagated(&self) {
+        if !self.aof.pending.lock().is_empty() {
+            self.run_logged(|| ());
+        }
+    }
+
+    /// Run f with no write running, the writes propagated before it all sent.
+    pub(crate) fn run_unlogged<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _writer = self.aof.writer.lock();
+        f()
+    }
+
+    /// With appendfsync always, wait until what was appended so far is fsynced, so a write
+    /// is replied once durable. Returns right away otherwise.
+    pub async fn sync_aof(&self) {
+        let appended = self.aof.appended.load(Ordering::Acquire);
+        if !self.is_aof_enabled() || self.aof.synced.load(Ordering::Acquire) >= appended {
+            return;
+        }
+        let synced = match &*self.aof.writer.lock() {
+            Some(writer) if writer.fsync == AppendFsync::Always => {
+                let (tx, rx) = oneshot::channel();
+                let _ = writer.tx.send(AofRequest::Sync(tx));
+                rx
+            }
+            _ => return,
+        };
+        let _ = synced.await;
+    }
+
+    /// Whether the AOF is on and the writes appended to it so far are all fsynced, with
+    /// appendfsync no once written.
+    pub fn is_aof_fsynced(&self) -> bool {
+        self.is_aof_enabled()
+            && self.is_aof_fsynced_up_to(self.aof.appended.load(Ordering::Acquire))
+    }
+
+    fn is_aof_fsynced_up_to(&self, appended: u64) -> bool {
+        self.aof.synced.load(Ordering::Acquire) >= appended
+    }
+
+    /// What WAITAOF waits for: the writes so far, fsynced to the AOF if numlocal isn't 0.
+    pub fn aof_target(&self, numlocal: usize) -> Result<AofTarget, BackendError> {
+        if self.is_replica() {
+            return Err(BackendError::WaitAofReplica);
+        }
+        if numlocal > 0 && !self.is_aof_enabled() {
+            return Err(BackendError::WaitAofAppendOnly);
+        }
+        Ok(AofTarget {
+            appended: self.aof.appended.load(Ordering::Acquire),
+            offset: self.master_repl_offset(),
+        })
+    }
+
+    /// Whether the local AOF is fsynced up to target, and how many replicas fsynced theirs.
+    pub fn aof_acks(&self, target: AofTarget) -> (bool, usize) {
+        let local = self.is_aof_enabled() && self.is_aof_fsynced_up_to(target.appended);
+        (local, self.replicas_aof_acked(target.offset))
+    }
+}
+
+/// Check an append only file without replaying it, finding where a replay would stop and
+/// what a crash cut short at its end. An RDB preamble is decoded whole, nothing replays if
+/// it doesn't.
+pub fn check_aof(data: &[u8]) -> AofCheck {
+    let mut start = 0;
+    let mut preamble_keys = None;
+    if is_dump(data) {
+        let decoded = decode_dump_prefix(
+            data,
+            || QuickList::new(DEFAULT_LIST_MAX_LISTPACK_SIZE),
+            || SetMembers::new(DEFAULT_SET_MAX_INTSET_ENTRIES),
+            HashTable::new,
+        );
+        match decoded {
+            Ok((entries, len)) => {
+                preamble_keys = Some(entries.len());
+                start = len;
+            }
+            Err(e) => {
+                return AofCheck {
+                    error: Some(format!("bad RDB preamble: {}", e)),
+                    ..AofCheck::default()
+                }
+            }
+        }
+    }
+    let mut commands = BTreeMap::new();
+    let (valid_len, bad) = read_aof(data, start, Command::try_from, |name, _| {
+        *commands.entry(name.to_string()).or_default() += 1;
+    });
+    let error = bad.or_else(|| {
+        (valid_len < data.len())
+            .then(|| format!("incomplete command or transaction at offset {}", valid_len))
+    });
+    AofCheck {
+        preamble_keys,
+        commands,
+        valid_len,
+        error,
+    }
+}
+
+// parse the commands of an AOF from start on with parse, handing those replayed to f with
+// their lowercased name, the commands of a transaction once its EXEC is read. Returns the
+// length of the valid part, up to a bad command, reported, or what a crash cut short at the end
+fn read_aof(
+    data: &[u8],
+    start: usize,
+    parse: impl Fn(RespArray) -> Result<Command, CommandError>,
+    mut f: impl FnMut(&str, Command),
+) -> (usize, Option<String>) {
+    let mut buf = BytesMut::from(&data[start..]);
+    // the offset of an open MULTI, and the commands queued since
+    let mut transaction: Option<(usize, Vec<(String, Command)>)> = None;
+    let mut bad = None;
+    while !buf.is_empty() {
+        let offset = data.len() - buf.len();
+        let frame = match RespArray::decode(&mut buf) {
+            Ok(frame) => frame,
+            Err(RespError::NotComplete) => break,
+            Err(e) => {
+                bad = Some((offset, format!("bad command at offset {}: {}", offset, e)));
+                break;
+            }
+        };
+        let name = match frame.first() {
+            Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name).to_lowercase(),
+            _ => String::new(),
+        };
+        let cmd = match parse(frame) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                bad = Some((offset, format!("bad command at offset {}: {}", offset, e)));
+                break;
+            }
+        };
+        match (cmd, &mut transaction) {
+            (Command::Multi(_), _) => transaction = Some((offset, Vec::new())),
+            (Command::Exec(_), transaction) => {
+                for (name, cmd) in transaction
+                    .take()
+                    .map(|(_, queued)| queued)
+                    .unwrap_or_default()
+                {
+                    f(&name, cmd);
+                }
+            }
+            (cmd, Some((_, queued))) => queued.push((name, cmd)),
+            (cmd, None) => f(&name, cmd),
+        }
+    }
+
+    let valid = match (&transaction, &bad) {
+        (Some((offset, _)), _) => *offset,
+        (None, Some((offset, _))) => *offset,
+        (None, None) => data.len() - buf.len(),
+    };
+    (valid, bad.map(|(_, e)| e))
+}
+
+impl AofWriter {
+    // returns how many bytes were appended
+    fn append(&mut self, data: Vec<u8>) -> usize {
+        if data.is_empty() {
+            return 0;
+        }
+        if let Some(buffer) = &mut self.rewrite_buffer {
+            buffer.extend(&data);
+        }
+        let len = data.len();
+        if self.tx.send(AofRequest::Append(data)).is_err() {
+            warn!("the AOF is no longer written");
+            return 0;
+        }
+        len
+    }
+}
+
+// the commands propagated by a write, more than one wrapped in a transaction
+fn encode_propagated(mut commands: Vec<RespArray>) -> Vec<u8> {
+    match commands.len() {
+        0 => Vec::new(),
+        1 => commands.remove(0).encode(),
+        _ => {
+            let mut data = command(["MULTI"]).encode();
+            for command in commands {
+                data.extend(command.encode());
+            }
+            data.extend(command(["EXEC"]).encode());
+            data
+        }
+    }
+}
+
+/// A command to propagate, from its name and arguments.
+pub(crate) fn command<A: AsRef<[u8]>>(args: impl IntoIterator<Item = A>) -> RespArray {
+    RespArray::new(
+        args.into_iter()
+            .map(|arg| BulkString::new(arg.as_ref().to_vec()).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+}
+
+// how many appends the writing task fsynced, and who waits for it
+struct Synced {
+    count: Arc<AtomicU64>,
+    acks: AcksWaker,
+}
+
+impl Synced {
+    fn store(&self, written: u64) {
+        self.count.store(written, Ordering::Release);
+        self.acks.wake();
+    }
+}
+
+async fn write_aof(
+    mut file: File,
+    mut rx: mpsc::UnboundedReceiver<AofRequest>,
+    fsync: AppendFsync,
+    synced: Synced,
+    write_ok: Arc<AtomicBool>,
+) {
+    let mut every_second = tokio::time::interval(Duration::from_secs(1));
+    let mut unsynced = false;
+    let mut written = synced.count.load(Ordering::Acquire);
+    loop {
+        tokio::select! {
+            request = rx.recv() => {
+                let Some(request) = request else {
+                    break;
+                };
+                // what was queued meanwhile goes in the same write
+                let mut data = Vec::new();
+                let mut waiting = Vec::new();
+                let queued = std::iter::from_fn(|| rx.try_recv().ok());
+                for request in std::iter::once(request).chain(queued) {
+                    match request {
+                        AofRequest::Append(bytes) => {
+                            data.extend(bytes);
+                            written += 1;
+                        }
+                        AofRequest::Sync(tx) => waiting.push(tx),
+                        AofRequest::Reopen(rewritten) => {
+                            // already in the rewritten file, which is fsynced
+                            data.clear();
+                            file = File::from_std(rewritten);
+                            unsynced = false;
+                            synced.store(written);
+                        }
+                    }
+                }
+                if !data.is_empty() {
+                    match append(&mut file, &data).await {
+                        Ok(()) => write_ok.store(true, Ordering::Relaxed),
+                        Err(e) => {
+                            warn!("failed to write the AOF: {}", e);
+                            write_ok.store(false, Ordering::Relaxed);
+                        }
+                    }
+                    unsynced = true;
+                }
+                if fsync == AppendFsync::Always && unsynced {
+                    sync(&mut file, &write_ok).await;
+                    synced.store(written);
+                    unsynced = false;
+                }
+                // left to the OS, the writes count as fsynced once written
+                if fsync == AppendFsync::No && unsynced {
+                    synced.store(written);
+                }
+                for tx in waiting {
+                    let _ = tx.send(());
+                }
+            }
+            _ = every_second.tick(), if fsync == AppendFsync::EverySec && unsynced => {
+                sync(&mut file, &write_ok).await;
+                synced.store(written);
+                unsynced = false;
+            }
+        }
+    }
+    if unsynced {
+        sync(&mut file, &write_ok).await;
+    }
+}
+
+async fn append(file: &mut File, data: &[u8]) -> io::Result<()> {
+    file.write_all(data).await?;
+    file.flush().await
+}
+
+async fn sync(file: &mut File, write_ok: &AtomicBool) {
+    if let Err(e) = file.sync_data().await {
+        warn!("failed to fsync the AOF: {}", e);
+        write_ok.store(false, Ordering::Relaxed);
+    }
+}
+
+// write the commands rebuilding the libraries and the keyspace to path, fsynced. With a
+// preamble, the keyspace is a dump instead
+fn write_rewrite(
+    path: &Path,
+    snapshot: &Snapshot,
+    libraries: &[Library],
+    preamble: bool,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(fs::File::create(path)?);
+    if preamble {
+        w.write_all(&encode_dump(snapshot.iter()))?;
+    }
+    for library in libraries {
+        w.write_all(&command(["FUNCTION", "LOAD", &library.code]).encode())?;
+    }
+    if !preamble {
+        for command in snapshot.iter().flat_map(rewrite_entry) {
+            w.write_all(&command.encode())?;
+        }
+    }
+    w.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+/// The commands rebuilding a key, its value then its TTL.
+pub fn rewrite_entry(entry: &SnapshotEntry) -> Vec<RespArray> {
+    let key = entry.key.as_bytes();
+    let mut commands = Vec::new();
+    // the command adding items, split so none gets too long
+    let mut batched = |name: &str, items: Vec<Vec<u8>>, per_item: usize| {
+        for chunk in items.chunks(REWRITE_ITEMS_PER_COMMAND * per_item) {
+            let args = [name.as_bytes().to_vec(), key.to_vec()];
+            commands.push(command(args.into_iter().chain(chunk.iter().cloned())));
+        }
+    };
+    match &*entry.value {
+        Value::String(value) => {
+            batched("SET", vec![string_bytes(value)], 1);
+        }
+        Value::List(list) => batched("RPUSH", list.iter().map(<[u8]>::to_vec).collect(), 1),
+        Value::Set(set) => batched(
+            "SADD",
+            set.iter().map(|m| m.as_bytes().to_vec()).collect(),
+            1,
+        ),
+        Value::ZSet(zset) => {
+            let items = zset
+                .iter()
+                .flat_map(|(member, score)| {
+                    [score.to_string().into_bytes(), member.as_bytes().to_vec()]
+                })
+                .collect();
+            batched("ZADD", items, 2);
+        }
+        Value::Hash(hash) => {
+            let items = hash
+                .iter()
+                .flat_map(|(field, value)| [field.as_bytes().to_vec(), string_bytes(value)])
+                .collect();
+            batched("HSET", items, 2);
+            commands.extend(rewrite_field_ttls(&entry.key, hash));
+        }
+        Value::Stream(stream) => commands.extend(rewrite_stream(&entry.key, stream)),
+    }
+    if let Some(at) = entry.expire_at {
+        commands.push(command([b"PEXPIREAT", key, at.to_string().as_bytes()]));
+    }
+    commands
+}
+
+// the TTLs of the fields of a hash, absolute so they don't move while the AOF isn't loaded
+fn rewrite_field_ttls(key: &str, hash: &HashTable) -> Vec<RespArray> {
+    hash.expires()
+        .map(|(field, at)| command(["HPEXPIREAT", key, &at.to_string(), "FIELDS", "1", field]))
+        .collect()
+}
+
+// the entries of the stream, its groups with their pending entries and consumers, then its
+// last ID
+fn rewrite_stream(key: &str, stream: &Stream) -> Vec<RespArray> {
+    let mut commands = Vec::new();
+    let mut top = None;
+    for (id, fields) in stream.iter() {
+        let mut args = vec![b"XADD".to_vec(), key.into(), id.to_string().into_bytes()];
+        for (field, value) in fields {
+            args.push(field.clone().into_bytes());
+            args.push(string_bytes(value));
+        }
+        commands.push(command(args));
+        top = Some(*id);
+    }
+
+    let mut groups = stream.groups().peekable();
+    if top.is_none() && groups.peek().is_none() {
+        // an empty stream is created with a group
+        commands.push(command(["XGROUP", "CREATE", key, "-", "0", "MKSTREAM"]));
+        commands.push(command(["XGROUP", "DESTROY", key, "-"]));
+    }
+    for (name, group) in groups {
+        let last_delivered = group.last_delivered().to_string();
+        commands.push(command([
+            "XGROUP",
+            "CREATE",
+            key,
+            name,
+            &last_delivered,
+            "MKSTREAM",
+        ]));
+        for (id, entry) in group.pending() {
+            commands.push(command([
+                "XCLAIM",
+                key,
+                name,
+                &entry.consumer,
+                "0",
+                &id.to_string(),
+                "TIME",
+                &entry.delivered_at.to_string(),
+                "RETRYCOUNT",
+                &entry.deliveries.to_string(),
+                "FORCE",
+                "JUSTID",
+            ]));
+        }
+        // the consumers without pending entries
+        for (consumer, _) in group.consumers() {
+            if group
+                .pending()
+                .values()
+                .all(|entry| entry.consumer != *consumer)
+            {
+                commands.push(command(["XGROUP", "CREATECONSUMER", key, name, consumer]));
+            }
+        }
+    }
+
+    // the IDs keep growing from the last one, even once its entry is deleted
+    let last_id = stream.last_id();
+    if last_id != StreamId::default() && top != Some(last_id) {
+        commands.push(command(["XSETID", key, &last_id.to_string()]));
+    }
+    commands
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Transaction;
+    use crate::RespFrame;
+    use std::env;
+    use std::path::PathBuf;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("simple-redis-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_aof_round_trip() {
+        let dir = temp_dir("aof-round-trip");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        let _ = fs::remove_file(backend.aof_path());
+        backend
+            .start_aof(AppendFsync::Always, &Handle::current())
+            .unwrap();
+
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let script = "redis.call('SET', KEYS[1], 'x') return redis.call('SPOP', KEYS[2])";
+        let requests: [&[&str]; 12] = [
+            &["RPUSH", "l", "a", "b", "c"],
+            &["SADD", "s", "x", "y", "z"],
+            &["SPOP", "s"],
+            &["XADD", "x", "*", "f", "v"],
+            &["EXPIRE", "l", "100"],
+            &["GET", "l"],
+            &["MULTI"],
+            &["SET", "k", "v"],
+            &["INCR", "n"],
+            &["EXEC"],
+            &["EVAL", script, "2", "k2", "s"],
+            &["SETEX", "e", "100", "v"],
+        ];
+        for args in requests {
+            let request = command(args.iter().copied());
+            Command::try_from(request.clone())
+                .unwrap()
+                .execute_on_connection(
+                    &backend,
+                    Some(request),
+                    &mut subscriptions,
+                    &mut transaction,
+                )
+                .await;
+        }
+
+        let restored = Backend::new();
+        restored.set_dir(&dir);
+        assert_eq!(restored.load_aof().unwrap(), 11);
+        assert_eq!(restored.digest(), backend.digest());
+        assert_eq!(restored.expire_time("l"), backend.expire_time("l"));
+        assert_eq!(restored.expire_time("e"), backend.expire_time("e"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_propagation_capture() {
+        let backend = Backend::new();
+        backend.propagate(command(["SET", "k", "v"]));
+
+        let ((), outer) = backend.capture_propagated(|| {
+            backend.propagate(command(["SET", "a", "1"]));
+            let ((), inner) =
+                backend.capture_propagated(|| backend.propagate(command(["SET", "b", "2"])));
+            assert_eq!(inner, vec![command(["SET", "b", "2"])]);
+        });
+        assert_eq!(outer, vec![command(["SET", "a", "1"])]);
+    }
+
+    fn run(backend: &Backend, args: &[&str]) -> RespFrame {
+        Command::try_from(command(args.iter().copied()))
+            .unwrap()
+            .execute(backend)
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let dir = temp_dir("aof-rewrite");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        let requests: [&[&str]; 16] = [
+            &["SET", "s", "v"],
+            &["RPUSH", "l", "a", "b", "c"],
+            &["PEXPIRE", "l", "100000"],
+            &["SADD", "set", "a", "b"],
+            &["ZADD", "z", "1.5", "a", "-inf", "b"],
+            &["HSET", "h", "f", "v", "g", "w"],
+            &["XADD", "x", "1-0", "f", "v"],
+            &["XADD", "x", "2-0", "f", "w"],
+            &["XADD", "x", "3-0", "f", "x"],
+            &["XDEL", "x", "3-0"],
+            &["XGROUP", "CREATE", "x", "g", "0"],
+            &[
+                "XREADGROUP",
+                "GROUP",
+                "g",
+                "c",
+                "COUNT",
+                "1",
+                "STREAMS",
+                "x",
+                ">",
+            ],
+            &["XGROUP", "CREATE", "x", "g2", "$"],
+            &["XREADGROUP", "GROUP", "g2", "d", "STREAMS", "x", ">"],
+            &["XGROUP", "CREATE", "e", "g", "0", "MKSTREAM"],
+            &["XGROUP", "CREATE", "empty", "g", "0", "MKSTREAM"],
+        ];
+        for args in requests {
+            run(&backend, args);
+        }
+        run(&backend, &["XGROUP", "DESTROY", "empty", "g"]);
+        // many elements are added a batch at a time
+        for i in 0..100 {
+            run(&backend, &["RPUSH", "long", &i.to_string()]);
+        }
+
+        for preamble in [false, true] {
+            write_rewrite(&backend.aof_path(), &backend.snapshot(), &[], preamble).unwrap();
+            let data = fs::read(backend.aof_path()).unwrap();
+            assert_eq!(is_dump(&data), preamble);
+            let restored = Backend::new();
+            restored.set_dir(&dir);
+            restored.load_aof().unwrap();
+            assert_eq!(restored.digest(), backend.digest());
+            assert_eq!(restored.expire_time("l"), backend.expire_time("l"));
+            assert_eq!(restored.xlast_id("x"), Ok(StreamId::new(3, 0)));
+            assert_eq!(restored.xpending("x", "g"), backend.xpending("x", "g"));
+            assert_eq!(
+                run(&restored, &["XPENDING", "x", "g", "-", "+", "10"]),
+                run(&backend, &["XPENDING", "x", "g", "-", "+", "10"])
+            );
+            assert_eq!(restored.xlen("empty"), Ok(0));
+        }
+
+        // the TTLs of hash fields are kept as they are, however late the AOF loads
+        run(&backend, &["HPEXPIRE", "h", "100000", "FIELDS", "1", "g"]);
+        let fields = ["f".to_string(), "g".to_string()];
+        let expire_times = backend.hexpire_time("h", &fields).unwrap();
+        assert!(matches!(expire_times[..], [Some(None), Some(Some(_))]));
+        for preamble in [false, true] {
+            write_rewrite(&backend.aof_path(), &backend.snapshot(), &[], preamble).unwrap();
+            let restored = Backend::new();
+            restored.set_dir(&dir);
+            std::thread::sleep(Duration::from_millis(5));
+            restored.load_aof().unwrap();
+            assert_eq!(
+                restored.hexpire_time("h", &fields),
+                Ok(expire_times.clone())
+            );
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // run a command for a connection of its own
+    async fn execute(backend: &Backend, args: &[&str]) -> Vec<RespFrame> {
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let request = command(args.iter().copied());
+        Command::try_from(request.clone())
+            .unwrap()
+            .execute_on_connection(
+                backend,
+                Some(request),
+                &mut subscriptions,
+                &mut Transaction::default(),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_bgrewriteaof() {
+        let dir = temp_dir("bgrewriteaof");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        let _ = fs::remove_file(backend.aof_path());
+        backend
+            .start_aof(AppendFsync::Always, &Handle::current())
+            .unwrap();
+        for _ in 0..100 {
+            execute(&backend, &["INCR", "n"]).await;
+        }
+        let size = fs::metadata(backend.aof_path()).unwrap().len();
+        assert_eq!(backend.bgrewriteaof(), Ok(()));
+        execute(&backend, &["SET", "k", "v"]).await;
+        while backend.is_aof_rewriting() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        execute(&backend, &["INCR", "n"]).await;
+        assert!(fs::metadata(backend.aof_path()).unwrap().len() < size);
+
+        let restored = Backend::new();
+        restored.set_dir(&dir);
+        restored.load_aof().unwrap();
+        assert_eq!(restored.digest(), backend.digest());
+        assert_eq!(restored.get("n"), Ok(Some(bulk("101"))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_waitaof() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.aof_target(1).unwrap_err(),
+            BackendError::WaitAofAppendOnly
+        );
+        let target = backend.aof_target(0).unwrap();
+        assert_eq!(backend.aof_acks(target), (false, 0));
+
+        let dir = temp_dir("waitaof");
+        backend.set_dir(&dir);
+        let _ = fs::remove_file(backend.aof_path());
+        backend
+            .start_aof(AppendFsync::EverySec, &Handle::current())
+            .unwrap();
+        execute(&backend, &["SET", "k", "v"]).await;
+        let target = backend.aof_target(1).unwrap();
+        // fsynced within a second
+        let acked = backend.wait_acks(|| backend.aof_acks(target).0);
+        assert!(tokio::time::timeout(Duration::from_secs(3), acked)
+            .await
+            .is_ok());
+        assert_eq!(backend.aof_acks(target), (true, 0));
+        assert!(backend.is_aof_fsynced());
+
+        // no replica ever acknowledges
+        let acked = backend.wait_acks(|| backend.aof_acks(target).1 >= 1);
+        assert!(tokio::time::timeout(Duration::from_millis(50), acked)
+            .await
+            .is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_aof() {
+        let dir = temp_dir("aof");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+
+        let mut data = command(["SET", "k", "v"]).encode();
+        data.extend(command(["RPUSH", "l", "a", "b"]).encode());
+        let complete = data.len();
+        // a transaction cut short by a crash
+        data.extend(command(["MULTI"]).encode());
+        data.extend(command(["SET", "k", "lost"]).encode());
+        data.extend(b"*2\r\n$3\r\nDEL");
+        fs::write(backend.aof_path(), &data).unwrap();
+
+        assert_eq!(backend.load_aof().unwrap(), 2);
+        assert_eq!(backend.get("k"), Ok(Some(bulk("v"))));
+        assert_eq!(backend.llen("l"), Ok(2));
+        assert_eq!(fs::read(backend.aof_path()).unwrap().len(), complete);
+
+        fs::write(backend.aof_path(), b"*1\r\n$7\r\nUNKNOWN\r\n").unwrap();
+        assert!(backend.load_aof().is_err());
+        assert!(!backend.is_loading());
+
+        // the keys replayed have the TTLs the AOF has, not the default one
+        let cache = Backend::new();
+        cache.set_dir(&dir);
+        cache.set_default_ttl(100);
+        let mut data = command(["RPUSH", "l", "a"]).encode();
+        data.extend(command(["SET", "k", "v"]).encode());
+        data.extend(command(["PEXPIREAT", "k", "99999999999999"]).encode());
+        fs::write(cache.aof_path(), &data).unwrap();
+        assert_eq!(cache.load_aof().unwrap(), 3);
+        assert_eq!(cache.expire_time("l"), Some(None));
+        assert_eq!(cache.expire_time("k"), Some(Some(99999999999999)));
+        // nor those a replayed command writes logging itself, as a blocking move does
+        cache.aof.loading.store(true, Ordering::Release);
+        cache.run_logged(|| cache.set("m".to_string(), bulk("v")));
+        cache.aof.loading.store(false, Ordering::Release);
+        assert_eq!(cache.expire_time("m"), Some(None));
+
+        // the commands following an RDB preamble replay after its keys are loaded
+        let restored = Backend::new();
+        restored.set_dir(&dir);
+        let mut data = encode_dump(backend.snapshot().iter());
+        data.extend(command(["RPUSH", "l", "c"]).encode());
+        let complete = data.len();
+        data.extend(b"*2\r\n$3\r\nDEL");
+        fs::write(backend.aof_path(), &data).unwrap();
+        assert_eq!(restored.load_aof().unwrap(), 1);
+        assert_eq!(restored.get("k"), Ok(Some(bulk("v"))));
+        assert_eq!(restored.llen("l"), Ok(3));
+        assert_eq!(fs::read(backend.aof_path()).unwrap().len(), complete);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_aof() {
+        let mut data = command(["SET", "k", "v"]).encode();
+        data.extend(command(["MULTI"]).encode());
+        data.extend(command(["SET", "k", "w"]).encode());
+        data.extend(command(["EXEC"]).encode());
+        data.extend(command(["RPUSH", "l", "a"]).encode());
+        let complete = data.len();
+        let check = check_aof(&data);
+        assert_eq!(check.error, None);
+        assert_eq!(check.valid_len, complete);
+        let commands = BTreeMap::from([("rpush".to_string(), 1), ("set".to_string(), 2)]);
+        assert_eq!(check.commands, commands);
+
+        // a transaction cut short is dropped whole
+        data.extend(command(["MULTI"]).encode());
+        data.extend(command(["DEL", "k"]).encode());
+        let check = check_aof(&data);
+        assert_eq!(check.valid_len, complete);
+        assert!(check.error.unwrap().starts_with("incomplete"));
+
+        // nothing after a bad command replays
+        let mut data = command(["SET", "k", "v"]).encode();
+        let valid = data.len();
+        data.extend(command(["UNKNOWN"]).encode());
+        data.extend(command(["SET", "k", "w"]).encode());
+        let check = check_aof(&data);
+        assert_eq!(check.valid_len, valid);
+        assert_eq!(check.commands.get("set"), Some(&1));
+        assert!(check.error.unwrap().starts_with("bad command at offset"));
+
+        let backend = Backend::new();
+        backend.set("k".to_string(), bulk("v"));
+        let mut data = encode_dump(backend.snapshot().iter());
+        let preamble = data.len();
+        data.extend(command(["SET", "k", "w"]).encode());
+        data.extend(command(["UNKNOWN"]).encode());
+        let check = check_aof(&data);
+        assert_eq!(check.preamble_keys, Some(1));
+        assert_eq!(check.commands.get("set"), Some(&1));
+        let offset = preamble + command(["SET", "k", "w"]).encode().len();
+        assert!(check
+            .error
+            .unwrap()
+            .starts_with(&format!("bad command at offset {}:", offset)));
+        // nothing replays without the preamble
+        let check = check_aof(&data[..preamble - 1]);
+        assert_eq!(check.valid_len, 0);
+        assert!(check.error.unwrap().starts_with("bad RDB preamble"));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::backend::string::string_bytes;
+use crate::{Backend, BackendError, BulkString, NotifyFlags, RespFrame, Value};
+
+/// An integer field of a BITFIELD: signed of 1 to 64 bits or unsigned of 1 to 63 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitFieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+/// What BITFIELD SET and INCRBY do with a result that doesn't fit in the field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitFieldOverflow {
+    /// Keep the low bits, like two's complement arithmetic.
+    #[default]
+    Wrap,
+    /// Clamp to the minimum or maximum value of the field.
+    Sat,
+    /// Leave the field alone and return `None`.
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitFieldOp {
+    Get {
+        field: BitFieldType,
+        offset: u64,
+    },
+    Set {
+        field: BitFieldType,
+        offset: u64,
+        value: i64,
+        overflow: BitFieldOverflow,
+    },
+    IncrBy {
+        field: BitFieldType,
+        offset: u64,
+        increment: i64,
+        overflow: BitFieldOverflow,
+    },
+}
+
+impl BitFieldType {
+    fn min(&self) -> i128 {
+        if self.signed {
+            -(1i128 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(&self) -> i128 {
+        if self.signed {
+            (1i128 << (self.bits - 1)) - 1
+        } else {
+            (1i128 << self.bits) - 1
+        }
+    }
+
+    // the value of the raw bits, sign extended for signed fields
+    fn decode(&self, raw: u64) -> i64 {
+        if self.signed && self.bits < 64 && raw & (1 << (self.bits - 1)) != 0 {
+            (raw | (u64::MAX << self.bits)) as i64
+        } else {
+            raw as i64
+        }
+    }
+
+    // fit value in the field according to overflow, None if it doesn't fit and overflow fails
+    fn fit(&self, value: i128, overflow: BitFieldOverflow) -> Option<i64> {
+        let (min, max) = (self.min(), self.max());
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            BitFieldOverflow::Wrap => {
+                Some(((value - min).rem_euclid(1i128 << self.bits) + min) as i64)
+            }
+            BitFieldOverflow::Sat => Some(value.clamp(min, max) as i64),
+            BitFieldOverflow::Fail => None,
+        }
+    }
+}
+
+impl Backend {
+    /// Run the BITFIELD operations on the string at key, in order. Returns one result per
+    /// operation: the value read for GET, the old value for SET, the new value for INCRBY, and
+    /// `None` for a write that failed because of `BitFieldOverflow::Fail`.
+    pub fn bitfield(
+        &self,
+        key: &str,
+        ops: &[BitFieldOp],
+    ) -> Result<Vec<Option<i64>>, BackendError> {
+        let read_only = ops.iter().all(|op| matches!(op, BitFieldOp::Get { .. }));
+        if read_only {
+            let shard = self.read(key);
+            let bytes = match shard.get(key).map(|obj| &**obj) {
+                None => Vec::new(),
+                Some(Value::String(v)) => string_bytes(v),
+                Some(_) => return Err(BackendError::WrongType),
+            };
+            return Ok(ops
+                .iter()
+                .map(|op| match op {
+                    BitFieldOp::Get { field, offset } => {
+                        Some(field.decode(get_field(&bytes, *offset, field.bits)))
+                    }
+                    _ => None,
+                })
+                .collect());
+        }
+
+        let mut shard = self.write(key);
+        let mut bytes = match shard.get(key).map(|obj| &**obj) {
+            None => None,
+            Some(Value::String(v)) => Some(string_bytes(v)),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        let created = bytes.is_none();
+        let buf = bytes.get_or_insert_with(Vec::new);
+
+        let mut changed = false;
+        let results = ops
+            .iter()
+            .map(|op| match *op {
+                BitFieldOp::Get { field, offset } => {
+                    Some(field.decode(get_field(buf, offset, field.bits)))
+                }
+                BitFieldOp::Set {
+                    field,
+                    offset,
+                    value,
+                    overflow,
+                } => {
+                    let old = field.decode(get_field(buf, offset, field.bits));
+                    let new = field.fit(value as i128, overflow)?;
+                    set_field(buf, offset, field.bits, new as u64);
+                    changed = true;
+                    Some(old)
+                }
+                BitFieldOp::IncrBy {
+                    field,
+                    offset,
+                    increment,
+                    overflow,
+                } => {
+                    let old = field.decode(get_field(buf, offset, field.bits));
+                    let new = field.fit(old as i128 + increment as i128, overflow)?;
+                    set_field(buf, offset, field.bits, new as u64);
+                    changed = true;
+                    Some(new)
+                }
+            })
+            .collect();
+
+        // like redis, any write operation creates the key even if it ends up failing
+        let value = Value::String(RespFrame::BulkString(BulkString::new(buf.clone())));
+        match shard.get_mut(key) {
+            Some(obj) => {
+                obj.touch();
+                **obj = value;
+            }
+            None => {
+                shard.insert(key.to_string(), value);
+            }
+        }
+        if created {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+        }
+        if changed {
+            self.notify_keyspace_event(NotifyFlags::STRING, "setbit", key);
+        }
+        Ok(results)
+    }
+}
+
+// the bits unsigned bits at offset, most significant first, zero past the end of bytes
+fn get_field(bytes: &[u8], offset: u64, bits: u32) -> u64 {
+    let mut value = 0u64;
+    for pos in offset..offset + bits as u64 {
+        let bit = bytes
+            .get((pos / 8) as usize)
+            .map_or(0, |b| (b >> (7 - pos % 8)) & 1);
+        value = (value << 1) | bit as u64;
+    }
+    value
+}
+
+// write the low bits bits of value at offset, growing bytes with zeros as needed
+fn set_field(bytes: &mut Vec<u8>, offset: u64, bits: u32, value: u64) {
+    let last_byte = ((offset + bits as u64 - 1) / 8) as usize;
+    if bytes.len() <= last_byte {
+        bytes.resize(last_byte + 1, 0);
+    }
+    for i in 0..bits as u64 {
+        let pos = offset + i;
+        let bit = (value >> (bits as u64 - 1 - i)) & 1;
+        let mask = 1u8 << (7 - pos % 8);
+        if bit == 1 {
+            bytes[(pos / 8) as usize] |= mask;
+        } else {
+            bytes[(pos / 8) as usize] &= !mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const U8: BitFieldType = BitFieldType {
+        signed: false,
+        bits: 8,
+    };
+    const I5: BitFieldType = BitFieldType {
+        signed: true,
+        bits: 5,
+    };
+
+    #[test]
+    fn test_bitfield() {
+        let backend = Backend::new();
+        let ops = [
+            BitFieldOp::IncrBy {
+                field: I5,
+                offset: 100,
+                increment: 1,
+                overflow: BitFieldOverflow::Wrap,
+            },
+            BitFieldOp::Get {
+                field: U8,
+                offset: 0,
+            },
+        ];
+        assert_eq!(backend.bitfield("k1", &ops), Ok(vec![Some(1), Some(0)]));
+
+        let ops = [
+            BitFieldOp::Set {
+                field: U8,
+                offset: 0,
+                value: 255,
+                overflow: BitFieldOverflow::Wrap,
+            },
+            BitFieldOp::Get {
+                field: I5,
+                offset: 0,
+            },
+        ];
+        assert_eq!(backend.bitfield("k1", &ops), Ok(vec![Some(0), Some(-1)]));
+        assert_eq!(backend.getbit("k1", 7), Ok(1));
+    }
+
+    #[test]
+    fn test_bitfield_overflow() {
+        let backend = Backend::new();
+        let incr = |overflow| {
+            [BitFieldOp::IncrBy {
+                field: U8,
+                offset: 0,
+                increment: 200,
+                overflow,
+            }]
+        };
+        assert_eq!(
+            backend.bitfield("k1", &incr(BitFieldOverflow::Wrap)),
+            Ok(vec![Some(200)])
+        );
+        assert_eq!(
+            backend.bitfield("k1", &incr(BitFieldOverflow::Wrap)),
+            Ok(vec![Some(144)])
+        );
+        assert_eq!(
+            backend.bitfield("k1", &incr(BitFieldOverflow::Sat)),
+            Ok(vec![Some(255)])
+        );
+        assert_eq!(
+            backend.bitfield("k1", &incr(BitFieldOverflow::Fail)),
+            Ok(vec![None])
+        );
+
+        let i64_field = BitFieldType {
+            signed: true,
+            bits: 64,
+        };
+        let ops = [
+            BitFieldOp::Set {
+                field: i64_field,
+                offset: 8,
+                value: i64::MAX,
+                overflow: BitFieldOverflow::Wrap,
+            },
+            BitFieldOp::IncrBy {
+                field: i64_field,
+                offset: 8,
+                increment: 1,
+                overflow: BitFieldOverflow::Wrap,
+            },
+            BitFieldOp::IncrBy {
+                field: I5,
+                offset: 100,
+                increment: -100,
+                overflow: BitFieldOverflow::Sat,
+            },
+        ];
+        assert_eq!(
+            backend.bitfield("k1", &ops),
+            Ok(vec![Some(0), Some(i64::MIN), Some(-16)])
+        );
+
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(
+            backend.bitfield("h", &incr(BitFieldOverflow::Wrap)),
+            Err(BackendError::WrongType)
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use crate::backend::string::string_bytes;
+use crate::{Backend, BackendError, BulkString, NotifyFlags, RespFrame, Value};
+
+/// The largest bit offset of SETBIT and friends: strings are capped at 512MB like
+/// `proto-max-bulk-len`, so a single SETBIT can't allocate more than that.
+pub const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8 - 1;
+
+/// The unit of the ranges of BITCOUNT and BITPOS.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+impl Backend {
+    /// Set or clear the bit at offset of the string at key, growing it with zero bytes as
+    /// needed. Returns the previous bit. Bit 0 is the most significant bit of the first byte.
+    pub fn setbit(&self, key: &str, offset: u64, bit: bool) -> Result<u8, BackendError> {
+        let byte = (offset >> 3) as usize;
+        let mask = 1u8 << (7 - (offset & 7));
+
+        let mut shard = self.write(key);
+        let old = match shard.get_mut(key) {
+            Some(obj) => {
+                obj.touch();
+                // bitmaps are plain bytes, convert integers and the like in place
+                if let Value::String(v) = &mut **obj {
+                    if !matches!(v, RespFrame::BulkString(_)) {
+                        *v = RespFrame::BulkString(BulkString::new(string_bytes(v)));
+                    }
+                }
+                match &mut **obj {
+                    Value::String(RespFrame::BulkString(s)) => {
+                        // without a copy unless a reply still shares the bytes
+                        let mut bytes = Vec::from(std::mem::take(&mut s.0));
+                        let old = set_bit(&mut bytes, byte, mask, bit);
+                        s.0 = bytes.into();
+                        old
+                    }
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+            None => {
+                let mut bytes = Vec::new();
+                set_bit(&mut bytes, byte, mask, bit);
+                self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+                shard.insert(
+                    key.to_string(),
+                    Value::String(RespFrame::BulkString(BulkString::new(bytes))),
+                );
+                0
+            }
+        };
+
+        self.notify_keyspace_event(NotifyFlags::STRING, "setbit", key);
+        Ok(old)
+    }
+
+    /// The bit at offset of the string at key, 0 past the end of the string.
+    pub fn getbit(&self, key: &str, offset: u64) -> Result<u8, BackendError> {
+        let shard = self.read(key);
+        let byte = (offset >> 3) as usize;
+        match shard.get(key) {
+            None => Ok(0),
+            Some(obj) => {
+                obj.touch();
+                let bit =
+                    |bytes: &[u8]| bytes.get(byte).map_or(0, |b| (b >> (7 - (offset & 7))) & 1);
+                match &**obj {
+                    Value::String(RespFrame::BulkString(s)) => Ok(bit(s)),
+                    Value::String(v) => Ok(bit(&string_bytes(v))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+}
+
+impl Backend {
+    /// The number of set bits of the string at key, within the inclusive range start..=end
+    /// counted in unit, negative indexes count from the end.
+    pub fn bitcount(
+        &self,
+        key: &str,
+        range: Option<(i64, i64, BitUnit)>,
+    ) -> Result<u64, BackendError> {
+        let bytes = match self.bitmap(key)? {
+            Some(bytes) => bytes,
+            None => return Ok(0),
+        };
+        let (start, end, unit) = range.unwrap_or((0, -1, BitUnit::Byte));
+        let Some((first, last)) = bit_range(bytes.len(), start, end, unit) else {
+            return Ok(0);
+        };
+
+        let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+        let mut count = popcount(&bytes[first_byte..=last_byte]);
+        // don't count the bits of the edge bytes outside of a BIT range
+        let head = bytes[first_byte] & !(0xffu8 >> (first % 8));
+        let tail = bytes[last_byte] & 0xffu8.checked_shr(last as u32 % 8 + 1).unwrap_or(0);
+        count -= head.count_ones() as u64 + tail.count_ones() as u64;
+        Ok(count)
+    }
+
+    /// The position of the first bit set to bit in the string at key, within the inclusive
+    /// range start..=end counted in unit. Returns -1 if there's none, except that when looking
+    /// for a clear bit without an explicit end the string is considered padded with zeros.
+    pub fn bitpos(
+        &self,
+        key: &str,
+        bit: bool,
+        start: Option<i64>,
+        end: Option<i64>,
+        unit: BitUnit,
+    ) -> Result<i64, BackendError> {
+        let bytes = match self.bitmap(key)? {
+            Some(bytes) => bytes,
+            None => return Ok(if bit { -1 } else { 0 }),
+        };
+        let Some((first, last)) =
+            bit_range(bytes.len(), start.unwrap_or(0), end.unwrap_or(-1), unit)
+        else {
+            return Ok(-1);
+        };
+
+        match first_bit(&bytes, bit, first, last) {
+            Some(pos) => Ok(pos as i64),
+            None if !bit && end.is_none() => Ok(bytes.len() as i64 * 8),
+            None => Ok(-1),
+        }
+    }
+
+    fn bitmap(&self, key: &str) -> Result<Option<Vec<u8>>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::String(v) => Ok(Some(string_bytes(v))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+}
+
+// the inclusive range of bit positions a start/end pair selects in a string of len bytes,
+// None if it's empty
+fn bit_range(len: usize, start: i64, end: i64, unit: BitUnit) -> Option<(u64, u64)> {
+    let total = match unit {
+        BitUnit::Byte => len as i64,
+        BitUnit::Bit => len as i64 * 8,
+    };
+    let resolve = |i: i64| if i < 0 { (total + i).max(0) } else { i };
+    let (start, end) = (resolve(start), resolve(end).min(total - 1));
+    if total == 0 || start > end {
+        return None;
+    }
+
+    match unit {
+        BitUnit::Byte => Some((start as u64 * 8, end as u64 * 8 + 7)),
+        BitUnit::Bit => Some((start as u64, end as u64)),
+    }
+}
+
+// count the set bits a word at a time, the tail a byte at a time
+fn popcount(bytes: &[u8]) -> u64 {
+    let mut chunks = bytes.chunks_exact(8);
+    let mut count: u64 = chunks
+        .by_ref()
+        .map(|c| u64::from_ne_bytes(c.try_into().unwrap()).count_ones() as u64)
+        .sum();
+    count += chunks
+        .remainder()
+        .iter()
+        .map(|b| b.count_ones() as u64)
+        .sum::<u64>();
+    count
+}
+
+// the first bit position in first..=last set to bit, skipping whole words and bytes made
+// only of the other bit value
+fn first_bit(bytes: &[u8], bit: bool, first: u64, last: u64) -> Option<u64> {
+    let skip_byte = if bit { 0x00 } else { 0xff };
+    let mut pos = first;
+    while pos <= last {
+        let byte = (pos / 8) as usize;
+        if pos.is_multiple_of(8) {
+            if pos + 63 <= last {
+                let word = u64::from_ne_bytes(bytes[byte..byte + 8].try_into().unwrap());
+                if word == u64::from_ne_bytes([skip_byte; 8]) {
+                    pos += 64;
+                    continue;
+                }
+            }
+            if pos + 7 <= last && bytes[byte] == skip_byte {
+                pos += 8;
+                continue;
+            }
+        }
+        if (bytes[byte] >> (7 - pos % 8)) & 1 == bit as u8 {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
+fn set_bit(bytes: &mut Vec<u8>, byte: usize, mask: u8, bit: bool) -> u8 {
+    if bytes.len() <= byte {
+        bytes.resize(byte + 1, 0);
+    }
+    let old = (bytes[byte] & mask != 0) as u8;
+    if bit {
+        bytes[byte] |= mask;
+    } else {
+        bytes[byte] &= !mask;
+    }
+    old
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitcount() {
+        let backend = Backend::new();
+        backend.set("k1".to_string(), RespFrame::BulkString(b"foobar".into()));
+        assert_eq!(backend.bitcount("k1", None), Ok(26));
+        assert_eq!(backend.bitcount("k1", Some((0, 0, BitUnit::Byte))), Ok(4));
+        assert_eq!(backend.bitcount("k1", Some((1, 1, BitUnit::Byte))), Ok(6));
+        assert_eq!(backend.bitcount("k1", Some((1, -1, BitUnit::Byte))), Ok(22));
+        assert_eq!(backend.bitcount("k1", Some((5, 30, BitUnit::Bit))), Ok(17));
+        assert_eq!(backend.bitcount("k1", Some((3, 1, BitUnit::Byte))), Ok(0));
+        assert_eq!(backend.bitcount("missing", None), Ok(0));
+
+        // large enough to go through the word at a time path
+        let big = vec![0xf0u8; 1001];
+        backend.set(
+            "big".to_string(),
+            RespFrame::BulkString(BulkString::new(big)),
+        );
+        assert_eq!(backend.bitcount("big", None), Ok(4004));
+        assert_eq!(
+            backend.bitcount("big", Some((2, 8001, BitUnit::Bit))),
+            Ok(4000)
+        );
+    }
+
+    #[test]
+    fn test_bitpos() {
+        let backend = Backend::new();
+        backend.set(
+            "k1".to_string(),
+            RespFrame::BulkString(b"\xff\xf0\x00".into()),
+        );
+        assert_eq!(
+            backend.bitpos("k1", false, None, None, BitUnit::Byte),
+            Ok(12)
+        );
+        assert_eq!(
+            backend.bitpos("k1", true, Some(2), None, BitUnit::Byte),
+            Ok(-1)
+        );
+        assert_eq!(
+            backend.bitpos("k1", true, Some(7), Some(15), BitUnit::Bit),
+            Ok(7)
+        );
+
+        backend.set("k2".to_string(), RespFrame::BulkString(b"\xff\xff".into()));
+        // the string is considered padded with zeros without an explicit end
+        assert_eq!(
+            backend.bitpos("k2", false, None, None, BitUnit::Byte),
+            Ok(16)
+        );
+        assert_eq!(
+            backend.bitpos("k2", false, Some(0), Some(-1), BitUnit::Byte),
+            Ok(-1)
+        );
+
+        assert_eq!(
+            backend.bitpos("missing", false, None, None, BitUnit::Byte),
+            Ok(0)
+        );
+        assert_eq!(
+            backend.bitpos("missing", true, None, None, BitUnit::Byte),
+            Ok(-1)
+        );
+
+        let mut big = vec![0u8; 1000];
+        big[900] = 0x01;
+        backend.set(
+            "big".to_string(),
+            RespFrame::BulkString(BulkString::new(big)),
+        );
+        assert_eq!(
+            backend.bitpos("big", true, None, None, BitUnit::Byte),
+            Ok(7207)
+        );
+        assert_eq!(
+            backend.bitpos("big", true, Some(1), None, BitUnit::Bit),
+            Ok(7207)
+        );
+    }
+
+    #[test]
+    fn test_setbit_getbit() {
+        let backend = Backend::new();
+        assert_eq!(backend.setbit("k1", 7, true), Ok(0));
+        assert_eq!(backend.setbit("k1", 7, true), Ok(1));
+        assert_eq!(
+            backend.get("k1"),
+            Ok(Some(RespFrame::BulkString(b"\x01".into())))
+        );
+
+        // growing pads with zero bytes
+        assert_eq!(backend.setbit("k1", 100, true), Ok(0));
+        assert_eq!(backend.strlen("k1"), Ok(13));
+        assert_eq!(backend.getbit("k1", 100), Ok(1));
+        assert_eq!(backend.getbit("k1", 99), Ok(0));
+        assert_eq!(backend.getbit("k1", 10_000), Ok(0));
+        assert_eq!(backend.getbit("missing", 0), Ok(0));
+
+        assert_eq!(backend.setbit("k1", 7, false), Ok(1));
+        assert_eq!(backend.getbit("k1", 7), Ok(0));
+
+        // "1" is 0x31
+        backend.set("k2".to_string(), RespFrame::Integer(1));
+        assert_eq!(backend.getbit("k2", 2), Ok(1));
+        assert_eq!(backend.setbit("k2", 7, false), Ok(1));
+        assert_eq!(
+            backend.get("k2"),
+            Ok(Some(RespFrame::BulkString(b"0".into())))
+        );
+
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(backend.setbit("h", 0, true), Err(BackendError::WrongType));
+    }
+}
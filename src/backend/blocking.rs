@@ -0,0 +1,568 @@
+use crate::backend::{command, string_bytes};
+use crate::{
+    Backend, BackendError, BulkString, RespFrame, Shard, ShardsWriteGuard, SortedSet, Value,
+};
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// The elements popped for a client, with the key they were popped from. Members popped from
+/// a zset are each followed by their score.
+pub type Popped = (String, Vec<RespFrame>);
+
+/// The type and end a blocking pop takes elements from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopFrom {
+    ListHead,
+    ListTail,
+    ZSetMin,
+    ZSetMax,
+    /// XREAD: nothing is popped, the client is only woken to read the new entries.
+    Stream,
+    /// WAIT and WAITAOF: nothing is popped, the client is only woken to count the
+    /// acknowledgements again.
+    Acks,
+}
+
+impl PopFrom {
+    pub fn list(head: bool) -> Self {
+        if head {
+            PopFrom::ListHead
+        } else {
+            PopFrom::ListTail
+        }
+    }
+
+    // whether value is of the type popped from
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            PopFrom::ListHead | PopFrom::ListTail => matches!(value, Value::List(_)),
+            PopFrom::ZSetMin | PopFrom::ZSetMax => matches!(value, Value::ZSet(_)),
+            PopFrom::Stream => matches!(value, Value::Stream(_)),
+            PopFrom::Acks => false,
+        }
+    }
+}
+
+/// The clients blocked on list, zset and stream keys, and on the acknowledgements of the
+/// replicas and the fsyncs of the AOF. Each key has a FIFO queue, so the client blocked the
+/// longest among those popping that type is served first when an element is added. Stream
+/// readers, and the clients waiting for acknowledgements, are all woken at once.
+#[derive(Debug, Default)]
+pub struct BlockedClients {
+    next_id: AtomicU64,
+    // shared with the blocked clients, which leave them once dropped
+    queues: Arc<Queues>,
+}
+
+// what a client is blocked on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Wakeup {
+    Key(String),
+    Acks,
+}
+
+#[derive(Debug, Default)]
+struct Queues(Mutex<HashMap<Wakeup, VecDeque<Arc<Waiter>>>>);
+
+impl Queues {
+    fn dequeue(&self, id: u64, wakeups: &[Wakeup]) {
+        let mut queues = self.0.lock();
+        for wakeup in wakeups {
+            if let Some(queue) = queues.get_mut(wakeup) {
+                queue.retain(|waiter| waiter.id != id);
+                if queue.is_empty() {
+                    queues.remove(wakeup);
+                }
+            }
+        }
+    }
+
+    // wake every client blocked on wakeup, to check again what it waits for
+    fn wake_all(&self, wakeup: &Wakeup) {
+        let queue = self.0.lock().remove(wakeup).unwrap_or_default();
+        for waiter in queue {
+            if let Some(tx) = waiter.tx.lock().take() {
+                let _ = tx.send((String::new(), Vec::new()));
+            }
+        }
+    }
+}
+
+/// Wakes the clients blocked on acknowledgements, from where the backend isn't at hand.
+#[derive(Debug, Clone)]
+pub(crate) struct AcksWaker(Arc<Queues>);
+
+impl AcksWaker {
+    pub(crate) fn wake(&self) {
+        self.0.wake_all(&Wakeup::Acks);
+    }
+}
+
+// a client waiting on one or more keys, queued on each of them until one serves it
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    from: PopFrom,
+    count: usize,
+    tx: Mutex<Option<oneshot::Sender<Popped>>>,
+}
+
+/// The outcome of `Backend::bpop`.
+#[derive(Debug)]
+pub enum BlockingPop {
+    Ready(Popped),
+    Blocked(BlockedPop),
+}
+
+/// A client blocked by `Backend::bpop`, pass it to `Backend::unblock` once done waiting. One
+/// dropped meanwhile, as its connection is closed, leaves the queues it's in.
+#[derive(Debug)]
+pub struct BlockedPop {
+    id: u64,
+    wakeups: Vec<Wakeup>,
+    rx: oneshot::Receiver<Popped>,
+    queues: Arc<Queues>,
+}
+
+impl BlockedPop {
+    /// Wait until elements are pushed for this client.
+    pub async fn wait(&mut self) -> Option<Popped> {
+        (&mut self.rx).await.ok()
+    }
+}
+
+impl Drop for BlockedPop {
+    fn drop(&mut self) {
+        self.queues.dequeue(self.id, &self.wakeups);
+    }
+}
+
+impl Backend {
+    /// Pop up to count elements from the first non-empty list among keys, `None` if they are
+    /// all empty.
+    pub fn lmpop(
+        &self,
+        keys: &[String],
+        head: bool,
+        count: usize,
+    ) -> Result<Option<Popped>, BackendError> {
+        let mut guard = self.write_many(keys.iter().map(String::as_str));
+        self.pop_first(&mut guard, keys, PopFrom::list(head), count)
+    }
+
+    /// Pop up to count members from the first non-empty zset among keys, `None` if they are all
+    /// empty. Members are followed by their scores.
+    pub fn zmpop(
+        &self,
+        keys: &[String],
+        max: bool,
+        count: usize,
+    ) -> Result<Option<Popped>, BackendError> {
+        let mut guard = self.write_many(keys.iter().map(String::as_str));
+        let from = if max {
+            PopFrom::ZSetMax
+        } else {
+            PopFrom::ZSetMin
+        };
+        self.pop_first(&mut guard, keys, from, count)
+    }
+
+    /// Pop up to count elements from the first non-empty list or zset among keys. If they are
+    /// all empty the client is queued on every key, atomically with the check so no push can be
+    /// missed.
+    pub fn bpop(
+        &self,
+        keys: &[String],
+        from: PopFrom,
+        count: usize,
+    ) -> Result<BlockingPop, BackendError> {
+        self.run_logged(|| {
+            let mut guard = self.write_many(keys.iter().map(String::as_str));
+            if let Some(popped) = self.pop_first(&mut guard, keys, from, count)? {
+                self.propagate_pop(&popped.0, from, popped.1.len());
+                return Ok(BlockingPop::Ready(popped));
+            }
+            Ok(BlockingPop::Blocked(self.block_on(keys, from, count)))
+        })
+    }
+
+    // propagate the len values popped from key as the non-blocking pop of as many
+    fn propagate_pop(&self, key: &str, from: PopFrom, len: usize) {
+        let (name, count) = match from {
+            PopFrom::ListHead => ("LPOP", len),
+            PopFrom::ListTail => ("RPOP", len),
+            // members are followed by their scores
+            PopFrom::ZSetMin => ("ZPOPMIN", len / 2),
+            PopFrom::ZSetMax => ("ZPOPMAX", len / 2),
+            PopFrom::Stream | PopFrom::Acks => return,
+        };
+        self.propagate(command([name, key, &count.to_string()]));
+    }
+
+    // queue a client on every key, called with the keys locked
+    pub(crate) fn block_on(&self, keys: &[String], from: PopFrom, count: usize) -> BlockedPop {
+        let wakeups = keys.iter().cloned().map(Wakeup::Key).collect();
+        self.enqueue(wakeups, from, count)
+    }
+
+    /// Queue a client until a replica acknowledges an offset or the AOF is fsynced, for WAIT
+    /// and WAITAOF to count the acknowledgements again. Queued before they're counted, none
+    /// can be missed.
+    pub fn block_on_acks(&self) -> BlockedPop {
+        self.enqueue(vec![Wakeup::Acks], PopFrom::Acks, 1)
+    }
+
+    /// Wait until acked holds, checking it again each time a replica acknowledges an offset
+    /// or the AOF is fsynced.
+    pub async fn wait_acks(&self, acked: impl Fn() -> bool) {
+        loop {
+            let mut blocked = self.block_on_acks();
+            if acked() {
+                return;
+            }
+            blocked.wait().await;
+        }
+    }
+
+    /// Wake the clients blocked by `block_on_acks`.
+    pub(crate) fn wake_acks(&self) {
+        self.blocked.queues.wake_all(&Wakeup::Acks);
+    }
+
+    pub(crate) fn acks_waker(&self) -> AcksWaker {
+        AcksWaker(self.blocked.queues.clone())
+    }
+
+    fn enqueue(&self, wakeups: Vec<Wakeup>, from: PopFrom, count: usize) -> BlockedPop {
+        let (tx, rx) = oneshot::channel();
+        let waiter = Arc::new(Waiter {
+            id: self.blocked.next_id.fetch_add(1, Ordering::Relaxed),
+            from,
+            count: count.max(1),
+            tx: Mutex::new(Some(tx)),
+        });
+        let mut queues = self.blocked.queues.0.lock();
+        for wakeup in &wakeups {
+            queues
+                .entry(wakeup.clone())
+                .or_default()
+                .push_back(waiter.clone());
+        }
+        BlockedPop {
+            id: waiter.id,
+            wakeups,
+            rx,
+            queues: self.blocked.queues.clone(),
+        }
+    }
+
+    fn pop_first(
+        &self,
+        guard: &mut ShardsWriteGuard,
+        keys: &[String],
+        from: PopFrom,
+        count: usize,
+    ) -> Result<Option<Popped>, BackendError> {
+        for key in keys {
+            let shard = guard.shard(key);
+            match shard.get(key).map(|obj| &**obj) {
+                None => continue,
+                Some(value) if from.matches(value) => {}
+                Some(_) => return Err(BackendError::WrongType),
+            }
+            let popped = self.pop_from(shard, key, from, count.max(1));
+            if !popped.is_empty() {
+                return Ok(Some((key.clone(), popped)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Dequeue a blocked client, returning the elements it was served in the meantime if any.
+    pub fn unblock(&self, mut blocked: BlockedPop) -> Option<Popped> {
+        blocked.queues.dequeue(blocked.id, &blocked.wakeups);
+        blocked.rx.try_recv().ok()
+    }
+
+    /// Hand the elements of the list or zset at key to the clients blocked on it popping that
+    /// type, in FIFO order. Called with the shard locked right after elements were added.
+    pub(crate) fn serve_blocked(&self, shard: &mut Shard, key: &str) {
+        loop {
+            let waiter = {
+                let Some(value) = shard.get(key) else {
+                    return;
+                };
+                let wakeup = Wakeup::Key(key.to_string());
+                let mut queues = self.blocked.queues.0.lock();
+                let Some(queue) = queues.get_mut(&wakeup) else {
+                    return;
+                };
+                let waiter = queue
+                    .iter()
+                    .position(|waiter| waiter.from.matches(value))
+                    .and_then(|i| queue.remove(i));
+                if queue.is_empty() {
+                    queues.remove(&wakeup);
+                }
+                match waiter {
+                    Some(waiter) => waiter,
+                    None => return,
+                }
+            };
+
+            // None if the client was already served through another key
+            let Some(tx) = waiter.tx.lock().take() else {
+                continue;
+            };
+            if waiter.from == PopFrom::Stream {
+                let _ = tx.send((key.to_string(), Vec::new()));
+                continue;
+            }
+            let values = self.pop_from(shard, key, waiter.from, waiter.count);
+            if values.is_empty() {
+                return;
+            }
+            let len = values.len();
+            match tx.send((key.to_string(), values)) {
+                Ok(()) => self.propagate_pop(key, waiter.from, len),
+                // the client is gone, put the elements back where they were
+                Err((_, values)) => self.push_back_unserved(shard, key, values, waiter.from),
+            }
+        }
+    }
+
+    fn pop_from(
+        &self,
+        shard: &mut Shard,
+        key: &str,
+        from: PopFrom,
+        count: usize,
+    ) -> Vec<RespFrame> {
+        match from {
+            PopFrom::ListHead | PopFrom::ListTail => {
+                self.pop_many(shard, key, count, from == PopFrom::ListHead)
+            }
+            PopFrom::ZSetMin | PopFrom::ZSetMax => self
+                .zpop_many(shard, key, count, from == PopFrom::ZSetMax)
+                .into_iter()
+                .flat_map(|(member, score)| {
+                    [BulkString::new(member).into(), RespFrame::Double(score)]
+                })
+                .collect(),
+            PopFrom::Stream | PopFrom::Acks => Vec::new(),
+        }
+    }
+
+    fn push_back_unserved(
+        &self,
+        shard: &mut Shard,
+        key: &str,
+        values: Vec<RespFrame>,
+        from: PopFrom,
+    ) {
+        if !shard.contains_key(key) {
+            let value = match from {
+                PopFrom::ListHead | PopFrom::ListTail => Value::List(self.new_list()),
+                PopFrom::ZSetMin | PopFrom::ZSetMax => Value::ZSet(SortedSet::new()),
+                // nothing is popped from a stream
+                PopFrom::Stream | PopFrom::Acks => return,
+            };
+            shard.insert(key.to_string(), value);
+        }
+        match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::List(list)) => {
+                // values come in pop order, the first popped is the closest to the end
+                for value in values.iter().rev() {
+                    if from == PopFrom::ListHead {
+                        list.push_front(&string_bytes(value));
+                    } else {
+                        list.push_back(&string_bytes(value));
+                    }
+                }
+            }
+            Some(Value::ZSet(zset)) => {
+                for pair in values.chunks(2) {
+                    if let [member, RespFrame::Double(score)] = pair {
+                        let member = String::from_utf8_lossy(&string_bytes(member)).into_owned();
+                        zset.insert(member, *score);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    fn blocked(result: Result<BlockingPop, BackendError>) -> BlockedPop {
+        match result {
+            Ok(BlockingPop::Blocked(blocked)) => blocked,
+            other => panic!("expected a blocked pop, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bpop_ready() {
+        let backend = Backend::new();
+        backend.rpush("l2", vec![bulk("a"), bulk("b")]).unwrap();
+        let keys = ["l1".to_string(), "l2".to_string()];
+        match backend.bpop(&keys, PopFrom::ListTail, 1) {
+            Ok(BlockingPop::Ready(popped)) => {
+                assert_eq!(popped, ("l2".to_string(), vec![bulk("b")]))
+            }
+            other => panic!("expected a ready pop, got {:?}", other),
+        }
+
+        backend.set("s".to_string(), bulk("v"));
+        let keys = ["l1".to_string(), "s".to_string()];
+        assert!(matches!(
+            backend.bpop(&keys, PopFrom::ListHead, 1),
+            Err(BackendError::WrongType)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bpop_served_in_fifo_order() {
+        let backend = Backend::new();
+        let keys = ["l1".to_string(), "l2".to_string()];
+        let mut first = blocked(backend.bpop(&keys, PopFrom::ListHead, 1));
+        let mut second = blocked(backend.bpop(&keys[1..], PopFrom::ListHead, 1));
+
+        // the first client is served through l2, and no longer waits on l1
+        backend.rpush("l2", vec![bulk("a"), bulk("b")]).unwrap();
+        assert_eq!(
+            first.wait().await,
+            Some(("l2".to_string(), vec![bulk("a")]))
+        );
+        assert_eq!(
+            second.wait().await,
+            Some(("l2".to_string(), vec![bulk("b")]))
+        );
+        assert!(!backend.contains_key("l2"));
+
+        backend.rpush("l1", vec![bulk("c")]).unwrap();
+        assert_eq!(backend.unblock(first), None);
+        assert_eq!(backend.unblock(second), None);
+        assert_eq!(backend.llen("l1"), Ok(1));
+    }
+
+    #[test]
+    fn test_unblocked_client_not_served() {
+        let backend = Backend::new();
+        let keys = ["l".to_string()];
+        let waiter = blocked(backend.bpop(&keys, PopFrom::ListHead, 1));
+        assert_eq!(backend.unblock(waiter), None);
+
+        // nobody is waiting anymore, the element stays in the list
+        backend.rpush("l", vec![bulk("a")]).unwrap();
+        assert_eq!(backend.llen("l"), Ok(1));
+
+        // a client that went away without unblocking doesn't lose the element either
+        let waiter = blocked(backend.bpop(&["x".to_string()], PopFrom::ListTail, 2));
+        drop(waiter);
+        backend.rpush("x", vec![bulk("a"), bulk("b")]).unwrap();
+        assert_eq!(backend.lrange("x", 0, -1), Ok(vec![bulk("a"), bulk("b")]));
+    }
+
+    #[test]
+    fn test_dropped_client_dequeued() {
+        let backend = Backend::new();
+        let keys = ["l1".to_string(), "l2".to_string()];
+        let waiter = blocked(backend.bpop(&keys, PopFrom::ListHead, 1));
+        let acks = backend.block_on_acks();
+        assert_eq!(backend.blocked.queues.0.lock().len(), 3);
+
+        // as when the connection closes while blocked
+        drop(waiter);
+        drop(acks);
+        assert!(backend.blocked.queues.0.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wait_acks() {
+        let backend = Backend::new();
+        let acked = Arc::new(AtomicBool::new(false));
+        let waiting = tokio::spawn({
+            let (backend, acked) = (backend.clone(), acked.clone());
+            async move { backend.wait_acks(|| acked.load(Ordering::Acquire)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // woken, but not acknowledged yet
+        backend.wake_acks();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        acked.store(true, Ordering::Release);
+        backend.wake_acks();
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(backend.blocked.queues.0.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bpop_count() {
+        let backend = Backend::new();
+        let keys = ["l1".to_string(), "l2".to_string()];
+        assert_eq!(backend.lmpop(&keys, true, 2), Ok(None));
+
+        let mut waiter = blocked(backend.bpop(&keys, PopFrom::ListTail, 2));
+        backend
+            .rpush("l2", vec![bulk("a"), bulk("b"), bulk("c")])
+            .unwrap();
+        assert_eq!(
+            waiter.wait().await,
+            Some(("l2".to_string(), vec![bulk("c"), bulk("b")]))
+        );
+
+        backend.rpush("l1", vec![bulk("d")]).unwrap();
+        assert_eq!(
+            backend.lmpop(&keys, true, 5),
+            Ok(Some(("l1".to_string(), vec![bulk("d")])))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bzpop() {
+        let backend = Backend::new();
+        let keys = ["z".to_string()];
+        let mut list_waiter = blocked(backend.bpop(&keys, PopFrom::ListHead, 1));
+        let mut zset_waiter = blocked(backend.bpop(&keys, PopFrom::ZSetMax, 1));
+
+        // only the client popping from a zset is served by ZADD
+        backend
+            .zadd("z", vec![(1.0, "a".to_string()), (2.0, "b".to_string())])
+            .unwrap();
+        assert_eq!(
+            zset_waiter.wait().await,
+            Some(("z".to_string(), vec![bulk("b"), RespFrame::Double(2.0)]))
+        );
+        assert_eq!(backend.zcard("z"), Ok(1));
+        assert_eq!(backend.unblock(zset_waiter), None);
+
+        assert!(matches!(
+            backend.bpop(&keys, PopFrom::ListHead, 1),
+            Err(BackendError::WrongType)
+        ));
+        backend.zrem("z", &["a".to_string()]).unwrap();
+        backend.rpush("z", vec![bulk("x")]).unwrap();
+        assert_eq!(
+            list_waiter.wait().await,
+            Some(("z".to_string(), vec![bulk("x")]))
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::network::RespFrameCodec;
+use crate::{Backend, BulkString, RespArray, RespFrame, SlotRanges};
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Handle;
+use tokio::time::timeout;
+use tokio_util::codec::Framed;
+use tracing::{debug, info};
+
+/// How often a node pings each of the others over the cluster bus.
+pub const CLUSTER_PING_PERIOD: Duration = Duration::from_secs(1);
+
+// how often the nodes learned meanwhile get a link of their own
+const CLUSTER_LINK_PERIOD: Duration = Duration::from_millis(100);
+
+// how long a node waits for the answer of another, to connect or to a ping
+const CLUSTER_LINK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BusMessageKind {
+    Meet,
+    Ping,
+    Pong,
+}
+
+/// A message of the cluster bus, sent as an array of bulk strings: the kind, the ID, port,
+/// bus port and slots of the sender, then a `id ip:port@cport` for each node it knows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct BusMessage {
+    pub(super) kind: BusMessageKind,
+    pub(super) id: String,
+    pub(super) port: u16,
+    pub(super) cport: u16,
+    pub(super) slots: SlotRanges,
+    pub(super) gossip: Vec<Gossip>,
+}
+
+/// A node the sender of a message knows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Gossip {
+    pub(super) id: String,
+    pub(super) ip: String,
+    pub(super) port: u16,
+    pub(super) cport: u16,
+}
+
+impl Backend {
+    /// Serve the cluster bus on listener, and ping the other nodes, each over a link of its
+    /// own, from tasks spawned on handle.
+    pub fn start_cluster_bus(&self, listener: TcpListener, handle: &Handle) {
+        let backend = self.clone();
+        handle.spawn(async move {
+            loop {
+                let (stream, raddr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        info!("cluster bus accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = answer(&backend, stream, &raddr.ip().to_string()).await {
+                        debug!("cluster bus link from {} lost: {}", raddr, e);
+                    }
+                });
+            }
+        });
+
+        let backend = self.clone();
+        handle.spawn(async move {
+            let linked = Arc::new(Mutex::new(HashSet::new()));
+            let mut interval = tokio::time::interval(CLUSTER_LINK_PERIOD);
+            loop {
+                interval.tick().await;
+                for address in backend.cluster_links() {
+                    if linked.lock().insert(address.clone()) {
+                        tokio::spawn(link(backend.clone(), address, linked.clone()));
+                    }
+                }
+            }
+        });
+    }
+}
+
+// answer the messages of a node until it disconnects
+async fn answer(backend: &Backend, stream: TcpStream, ip: &str) -> Result<()> {
+    let mut framed = Framed::new(stream, RespFrameCodec::client());
+    while let Some(frame) = framed.next().await {
+        let message = BusMessage::try_from(frame?)?;
+        let kind = message.kind;
+        backend.cluster_receive(ip, message, None);
+        if kind != BusMessageKind::Pong {
+            framed.send(RespFrame::from(backend.cluster_pong())).await?;
+        }
+    }
+    Ok(())
+}
+
+// ping the node at a cluster bus address, connecting again whenever the connection is lost,
+// until it's forgotten
+async fn link(
+    backend: Backend,
+    address: (String, u16),
+    linked: Arc<Mutex<HashSet<(String, u16)>>>,
+) {
+    let (ip, cport) = (address.0.as_str(), address.1);
+    loop {
+        match ping(&backend, ip, cport).await {
+            Ok(()) => break,
+            Err(e) => debug!("cluster bus link to {}:{} lost: {}", ip, cport, e),
+        }
+        tokio::time::sleep(CLUSTER_PING_PERIOD).await;
+    }
+    linked.lock().remove(&address);
+}
+
+async fn ping(backend: &Backend, ip: &str, cport: u16) -> Result<()> {
+    let stream = timeout(CLUSTER_LINK_TIMEOUT, TcpStream::connect((ip, cport))).await??;
+    let mut framed = Framed::new(stream, RespFrameCodec::client());
+    let mut interval = tokio::time::interval(CLUSTER_PING_PERIOD);
+    loop {
+        interval.tick().await;
+        let Some(ping) = backend.cluster_ping(ip, cport) else {
+            return Ok(());
+        };
+        framed.send(RespFrame::from(ping)).await?;
+        let frame = timeout(CLUSTER_LINK_TIMEOUT, framed.next())
+            .await?
+            .ok_or_else(|| anyhow!("connection closed"))??;
+        let pong = BusMessage::try_from(frame)?;
+        if pong.kind != BusMessageKind::Pong {
+            return Err(anyhow!("expected a PONG"));
+        }
+        backend.cluster_receive(ip, pong, Some((ip, cport)));
+    }
+}
+
+impl From<BusMessage> for RespFrame {
+    fn from(message: BusMessage) -> Self {
+        let kind = match message.kind {
+            BusMessageKind::Meet => "MEET",
+            BusMessageKind::Ping => "PING",
+            BusMessageKind::Pong => "PONG",
+        };
+        let header = [
+            kind.to_string(),
+            message.id,
+            message.port.to_string(),
+            message.cport.to_string(),
+            message.slots.to_string(),
+        ];
+        let gossip = message
+            .gossip
+            .into_iter()
+            .map(|node| format!("{} {}:{}@{}", node.id, node.ip, node.port, node.cport));
+        let fields: Vec<RespFrame> = header
+            .into_iter()
+            .chain(gossip)
+            .map(|field| BulkString::from(field.as_str()).into())
+            .collect();
+        RespArray::new(fields).into()
+    }
+}
+
+impl TryFrom<RespFrame> for BusMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(frame: RespFrame) -> Result<Self> {
+        let invalid = || anyhow!("invalid cluster bus message");
+        let RespFrame::Array(array) = frame else {
+            return Err(invalid());
+        };
+        let fields = array
+            .iter()
+            .map(|field| match field {
+                RespFrame::BulkString(field) => Some(String::from_utf8_lossy(field).into_owned()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        let [kind, id, port, cport, slots, gossip @ ..] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        let kind = match kind.as_str() {
+            "MEET" => BusMessageKind::Meet,
+            "PING" => BusMessageKind::Ping,
+            "PONG" => BusMessageKind::Pong,
+            _ => return Err(invalid()),
+        };
+        let gossip = gossip
+            .iter()
+            .map(|node| {
+                let (id, address) = node.split_once(' ')?;
+                let (address, cport) = address.split_once('@')?;
+                let (ip, port) = address.rsplit_once(':')?;
+                Some(Gossip {
+                    id: id.to_string(),
+                    ip: ip.to_string(),
+                    port: port.parse().ok()?,
+                    cport: cport.parse().ok()?,
+                })
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        Ok(BusMessage {
+            kind,
+            id: id.clone(),
+            port: port.parse()?,
+            cport: cport.parse()?,
+            slots: slots.parse().map_err(|e: String| anyhow!(e))?,
+            gossip,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_encoding() -> Result<()> {
+        let message = BusMessage {
+            kind: BusMessageKind::Ping,
+            id: "a".repeat(40),
+            port: 7000,
+            cport: 17000,
+            slots: SlotRanges(vec![(0, 5460), (5462, 5462)]),
+            gossip: vec![Gossip {
+                id: "b".repeat(40),
+                ip: "::1".to_string(),
+                port: 7001,
+                cport: 17001,
+            }],
+        };
+        let frame = RespFrame::from(message.clone());
+        let RespFrame::Array(fields) = &frame else {
+            panic!("a message isn't an array");
+        };
+        assert_eq!(fields[0], BulkString::from("PING").into());
+        assert_eq!(fields[4], BulkString::from("0-5460,5462").into());
+        assert_eq!(BusMessage::try_from(frame)?, message);
+
+        let invalid = RespArray::new(vec![BulkString::from("PING").into()]);
+        assert!(BusMessage::try_from(RespFrame::from(invalid)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{Backend, ConsumerGroup, StreamId, Value};
+use std::fmt;
+
+// Invariants checked when walking the keyspace:
+// - an aggregate value always has at least one element, empty ones are deleted in redis
+// - a TTL always belongs to an existing key
+// - a consumer group delivered no entry past the last ID of its stream, so none is pending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    EmptyValue(String, &'static str),
+    DanglingExpire(String),
+    // the stream and the group
+    GroupAhead(String, String),
+    // the stream, the group and the ID pending
+    DanglingPending(String, String, StreamId),
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inconsistency::EmptyValue(key, type_name) => {
+                write!(f, "{} {:?} has no elements", type_name, key)
+            }
+            Inconsistency::DanglingExpire(key) => {
+                write!(f, "TTL set for missing key {:?}", key)
+            }
+            Inconsistency::GroupAhead(key, group) => {
+                write!(
+                    f,
+                    "group {:?} of stream {:?} delivered past its last ID",
+                    group, key
+                )
+            }
+            Inconsistency::DanglingPending(key, group, id) => {
+                write!(
+                    f,
+                    "group {:?} of stream {:?} has {} pending past its last ID",
+                    group, key, id
+                )
+            }
+        }
+    }
+}
+
+impl Backend {
+    /// Walk the keyspace and report every broken invariant without touching the data.
+    pub fn check(&self) -> Vec<Inconsistency> {
+        let mut found = Vec::new();
+        for shard in self.shards() {
+            let shard = shard.read();
+            for (key, value) in shard.iter() {
+                let empty = match &**value {
+                    Value::String(_) => false,
+                    Value::Hash(hash) => hash.is_empty(),
+                    Value::List(list) => list.is_empty(),
+                    Value::Set(set) => set.is_empty(),
+                    Value::ZSet(zset) => zset.is_empty(),
+                    // unlike the other containers, an empty stream is kept
+                    Value::Stream(_) => false,
+                };
+                if empty {
+                    found.push(Inconsistency::EmptyValue(
+                        key.to_string(),
+                        value.type_name(),
+                    ));
+                }
+                if let Value::Stream(stream) = &**value {
+                    let last_id = stream.last_id();
+                    for (name, group) in stream.groups() {
+                        if group.last_delivered() > last_id {
+                            found.push(Inconsistency::GroupAhead(key.to_string(), name.clone()));
+                        }
+                        for id in group.pending().keys().filter(|id| **id > last_id) {
+                            found.push(Inconsistency::DanglingPending(
+                                key.to_string(),
+                                name.clone(),
+                                *id,
+                            ));
+                        }
+                    }
+                }
+            }
+            for (key, _) in shard.expires() {
+                if !shard.contains_key(key) {
+                    found.push(Inconsistency::DanglingExpire(key.to_string()));
+                }
+            }
+        }
+        found
+    }
+
+    /// Like `check`, but also fix what was found.
+    pub fn repair(&self) -> Vec<Inconsistency> {
+        let found = self.check();
+        for inconsistency in found.iter() {
+            match inconsistency {
+                Inconsistency::EmptyValue(key, _) | Inconsistency::DanglingExpire(key) => {
+                    self.shard(key).write().remove(key);
+                }
+                Inconsistency::GroupAhead(key, group) => {
+                    self.repair_group(key, group, |last_id, group| {
+                        group.set_last_delivered(last_id)
+                    });
+                }
+                Inconsistency::DanglingPending(key, group, id) => {
+                    self.repair_group(key, group, |_, group| {
+                        group.ack(*id);
+                    });
+                }
+            }
+        }
+        found
+    }
+
+    // run f on group of the stream at key with the stream's last ID
+    fn repair_group(&self, key: &str, group: &str, f: impl FnOnce(StreamId, &mut ConsumerGroup)) {
+        let mut shard = self.shard(key).write();
+        if let Some(Value::Stream(stream)) = shard.get_mut(key).map(|value| &mut **value) {
+            let last_id = stream.last_id();
+            if let Some(group) = stream.group_mut(group) {
+                f(last_id, group);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PendingEntry, RespFrame, XAddId};
+
+    #[test]
+    fn test_check_and_repair() {
+        let backend = Backend::new();
+        backend
+            .hset(
+                "k2".to_string(),
+                "f1".to_string(),
+                RespFrame::BulkString(b"v1".into()),
+            )
+            .unwrap();
+        backend
+            .write("k3")
+            .insert("k3".to_string(), Value::Hash(Default::default()));
+        backend.write("k4").set_expire("k4".to_string(), i64::MAX);
+
+        let mut found = backend.check();
+        found.sort_by_key(|i| i.to_string());
+        assert_eq!(
+            found,
+            vec![
+                Inconsistency::DanglingExpire("k4".to_string()),
+                Inconsistency::EmptyValue("k3".to_string(), "hash"),
+            ]
+        );
+
+        assert_eq!(backend.repair().len(), 2);
+        assert!(backend.check().is_empty());
+        assert!(backend.hget("k2", "f1").unwrap().is_some());
+    }
+
+    // the stream s with entries 1-0 and 2-0, and its group g
+    fn stream_with_group() -> Backend {
+        let backend = Backend::new();
+        for ms in 1..=2 {
+            let id = XAddId::Explicit(StreamId::new(ms, 0));
+            let fields = vec![("f".to_string(), RespFrame::BulkString(b"v".into()))];
+            backend.xadd("s", id, fields, false).unwrap();
+        }
+        backend
+            .xgroup_create("s", "g", Some(StreamId::new(0, 0)), false)
+            .unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_group_ahead() {
+        let backend = stream_with_group();
+        assert!(backend.check().is_empty());
+        backend
+            .xgroup_setid("s", "g", Some(StreamId::new(5, 0)))
+            .unwrap();
+
+        let ahead = Inconsistency::GroupAhead("s".to_string(), "g".to_string());
+        assert_eq!(backend.check(), vec![ahead.clone()]);
+        assert_eq!(backend.repair(), vec![ahead]);
+        assert!(backend.check().is_empty());
+        let groups = backend.xinfo_groups("s").unwrap();
+        assert_eq!(groups[0].last_delivered, StreamId::new(2, 0));
+    }
+
+    #[test]
+    fn test_dangling_pending() {
+        let backend = stream_with_group();
+        let keys = ["s".to_string()];
+        backend
+            .xreadgroup("g", "alice", &keys, &[None], Some(1), false)
+            .unwrap();
+        let mut shard = backend.shard("s").write();
+        if let Some(Value::Stream(stream)) = shard.get_mut("s").map(|value| &mut **value) {
+            let entry = PendingEntry {
+                consumer: "alice".to_string(),
+                delivered_at: 0,
+                deliveries: 1,
+            };
+            let group = stream.group_mut("g").unwrap();
+            group.restore_pending(StreamId::new(7, 0), entry);
+        }
+        drop(shard);
+
+        let dangling =
+            Inconsistency::DanglingPending("s".to_string(), "g".to_string(), StreamId::new(7, 0));
+        assert_eq!(backend.check(), vec![dangling.clone()]);
+        assert_eq!(backend.repair(), vec![dangling]);
+        assert!(backend.check().is_empty());
+        // the entry delivered stays pending
+        let pending = backend.xpending("s", "g").unwrap();
+        assert_eq!(pending.count, 1);
+        assert_eq!(pending.consumers, vec![("alice".to_string(), 1)]);
+    }
+}
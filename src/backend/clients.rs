@@ -0,0 +1,309 @@
+use crate::backend::now_ms;
+use crate::{parse_memory, Backend, Stat};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tracing::info;
+
+/// The connected clients, as CLIENT LIST reports them, and the limit on the memory their
+/// connections use together.
+#[derive(Debug, Default)]
+pub struct Clients {
+    maxmemory_clients: RwLock<MaxMemoryClients>,
+    // shared with the connections, which leave as they close
+    clients: Arc<Mutex<HashMap<u64, Arc<ClientConnection>>>>,
+}
+
+#[derive(Debug)]
+struct ClientConnection {
+    addr: SocketAddr,
+    // unix time in milliseconds
+    created: i64,
+    // the bytes of the query buffer used and allocated, and of the output buffer allocated
+    qbuf: AtomicU64,
+    qbuf_capacity: AtomicU64,
+    omem: AtomicU64,
+    // CLIENT NO-EVICT on
+    no_evict: AtomicBool,
+    // set once evicted, until the connection is closed
+    evicting: AtomicBool,
+    evicted: Notify,
+}
+
+/// The limit on the memory the connections of the clients use together, in bytes or as a
+/// percentage of maxmemory, as `1gb` or `10%` in the config. 0 for no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxMemoryClients {
+    Bytes(u64),
+    Percent(u64),
+}
+
+/// A client as CLIENT LIST reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: SocketAddr,
+    /// Seconds since it connected, and since it was last done with a command.
+    pub age: u64,
+    pub idle: u64,
+    pub no_evict: bool,
+    pub qbuf: u64,
+    pub qbuf_free: u64,
+    pub omem: u64,
+    /// The buffers and the keys tracked for it, the memory counted towards
+    /// maxmemory-clients.
+    pub tot_mem: u64,
+}
+
+/// The connection of a client, counted in CLIENT LIST and towards maxmemory-clients until
+/// dropped.
+#[derive(Debug)]
+pub struct ClientHandle {
+    id: u64,
+    state: Arc<ClientConnection>,
+    clients: Arc<Mutex<HashMap<u64, Arc<ClientConnection>>>>,
+}
+
+impl Default for MaxMemoryClients {
+    fn default() -> Self {
+        MaxMemoryClients::Bytes(0)
+    }
+}
+
+impl ClientHandle {
+    /// The bytes the query buffer of the connection uses and has allocated, and those the
+    /// output buffer has.
+    pub fn buffers(&self, qbuf: usize, qbuf_capacity: usize, omem: usize) {
+        self.state.qbuf.store(qbuf as u64, Ordering::Relaxed);
+        self.state
+            .qbuf_capacity
+            .store(qbuf_capacity as u64, Ordering::Relaxed);
+        self.state.omem.store(omem as u64, Ordering::Relaxed);
+    }
+
+    /// Wait until the client is evicted for the memory of the clients, which closes it.
+    pub async fn evicted(&self) {
+        self.state.evicted.notified().await;
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.clients.lock().remove(&self.id);
+    }
+}
+
+impl Backend {
+    /// Count the connection of ID id from addr in CLIENT LIST and towards maxmemory-clients.
+    pub fn register_client(&self, id: u64, addr: SocketAddr) -> ClientHandle {
+        let state = Arc::new(ClientConnection {
+            addr,
+            created: now_ms(),
+            qbuf: AtomicU64::new(0),
+            qbuf_capacity: AtomicU64::new(0),
+            omem: AtomicU64::new(0),
+            no_evict: AtomicBool::new(false),
+            evicting: AtomicBool::new(false),
+            evicted: Notify::new(),
+        });
+        self.clients.clients.lock().insert(id, state.clone());
+        ClientHandle {
+            id,
+            state,
+            clients: self.clients.clients.clone(),
+        }
+    }
+
+    /// The address the client of ID id connected from, None once it's gone.
+    pub fn client_addr(&self, id: u64) -> Option<SocketAddr> {
+        self.clients.clients.lock().get(&id).map(|state| state.addr)
+    }
+
+    /// Whether the client of ID id may be evicted for the memory of the clients.
+    pub fn client_no_evict(&self, id: u64, on: bool) {
+        if let Some(state) = self.clients.clients.lock().get(&id) {
+            state.no_evict.store(on, Ordering::Relaxed);
+        }
+    }
+
+    /// The connected clients, by ID.
+    pub fn client_list(&self) -> Vec<ClientInfo> {
+        let tracking = self.tracking_memory();
+        let now = now_ms();
+        let mut clients: Vec<ClientInfo> = self
+            .clients
+            .clients
+            .lock()
+            .iter()
+            .map(|(&id, state)| {
+                let qbuf = state.qbuf.load(Ordering::Relaxed);
+                let qbuf_capacity = state.qbuf_capacity.load(Ordering::Relaxed);
+                let omem = state.omem.load(Ordering::Relaxed);
+                ClientInfo {
+                    id,
+                    addr: state.addr,
+                    age: (now - state.created).max(0) as u64 / 1000,
+                    idle: self.client_idle(id),
+                    no_evict: state.no_evict.load(Ordering::Relaxed),
+                    qbuf,
+                    qbuf_free: qbuf_capacity.saturating_sub(qbuf),
+                    omem,
+                    tot_mem: qbuf_capacity + omem + tracking.get(&id).copied().unwrap_or(0),
+                }
+            })
+            .collect();
+        clients.sort_unstable_by_key(|client| client.id);
+        clients
+    }
+
+    pub fn maxmemory_clients(&self) -> MaxMemoryClients {
+        *self.clients.maxmemory_clients.read()
+    }
+
+    pub fn set_maxmemory_clients(&self, limit: MaxMemoryClients) {
+        *self.clients.maxmemory_clients.write() = limit;
+    }
+
+    // the limit in bytes, 0 for none, a percentage of no maxmemory included
+    fn maxmemory_clients_bytes(&self) -> u64 {
+        match self.maxmemory_clients() {
+            MaxMemoryClients::Bytes(bytes) => bytes,
+            MaxMemoryClients::Percent(percent) => self.maxmemory() * percent / 100,
+        }
+    }
+
+    // while the clients use more memory together than maxmemory-clients, close the one using
+    // the most, unless it's not to be evicted, which the cron does
+    pub(crate) fn evict_clients(&self) {
+        let limit = self.maxmemory_clients_bytes();
+        if limit == 0 {
+            return;
+        }
+        let mut clients = self.client_list();
+        let mut used: u64 = clients.iter().map(|client| client.tot_mem).sum();
+        if used <= limit {
+            return;
+        }
+        clients.retain(|client| !client.no_evict);
+        clients.sort_unstable_by_key(|client| std::cmp::Reverse(client.tot_mem));
+        let states = self.clients.clients.lock();
+        for client in clients {
+            if used <= limit {
+                break;
+            }
+            let Some(state) = states.get(&client.id) else {
+                continue;
+            };
+            used -= client.tot_mem;
+            if !state.evicting.swap(true, Ordering::Relaxed) {
+                info!(
+                    "evicting client {} using {} bytes, the clients use more than maxmemory-clients",
+                    client.id, client.tot_mem
+                );
+                state.evicted.notify_one();
+                self.count(Stat::EvictedClient);
+            }
+        }
+    }
+}
+
+impl FromStr for MaxMemoryClients {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.parse() {
+                Ok(percent @ 0..=100) => Ok(MaxMemoryClients::Percent(percent)),
+                _ => Err("argument must be a percentage between 0 and 100".to_string()),
+            },
+            None => parse_memory(s)
+                .map(MaxMemoryClients::Bytes)
+                .ok_or_else(|| "argument must be a memory or percent value".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for MaxMemoryClients {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MaxMemoryClients::Bytes(bytes) => write!(f, "{}", bytes),
+            MaxMemoryClients::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[test]
+    fn test_client_list() {
+        let backend = Backend::new();
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let second = backend.register_client(2, addr);
+        let first = backend.register_client(1, addr);
+        first.buffers(10, 1024, 4096);
+        backend.client_no_evict(2, true);
+
+        let clients = backend.client_list();
+        assert_eq!(
+            clients.iter().map(|client| client.id).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(
+            (clients[0].qbuf, clients[0].qbuf_free, clients[0].omem),
+            (10, 1014, 4096)
+        );
+        assert_eq!(clients[0].tot_mem, 1024 + 4096);
+        assert!(!clients[0].no_evict && clients[1].no_evict);
+
+        drop((first, second));
+        assert!(backend.client_list().is_empty());
+    }
+
+    #[test]
+    fn test_evict_clients() {
+        let backend = Backend::new();
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let clients: Vec<_> = (1..=4)
+            .map(|id| backend.register_client(id, addr))
+            .collect();
+        for (client, bytes) in clients.iter().zip([1000, 4000, 3000, 2000]) {
+            client.buffers(0, bytes, 0);
+        }
+        backend.client_no_evict(2, true);
+        let evicted = |client: &ClientHandle| client.evicted().now_or_never().is_some();
+
+        // no limit
+        backend.evict_clients();
+        assert!(!clients.iter().any(evicted));
+
+        // the biggest first, but the one not to be evicted, until the rest fits
+        backend.set_maxmemory_clients("6000".parse().unwrap());
+        backend.evict_clients();
+        let evicted: Vec<_> = clients.iter().map(evicted).collect();
+        assert_eq!(evicted, [false, false, true, true]);
+        assert_eq!(backend.stats_report().evicted_clients, 2);
+        // not again while they close
+        backend.evict_clients();
+        assert_eq!(backend.stats_report().evicted_clients, 2);
+
+        // of maxmemory, none without it
+        backend.set_maxmemory_clients("50%".parse().unwrap());
+        assert_eq!(backend.maxmemory_clients_bytes(), 0);
+        backend.set_maxmemory(1000);
+        assert_eq!(backend.maxmemory_clients_bytes(), 500);
+        assert_eq!(backend.maxmemory_clients().to_string(), "50%");
+        assert!("101%".parse::<MaxMemoryClients>().is_err());
+        assert_eq!(
+            "1kb".parse::<MaxMemoryClients>(),
+            Ok(MaxMemoryClients::Bytes(1024))
+        );
+    }
+}
@@ -0,0 +1,955 @@
+use super::bus::{BusMessage, BusMessageKind, Gossip};
+use crate::backend::now_ms;
+use crate::{key_hash_slot, Backend, BackendError};
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::time::Duration;
+
+/// How many hash slots the keyspace of a cluster is split into.
+pub const CLUSTER_SLOTS: usize = 16384;
+
+/// The port of the cluster bus is that of the clients, offset by this.
+pub const CLUSTER_PORT_INCR: u16 = 10000;
+
+/// How long a node may leave the pings unanswered before it's flagged as failing.
+pub const CLUSTER_NODE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// how long a forgotten node isn't learned again from the gossip of the others
+const CLUSTER_FORGET_BAN: Duration = Duration::from_secs(60);
+
+/// The cluster state of a node: the slots it serves, and the other nodes serving the others,
+/// which the clients asking for their keys are redirected to. The other nodes are configured
+/// with cluster-peers or met with CLUSTER MEET, then pinged over the cluster bus, whose
+/// messages tell the slots each serves and the nodes it knows. Standalone, the node is a
+/// cluster of its own serving no slot, which is what CLUSTER replies for the clients probing
+/// it.
+#[derive(Debug)]
+pub struct Cluster {
+    node_id: String,
+    enabled: AtomicBool,
+    slots: RwLock<SlotTable>,
+    // the clients which sent ASKING, for their next command
+    asking: Mutex<HashSet<u64>>,
+    // the ports listened on for the clients and the cluster bus, once the server runs
+    port: AtomicU16,
+    bus_port: AtomicU16,
+    // the IDs of the nodes forgotten, with when they may be learned again, in unix time ms
+    banned: Mutex<HashMap<String, i64>>,
+}
+
+#[derive(Debug)]
+struct SlotTable {
+    peers: Vec<Peer>,
+    owners: Box<[Owner]>,
+    // the slots moving to a peer, and those moving from one, by the index of the peer
+    migrating: BTreeMap<u16, usize>,
+    importing: BTreeMap<u16, usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    Unassigned,
+    Myself,
+    // an index in the peers
+    Peer(usize),
+}
+
+#[derive(Debug)]
+struct Peer {
+    id: String,
+    ip: String,
+    port: u16,
+    cport: u16,
+    // until its first PONG tells its ID, a node configured or met is known by one derived
+    // from its address, so every node configured with it knows it by the same ID meanwhile
+    handshake: bool,
+    // when the last PONG was received, 0 for none, and when the node was learned, in unix
+    // time ms
+    pong_received: i64,
+    added: i64,
+}
+
+impl Peer {
+    fn new(id: String, ip: &str, port: u16, cport: u16) -> Self {
+        Self {
+            id,
+            ip: ip.to_string(),
+            port,
+            cport,
+            handshake: false,
+            pong_received: 0,
+            added: now_ms(),
+        }
+    }
+
+    fn handshake(ip: &str, port: u16, cport: u16) -> Self {
+        let id = sha1_smol::Sha1::from(format!("{}:{}", ip, port))
+            .digest()
+            .to_string();
+        Self {
+            handshake: true,
+            ..Self::new(id, ip, port, cport)
+        }
+    }
+
+    // no PONG for longer than the node timeout, since it was learned if it never answered
+    fn is_failing(&self, now: i64) -> bool {
+        !self.handshake
+            && now - self.pong_received.max(self.added) > CLUSTER_NODE_TIMEOUT.as_millis() as i64
+    }
+}
+
+/// A node of the cluster, as CLUSTER NODES, SLOTS and SHARDS report it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterNode {
+    pub id: String,
+    /// Empty for this node when it listens on every address.
+    pub ip: String,
+    pub port: u16,
+    /// The port of the cluster bus.
+    pub cport: u16,
+    pub myself: bool,
+    /// Whether the node was never reached, so its ID is yet to be known.
+    pub handshake: bool,
+    /// Whether the node didn't answer the pings for longer than the node timeout.
+    pub failing: bool,
+    /// When the node last answered a ping in unix time ms, 0 if it never did.
+    pub pong_received: i64,
+    pub slots: SlotRanges,
+}
+
+/// Ranges of slots, inclusive, as `0-5460,5462` in the config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlotRanges(pub Vec<(u16, u16)>);
+
+/// The other nodes with the slots they serve, as `ip:port=5461-10922 ip:port@cport=10923-16383`
+/// in the config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClusterPeers(pub Vec<ClusterPeer>);
+
+/// A node of `ClusterPeers`, whose cluster bus listens on `cport`, by default the port offset
+/// by `CLUSTER_PORT_INCR`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterPeer {
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub slots: SlotRanges,
+}
+
+/// The slots moving between this node and another, with the ID of the other node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlotMigrations {
+    pub migrating: Vec<(u16, String)>,
+    pub importing: Vec<(u16, String)>,
+}
+
+/// How CLUSTER SETSLOT changes a slot, given the ID of a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotState {
+    /// The slot is moving from the node to this one, which serves the clients sending ASKING.
+    Importing(String),
+    /// The slot is moving to the node, which the keys no longer here are asked to.
+    Migrating(String),
+    /// The slot is now served by the node.
+    Node(String),
+    /// The slot is no longer moving.
+    Stable,
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Self {
+            node_id: (0..20)
+                .map(|_| format!("{:02x}", rand::random::<u8>()))
+                .collect(),
+            enabled: AtomicBool::new(false),
+            slots: RwLock::new(SlotTable {
+                peers: Vec::new(),
+                owners: vec![Owner::Unassigned; CLUSTER_SLOTS].into_boxed_slice(),
+                migrating: BTreeMap::new(),
+                importing: BTreeMap::new(),
+            }),
+            asking: Mutex::new(HashSet::new()),
+            port: AtomicU16::new(0),
+            bus_port: AtomicU16::new(0),
+            banned: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl SlotTable {
+    // the ranges of the slots served by owner
+    fn ranges(&self, owner: Owner) -> SlotRanges {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for (slot, _) in (0..).zip(self.owners.iter()).filter(|(_, o)| **o == owner) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        SlotRanges(ranges)
+    }
+
+    fn peer(&self, id: &str) -> Result<usize, BackendError> {
+        self.peers
+            .iter()
+            .position(|peer| peer.id == id)
+            .ok_or_else(|| BackendError::UnknownNode(id.to_string()))
+    }
+
+    fn address(&self, peer: usize) -> String {
+        let peer = &self.peers[peer];
+        format!("{}:{}", peer.ip, peer.port)
+    }
+
+    // forget a peer, its slots become unassigned and those moving from or to it stable
+    fn remove_peer(&mut self, peer: usize) {
+        self.peers.remove(peer);
+        let reindex = |i: usize| match i.cmp(&peer) {
+            std::cmp::Ordering::Less => Some(i),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(i - 1),
+        };
+        for owner in self.owners.iter_mut() {
+            if let Owner::Peer(i) = *owner {
+                *owner = reindex(i).map_or(Owner::Unassigned, Owner::Peer);
+            }
+        }
+        for slots in [&mut self.migrating, &mut self.importing] {
+            *slots = slots
+                .iter()
+                .filter_map(|(&slot, &i)| Some((slot, reindex(i)?)))
+                .collect();
+        }
+    }
+
+    // assign the slots to a peer, but those this node serves
+    fn assign(&mut self, peer: usize, slots: &SlotRanges) {
+        for slot in slots.slots() {
+            if self.owners[slot as usize] != Owner::Myself {
+                self.owners[slot as usize] = Owner::Peer(peer);
+            }
+        }
+    }
+}
+
+impl SlotRanges {
+    pub fn slots(&self) -> impl Iterator<Item = u16> + '_ {
+        self.0.iter().flat_map(|&(start, end)| start..=end)
+    }
+
+    pub fn count(&self) -> usize {
+        self.0
+            .iter()
+            .map(|&(start, end)| (end - start) as usize + 1)
+            .sum()
+    }
+}
+
+impl Backend {
+    /// The ID of the node, 40 hex characters, new for each run.
+    pub fn node_id(&self) -> String {
+        self.cluster.node_id.clone()
+    }
+
+    /// The address the node announces, `ip:port@cport`. The IP is left empty when the server
+    /// listens on all of them, for the clients to use that they connected to.
+    pub fn node_address(&self) -> String {
+        let (port, cport) = self.node_ports();
+        format!("{}:{}@{}", self.node_ip(), port, cport)
+    }
+
+    // the first address listened on
+    fn node_ip(&self) -> String {
+        match self.bind().0.first() {
+            Some(ip) if !ip.is_unspecified() => ip.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // the ports listened on once the server runs, those configured until then
+    fn node_ports(&self) -> (u16, u16) {
+        let port = match self.cluster.port.load(Ordering::Relaxed) {
+            0 => self.port(),
+            port => port,
+        };
+        match self.cluster.bus_port.load(Ordering::Relaxed) {
+            0 => (port, port.wrapping_add(CLUSTER_PORT_INCR)),
+            cport => (port, cport),
+        }
+    }
+
+    /// The ports the server listens on, for the clients and the cluster bus, which the node
+    /// announces to the others.
+    pub fn set_cluster_ports(&self, port: u16, bus_port: u16) {
+        self.cluster.port.store(port, Ordering::Relaxed);
+        self.cluster.bus_port.store(bus_port, Ordering::Relaxed);
+    }
+
+    /// Whether the server runs in cluster mode, set at startup.
+    pub fn is_cluster_enabled(&self) -> bool {
+        self.cluster.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_cluster_enabled(&self, enabled: bool) {
+        self.cluster.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// The slots this node serves.
+    pub fn cluster_slots(&self) -> SlotRanges {
+        self.cluster.slots.read().ranges(Owner::Myself)
+    }
+
+    /// Serve the slots, instead of those served so far, even if a peer was serving them.
+    pub fn set_cluster_slots(&self, slots: SlotRanges) {
+        let mut table = self.cluster.slots.write();
+        for owner in table.owners.iter_mut() {
+            if *owner == Owner::Myself {
+                *owner = Owner::Unassigned;
+            }
+        }
+        for slot in slots.slots() {
+            table.owners[slot as usize] = Owner::Myself;
+        }
+    }
+
+    /// The other nodes, with the slots they serve.
+    pub fn cluster_peers(&self) -> ClusterPeers {
+        let table = self.cluster.slots.read();
+        let peers = table
+            .peers
+            .iter()
+            .enumerate()
+            .map(|(i, peer)| ClusterPeer {
+                ip: peer.ip.clone(),
+                port: peer.port,
+                cport: peer.cport,
+                slots: table.ranges(Owner::Peer(i)),
+            })
+            .collect();
+        ClusterPeers(peers)
+    }
+
+    /// Replace the other nodes, and the slots they serve but those this node serves. The
+    /// slots moving from or to a node are forgotten.
+    pub fn set_cluster_peers(&self, peers: ClusterPeers) {
+        let mut table = self.cluster.slots.write();
+        table.peers = peers
+            .0
+            .iter()
+            .map(|peer| Peer::handshake(&peer.ip, peer.port, peer.cport))
+            .collect();
+        table.migrating.clear();
+        table.importing.clear();
+        for owner in table.owners.iter_mut() {
+            if let Owner::Peer(_) = owner {
+                *owner = Owner::Unassigned;
+            }
+        }
+        for (i, peer) in peers.0.iter().enumerate() {
+            table.assign(i, &peer.slots);
+        }
+    }
+
+    /// The nodes of the cluster, this one first.
+    pub fn cluster_nodes(&self) -> Vec<ClusterNode> {
+        let table = self.cluster.slots.read();
+        let (port, cport) = self.node_ports();
+        let myself = ClusterNode {
+            id: self.node_id(),
+            ip: self.node_ip(),
+            port,
+            cport,
+            myself: true,
+            handshake: false,
+            failing: false,
+            pong_received: 0,
+            slots: table.ranges(Owner::Myself),
+        };
+        let now = now_ms();
+        let peers = table.peers.iter().enumerate().map(|(i, peer)| ClusterNode {
+            id: peer.id.clone(),
+            ip: peer.ip.clone(),
+            port: peer.port,
+            cport: peer.cport,
+            myself: false,
+            handshake: peer.handshake,
+            failing: peer.is_failing(now),
+            pong_received: peer.pong_received,
+            slots: table.ranges(Owner::Peer(i)),
+        });
+        std::iter::once(myself).chain(peers).collect()
+    }
+
+    /// Add the node at ip:port to the cluster, whose cluster bus listens on cport. It's known
+    /// once it answered the MEET sent to it, and then knows this node.
+    pub fn cluster_meet(&self, ip: &str, port: u16, cport: u16) -> Result<(), BackendError> {
+        if !self.is_cluster_enabled() {
+            return Err(BackendError::ClusterDisabled);
+        }
+        if ip.parse::<IpAddr>().is_err() {
+            return Err(BackendError::InvalidNodeAddress(format!("{}:{}", ip, port)));
+        }
+        let mut table = self.cluster.slots.write();
+        let met = table
+            .peers
+            .iter()
+            .any(|peer| peer.ip == ip && peer.cport == cport);
+        if !met {
+            table.peers.push(Peer::handshake(ip, port, cport));
+        }
+        Ok(())
+    }
+
+    /// Forget a node, which isn't learned again from the gossip of the others for a minute,
+    /// so CLUSTER FORGET has time to be sent to each of them.
+    pub fn cluster_forget(&self, id: &str) -> Result<(), BackendError> {
+        if !self.is_cluster_enabled() {
+            return Err(BackendError::ClusterDisabled);
+        }
+        if id == self.cluster.node_id {
+            return Err(BackendError::ForgetMyself);
+        }
+        let mut table = self.cluster.slots.write();
+        let peer = table.peer(id)?;
+        table.remove_peer(peer);
+        let until = now_ms() + CLUSTER_FORGET_BAN.as_millis() as i64;
+        self.cluster.banned.lock().insert(id.to_string(), until);
+        Ok(())
+    }
+
+    fn is_banned(&self, id: &str) -> bool {
+        let now = now_ms();
+        let mut banned = self.cluster.banned.lock();
+        banned.retain(|_, until| *until > now);
+        banned.contains_key(id)
+    }
+
+    /// The cluster bus addresses of the other nodes, `(ip, cport)`, each pinged over a link.
+    pub(super) fn cluster_links(&self) -> Vec<(String, u16)> {
+        let table = self.cluster.slots.read();
+        table
+            .peers
+            .iter()
+            .map(|peer| (peer.ip.clone(), peer.cport))
+            .collect()
+    }
+
+    /// The message pinging the node at a cluster bus address, a MEET until it answered, or
+    /// None once it's forgotten.
+    pub(super) fn cluster_ping(&self, ip: &str, cport: u16) -> Option<BusMessage> {
+        let table = self.cluster.slots.read();
+        let peer = table
+            .peers
+            .iter()
+            .find(|peer| peer.ip == ip && peer.cport == cport)?;
+        let kind = match peer.handshake {
+            true => BusMessageKind::Meet,
+            false => BusMessageKind::Ping,
+        };
+        Some(self.cluster_message(&table, kind))
+    }
+
+    /// The message answering a ping.
+    pub(super) fn cluster_pong(&self) -> BusMessage {
+        self.cluster_message(&self.cluster.slots.read(), BusMessageKind::Pong)
+    }
+
+    // this node with the slots it serves, and the nodes it knows the ID of
+    fn cluster_message(&self, table: &SlotTable, kind: BusMessageKind) -> BusMessage {
+        let (port, cport) = self.node_ports();
+        let gossip = table
+            .peers
+            .iter()
+            .filter(|peer| !peer.handshake)
+            .map(|peer| Gossip {
+                id: peer.id.clone(),
+                ip: peer.ip.clone(),
+                port: peer.port,
+                cport: peer.cport,
+            })
+            .collect();
+        BusMessage {
+            kind,
+            id: self.node_id(),
+            port,
+            cport,
+            slots: table.ranges(Owner::Myself),
+            gossip,
+        }
+    }
+
+    /// Update the node table with a message received over the cluster bus from ip: the PONG
+    /// of the node pinged at `link`, or a message it sent. A node met is added, as are the
+    /// nodes it knows which this one doesn't. A node known claims the slots it serves, those
+    /// this node serves aside.
+    pub(super) fn cluster_receive(&self, ip: &str, message: BusMessage, link: Option<(&str, u16)>) {
+        if message.id == self.cluster.node_id {
+            return;
+        }
+        let banned = self.is_banned(&message.id);
+        let mut table = self.cluster.slots.write();
+        let known = table.peers.iter().position(|peer| peer.id == message.id);
+        let sender = match link {
+            Some((ip, cport)) => {
+                let Some(pinged) = table
+                    .peers
+                    .iter()
+                    .position(|peer| peer.ip == ip && peer.cport == cport)
+                else {
+                    return;
+                };
+                match known {
+                    // met again under another address, or already learned from the gossip
+                    Some(known) if known != pinged => {
+                        table.remove_peer(pinged);
+                        return;
+                    }
+                    _ => pinged,
+                }
+            }
+            None => match known {
+                Some(known) => known,
+                None if message.kind == BusMessageKind::Meet && !banned => {
+                    let met = table.peers.iter().position(|peer| {
+                        peer.handshake && peer.ip == ip && peer.cport == message.cport
+                    });
+                    match met {
+                        Some(met) => met,
+                        None => {
+                            let peer =
+                                Peer::new(message.id.clone(), ip, message.port, message.cport);
+                            table.peers.push(peer);
+                            table.peers.len() - 1
+                        }
+                    }
+                }
+                None => return,
+            },
+        };
+        let peer = &mut table.peers[sender];
+        peer.id = message.id;
+        peer.port = message.port;
+        if link.is_some() {
+            peer.handshake = false;
+            peer.pong_received = now_ms();
+        }
+        if !peer.handshake {
+            table.assign(sender, &message.slots);
+        }
+        for gossip in message.gossip {
+            let known = gossip.id == self.cluster.node_id
+                || table.peers.iter().any(|peer| {
+                    peer.id == gossip.id || peer.ip == gossip.ip && peer.cport == gossip.cport
+                });
+            if !known && !self.is_banned(&gossip.id) {
+                let peer = Peer::new(gossip.id, &gossip.ip, gossip.port, gossip.cport);
+                table.peers.push(peer);
+            }
+        }
+    }
+
+    /// The slots moving to another node, and those moving from one.
+    pub fn cluster_migrations(&self) -> SlotMigrations {
+        let table = self.cluster.slots.read();
+        let ids = |slots: &BTreeMap<u16, usize>| {
+            slots
+                .iter()
+                .map(|(&slot, &peer)| (slot, table.peers[peer].id.clone()))
+                .collect()
+        };
+        SlotMigrations {
+            migrating: ids(&table.migrating),
+            importing: ids(&table.importing),
+        }
+    }
+
+    /// Change the node serving a slot, or mark it as moving from or to one.
+    pub fn cluster_setslot(&self, slot: u16, state: SlotState) -> Result<(), BackendError> {
+        if !self.is_cluster_enabled() {
+            return Err(BackendError::ClusterDisabled);
+        }
+        let mut table = self.cluster.slots.write();
+        let owner = table.owners[slot as usize];
+        match state {
+            SlotState::Migrating(_) if owner != Owner::Myself => {
+                Err(BackendError::NotSlotOwner(slot))
+            }
+            SlotState::Migrating(id) => {
+                let peer = table.peer(&id)?;
+                table.migrating.insert(slot, peer);
+                Ok(())
+            }
+            SlotState::Importing(_) if owner == Owner::Myself => {
+                Err(BackendError::AlreadySlotOwner(slot))
+            }
+            SlotState::Importing(id) => {
+                let peer = table.peer(&id)?;
+                table.importing.insert(slot, peer);
+                Ok(())
+            }
+            SlotState::Node(id) => {
+                let owner = match id == self.cluster.node_id {
+                    true => Owner::Myself,
+                    false => Owner::Peer(table.peer(&id)?),
+                };
+                table.owners[slot as usize] = owner;
+                table.migrating.remove(&slot);
+                table.importing.remove(&slot);
+                Ok(())
+            }
+            SlotState::Stable => {
+                table.migrating.remove(&slot);
+                table.importing.remove(&slot);
+                Ok(())
+            }
+        }
+    }
+
+    /// Let the next command of the client run on a slot being imported, as ASKING does.
+    pub fn cluster_asking(&self, client: u64) {
+        self.cluster.asking.lock().insert(client);
+    }
+
+    /// Forget the ASKING of a disconnected client.
+    pub fn cluster_disconnect(&self, client: u64) {
+        self.cluster.asking.lock().remove(&client);
+    }
+
+    /// Check whether this node serves the keys of a command of the client, in cluster mode:
+    /// they must hash to a single slot, served here unless it's being imported and the client
+    /// sent ASKING. The keys of a slot moving to another node which aren't here any longer
+    /// are asked to it.
+    pub fn cluster_check(&self, client: u64, keys: &[String]) -> Result<(), BackendError> {
+        if !self.is_cluster_enabled() {
+            return Ok(());
+        }
+        let asking = self.cluster.asking.lock().remove(&client);
+        let mut slots = keys.iter().map(|key| key_hash_slot(key.as_bytes()));
+        let Some(slot) = slots.next() else {
+            return Ok(());
+        };
+        if slots.any(|other| other != slot) {
+            return Err(BackendError::CrossSlot);
+        }
+        let table = self.cluster.slots.read();
+        match table.owners[slot as usize] {
+            Owner::Myself => match table.migrating.get(&slot) {
+                Some(&peer) if keys.iter().any(|key| !self.contains_key(key)) => {
+                    Err(BackendError::Ask(slot, table.address(peer)))
+                }
+                _ => Ok(()),
+            },
+            _ if asking && table.importing.contains_key(&slot) => Ok(()),
+            Owner::Peer(peer) => Err(BackendError::Moved(slot, table.address(peer))),
+            Owner::Unassigned => Err(BackendError::ClusterDown),
+        }
+    }
+}
+
+impl FromStr for SlotRanges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let slot = |s: &str| match s.parse::<u16>() {
+            Ok(slot) if (slot as usize) < CLUSTER_SLOTS => Ok(slot),
+            _ => Err(format!("invalid slot '{}'", s)),
+        };
+        let ranges = s
+            .split(',')
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (slot(start)?, slot(end)?),
+                    None => (slot(range)?, slot(range)?),
+                };
+                match start <= end {
+                    true => Ok((start, end)),
+                    false => Err(format!("invalid slot range '{}'", range)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SlotRanges(ranges))
+    }
+}
+
+impl fmt::Display for SlotRanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges: Vec<String> = self
+            .0
+            .iter()
+            .map(|&(start, end)| match start == end {
+                true => start.to_string(),
+                false => format!("{}-{}", start, end),
+            })
+            .collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+impl FromStr for ClusterPeers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let peers = s
+            .split_whitespace()
+            .map(|peer| {
+                let (address, slots) = peer.split_once('=').unwrap_or((peer, ""));
+                let (address, cport) = match address.split_once('@') {
+                    Some((address, cport)) => (address, Some(cport)),
+                    None => (address, None),
+                };
+                let invalid = || format!("invalid address '{}'", address);
+                let (ip, port) = address
+                    .rsplit_once(':')
+                    .and_then(|(ip, port)| Some((ip, port.parse::<u16>().ok()?)))
+                    .ok_or_else(invalid)?;
+                let cport = match cport {
+                    Some(cport) => cport.parse().map_err(|_| invalid())?,
+                    None => port.wrapping_add(CLUSTER_PORT_INCR),
+                };
+                Ok(ClusterPeer {
+                    ip: ip.to_string(),
+                    port,
+                    cport,
+                    slots: slots.parse()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(ClusterPeers(peers))
+    }
+}
+
+impl fmt::Display for ClusterPeers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let peers: Vec<String> = self
+            .0
+            .iter()
+            .map(|peer| {
+                let mut address = format!("{}:{}", peer.ip, peer.port);
+                if peer.cport != peer.port.wrapping_add(CLUSTER_PORT_INCR) {
+                    address.push_str(&format!("@{}", peer.cport));
+                }
+                match peer.slots.0.is_empty() {
+                    true => address,
+                    false => format!("{}={}", address, peer.slots),
+                }
+            })
+            .collect();
+        write!(f, "{}", peers.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    fn cluster() -> Result<Backend, BackendError> {
+        let backend = Backend::new();
+        backend.config_load(vec![
+            ("cluster-enabled".to_string(), "yes".to_string()),
+            ("cluster-slots".to_string(), "0-8191".to_string()),
+            (
+                "cluster-peers".to_string(),
+                "127.0.0.1:7001=8192-16383".to_string(),
+            ),
+        ])?;
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_node() -> Result<(), BackendError> {
+        let backend = Backend::new();
+        let id = backend.node_id();
+        assert_eq!(id.len(), 40);
+        assert!(id.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(backend.node_id(), id);
+        assert_ne!(Backend::new().node_id(), id);
+
+        assert_eq!(backend.node_address(), ":6379@16379");
+        backend.config_load(vec![
+            ("bind".to_string(), "127.0.0.1".to_string()),
+            ("port".to_string(), "7000".to_string()),
+        ])?;
+        assert_eq!(backend.node_address(), "127.0.0.1:7000@17000");
+        Ok(())
+    }
+
+    #[test]
+    fn test_slot_config() -> Result<(), BackendError> {
+        let backend = cluster()?;
+        assert!(backend.is_cluster_enabled());
+        assert_eq!(backend.cluster_slots().to_string(), "0-8191");
+        assert_eq!(
+            backend.cluster_peers().to_string(),
+            "127.0.0.1:7001=8192-16383"
+        );
+        let nodes = backend.cluster_nodes();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes[0].myself);
+        assert_eq!(nodes[1].slots, SlotRanges(vec![(8192, 16383)]));
+
+        // the slots of this node win over those of a peer
+        backend.set_cluster_slots(SlotRanges(vec![(0, 8192), (16383, 16383)]));
+        assert_eq!(
+            backend.cluster_peers().to_string(),
+            "127.0.0.1:7001=8193-16382"
+        );
+        let peers: ClusterPeers = "10.0.0.1:7001@7011=1-2 10.0.0.2:7002".parse().unwrap();
+        assert_eq!(peers.0[0].cport, 7011);
+        assert_eq!(peers.0[1].cport, 17002);
+        assert_eq!(peers.to_string(), "10.0.0.1:7001@7011=1-2 10.0.0.2:7002");
+        assert!("10.0.0.1:7001@x".parse::<ClusterPeers>().is_err());
+        assert!(matches!(
+            backend.config_set(vec![("cluster-slots".to_string(), "16384".to_string())]),
+            Err(BackendError::InvalidConfig(..))
+        ));
+        assert!(matches!(
+            backend.config_set(vec![("cluster-enabled".to_string(), "no".to_string())]),
+            Err(BackendError::InvalidConfig(..))
+        ));
+        Ok(())
+    }
+
+    // the PONG of the node at 127.0.0.1:7001, serving slots, which knows another node
+    fn pong(id: &str, slots: SlotRanges, gossip: Vec<Gossip>) -> BusMessage {
+        BusMessage {
+            kind: BusMessageKind::Pong,
+            id: id.to_string(),
+            port: 7001,
+            cport: 17001,
+            slots,
+            gossip,
+        }
+    }
+
+    #[test]
+    fn test_cluster_receive() -> Result<(), BackendError> {
+        let backend = cluster()?;
+        assert_eq!(
+            backend.cluster_links(),
+            vec![("127.0.0.1".to_string(), 17001)]
+        );
+        let ping = backend.cluster_ping("127.0.0.1", 17001);
+        assert_eq!(ping.map(|ping| ping.kind), Some(BusMessageKind::Meet));
+        assert!(backend.cluster_ping("127.0.0.1", 17002).is_none());
+
+        // the PONG tells the ID of the node, the slots it serves and a node it knows
+        let id = "a".repeat(40);
+        let other = Gossip {
+            id: "b".repeat(40),
+            ip: "127.0.0.1".to_string(),
+            port: 7002,
+            cport: 17002,
+        };
+        let slots = SlotRanges(vec![(0, 0), (8192, 12287)]);
+        let message = pong(&id, slots.clone(), vec![other.clone()]);
+        backend.cluster_receive("127.0.0.1", message, Some(("127.0.0.1", 17001)));
+        let nodes = backend.cluster_nodes();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[1].id, id);
+        assert!(!nodes[1].handshake && !nodes[1].failing && nodes[1].pong_received > 0);
+        // the slots this node serves stay, those not claimed any longer are unassigned
+        assert_eq!(nodes[1].slots, SlotRanges(vec![(8192, 16383)]));
+        assert_eq!(nodes[2].id, other.id);
+        let ping = backend.cluster_ping("127.0.0.1", 17001);
+        assert_eq!(ping.map(|ping| ping.gossip.len()), Some(2));
+
+        // a node met by another, then forgotten and not learned again
+        let mut meet = pong(&"c".repeat(40), SlotRanges::default(), vec![]);
+        meet.kind = BusMessageKind::Meet;
+        meet.cport = 17003;
+        backend.cluster_receive("10.0.0.1", meet, None);
+        assert_eq!(backend.cluster_nodes()[3].ip, "10.0.0.1");
+        backend.cluster_forget(&other.id)?;
+        let message = pong(&id, slots, vec![other.clone()]);
+        backend.cluster_receive("127.0.0.1", message, Some(("127.0.0.1", 17001)));
+        assert_eq!(backend.cluster_nodes().len(), 3);
+        assert_eq!(
+            backend.cluster_forget(&backend.node_id()),
+            Err(BackendError::ForgetMyself)
+        );
+        assert_eq!(
+            backend.cluster_forget(&other.id),
+            Err(BackendError::UnknownNode(other.id))
+        );
+
+        // the PING of an unknown node is ignored
+        let mut ping = pong(&"d".repeat(40), SlotRanges(vec![(1, 1)]), vec![]);
+        ping.kind = BusMessageKind::Ping;
+        backend.cluster_receive("10.0.0.2", ping, None);
+        assert_eq!(backend.cluster_nodes().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_check() -> Result<(), BackendError> {
+        let backend = cluster()?;
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        // foo is in 12182, bar in 5061
+        assert_eq!(backend.cluster_check(1, &keys(&["bar"])), Ok(()));
+        assert_eq!(backend.cluster_check(1, &keys(&[])), Ok(()));
+        assert_eq!(
+            backend.cluster_check(1, &keys(&["foo"])),
+            Err(BackendError::Moved(12182, "127.0.0.1:7001".to_string()))
+        );
+        assert_eq!(
+            backend.cluster_check(1, &keys(&["foo", "bar"])),
+            Err(BackendError::CrossSlot)
+        );
+        assert_eq!(
+            backend.cluster_check(1, &keys(&["{bar}1", "{bar}2"])),
+            Ok(())
+        );
+
+        // moving bar's slot to the peer, the missing keys are asked to it
+        let peer = backend.cluster_nodes()[1].id.clone();
+        backend.cluster_setslot(5061, SlotState::Migrating(peer.clone()))?;
+        assert_eq!(
+            backend.cluster_check(1, &keys(&["bar"])),
+            Err(BackendError::Ask(5061, "127.0.0.1:7001".to_string()))
+        );
+        backend.set("bar".to_string(), BulkString::from("1").into());
+        assert_eq!(backend.cluster_check(1, &keys(&["bar"])), Ok(()));
+        backend.cluster_setslot(5061, SlotState::Node(peer.clone()))?;
+        assert!(matches!(
+            backend.cluster_check(1, &keys(&["bar"])),
+            Err(BackendError::Moved(5061, _))
+        ));
+
+        // importing foo's slot, served after ASKING only
+        backend.cluster_setslot(12182, SlotState::Importing(peer))?;
+        assert_eq!(backend.cluster_migrations().importing.len(), 1);
+        backend.cluster_asking(1);
+        assert_eq!(backend.cluster_check(1, &keys(&["foo"])), Ok(()));
+        assert!(backend.cluster_check(1, &keys(&["foo"])).is_err());
+
+        assert_eq!(
+            backend.cluster_setslot(0, SlotState::Importing("x".repeat(40))),
+            Err(BackendError::AlreadySlotOwner(0))
+        );
+        assert_eq!(
+            backend.cluster_setslot(5061, SlotState::Migrating("x".repeat(40))),
+            Err(BackendError::NotSlotOwner(5061))
+        );
+        assert_eq!(
+            backend.cluster_setslot(12182, SlotState::Node("x".repeat(40))),
+            Err(BackendError::UnknownNode("x".repeat(40)))
+        );
+
+        // a standalone server serves any key
+        let backend = Backend::new();
+        assert_eq!(backend.cluster_check(1, &keys(&["foo", "bar"])), Ok(()));
+        assert_eq!(
+            backend.cluster_setslot(0, SlotState::Stable),
+            Err(BackendError::ClusterDisabled)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::ops::Deref;
+
+// the longest string kept inline, so a CompactString is no larger than a String
+const INLINE_CAPACITY: usize = size_of::<String>() - 2;
+
+/// An immutable string in as little memory as possible, for the keys of the keyspace: those
+/// up to 22 bytes, most of them, are kept inline without any allocation, longer ones in an
+/// allocation of their exact length, without the spare capacity of a String.
+#[derive(Clone)]
+pub struct CompactString(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline(u8, [u8; INLINE_CAPACITY]),
+    Heap(Box<str>),
+}
+
+impl CompactString {
+    pub fn new(s: &str) -> Self {
+        match s.len() <= INLINE_CAPACITY {
+            true => {
+                let mut data = [0; INLINE_CAPACITY];
+                data[..s.len()].copy_from_slice(s.as_bytes());
+                CompactString(Repr::Inline(s.len() as u8, data))
+            }
+            false => CompactString(Repr::Heap(s.into())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // copied from a str, and cut where it ended
+            Repr::Inline(len, data) => std::str::from_utf8(&data[..*len as usize])
+                .expect("an inline string is valid UTF-8"),
+            Repr::Heap(s) => s,
+        }
+    }
+
+    /// The bytes allocated for the string, 0 if it's inline.
+    pub fn heap_size(&self) -> usize {
+        Self::heap_size_of(self)
+    }
+
+    /// The bytes allocated for s once it's made a CompactString.
+    pub fn heap_size_of(s: &str) -> usize {
+        match s.len() <= INLINE_CAPACITY {
+            true => 0,
+            false => s.len(),
+        }
+    }
+}
+
+impl Deref for CompactString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for CompactString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for CompactString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// hashed and compared as the str, so maps keyed by them are looked up by &str
+impl Hash for CompactString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialEq for CompactString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for CompactString {}
+
+impl PartialOrd for CompactString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CompactString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl From<&str> for CompactString {
+    fn from(s: &str) -> Self {
+        CompactString::new(s)
+    }
+}
+
+impl From<String> for CompactString {
+    fn from(s: String) -> Self {
+        match s.len() <= INLINE_CAPACITY {
+            true => CompactString::new(&s),
+            // without a copy, unless it has spare capacity
+            false => CompactString(Repr::Heap(s.into_boxed_str())),
+        }
+    }
+}
+
+impl fmt::Debug for CompactString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for CompactString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compact_string() {
+        assert_eq!(size_of::<CompactString>(), size_of::<String>());
+
+        let short = CompactString::from("user:1000");
+        let exact = CompactString::from("a".repeat(INLINE_CAPACITY));
+        let long = CompactString::from("session:".repeat(4));
+        assert_eq!(&*short, "user:1000");
+        assert_eq!(exact.len(), INLINE_CAPACITY);
+        assert_eq!((short.heap_size(), exact.heap_size()), (0, 0));
+        assert_eq!(long.as_str(), "session:".repeat(4));
+        assert_eq!(long.heap_size(), 32);
+        assert_eq!(CompactString::from("é".repeat(11)).heap_size(), 0);
+
+        // looked up by str
+        let mut map = HashMap::new();
+        map.insert(short.clone(), 1);
+        map.insert(long, 2);
+        assert_eq!(map.get("user:1000"), Some(&1));
+        assert_eq!(map.get("session:".repeat(4).as_str()), Some(&2));
+        assert_eq!(map.get(""), None);
+        assert!(short < CompactString::from("user:2"));
+    }
+}
@@ -0,0 +1,914 @@
+use crate::systemd::Supervised;
+use crate::{
+    glob_match, AppendFsync, Backend, BackendError, CidrList, ClusterPeers, LogLevel,
+    MaxMemoryClients, NotifyFlags, SaveRules, SlotRanges, TlsAuthClients, TtlPrefixes, MAX_HZ,
+    MAX_WORKER_THREADS, MIN_HZ,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use tracing::warn;
+
+pub const DEFAULT_BIND: &str = "0.0.0.0";
+pub const DEFAULT_PORT: u16 = 6379;
+
+/// The addresses the server listens on, one accept loop each, as `127.0.0.1 ::1` in the
+/// config: `*` for every IPv4 address and `::*` for every IPv6 one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindAddrs(pub Vec<IpAddr>);
+
+// the parameters CONFIG GET and CONFIG SET know
+const PARAMETERS: &[&str] = &[
+    "bind",
+    "port",
+    "logfile",
+    "loglevel",
+    "requirepass",
+    "aclfile",
+    "supervised",
+    "tls-port",
+    "tls-cert-file",
+    "tls-key-file",
+    "tls-ca-cert-file",
+    "tls-auth-clients",
+    "save",
+    "dir",
+    "dbfilename",
+    "appendonly",
+    "appendfilename",
+    "appendfsync",
+    "aof-use-rdb-preamble",
+    "auto-aof-rewrite-percentage",
+    "auto-aof-rewrite-min-size",
+    "repl-backlog-size",
+    "maxmemory",
+    "maxmemory-clients",
+    "timeout",
+    "max-connections-per-ip",
+    "max-connection-rate-per-ip",
+    "allow-clients",
+    "deny-clients",
+    "hz",
+    "active-expire-effort",
+    "default-ttl",
+    "default-ttl-prefixes",
+    "latency-monitor-threshold",
+    "busy-script-time-limit",
+    "worker-threads",
+    "notify-keyspace-events",
+    "list-max-listpack-size",
+    "set-max-intset-entries",
+    "hash-max-listpack-entries",
+    "hash-max-listpack-value",
+    "cluster-enabled",
+    "cluster-slots",
+    "cluster-peers",
+];
+
+// the parameters only set at startup
+const IMMUTABLE: &[&str] = &[
+    "bind",
+    "port",
+    "logfile",
+    "aclfile",
+    "supervised",
+    "tls-port",
+    "tls-cert-file",
+    "tls-key-file",
+    "tls-ca-cert-file",
+    "tls-auth-clients",
+    "appendonly",
+    "appendfilename",
+    "appendfsync",
+    "worker-threads",
+    "cluster-enabled",
+];
+
+// a parameter with its new value, checked
+enum Setting {
+    Bind(BindAddrs),
+    Port(u16),
+    LogFile(String),
+    LogLevel(LogLevel),
+    RequirePass(String),
+    AclFile(String),
+    Supervised(Supervised),
+    TlsPort(u16),
+    TlsCertFile(String),
+    TlsKeyFile(String),
+    TlsCaCertFile(String),
+    TlsAuthClients(TlsAuthClients),
+    Save(SaveRules),
+    Dir(PathBuf),
+    DbFilename(String),
+    AppendOnly(bool),
+    AppendFilename(String),
+    AppendFsync(AppendFsync),
+    AofUseRdbPreamble(bool),
+    AutoAofRewritePercentage(u64),
+    AutoAofRewriteMinSize(u64),
+    ReplBacklogSize(usize),
+    MaxMemory(u64),
+    MaxMemoryClients(MaxMemoryClients),
+    Timeout(u64),
+    MaxConnectionsPerIp(u64),
+    MaxConnectionRatePerIp(u64),
+    AllowClients(CidrList),
+    DenyClients(CidrList),
+    Hz(u64),
+    ActiveExpireEffort(u64),
+    DefaultTtl(u64),
+    DefaultTtlPrefixes(TtlPrefixes),
+    LatencyMonitorThreshold(u64),
+    BusyScriptTimeLimit(u64),
+    WorkerThreads(usize),
+    NotifyKeyspaceEvents(NotifyFlags),
+    ListMaxListpackSize(i64),
+    SetMaxIntsetEntries(usize),
+    HashMaxListpackEntries(usize),
+    HashMaxListpackValue(usize),
+    ClusterEnabled(bool),
+    ClusterSlots(SlotRanges),
+    ClusterPeers(ClusterPeers),
+}
+
+impl Backend {
+    /// The parameters matching any of patterns, with their values.
+    pub fn config_get(&self, patterns: &[String]) -> Vec<(String, String)> {
+        PARAMETERS
+            .iter()
+            .filter(|name| {
+                patterns.iter().any(|pattern| {
+                    glob_match(pattern.to_ascii_lowercase().as_bytes(), name.as_bytes())
+                })
+            })
+            .map(|name| (name.to_string(), self.config_value(name)))
+            .collect()
+    }
+
+    /// Set the parameters, all or none of them: every value is checked before any is set.
+    pub fn config_set(&self, pairs: Vec<(String, String)>) -> Result<(), BackendError> {
+        self.apply_settings(pairs, false)
+    }
+
+    /// Set the parameters the server starts with, from its config file then its command line,
+    /// those only set at startup included, and apply the rename-command directives, and the
+    /// sentinel ones in sentinel mode. The directives of unknown parameters are ignored.
+    pub fn config_load(&self, pairs: Vec<(String, String)>) -> Result<(), BackendError> {
+        let (renames, pairs): (Vec<_>, Vec<_>) = pairs
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .partition(|(name, _)| name == "rename-command");
+        let (sentinel, pairs): (Vec<_>, Vec<_>) =
+            pairs.into_iter().partition(|(name, _)| name == "sentinel");
+        let renames = renames
+            .into_iter()
+            .map(|(name, value)| match value.split_once(' ') {
+                Some((command, new_name)) if !command.is_empty() && !new_name.contains(' ') => {
+                    Ok((command.to_ascii_lowercase(), new_name.to_ascii_lowercase()))
+                }
+                _ => Err(BackendError::InvalidConfig(
+                    name,
+                    "wrong number of arguments".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let pairs = pairs
+            .into_iter()
+            .filter(|(name, _)| match PARAMETERS.contains(&name.as_str()) {
+                true => true,
+                false => {
+                    warn!("ignoring the unsupported config directive {}", name);
+                    false
+                }
+            })
+            .collect();
+        self.apply_settings(pairs, true)?;
+        self.command_renames.write().extend(renames);
+        for (_, value) in sentinel {
+            self.sentinel_directive(&value)?;
+        }
+        Ok(())
+    }
+
+    /// The name of the command called as called, given rename-command: its own if it wasn't
+    /// renamed, None if it's called by a name it no longer has or it was disabled.
+    pub fn resolve_command_name(&self, called: &str) -> Option<String> {
+        let renames = self.command_renames.read();
+        let renamed = renames
+            .iter()
+            .find(|(_, new_name)| !new_name.is_empty() && *new_name == called);
+        match renamed {
+            Some((name, _)) => Some(name.clone()),
+            None if renames.contains_key(called) => None,
+            None => Some(called.to_string()),
+        }
+    }
+
+    /// Whether rename-command renamed or disabled any command.
+    pub fn has_renamed_commands(&self) -> bool {
+        !self.command_renames.read().is_empty()
+    }
+
+    fn apply_settings(
+        &self,
+        pairs: Vec<(String, String)>,
+        startup: bool,
+    ) -> Result<(), BackendError> {
+        let settings = pairs
+            .into_iter()
+            .map(|(name, value)| parse_setting(&name.to_ascii_lowercase(), &value, startup))
+            .collect::<Result<Vec<_>, _>>()?;
+        for setting in settings {
+            match setting {
+                Setting::Bind(bind) => *self.bind.write() = bind,
+                Setting::Port(port) => self.port.store(port, Ordering::Relaxed),
+                Setting::LogFile(logfile) => *self.logfile.write() = logfile,
+                Setting::LogLevel(level) => self.set_loglevel(level),
+                Setting::RequirePass(password) => self.set_requirepass(password),
+                Setting::AclFile(path) => self.set_aclfile(path),
+                Setting::Supervised(supervised) => *self.supervised.write() = supervised,
+                Setting::TlsPort(port) => self.tls.port.store(port, Ordering::Relaxed),
+                Setting::TlsCertFile(path) => *self.tls.cert_file.write() = path,
+                Setting::TlsKeyFile(path) => *self.tls.key_file.write() = path,
+                Setting::TlsCaCertFile(path) => *self.tls.ca_cert_file.write() = path,
+                Setting::TlsAuthClients(auth) => *self.tls.auth_clients.write() = auth,
+                Setting::Save(rules) => self.set_save_rules(rules),
+                Setting::Dir(dir) => self.set_dir(dir),
+                Setting::DbFilename(dbfilename) => self.set_dbfilename(dbfilename),
+                Setting::AppendOnly(appendonly) => self.set_appendonly(appendonly),
+                Setting::AppendFilename(appendfilename) => self.set_appendfilename(appendfilename),
+                Setting::AppendFsync(fsync) => self.set_appendfsync(fsync),
+                Setting::AofUseRdbPreamble(preamble) => self.set_aof_use_rdb_preamble(preamble),
+                Setting::AutoAofRewritePercentage(percentage) => {
+                    self.set_auto_aof_rewrite(percentage, self.auto_aof_rewrite().1)
+                }
+                Setting::AutoAofRewriteMinSize(min_size) => {
+                    self.set_auto_aof_rewrite(self.auto_aof_rewrite().0, min_size)
+                }
+                Setting::ReplBacklogSize(size) => self.set_repl_backlog_size(size),
+                Setting::MaxMemory(bytes) => self.set_maxmemory(bytes),
+                Setting::MaxMemoryClients(limit) => self.set_maxmemory_clients(limit),
+                Setting::Timeout(seconds) => self.set_timeout(seconds),
+                Setting::MaxConnectionsPerIp(max) => self.set_max_connections_per_ip(max),
+                Setting::MaxConnectionRatePerIp(max) => self.set_max_connection_rate_per_ip(max),
+                Setting::AllowClients(allow) => self.set_allow_clients(allow),
+                Setting::DenyClients(deny) => self.set_deny_clients(deny),
+                Setting::Hz(hz) => self.set_hz(hz),
+                Setting::ActiveExpireEffort(effort) => self.set_active_expire_effort(effort),
+                Setting::DefaultTtl(seconds) => self.set_default_ttl(seconds),
+                Setting::DefaultTtlPrefixes(prefixes) => self.set_default_ttl_prefixes(prefixes),
+                Setting::LatencyMonitorThreshold(ms) => self.set_latency_monitor_threshold(ms),
+                Setting::BusyScriptTimeLimit(ms) => self.set_busy_script_time_limit(ms),
+                Setting::WorkerThreads(threads) => self.set_worker_threads(threads),
+                Setting::NotifyKeyspaceEvents(flags) => self.set_notify_keyspace_events(flags),
+                Setting::ListMaxListpackSize(fill) => self.set_list_max_listpack_size(fill),
+                Setting::SetMaxIntsetEntries(entries) => self.set_set_max_intset_entries(entries),
+                Setting::HashMaxListpackEntries(entries) => {
+                    self.set_hash_max_listpack_entries(entries)
+                }
+                Setting::HashMaxListpackValue(value) => self.set_hash_max_listpack_value(value),
+                Setting::ClusterEnabled(enabled) => self.set_cluster_enabled(enabled),
+                Setting::ClusterSlots(slots) => self.set_cluster_slots(slots),
+                Setting::ClusterPeers(peers) => self.set_cluster_peers(peers),
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the config file with the values the parameters have now. Its comments and the
+    /// lines it has for other parameters are kept, the parameters it doesn't have are added
+    /// unless they have their default value.
+    pub fn config_rewrite(&self) -> Result<(), BackendError> {
+        let path = self.config_file().ok_or(BackendError::NoConfigFile)?;
+        let rewrite = || -> io::Result<()> {
+            let config = match fs::read_to_string(&path) {
+                Ok(config) => config,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e),
+            };
+            let config = rewrite_config(&config, |name| self.config_value(name));
+            // written aside then renamed, a crash leaves one file or the other
+            let temp = path.with_extension(format!("rewrite-{}", std::process::id()));
+            fs::write(&temp, config)?;
+            fs::rename(&temp, &path)
+        };
+        rewrite().map_err(|e| BackendError::RewriteConfigFailed(e.to_string()))
+    }
+
+    /// The file the server was started with, which CONFIG REWRITE writes.
+    pub fn config_file(&self) -> Option<PathBuf> {
+        self.config_file.read().clone()
+    }
+
+    pub fn set_config_file(&self, path: impl Into<PathBuf>) {
+        *self.config_file.write() = Some(path.into());
+    }
+
+    /// The addresses the server listens on.
+    pub fn bind(&self) -> BindAddrs {
+        self.bind.read().clone()
+    }
+
+    /// The port the server listens on, none if 0, e.g. to serve TLS connections only.
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    /// The file the server logs to, stdout if it's empty.
+    pub fn logfile(&self) -> String {
+        self.logfile.read().clone()
+    }
+
+    /// The password of the default user, none if it's empty.
+    pub fn requirepass(&self) -> String {
+        self.requirepass.read().clone()
+    }
+
+    pub fn set_requirepass(&self, password: impl Into<String>) {
+        let password = password.into();
+        self.set_default_password(&password);
+        *self.requirepass.write() = password;
+    }
+
+    /// Who supervises the server, set at startup.
+    pub fn supervised(&self) -> Supervised {
+        *self.supervised.read()
+    }
+
+    /// The memory limit in bytes, 0 for none. Nothing is evicted yet, it's only reported.
+    pub fn maxmemory(&self) -> u64 {
+        self.maxmemory.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maxmemory(&self, bytes: u64) {
+        self.maxmemory.store(bytes, Ordering::Relaxed);
+    }
+
+    /// How many seconds a client may stay idle before it's disconnected, 0 for ever.
+    /// Subscribers and replicas are never disconnected for it.
+    pub fn timeout(&self) -> u64 {
+        self.timeout.load(Ordering::Relaxed)
+    }
+
+    pub fn set_timeout(&self, seconds: u64) {
+        self.timeout.store(seconds, Ordering::Relaxed);
+    }
+
+    /// How many times per second the cron, and so the active expiration cycle, runs.
+    pub fn hz(&self) -> u64 {
+        self.hz.load(Ordering::Relaxed)
+    }
+
+    pub fn set_hz(&self, hz: u64) {
+        self.hz.store(hz, Ordering::Relaxed);
+    }
+
+    /// From 1 to 10, how much of the CPU the active expiration may use to leave fewer
+    /// expired keys in memory.
+    pub fn active_expire_effort(&self) -> u64 {
+        self.active_expire_effort.load(Ordering::Relaxed)
+    }
+
+    pub fn set_active_expire_effort(&self, effort: u64) {
+        self.active_expire_effort.store(effort, Ordering::Relaxed);
+    }
+
+    fn config_value(&self, name: &str) -> String {
+        let yes_no = |yes: bool| if yes { "yes" } else { "no" }.to_string();
+        match name {
+            "bind" => self.bind().to_string(),
+            "port" => self.port().to_string(),
+            "logfile" => self.logfile(),
+            "loglevel" => self.loglevel().to_string(),
+            "requirepass" => self.requirepass(),
+            "aclfile" => self.aclfile(),
+            "supervised" => self.supervised().to_string(),
+            "tls-port" => self.tls_port().to_string(),
+            "tls-cert-file" => self.tls_cert_file(),
+            "tls-key-file" => self.tls_key_file(),
+            "tls-ca-cert-file" => self.tls_ca_cert_file(),
+            "tls-auth-clients" => self.tls_auth_clients().to_string(),
+            "save" => self.save_rules().to_string(),
+            "dir" => self.dir().display().to_string(),
+            "dbfilename" => self.dbfilename(),
+            "appendonly" => yes_no(self.appendonly()),
+            "appendfilename" => self.appendfilename(),
+            "appendfsync" => self.appendfsync().to_string(),
+            "aof-use-rdb-preamble" => yes_no(self.aof_use_rdb_preamble()),
+            "auto-aof-rewrite-percentage" => self.auto_aof_rewrite().0.to_string(),
+            "auto-aof-rewrite-min-size" => self.auto_aof_rewrite().1.to_string(),
+            "repl-backlog-size" => self.repl_backlog_size().to_string(),
+            "maxmemory" => self.maxmemory().to_string(),
+            "maxmemory-clients" => self.maxmemory_clients().to_string(),
+            "timeout" => self.timeout().to_string(),
+            "max-connections-per-ip" => self.max_connections_per_ip().to_string(),
+            "max-connection-rate-per-ip" => self.max_connection_rate_per_ip().to_string(),
+            "allow-clients" => self.allow_clients().to_string(),
+            "deny-clients" => self.deny_clients().to_string(),
+            "hz" => self.hz().to_string(),
+            "active-expire-effort" => self.active_expire_effort().to_string(),
+            "default-ttl" => self.default_ttl().to_string(),
+            "default-ttl-prefixes" => self.default_ttl_prefixes().to_string(),
+            "latency-monitor-threshold" => self.latency_monitor_threshold().to_string(),
+            "busy-script-time-limit" => self.busy_script_time_limit().to_string(),
+            "worker-threads" => self.worker_threads().to_string(),
+            "notify-keyspace-events" => self.notify_keyspace_events().to_string(),
+            "list-max-listpack-size" => self.list_max_listpack_size().to_string(),
+            "set-max-intset-entries" => self.set_max_intset_entries().to_string(),
+            "hash-max-listpack-entries" => self.hash_max_listpack_entries().to_string(),
+            "hash-max-listpack-value" => self.hash_max_listpack_value().to_string(),
+            "cluster-enabled" => yes_no(self.is_cluster_enabled()),
+            "cluster-slots" => self.cluster_slots().to_string(),
+            "cluster-peers" => self.cluster_peers().to_string(),
+            _ => unreachable!("unknown parameter {}", name),
+        }
+    }
+}
+
+/// Parse an amount of memory, in bytes or with a unit: k, m and g are powers of 1000, kb, mb
+/// and gb of 1024, as in redis.conf.
+pub fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_ascii_lowercase();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Parse a config file in the format of redis.conf: a directive per line, its name then its
+/// arguments, which may be quoted. Comments and blank lines are skipped, the arguments of a
+/// directive are joined by spaces, and the rules of several save directives are merged.
+pub fn parse_config(config: &str) -> Result<Vec<(String, String)>, String> {
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for (i, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let args = split_args(line).ok_or_else(|| format!("line {}: unbalanced quotes", i + 1))?;
+        let Some((name, args)) = args.split_first() else {
+            continue;
+        };
+        let name = name.to_ascii_lowercase();
+        let value = args.join(" ");
+        match pairs.iter_mut().rev().find(|(other, _)| *other == "save") {
+            Some((_, rules)) if name == "save" && !value.is_empty() && !rules.is_empty() => {
+                rules.push(' ');
+                rules.push_str(&value);
+            }
+            _ => pairs.push((name, value)),
+        }
+    }
+    Ok(pairs)
+}
+
+// the arguments of a line, in double quotes with escapes or in single quotes for those with
+// spaces, None if a quote isn't closed
+fn split_args(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return Some(args);
+        };
+        let mut arg = String::new();
+        match first {
+            '"' => loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => arg.push(match chars.next()? {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        c => c,
+                    }),
+                    c => arg.push(c),
+                }
+            },
+            '\'' => loop {
+                match chars.next()? {
+                    '\'' => break,
+                    c => arg.push(c),
+                }
+            },
+            c => {
+                arg.push(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    arg.push(c);
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn parse_setting(name: &str, value: &str, startup: bool) -> Result<Setting, BackendError> {
+    let invalid = |reason: &str| BackendError::InvalidConfig(name.to_string(), reason.to_string());
+    let memory = || parse_memory(value).ok_or_else(|| invalid("argument must be a memory value"));
+    match name {
+        name if IMMUTABLE.contains(&name) && !startup => Err(invalid("can't set immutable config")),
+        "bind" => value
+            .parse()
+            .map(Setting::Bind)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "port" => integer(name, value).map(Setting::Port),
+        "logfile" => Ok(Setting::LogFile(value.to_string())),
+        "loglevel" => value
+            .parse()
+            .map(Setting::LogLevel)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "requirepass" => Ok(Setting::RequirePass(value.to_string())),
+        "aclfile" => Ok(Setting::AclFile(value.to_string())),
+        "supervised" => value
+            .parse()
+            .map(Setting::Supervised)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "tls-port" => integer(name, value).map(Setting::TlsPort),
+        "tls-cert-file" => Ok(Setting::TlsCertFile(value.to_string())),
+        "tls-key-file" => Ok(Setting::TlsKeyFile(value.to_string())),
+        "tls-ca-cert-file" => Ok(Setting::TlsCaCertFile(value.to_string())),
+        "tls-auth-clients" => value
+            .parse()
+            .map(Setting::TlsAuthClients)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "appendonly" => match value.to_ascii_lowercase().as_str() {
+            "yes" => Ok(Setting::AppendOnly(true)),
+            "no" => Ok(Setting::AppendOnly(false)),
+            _ => Err(invalid("argument must be 'yes' or 'no'")),
+        },
+        "appendfilename" => match value.is_empty() || value.contains(['/', '\\']) {
+            true => Err(invalid("appendfilename can't be a path, just a filename")),
+            false => Ok(Setting::AppendFilename(value.to_string())),
+        },
+        "appendfsync" => value
+            .parse()
+            .map(Setting::AppendFsync)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "aof-use-rdb-preamble" => match value.to_ascii_lowercase().as_str() {
+            "yes" => Ok(Setting::AofUseRdbPreamble(true)),
+            "no" => Ok(Setting::AofUseRdbPreamble(false)),
+            _ => Err(invalid("argument must be 'yes' or 'no'")),
+        },
+        "save" => value
+            .parse()
+            .map(Setting::Save)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "dir" => match Path::new(value).is_dir() {
+            true => Ok(Setting::Dir(PathBuf::from(value))),
+            false => Err(invalid("No such file or directory")),
+        },
+        "dbfilename" => match value.is_empty() || value.contains(['/', '\\']) {
+            true => Err(invalid("dbfilename can't be a path, just a filename")),
+            false => Ok(Setting::DbFilename(value.to_string())),
+        },
+        "auto-aof-rewrite-percentage" => {
+            integer(name, value).map(Setting::AutoAofRewritePercentage)
+        }
+        "auto-aof-rewrite-min-size" => memory().map(Setting::AutoAofRewriteMinSize),
+        "repl-backlog-size" => memory().map(|size| Setting::ReplBacklogSize(size as usize)),
+        "maxmemory" => memory().map(Setting::MaxMemory),
+        "maxmemory-clients" => value
+            .parse()
+            .map(Setting::MaxMemoryClients)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "timeout" => integer(name, value).map(Setting::Timeout),
+        "max-connections-per-ip" => integer(name, value).map(Setting::MaxConnectionsPerIp),
+        "max-connection-rate-per-ip" => integer(name, value).map(Setting::MaxConnectionRatePerIp),
+        "allow-clients" => value
+            .parse()
+            .map(Setting::AllowClients)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "deny-clients" => value
+            .parse()
+            .map(Setting::DenyClients)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        // like Redis, out of range values are brought back in range
+        "hz" => integer(name, value).map(|hz: u64| Setting::Hz(hz.clamp(MIN_HZ, MAX_HZ))),
+        "active-expire-effort" => match integer(name, value)? {
+            effort @ 1..=10 => Ok(Setting::ActiveExpireEffort(effort)),
+            _ => Err(invalid("argument must be between 1 and 10 inclusive")),
+        },
+        "default-ttl" => integer(name, value).map(Setting::DefaultTtl),
+        "default-ttl-prefixes" => value
+            .parse()
+            .map(Setting::DefaultTtlPrefixes)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "latency-monitor-threshold" => integer(name, value).map(Setting::LatencyMonitorThreshold),
+        "busy-script-time-limit" => integer(name, value).map(Setting::BusyScriptTimeLimit),
+        "worker-threads" => match integer(name, value)? {
+            threads @ 0..=MAX_WORKER_THREADS => Ok(Setting::WorkerThreads(threads)),
+            _ => Err(invalid(&format!(
+                "argument must be between 0 and {} inclusive",
+                MAX_WORKER_THREADS
+            ))),
+        },
+        "notify-keyspace-events" => value
+            .parse()
+            .map(Setting::NotifyKeyspaceEvents)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "list-max-listpack-size" => integer(name, value).map(Setting::ListMaxListpackSize),
+        "set-max-intset-entries" => integer(name, value).map(Setting::SetMaxIntsetEntries),
+        "hash-max-listpack-entries" => integer(name, value).map(Setting::HashMaxListpackEntries),
+        "hash-max-listpack-value" => integer(name, value).map(Setting::HashMaxListpackValue),
+        "cluster-enabled" => match value.to_ascii_lowercase().as_str() {
+            "yes" => Ok(Setting::ClusterEnabled(true)),
+            "no" => Ok(Setting::ClusterEnabled(false)),
+            _ => Err(invalid("argument must be 'yes' or 'no'")),
+        },
+        "cluster-slots" => value
+            .parse()
+            .map(Setting::ClusterSlots)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        "cluster-peers" => value
+            .parse()
+            .map(Setting::ClusterPeers)
+            .map_err(|e| BackendError::InvalidConfig(name.to_string(), e)),
+        _ => Err(BackendError::UnknownConfig(name.to_string())),
+    }
+}
+
+impl Default for BindAddrs {
+    fn default() -> Self {
+        BindAddrs(vec![DEFAULT_BIND.parse().unwrap()])
+    }
+}
+
+impl FromStr for BindAddrs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let addrs = s
+            .split_whitespace()
+            .map(|addr| match addr {
+                "*" => Ok(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                "::*" => Ok(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+                addr => addr
+                    .parse()
+                    .map_err(|_| format!("invalid address {}", addr)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if addrs.is_empty() {
+            return Err("bind needs an address".to_string());
+        }
+        Ok(BindAddrs(addrs))
+    }
+}
+
+impl fmt::Display for BindAddrs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let addrs: Vec<String> = self.0.iter().map(IpAddr::to_string).collect();
+        write!(f, "{}", addrs.join(" "))
+    }
+}
+
+fn integer<T: FromStr>(name: &str, value: &str) -> Result<T, BackendError> {
+    value.parse().map_err(|_| {
+        BackendError::InvalidConfig(
+            name.to_string(),
+            "argument couldn't be parsed into an integer".to_string(),
+        )
+    })
+}
+
+// the value of every parameter by default, as CONFIG GET has it
+fn default_value(name: &str) -> String {
+    Backend::new().config_value(name)
+}
+
+// the config with the line of every parameter set to its value, one line each, and the
+// parameters it doesn't have added unless they have their default value
+fn rewrite_config(config: &str, value: impl Fn(&str) -> String) -> String {
+    let directive = |name: &str| {
+        let value = value(name);
+        // save holds several rules, a value with spaces otherwise is a single argument
+        match value.is_empty() || (name != "save" && value.contains(char::is_whitespace)) {
+            true => format!("{} \"{}\"", name, value),
+            false => format!("{} {}", name, value),
+        }
+    };
+
+    let mut written = HashSet::new();
+    let mut lines = Vec::new();
+    for line in config.lines() {
+        let name = line.split_whitespace().next().map(str::to_ascii_lowercase);
+        match name.as_deref() {
+            Some(name) if PARAMETERS.contains(&name) => {
+                // the parameters given more than once get the line of the first
+                if written.insert(name.to_string()) {
+                    lines.push(directive(name));
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let mut generated = PARAMETERS
+        .iter()
+        .filter(|name| !written.contains(**name) && value(name) != default_value(name))
+        .peekable();
+    if generated.peek().is_some() {
+        lines.push("# Generated by CONFIG REWRITE".to_string());
+        lines.extend(generated.map(|name| directive(name)));
+    }
+    let mut config = lines.join("\n");
+    config.push('\n');
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SaveRule;
+
+    #[test]
+    fn test_config_get_and_set() {
+        let backend = Backend::new();
+        let pairs = vec![("SAVE".to_string(), "900 1 300 10".to_string())];
+        backend.config_set(pairs).unwrap();
+        assert_eq!(
+            backend.save_rules(),
+            SaveRules(vec![
+                SaveRule {
+                    seconds: 900,
+                    changes: 1
+                },
+                SaveRule {
+                    seconds: 300,
+                    changes: 10
+                },
+            ])
+        );
+        assert_eq!(
+            backend.config_get(&["sa*".to_string()]),
+            vec![("save".to_string(), "900 1 300 10".to_string())]
+        );
+        assert!(backend.config_get(&["x*".to_string()]).is_empty());
+        backend
+            .config_set(vec![("repl-backlog-size".to_string(), "4096".to_string())])
+            .unwrap();
+        assert_eq!(backend.repl_backlog_size(), 4096);
+        assert_eq!(
+            backend.config_get(&["repl-*".to_string()]),
+            vec![("repl-backlog-size".to_string(), "4096".to_string())]
+        );
+
+        // nothing is set when a value is invalid
+        let pairs = vec![
+            ("save".to_string(), "".to_string()),
+            ("save".to_string(), "900".to_string()),
+        ];
+        assert!(matches!(
+            backend.config_set(pairs),
+            Err(BackendError::InvalidConfig(..))
+        ));
+        assert_eq!(backend.save_rules().to_string(), "900 1 300 10");
+        assert!(matches!(
+            backend.config_set(vec![("nope".to_string(), "1".to_string())]),
+            Err(BackendError::UnknownConfig(_))
+        ));
+
+        // an empty value disables autosave
+        backend
+            .config_set(vec![("save".to_string(), "".to_string())])
+            .unwrap();
+        assert_eq!(backend.save_rules(), SaveRules::default());
+    }
+
+    #[test]
+    fn test_config_parameters() {
+        let backend = Backend::new();
+        let pairs = vec![
+            ("maxmemory".to_string(), "100mb".to_string()),
+            ("timeout".to_string(), "300".to_string()),
+            ("notify-keyspace-events".to_string(), "Kx".to_string()),
+            ("auto-aof-rewrite-min-size".to_string(), "1k".to_string()),
+            ("hz".to_string(), "1000".to_string()),
+            ("default-ttl-prefixes".to_string(), "cache: 60".to_string()),
+        ];
+        backend.config_set(pairs).unwrap();
+        assert_eq!(backend.maxmemory(), 100 * 1024 * 1024);
+        assert_eq!(backend.timeout(), 300);
+        assert_eq!(backend.hz(), MAX_HZ);
+        assert_eq!(backend.auto_aof_rewrite(), (100, 1000));
+        assert_eq!(backend.default_ttl_of("cache:1"), 60);
+        assert_eq!(
+            backend.config_get(&["notify-*".to_string(), "max*".to_string()]),
+            vec![
+                ("maxmemory".to_string(), "104857600".to_string()),
+                ("maxmemory-clients".to_string(), "0".to_string()),
+                ("max-connections-per-ip".to_string(), "0".to_string()),
+                ("max-connection-rate-per-ip".to_string(), "0".to_string()),
+                ("notify-keyspace-events".to_string(), "Kx".to_string()),
+            ]
+        );
+
+        for (name, value) in [
+            ("maxmemory", "10xb"),
+            ("timeout", "-1"),
+            ("dir", "/no/such/dir"),
+            ("dbfilename", "a/b.rdb"),
+            ("appendonly", "yes"),
+            ("active-expire-effort", "11"),
+            ("default-ttl", "-1"),
+            ("default-ttl-prefixes", "cache:"),
+        ] {
+            assert!(matches!(
+                backend.config_set(vec![(name.to_string(), value.to_string())]),
+                Err(BackendError::InvalidConfig(..))
+            ));
+        }
+        assert_eq!(parse_memory("2GB"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_memory("5"), Some(5));
+        assert_eq!(parse_memory("mb"), None);
+    }
+
+    #[test]
+    fn test_config_rewrite() {
+        let backend = Backend::new();
+        assert_eq!(backend.config_rewrite(), Err(BackendError::NoConfigFile));
+
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.conf", std::process::id()));
+        let config = "# the port\nport 6380\ntcp-keepalive 300\ntimeout 10\nTIMEOUT 20\n";
+        fs::write(&path, config).unwrap();
+        backend.config_load(parse_config(config).unwrap()).unwrap();
+        backend.set_config_file(&path);
+        backend.set_timeout(30);
+        backend.set_maxmemory(1024);
+        backend.set_notify_keyspace_events("".parse().unwrap());
+        backend.config_rewrite().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# the port\nport 6380\ntcp-keepalive 300\ntimeout 30\n# Generated by CONFIG REWRITE\nmaxmemory 1024\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_config_load() {
+        let config = r#"
+# a comment
+port 6380
+bind 127.0.0.1 ::1
+save 900 1
+save 300 10
+requirepass "p@ss word\n"
+logfile ''
+appendonly yes
+include other.conf
+"#;
+        let pairs = parse_config(config).unwrap();
+        assert_eq!(pairs[2], ("save".to_string(), "900 1 300 10".to_string()));
+        assert_eq!(
+            pairs[3],
+            ("requirepass".to_string(), "p@ss word\n".to_string())
+        );
+        assert_eq!(pairs[4], ("logfile".to_string(), "".to_string()));
+        assert!(parse_config("requirepass \"open\n").is_err());
+
+        let backend = Backend::new();
+        backend.config_load(pairs).unwrap();
+        assert_eq!(backend.port(), 6380);
+        assert_eq!(backend.bind().to_string(), "127.0.0.1 ::1");
+        assert_eq!(backend.save_rules().to_string(), "900 1 300 10");
+        assert_eq!(backend.requirepass(), "p@ss word\n");
+        assert!(backend.appendonly());
+        assert!(!backend.is_aof_enabled());
+
+        // the parameters only set at startup can't be set later
+        assert!(matches!(
+            backend.config_set(vec![("port".to_string(), "6381".to_string())]),
+            Err(BackendError::InvalidConfig(..))
+        ));
+        backend
+            .config_set(vec![("requirepass".to_string(), "".to_string())])
+            .unwrap();
+        assert_eq!(backend.requirepass(), "");
+    }
+
+    #[test]
+    fn test_bind_addrs() {
+        let addrs: BindAddrs = "* ::*  10.0.0.5".parse().unwrap();
+        assert_eq!(addrs.to_string(), "0.0.0.0 :: 10.0.0.5");
+        assert!(addrs.0[1].is_ipv6() && addrs.0[1].is_unspecified());
+        assert_eq!(BindAddrs::default().to_string(), DEFAULT_BIND);
+        assert!("".parse::<BindAddrs>().is_err());
+        assert!("127.0.0.1 localhost".parse::<BindAddrs>().is_err());
+    }
+}
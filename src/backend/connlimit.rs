@@ -0,0 +1,367 @@
+use crate::backend::now_ms;
+use crate::{Backend, BackendError, Stat};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tracing::info;
+
+/// The limits on the connections from each source address, and what they count.
+#[derive(Debug, Default)]
+pub struct ConnectionLimits {
+    // connections open at once, and new connections per second, 0 for no limit
+    max_per_ip: AtomicU64,
+    max_rate_per_ip: AtomicU64,
+    // the addresses connections are accepted from, any if empty, and those they aren't
+    allow: RwLock<CidrList>,
+    deny: RwLock<CidrList>,
+    // shared with the permits, which leave as their connections close
+    by_ip: Arc<Mutex<HashMap<IpAddr, IpConnections>>>,
+}
+
+#[derive(Debug, Default)]
+struct IpConnections {
+    open: u64,
+    // the unix time in seconds the connections were attempted in, and how many were
+    second: i64,
+    attempts: u64,
+    // the connections open from the address are closed once it's denied
+    denied: Arc<Notify>,
+}
+
+/// A connection admitted by the limits of its address, counted as open until dropped.
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    ip: IpAddr,
+    denied: Arc<Notify>,
+    by_ip: Arc<Mutex<HashMap<IpAddr, IpConnections>>>,
+}
+
+/// A range of addresses, as `10.0.0.0/8` or `::1` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+/// Ranges of addresses, space separated in the config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CidrList(pub Vec<Cidr>);
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // the bits of the prefix of each address, the same length or none in common
+        let bits = |addr: IpAddr| match addr {
+            IpAddr::V4(v4) => (u32::from(v4) as u128, 32),
+            IpAddr::V6(v6) => (u128::from(v6), 128),
+        };
+        let (net, len) = bits(self.addr);
+        let (ip, ip_len) = bits(ip.to_canonical());
+        let shift = len - self.prefix as u32;
+        len == ip_len && net.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+    }
+}
+
+impl CidrList {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+impl ConnectionPermit {
+    /// Wait until the address the connection is from is denied, which closes it.
+    pub async fn denied(&self) {
+        self.denied.notified().await;
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        if let Some(connections) = self.by_ip.lock().get_mut(&self.ip) {
+            connections.open = connections.open.saturating_sub(1);
+        }
+    }
+}
+
+impl Backend {
+    /// Admit a connection from ip, unless the address has as many connections open as
+    /// allowed, or attempted as many this second. The rejected attempts count towards the
+    /// rate, so a client connecting in a loop stays rejected until it slows down.
+    pub fn admit_connection(&self, ip: IpAddr) -> Result<ConnectionPermit, BackendError> {
+        let ip = ip.to_canonical();
+        if !self.is_address_allowed(ip) {
+            self.count(Stat::RejectedConnection);
+            return Err(BackendError::AddressDenied);
+        }
+        let limits = &self.connection_limits;
+        let max_per_ip = limits.max_per_ip.load(Ordering::Relaxed);
+        let max_rate_per_ip = limits.max_rate_per_ip.load(Ordering::Relaxed);
+        let mut by_ip = limits.by_ip.lock();
+        let connections = by_ip.entry(ip).or_default();
+        let second = now_ms() / 1000;
+        if connections.second != second {
+            connections.second = second;
+            connections.attempts = 0;
+        }
+        connections.attempts += 1;
+
+        let rejected = if max_rate_per_ip != 0 && connections.attempts > max_rate_per_ip {
+            Some(BackendError::ConnectionRateExceeded)
+        } else if max_per_ip != 0 && connections.open >= max_per_ip {
+            Some(BackendError::TooManyConnections)
+        } else {
+            None
+        };
+        if let Some(e) = rejected {
+            drop(by_ip);
+            self.count(Stat::RejectedConnection);
+            return Err(e);
+        }
+        connections.open += 1;
+        Ok(ConnectionPermit {
+            ip,
+            denied: connections.denied.clone(),
+            by_ip: limits.by_ip.clone(),
+        })
+    }
+
+    /// Whether connections are accepted from ip: it's in none of the ranges denied, and in
+    /// one of those allowed unless every address is.
+    pub fn is_address_allowed(&self, ip: IpAddr) -> bool {
+        let limits = &self.connection_limits;
+        let allow = limits.allow.read();
+        (allow.0.is_empty() || allow.contains(ip)) && !limits.deny.read().contains(ip)
+    }
+
+    /// The addresses connections are accepted from, any if there's none.
+    pub fn allow_clients(&self) -> CidrList {
+        self.connection_limits.allow.read().clone()
+    }
+
+    /// Accept connections from the addresses in allow only, and close those open from others.
+    pub fn set_allow_clients(&self, allow: CidrList) {
+        *self.connection_limits.allow.write() = allow;
+        self.close_denied_connections();
+    }
+
+    /// The addresses connections aren't accepted from, even if they're allowed.
+    pub fn deny_clients(&self) -> CidrList {
+        self.connection_limits.deny.read().clone()
+    }
+
+    /// Refuse the connections from the addresses in deny, and close those open.
+    pub fn set_deny_clients(&self, deny: CidrList) {
+        *self.connection_limits.deny.write() = deny;
+        self.close_denied_connections();
+    }
+
+    fn close_denied_connections(&self) {
+        for (ip, connections) in self.connection_limits.by_ip.lock().iter() {
+            if connections.open > 0 && !self.is_address_allowed(*ip) {
+                info!("closing the connections from {}, now denied", ip);
+                connections.denied.notify_waiters();
+            }
+        }
+    }
+
+    /// How many connections an address may have open at once, 0 for no limit.
+    pub fn max_connections_per_ip(&self) -> u64 {
+        self.connection_limits.max_per_ip.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_connections_per_ip(&self, max: u64) {
+        self.connection_limits
+            .max_per_ip
+            .store(max, Ordering::Relaxed);
+    }
+
+    /// How many new connections an address may attempt per second, 0 for no limit.
+    pub fn max_connection_rate_per_ip(&self) -> u64 {
+        self.connection_limits
+            .max_rate_per_ip
+            .load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_connection_rate_per_ip(&self, max: u64) {
+        self.connection_limits
+            .max_rate_per_ip
+            .store(max, Ordering::Relaxed);
+    }
+
+    // forget the addresses with no connection open nor attempted this second, which the
+    // cron does
+    pub(crate) fn forget_idle_addresses(&self) {
+        let second = now_ms() / 1000;
+        self.connection_limits
+            .by_ip
+            .lock()
+            .retain(|_, connections| connections.open > 0 || connections.second == second);
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid address range '{}'", s);
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None => max,
+        };
+        match prefix <= max {
+            true => Ok(Cidr { addr, prefix }),
+            false => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.addr, self.prefix) {
+            (IpAddr::V4(_), 32) | (IpAddr::V6(_), 128) => write!(f, "{}", self.addr),
+            _ => write!(f, "{}/{}", self.addr, self.prefix),
+        }
+    }
+}
+
+impl FromStr for CidrList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ranges = s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(CidrList(ranges))
+    }
+}
+
+impl fmt::Display for CidrList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges: Vec<String> = self.0.iter().map(Cidr::to_string).collect();
+        write!(f, "{}", ranges.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_connections_per_ip() {
+        let backend = Backend::new();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let unlimited: Vec<_> = (0..10)
+            .map(|_| backend.admit_connection(ip).unwrap())
+            .collect();
+        drop(unlimited);
+
+        backend.set_max_connections_per_ip(2);
+        let first = backend.admit_connection(ip).unwrap();
+        let _second = backend.admit_connection(ip).unwrap();
+        assert_eq!(
+            backend.admit_connection(ip).unwrap_err(),
+            BackendError::TooManyConnections
+        );
+        assert!(backend.admit_connection(other).is_ok());
+        // one closed, another may open
+        drop(first);
+        assert!(backend.admit_connection(ip).is_ok());
+        assert_eq!(backend.stats_report().rejected_connections, 1);
+    }
+
+    #[test]
+    fn test_max_connection_rate_per_ip() {
+        let backend = Backend::new();
+        let ip: IpAddr = "::1".parse().unwrap();
+        backend.set_max_connection_rate_per_ip(3);
+        let second = now_ms() / 1000;
+        let admitted = (0..5)
+            .filter(|_| backend.admit_connection(ip).is_ok())
+            .count();
+        // unless the second changed meanwhile
+        if now_ms() / 1000 == second {
+            assert_eq!(admitted, 3);
+            assert_eq!(
+                backend.admit_connection(ip).unwrap_err(),
+                BackendError::ConnectionRateExceeded
+            );
+        }
+
+        // the permits were dropped, the address is forgotten in the next second
+        backend
+            .connection_limits
+            .by_ip
+            .lock()
+            .get_mut(&ip)
+            .unwrap()
+            .second -= 1;
+        backend.forget_idle_addresses();
+        assert!(backend.connection_limits.by_ip.lock().is_empty());
+        assert!(backend.admit_connection(ip).is_ok());
+    }
+
+    #[test]
+    fn test_cidr_lists() {
+        let list: CidrList = "10.0.0.0/8 192.168.1.7 fe80::/10".parse().unwrap();
+        assert_eq!(list.to_string(), "10.0.0.0/8 192.168.1.7 fe80::/10");
+        for (ip, contained) in [
+            ("10.1.2.3", true),
+            ("11.0.0.1", false),
+            ("192.168.1.7", true),
+            ("192.168.1.8", false),
+            ("fe80::1", true),
+            ("::1", false),
+            // IPv4 as IPv6 sees it
+            ("::ffff:10.0.0.1", true),
+        ] {
+            assert_eq!(list.contains(ip.parse().unwrap()), contained, "{}", ip);
+        }
+        assert!("0.0.0.0/0"
+            .parse::<Cidr>()
+            .unwrap()
+            .contains("1.2.3.4".parse().unwrap()));
+        for invalid in ["10.0.0.0/33", "::/129", "nope", "10.0.0.0/x"] {
+            assert!(invalid.parse::<Cidr>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_allow_and_deny_clients() {
+        let backend = Backend::new();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let permit = backend.admit_connection(ip).unwrap();
+
+        backend.set_allow_clients("10.0.0.0/8".parse().unwrap());
+        assert!(backend.admit_connection(ip).is_ok());
+        assert_eq!(
+            backend
+                .admit_connection("127.0.0.1".parse().unwrap())
+                .unwrap_err(),
+            BackendError::AddressDenied
+        );
+
+        // denied, even if allowed, and the connections open closed
+        let denied = permit.denied();
+        tokio::pin!(denied);
+        assert!(futures::poll!(denied.as_mut()).is_pending());
+        backend.set_deny_clients("10.0.0.1".parse().unwrap());
+        denied.await;
+        assert_eq!(
+            backend.admit_connection(ip).unwrap_err(),
+            BackendError::AddressDenied
+        );
+        assert_eq!(backend.stats_report().rejected_connections, 2);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::backend::now_ms;
+use crate::{Backend, REPL_PING_REPLICA_PERIOD};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+use tracing::info;
+
+/// How many times per second the cron runs by default.
+pub const DEFAULT_HZ: u64 = 10;
+pub const MIN_HZ: u64 = 1;
+pub const MAX_HZ: u64 = 500;
+
+// how often the jobs run less often than every tick of the cron do, in milliseconds
+const STATS_SAMPLE_PERIOD: u64 = 100;
+const CLIENTS_TIMEOUT_PERIOD: u64 = 1000;
+const AUTOSAVE_PERIOD: u64 = 1000;
+const MIGRATE_SOCKETS_PERIOD: u64 = 1000;
+const CONNECTION_LIMITS_PERIOD: u64 = 1000;
+
+/// The periodic task of the server, serverCron's: how many times it ran, and the activity of
+/// the clients it disconnects once idle for longer than the timeout.
+#[derive(Debug, Default)]
+pub struct Cron {
+    loops: AtomicU64,
+    // shared with the connections, which leave it once closed
+    clients: Arc<Mutex<HashMap<u64, Arc<Activity>>>>,
+}
+
+#[derive(Debug)]
+struct Activity {
+    // unix time in milliseconds the client was last done with a request, and whether it's
+    // exempt from the timeout meanwhile: subscribed, or running a command
+    last: AtomicU64,
+    exempt: AtomicBool,
+    timed_out: Notify,
+}
+
+/// The activity of a connection, for the cron to close it once idle for longer than the
+/// timeout. Dropped as the connection closes, the cron forgets it.
+#[derive(Debug)]
+pub struct IdleTimer {
+    id: u64,
+    activity: Arc<Activity>,
+    clients: Arc<Mutex<HashMap<u64, Arc<Activity>>>>,
+}
+
+impl IdleTimer {
+    /// The client runs a command, however long it blocks.
+    pub fn busy(&self) {
+        self.activity.exempt.store(true, Ordering::Relaxed);
+    }
+
+    /// The client is done with a command, and waits for messages however long it takes if
+    /// subscribed.
+    pub fn idle(&self, subscribed: bool) {
+        self.activity.last.store(now_ms() as u64, Ordering::Relaxed);
+        self.activity.exempt.store(subscribed, Ordering::Relaxed);
+    }
+
+    /// Wait until the cron finds the client idle for longer than the timeout.
+    pub async fn timed_out(&self) {
+        self.activity.timed_out.notified().await;
+    }
+}
+
+impl Drop for IdleTimer {
+    fn drop(&mut self) {
+        self.clients.lock().remove(&self.id);
+    }
+}
+
+impl Backend {
+    /// Run the cron hz times per second, from a task spawned on handle.
+    pub fn start_cron(&self, handle: &Handle) {
+        let backend = self.clone();
+        handle.spawn(async move {
+            loop {
+                // a new hz applies from the next period on
+                tokio::time::sleep(Duration::from_millis(1000 / backend.hz())).await;
+                backend.cron();
+            }
+        });
+    }
+
+    /// Run the periodic jobs once: the active expiration and the eviction of the clients
+    /// using more memory than maxmemory-clients every time, the sampling of the
+    /// stats, the timeout of the clients, the save rules, the connections MIGRATE kept, the
+    /// addresses the connection limits counted and the pings of the replicas once per period
+    /// of theirs.
+    pub fn cron(&self) {
+        let loops = self.cron.loops.fetch_add(1, Ordering::Relaxed);
+        let tick = 1000 / self.hz();
+        let every = |period: u64| period <= tick || loops.is_multiple_of(period / tick);
+
+        self.active_expire_cycle();
+        self.evict_clients();
+        if every(STATS_SAMPLE_PERIOD) {
+            self.sample_ops();
+        }
+        if every(CLIENTS_TIMEOUT_PERIOD) {
+            self.close_timed_out_clients();
+        }
+        if every(AUTOSAVE_PERIOD) {
+            self.autosave();
+        }
+        if every(MIGRATE_SOCKETS_PERIOD) {
+            self.close_idle_migrate_sockets();
+        }
+        if every(CONNECTION_LIMITS_PERIOD) {
+            self.forget_idle_addresses();
+        }
+        if every(REPL_PING_REPLICA_PERIOD.as_millis() as u64) {
+            self.ping_replicas();
+        }
+    }
+
+    /// Track the activity of the connection of ID id, for the cron to close it once idle.
+    pub fn idle_timer(&self, id: u64) -> IdleTimer {
+        let activity = Arc::new(Activity {
+            last: AtomicU64::new(now_ms() as u64),
+            exempt: AtomicBool::new(false),
+            timed_out: Notify::new(),
+        });
+        self.cron.clients.lock().insert(id, activity.clone());
+        IdleTimer {
+            id,
+            activity,
+            clients: self.cron.clients.clone(),
+        }
+    }
+
+    // how many seconds the client of ID id has been idle, none while it runs a command
+    pub(crate) fn client_idle(&self, id: u64) -> u64 {
+        let clients = self.cron.clients.lock();
+        let Some(activity) = clients.get(&id) else {
+            return 0;
+        };
+        let last = activity.last.load(Ordering::Relaxed);
+        (now_ms() as u64).saturating_sub(last) / 1000
+    }
+
+    // wake the connections idle for longer than the timeout, which close
+    fn close_timed_out_clients(&self) {
+        let timeout = self.timeout();
+        if timeout == 0 {
+            return;
+        }
+        let now = now_ms() as u64;
+        for (id, activity) in self.cron.clients.lock().iter() {
+            if !activity.exempt.load(Ordering::Relaxed)
+                && now.saturating_sub(activity.last.load(Ordering::Relaxed)) >= timeout * 1000
+            {
+                info!("closing client {} idle for {} seconds", id, timeout);
+                activity.timed_out.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn test_idle_timer() {
+        let backend = Backend::new();
+        backend.set_timeout(1);
+        let timer = backend.idle_timer(1);
+        let subscriber = backend.idle_timer(2);
+        subscriber.idle(true);
+        let busy = backend.idle_timer(3);
+        busy.busy();
+        for timer in [&timer, &subscriber, &busy] {
+            timer.activity.last.fetch_sub(1000, Ordering::Relaxed);
+        }
+
+        backend.close_timed_out_clients();
+        let timed_out = |timer: &IdleTimer| timer.timed_out().now_or_never().is_some();
+        assert!(timed_out(&timer));
+        assert!(!timed_out(&subscriber));
+        assert!(!timed_out(&busy));
+
+        // active again
+        timer.idle(false);
+        backend.close_timed_out_clients();
+        assert!(!timed_out(&timer));
+
+        drop((timer, subscriber, busy));
+        assert!(backend.cron.clients.lock().is_empty());
+    }
+
+    #[test]
+    fn test_cron() {
+        let backend = Backend::new();
+        backend.set_hz(MAX_HZ);
+        let pings = backend.master_repl_offset();
+        for _ in 0..10 {
+            backend.cron();
+        }
+        assert_eq!(backend.cron.loops.load(Ordering::Relaxed), 10);
+        // nobody to ping
+        assert_eq!(backend.master_repl_offset(), pings);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::cmd::{built_in, Command, CommandError, CommandSpec, CustomCommand, KeySpec};
+use crate::{Backend, RespArray, RespFrame};
+#[cfg(feature = "modules")]
+use parking_lot::Mutex;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The symbol of a module's entry point, the `extern "C"` function called once as the
+/// module loads:
+///
+/// ```ignore
+/// #[no_mangle]
+/// pub extern "C" fn simple_redis_module_init(abi: u32, registry: *mut ModuleRegistry) -> i32
+/// ```
+///
+/// abi is the `MODULE_ABI_VERSION` of the server: a module built for another one returns
+/// nonzero without touching the registry. Otherwise it registers its commands with
+/// `ModuleRegistry::register`, the registry valid for the call only, and returns 0, anything
+/// else failing the load. It must not unwind. A `CommandSpec` being a Rust type, the module
+/// is built with the same compiler as the server.
+#[cfg(feature = "modules")]
+pub const MODULE_ENTRY_POINT: &str = "simple_redis_module_init";
+
+/// The version of the module ABI, raised whenever `CommandSpec` or the entry point change.
+#[cfg(feature = "modules")]
+pub const MODULE_ABI_VERSION: u32 = 1;
+
+#[cfg(feature = "modules")]
+type ModuleInit = unsafe extern "C" fn(u32, *mut ModuleRegistry) -> i32;
+
+/// The commands a module registers from its entry point.
+#[cfg(feature = "modules")]
+#[derive(Debug, Default)]
+pub struct ModuleRegistry {
+    specs: Vec<CommandSpec>,
+}
+
+#[cfg(feature = "modules")]
+impl ModuleRegistry {
+    pub fn register(&mut self, spec: CommandSpec) {
+        self.specs.push(spec);
+    }
+}
+
+// the custom commands of a backend by name, and the modules it loaded, unloaded with it
+#[derive(Debug, Default)]
+pub(crate) struct CustomCommands {
+    commands: RwLock<HashMap<String, Arc<CommandSpec>>>,
+    #[cfg(feature = "modules")]
+    modules: Mutex<Vec<Arc<libloading::Library>>>,
+}
+
+impl Backend {
+    /// Register a custom command, known from then on to the servers of the backend. Those
+    /// the AOF has must be registered before it loads. Fails for a name a command already
+    /// has, built in or registered.
+    pub fn register_command(&self, spec: CommandSpec) -> Result<(), CommandError> {
+        if spec.name.is_empty() || spec.arity == 0 {
+            return Err(CommandError::InvalidArgument(format!(
+                "invalid command '{}' with arity {}",
+                spec.name, spec.arity
+            )));
+        }
+        let mut commands = self.custom_commands.commands.write();
+        if built_in(spec.name.as_bytes()).is_some() || commands.contains_key(&spec.name) {
+            return Err(CommandError::InvalidArgument(format!(
+                "command '{}' already exists",
+                spec.name
+            )));
+        }
+        commands.insert(spec.name.clone(), Arc::new(spec));
+        Ok(())
+    }
+
+    /// Register the commands of the module at path, a library exporting `MODULE_ENTRY_POINT`,
+    /// returning their names. The library stays loaded as long as the backend, or a command
+    /// it parsed, is around.
+    #[cfg(feature = "modules")]
+    pub fn load_module(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<Vec<String>> {
+        use anyhow::{bail, Context};
+
+        let path = path.as_ref();
+        // SAFETY: a module is trusted as the server's own code, its initializers and its
+        // entry point with the signature documented
+        let (library, registry) = unsafe {
+            let library = libloading::Library::new(path)
+                .with_context(|| format!("failed to load {}", path.display()))?;
+            let init = *library
+                .get::<ModuleInit>(MODULE_ENTRY_POINT.as_bytes())
+                .with_context(|| format!("{} has no {}", path.display(), MODULE_ENTRY_POINT))?;
+            let mut registry = ModuleRegistry::default();
+            let status = init(MODULE_ABI_VERSION, &mut registry);
+            if status != 0 {
+                bail!("{} failed to initialize: {}", path.display(), status);
+            }
+            (Arc::new(library), registry)
+        };
+        self.custom_commands.modules.lock().push(library.clone());
+        registry
+            .specs
+            .into_iter()
+            .map(|mut spec| {
+                let name = spec.name.clone();
+                spec.module = Some(library.clone());
+                self.register_command(spec)?;
+                Ok(name)
+            })
+            .collect()
+    }
+
+    /// The command a request asks for, built in or registered with the backend.
+    pub fn parse_command(&self, value: RespArray) -> Result<Command, CommandError> {
+        let spec = match value.first() {
+            Some(RespFrame::BulkString(name)) => self.custom_command(name),
+            _ => None,
+        };
+        match spec {
+            Some(spec) => Ok(CustomCommand::parse(spec, value)?.into()),
+            None => Command::try_from(value),
+        }
+    }
+
+    pub(crate) fn custom_key_specs(&self, name: &str) -> &'static [KeySpec] {
+        self.custom_commands
+            .commands
+            .read()
+            .get(name)
+            .map_or(&[], |spec| spec.key_specs)
+    }
+
+    pub(crate) fn custom_command(&self, name: &[u8]) -> Option<Arc<CommandSpec>> {
+        let commands = self.custom_commands.commands.read();
+        match commands.is_empty() {
+            true => None,
+            false => commands
+                .get(&String::from_utf8_lossy(name).to_lowercase())
+                .cloned(),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{Backend, RespEncode, RespFrame, Value};
+use sha1_smol::Sha1;
+
+const DIGEST_LEN: usize = 20;
+
+type Digest = [u8; DIGEST_LEN];
+
+impl Backend {
+    /// Compute an order independent digest of the whole keyspace, the same way `DEBUG DIGEST`
+    /// does in redis: every key is hashed together with its type and value, and the per-key
+    /// digests are xor-ed together. The consumer groups of streams are hashed too, so a reload
+    /// that lost them shows. An empty keyspace yields all zeros.
+    pub fn digest(&self) -> String {
+        let mut digest = [0u8; DIGEST_LEN];
+
+        for entry in self.snapshot() {
+            let mut key_digest = [0u8; DIGEST_LEN];
+            mix_digest(&mut key_digest, entry.key.as_bytes());
+            mix_digest(&mut key_digest, entry.value.type_name().as_bytes());
+            match &*entry.value {
+                Value::String(v) => mix_digest(&mut key_digest, &frame_bytes(v)),
+                Value::Hash(hash) => {
+                    let mut fields_digest = [0u8; DIGEST_LEN];
+                    for (field, v) in hash.iter() {
+                        let mut field_digest = [0u8; DIGEST_LEN];
+                        mix_digest(&mut field_digest, field.as_bytes());
+                        mix_digest(&mut field_digest, &frame_bytes(v));
+                        if let Some(at) = hash.expire_time(field) {
+                            mix_digest(&mut field_digest, b"!!expire!!");
+                            mix_digest(&mut field_digest, &at.to_be_bytes());
+                        }
+                        xor_digest(&mut fields_digest, &field_digest);
+                    }
+                    mix_digest(&mut key_digest, &fields_digest);
+                }
+                Value::List(list) => {
+                    for v in list.iter() {
+                        mix_digest(&mut key_digest, v);
+                    }
+                }
+                Value::Set(set) => {
+                    let mut members_digest = [0u8; DIGEST_LEN];
+                    for member in set.iter() {
+                        xor_digest(&mut members_digest, member.as_bytes());
+                    }
+                    mix_digest(&mut key_digest, &members_digest);
+                }
+                Value::ZSet(zset) => {
+                    let mut members_digest = [0u8; DIGEST_LEN];
+                    for (member, score) in zset.iter() {
+                        let mut member_digest = [0u8; DIGEST_LEN];
+                        mix_digest(&mut member_digest, member.as_bytes());
+                        mix_digest(&mut member_digest, score.to_string().as_bytes());
+                        xor_digest(&mut members_digest, &member_digest);
+                    }
+                    mix_digest(&mut key_digest, &members_digest);
+                }
+                Value::Stream(stream) => {
+                    for (id, fields) in stream.iter() {
+                        mix_digest(&mut key_digest, id.to_string().as_bytes());
+                        for (field, v) in fields {
+                            mix_digest(&mut key_digest, field.as_bytes());
+                            mix_digest(&mut key_digest, &frame_bytes(v));
+                        }
+                    }
+                    // the groups are ordered by name, what they delivered by ID
+                    for (name, group) in stream.groups() {
+                        mix_digest(&mut key_digest, name.as_bytes());
+                        mix_digest(
+                            &mut key_digest,
+                            group.last_delivered().to_string().as_bytes(),
+                        );
+                        for (id, entry) in group.pending() {
+                            mix_digest(&mut key_digest, id.to_string().as_bytes());
+                            mix_digest(&mut key_digest, entry.consumer.as_bytes());
+                            mix_digest(&mut key_digest, &entry.delivered_at.to_be_bytes());
+                            mix_digest(&mut key_digest, &entry.deliveries.to_be_bytes());
+                        }
+                        // not when the consumers were seen, which a rewrite of the AOF resets
+                        for (consumer, _) in group.consumers() {
+                            mix_digest(&mut key_digest, consumer.as_bytes());
+                        }
+                    }
+                }
+            }
+            if let Some(at) = entry.expire_at {
+                mix_digest(&mut key_digest, b"!!expire!!");
+                mix_digest(&mut key_digest, &at.to_be_bytes());
+            }
+            xor_digest(&mut digest, &key_digest);
+        }
+
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+// digest = SHA1(digest + data)
+fn mix_digest(digest: &mut Digest, data: &[u8]) {
+    let mut hasher = Sha1::new();
+    hasher.update(digest);
+    hasher.update(data);
+    *digest = hasher.digest().bytes();
+}
+
+// digest = digest ^ SHA1(data), used where the order of the elements must not matter
+fn xor_digest(digest: &mut Digest, data: &[u8]) {
+    let hashed = Sha1::from(data).digest().bytes();
+    for (d, h) in digest.iter_mut().zip(hashed.iter()) {
+        *d ^= h;
+    }
+}
+
+// bulk strings are hashed by their raw bytes so the digest doesn't depend on the wire encoding
+fn frame_bytes(frame: &RespFrame) -> Vec<u8> {
+    match frame {
+        RespFrame::BulkString(s) => s.to_vec(),
+        frame => frame.clone().encode(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Backend, RespFrame};
+
+    #[test]
+    fn test_digest() {
+        let backend = Backend::new();
+        assert_eq!(backend.digest(), "0".repeat(40));
+
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        backend
+            .hset(
+                "k2".to_string(),
+                "f1".to_string(),
+                RespFrame::BulkString(b"v1".into()),
+            )
+            .unwrap();
+        backend
+            .hset(
+                "k2".to_string(),
+                "f2".to_string(),
+                RespFrame::BulkString(b"v2".into()),
+            )
+            .unwrap();
+        let digest = backend.digest();
+        assert_ne!(digest, "0".repeat(40));
+
+        // same data inserted in a different order yields the same digest
+        let other = Backend::new();
+        other
+            .hset(
+                "k2".to_string(),
+                "f2".to_string(),
+                RespFrame::BulkString(b"v2".into()),
+            )
+            .unwrap();
+        other
+            .hset(
+                "k2".to_string(),
+                "f1".to_string(),
+                RespFrame::BulkString(b"v1".into()),
+            )
+            .unwrap();
+        other.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert_eq!(other.digest(), digest);
+
+        other.set("k1".to_string(), RespFrame::BulkString(b"v2".into()));
+        assert_ne!(other.digest(), digest);
+    }
+}
@@ -0,0 +1,527 @@
+use crate::backend::{command, now_ms};
+use crate::{Backend, NotifyFlags, Stat};
+use rand::seq::IteratorRandom;
+use std::cell::RefCell;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_ACTIVE_EXPIRE_EFFORT: u64 = 1;
+
+// the volatile keys a sample has at the lowest effort, a quarter more for every step above
+const ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP: usize = 20;
+// the share of its period a cycle may take at the lowest effort in percent, 2 more for
+// every step above
+const ACTIVE_EXPIRE_CYCLE_SLOW_TIME_PERC: u64 = 25;
+// the percentage of expired keys in a sample at or under which the cycle moves on to the
+// next shard at the lowest effort, 1 less for every step above
+const ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE: usize = 10;
+
+thread_local! {
+    // the keys written by the writes running on the thread, the innermost last
+    static WRITTEN: RefCell<Vec<Vec<String>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The TTLs in seconds of the keys written with a prefix, the longest matching, instead of
+/// default-ttl, as `session: 60 user: 0` in the config. 0 for no TTL.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TtlPrefixes(pub Vec<(String, u64)>);
+
+/// The conditions of EXPIRE, see `Backend::expire_at_with`. A key without TTL counts as
+/// having an infinite one for GT and LT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpireOptions {
+    /// Only set a TTL on a key without one.
+    pub nx: bool,
+    /// Only set a TTL on a key with one.
+    pub xx: bool,
+    /// Only extend the TTL.
+    pub gt: bool,
+    /// Only shorten the TTL.
+    pub lt: bool,
+}
+
+impl Backend {
+    /// Set the absolute expiration time of key in unix milliseconds. A time in the past deletes
+    /// the key right away. Returns false if the key doesn't exist.
+    pub fn expire_at(&self, key: &str, at_ms: i64) -> bool {
+        self.expire_at_with(key, at_ms, &ExpireOptions::default())
+    }
+
+    /// Like `expire_at`, also returning false when the TTL of key doesn't meet the conditions
+    /// of options.
+    pub fn expire_at_with(&self, key: &str, at_ms: i64, options: &ExpireOptions) -> bool {
+        let mut shard = self.write(key);
+        if !shard.contains_key(key) {
+            return false;
+        }
+        let refused = match shard.expire_time(key) {
+            None => options.xx || options.gt,
+            Some(current) => {
+                options.nx || options.gt && at_ms <= current || options.lt && at_ms >= current
+            }
+        };
+        if refused {
+            return false;
+        }
+
+        if at_ms <= now_ms() {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        } else {
+            shard.set_expire(key.to_string(), at_ms);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "expire", key);
+        }
+        true
+    }
+
+    /// The absolute expiration time of key in unix milliseconds: `None` if the key doesn't
+    /// exist, `Some(None)` if it exists but has no TTL.
+    pub fn expire_time(&self, key: &str) -> Option<Option<i64>> {
+        let shard = self.read(key);
+        if !shard.contains_key(key) {
+            return None;
+        }
+        Some(shard.expire_time(key))
+    }
+
+    /// Remove the TTL of key, returns false if the key doesn't exist or has no TTL.
+    pub fn persist(&self, key: &str) -> bool {
+        let mut shard = self.write(key);
+        let persisted = shard.contains_key(key) && shard.persist(key).is_some();
+        if persisted {
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "persist", key);
+        }
+        persisted
+    }
+
+    /// Lazily delete key if its TTL has elapsed. Returns true if the key was deleted.
+    pub fn expire_if_needed(&self, key: &str) -> bool {
+        let shard = self.shard(key);
+        if !shard.read().is_expired(key) {
+            return false;
+        }
+        let mut shard = shard.write();
+        let expired = shard.expire_if_needed(key);
+        if expired {
+            self.expired(key);
+        }
+        expired
+    }
+
+    /// Delete the expired keys among random samples of the volatile keys of every shard,
+    /// starting from the shard after the one the previous cycle ended on. A shard is sampled
+    /// again as long as too many of its sampled keys were expired, so that few expired keys
+    /// are left in memory, until the cycle runs out of its share of the period of hz.
+    /// Returns the number of keys deleted.
+    pub fn active_expire_cycle(&self) -> usize {
+        // a replica deletes its keys when its master does
+        if self.is_replica() {
+            return 0;
+        }
+        let effort = (self.active_expire_effort() - 1) as usize;
+        let keys_per_loop =
+            ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP + ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP / 4 * effort;
+        let acceptable_stale = ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE - effort;
+        let time_perc = ACTIVE_EXPIRE_CYCLE_SLOW_TIME_PERC + 2 * effort as u64;
+        let time_limit = Duration::from_micros(1_000_000 * time_perc / 100 / self.hz());
+
+        let started = Instant::now();
+        let mut deleted = 0;
+        'cycle: for _ in 0..self.shards.len() {
+            let index = self.active_expire_shard.fetch_add(1, Ordering::Relaxed);
+            let shard = &self.shards[index % self.shards.len()];
+            loop {
+                // sampled with the shard shared, the expired keys then deleted with it locked
+                let now = now_ms();
+                let (sampled, expired) = {
+                    let shard = shard.read();
+                    let sample = shard.expires().sample(&mut rand::rng(), keys_per_loop);
+                    let expired: Vec<String> = sample
+                        .iter()
+                        .filter(|(_, &at)| at <= now)
+                        .map(|(key, _)| key.to_string())
+                        .collect();
+                    (sample.len(), expired)
+                };
+                if sampled == 0 {
+                    break;
+                }
+                let expired = match expired.is_empty() {
+                    true => 0,
+                    false => self.run_shared(|| {
+                        let mut shard = shard.write();
+                        let mut count = 0;
+                        for key in &expired {
+                            if shard.expire_if_needed(key) {
+                                self.expired(key);
+                                count += 1;
+                            }
+                        }
+                        count
+                    }),
+                };
+                deleted += expired;
+
+                if started.elapsed() >= time_limit {
+                    self.count(Stat::ExpireCycleTimeCapReached);
+                    break 'cycle;
+                }
+                if expired * 100 <= sampled * acceptable_stale {
+                    break;
+                }
+            }
+        }
+        self.flush_propagated();
+        deleted
+    }
+
+    /// The TTL in seconds given to the keys a write leaves without one, unless
+    /// default-ttl-prefixes has theirs, 0 for none.
+    pub fn default_ttl(&self) -> u64 {
+        self.default_ttl.load(Ordering::Relaxed)
+    }
+
+    pub fn set_default_ttl(&self, seconds: u64) {
+        self.default_ttl.store(seconds, Ordering::Relaxed);
+    }
+
+    pub fn default_ttl_prefixes(&self) -> TtlPrefixes {
+        self.default_ttl_prefixes.read().clone()
+    }
+
+    pub fn set_default_ttl_prefixes(&self, prefixes: TtlPrefixes) {
+        *self.default_ttl_prefixes.write() = prefixes;
+    }
+
+    /// The TTL in seconds key is given if a write leaves it without one, 0 for none.
+    pub fn default_ttl_of(&self, key: &str) -> u64 {
+        match self.default_ttl_prefixes.read().ttl(key) {
+            Some(seconds) => seconds,
+            None => self.default_ttl(),
+        }
+    }
+
+    /// Run f, a write, then give the keys it wrote, if they're still there without a TTL,
+    /// their default one, propagated as PEXPIREAT: a new key or one SET overwrote, not one
+    /// PERSIST made persistent. Not on a replica, its master propagates theirs, nor while the
+    /// AOF replays, it has theirs.
+    pub(crate) fn with_default_ttl<T>(&self, f: impl FnOnce() -> T) -> T {
+        // dropped even if f panics, not to leave the thread collecting
+        struct Collect;
+        impl Drop for Collect {
+            fn drop(&mut self) {
+                WRITTEN.with(|c| c.borrow_mut().pop());
+            }
+        }
+
+        let disabled = self.default_ttl() == 0 && self.default_ttl_prefixes.read().0.is_empty();
+        if disabled || self.is_replica() || self.is_loading() {
+            return f();
+        }
+        WRITTEN.with(|c| c.borrow_mut().push(Vec::new()));
+        let collect = Collect;
+        let result = f();
+        let mut written = WRITTEN
+            .with(|c| c.borrow_mut().last_mut().map(std::mem::take))
+            .unwrap_or_default();
+        drop(collect);
+        written.sort_unstable();
+        written.dedup();
+        for key in written {
+            self.set_default_ttl_of(&key);
+        }
+        result
+    }
+
+    // note that the write running on the thread wrote key, if it gives keys a default TTL
+    pub(crate) fn key_written(&self, key: &str) {
+        WRITTEN.with(|c| {
+            if let Some(keys) = c.borrow_mut().last_mut() {
+                keys.push(key.to_string());
+            }
+        });
+    }
+
+    fn set_default_ttl_of(&self, key: &str) {
+        let seconds = self.default_ttl_of(key);
+        if seconds == 0 {
+            return;
+        }
+        let mut shard = self.write(key);
+        if !shard.contains_key(key) || shard.expire_time(key).is_some() {
+            return;
+        }
+        let ttl_ms = i64::try_from(seconds)
+            .unwrap_or(i64::MAX)
+            .saturating_mul(1000);
+        let at = now_ms().saturating_add(ttl_ms);
+        shard.set_expire(key.to_string(), at);
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "expire", key);
+        self.propagate(command(["PEXPIREAT", key, &at.to_string()]));
+    }
+
+    // notify that key expired and propagate its deletion, with its shard still locked
+    pub(crate) fn expired(&self, key: &str) {
+        self.count(Stat::ExpiredKey);
+        self.notify_keyspace_event(NotifyFlags::EXPIRED, "expired", key);
+        self.propagate_ahead(command(["DEL", key]));
+    }
+}
+
+impl TtlPrefixes {
+    // the TTL of the longest prefix of key, if it has one
+    fn ttl(&self, key: &str) -> Option<u64> {
+        self.0
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, seconds)| *seconds)
+    }
+}
+
+impl FromStr for TtlPrefixes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        args.chunks(2)
+            .map(|pair| match pair {
+                [prefix, seconds] => seconds
+                    .parse()
+                    .map(|seconds| (prefix.to_string(), seconds))
+                    .map_err(|_| format!("invalid TTL {} for {}", seconds, prefix)),
+                _ => Err("a prefix needs a TTL".to_string()),
+            })
+            .collect::<Result<_, _>>()
+            .map(TtlPrefixes)
+    }
+}
+
+impl fmt::Display for TtlPrefixes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs: Vec<String> = self
+            .0
+            .iter()
+            .map(|(prefix, seconds)| format!("{} {}", prefix, seconds))
+            .collect();
+        write!(f, "{}", pairs.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{command, now_ms};
+    use crate::cmd::Command;
+    use crate::{Backend, ExpireOptions, RespFrame, TtlPrefixes};
+
+    #[test]
+    fn test_expire_and_persist() {
+        let backend = Backend::new();
+        assert!(!backend.expire_at("k1", now_ms() + 10_000));
+        assert_eq!(backend.expire_time("k1"), None);
+
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert_eq!(backend.expire_time("k1"), Some(None));
+
+        let at = now_ms() + 10_000;
+        assert!(backend.expire_at("k1", at));
+        assert_eq!(backend.expire_time("k1"), Some(Some(at)));
+
+        assert!(backend.persist("k1"));
+        assert!(!backend.persist("k1"));
+        assert_eq!(backend.expire_time("k1"), Some(None));
+
+        // SET discards the TTL of the previous value
+        backend.expire_at("k1", at);
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v2".into()));
+        assert_eq!(backend.expire_time("k1"), Some(None));
+    }
+
+    #[test]
+    fn test_expire_conditions() {
+        let backend = Backend::new();
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        let at = now_ms() + 10_000;
+        let (nx, xx, gt, lt) = (
+            ExpireOptions {
+                nx: true,
+                ..Default::default()
+            },
+            ExpireOptions {
+                xx: true,
+                ..Default::default()
+            },
+            ExpireOptions {
+                gt: true,
+                ..Default::default()
+            },
+            ExpireOptions {
+                lt: true,
+                ..Default::default()
+            },
+        );
+
+        // without TTL, which counts as infinite
+        assert!(!backend.expire_at_with("k1", at, &xx));
+        assert!(!backend.expire_at_with("k1", at, &gt));
+        assert_eq!(backend.expire_time("k1"), Some(None));
+        assert!(backend.expire_at_with("k1", at, &nx));
+        assert!(!backend.expire_at_with("k1", at + 1000, &nx));
+        assert_eq!(backend.expire_time("k1"), Some(Some(at)));
+
+        assert!(!backend.expire_at_with("k1", at, &gt));
+        assert!(backend.expire_at_with("k1", at + 1000, &gt));
+        assert!(!backend.expire_at_with("k1", at + 1000, &lt));
+        assert!(backend.expire_at_with("k1", at, &lt));
+        assert!(backend.expire_at_with("k1", at + 2000, &xx));
+        assert_eq!(backend.expire_time("k1"), Some(Some(at + 2000)));
+
+        backend.persist("k1");
+        assert!(backend.expire_at_with("k1", at, &lt));
+        assert!(!backend.expire_at_with("missing", at, &lt));
+    }
+
+    #[test]
+    fn test_lazy_expire() {
+        let backend = Backend::new();
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        backend
+            .hset(
+                "k2".to_string(),
+                "f1".to_string(),
+                RespFrame::BulkString(b"v1".into()),
+            )
+            .unwrap();
+
+        backend
+            .write("k1")
+            .set_expire("k1".to_string(), now_ms() - 1);
+        backend
+            .write("k2")
+            .set_expire("k2".to_string(), now_ms() - 1);
+        assert_eq!(backend.get("k1"), Ok(None));
+        assert_eq!(backend.hget("k2", "f1"), Ok(None));
+        assert!(!backend.expire_if_needed("k1"));
+
+        // an expire time in the past deletes the key right away
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert!(backend.expire_at("k1", now_ms() - 1000));
+        assert!(!backend.contains_key("k1"));
+    }
+
+    #[test]
+    fn test_active_expire_cycle() {
+        let backend = Backend::new();
+        for i in 0..500 {
+            let key = format!("k{}", i);
+            backend.set(key.clone(), RespFrame::BulkString(b"v".into()));
+            // a fifth of the keys stays
+            let at = match i % 5 {
+                0 => now_ms() + 10_000,
+                _ => now_ms() - 1,
+            };
+            backend.write(&key).set_expire(key.clone(), at);
+        }
+        backend.set("persistent".to_string(), RespFrame::BulkString(b"v".into()));
+
+        // the shards are sampled until few of their keys are expired
+        let deleted = backend.active_expire_cycle();
+        assert!(deleted > 300, "{} keys deleted", deleted);
+        assert_eq!(backend.stats_report().expired_keys, deleted as u64);
+        assert!(backend.contains_key("persistent"));
+        assert!(backend.contains_key("k0"));
+        while backend.dbsize() > 101 {
+            backend.active_expire_cycle();
+        }
+        assert_eq!(backend.active_expire_cycle(), 0);
+    }
+
+    #[test]
+    fn test_default_ttl() {
+        let backend = Backend::new();
+        let set = |key: &str| {
+            backend.capture_propagated(|| {
+                backend.run_logged(|| backend.set(key.to_string(), RespFrame::Integer(1)))
+            })
+        };
+        set("k1");
+        assert_eq!(backend.expire_time("k1"), Some(None));
+
+        backend.set_default_ttl(100);
+        backend.set_default_ttl_prefixes("s 10 session: 20 user: 0".parse().unwrap());
+        let before = now_ms();
+        let ((), propagated) = set("k2");
+        let Some(Some(at)) = backend.expire_time("k2") else {
+            panic!("k2 has no TTL");
+        };
+        assert!(at >= before + 100_000 && at <= now_ms() + 100_000);
+        assert_eq!(
+            propagated,
+            vec![command(["PEXPIREAT", "k2", &at.to_string()])]
+        );
+        // a key written without one gets it too, a key with one keeps it
+        set("k1");
+        assert!(matches!(backend.expire_time("k1"), Some(Some(_))));
+        backend
+            .run_logged(|| backend.set_expire_at("k3".to_string(), RespFrame::Integer(1), at + 1));
+        assert_eq!(backend.expire_time("k3"), Some(Some(at + 1)));
+
+        // the longest prefix
+        set("session:1");
+        let Some(Some(at)) = backend.expire_time("session:1") else {
+            panic!("session:1 has no TTL");
+        };
+        assert!(at <= now_ms() + 20_000 && at > now_ms() + 10_000);
+        set("user:1");
+        assert_eq!(backend.expire_time("user:1"), Some(None));
+
+        // default-ttl without the prefixes
+        backend.set_default_ttl_prefixes(TtlPrefixes::default());
+        assert_eq!(backend.default_ttl_of("user:2"), 100);
+    }
+
+    #[test]
+    fn test_default_ttl_commands() {
+        let backend = Backend::new();
+        backend.set_default_ttl(100);
+        let run = |args: &[&str]| {
+            let cmd = Command::try_from(command(args.iter().copied())).unwrap();
+            cmd.execute_unblocked(&backend, None)
+        };
+        run(&["SET", "k", "v1"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(100));
+        // overwritten, its TTL cleared then given again
+        run(&["SET", "k", "v2"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(100));
+        run(&["GETSET", "k", "v3"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(100));
+        run(&["MSET", "k", "v4", "other", "v"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(100));
+        assert_eq!(run(&["TTL", "other"]), RespFrame::Integer(100));
+        // but PERSIST makes it persistent, until it's written again
+        run(&["PERSIST", "k"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(-1));
+        run(&["APPEND", "k", "x"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(100));
+        run(&["SETEX", "k", "5", "v"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(5));
+        run(&["SET", "k", "v", "EX", "7"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(7));
+        run(&["SET", "k", "v", "KEEPTTL"]);
+        assert_eq!(run(&["TTL", "k"]), RespFrame::Integer(7));
+    }
+
+    #[test]
+    fn test_ttl_prefixes() {
+        let prefixes: TtlPrefixes = "a 1 ab 2".parse().unwrap();
+        assert_eq!(prefixes.ttl("abc"), Some(2));
+        assert_eq!(prefixes.ttl("ax"), Some(1));
+        assert_eq!(prefixes.ttl("b"), None);
+        assert_eq!(prefixes.to_string(), "a 1 ab 2");
+        assert_eq!("".parse::<TtlPrefixes>(), Ok(TtlPrefixes::default()));
+        assert!("a".parse::<TtlPrefixes>().is_err());
+        assert!("a -1".parse::<TtlPrefixes>().is_err());
+    }
+}
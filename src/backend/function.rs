@@ -0,0 +1,432 @@
+use crate::{glob_match, lua_error_message, Backend, BackendError, BulkString, RespArray};
+use crate::{RespDecode, RespEncode, RespFrame};
+use bytes::BytesMut;
+use mlua::{Function, Lua, LuaOptions, MultiValue, RegistryKey, StdLib, Table, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+// the flags a function can be registered with
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+/// The libraries loaded with FUNCTION LOAD, and the Lua interpreter their functions live in,
+/// apart from the one of EVAL scripts so SCRIPT FLUSH keeps them.
+#[derive(Debug)]
+pub struct Functions {
+    lua: Lua,
+    libraries: BTreeMap<String, Library>,
+    // the registered functions, by name
+    handles: HashMap<String, RegistryKey>,
+}
+
+/// A library of functions, with the code registering them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Library {
+    pub name: String,
+    pub code: String,
+    pub functions: BTreeMap<String, FunctionMeta>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionMeta {
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+impl FunctionMeta {
+    /// Whether FCALL_RO can call the function.
+    pub fn is_read_only(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "no-writes")
+    }
+}
+
+/// What FUNCTION RESTORE does with the loaded libraries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestorePolicy {
+    /// Fail if a library exists.
+    #[default]
+    Append,
+    /// Replace the libraries that exist.
+    Replace,
+    /// Delete every library first.
+    Flush,
+}
+
+impl Default for Functions {
+    fn default() -> Self {
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH;
+        Self {
+            lua: Lua::new_with(libs, LuaOptions::default()).expect("Lua libraries load"),
+            libraries: BTreeMap::new(),
+            handles: HashMap::new(),
+        }
+    }
+}
+
+impl Functions {
+    fn load(&mut self, code: &str, replace: bool) -> Result<String, BackendError> {
+        let name = library_name(code)?;
+        if !replace && self.libraries.contains_key(&name) {
+            return Err(BackendError::LibraryExists(name));
+        }
+
+        let registered = self.register(code)?;
+        if registered.is_empty() {
+            return Err(BackendError::FunctionLoad("No functions registered".into()));
+        }
+        for (function, _, _) in &registered {
+            let owner = self
+                .libraries
+                .values()
+                .find(|library| library.name != name && library.functions.contains_key(function));
+            if owner.is_some() {
+                return Err(BackendError::FunctionExists(function.clone()));
+            }
+        }
+
+        self.delete(&name);
+        let mut functions = BTreeMap::new();
+        for (function, meta, handle) in registered {
+            self.handles.insert(function.clone(), handle);
+            functions.insert(function, meta);
+        }
+        let library = Library {
+            name: name.clone(),
+            code: code.to_string(),
+            functions,
+        };
+        self.libraries.insert(name.clone(), library);
+        Ok(name)
+    }
+
+    // run the library code, collecting the functions it registers
+    fn register(
+        &self,
+        code: &str,
+    ) -> Result<Vec<(String, FunctionMeta, RegistryKey)>, BackendError> {
+        let registered = RefCell::new(Vec::new());
+        let result = self.lua.scope(|scope| {
+            let register = scope.create_function(|lua, args: MultiValue| {
+                let (name, callback, meta) = registration(args)?;
+                let mut registered = registered.borrow_mut();
+                if registered.iter().any(|(function, _, _)| *function == name) {
+                    return Err(mlua::Error::runtime(
+                        "Function already exists in the library",
+                    ));
+                }
+                registered.push((name, meta, lua.create_registry_value(callback)?));
+                Ok(())
+            })?;
+            let redis = self.lua.create_table()?;
+            redis.set("register_function", register)?;
+            self.lua.globals().set("redis", redis)?;
+
+            // the metadata line replaced by an empty one, for the line numbers
+            let body = &code[code.find('\n').unwrap_or(code.len())..];
+            self.lua.load(body).set_name("@user_function").exec()
+        });
+        let _ = self.lua.globals().set("redis", Value::Nil);
+
+        result.map_err(|e| {
+            BackendError::FunctionLoad(format!(
+                "Error registering functions: {}",
+                lua_error_message(&e)
+            ))
+        })?;
+        Ok(registered.into_inner())
+    }
+
+    fn delete(&mut self, name: &str) -> bool {
+        let Some(library) = self.libraries.remove(name) else {
+            return false;
+        };
+        for function in library.functions.keys() {
+            if let Some(handle) = self.handles.remove(function) {
+                let _ = self.lua.remove_registry_value(handle);
+            }
+        }
+        true
+    }
+}
+
+// the name, callback and metadata of `redis.register_function(name, callback)` or
+// `redis.register_function{function_name=..., callback=..., flags=..., description=...}`
+fn registration(args: MultiValue) -> mlua::Result<(String, Function, FunctionMeta)> {
+    let mut args = args.into_iter();
+    let (name, callback, meta) = match (args.next(), args.next(), args.next()) {
+        (Some(Value::String(name)), Some(Value::Function(callback)), None) => (
+            name.to_str()?.to_string(),
+            callback,
+            FunctionMeta::default(),
+        ),
+        (Some(Value::Table(table)), None, None) => registration_table(table)?,
+        _ => {
+            return Err(mlua::Error::runtime(
+                "wrong arguments given to redis.register_function",
+            ))
+        }
+    };
+
+    if !is_valid_name(&name) {
+        return Err(mlua::Error::runtime(
+            "Function names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+        ));
+    }
+    if let Some(flag) = meta
+        .flags
+        .iter()
+        .find(|flag| !FUNCTION_FLAGS.contains(&flag.as_str()))
+    {
+        return Err(mlua::Error::runtime(format!(
+            "unknown flag given: {}",
+            flag
+        )));
+    }
+    Ok((name, callback, meta))
+}
+
+fn registration_table(table: Table) -> mlua::Result<(String, Function, FunctionMeta)> {
+    let name: String = table.get("function_name")?;
+    let callback: Function = table.get("callback")?;
+    let meta = FunctionMeta {
+        description: table.get("description")?,
+        flags: table
+            .get::<_, Option<Vec<String>>>("flags")?
+            .unwrap_or_default(),
+    };
+    Ok((name, callback, meta))
+}
+
+// the name in the `#!lua name=<name>` first line of a library
+fn library_name(code: &str) -> Result<String, BackendError> {
+    let metadata = code.lines().next().unwrap_or_default();
+    let Some(metadata) = metadata.strip_prefix("#!") else {
+        return Err(BackendError::FunctionLoad(
+            "Missing library metadata".into(),
+        ));
+    };
+
+    let mut parts = metadata.split_whitespace();
+    let engine = parts.next().unwrap_or_default();
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(BackendError::FunctionLoad(format!(
+            "Engine '{}' not found",
+            engine
+        )));
+    }
+    let mut name = None;
+    for part in parts {
+        match part.split_once('=') {
+            Some(("name", value)) => name = Some(value.to_string()),
+            _ => {
+                return Err(BackendError::FunctionLoad(format!(
+                    "Invalid metadata value given: {}",
+                    part
+                )))
+            }
+        }
+    }
+
+    match name {
+        Some(name) if is_valid_name(&name) => Ok(name),
+        Some(_) => Err(BackendError::FunctionLoad(
+            "Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".into(),
+        )),
+        None => Err(BackendError::FunctionLoad(
+            "Library name was not given".into(),
+        )),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+impl Backend {
+    /// Load a library, returns its name. An existing library is only replaced if asked to.
+    pub fn function_load(&self, code: &str, replace: bool) -> Result<String, BackendError> {
+        self.scripting.functions.lock().load(code, replace)
+    }
+
+    pub fn function_delete(&self, library: &str) -> Result<(), BackendError> {
+        match self.scripting.functions.lock().delete(library) {
+            true => Ok(()),
+            false => Err(BackendError::NoSuchLibrary),
+        }
+    }
+
+    pub fn function_flush(&self) {
+        *self.scripting.functions.lock() = Functions::default();
+    }
+
+    /// The libraries, those whose name matches the glob-style pattern if any.
+    pub fn function_list(&self, pattern: Option<&str>) -> Vec<Library> {
+        self.scripting
+            .functions
+            .lock()
+            .libraries
+            .values()
+            .filter(|library| {
+                pattern
+                    .is_none_or(|pattern| glob_match(pattern.as_bytes(), library.name.as_bytes()))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Serialize every library, for FUNCTION RESTORE.
+    pub fn function_dump(&self) -> Vec<u8> {
+        let codes: Vec<RespFrame> = self
+            .scripting
+            .functions
+            .lock()
+            .libraries
+            .values()
+            .map(|library| BulkString::new(library.code.clone()).into())
+            .collect();
+        RespArray::new(codes).encode()
+    }
+
+    /// Load the libraries of a FUNCTION DUMP payload.
+    pub fn function_restore(
+        &self,
+        payload: &[u8],
+        policy: RestorePolicy,
+    ) -> Result<(), BackendError> {
+        let codes = RespArray::decode(&mut BytesMut::from(payload))
+            .ok()
+            .and_then(|codes| {
+                codes
+                    .iter()
+                    .map(|code| match code {
+                        RespFrame::BulkString(code) => String::from_utf8(code.to_vec()).ok(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or(BackendError::BadPayload)?;
+
+        let mut functions = self.scripting.functions.lock();
+        match policy {
+            RestorePolicy::Append => {
+                for code in &codes {
+                    let name = library_name(code)?;
+                    if functions.libraries.contains_key(&name) {
+                        return Err(BackendError::LibraryExists(name));
+                    }
+                }
+            }
+            RestorePolicy::Replace => {}
+            RestorePolicy::Flush => *functions = Functions::default(),
+        }
+        for code in codes {
+            functions.load(&code, true)?;
+        }
+        Ok(())
+    }
+
+    /// Run f with the function called name, and the interpreter it lives in, locked for the
+    /// time of the call.
+    pub fn with_function<T>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&Lua, Function, &FunctionMeta) -> T,
+    ) -> Result<T, BackendError> {
+        let functions = self.scripting.functions.lock();
+        let meta = functions
+            .libraries
+            .values()
+            .find_map(|library| library.functions.get(name))
+            .ok_or(BackendError::NoSuchFunction)?;
+        let handle = &functions.handles[name];
+        let function = functions
+            .lua
+            .registry_value(handle)
+            .map_err(|_| BackendError::NoSuchFunction)?;
+        Ok(f(&functions.lua, function, meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY: &str = "#!lua name=mylib\nredis.register_function('hello', function() return 'hi' end)\nredis.register_function{function_name='ro', callback=function() return 1 end, flags={'no-writes'}}";
+
+    #[test]
+    fn test_function_load() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.function_load(LIBRARY, false),
+            Ok("mylib".to_string())
+        );
+        assert_eq!(
+            backend.function_load(LIBRARY, false),
+            Err(BackendError::LibraryExists("mylib".to_string()))
+        );
+        assert_eq!(
+            backend.function_load(LIBRARY, true),
+            Ok("mylib".to_string())
+        );
+
+        // a function name belongs to one library
+        let other = "#!lua name=other\nredis.register_function('hello', function() end)";
+        assert_eq!(
+            backend.function_load(other, false),
+            Err(BackendError::FunctionExists("hello".to_string()))
+        );
+        let result = backend.function_load("return 1", false);
+        assert_eq!(
+            result,
+            Err(BackendError::FunctionLoad(
+                "Missing library metadata".into()
+            ))
+        );
+        let result = backend.function_load("#!lua name=empty\nlocal x = 1", false);
+        assert_eq!(
+            result,
+            Err(BackendError::FunctionLoad("No functions registered".into()))
+        );
+
+        let libraries = backend.function_list(Some("my*"));
+        assert_eq!(libraries.len(), 1);
+        assert!(libraries[0].functions["ro"].is_read_only());
+        assert!(!libraries[0].functions["hello"].is_read_only());
+        let reply = backend.with_function("hello", |_, function, _| function.call::<_, String>(()));
+        assert_eq!(reply.unwrap().unwrap(), "hi");
+
+        assert_eq!(backend.function_delete("mylib"), Ok(()));
+        assert_eq!(
+            backend.function_delete("mylib"),
+            Err(BackendError::NoSuchLibrary)
+        );
+        assert!(backend.with_function("hello", |_, _, _| ()).is_err());
+    }
+
+    #[test]
+    fn test_function_dump_restore() {
+        let backend = Backend::new();
+        backend.function_load(LIBRARY, false).unwrap();
+        let payload = backend.function_dump();
+
+        assert_eq!(
+            backend.function_restore(&payload, RestorePolicy::Append),
+            Err(BackendError::LibraryExists("mylib".to_string()))
+        );
+        backend.function_flush();
+        assert!(backend.function_list(None).is_empty());
+        backend
+            .function_restore(&payload, RestorePolicy::Append)
+            .unwrap();
+        assert_eq!(backend.function_list(None)[0].code, LIBRARY);
+        assert_eq!(
+            backend.function_restore(b"garbage", RestorePolicy::Flush),
+            Err(BackendError::BadPayload)
+        );
+    }
+}
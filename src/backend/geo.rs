@@ -0,0 +1,473 @@
+use crate::{Backend, BackendError, ScoreBound, SortedSet, ZAddOptions};
+use std::str::FromStr;
+
+/// The longitudes geohashes encode.
+pub const GEO_LONG_MIN: f64 = -180.0;
+pub const GEO_LONG_MAX: f64 = 180.0;
+
+/// The latitudes geohashes encode, those of the Web Mercator projection (EPSG:3857).
+pub const GEO_LAT_MIN: f64 = -85.05112878;
+pub const GEO_LAT_MAX: f64 = 85.05112878;
+
+// the bits of a geohash for each of longitude and latitude, 52 in all so that a score, a
+// double, holds one exactly
+const GEO_STEP_MAX: u32 = 26;
+
+// the radius of the earth in meters distances are computed with
+const EARTH_RADIUS: f64 = 6372797.560856;
+
+/// A unit of distance of GEODIST and GEOSEARCH.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeoUnit {
+    #[default]
+    Meters,
+    Kilometers,
+    Feet,
+    Miles,
+}
+
+/// Where GEOSEARCH searches from: a member of the zset, or a longitude and a latitude.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoOrigin {
+    Member(String),
+    LonLat(f64, f64),
+}
+
+/// The area GEOSEARCH searches around its origin, in meters: a circle of a radius, or a box
+/// of a width and a height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoShape {
+    Radius(f64),
+    Box(f64, f64),
+}
+
+/// How GEOSEARCH sorts the members found by their distance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeoSort {
+    #[default]
+    Unsorted,
+    Asc,
+    Desc,
+}
+
+/// The options of GEOSEARCH, see `Backend::geosearch`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoSearchOptions {
+    pub origin: GeoOrigin,
+    pub shape: GeoShape,
+    pub sort: GeoSort,
+    /// How many members to return at most, and whether any may be returned, rather than the
+    /// nearest ones.
+    pub count: Option<(usize, bool)>,
+}
+
+/// A member found by GEOSEARCH, with its distance from the origin in meters, its geohash,
+/// and the longitude and latitude it decodes to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoMatch {
+    pub member: String,
+    pub distance: f64,
+    pub hash: u64,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl GeoUnit {
+    /// How many meters the unit is.
+    pub fn meters(self) -> f64 {
+        match self {
+            GeoUnit::Meters => 1.0,
+            GeoUnit::Kilometers => 1000.0,
+            GeoUnit::Feet => 0.3048,
+            GeoUnit::Miles => 1609.34,
+        }
+    }
+}
+
+impl FromStr for GeoUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "m" => Ok(GeoUnit::Meters),
+            "km" => Ok(GeoUnit::Kilometers),
+            "ft" => Ok(GeoUnit::Feet),
+            "mi" => Ok(GeoUnit::Miles),
+            _ => Err("unsupported unit provided. please use M, KM, FT, MI".to_string()),
+        }
+    }
+}
+
+/// The 52-bit geohash of a point, the bits of its longitude and latitude interleaved, None if
+/// it's out of the ranges geohashes encode.
+pub fn geohash_encode(lon: f64, lat: f64) -> Option<u64> {
+    if !(GEO_LONG_MIN..=GEO_LONG_MAX).contains(&lon) || !(GEO_LAT_MIN..=GEO_LAT_MAX).contains(&lat)
+    {
+        return None;
+    }
+    let cells = (1u64 << GEO_STEP_MAX) as f64;
+    let offset = |value: f64, min: f64, max: f64| {
+        (((value - min) / (max - min) * cells) as u64).min((1 << GEO_STEP_MAX) - 1)
+    };
+    let x = offset(lon, GEO_LONG_MIN, GEO_LONG_MAX);
+    let y = offset(lat, GEO_LAT_MIN, GEO_LAT_MAX);
+    Some(interleave(y, x))
+}
+
+/// The longitude and latitude of the center of the area a geohash encodes.
+pub fn geohash_decode(hash: u64) -> (f64, f64) {
+    let (y, x) = (squash(hash), squash(hash >> 1));
+    let cells = (1u64 << GEO_STEP_MAX) as f64;
+    let center = |offset: u64, min: f64, max: f64| {
+        let size = (max - min) / cells;
+        (min + (offset as f64 + 0.5) * size).clamp(min, max)
+    };
+    (
+        center(x, GEO_LONG_MIN, GEO_LONG_MAX),
+        center(y, GEO_LAT_MIN, GEO_LAT_MAX),
+    )
+}
+
+/// The distance in meters between two points on the earth, by the haversine formula.
+pub fn geo_distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let v = ((lon2 - lon1).to_radians() / 2.0).sin();
+    2.0 * EARTH_RADIUS * (u * u + lat1.cos() * lat2.cos() * v * v).sqrt().asin()
+}
+
+fn invalid_lon_lat(lon: f64, lat: f64) -> BackendError {
+    BackendError::InvalidLonLat(format!("{:.6},{:.6}", lon, lat))
+}
+
+// the bits of y at the even positions, those of x at the odd ones
+fn interleave(y: u64, x: u64) -> u64 {
+    spread(y) | spread(x) << 1
+}
+
+// the 32 low bits of value, each followed by a 0
+fn spread(value: u64) -> u64 {
+    let mut value = value & 0xffff_ffff;
+    value = (value | value << 16) & 0x0000_ffff_0000_ffff;
+    value = (value | value << 8) & 0x00ff_00ff_00ff_00ff;
+    value = (value | value << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    value = (value | value << 2) & 0x3333_3333_3333_3333;
+    (value | value << 1) & 0x5555_5555_5555_5555
+}
+
+// the bits at the even positions of value, packed
+fn squash(value: u64) -> u64 {
+    let mut value = value & 0x5555_5555_5555_5555;
+    value = (value | value >> 1) & 0x3333_3333_3333_3333;
+    value = (value | value >> 2) & 0x0f0f_0f0f_0f0f_0f0f;
+    value = (value | value >> 4) & 0x00ff_00ff_00ff_00ff;
+    value = (value | value >> 8) & 0x0000_ffff_0000_ffff;
+    (value | value >> 16) & 0x0000_0000_ffff_ffff
+}
+
+// the score ranges, [min, max), of the geohash cells around a point which cover a circle of
+// radius meters: the cell of the point and its neighbors, at the finest step where these 9
+// cells are large enough
+fn search_ranges(lon: f64, lat: f64, radius: f64) -> Vec<(u64, u64)> {
+    let lat_degrees = (radius / EARTH_RADIUS).to_degrees();
+    let lon_degrees = lat_degrees / lat.to_radians().cos();
+    let (lon_range, lat_range) = (GEO_LONG_MAX - GEO_LONG_MIN, GEO_LAT_MAX - GEO_LAT_MIN);
+    let mut step = GEO_STEP_MAX;
+    let (cells, x, y) = loop {
+        let cells = 1u64 << step;
+        let (lon_size, lat_size) = (lon_range / cells as f64, lat_range / cells as f64);
+        let x = (((lon - GEO_LONG_MIN) / lon_size) as u64).min(cells - 1);
+        let y = (((lat - GEO_LAT_MIN) / lat_size) as u64).min(cells - 1);
+        // the neighbors past the poles don't exist, nor are they needed
+        let covered = lon - lon_degrees >= GEO_LONG_MIN + (x as f64 - 1.0) * lon_size
+            && lon + lon_degrees <= GEO_LONG_MIN + (x as f64 + 2.0) * lon_size
+            && (y == 0 || lat - lat_degrees >= GEO_LAT_MIN + (y as f64 - 1.0) * lat_size)
+            && (y == cells - 1 || lat + lat_degrees <= GEO_LAT_MIN + (y as f64 + 2.0) * lat_size);
+        if covered || step == 1 {
+            break (cells, x, y);
+        }
+        step -= 1;
+    };
+    let shift = 2 * (GEO_STEP_MAX - step);
+    let mut ranges = Vec::with_capacity(9);
+    for dy in [-1i64, 0, 1] {
+        let y = y as i64 + dy;
+        if y < 0 || y >= cells as i64 {
+            continue;
+        }
+        for dx in [-1i64, 0, 1] {
+            // the longitudes wrap around
+            let x = (x as i64 + dx).rem_euclid(cells as i64);
+            let cell = interleave(y as u64, x as u64);
+            ranges.push((cell << shift, (cell + 1) << shift));
+        }
+    }
+    ranges.sort_unstable();
+    ranges.dedup();
+    ranges
+}
+
+impl Backend {
+    /// Add members at longitudes and latitudes to the zset at key, scored by their geohash,
+    /// as `zadd_with` does. No member is added if a point is out of the ranges geohashes
+    /// encode.
+    pub fn geoadd(
+        &self,
+        key: &str,
+        points: Vec<(f64, f64, String)>,
+        options: &ZAddOptions,
+    ) -> Result<usize, BackendError> {
+        let members = points
+            .into_iter()
+            .map(|(lon, lat, member)| match geohash_encode(lon, lat) {
+                Some(hash) => Ok((hash as f64, member)),
+                None => Err(invalid_lon_lat(lon, lat)),
+            })
+            .collect::<Result<_, _>>()?;
+        self.zadd_with(key, members, options)
+    }
+
+    /// The longitude and latitude of each member, None for those not in the zset at key.
+    pub fn geopos(
+        &self,
+        key: &str,
+        members: &[String],
+    ) -> Result<Vec<Option<(f64, f64)>>, BackendError> {
+        Ok(self
+            .read_zset(key, |zset| {
+                members
+                    .iter()
+                    .map(|member| zset.score(member).map(|score| geohash_decode(score as u64)))
+                    .collect()
+            })?
+            .unwrap_or_else(|| vec![None; members.len()]))
+    }
+
+    /// The distance in meters between two members of the zset at key, None if one isn't in
+    /// it.
+    pub fn geodist(
+        &self,
+        key: &str,
+        member1: &str,
+        member2: &str,
+    ) -> Result<Option<f64>, BackendError> {
+        let points = self.geopos(key, &[member1.to_string(), member2.to_string()])?;
+        match points[..] {
+            [Some((lon1, lat1)), Some((lon2, lat2))] => {
+                Ok(Some(geo_distance(lon1, lat1, lon2, lat2)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The members of the zset at key within the shape around the origin, sorted as options
+    /// say. With a count and no sort, the nearest members are returned, nearest first, unless
+    /// any of them may be.
+    pub fn geosearch(
+        &self,
+        key: &str,
+        options: &GeoSearchOptions,
+    ) -> Result<Vec<GeoMatch>, BackendError> {
+        if let GeoOrigin::LonLat(lon, lat) = options.origin {
+            if geohash_encode(lon, lat).is_none() {
+                return Err(invalid_lon_lat(lon, lat));
+            }
+        }
+        let found = self.read_zset(key, |zset| {
+            let (lon, lat) = match &options.origin {
+                GeoOrigin::LonLat(lon, lat) => (*lon, *lat),
+                GeoOrigin::Member(member) => match zset.score(member) {
+                    Some(score) => geohash_decode(score as u64),
+                    None => return Err(BackendError::GeoMemberMissing),
+                },
+            };
+            Ok(search(zset, lon, lat, options))
+        })?;
+        let mut found = found.transpose()?.unwrap_or_default();
+        let sort = match (options.sort, options.count) {
+            (GeoSort::Unsorted, Some((_, false))) => GeoSort::Asc,
+            (sort, _) => sort,
+        };
+        match sort {
+            GeoSort::Unsorted => {}
+            GeoSort::Asc => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+            GeoSort::Desc => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+        }
+        if let Some((count, _)) = options.count {
+            found.truncate(count);
+        }
+        Ok(found)
+    }
+}
+
+// the members of zset within the shape around lon, lat, stopping at the count with ANY
+fn search(zset: &SortedSet, lon: f64, lat: f64, options: &GeoSearchOptions) -> Vec<GeoMatch> {
+    let radius = match options.shape {
+        GeoShape::Radius(radius) => radius,
+        GeoShape::Box(width, height) => (width / 2.0).hypot(height / 2.0),
+    };
+    let limit = match options.count {
+        Some((count, true)) => count,
+        _ => usize::MAX,
+    };
+    let mut found = Vec::new();
+    for (min, max) in search_ranges(lon, lat, radius) {
+        let members = zset.range_by_score(
+            ScoreBound::Inclusive(min as f64),
+            ScoreBound::Exclusive(max as f64),
+            false,
+        );
+        for (member, score) in members {
+            let hash = score as u64;
+            let (member_lon, member_lat) = geohash_decode(hash);
+            let distance = match options.shape {
+                GeoShape::Radius(radius) => {
+                    let distance = geo_distance(lon, lat, member_lon, member_lat);
+                    (distance <= radius).then_some(distance)
+                }
+                // within half the height along the meridian, then half the width along the
+                // parallel of the member
+                GeoShape::Box(width, height) => {
+                    let lat_distance = geo_distance(lon, lat, lon, member_lat);
+                    let lon_distance = geo_distance(lon, member_lat, member_lon, member_lat);
+                    (lat_distance <= height / 2.0 && lon_distance <= width / 2.0)
+                        .then(|| geo_distance(lon, lat, member_lon, member_lat))
+                }
+            };
+            if let Some(distance) = distance {
+                found.push(GeoMatch {
+                    member: member.clone(),
+                    distance,
+                    hash,
+                    lon: member_lon,
+                    lat: member_lat,
+                });
+                if found.len() == limit {
+                    return found;
+                }
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sicily() -> Result<Backend, BackendError> {
+        let backend = Backend::new();
+        let points = vec![
+            (13.361389, 38.115556, "Palermo".to_string()),
+            (15.087269, 37.502669, "Catania".to_string()),
+            (12.758489, 38.788135, "edge1".to_string()),
+            (17.241510, 38.788135, "edge2".to_string()),
+        ];
+        backend.geoadd("Sicily", points, &ZAddOptions::default())?;
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_geohash() {
+        // the scores Redis gives these points
+        assert_eq!(geohash_encode(13.361389, 38.115556), Some(3479099956230698));
+        assert_eq!(geohash_encode(15.087269, 37.502669), Some(3479447370796909));
+        assert_eq!(geohash_encode(180.1, 0.0), None);
+        assert_eq!(geohash_encode(0.0, 85.06), None);
+
+        let (lon, lat) = geohash_decode(3479099956230698);
+        assert!((lon - 13.361389).abs() < 1e-5 && (lat - 38.115556).abs() < 1e-5);
+        for (lon, lat) in [(-180.0, GEO_LAT_MIN), (180.0, GEO_LAT_MAX), (0.0, 0.0)] {
+            let hash = geohash_encode(lon, lat).unwrap_or_default();
+            assert!(hash < 1 << 52);
+            let (decoded_lon, decoded_lat) = geohash_decode(hash);
+            assert!((decoded_lon - lon).abs() < 1e-5 && (decoded_lat - lat).abs() < 1e-5);
+        }
+
+        let distance = geo_distance(13.361389, 38.115556, 15.087269, 37.502669);
+        assert!((distance - 166274.15).abs() < 1.0);
+        assert_eq!("KM".parse(), Ok(GeoUnit::Kilometers));
+        assert!("yd".parse::<GeoUnit>().is_err());
+    }
+
+    #[test]
+    fn test_geo_members() -> Result<(), BackendError> {
+        let backend = sicily()?;
+        assert_eq!(backend.zcard("Sicily")?, 4);
+        let points = backend.geopos("Sicily", &["Palermo".to_string(), "x".to_string()])?;
+        assert!(matches!(points[..], [Some((lon, _)), None] if (lon - 13.361389).abs() < 1e-5));
+        assert_eq!(backend.geopos("missing", &["x".to_string()])?, vec![None]);
+
+        let distance = backend.geodist("Sicily", "Palermo", "Catania")?;
+        assert!(distance.is_some_and(|d| (d - 166274.15).abs() < 1.0));
+        assert_eq!(backend.geodist("Sicily", "Palermo", "x")?, None);
+
+        // nothing is added if a point is invalid
+        let points = vec![(1.0, 2.0, "a".to_string()), (1.0, 89.0, "b".to_string())];
+        assert_eq!(
+            backend.geoadd("Sicily", points, &ZAddOptions::default()),
+            Err(BackendError::InvalidLonLat(
+                "1.000000,89.000000".to_string()
+            ))
+        );
+        assert_eq!(backend.zcard("Sicily")?, 4);
+        backend.set("s".to_string(), crate::BulkString::from("v").into());
+        assert_eq!(backend.geopos("s", &[]), Err(BackendError::WrongType));
+        Ok(())
+    }
+
+    #[test]
+    fn test_geosearch() -> Result<(), BackendError> {
+        let backend = sicily()?;
+        let members = |found: Vec<GeoMatch>| -> Vec<String> {
+            found.into_iter().map(|found| found.member).collect()
+        };
+        let mut options = GeoSearchOptions {
+            origin: GeoOrigin::LonLat(15.0, 37.0),
+            shape: GeoShape::Radius(200_000.0),
+            sort: GeoSort::Asc,
+            count: None,
+        };
+        let found = backend.geosearch("Sicily", &options)?;
+        assert!((found[0].distance - 56441.27).abs() < 1.0);
+        assert_eq!(members(found), ["Catania", "Palermo"]);
+
+        options.shape = GeoShape::Box(400_000.0, 400_000.0);
+        options.sort = GeoSort::Desc;
+        let found = backend.geosearch("Sicily", &options)?;
+        // the edges are about as far, the farthest first
+        assert_eq!(found.len(), 4);
+        assert_eq!(members(found)[2..], ["Palermo", "Catania"]);
+
+        // the nearest ones first, or any with ANY
+        options.sort = GeoSort::Unsorted;
+        options.count = Some((1, false));
+        assert_eq!(members(backend.geosearch("Sicily", &options)?), ["Catania"]);
+        options.count = Some((2, true));
+        assert_eq!(backend.geosearch("Sicily", &options)?.len(), 2);
+
+        options.origin = GeoOrigin::Member("Palermo".to_string());
+        options.shape = GeoShape::Radius(1.0);
+        options.count = None;
+        assert_eq!(members(backend.geosearch("Sicily", &options)?), ["Palermo"]);
+        options.origin = GeoOrigin::Member("x".to_string());
+        assert_eq!(
+            backend.geosearch("Sicily", &options),
+            Err(BackendError::GeoMemberMissing)
+        );
+        assert_eq!(backend.geosearch("missing", &options)?, vec![]);
+
+        // the whole earth, across the antimeridian
+        let points = vec![
+            (179.9, 0.0, "east".to_string()),
+            (-179.9, 0.0, "west".to_string()),
+        ];
+        backend.geoadd("line", points, &ZAddOptions::default())?;
+        options.origin = GeoOrigin::LonLat(179.95, 0.0);
+        options.shape = GeoShape::Radius(20_000.0);
+        assert_eq!(backend.geosearch("line", &options)?.len(), 2);
+        options.shape = GeoShape::Radius(30_000_000.0);
+        assert_eq!(backend.geosearch("Sicily", &options)?.len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+/// Match string against a glob-style pattern the way redis does: `*` matches any sequence,
+/// `?` any single byte, `[abc]`, `[^abc]` and `[a-z]` a byte class, and `\` escapes the next
+/// byte.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // where to resume after the last `*` if the rest of the pattern doesn't match
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, s));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p, string[s]),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == string[s]).then_some(p + 2),
+            Some(c) => (*c == string[s]).then_some(p + 1),
+            None => None,
+        };
+
+        match (matched, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                s += 1;
+            }
+            // let the last `*` swallow one more byte
+            (None, Some((star, from))) => {
+                backtrack = Some((star, from + 1));
+                p = star + 1;
+                s = from + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+// match c against the class starting at pattern[start] == '[', returning the position after it
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<usize> {
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            matched |= pattern[p + 1] == c;
+            p += 2;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let (lo, hi) = if pattern[p] <= pattern[p + 2] {
+                (pattern[p], pattern[p + 2])
+            } else {
+                (pattern[p + 2], pattern[p])
+            };
+            matched |= (lo..=hi).contains(&c);
+            p += 3;
+        } else {
+            matched |= pattern[p] == c;
+            p += 1;
+        }
+    }
+
+    // like redis, an unterminated class ends with the pattern
+    let next = (p + 1).min(pattern.len());
+    (matched != negate).then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "heeeello", true),
+            ("h*llo", "hello world", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-b]llo", "hbllo", true),
+            ("h[b-a]llo", "hallo", true),
+            ("user:*:name", "user:42:name", true),
+            ("user:*:name", "user:42:age", false),
+            ("a\\*b", "a*b", true),
+            ("a\\*b", "axb", false),
+            ("*a*b*", "xxaxxbxx", true),
+            ("*a*b", "xxaxxbxx", false),
+        ];
+        for (pattern, string, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), string.as_bytes()),
+                *expected,
+                "{} ~ {}",
+                pattern,
+                string
+            );
+        }
+    }
+}
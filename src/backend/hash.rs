@@ -0,0 +1,692 @@
+use crate::backend::{key_hash, now_ms, parse_float, parse_integer, scan_page, string_bytes};
+use crate::{Backend, BackendError, BulkString, HashTable, NotifyFlags, RespFrame, Shard, Value};
+use parking_lot::RwLockWriteGuard;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::RngExt;
+use std::sync::atomic::Ordering;
+
+/// The outcome of setting the TTL of one hash field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldExpire {
+    /// The key or the field doesn't exist.
+    NoField,
+    Set,
+    /// The time was in the past, so the field was deleted.
+    Deleted,
+}
+
+impl Backend {
+    /// The hash-max-listpack-entries the hashes created from now on are converted past.
+    pub fn hash_max_listpack_entries(&self) -> usize {
+        self.hash_max_listpack_entries.load(Ordering::Relaxed)
+    }
+
+    pub fn set_hash_max_listpack_entries(&self, entries: usize) {
+        self.hash_max_listpack_entries
+            .store(entries, Ordering::Relaxed);
+    }
+
+    /// The hash-max-listpack-value, the longest field or value a listpack holds.
+    pub fn hash_max_listpack_value(&self) -> usize {
+        self.hash_max_listpack_value.load(Ordering::Relaxed)
+    }
+
+    pub fn set_hash_max_listpack_value(&self, value: usize) {
+        self.hash_max_listpack_value.store(value, Ordering::Relaxed);
+    }
+
+    pub(crate) fn new_hash(&self) -> HashTable {
+        HashTable::with_limits(
+            self.hash_max_listpack_entries(),
+            self.hash_max_listpack_value(),
+        )
+    }
+
+    /// Set the fields of the hash at key, creating it if needed. Returns the number of fields
+    /// that didn't exist before.
+    pub fn hset_multiple(
+        &self,
+        key: String,
+        pairs: Vec<(String, RespFrame)>,
+    ) -> Result<usize, BackendError> {
+        let mut shard = self.write_hash(&key);
+        let created = match shard.get_mut(&key) {
+            None => {
+                self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+                let mut hash = self.new_hash();
+                hash.extend(pairs);
+                let created = hash.len();
+                shard.insert(key.clone(), Value::Hash(hash));
+                created
+            }
+            Some(obj) => {
+                obj.touch();
+                match &mut **obj {
+                    Value::Hash(hash) => pairs
+                        .into_iter()
+                        .map(|(field, value)| hash.insert(field, value))
+                        .filter(Option::is_none)
+                        .count(),
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+        };
+        self.notify_keyspace_event(NotifyFlags::HASH, "hset", &key);
+        Ok(created)
+    }
+
+    /// The values of fields in the hash at key, `None` for missing fields.
+    pub fn hmget(
+        &self,
+        key: &str,
+        fields: &[String],
+    ) -> Result<Vec<Option<RespFrame>>, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                fields
+                    .iter()
+                    .map(|field| hash.get(field).cloned())
+                    .collect()
+            })?
+            .unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// Remove fields from the hash at key, deleting the key once it has no fields left.
+    /// Returns the number of fields removed.
+    pub fn hdel(&self, key: &str, fields: &[String]) -> Result<usize, BackendError> {
+        let mut shard = self.write_hash(key);
+        let (removed, empty) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Ok(0),
+            Some(Value::Hash(hash)) => {
+                let removed = fields.iter().filter(|f| hash.remove(f).is_some()).count();
+                (removed, hash.is_empty())
+            }
+            Some(_) => return Err(BackendError::WrongType),
+        };
+
+        if removed > 0 {
+            self.notify_keyspace_event(NotifyFlags::HASH, "hdel", key);
+        }
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        Ok(removed)
+    }
+
+    pub fn hexists(&self, key: &str, field: &str) -> Result<bool, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| hash.contains_key(field))?
+            .unwrap_or(false))
+    }
+
+    /// The number of fields of the hash at key, 0 if it doesn't exist.
+    pub fn hlen(&self, key: &str) -> Result<usize, BackendError> {
+        Ok(self.read_hash(key, |hash| hash.len())?.unwrap_or(0))
+    }
+
+    /// Add delta to the integer stored in field, a missing key or field counts as 0.
+    pub fn hincr_by(&self, key: &str, field: &str, delta: i64) -> Result<i64, BackendError> {
+        self.update_hash_field(key, field, "hincrby", |current| {
+            let current = match current {
+                None => 0,
+                Some(v) => parse_integer(v).ok_or(BackendError::HashNotInteger)?,
+            };
+            let value = current.checked_add(delta).ok_or(BackendError::Overflow)?;
+            Ok((BulkString::new(value.to_string()).into(), value))
+        })
+    }
+
+    /// Add delta to the float stored in field, a missing key or field counts as 0.
+    pub fn hincr_by_float(&self, key: &str, field: &str, delta: f64) -> Result<f64, BackendError> {
+        self.update_hash_field(key, field, "hincrbyfloat", |current| {
+            let current = match current {
+                None => 0.0,
+                Some(v) => parse_float(v).ok_or(BackendError::HashNotFloat)?,
+            };
+            let value = current + delta;
+            if !value.is_finite() {
+                return Err(BackendError::NanOrInfinity);
+            }
+            Ok((BulkString::new(value.to_string()).into(), value))
+        })
+    }
+
+    /// Random fields with their values. A positive count returns up to count distinct fields,
+    /// a negative one returns exactly -count fields which may repeat. Both make one pass over
+    /// the hash without copying it.
+    pub fn hrandfield(
+        &self,
+        key: &str,
+        count: i64,
+    ) -> Result<Vec<(String, RespFrame)>, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                random_picks(hash.iter(), hash.len(), count)
+                    .into_iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Incrementally iterate the fields of the hash at key with the same cursors as `scan`.
+    pub fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<(String, RespFrame)>), BackendError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                let batch = hash
+                    .iter()
+                    .map(|entry| (key_hash(entry.0), entry))
+                    .filter(|(hash, _)| *hash >= cursor)
+                    .collect();
+                let (next, page) = scan_page(batch, count);
+                let page = page
+                    .into_iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect();
+                (next, page)
+            })?
+            .unwrap_or_default())
+    }
+
+    /// The fields of the hash at key, sorted like the HGETALL reply.
+    pub fn hkeys(&self, key: &str) -> Result<Vec<String>, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| sorted_fields(hash).cloned().collect())?
+            .unwrap_or_default())
+    }
+
+    /// The values of the hash at key, in the same order as `hkeys`.
+    pub fn hvals(&self, key: &str) -> Result<Vec<RespFrame>, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                sorted_fields(hash)
+                    .filter_map(|field| hash.get(field).cloned())
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// The length of the value of field, 0 if the key or the field doesn't exist.
+    pub fn hstrlen(&self, key: &str, field: &str) -> Result<usize, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| hash.get(field).map(|v| string_bytes(v).len()))?
+            .flatten()
+            .unwrap_or(0))
+    }
+
+    /// Set the absolute expiration time of fields in unix milliseconds. A time in the past
+    /// deletes the fields right away, and the key with its last field.
+    pub fn hexpire_at(
+        &self,
+        key: &str,
+        fields: &[String],
+        at_ms: i64,
+    ) -> Result<Vec<FieldExpire>, BackendError> {
+        let mut shard = self.write_hash(key);
+        let (results, empty) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Ok(vec![FieldExpire::NoField; fields.len()]),
+            Some(Value::Hash(hash)) => {
+                let results: Vec<_> = fields
+                    .iter()
+                    .map(|field| {
+                        if !hash.contains_key(field) {
+                            FieldExpire::NoField
+                        } else if at_ms <= now_ms() {
+                            hash.remove(field);
+                            FieldExpire::Deleted
+                        } else {
+                            hash.set_expire(field, at_ms);
+                            FieldExpire::Set
+                        }
+                    })
+                    .collect();
+                (results, hash.is_empty())
+            }
+            Some(_) => return Err(BackendError::WrongType),
+        };
+
+        if results.contains(&FieldExpire::Set) {
+            self.notify_keyspace_event(NotifyFlags::HASH, "hexpire", key);
+        }
+        if results.contains(&FieldExpire::Deleted) {
+            self.notify_keyspace_event(NotifyFlags::HASH, "hdel", key);
+        }
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        Ok(results)
+    }
+
+    /// The absolute expiration time of fields in unix milliseconds: `None` for a missing field,
+    /// `Some(None)` for a field without TTL.
+    pub fn hexpire_time(
+        &self,
+        key: &str,
+        fields: &[String],
+    ) -> Result<Vec<Option<Option<i64>>>, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| {
+                fields
+                    .iter()
+                    .map(|field| hash.contains_key(field).then(|| hash.expire_time(field)))
+                    .collect()
+            })?
+            .unwrap_or_else(|| vec![None; fields.len()]))
+    }
+
+    /// Remove the TTL of fields: `None` for a missing field, otherwise whether it had a TTL.
+    pub fn hpersist(
+        &self,
+        key: &str,
+        fields: &[String],
+    ) -> Result<Vec<Option<bool>>, BackendError> {
+        let mut shard = self.write_hash(key);
+        let results: Vec<_> = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Ok(vec![None; fields.len()]),
+            Some(Value::Hash(hash)) => fields
+                .iter()
+                .map(|field| {
+                    hash.contains_key(field)
+                        .then(|| hash.persist(field).is_some())
+                })
+                .collect(),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+
+        if results.contains(&Some(true)) {
+            self.notify_keyspace_event(NotifyFlags::HASH, "hpersist", key);
+        }
+        Ok(results)
+    }
+
+    // lock the shard owning key for writing, after lazily expiring the fields of its hash
+    fn write_hash(&self, key: &str) -> RwLockWriteGuard<'_, Shard> {
+        let mut shard = self.write(key);
+        self.remove_expired_fields(&mut shard, key);
+        shard
+    }
+
+    // delete the fields of the hash at key whose TTL has elapsed, and the key once empty
+    fn remove_expired_fields(&self, shard: &mut Shard, key: &str) {
+        let (expired, empty) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::Hash(hash)) => (hash.remove_expired(now_ms()), hash.is_empty()),
+            _ => return,
+        };
+        if expired > 0 {
+            self.notify_keyspace_event(NotifyFlags::HASH, "hexpired", key);
+        }
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+    }
+
+    // replace field with the value f computes from the current one under a single write lock,
+    // creating the hash if needed. Nothing is written when f fails.
+    fn update_hash_field<R>(
+        &self,
+        key: &str,
+        field: &str,
+        event: &str,
+        f: impl FnOnce(Option<&RespFrame>) -> Result<(RespFrame, R), BackendError>,
+    ) -> Result<R, BackendError> {
+        let mut shard = self.write_hash(key);
+        let ret = match shard.get_mut(key) {
+            None => {
+                let (value, ret) = f(None)?;
+                self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+                let mut hash = self.new_hash();
+                hash.insert(field.to_string(), value);
+                shard.insert(key.to_string(), Value::Hash(hash));
+                ret
+            }
+            Some(obj) => {
+                obj.touch();
+                match &mut **obj {
+                    Value::Hash(hash) => {
+                        let (value, ret) = f(hash.get(field))?;
+                        // like redis, an increment keeps the TTL of the field
+                        hash.update(field.to_string(), value);
+                        ret
+                    }
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+        };
+        self.notify_keyspace_event(NotifyFlags::HASH, event, key);
+        Ok(ret)
+    }
+
+    // run f on the hash at key, None if the key doesn't exist
+    pub(crate) fn read_hash<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&HashTable) -> R,
+    ) -> Result<Option<R>, BackendError> {
+        let has_expired = |shard: &Shard| matches!(shard.get(key).map(|obj| &**obj), Some(Value::Hash(hash)) if hash.has_expired(now_ms()));
+        let mut shard = self.read(key);
+        if has_expired(&shard) {
+            drop(shard);
+            self.remove_expired_fields(&mut self.write(key), key);
+            shard = self.read(key);
+        }
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::Hash(hash) => Ok(Some(f(hash))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+}
+
+/// Random items of an iterator over len items, with the count semantics of HRANDFIELD and
+/// SRANDMEMBER: up to count distinct items if positive, exactly -count items which may repeat
+/// if negative.
+pub(crate) fn random_picks<T>(items: impl Iterator<Item = T>, len: usize, count: i64) -> Vec<T>
+where
+    T: Clone,
+{
+    let mut rng = rand::rng();
+    let mut picked: Vec<_> = if count >= 0 {
+        items.sample(&mut rng, count as usize)
+    } else if len == 0 {
+        Vec::new()
+    } else {
+        // sorted random positions, picked up in a single walk of the items
+        let mut positions: Vec<usize> = (0..count.unsigned_abs())
+            .map(|_| rng.random_range(0..len))
+            .collect();
+        positions.sort_unstable();
+        let mut positions = positions.into_iter().peekable();
+        let mut picked = Vec::with_capacity(positions.len());
+        for (i, item) in items.enumerate() {
+            while positions.next_if_eq(&i).is_some() {
+                picked.push(item.clone());
+            }
+        }
+        picked
+    };
+    picked.shuffle(&mut rng);
+    picked
+}
+
+fn sorted_fields(hash: &HashTable) -> impl Iterator<Item = &String> {
+    let mut fields: Vec<_> = hash.keys().collect();
+    fields.sort();
+    fields.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdel_hexists_hlen() {
+        let backend = Backend::new();
+        for field in ["f1", "f2", "f3"] {
+            backend
+                .hset("h".to_string(), field.to_string(), RespFrame::Integer(1))
+                .unwrap();
+        }
+        assert_eq!(backend.hlen("h"), Ok(3));
+        assert_eq!(backend.hexists("h", "f1"), Ok(true));
+
+        let fields = ["f1".to_string(), "f1".to_string(), "missing".to_string()];
+        assert_eq!(backend.hdel("h", &fields), Ok(1));
+        assert_eq!(backend.hexists("h", "f1"), Ok(false));
+        assert_eq!(backend.hlen("h"), Ok(2));
+
+        // removing the last field deletes the key
+        let fields = ["f2".to_string(), "f3".to_string()];
+        assert_eq!(backend.hdel("h", &fields), Ok(2));
+        assert!(!backend.contains_key("h"));
+        assert_eq!(backend.hlen("h"), Ok(0));
+        assert_eq!(backend.hdel("h", &fields), Ok(0));
+
+        backend.set("s".to_string(), RespFrame::Integer(1));
+        assert_eq!(backend.hlen("s"), Err(BackendError::WrongType));
+        assert_eq!(backend.hdel("s", &fields), Err(BackendError::WrongType));
+    }
+
+    #[test]
+    fn test_hkeys_hvals_hstrlen() {
+        let backend = Backend::new();
+        assert_eq!(backend.hkeys("h"), Ok(vec![]));
+        assert_eq!(backend.hstrlen("h", "f"), Ok(0));
+
+        backend
+            .hset("h".to_string(), "b".to_string(), RespFrame::Integer(-12))
+            .unwrap();
+        backend
+            .hset(
+                "h".to_string(),
+                "a".to_string(),
+                RespFrame::BulkString(b"hello".into()),
+            )
+            .unwrap();
+
+        assert_eq!(
+            backend.hkeys("h"),
+            Ok(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(
+            backend.hvals("h"),
+            Ok(vec![
+                RespFrame::BulkString(b"hello".into()),
+                RespFrame::Integer(-12)
+            ])
+        );
+        assert_eq!(backend.hstrlen("h", "a"), Ok(5));
+        assert_eq!(backend.hstrlen("h", "b"), Ok(3));
+        assert_eq!(backend.hstrlen("h", "missing"), Ok(0));
+    }
+
+    #[test]
+    fn test_hgetall_shares_values() {
+        let backend = Backend::new();
+        let value = BulkString::new(vec![b'v'; 4096]);
+        backend
+            .hset("h".to_string(), "f".to_string(), value.clone().into())
+            .unwrap();
+
+        let fields = backend.hgetall("h").unwrap().unwrap();
+        match &fields[..] {
+            [(field, RespFrame::BulkString(read))] => {
+                assert_eq!(field, "f");
+                assert_eq!(read.as_ptr(), value.as_ptr());
+            }
+            fields => panic!("unexpected fields {:?}", fields),
+        }
+        assert_eq!(backend.hgetall("missing"), Ok(None));
+    }
+
+    #[test]
+    fn test_hset_multiple_hmget() {
+        let backend = Backend::new();
+        let pairs = vec![
+            ("f1".to_string(), RespFrame::Integer(1)),
+            ("f2".to_string(), RespFrame::Integer(2)),
+        ];
+        assert_eq!(backend.hset_multiple("h".to_string(), pairs), Ok(2));
+
+        // only new fields are counted, existing ones are overwritten
+        let pairs = vec![
+            ("f2".to_string(), RespFrame::Integer(20)),
+            ("f3".to_string(), RespFrame::Integer(3)),
+        ];
+        assert_eq!(backend.hset_multiple("h".to_string(), pairs), Ok(1));
+
+        let fields = ["f2".to_string(), "missing".to_string(), "f3".to_string()];
+        assert_eq!(
+            backend.hmget("h", &fields),
+            Ok(vec![
+                Some(RespFrame::Integer(20)),
+                None,
+                Some(RespFrame::Integer(3))
+            ])
+        );
+        assert_eq!(
+            backend.hmget("missing", &fields),
+            Ok(vec![None, None, None])
+        );
+    }
+
+    #[test]
+    fn test_hincr_by() {
+        let backend = Backend::new();
+        assert_eq!(backend.hincr_by("h", "n", 5), Ok(5));
+        assert_eq!(backend.hincr_by("h", "n", -7), Ok(-2));
+        assert_eq!(
+            backend.hget("h", "n"),
+            Ok(Some(RespFrame::BulkString(b"-2".into())))
+        );
+        assert_eq!(backend.hincr_by("h", "n", i64::MAX - 1), Ok(i64::MAX - 3));
+        assert_eq!(backend.hincr_by("h", "n", 10), Err(BackendError::Overflow));
+
+        backend
+            .hset(
+                "h".to_string(),
+                "s".to_string(),
+                RespFrame::BulkString(b"abc".into()),
+            )
+            .unwrap();
+        assert_eq!(
+            backend.hincr_by("h", "s", 1),
+            Err(BackendError::HashNotInteger)
+        );
+        assert_eq!(
+            backend.hincr_by_float("h", "s", 1.0),
+            Err(BackendError::HashNotFloat)
+        );
+
+        assert_eq!(backend.hincr_by_float("h", "f", 10.5), Ok(10.5));
+        assert_eq!(backend.hincr_by_float("h", "f", 0.1), Ok(10.6));
+        assert_eq!(
+            backend.hincr_by_float("h", "f", f64::INFINITY),
+            Err(BackendError::NanOrInfinity)
+        );
+        assert_eq!(
+            backend.hget("h", "f"),
+            Ok(Some(RespFrame::BulkString(b"10.6".into())))
+        );
+    }
+
+    #[test]
+    fn test_hrandfield() {
+        let backend = Backend::new();
+        assert_eq!(backend.hrandfield("h", 3), Ok(vec![]));
+        assert_eq!(backend.hrandfield("h", -3), Ok(vec![]));
+
+        let pairs = (0..10)
+            .map(|i| (format!("f{}", i), RespFrame::Integer(i)))
+            .collect();
+        backend.hset_multiple("h".to_string(), pairs).unwrap();
+
+        let picked = backend.hrandfield("h", 5).unwrap();
+        assert_eq!(picked.len(), 5);
+        let mut fields: Vec<_> = picked.iter().map(|(f, _)| f.clone()).collect();
+        fields.sort();
+        fields.dedup();
+        assert_eq!(fields.len(), 5);
+        for (field, value) in picked {
+            assert_eq!(backend.hget("h", &field), Ok(Some(value)));
+        }
+
+        // a positive count larger than the hash returns every field once
+        assert_eq!(backend.hrandfield("h", 20).unwrap().len(), 10);
+        // a negative count returns exactly that many fields
+        assert_eq!(backend.hrandfield("h", -25).unwrap().len(), 25);
+        assert_eq!(backend.hrandfield("h", 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_hscan() {
+        let backend = Backend::new();
+        assert_eq!(backend.hscan("h", 0, 10), Ok((0, vec![])));
+
+        let pairs = (0..100)
+            .map(|i| (format!("f{}", i), RespFrame::Integer(i)))
+            .collect();
+        backend.hset_multiple("h".to_string(), pairs).unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, page) = backend.hscan("h", cursor, 7).unwrap();
+            assert!(next == 0 || page.len() >= 7);
+            seen.extend(page.into_iter().map(|(field, _)| field));
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 100);
+    }
+
+    #[test]
+    fn test_hash_field_ttl() {
+        let backend = Backend::new();
+        let fields = ["f1".to_string(), "f2".to_string(), "missing".to_string()];
+        assert_eq!(
+            backend.hexpire_at("h", &fields, now_ms() + 10_000),
+            Ok(vec![FieldExpire::NoField; 3])
+        );
+
+        for field in ["f1", "f2", "f3"] {
+            backend
+                .hset("h".to_string(), field.to_string(), RespFrame::Integer(1))
+                .unwrap();
+        }
+        let at = now_ms() + 10_000;
+        assert_eq!(
+            backend.hexpire_at("h", &fields, at),
+            Ok(vec![
+                FieldExpire::Set,
+                FieldExpire::Set,
+                FieldExpire::NoField
+            ])
+        );
+        assert_eq!(
+            backend.hexpire_time("h", &fields),
+            Ok(vec![Some(Some(at)), Some(Some(at)), None])
+        );
+
+        // HINCRBY keeps the TTL, HSET drops it
+        backend.hincr_by("h", "f1", 1).unwrap();
+        backend
+            .hset("h".to_string(), "f2".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(
+            backend.hpersist("h", &fields),
+            Ok(vec![Some(true), Some(false), None])
+        );
+
+        // a time in the past deletes the fields right away
+        let past = ["f1".to_string(), "f2".to_string()];
+        assert_eq!(
+            backend.hexpire_at("h", &past, now_ms() - 1),
+            Ok(vec![FieldExpire::Deleted, FieldExpire::Deleted])
+        );
+        assert_eq!(backend.hlen("h"), Ok(1));
+
+        // expired fields are lazily deleted on access, with the key once empty
+        if let Some(Value::Hash(hash)) = backend.write("h").get_mut("h").map(|obj| &mut **obj) {
+            hash.set_expire("f3", now_ms() - 1);
+        }
+        assert_eq!(backend.hget("h", "f3"), Ok(None));
+        assert!(!backend.contains_key("h"));
+    }
+}
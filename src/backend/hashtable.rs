@@ -0,0 +1,306 @@
+use crate::RespFrame;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// The default hash-max-listpack-entries.
+pub const DEFAULT_HASH_MAX_LISTPACK_ENTRIES: usize = 128;
+/// The default hash-max-listpack-value.
+pub const DEFAULT_HASH_MAX_LISTPACK_VALUE: usize = 64;
+
+/// The fields of a hash, with the optional per-field TTLs set by HEXPIRE. Setting or removing
+/// a field always drops its TTL.
+///
+/// While there are at most `max_listpack_entries` fields, none of them or their values longer
+/// than `max_listpack_value` bytes, they are stored as a listpack: a single array of pairs in
+/// the order they were added, searched in order, without the buckets and the hashes of a
+/// table. The first field that doesn't fit converts the hash to a hash table for good.
+#[derive(Debug, Clone)]
+pub struct HashTable {
+    repr: Repr,
+    expires: HashMap<String, i64>,
+    max_listpack_entries: usize,
+    max_listpack_value: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Repr {
+    Listpack(Vec<(String, RespFrame)>),
+    HashTable(HashMap<String, RespFrame>),
+}
+
+impl HashTable {
+    pub fn new() -> Self {
+        Self::with_limits(
+            DEFAULT_HASH_MAX_LISTPACK_ENTRIES,
+            DEFAULT_HASH_MAX_LISTPACK_VALUE,
+        )
+    }
+
+    /// An empty hash, converted past max_listpack_entries fields or a field or a value of
+    /// more than max_listpack_value bytes.
+    pub fn with_limits(max_listpack_entries: usize, max_listpack_value: usize) -> Self {
+        Self {
+            repr: Repr::Listpack(Vec::new()),
+            expires: HashMap::new(),
+            max_listpack_entries,
+            max_listpack_value,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Listpack(pairs) => pairs.len(),
+            Repr::HashTable(fields) => fields.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The encoding reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match &self.repr {
+            Repr::Listpack(_) => "listpack",
+            Repr::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&RespFrame> {
+        match &self.repr {
+            Repr::Listpack(pairs) => pairs.iter().find(|(f, _)| f == field).map(|(_, v)| v),
+            Repr::HashTable(fields) => fields.get(field),
+        }
+    }
+
+    pub fn contains_key(&self, field: &str) -> bool {
+        self.get(field).is_some()
+    }
+
+    /// The fields with their values, in the order they were added for a listpack and in no
+    /// particular order otherwise.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &RespFrame)> {
+        let (pairs, fields) = match &self.repr {
+            Repr::Listpack(pairs) => (Some(pairs.iter()), None),
+            Repr::HashTable(fields) => (None, Some(fields.iter())),
+        };
+        let pairs = pairs.into_iter().flatten().map(|(f, v)| (f, v));
+        pairs.chain(fields.into_iter().flatten())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(field, _)| field)
+    }
+
+    /// Set field to value, dropping its TTL. Returns the previous value.
+    pub fn insert(&mut self, field: String, value: RespFrame) -> Option<RespFrame> {
+        self.expires.remove(&field);
+        self.set(field, value)
+    }
+
+    /// Set field to value, keeping its TTL. Returns the previous value.
+    pub fn update(&mut self, field: String, value: RespFrame) -> Option<RespFrame> {
+        self.set(field, value)
+    }
+
+    pub fn remove(&mut self, field: &str) -> Option<RespFrame> {
+        self.expires.remove(field);
+        match &mut self.repr {
+            Repr::Listpack(pairs) => {
+                let pos = pairs.iter().position(|(f, _)| f == field)?;
+                Some(pairs.remove(pos).1)
+            }
+            Repr::HashTable(fields) => fields.remove(field),
+        }
+    }
+
+    /// The absolute expiration time of field in unix milliseconds.
+    pub fn expire_time(&self, field: &str) -> Option<i64> {
+        self.expires.get(field).copied()
+    }
+
+    /// Set the TTL of field, returns false if the field doesn't exist.
+    pub fn set_expire(&mut self, field: &str, at_ms: i64) -> bool {
+        if !self.contains_key(field) {
+            return false;
+        }
+        self.expires.insert(field.to_string(), at_ms);
+        true
+    }
+
+    pub fn persist(&mut self, field: &str) -> Option<i64> {
+        self.expires.remove(field)
+    }
+
+    /// The fields having a TTL, with their expiration time.
+    pub fn expires(&self) -> impl Iterator<Item = (&String, i64)> {
+        self.expires.iter().map(|(field, at)| (field, *at))
+    }
+
+    pub fn has_expired(&self, now_ms: i64) -> bool {
+        self.expires.values().any(|at| *at <= now_ms)
+    }
+
+    /// Remove the fields whose TTL has elapsed, returns how many were removed.
+    pub fn remove_expired(&mut self, now_ms: i64) -> usize {
+        let expired: Vec<String> = self
+            .expires
+            .iter()
+            .filter(|(_, at)| **at <= now_ms)
+            .map(|(field, _)| field.clone())
+            .collect();
+        for field in expired.iter() {
+            self.remove(field);
+        }
+        expired.len()
+    }
+
+    /// The size of the array of a listpack, None for a hash table, whose size is estimated
+    /// by sampling. The fields and the values are accounted for by the caller.
+    pub fn listpack_size(&self) -> Option<usize> {
+        match &self.repr {
+            Repr::Listpack(pairs) => Some(pairs.capacity() * size_of::<(String, RespFrame)>()),
+            Repr::HashTable(_) => None,
+        }
+    }
+
+    fn set(&mut self, field: String, value: RespFrame) -> Option<RespFrame> {
+        if let Repr::Listpack(pairs) = &mut self.repr {
+            let fits = field.len() <= self.max_listpack_value
+                && value_len(&value) <= self.max_listpack_value;
+            let room = pairs.len() < self.max_listpack_entries;
+            match pairs.iter_mut().find(|(f, _)| *f == field) {
+                Some((_, current)) if fits => return Some(std::mem::replace(current, value)),
+                None if fits && room => {
+                    pairs.push((field, value));
+                    return None;
+                }
+                _ => self.convert(),
+            }
+        }
+        match &mut self.repr {
+            Repr::HashTable(fields) => fields.insert(field, value),
+            Repr::Listpack(_) => unreachable!("converted above"),
+        }
+    }
+
+    fn convert(&mut self) {
+        if let Repr::Listpack(pairs) = &mut self.repr {
+            let fields = std::mem::take(pairs).into_iter().collect();
+            self.repr = Repr::HashTable(fields);
+        }
+    }
+}
+
+impl Default for HashTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// hashes are equal when their fields, values and TTLs are, whatever their encodings
+impl PartialEq for HashTable {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(field, value)| other.get(field) == Some(value))
+            && self.expires == other.expires
+    }
+}
+
+impl FromIterator<(String, RespFrame)> for HashTable {
+    fn from_iter<I: IntoIterator<Item = (String, RespFrame)>>(iter: I) -> Self {
+        let mut hash = HashTable::new();
+        hash.extend(iter);
+        hash
+    }
+}
+
+impl Extend<(String, RespFrame)> for HashTable {
+    fn extend<I: IntoIterator<Item = (String, RespFrame)>>(&mut self, iter: I) {
+        for (field, value) in iter {
+            self.insert(field, value);
+        }
+    }
+}
+
+// the length of a value as its string form, for hash-max-listpack-value
+fn value_len(value: &RespFrame) -> usize {
+    match value {
+        RespFrame::BulkString(s) => s.len(),
+        RespFrame::SimpleString(s) => s.len(),
+        RespFrame::Integer(n) => n.to_string().len(),
+        RespFrame::Double(d) => d.to_string().len(),
+        _ => usize::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_table_field_ttl() {
+        let mut hash: HashTable = [
+            ("f1".to_string(), RespFrame::Integer(1)),
+            ("f2".to_string(), RespFrame::Integer(2)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(hash.set_expire("f1", 100));
+        assert!(!hash.set_expire("missing", 100));
+        assert_eq!(hash.expire_time("f1"), Some(100));
+        assert!(!hash.has_expired(99));
+        assert!(hash.has_expired(100));
+
+        // overwriting a field drops its TTL
+        hash.insert("f1".to_string(), RespFrame::Integer(10));
+        assert_eq!(hash.expire_time("f1"), None);
+
+        hash.set_expire("f2", 100);
+        assert_eq!(hash.remove_expired(100), 1);
+        assert_eq!(hash.len(), 1);
+        assert_eq!(hash.expires().count(), 0);
+    }
+
+    #[test]
+    fn test_listpack_conversion() {
+        let mut hash = HashTable::with_limits(2, 8);
+        hash.insert("a".to_string(), RespFrame::Integer(1));
+        hash.insert("b".to_string(), RespFrame::BulkString(b"short".into()));
+        assert_eq!(hash.encoding(), "listpack");
+        // in the order they were added
+        assert_eq!(hash.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        hash.set_expire("a", 100);
+        assert_eq!(
+            hash.update("a".to_string(), RespFrame::Integer(2)),
+            Some(RespFrame::Integer(1))
+        );
+        assert_eq!(hash.expire_time("a"), Some(100));
+
+        // too many fields
+        let mut many = hash.clone();
+        many.insert("c".to_string(), RespFrame::Integer(3));
+        assert_eq!(many.encoding(), "hashtable");
+        assert_eq!(many.len(), 3);
+        assert_eq!(many.get("a"), Some(&RespFrame::Integer(2)));
+        assert_eq!(many.expire_time("a"), Some(100));
+        // a value too long
+        let long = RespFrame::BulkString(b"much too long".into());
+        hash.insert("b".to_string(), long.clone());
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(hash.get("b"), Some(&long));
+        // for good
+        hash.remove("b");
+        hash.persist("a");
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(
+            hash,
+            [("a".to_string(), RespFrame::Integer(2))]
+                .into_iter()
+                .collect::<HashTable>()
+        );
+    }
+}
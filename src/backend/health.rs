@@ -0,0 +1,57 @@
+use crate::Backend;
+use std::sync::atomic::Ordering;
+
+impl Backend {
+    /// Mark the server as accepting connections, once it listens, and no longer when it's
+    /// shutting down.
+    pub fn set_accepting(&self, accepting: bool) {
+        self.accepting.store(accepting, Ordering::Release);
+    }
+
+    /// Whether clients can be served: the server accepts connections, which it does once what
+    /// it persisted is loaded, and a replica isn't loading the keyspace of its master.
+    pub fn is_ready(&self) -> bool {
+        let syncing = self
+            .master_report()
+            .is_some_and(|master| master.sync_in_progress);
+        self.accepting.load(Ordering::Acquire) && !syncing
+    }
+
+    /// Ask the server to shut down, saving the keyspace if save, or if it's None and snapshots
+    /// are configured. Without saving, a script running is stopped whatever it wrote.
+    pub fn shutdown(&self, save: Option<bool>) {
+        if save == Some(false) {
+            self.script_abort();
+        }
+        *self.shutdown_save.lock() = save;
+        self.shutdown.notify_one();
+    }
+
+    /// Wait until SHUTDOWN asks the server to shut down, returning whether to save then.
+    pub async fn shutdown_requested(&self) -> Option<bool> {
+        self.shutdown.notified().await;
+        *self.shutdown_save.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_once_accepting() {
+        let backend = Backend::new();
+        assert!(!backend.is_ready());
+        backend.set_accepting(true);
+        assert!(backend.is_ready());
+        backend.set_accepting(false);
+        assert!(!backend.is_ready());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_requested() {
+        let backend = Backend::new();
+        backend.shutdown(Some(false));
+        assert_eq!(backend.shutdown_requested().await, Some(false));
+    }
+}
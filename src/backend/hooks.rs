@@ -0,0 +1,133 @@
+use crate::cmd::Command;
+use crate::{Backend, RespArray, RespFrame};
+use parking_lot::RwLock;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Run before a command with the client running it: a reply stops the command, the client
+/// getting the reply instead.
+pub type BeforeCommandHook = dyn Fn(&ClientState, &Command) -> Option<RespFrame> + Send + Sync;
+
+/// Run after a command with its reply and how long it took.
+pub type AfterCommandHook = dyn Fn(&Command, &RespFrame, Duration) + Send + Sync;
+
+/// The client running a command, as the hooks see it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientState {
+    pub id: u64,
+    /// Where it connected from, None for the connections not counted in CLIENT LIST.
+    pub addr: Option<SocketAddr>,
+    /// The user it authenticated as, None before it did when a password is required.
+    pub user: Option<String>,
+}
+
+/// The hooks applications embedding the server run around the commands of the connections,
+/// for custom authentication, auditing, shadowing the requests or metrics. Each list is
+/// replaced as a whole when a hook is added, so a running hook may add others.
+#[derive(Default)]
+pub struct CommandHooks {
+    before: RwLock<Arc<Vec<Arc<BeforeCommandHook>>>>,
+    after: RwLock<Arc<Vec<Arc<AfterCommandHook>>>>,
+}
+
+impl fmt::Debug for CommandHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandHooks")
+            .field("before", &self.before.read().len())
+            .field("after", &self.after.read().len())
+            .finish()
+    }
+}
+
+impl Backend {
+    /// Run hook before every command a connection runs, once it passed the ACL and cluster
+    /// checks, in the order the hooks were added. The first one replying stops the command,
+    /// and those after it aren't run. Queued commands are seen as they're queued.
+    pub fn add_before_command_hook(
+        &self,
+        hook: impl Fn(&ClientState, &Command) -> Option<RespFrame> + Send + Sync + 'static,
+    ) {
+        let mut before = self.hooks.before.write();
+        let mut hooks = Vec::clone(&before);
+        hooks.push(Arc::new(hook));
+        *before = Arc::new(hooks);
+    }
+
+    /// Run hook after every command a connection ran, with its reply, the frames of the
+    /// commands replying several, as SUBSCRIBE, in an array.
+    pub fn add_after_command_hook(
+        &self,
+        hook: impl Fn(&Command, &RespFrame, Duration) + Send + Sync + 'static,
+    ) {
+        let mut after = self.hooks.after.write();
+        let mut hooks = Vec::clone(&after);
+        hooks.push(Arc::new(hook));
+        *after = Arc::new(hooks);
+    }
+
+    pub fn has_after_command_hooks(&self) -> bool {
+        !self.hooks.after.read().is_empty()
+    }
+
+    // the reply of the first hook stopping the command the client of ID id is to run
+    pub(crate) fn before_command(&self, id: u64, cmd: &Command) -> Option<RespFrame> {
+        let hooks = self.hooks.before.read().clone();
+        if hooks.is_empty() {
+            return None;
+        }
+        let client = ClientState {
+            id,
+            addr: self.client_addr(id),
+            user: self.client_user(id),
+        };
+        hooks.iter().find_map(|hook| hook(&client, cmd))
+    }
+
+    pub(crate) fn after_command(&self, cmd: &Command, frames: &[RespFrame], elapsed: Duration) {
+        let hooks = self.hooks.after.read().clone();
+        let reply = match frames {
+            [frame] => frame.clone(),
+            frames => RespArray::new(frames.to_vec()).into(),
+        };
+        for hook in hooks.iter() {
+            hook(cmd, &reply, elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimpleError;
+    use parking_lot::Mutex;
+
+    #[test]
+    fn test_command_hooks() {
+        let backend = Backend::new();
+        let ping = Command::try_from(crate::backend::command(["PING"])).unwrap();
+        assert_eq!(backend.before_command(1, &ping), None);
+
+        backend.add_before_command_hook(|client, cmd| match cmd {
+            Command::Ping(_) if client.id == 2 => Some(SimpleError::new("ERR denied").into()),
+            _ => None,
+        });
+        backend.add_before_command_hook(|_, _| panic!("not reached once a hook replied"));
+        assert_eq!(
+            backend.before_command(2, &ping),
+            Some(SimpleError::new("ERR denied").into())
+        );
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        assert!(!backend.has_after_command_hooks());
+        backend.add_after_command_hook({
+            let seen = seen.clone();
+            move |cmd, reply, _| seen.lock().push((format!("{:?}", cmd), reply.clone()))
+        });
+        assert!(backend.has_after_command_hooks());
+        let frames = [RespFrame::Integer(1), RespFrame::Integer(2)];
+        backend.after_command(&ping, &frames, Duration::ZERO);
+        assert_eq!(seen.lock()[0].1, RespArray::new(frames.to_vec()).into());
+    }
+}
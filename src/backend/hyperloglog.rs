@@ -0,0 +1,358 @@
+use crate::backend::string::{store_string, string_bytes};
+use crate::{Backend, BackendError, NotifyFlags, RespFrame, Value};
+
+// the registers are indexed by the low bits of a hash, the others count the leading run of
+// zeroes
+const HLL_P: u32 = 14;
+const HLL_Q: u32 = 64 - HLL_P;
+const HLL_REGISTERS: usize = 1 << HLL_P;
+const HLL_BITS: usize = 6;
+const HLL_REGISTER_MAX: u8 = (1 << HLL_BITS) - 1;
+
+// the header, as Redis lays it out: the magic, the encoding, 3 unused bytes, then the cached
+// cardinality, little endian, whose most significant bit set marks it as stale
+const HLL_HDR_SIZE: usize = 16;
+const HLL_DENSE: u8 = 0;
+const HLL_SPARSE: u8 = 1;
+const HLL_DENSE_SIZE: usize = HLL_HDR_SIZE + (HLL_REGISTERS * HLL_BITS).div_ceil(8);
+
+const HLL_ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+/// The registers of a HyperLogLog, stored as a string in the dense encoding of Redis, so
+/// that its values can be dumped to and restored from it. Sparse ones are read too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hll {
+    registers: Vec<u8>,
+}
+
+impl Hll {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS],
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, BackendError> {
+        if bytes.len() < HLL_HDR_SIZE || &bytes[..4] != b"HYLL" {
+            return Err(BackendError::InvalidHll);
+        }
+        let data = &bytes[HLL_HDR_SIZE..];
+        match bytes[4] {
+            HLL_DENSE if bytes.len() == HLL_DENSE_SIZE => {
+                let registers = (0..HLL_REGISTERS).map(|i| dense_get(data, i)).collect();
+                Ok(Self { registers })
+            }
+            HLL_SPARSE => sparse_registers(data).map(|registers| Self { registers }),
+            _ => Err(BackendError::InvalidHll),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0; HLL_DENSE_SIZE];
+        bytes[..4].copy_from_slice(b"HYLL");
+        bytes[4] = HLL_DENSE;
+        // no cardinality cached
+        bytes[15] = 0x80;
+        for (i, &value) in self.registers.iter().enumerate() {
+            dense_set(&mut bytes[HLL_HDR_SIZE..], i, value);
+        }
+        bytes
+    }
+
+    // whether a register changed
+    fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmur_hash64a(element, 0xadc83b19);
+        let index = hash as usize & (HLL_REGISTERS - 1);
+        // the run of zeroes plus one, the bit past the Q bits ending it
+        let count = ((hash >> HLL_P) | 1 << HLL_Q).trailing_zeros() as u8 + 1;
+        if count > self.registers[index] {
+            self.registers[index] = count;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &Hll) {
+        for (register, &value) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(value);
+        }
+    }
+
+    // the estimator of Otmar Ertl's "New cardinality estimation algorithms for HyperLogLog
+    // sketches", as Redis computes it
+    fn count(&self) -> u64 {
+        let mut histogram = [0u32; HLL_Q as usize + 2];
+        for &value in &self.registers {
+            histogram[value as usize] += 1;
+        }
+        let m = HLL_REGISTERS as f64;
+        let mut z = m * tau((m - histogram[HLL_Q as usize + 1] as f64) / m);
+        for &registers in histogram[1..=HLL_Q as usize].iter().rev() {
+            z += registers as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        (HLL_ALPHA_INF * m * m / z).round() as u64
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let (mut y, mut z) = (1.0, x);
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if z == previous {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let (mut y, mut z) = (1.0, 1.0 - x);
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if z == previous {
+            return z / 3.0;
+        }
+    }
+}
+
+// the registers are 6 bits each, packed from the least significant bit of each byte
+fn dense_get(data: &[u8], i: usize) -> u8 {
+    let (byte, shift) = (i * HLL_BITS / 8, i * HLL_BITS % 8);
+    let low = data[byte] >> shift;
+    let high = data
+        .get(byte + 1)
+        .map_or(0, |b| b.checked_shl(8 - shift as u32).unwrap_or(0));
+    (low | high) & HLL_REGISTER_MAX
+}
+
+fn dense_set(data: &mut [u8], i: usize, value: u8) {
+    let (byte, shift) = (i * HLL_BITS / 8, i * HLL_BITS % 8);
+    data[byte] &= !(HLL_REGISTER_MAX << shift);
+    data[byte] |= value << shift;
+    if shift > 8 - HLL_BITS {
+        let rest = 8 - shift;
+        data[byte + 1] &= !(HLL_REGISTER_MAX >> rest);
+        data[byte + 1] |= value >> rest;
+    }
+}
+
+// the opcodes of the sparse encoding: runs of zero registers, short or long, then runs of
+// registers of a same value
+fn sparse_registers(data: &[u8]) -> Result<Vec<u8>, BackendError> {
+    let mut registers = Vec::with_capacity(HLL_REGISTERS);
+    let mut bytes = data.iter();
+    while let Some(&op) = bytes.next() {
+        let (len, value) = match op >> 6 {
+            0b00 => ((op & 0x3f) as usize + 1, 0),
+            0b01 => {
+                let &low = bytes.next().ok_or(BackendError::InvalidHll)?;
+                ((((op & 0x3f) as usize) << 8 | low as usize) + 1, 0)
+            }
+            _ => ((op & 0x3) as usize + 1, ((op >> 2) & 0x1f) + 1),
+        };
+        registers.extend(std::iter::repeat_n(value, len));
+    }
+    match registers.len() == HLL_REGISTERS {
+        true => Ok(registers),
+        false => Err(BackendError::InvalidHll),
+    }
+}
+
+// MurmurHash64A, the hash Redis gives the elements
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap_or_default());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, &byte) in rest.iter().enumerate() {
+            h ^= (byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+// the HyperLogLog in a string value
+fn hll_value(value: &Value) -> Result<Hll, BackendError> {
+    match value {
+        Value::String(RespFrame::BulkString(s)) => Hll::decode(s),
+        Value::String(v) => Hll::decode(&string_bytes(v)),
+        _ => Err(BackendError::WrongType),
+    }
+}
+
+impl Backend {
+    /// Add elements to the HyperLogLog at key, creating it if needed. Returns whether its
+    /// estimate may have changed: a register changed, or it was created.
+    pub fn pfadd(&self, key: &str, elements: &[Vec<u8>]) -> Result<bool, BackendError> {
+        let mut shard = self.write(key);
+        let (mut hll, created) = match shard.get(key) {
+            Some(obj) => (hll_value(obj)?, false),
+            None => (Hll::new(), true),
+        };
+        let mut changed = created;
+        for element in elements {
+            changed |= hll.add(element);
+        }
+        if changed {
+            store_string(self, &mut shard, key, hll.encode());
+            self.notify_keyspace_event(NotifyFlags::STRING, "pfadd", key);
+        }
+        Ok(changed)
+    }
+
+    /// The estimated count of unique elements added to the HyperLogLogs at keys, that of
+    /// their union for several. Missing keys are empty ones.
+    pub fn pfcount(&self, keys: &[String]) -> Result<u64, BackendError> {
+        let guard = self.read_many(keys.iter().map(String::as_str));
+        let mut union = Hll::new();
+        for key in keys {
+            let shard = guard.shard(key);
+            if shard.is_expired(key) {
+                continue;
+            }
+            if let Some(obj) = shard.get(key) {
+                obj.touch();
+                union.merge(&hll_value(obj)?);
+            }
+        }
+        Ok(union.count())
+    }
+
+    /// Merge the HyperLogLogs at sources into that at destination, creating it if needed.
+    pub fn pfmerge(&self, destination: &str, sources: &[String]) -> Result<(), BackendError> {
+        let keys = sources.iter().map(String::as_str).chain([destination]);
+        let mut guard = self.write_many(keys);
+        let mut union = Hll::new();
+        for key in sources.iter().map(String::as_str).chain([destination]) {
+            if let Some(obj) = guard.shard_ref(key).get(key) {
+                union.merge(&hll_value(obj)?);
+            }
+        }
+        store_string(self, guard.shard(destination), destination, union.encode());
+        self.notify_keyspace_event(NotifyFlags::STRING, "pfadd", destination);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    fn elements(range: std::ops::Range<u32>) -> Vec<Vec<u8>> {
+        range
+            .map(|i| format!("element:{}", i).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_hll_encoding() -> Result<(), BackendError> {
+        let mut hll = Hll::new();
+        for element in elements(0..1000) {
+            hll.add(&element);
+        }
+        let bytes = hll.encode();
+        assert_eq!(bytes.len(), HLL_DENSE_SIZE);
+        assert_eq!(Hll::decode(&bytes)?, hll);
+        assert_eq!(Hll::decode(b"HYLL"), Err(BackendError::InvalidHll));
+        assert_eq!(Hll::decode(&bytes[1..]), Err(BackendError::InvalidHll));
+
+        // the sparse encoding of a register at 3 amid zeroes, as Redis writes it
+        let mut sparse = bytes[..HLL_HDR_SIZE].to_vec();
+        sparse[4] = HLL_SPARSE;
+        sparse.extend([0x40 | 0x03, 0xe7, 0x80 | (2 << 2), 0x40 | 0x3c, 0x16]);
+        let hll = Hll::decode(&sparse)?;
+        assert_eq!(hll.registers[1000], 3);
+        assert_eq!(hll.registers.iter().filter(|&&r| r > 0).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pfadd_pfcount() -> Result<(), BackendError> {
+        let backend = Backend::new();
+        assert!(backend.pfadd("h", &[])?);
+        assert_eq!(backend.pfcount(&["h".to_string()])?, 0);
+        assert!(!backend.pfadd("h", &[])?);
+        assert!(backend.pfadd("h", &[b"a".to_vec(), b"b".to_vec()])?);
+        assert!(!backend.pfadd("h", &[b"a".to_vec()])?);
+        assert_eq!(backend.pfcount(&["h".to_string()])?, 2);
+
+        backend.pfadd("big", &elements(0..10000))?;
+        let count = backend.pfcount(&["big".to_string()])? as f64;
+        assert!(
+            (count - 10000.0).abs() < 10000.0 * 0.02,
+            "estimated {}",
+            count
+        );
+
+        backend.pfadd("other", &elements(5000..15000))?;
+        let keys = [
+            "big".to_string(),
+            "other".to_string(),
+            "missing".to_string(),
+        ];
+        let count = backend.pfcount(&keys)? as f64;
+        assert!(
+            (count - 15000.0).abs() < 15000.0 * 0.02,
+            "estimated {}",
+            count
+        );
+
+        backend.set("s".to_string(), BulkString::from("v").into());
+        assert_eq!(backend.pfadd("s", &[]), Err(BackendError::InvalidHll));
+        assert_eq!(
+            backend.pfcount(&["s".to_string()]),
+            Err(BackendError::InvalidHll)
+        );
+        backend.lpush("l", vec![BulkString::from("a").into()])?;
+        assert_eq!(backend.pfadd("l", &[]), Err(BackendError::WrongType));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pfmerge() -> Result<(), BackendError> {
+        let backend = Backend::new();
+        backend.pfadd("a", &elements(0..100))?;
+        backend.pfadd("b", &elements(50..150))?;
+        backend.pfmerge("dest", &["a".to_string(), "b".to_string()])?;
+        let merged = backend.pfcount(&["dest".to_string()])?;
+        let union = backend.pfcount(&["a".to_string(), "b".to_string()])?;
+        assert_eq!(merged, union);
+        assert!((merged as i64 - 150).abs() <= 3);
+
+        // the destination's own registers are kept
+        backend.pfmerge("dest", &["missing".to_string()])?;
+        assert_eq!(backend.pfcount(&["dest".to_string()])?, merged);
+        backend.pfmerge("empty", &[])?;
+        assert_eq!(backend.pfcount(&["empty".to_string()])?, 0);
+        Ok(())
+    }
+}
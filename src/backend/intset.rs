@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::mem::size_of;
+
+/// The default set-max-intset-entries.
+pub const DEFAULT_SET_MAX_INTSET_ENTRIES: usize = 512;
+
+/// The members of a set. While every member is the canonical form of an integer and there are
+/// at most `max_intset_entries` of them, they are stored as a sorted array of integers: eight
+/// bytes a member instead of a string and a table entry. The first member that doesn't fit
+/// converts the set to a hash table for good.
+#[derive(Debug, Clone)]
+pub struct SetMembers {
+    repr: Repr,
+    max_intset_entries: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Repr {
+    IntSet(Vec<i64>),
+    HashTable(HashSet<String>),
+}
+
+impl SetMembers {
+    pub fn new(max_intset_entries: usize) -> Self {
+        Self {
+            repr: Repr::IntSet(Vec::new()),
+            max_intset_entries,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::IntSet(ints) => ints.len(),
+            Repr::HashTable(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The encoding reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match &self.repr {
+            Repr::IntSet(_) => "intset",
+            Repr::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        match &self.repr {
+            Repr::IntSet(ints) => {
+                canonical_int(member).is_some_and(|n| ints.binary_search(&n).is_ok())
+            }
+            Repr::HashTable(set) => set.contains(member),
+        }
+    }
+
+    /// Add member, returns whether it was new.
+    pub fn insert(&mut self, member: String) -> bool {
+        if let Repr::IntSet(ints) = &mut self.repr {
+            match canonical_int(&member).map(|n| (n, ints.binary_search(&n))) {
+                Some((_, Ok(_))) => return false,
+                Some((n, Err(pos))) if ints.len() < self.max_intset_entries => {
+                    ints.insert(pos, n);
+                    return true;
+                }
+                _ => self.convert(),
+            }
+        }
+        match &mut self.repr {
+            Repr::HashTable(set) => set.insert(member),
+            Repr::IntSet(_) => unreachable!("converted above"),
+        }
+    }
+
+    /// Remove member, returns whether it was there.
+    pub fn remove(&mut self, member: &str) -> bool {
+        match &mut self.repr {
+            Repr::IntSet(ints) => match canonical_int(member).map(|n| ints.binary_search(&n)) {
+                Some(Ok(pos)) => {
+                    ints.remove(pos);
+                    true
+                }
+                _ => false,
+            },
+            Repr::HashTable(set) => set.remove(member),
+        }
+    }
+
+    /// The members, in ascending order for an intset and in no particular order otherwise.
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        let (ints, strings) = match &self.repr {
+            Repr::IntSet(ints) => (Some(ints.iter()), None),
+            Repr::HashTable(set) => (None, Some(set.iter())),
+        };
+        let ints = ints.into_iter().flatten();
+        let strings = strings.into_iter().flatten();
+        ints.map(|n| Cow::Owned(n.to_string()))
+            .chain(strings.map(|member| Cow::Borrowed(member.as_str())))
+    }
+
+    /// The heap size of an intset, None for a hash table, whose size is estimated by sampling.
+    pub fn intset_heap_size(&self) -> Option<usize> {
+        match &self.repr {
+            Repr::IntSet(ints) => Some(ints.capacity() * size_of::<i64>()),
+            Repr::HashTable(_) => None,
+        }
+    }
+
+    fn convert(&mut self) {
+        if let Repr::IntSet(ints) = &self.repr {
+            self.repr = Repr::HashTable(ints.iter().map(|n| n.to_string()).collect());
+        }
+    }
+}
+
+impl Default for SetMembers {
+    fn default() -> Self {
+        Self::new(DEFAULT_SET_MAX_INTSET_ENTRIES)
+    }
+}
+
+// sets are equal when their members are, whatever their encodings
+impl PartialEq for SetMembers {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|member| other.contains(&member))
+    }
+}
+
+impl<T: Into<String>> FromIterator<T> for SetMembers {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = SetMembers::default();
+        for member in iter {
+            set.insert(member.into());
+        }
+        set
+    }
+}
+
+// the integer whose canonical form is member, if any: no sign but `-`, no leading zeros
+fn canonical_int(member: &str) -> Option<i64> {
+    member
+        .parse()
+        .ok()
+        .filter(|n: &i64| n.to_string() == member)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intset() {
+        let mut set = SetMembers::new(3);
+        assert!(set.insert("3".to_string()));
+        assert!(set.insert("-1".to_string()));
+        assert!(!set.insert("3".to_string()));
+        assert_eq!(set.encoding(), "intset");
+        assert!(set.contains("-1"));
+        // not the canonical form of an integer
+        assert!(!set.contains("03"));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec!["-1", "3"]);
+
+        assert!(set.remove("-1"));
+        assert!(!set.remove("+3"));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_intset_conversion() {
+        let mut set = SetMembers::new(3);
+        set.insert("1".to_string());
+        set.insert("01".to_string());
+        assert_eq!(set.encoding(), "hashtable");
+        assert!(set.contains("1") && set.contains("01"));
+
+        // too many members
+        let mut set = SetMembers::new(3);
+        for member in ["1", "2", "3"] {
+            set.insert(member.to_string());
+        }
+        assert_eq!(set.encoding(), "intset");
+        set.insert("4".to_string());
+        assert_eq!(set.encoding(), "hashtable");
+
+        // equal to the same members as an intset
+        let intset: SetMembers = ["4", "3", "2", "1"].into_iter().collect();
+        assert_eq!(intset.encoding(), "intset");
+        assert_eq!(set, intset);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::backend::now_ms;
+use crate::Backend;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// How many samples are kept per event, a second each at most.
+pub const LATENCY_HISTORY_LEN: usize = 160;
+
+/// The latency spikes of the events taking at least `latency-monitor-threshold` milliseconds.
+#[derive(Debug, Default)]
+pub struct Latency {
+    // milliseconds, 0 to monitor nothing
+    threshold: AtomicU64,
+    events: Mutex<BTreeMap<&'static str, EventHistory>>,
+}
+
+#[derive(Debug, Default)]
+struct EventHistory {
+    samples: VecDeque<LatencySample>,
+    // the highest of all the samples, dropped ones included
+    max: u64,
+}
+
+/// A spike: when it happened, as a unix time in seconds, and how long it took in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySample {
+    pub time: i64,
+    pub latency: u64,
+}
+
+/// The last spike of an event, as LATENCY LATEST reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyLatest {
+    pub event: String,
+    pub time: i64,
+    pub latency: u64,
+    pub max: u64,
+}
+
+// what a latency sample measures
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum LatencyEvent {
+    // the execution of a command, what it spent blocked aside
+    Command,
+    // copying the keyspace for a background save, rewrite or full sync
+    Fork,
+}
+
+impl LatencyEvent {
+    fn name(self) -> &'static str {
+        match self {
+            LatencyEvent::Command => "command",
+            LatencyEvent::Fork => "fork",
+        }
+    }
+}
+
+impl Backend {
+    /// The latency in milliseconds from which events are sampled, 0 if they aren't.
+    pub fn latency_monitor_threshold(&self) -> u64 {
+        self.latency.threshold.load(Ordering::Relaxed)
+    }
+
+    pub fn set_latency_monitor_threshold(&self, ms: u64) {
+        self.latency.threshold.store(ms, Ordering::Relaxed);
+    }
+
+    // sample the event if it took the threshold or more, the spikes of a same second merged
+    pub(crate) fn latency_sample(&self, event: LatencyEvent, elapsed: Duration) {
+        let threshold = self.latency_monitor_threshold();
+        let latency = elapsed.as_millis() as u64;
+        if threshold == 0 || latency < threshold {
+            return;
+        }
+        let time = now_ms() / 1000;
+        let mut events = self.latency.events.lock();
+        let history = events.entry(event.name()).or_default();
+        history.max = history.max.max(latency);
+        match history.samples.back_mut() {
+            Some(last) if last.time == time => last.latency = last.latency.max(latency),
+            _ => {
+                if history.samples.len() == LATENCY_HISTORY_LEN {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back(LatencySample { time, latency });
+            }
+        }
+    }
+
+    /// The last spike of every event sampled, by event name.
+    pub fn latency_latest(&self) -> Vec<LatencyLatest> {
+        let events = self.latency.events.lock();
+        events
+            .iter()
+            .filter_map(|(event, history)| {
+                let last = history.samples.back()?;
+                Some(LatencyLatest {
+                    event: event.to_string(),
+                    time: last.time,
+                    latency: last.latency,
+                    max: history.max,
+                })
+            })
+            .collect()
+    }
+
+    /// The spikes of event, oldest first.
+    pub fn latency_history(&self, event: &str) -> Vec<LatencySample> {
+        let events = self.latency.events.lock();
+        events
+            .get(event)
+            .map(|history| history.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Forget the spikes of the events, of all of them if there's none. Returns how many
+    /// events had some.
+    pub fn latency_reset(&self, events: &[String]) -> usize {
+        let mut history = self.latency.events.lock();
+        if events.is_empty() {
+            let reset = history.len();
+            history.clear();
+            return reset;
+        }
+        events
+            .iter()
+            .filter(|event| {
+                history
+                    .remove(event.to_ascii_lowercase().as_str())
+                    .is_some()
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_samples() {
+        let backend = Backend::new();
+        backend.latency_sample(LatencyEvent::Command, Duration::from_millis(500));
+        assert!(backend.latency_latest().is_empty());
+
+        backend.set_latency_monitor_threshold(100);
+        backend.latency_sample(LatencyEvent::Command, Duration::from_millis(50));
+        backend.latency_sample(LatencyEvent::Command, Duration::from_millis(120));
+        backend.latency_sample(LatencyEvent::Command, Duration::from_millis(300));
+        backend.latency_sample(LatencyEvent::Fork, Duration::from_millis(100));
+        let latest = backend.latency_latest();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].event, "command");
+        assert_eq!((latest[0].latency, latest[0].max), (300, 300));
+        assert_eq!(latest[1].event, "fork");
+
+        // the spikes of a same second are one sample
+        let history = backend.latency_history("command");
+        assert!(history.len() <= 2);
+        assert_eq!(history.last().unwrap().latency, 300);
+        assert!(backend.latency_history("nope").is_empty());
+
+        assert_eq!(
+            backend.latency_reset(&["FORK".to_string(), "nope".to_string()]),
+            1
+        );
+        assert_eq!(backend.latency_reset(&[]), 1);
+        assert!(backend.latency_latest().is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::backend::string::string_bytes;
+use crate::{Backend, BackendError, Value};
+
+/// The longest common subsequence of two strings, see `Backend::lcs`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lcs {
+    pub string: Vec<u8>,
+    /// The matching ranges, from the end of the strings to their start.
+    pub matches: Vec<LcsMatch>,
+}
+
+/// A run of the subsequence that's contiguous in both strings, with inclusive byte ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LcsMatch {
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+}
+
+impl LcsMatch {
+    pub fn match_len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+}
+
+impl Backend {
+    /// The longest common subsequence of the strings at key1 and key2, missing keys are empty
+    /// strings. Only matching ranges of at least min_match_len bytes are reported.
+    pub fn lcs(&self, key1: &str, key2: &str, min_match_len: usize) -> Result<Lcs, BackendError> {
+        let a = self.string_or_empty(key1)?;
+        let b = self.string_or_empty(key2)?;
+        lcs(&a, &b, min_match_len)
+    }
+
+    fn string_or_empty(&self, key: &str) -> Result<Vec<u8>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key).map(|obj| &**obj) {
+            None => Ok(Vec::new()),
+            Some(Value::String(v)) => Ok(string_bytes(v)),
+            Some(_) => Err(BackendError::WrongType),
+        }
+    }
+}
+
+// the dynamic programming algorithm redis uses: table[i][j] is the LCS length of a[..i] and
+// b[..j], the subsequence and its ranges are then read walking the table back from the end
+fn lcs(a: &[u8], b: &[u8], min_match_len: usize) -> Result<Lcs, BackendError> {
+    let width = b.len() + 1;
+    let size = (a.len() + 1)
+        .checked_mul(width)
+        .ok_or(BackendError::LcsTooLong)?;
+    let mut table = vec![0u32; size];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+
+    let mut string = Vec::with_capacity(table[size - 1] as usize);
+    let mut matches = Vec::new();
+    let mut range: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        let mut emit = false;
+        if a[i - 1] == b[j - 1] {
+            string.push(a[i - 1]);
+            match range.as_mut() {
+                None => {
+                    range = Some(LcsMatch {
+                        a: (i - 1, i - 1),
+                        b: (j - 1, j - 1),
+                    })
+                }
+                // extend the current range backward while it's contiguous in both strings
+                Some(r) if r.a.0 == i && r.b.0 == j => {
+                    r.a.0 -= 1;
+                    r.b.0 -= 1;
+                }
+                Some(_) => emit = true,
+            }
+            if range.is_some_and(|r| r.a.0 == 0 || r.b.0 == 0) {
+                emit = true;
+            }
+            i -= 1;
+            j -= 1;
+        } else {
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            emit = range.is_some();
+        }
+
+        if emit {
+            if let Some(r) = range.take() {
+                if r.match_len() >= min_match_len {
+                    matches.push(r);
+                }
+            }
+        }
+    }
+
+    string.reverse();
+    Ok(Lcs { string, matches })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+
+    #[test]
+    fn test_lcs() {
+        let backend = Backend::new();
+        backend.set(
+            "key1".to_string(),
+            RespFrame::BulkString(b"ohmytext".into()),
+        );
+        backend.set(
+            "key2".to_string(),
+            RespFrame::BulkString(b"mynewtext".into()),
+        );
+
+        let lcs = backend.lcs("key1", "key2", 0).unwrap();
+        assert_eq!(lcs.string, b"mytext");
+        assert_eq!(
+            lcs.matches,
+            vec![
+                LcsMatch {
+                    a: (4, 7),
+                    b: (5, 8)
+                },
+                LcsMatch {
+                    a: (2, 3),
+                    b: (0, 1)
+                },
+            ]
+        );
+
+        let lcs = backend.lcs("key1", "key2", 4).unwrap();
+        assert_eq!(lcs.matches.len(), 1);
+
+        assert_eq!(backend.lcs("key1", "missing", 0), Ok(Lcs::default()));
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(backend.lcs("key1", "h", 0), Err(BackendError::WrongType));
+    }
+}
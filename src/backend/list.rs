@@ -0,0 +1,592 @@
+use crate::backend::{command, string_bytes};
+use crate::{Backend, BackendError, BulkString, NotifyFlags, QuickList, RespFrame, Shard, Value};
+use std::sync::atomic::Ordering;
+
+/// An end of a list, as in the LEFT and RIGHT arguments of LMOVE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListSide {
+    Left,
+    Right,
+}
+
+impl ListSide {
+    /// Whether the side is the head of the list.
+    pub fn is_head(self) -> bool {
+        self == ListSide::Left
+    }
+}
+
+impl Backend {
+    /// The list-max-listpack-size the lists created from now on are split with.
+    pub fn list_max_listpack_size(&self) -> i64 {
+        self.list_max_listpack_size.load(Ordering::Relaxed)
+    }
+
+    pub fn set_list_max_listpack_size(&self, fill: i64) {
+        self.list_max_listpack_size.store(fill, Ordering::Relaxed);
+    }
+
+    pub(crate) fn new_list(&self) -> QuickList {
+        QuickList::new(self.list_max_listpack_size())
+    }
+
+    /// Push values to the head of the list at key one after the other, creating it if needed.
+    /// Returns the length of the list.
+    pub fn lpush(&self, key: &str, values: Vec<RespFrame>) -> Result<usize, BackendError> {
+        self.push(key, values, true)
+    }
+
+    /// Push values to the tail of the list at key, creating it if needed.
+    pub fn rpush(&self, key: &str, values: Vec<RespFrame>) -> Result<usize, BackendError> {
+        self.push(key, values, false)
+    }
+
+    /// Pop up to count elements from the head of the list, `None` if the key doesn't exist.
+    pub fn lpop(&self, key: &str, count: usize) -> Result<Option<Vec<RespFrame>>, BackendError> {
+        self.pop(key, count, true)
+    }
+
+    pub fn rpop(&self, key: &str, count: usize) -> Result<Option<Vec<RespFrame>>, BackendError> {
+        self.pop(key, count, false)
+    }
+
+    pub fn llen(&self, key: &str) -> Result<usize, BackendError> {
+        Ok(self.read_list(key, |list| list.len())?.unwrap_or(0))
+    }
+
+    /// The elements between start and stop inclusive, negative indexes count from the tail.
+    pub fn lrange(&self, key: &str, start: i64, stop: i64) -> Result<Vec<RespFrame>, BackendError> {
+        Ok(self
+            .read_list(key, |list| match list_range(start, stop, list.len()) {
+                Some((start, stop)) => list
+                    .range(start, stop - start + 1)
+                    .map(element_frame)
+                    .collect(),
+                None => Vec::new(),
+            })?
+            .unwrap_or_default())
+    }
+
+    pub fn lindex(&self, key: &str, index: i64) -> Result<Option<RespFrame>, BackendError> {
+        Ok(self
+            .read_list(key, |list| {
+                list_index(index, list.len())
+                    .and_then(|i| list.get(i))
+                    .map(element_frame)
+            })?
+            .flatten())
+    }
+
+    /// The positions of the elements equal to element, skipping the first rank - 1 matches.
+    /// A negative rank searches from the tail. At most count positions are returned and
+    /// maxlen elements compared, 0 meaning no limit for both.
+    pub fn lpos(
+        &self,
+        key: &str,
+        element: &RespFrame,
+        rank: i64,
+        count: usize,
+        maxlen: usize,
+    ) -> Result<Vec<usize>, BackendError> {
+        let element = string_bytes(element);
+        let count = if count == 0 { usize::MAX } else { count };
+        let maxlen = if maxlen == 0 { usize::MAX } else { maxlen };
+        let skip = rank.unsigned_abs().saturating_sub(1) as usize;
+
+        Ok(self
+            .read_list(key, |list| {
+                let last = list.len().saturating_sub(1);
+                let matching = |(_, v): &(usize, &[u8])| *v == element;
+                let found: Box<dyn Iterator<Item = (usize, &[u8])>> = if rank < 0 {
+                    let positions = list.iter().rev().enumerate().map(|(i, v)| (last - i, v));
+                    Box::new(positions.take(maxlen).filter(matching))
+                } else {
+                    Box::new(list.iter().enumerate().take(maxlen).filter(matching))
+                };
+                found.skip(skip).take(count).map(|(i, _)| i).collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Atomically pop an element from one side of source and push it to one side of
+    /// destination, which may be the same list. Returns the element, `None` if source doesn't
+    /// exist.
+    pub fn lmove(
+        &self,
+        source: &str,
+        destination: &str,
+        from: ListSide,
+        to: ListSide,
+    ) -> Result<Option<RespFrame>, BackendError> {
+        let mut guard = self.write_many([source, destination]);
+        for key in [source, destination] {
+            match guard.shard(key).get(key).map(|obj| &**obj) {
+                None | Some(Value::List(_)) => {}
+                Some(_) => return Err(BackendError::WrongType),
+            }
+        }
+
+        let Some(value) = self.pop_one(guard.shard(source), source, from.is_head()) else {
+            return Ok(None);
+        };
+        self.push_to(
+            guard.shard(destination),
+            destination,
+            vec![value.clone()],
+            to.is_head(),
+        )?;
+        Ok(Some(value))
+    }
+
+    /// Finish a blocking move: push value, popped from source by `bpop`, to destination. If
+    /// destination no longer holds a list the value goes back to source.
+    pub fn lmove_popped(
+        &self,
+        source: &str,
+        destination: &str,
+        value: RespFrame,
+        from: ListSide,
+        to: ListSide,
+    ) -> Result<RespFrame, BackendError> {
+        let push = || match self.push(destination, vec![value.clone()], to.is_head()) {
+            Ok(_) => (Ok(value.clone()), Some((destination, to))),
+            Err(e) => match self.push(source, vec![value.clone()], from.is_head()) {
+                Ok(_) => (Err(e), Some((source, from))),
+                Err(e) => (Err(e), None),
+            },
+        };
+        let (result, _) = self.run_logged(|| {
+            self.propagate_effects(push, |(_, pushed)| match pushed {
+                Some((key, side)) => {
+                    let name = if side.is_head() { "LPUSH" } else { "RPUSH" };
+                    vec![command([
+                        name.as_bytes(),
+                        key.as_bytes(),
+                        &string_bytes(&value),
+                    ])]
+                }
+                None => Vec::new(),
+            })
+        });
+        result
+    }
+
+    /// Replace the element at index.
+    pub fn lset(&self, key: &str, index: i64, value: RespFrame) -> Result<(), BackendError> {
+        self.update_list(key, "lset", |list| match list_index(index, list.len()) {
+            Some(i) => {
+                list.set(i, &string_bytes(&value));
+                (Ok(()), true)
+            }
+            None => (Err(BackendError::OutOfRange), false),
+        })?
+        .unwrap_or(Err(BackendError::NoSuchKey))
+    }
+
+    /// Insert value before or after the first element equal to pivot. Returns the length of the
+    /// list, -1 if the pivot wasn't found and 0 if the key doesn't exist.
+    pub fn linsert(
+        &self,
+        key: &str,
+        before: bool,
+        pivot: &RespFrame,
+        value: RespFrame,
+    ) -> Result<i64, BackendError> {
+        let pivot = string_bytes(pivot);
+        Ok(self
+            .update_list(key, "linsert", |list| {
+                let position = list.iter().position(|v| v == pivot);
+                match position {
+                    Some(i) => {
+                        list.insert(if before { i } else { i + 1 }, &string_bytes(&value));
+                        (list.len() as i64, true)
+                    }
+                    None => (-1, false),
+                }
+            })?
+            .unwrap_or(0))
+    }
+
+    /// Remove the elements equal to value: the first count ones from the head if count is
+    /// positive, from the tail if it's negative, all of them if it's 0. Returns how many were
+    /// removed.
+    pub fn lrem(&self, key: &str, count: i64, value: &RespFrame) -> Result<usize, BackendError> {
+        let value = string_bytes(value);
+        let limit = match count {
+            0 => usize::MAX,
+            n => n.unsigned_abs() as usize,
+        };
+        Ok(self
+            .update_list(key, "lrem", |list| {
+                let mut matching: Vec<usize> = list
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| *v == value)
+                    .map(|(i, _)| i)
+                    .collect();
+                if count < 0 {
+                    matching.reverse();
+                }
+                matching.truncate(limit);
+                matching.sort_unstable();
+
+                // remove from the back so the remaining positions stay valid
+                for i in matching.iter().rev() {
+                    list.remove(*i);
+                }
+                (matching.len(), !matching.is_empty())
+            })?
+            .unwrap_or(0))
+    }
+
+    /// Keep only the elements between start and stop inclusive.
+    pub fn ltrim(&self, key: &str, start: i64, stop: i64) -> Result<(), BackendError> {
+        self.update_list(key, "ltrim", |list| {
+            match list_range(start, stop, list.len()) {
+                Some((start, stop)) => list.retain_range(start, stop - start + 1),
+                None => list.clear(),
+            }
+            ((), true)
+        })?;
+        Ok(())
+    }
+
+    fn push(&self, key: &str, values: Vec<RespFrame>, head: bool) -> Result<usize, BackendError> {
+        self.push_to(&mut self.write(key), key, values, head)
+    }
+
+    // push values to the list at key in the locked shard, then serve the clients blocked on it
+    pub(crate) fn push_to(
+        &self,
+        shard: &mut Shard,
+        key: &str,
+        values: Vec<RespFrame>,
+        head: bool,
+    ) -> Result<usize, BackendError> {
+        let list = match shard.get_mut(key) {
+            None => {
+                self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+                shard.insert(key.to_string(), Value::List(self.new_list()));
+                shard.get_mut(key).map(|obj| &mut **obj)
+            }
+            Some(obj) => {
+                obj.touch();
+                Some(&mut **obj)
+            }
+        };
+        let len = match list {
+            Some(Value::List(list)) => {
+                for value in values {
+                    if head {
+                        list.push_front(&string_bytes(&value));
+                    } else {
+                        list.push_back(&string_bytes(&value));
+                    }
+                }
+                list.len()
+            }
+            _ => return Err(BackendError::WrongType),
+        };
+
+        let event = if head { "lpush" } else { "rpush" };
+        self.notify_keyspace_event(NotifyFlags::LIST, event, key);
+        self.serve_blocked(shard, key);
+        Ok(len)
+    }
+
+    // pop one element from the list at key in the locked shard, deleting the key once empty
+    pub(crate) fn pop_one(&self, shard: &mut Shard, key: &str, head: bool) -> Option<RespFrame> {
+        self.pop_many(shard, key, 1, head).pop()
+    }
+
+    // pop up to count elements from the list at key in the locked shard, in pop order
+    pub(crate) fn pop_many(
+        &self,
+        shard: &mut Shard,
+        key: &str,
+        count: usize,
+        head: bool,
+    ) -> Vec<RespFrame> {
+        let (values, empty) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::List(list)) => (pop_elements(list, count, head), list.is_empty()),
+            _ => return Vec::new(),
+        };
+        if values.is_empty() {
+            return values;
+        }
+
+        let event = if head { "lpop" } else { "rpop" };
+        self.notify_keyspace_event(NotifyFlags::LIST, event, key);
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        values
+    }
+
+    fn pop(
+        &self,
+        key: &str,
+        count: usize,
+        head: bool,
+    ) -> Result<Option<Vec<RespFrame>>, BackendError> {
+        let event = if head { "lpop" } else { "rpop" };
+        self.update_list(key, event, |list| {
+            let popped = pop_elements(list, count, head);
+            let changed = !popped.is_empty();
+            (popped, changed)
+        })
+    }
+
+    // run f on the list at key, None if the key doesn't exist
+    pub(crate) fn read_list<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&QuickList) -> R,
+    ) -> Result<Option<R>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::List(list) => Ok(Some(f(list))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+
+    // run f on the list at key under the write lock, None if the key doesn't exist. f also
+    // tells whether it changed the list: the event is notified, and the key deleted once the
+    // list is empty.
+    fn update_list<R>(
+        &self,
+        key: &str,
+        event: &str,
+        f: impl FnOnce(&mut QuickList) -> (R, bool),
+    ) -> Result<Option<R>, BackendError> {
+        let mut shard = self.write(key);
+        let (ret, changed, empty) = match shard.get_mut(key) {
+            None => return Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &mut **obj {
+                    Value::List(list) => {
+                        let (ret, changed) = f(list);
+                        (ret, changed, list.is_empty())
+                    }
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+        };
+
+        if changed {
+            self.notify_keyspace_event(NotifyFlags::LIST, event, key);
+        }
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        Ok(Some(ret))
+    }
+}
+
+// list elements are stored as raw bytes and replied as bulk strings
+pub(crate) fn element_frame(value: &[u8]) -> RespFrame {
+    BulkString::new(value.to_vec()).into()
+}
+
+// pop up to count elements from one end of the list, in pop order
+fn pop_elements(list: &mut QuickList, count: usize, head: bool) -> Vec<RespFrame> {
+    (0..count)
+        .map_while(|_| {
+            if head {
+                list.pop_front()
+            } else {
+                list.pop_back()
+            }
+        })
+        .map(|value| BulkString::new(value).into())
+        .collect()
+}
+
+// the position of index in a list of len elements, negative indexes count from the tail
+fn list_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+// the inclusive range between start and stop clamped to the list, None if it's empty
+fn list_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        stop + len
+    } else {
+        stop.min(len - 1)
+    };
+    (start <= stop && start < len).then_some((start as usize, stop as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    fn list(values: &[&str]) -> Vec<RespFrame> {
+        values.iter().map(|v| bulk(v)).collect()
+    }
+
+    #[test]
+    fn test_push_pop_range() {
+        let backend = Backend::new();
+        assert_eq!(backend.lpop("l", 1), Ok(None));
+        assert_eq!(backend.rpush("l", list(&["b", "c"])), Ok(2));
+        assert_eq!(backend.lpush("l", list(&["a", "z"])), Ok(4));
+        assert_eq!(backend.lrange("l", 0, -1), Ok(list(&["z", "a", "b", "c"])));
+        assert_eq!(backend.lrange("l", -2, 100), Ok(list(&["b", "c"])));
+        assert_eq!(backend.lrange("l", 3, 1), Ok(vec![]));
+
+        assert_eq!(backend.lpop("l", 1), Ok(Some(list(&["z"]))));
+        assert_eq!(backend.rpop("l", 2), Ok(Some(list(&["c", "b"]))));
+        assert_eq!(backend.llen("l"), Ok(1));
+
+        // popping the last element deletes the key
+        assert_eq!(backend.rpop("l", 5), Ok(Some(list(&["a"]))));
+        assert!(!backend.contains_key("l"));
+    }
+
+    #[test]
+    fn test_lindex_lset_linsert() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.lset("l", 0, bulk("x")),
+            Err(BackendError::NoSuchKey)
+        );
+        assert_eq!(backend.linsert("l", true, &bulk("a"), bulk("x")), Ok(0));
+
+        backend.rpush("l", list(&["a", "b", "c"])).unwrap();
+        assert_eq!(backend.lindex("l", -1), Ok(Some(bulk("c"))));
+        assert_eq!(backend.lindex("l", 3), Ok(None));
+
+        assert_eq!(backend.lset("l", -2, bulk("B")), Ok(()));
+        assert_eq!(
+            backend.lset("l", 5, bulk("x")),
+            Err(BackendError::OutOfRange)
+        );
+
+        assert_eq!(backend.linsert("l", true, &bulk("a"), bulk("0")), Ok(4));
+        assert_eq!(backend.linsert("l", false, &bulk("c"), bulk("d")), Ok(5));
+        assert_eq!(backend.linsert("l", false, &bulk("x"), bulk("y")), Ok(-1));
+        assert_eq!(
+            backend.lrange("l", 0, -1),
+            Ok(list(&["0", "a", "B", "c", "d"]))
+        );
+    }
+
+    #[test]
+    fn test_list_encoding() {
+        let backend = Backend::new();
+        backend.set_list_max_listpack_size(4);
+        backend.rpush("l", list(&["a", "b", "c"])).unwrap();
+        assert_eq!(backend.object_encoding("l"), Some("listpack"));
+
+        backend.rpush("l", list(&["d", "e"])).unwrap();
+        assert_eq!(backend.object_encoding("l"), Some("quicklist"));
+        assert_eq!(
+            backend.lrange("l", 0, -1),
+            Ok(list(&["a", "b", "c", "d", "e"]))
+        );
+
+        backend.ltrim("l", 1, 2).unwrap();
+        assert_eq!(backend.object_encoding("l"), Some("listpack"));
+        assert_eq!(backend.lrange("l", 0, -1), Ok(list(&["b", "c"])));
+    }
+
+    #[test]
+    fn test_lmove() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.lmove("src", "dst", ListSide::Left, ListSide::Right),
+            Ok(None)
+        );
+
+        backend.rpush("src", list(&["a", "b", "c"])).unwrap();
+        assert_eq!(
+            backend.lmove("src", "dst", ListSide::Right, ListSide::Left),
+            Ok(Some(bulk("c")))
+        );
+        assert_eq!(
+            backend.lmove("src", "dst", ListSide::Left, ListSide::Left),
+            Ok(Some(bulk("a")))
+        );
+        assert_eq!(backend.lrange("dst", 0, -1), Ok(list(&["a", "c"])));
+
+        // rotating a list onto itself
+        backend.rpush("src", list(&["x", "y"])).unwrap();
+        assert_eq!(
+            backend.lmove("src", "src", ListSide::Left, ListSide::Right),
+            Ok(Some(bulk("b")))
+        );
+        assert_eq!(backend.lrange("src", 0, -1), Ok(list(&["x", "y", "b"])));
+
+        // a wrong destination type fails without popping
+        backend.set("s".to_string(), bulk("v"));
+        assert_eq!(
+            backend.lmove("src", "s", ListSide::Left, ListSide::Left),
+            Err(BackendError::WrongType)
+        );
+        assert_eq!(backend.llen("src"), Ok(3));
+        assert_eq!(
+            backend.lmove_popped("src", "s", bulk("z"), ListSide::Left, ListSide::Left),
+            Err(BackendError::WrongType)
+        );
+        assert_eq!(backend.lindex("src", 0), Ok(Some(bulk("z"))));
+    }
+
+    #[test]
+    fn test_lpos() {
+        let backend = Backend::new();
+        assert_eq!(backend.lpos("l", &bulk("a"), 1, 0, 0), Ok(vec![]));
+
+        backend
+            .rpush("l", list(&["a", "b", "c", "1", "2", "3", "c", "c"]))
+            .unwrap();
+        assert_eq!(backend.lpos("l", &bulk("c"), 1, 1, 0), Ok(vec![2]));
+        assert_eq!(backend.lpos("l", &bulk("c"), 2, 1, 0), Ok(vec![6]));
+        assert_eq!(backend.lpos("l", &bulk("c"), -1, 1, 0), Ok(vec![7]));
+        assert_eq!(backend.lpos("l", &bulk("c"), 1, 0, 0), Ok(vec![2, 6, 7]));
+        assert_eq!(backend.lpos("l", &bulk("c"), -2, 0, 0), Ok(vec![6, 2]));
+        assert_eq!(backend.lpos("l", &bulk("c"), 1, 0, 3), Ok(vec![2]));
+        assert_eq!(backend.lpos("l", &bulk("x"), 1, 0, 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_lrem_ltrim() {
+        let backend = Backend::new();
+        backend
+            .rpush("l", list(&["x", "a", "x", "b", "x", "c", "x"]))
+            .unwrap();
+
+        assert_eq!(backend.lrem("l", 1, &bulk("x")), Ok(1));
+        assert_eq!(backend.lrem("l", -2, &bulk("x")), Ok(2));
+        assert_eq!(backend.lrange("l", 0, -1), Ok(list(&["a", "x", "b", "c"])));
+        assert_eq!(backend.lrem("l", 0, &bulk("x")), Ok(1));
+        assert_eq!(backend.lrem("l", 0, &bulk("missing")), Ok(0));
+
+        backend.ltrim("l", 1, -1).unwrap();
+        assert_eq!(backend.lrange("l", 0, -1), Ok(list(&["b", "c"])));
+
+        // an empty range deletes the key
+        backend.ltrim("l", 5, 10).unwrap();
+        assert!(!backend.contains_key("l"));
+    }
+}
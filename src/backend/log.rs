@@ -0,0 +1,184 @@
+use crate::Backend;
+use parking_lot::{Mutex, RwLock};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::str::FromStr;
+use tokio::runtime::Handle;
+use tracing::level_filters::LevelFilter;
+use tracing::{info, warn, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// How much the server logs, as the `loglevel` of redis.conf.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Verbose,
+    #[default]
+    Notice,
+    Warning,
+    Nothing,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Verbose | LogLevel::Notice => LevelFilter::INFO,
+            LogLevel::Warning => LevelFilter::WARN,
+            LogLevel::Nothing => LevelFilter::OFF,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "verbose" => Ok(LogLevel::Verbose),
+            "notice" => Ok(LogLevel::Notice),
+            "warning" => Ok(LogLevel::Warning),
+            "nothing" => Ok(LogLevel::Nothing),
+            _ => Err("argument(s) must be one of the following: debug, verbose, notice, warning, nothing".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Verbose => "verbose",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Nothing => "nothing",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The level of the log, and the file it's written to if it isn't stdout.
+#[derive(Debug, Default)]
+pub struct Log {
+    level: RwLock<LogLevel>,
+    file: Mutex<Option<File>>,
+}
+
+/// Writes the log where the backend has it go, for a `tracing_subscriber` layer.
+#[derive(Debug, Clone)]
+pub struct LogWriter(Backend);
+
+impl Backend {
+    pub fn loglevel(&self) -> LogLevel {
+        *self.log.level.read()
+    }
+
+    pub fn set_loglevel(&self, level: LogLevel) {
+        *self.log.level.write() = level;
+    }
+
+    /// Whether an event or a span is logged at the level set now.
+    pub fn is_logged(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.loglevel().filter()
+    }
+
+    /// The writer of the log, to stdout until `open_log`.
+    pub fn log_writer(&self) -> LogWriter {
+        LogWriter(self.clone())
+    }
+
+    /// Write the log to `logfile` from now on, or to stdout if it's empty. The file is
+    /// reopened if it already was, after it was rotated.
+    pub fn open_log(&self) -> io::Result<()> {
+        let file = match self.logfile().as_str() {
+            "" => None,
+            logfile => Some(OpenOptions::new().create(true).append(true).open(logfile)?),
+        };
+        *self.log.file.lock() = file;
+        Ok(())
+    }
+
+    /// Reopen the log file on every SIGHUP, with the task waiting for them spawned on handle.
+    #[cfg(unix)]
+    pub fn reopen_log_on_sighup(&self, handle: &Handle) -> io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let _guard = handle.enter();
+        let mut hangups = signal(SignalKind::hangup())?;
+        let backend = self.clone();
+        handle.spawn(async move {
+            while hangups.recv().await.is_some() {
+                match backend.open_log() {
+                    Ok(()) => info!("log file reopened on SIGHUP"),
+                    Err(e) => warn!("failed to reopen the log file: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.0.log.file.lock() {
+            Some(file) => file.write(buf),
+            None => io::stdout().write(buf),
+        }
+    }
+
+    // a line at once, so lines written concurrently don't interleave
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match &mut *self.0.log.file.lock() {
+            Some(file) => file.write_all(buf),
+            None => io::stdout().lock().write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.0.log.file.lock() {
+            Some(file) => file.flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_log_file() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        assert_eq!(backend.loglevel(), LogLevel::Notice);
+        backend.set_loglevel("WARNING".parse().map_err(anyhow::Error::msg)?);
+        assert_eq!(backend.loglevel().to_string(), "warning");
+        assert!("loud".parse::<LogLevel>().is_err());
+
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.log", std::process::id()));
+        backend.config_load(vec![("logfile".to_string(), path.display().to_string())])?;
+        backend.open_log()?;
+        backend.log_writer().write_all(b"first\n")?;
+        // rotated, then reopened
+        let rotated = path.with_extension("log.1");
+        fs::rename(&path, &rotated)?;
+        backend.open_log()?;
+        backend.log_writer().write_all(b"second\n")?;
+        assert_eq!(fs::read_to_string(&rotated)?, "first\n");
+        assert_eq!(fs::read_to_string(&path)?, "second\n");
+        fs::remove_file(&path)?;
+        fs::remove_file(&rotated)?;
+
+        Ok(())
+    }
+}
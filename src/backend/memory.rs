@@ -0,0 +1,282 @@
+use crate::{Backend, CompactString, HashTable, Object, RespFrame, Shard, StreamId, Value};
+use std::mem::size_of;
+
+// overhead of one slot in a hash table: the key/value pair plus hashing metadata
+const TABLE_ENTRY_OVERHEAD: usize = 16;
+// the strong and weak counts allocated with a shared value
+const SHARED_VALUE_OVERHEAD: usize = 2 * size_of::<usize>();
+
+const BIG_KEY_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub keys: usize,
+    pub strings: usize,
+    pub hashes: usize,
+    pub lists: usize,
+    pub sets: usize,
+    pub zsets: usize,
+    pub streams: usize,
+    pub dataset_bytes: usize,
+    pub overhead_bytes: usize,
+    pub biggest_key: Option<(String, usize)>,
+}
+
+impl MemoryReport {
+    pub fn total_bytes(&self) -> usize {
+        self.dataset_bytes + self.overhead_bytes
+    }
+
+    pub fn bytes_per_key(&self) -> usize {
+        if self.keys == 0 {
+            return 0;
+        }
+        self.total_bytes() / self.keys
+    }
+}
+
+impl Backend {
+    /// Estimate the bytes used by the key and its value. Hashes with more fields than `samples`
+    /// are estimated from the average size of the first `samples` fields, 0 samples all of them.
+    pub fn memory_usage(&self, key: &str, samples: usize) -> Option<usize> {
+        let shard = self.read(key);
+        shard
+            .get(key)
+            .map(|obj| key_size(key) + value_size(obj, samples))
+    }
+
+    /// Walk the whole keyspace and account for every key.
+    pub fn memory_stats(&self) -> MemoryReport {
+        let mut stats = MemoryReport {
+            overhead_bytes: size_of::<Backend>(),
+            ..Default::default()
+        };
+
+        for shard in self.shards() {
+            let shard = shard.read();
+            stats.overhead_bytes += size_of::<Shard>() + shard.overhead();
+
+            for (key, obj) in shard.iter() {
+                let bytes = key_size(key) + value_size(obj, 0);
+                stats.keys += 1;
+                stats.dataset_bytes += bytes;
+                match &**obj {
+                    Value::String(_) => stats.strings += 1,
+                    Value::Hash(_) => stats.hashes += 1,
+                    Value::List(_) => stats.lists += 1,
+                    Value::Set(_) => stats.sets += 1,
+                    Value::ZSet(_) => stats.zsets += 1,
+                    Value::Stream(_) => stats.streams += 1,
+                }
+                if stats.biggest_key.as_ref().is_none_or(|(_, b)| bytes > *b) {
+                    stats.biggest_key = Some((key.to_string(), bytes));
+                }
+            }
+        }
+        stats
+    }
+
+    /// Human readable advice about the memory usage of the instance.
+    pub fn memory_doctor(&self) -> String {
+        let stats = self.memory_stats();
+        if stats.keys == 0 {
+            return "This instance is empty or is using very little memory, \
+                    there is nothing to diagnose yet."
+                .to_string();
+        }
+
+        let mut issues = Vec::new();
+        if let Some((key, bytes)) = stats.biggest_key.as_ref() {
+            if *bytes >= BIG_KEY_BYTES && *bytes * 2 >= stats.dataset_bytes {
+                issues.push(format!(
+                    " * Big key: {:?} uses {} bytes, more than half of the dataset. \
+                     Consider splitting it into smaller keys.",
+                    key, bytes
+                ));
+            }
+        }
+        if stats.overhead_bytes > stats.dataset_bytes {
+            issues.push(format!(
+                " * High overhead: {} bytes of overhead for {} bytes of data. \
+                 The keyspace holds many tiny keys, hashes could group them more efficiently.",
+                stats.overhead_bytes, stats.dataset_bytes
+            ));
+        }
+
+        if issues.is_empty() {
+            "No memory issues detected in this instance.".to_string()
+        } else {
+            format!(
+                "The following memory issues were detected:\n\n{}\n",
+                issues.join("\n")
+            )
+        }
+    }
+}
+
+fn key_size(key: &str) -> usize {
+    size_of::<CompactString>() + CompactString::heap_size_of(key) + TABLE_ENTRY_OVERHEAD
+}
+
+fn value_size(obj: &Object<Value>, samples: usize) -> usize {
+    size_of::<Object<Value>>()
+        + size_of::<Value>()
+        + SHARED_VALUE_OVERHEAD
+        + match &**obj {
+            Value::String(v) => frame_heap_size(v),
+            Value::Hash(hash) => hash_size(hash, samples),
+            Value::List(list) => list.heap_size(),
+            Value::Set(set) => set.intset_heap_size().unwrap_or_else(|| {
+                sampled_size(set.iter(), set.len(), samples, |member| {
+                    size_of::<String>() + member.len() + TABLE_ENTRY_OVERHEAD
+                })
+            }),
+            // a zset indexes every member twice: by name and by score
+            Value::ZSet(zset) => sampled_size(zset.iter(), zset.len(), samples, |(member, _)| {
+                2 * (size_of::<String>() + member.len() + size_of::<f64>()) + TABLE_ENTRY_OVERHEAD
+            }),
+            Value::Stream(stream) => {
+                sampled_size(stream.iter(), stream.len(), samples, |(_, fields)| {
+                    size_of::<StreamId>()
+                        + TABLE_ENTRY_OVERHEAD
+                        + fields
+                            .iter()
+                            .map(|(field, v)| {
+                                size_of::<String>()
+                                    + field.len()
+                                    + size_of::<RespFrame>()
+                                    + frame_heap_size(v)
+                            })
+                            .sum::<usize>()
+                })
+            }
+        }
+}
+
+fn hash_size(hash: &HashTable, samples: usize) -> usize {
+    let fields = match hash.listpack_size() {
+        // a listpack is small, its pairs are all counted
+        Some(size) => {
+            size + hash
+                .iter()
+                .map(|(field, v)| field.len() + frame_heap_size(v))
+                .sum::<usize>()
+        }
+        None => sampled_size(hash.iter(), hash.len(), samples, |(field, v)| {
+            size_of::<String>()
+                + field.len()
+                + size_of::<RespFrame>()
+                + frame_heap_size(v)
+                + TABLE_ENTRY_OVERHEAD
+        }),
+    };
+    // field TTLs are kept in a second table
+    let expires = hash
+        .expires()
+        .map(|(field, _)| {
+            size_of::<String>() + field.len() + size_of::<i64>() + TABLE_ENTRY_OVERHEAD
+        })
+        .sum::<usize>();
+    fields + expires
+}
+
+// estimate the size of len elements from the average size of the first samples ones
+fn sampled_size<T>(
+    elements: impl Iterator<Item = T>,
+    len: usize,
+    samples: usize,
+    size: impl Fn(T) -> usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+
+    let sampled = if samples == 0 { len } else { samples.min(len) };
+    let total: usize = elements.take(sampled).map(size).sum();
+    total / sampled * len
+}
+
+// heap bytes owned by a frame, the frame itself is accounted for by its container
+fn frame_heap_size(frame: &RespFrame) -> usize {
+    match frame {
+        RespFrame::SimpleString(s) => s.capacity(),
+        RespFrame::Error(e) => e.capacity(),
+        RespFrame::BulkString(s) => s.len(),
+        RespFrame::Array(a) => a
+            .iter()
+            .map(|f| size_of::<RespFrame>() + frame_heap_size(f))
+            .sum(),
+        RespFrame::Map(m) => m
+            .iter()
+            .map(|(k, v)| {
+                size_of::<String>() + k.capacity() + size_of::<RespFrame>() + frame_heap_size(v)
+            })
+            .sum(),
+        RespFrame::Set(s) => s
+            .iter()
+            .map(|f| size_of::<RespFrame>() + frame_heap_size(f))
+            .sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    #[test]
+    fn test_memory_usage() {
+        let backend = Backend::new();
+        backend.set("k".to_string(), RespFrame::BulkString(b"v".into()));
+        backend.set(
+            "big".to_string(),
+            RespFrame::BulkString(BulkString::new(vec![0; 4096])),
+        );
+        for i in 0..100 {
+            backend
+                .hset(
+                    "h".to_string(),
+                    format!("field{:03}", i),
+                    RespFrame::BulkString(b"value".into()),
+                )
+                .unwrap();
+        }
+
+        let small = backend.memory_usage("k", 0).unwrap();
+        let big = backend.memory_usage("big", 0).unwrap();
+        // both keys are inline, only the values tell them apart
+        assert!(big >= small + 4095);
+
+        // all fields have the same size, so sampling gives the exact estimate
+        let hash = backend.memory_usage("h", 0).unwrap();
+        assert_eq!(backend.memory_usage("h", 5), Some(hash));
+        assert!(backend.memory_usage("missing", 0).is_none());
+    }
+
+    #[test]
+    fn test_memory_stats_and_doctor() {
+        let backend = Backend::new();
+        assert!(backend.memory_doctor().contains("empty"));
+
+        backend.set("k".to_string(), RespFrame::BulkString(b"v".into()));
+        backend
+            .hset(
+                "h".to_string(),
+                "f".to_string(),
+                RespFrame::BulkString(b"v".into()),
+            )
+            .unwrap();
+        backend.set(
+            "big".to_string(),
+            RespFrame::BulkString(BulkString::new(vec![0; 2 * BIG_KEY_BYTES])),
+        );
+
+        let stats = backend.memory_stats();
+        assert_eq!(stats.keys, 3);
+        assert_eq!(stats.strings, 2);
+        assert_eq!(stats.hashes, 1);
+        assert_eq!(stats.biggest_key.as_ref().unwrap().0, "big");
+        assert!(backend.memory_doctor().contains("Big key"));
+    }
+}
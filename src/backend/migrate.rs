@@ -0,0 +1,359 @@
+use crate::backend::{command, now_ms};
+use crate::network::RespFrameCodec;
+use crate::{
+    decode_value_dump, encode_value_dump, Backend, BackendError, NotifyFlags, RespEncode, RespFrame,
+};
+use bytes::BytesMut;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tokio_util::codec::Decoder;
+
+/// How long a connection MIGRATE opened to a target is kept unused for the next ones.
+pub const MIGRATE_SOCKET_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// The connections MIGRATE keeps to its targets, by address.
+#[derive(Debug, Default)]
+pub struct Migrations {
+    sockets: Mutex<HashMap<String, MigrateSocket>>,
+}
+
+#[derive(Debug)]
+struct MigrateSocket {
+    stream: TcpStream,
+    // the database selected on the target, none until one is
+    db: Option<i64>,
+    last_use: Instant,
+}
+
+/// The options of RESTORE.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// The TTL of the key in milliseconds, none if 0.
+    pub ttl: i64,
+    /// Replace the key if it exists.
+    pub replace: bool,
+    /// The TTL is a unix time in milliseconds.
+    pub absttl: bool,
+}
+
+/// Where MIGRATE moves keys, and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrateOptions {
+    pub host: String,
+    pub port: u16,
+    pub db: i64,
+    /// How long connecting, and each write and read, may take in milliseconds.
+    pub timeout: u64,
+    /// Keep the keys here.
+    pub copy: bool,
+    /// Replace the keys on the target.
+    pub replace: bool,
+    /// The username, none for the default user, and the password to authenticate with.
+    pub auth: Option<(Option<String>, String)>,
+}
+
+impl Backend {
+    /// The value of key serialized as DUMP replies it, none if the key doesn't exist.
+    pub fn dump(&self, key: &str) -> Option<Vec<u8>> {
+        let shard = self.read(key);
+        shard.get(key).map(|obj| encode_value_dump(obj))
+    }
+
+    /// Create key with the value of a DUMP payload. An absolute TTL in the past deletes the
+    /// key instead.
+    pub fn restore(
+        &self,
+        key: String,
+        payload: &[u8],
+        options: RestoreOptions,
+    ) -> Result<(), BackendError> {
+        if options.ttl < 0 {
+            return Err(BackendError::InvalidTtl);
+        }
+        let mut shard = self.write(&key);
+        let exists = shard.contains_key(&key);
+        if exists && !options.replace {
+            return Err(BackendError::BusyKey);
+        }
+        let value = decode_value_dump(
+            payload,
+            || self.new_list(),
+            || self.new_set(),
+            || self.new_hash(),
+        )
+        .map_err(|_| BackendError::BadDumpPayload)?;
+        let expire_at = match options.ttl {
+            0 => None,
+            at if options.absttl => Some(at),
+            ttl => Some(now_ms() + ttl),
+        };
+
+        shard.remove(&key);
+        if expire_at.is_some_and(|at| at <= now_ms()) {
+            if exists {
+                self.notify_keyspace_event(NotifyFlags::GENERIC, "del", &key);
+            }
+            return Ok(());
+        }
+        if !exists {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+        }
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "restore", &key);
+        shard.insert(key.clone(), value);
+        if let Some(at) = expire_at {
+            shard.set_expire(key, at);
+        }
+        Ok(())
+    }
+
+    /// Move keys to another instance, sending it a RESTORE for each with its TTL, then delete
+    /// those restored unless copying. The connection to the target is kept for the next
+    /// MIGRATE to it, for up to MIGRATE_SOCKET_CACHE_TTL. Like in Redis, this blocks until the
+    /// target replies or the timeout elapses. Returns false if none of the keys exists.
+    pub fn migrate(&self, keys: &[String], options: &MigrateOptions) -> Result<bool, BackendError> {
+        let mut dumped = Vec::new();
+        for key in keys {
+            let shard = self.read(key);
+            let Some(obj) = shard.get(key) else {
+                continue;
+            };
+            let ttl = match shard.expire_time(key) {
+                // elapsing right now
+                Some(at) => (at - now_ms()).max(1),
+                None => 0,
+            };
+            dumped.push((key.clone(), encode_value_dump(obj), ttl));
+        }
+        if dumped.is_empty() {
+            return Ok(false);
+        }
+
+        let addr = format!("{}:{}", options.host, options.port);
+        let timeout = Duration::from_millis(options.timeout.max(1));
+        let mut socket = self.migrate_socket(&addr, timeout)?;
+        let mut requests = Vec::new();
+        if let Some((username, password)) = &options.auth {
+            requests.push(match username {
+                Some(username) => command(["AUTH", username, password]),
+                None => command(["AUTH", password]),
+            });
+        }
+        let select = socket.db != Some(options.db);
+        if select {
+            requests.push(command(["SELECT", &options.db.to_string()]));
+        }
+        for (key, payload, ttl) in &dumped {
+            let mut args = vec![
+                b"RESTORE".to_vec(),
+                key.as_bytes().to_vec(),
+                ttl.to_string().into_bytes(),
+                payload.clone(),
+            ];
+            if options.replace {
+                args.push(b"REPLACE".to_vec());
+            }
+            requests.push(command(args));
+        }
+
+        let mut data = Vec::new();
+        for request in requests.iter() {
+            data.extend(request.clone().encode());
+        }
+        if socket.stream.write_all(&data).is_err() {
+            return Err(BackendError::MigrateIo("writing to".to_string()));
+        }
+        let mut buf = BytesMut::new();
+        let mut replies = Vec::with_capacity(requests.len());
+        for _ in 0..requests.len() {
+            match read_reply(&mut socket.stream, &mut buf) {
+                Ok(reply) => replies.push(reply),
+                Err(_) => return Err(BackendError::MigrateIo("reading from".to_string())),
+            }
+        }
+
+        // the replies to AUTH and SELECT first, then to every RESTORE
+        let restores = replies.split_off(replies.len() - dumped.len());
+        if let Some(RespFrame::Error(e)) = replies.iter().find(|r| matches!(r, RespFrame::Error(_)))
+        {
+            return Err(BackendError::MigrateTarget(e.to_string()));
+        }
+        if select {
+            socket.db = Some(options.db);
+        }
+        let mut error = None;
+        let mut restored = Vec::new();
+        for ((key, _, _), reply) in dumped.into_iter().zip(restores) {
+            match reply {
+                RespFrame::Error(e) => error = error.or(Some(e.to_string())),
+                _ => restored.push(key),
+            }
+        }
+        if !options.copy {
+            self.del(&restored);
+        }
+        socket.last_use = Instant::now();
+        self.migrations.sockets.lock().insert(addr, socket);
+        match error {
+            Some(e) => Err(BackendError::MigrateTarget(e)),
+            None => Ok(true),
+        }
+    }
+
+    /// Close the connections MIGRATE kept, unused for MIGRATE_SOCKET_CACHE_TTL. The cron does
+    /// every second.
+    pub fn close_idle_migrate_sockets(&self) {
+        self.migrations
+            .sockets
+            .lock()
+            .retain(|_, socket| socket.last_use.elapsed() < MIGRATE_SOCKET_CACHE_TTL);
+    }
+
+    // the connection kept to addr, taken out for one MIGRATE, or a new one
+    fn migrate_socket(&self, addr: &str, timeout: Duration) -> Result<MigrateSocket, BackendError> {
+        if let Some(socket) = self.migrations.sockets.lock().remove(addr) {
+            return Ok(socket);
+        }
+        let connect = || -> io::Result<TcpStream> {
+            let addr = addr
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no address"))?;
+            let stream = TcpStream::connect_timeout(&addr, timeout)?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            stream.set_nodelay(true)?;
+            Ok(stream)
+        };
+        match connect() {
+            Ok(stream) => Ok(MigrateSocket {
+                stream,
+                db: None,
+                last_use: Instant::now(),
+            }),
+            Err(_) => Err(BackendError::MigrateConnect),
+        }
+    }
+}
+
+// read a reply from stream, with what was read of it already in buf
+fn read_reply(stream: &mut TcpStream, buf: &mut BytesMut) -> io::Result<RespFrame> {
+    let mut codec = RespFrameCodec::client();
+    let mut chunk = [0; 16 * 1024];
+    loop {
+        match codec.decode(buf) {
+            Ok(Some(frame)) => return Ok(frame),
+            Ok(None) => {}
+            Err(e) => return Err(io::Error::new(ErrorKind::InvalidData, e.to_string())),
+        }
+        match stream.read(&mut chunk)? {
+            0 => return Err(ErrorKind::UnexpectedEof.into()),
+            n => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Server;
+
+    #[test]
+    fn test_dump_and_restore() {
+        let backend = Backend::new();
+        assert_eq!(backend.dump("k"), None);
+        backend
+            .rpush("k", vec![RespFrame::BulkString("a".into())])
+            .unwrap();
+        let payload = backend.dump("k").unwrap();
+
+        let options = RestoreOptions::default();
+        assert_eq!(
+            backend.restore("k".to_string(), &payload, options),
+            Err(BackendError::BusyKey)
+        );
+        assert_eq!(
+            backend.restore("k2".to_string(), b"garbage", options),
+            Err(BackendError::BadDumpPayload)
+        );
+        let options = RestoreOptions {
+            ttl: 10_000,
+            ..options
+        };
+        assert_eq!(backend.restore("k2".to_string(), &payload, options), Ok(()));
+        assert_eq!(backend.lrange("k2", 0, -1), backend.lrange("k", 0, -1));
+        assert!(backend.expire_time("k2").flatten().is_some());
+
+        // replaced by a key whose TTL already elapsed
+        let options = RestoreOptions {
+            ttl: 1,
+            replace: true,
+            absttl: true,
+        };
+        assert_eq!(backend.restore("k2".to_string(), &payload, options), Ok(()));
+        assert!(!backend.contains_key("k2"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_migrate() {
+        let target = Backend::new();
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .backend(target.clone())
+            .build()
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run(std::future::pending()));
+
+        let source = Backend::new();
+        for key in ["a", "b"] {
+            source.set(key.to_string(), RespFrame::BulkString(key.into()));
+        }
+        source.expire_at("a", now_ms() + 10_000);
+        let options = MigrateOptions {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            db: 0,
+            timeout: 1000,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        let migrate = |keys: &[&str], options: &MigrateOptions| {
+            let (source, options) = (source.clone(), options.clone());
+            let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+            tokio::task::spawn_blocking(move || source.migrate(&keys, &options))
+        };
+
+        assert_eq!(
+            migrate(&["a", "missing"], &options).await.unwrap(),
+            Ok(true)
+        );
+        assert!(!source.contains_key("a"));
+        assert_eq!(target.get("a"), Ok(Some(RespFrame::BulkString("a".into()))));
+        assert!(target.expire_time("a").flatten().is_some());
+        assert_eq!(migrate(&["missing"], &options).await.unwrap(), Ok(false));
+
+        // the connection is kept for the next MIGRATE
+        assert_eq!(source.migrations.sockets.lock().len(), 1);
+        let copy = MigrateOptions {
+            copy: true,
+            ..options.clone()
+        };
+        assert_eq!(migrate(&["b"], &copy).await.unwrap(), Ok(true));
+        assert!(source.contains_key("b"));
+        assert!(matches!(
+            migrate(&["b"], &options).await.unwrap(),
+            Err(BackendError::MigrateTarget(e)) if e.starts_with("BUSYKEY")
+        ));
+        assert!(source.contains_key("b"));
+
+        let closed = MigrateOptions { port: 1, ..options };
+        assert_eq!(
+            migrate(&["b"], &closed).await.unwrap(),
+            Err(BackendError::MigrateConnect)
+        );
+    }
+}
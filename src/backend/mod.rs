@@ -1,15 +1,365 @@
+use crate::systemd::Supervised;
 use crate::RespFrame;
-use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU16, AtomicU32, AtomicU64, AtomicUsize};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+mod acl;
+mod allocator;
+mod aof;
+mod bitfield;
+mod bitmap;
+mod blocking;
+mod bus;
+mod check;
+mod clients;
+mod cluster;
+mod compact;
+mod config;
+mod connlimit;
+mod cron;
+mod custom;
+mod digest;
+mod expire;
+mod function;
+mod geo;
+mod glob;
+mod hash;
+mod hashtable;
+mod health;
+mod hooks;
+mod hyperloglog;
+mod intset;
+mod latency;
+mod lcs;
+mod list;
+mod log;
+mod memory;
+mod migrate;
+mod multikey;
+mod notify;
+mod object;
+mod persistence;
+mod pubsub;
+mod quicklist;
+mod rdb;
+mod replica;
+mod replication;
+mod script;
+mod sentinel;
+mod set;
+mod shard;
+mod slot;
+mod snapshot;
+mod sort;
+mod stats;
+mod storage;
+mod stream;
+mod string;
+mod tls;
+mod tracking;
+mod watch;
+mod workers;
+mod zset;
+
+pub use acl::*;
+pub use allocator::*;
+pub use aof::*;
+pub use bitfield::*;
+pub use bitmap::*;
+pub use blocking::*;
+pub use bus::*;
+pub use check::*;
+pub use clients::*;
+pub use cluster::*;
+pub use compact::*;
+pub use config::*;
+pub use connlimit::*;
+pub use cron::*;
+pub(crate) use custom::CustomCommands;
+#[cfg(feature = "modules")]
+pub use custom::{ModuleRegistry, MODULE_ABI_VERSION, MODULE_ENTRY_POINT};
+pub use expire::*;
+pub use function::*;
+pub use geo::*;
+pub use glob::*;
+pub use hash::*;
+pub use hashtable::*;
+pub use hooks::*;
+pub use intset::*;
+pub use latency::*;
+pub use lcs::*;
+pub use list::*;
+pub use log::*;
+pub use memory::*;
+pub use migrate::*;
+pub use multikey::*;
+pub use notify::*;
+pub use object::*;
+pub use persistence::*;
+pub use pubsub::*;
+pub use quicklist::*;
+pub use rdb::*;
+pub use replica::*;
+pub use replication::*;
+pub use script::*;
+pub use sentinel::*;
+pub use set::*;
+pub use shard::*;
+pub use slot::*;
+pub use snapshot::*;
+pub use sort::*;
+pub use stats::*;
+pub use storage::*;
+pub use stream::*;
+pub use string::*;
+pub use tls::*;
+pub use tracking::*;
+pub use watch::*;
+pub use workers::*;
+pub use zset::*;
+
+pub const DEFAULT_SHARDS: usize = 16;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BackendError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERR One or more scores can't be converted into double")]
+    NotDouble,
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+    #[error("ERR increment would produce NaN or Infinity")]
+    NanOrInfinity,
+    #[error("ERR hash value is not an integer")]
+    HashNotInteger,
+    #[error("ERR hash value is not a float")]
+    HashNotFloat,
+    #[error("ERR no such key")]
+    NoSuchKey,
+    #[error("ERR index out of range")]
+    OutOfRange,
+    #[error("ERR String too long for LCS")]
+    LcsTooLong,
+    #[error("ERR resulting score is not a number (NaN)")]
+    ScoreNan,
+    #[error("ERR The ID specified in XADD is equal or smaller than the target stream top item")]
+    StreamIdTooSmall,
+    #[error("ERR The ID specified in XADD must be greater than 0-0")]
+    StreamIdZero,
+    #[error("ERR The stream has exhausted the last possible ID, unable to add more items")]
+    StreamExhausted,
+    #[error("ERR The ID specified in XSETID is smaller than the target stream top item")]
+    XSetIdTooSmall,
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+    #[error("NOGROUP No such key '{0}' or consumer group '{1}'")]
+    NoGroup(String, String),
+    #[error("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")]
+    XGroupNoKey,
+    #[error("ERR The client ID you want redirect to does not exist")]
+    NoRedirectClient,
+    #[error("ERR CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled")]
+    CachingNotTracking,
+    #[error("ERR CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode.")]
+    CachingYesNotOptin,
+    #[error("ERR CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.")]
+    CachingNoNotOptout,
+    #[error("ERR Library '{0}' already exists")]
+    LibraryExists(String),
+    #[error("ERR Function {0} already exists")]
+    FunctionExists(String),
+    #[error("ERR {0}")]
+    FunctionLoad(String),
+    #[error("ERR Library not found")]
+    NoSuchLibrary,
+    #[error("ERR Function not found")]
+    NoSuchFunction,
+    #[error(
+        "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE."
+    )]
+    Busy,
+    #[error("NOTBUSY No scripts in execution right now.")]
+    NotBusy,
+    #[error("UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.")]
+    Unkillable,
+    #[error("ERR payload version or checksum are wrong")]
+    BadPayload,
+    #[error("ERR Background save already in progress")]
+    SaveInProgress,
+    #[error("ERR {0}")]
+    SaveFailed(String),
+    #[error("ERR Error trying to load the RDB dump: {0}")]
+    ReloadFailed(String),
+    #[error("ERR Background append only file rewriting already in progress")]
+    RewriteInProgress,
+    #[error("ERR Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownConfig(String),
+    #[error("ERR CONFIG SET failed (possibly related to argument '{0}') - {1}")]
+    InvalidConfig(String, String),
+    #[error("ERR The server is running without a config file")]
+    NoConfigFile,
+    #[error("ERR Rewriting config file: {0}")]
+    RewriteConfigFailed(String),
+    #[error("ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?")]
+    NoPassword,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error("NOPERM User {0} has no permissions to run the '{1}' command")]
+    NoPermCommand(String, String),
+    #[error("NOPERM No permissions to access a key")]
+    NoPermKey,
+    #[error("ERR Error in ACL SETUSER modifier '{0}': {1}")]
+    AclRule(String, String),
+    #[error("ERR The 'default' user cannot be removed")]
+    DefaultUserRemoved,
+    #[error("ERR This Redis instance is not configured to use an ACL file. You may want to specify users via the ACL SETUSER command and then issue a CONFIG REWRITE (assuming you have a Redis configuration file set) in order to store users in the Redis configuration.")]
+    NoAclFile,
+    #[error("ERR {0}")]
+    AclFile(String),
+    #[error("MOVED {0} {1}")]
+    Moved(u16, String),
+    #[error("ASK {0} {1}")]
+    Ask(u16, String),
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    #[error("CLUSTERDOWN Hash slot not served")]
+    ClusterDown,
+    #[error("ERR This instance has cluster support disabled")]
+    ClusterDisabled,
+    #[error("ERR I don't know about node {0}")]
+    UnknownNode(String),
+    #[error("ERR I'm not the owner of hash slot {0}")]
+    NotSlotOwner(u16),
+    #[error("ERR I'm already the owner of hash slot {0}")]
+    AlreadySlotOwner(u16),
+    #[error("ERR I tried hard but I can't forget myself...")]
+    ForgetMyself,
+    #[error("ERR Invalid node address specified: {0}")]
+    InvalidNodeAddress(String),
+    #[error("ERR invalid longitude,latitude pair {0}")]
+    InvalidLonLat(String),
+    #[error("ERR could not decode requested zset member")]
+    GeoMemberMissing,
+    #[error("WRONGTYPE Key is not a valid HyperLogLog string value.")]
+    InvalidHll,
+    #[error("ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled.")]
+    WaitAofAppendOnly,
+    #[error("ERR WAITAOF cannot be used with replica instances. Please also note that writes to replicas are just local and are not propagated.")]
+    WaitAofReplica,
+    #[error("ERR WAIT cannot be used with replica instances. Please also note that since Redis 4.0 if a replica is configured to be writable (which is not the default) writes to replicas are just local and are not propagated.")]
+    WaitReplica,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    #[error("ERR DUMP payload version or checksum are wrong")]
+    BadDumpPayload,
+    #[error("ERR Invalid TTL value, must be >= 0")]
+    InvalidTtl,
+    #[error("IOERR error or timeout connecting to the client")]
+    MigrateConnect,
+    #[error("IOERR error or timeout {0} target instance")]
+    MigrateIo(String),
+    #[error("ERR Target instance replied with error: {0}")]
+    MigrateTarget(String),
+    #[error("ERR max number of connections from your address reached")]
+    TooManyConnections,
+    #[error("ERR too many connections from your address per second")]
+    ConnectionRateExceeded,
+    #[error("ERR connections from your address are not allowed")]
+    AddressDenied,
+    #[error("ERR Error purging dirty pages")]
+    PurgeFailed,
+    #[error("ERR No such master with that name")]
+    NoSuchMaster,
+    #[error("ERR Duplicated master name")]
+    DuplicatedMaster,
+    #[error("ERR {0}")]
+    SentinelBadArgument(String),
+    #[error("INPROG Failover already in progress")]
+    FailoverInProgress,
+    #[error("NOGOODSLAVE No suitable replica to promote")]
+    NoGoodReplica,
+}
 
 #[derive(Clone, Debug)]
 pub struct Backend(Arc<BackendInner>);
 
 #[derive(Debug)]
 pub struct BackendInner {
-    pub(crate) map: DashMap<String, RespFrame>,
-    pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
+    shards: Box<[RwLock<Shard>]>,
+    pubsub: PubSub,
+    tracking: Tracking,
+    watches: Watches,
+    scripting: Scripting,
+    persistence: Persistence,
+    aof: Aof,
+    replication: Replication,
+    cluster: Cluster,
+    master: Master,
+    stats: Stats,
+    cron: Cron,
+    migrations: Migrations,
+    connection_limits: ConnectionLimits,
+    clients: Clients,
+    latency: Latency,
+    log: Log,
+    acl: Acl,
+    tls: Tls,
+    sentinel: Sentinel,
+    workers: Workers,
+    hooks: CommandHooks,
+    custom_commands: CustomCommands,
+    // taken shared by every command, and exclusively by a transaction
+    exclusive: RwLock<()>,
+    blocked: BlockedClients,
+    notify_flags: AtomicU32,
+    list_max_listpack_size: AtomicI64,
+    set_max_intset_entries: AtomicUsize,
+    hash_max_listpack_entries: AtomicUsize,
+    hash_max_listpack_value: AtomicUsize,
+    // bytes, 0 for no limit
+    maxmemory: AtomicU64,
+    // seconds a client may stay idle before it's disconnected, 0 for ever
+    timeout: AtomicU64,
+    // how many times per second the cron runs, and how hard the active expiration tries
+    hz: AtomicU64,
+    active_expire_effort: AtomicU64,
+    // the shard the next active expiration cycle starts from
+    active_expire_shard: AtomicUsize,
+    // seconds the keys created without a TTL are given, 0 for none, and by prefix
+    default_ttl: AtomicU64,
+    default_ttl_prefixes: RwLock<TtlPrefixes>,
+    // the file CONFIG REWRITE writes, if the server was started with one
+    config_file: RwLock<Option<PathBuf>>,
+    // the names rename-command gave, by the command's own name, empty for those disabled
+    command_renames: RwLock<HashMap<String, String>>,
+    // where the server listens, set at startup
+    bind: RwLock<BindAddrs>,
+    port: AtomicU16,
+    // the file the server logs to, stdout if empty
+    logfile: RwLock<String>,
+    // the password of the default user, none if empty
+    requirepass: RwLock<String>,
+    supervised: RwLock<Supervised>,
+    // set while the server accepts connections
+    accepting: AtomicBool,
+    // SHUTDOWN asked the server to stop, and whether to save the keyspace then
+    shutdown: Notify,
+    shutdown_save: Mutex<Option<bool>>,
 }
 
 impl Deref for Backend {
@@ -28,9 +378,66 @@ impl Default for Backend {
 
 impl Default for BackendInner {
     fn default() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+}
+
+impl BackendInner {
+    fn with_shards(n: usize) -> Self {
+        Self::with_engine(n, StorageEngine::default())
+    }
+
+    fn with_engine(n: usize, engine: StorageEngine) -> Self {
         Self {
-            map: DashMap::new(),
-            hmap: DashMap::new(),
+            shards: (0..n.max(1))
+                .map(|_| RwLock::new(Shard::new(engine.create())))
+                .collect(),
+            pubsub: PubSub::default(),
+            tracking: Tracking::default(),
+            watches: Watches::default(),
+            scripting: Scripting::default(),
+            persistence: Persistence::default(),
+            aof: Aof::default(),
+            replication: Replication::default(),
+            cluster: Cluster::default(),
+            master: Master::default(),
+            stats: Stats::default(),
+            cron: Cron::default(),
+            migrations: Migrations::default(),
+            connection_limits: ConnectionLimits::default(),
+            clients: Clients::default(),
+            latency: Latency::default(),
+            log: Log::default(),
+            acl: Acl::default(),
+            tls: Tls::default(),
+            sentinel: Sentinel::default(),
+            workers: Workers::default(),
+            hooks: CommandHooks::default(),
+            custom_commands: CustomCommands::default(),
+            exclusive: RwLock::new(()),
+            blocked: BlockedClients::default(),
+            notify_flags: AtomicU32::new(0),
+            list_max_listpack_size: AtomicI64::new(DEFAULT_LIST_MAX_LISTPACK_SIZE),
+            set_max_intset_entries: AtomicUsize::new(DEFAULT_SET_MAX_INTSET_ENTRIES),
+            hash_max_listpack_entries: AtomicUsize::new(DEFAULT_HASH_MAX_LISTPACK_ENTRIES),
+            hash_max_listpack_value: AtomicUsize::new(DEFAULT_HASH_MAX_LISTPACK_VALUE),
+            maxmemory: AtomicU64::new(0),
+            timeout: AtomicU64::new(0),
+            hz: AtomicU64::new(DEFAULT_HZ),
+            active_expire_effort: AtomicU64::new(DEFAULT_ACTIVE_EXPIRE_EFFORT),
+            active_expire_shard: AtomicUsize::new(0),
+            default_ttl: AtomicU64::new(0),
+            default_ttl_prefixes: RwLock::new(TtlPrefixes::default()),
+            config_file: RwLock::new(None),
+            command_renames: RwLock::new(HashMap::new()),
+            bind: RwLock::new(BindAddrs::default()),
+            port: AtomicU16::new(DEFAULT_PORT),
+            logfile: RwLock::new(String::new()),
+            requirepass: RwLock::new(String::new()),
+            supervised: RwLock::new(Supervised::default()),
+            accepting: AtomicBool::new(false),
+            shutdown: Notify::new(),
+            shutdown_save: Mutex::new(None),
         }
     }
 }
@@ -40,26 +447,217 @@ impl Backend {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<RespFrame> {
-        self.map.get(key).map(|v| v.value().clone())
+    /// A backend whose keyspace is split into `n` independently locked shards.
+    pub fn with_shards(n: usize) -> Self {
+        Self(Arc::new(BackendInner::with_shards(n)))
+    }
+
+    /// A backend with `n` shards, each storing its keys in a `engine` storage.
+    pub fn with_engine(n: usize, engine: StorageEngine) -> Self {
+        Self(Arc::new(BackendInner::with_engine(n, engine)))
+    }
+
+    /// Run a single command, which can't interleave with a batch run by `run_exclusive`.
+    /// Blocking commands take their locks step by step and run outside of it.
+    pub fn run_shared<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _guard = self.exclusive.read();
+        f()
     }
 
+    /// Run a batch of commands, such as a transaction, with no other command running.
+    pub fn run_exclusive<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _guard = self.exclusive.write();
+        f()
+    }
+
+    /// The shard owning key.
+    pub fn shard(&self, key: &str) -> &RwLock<Shard> {
+        &self.shards[self.shard_index(key)]
+    }
+
+    pub(crate) fn shard_index(&self, key: &str) -> usize {
+        key_hash(key) as usize % self.shards.len()
+    }
+
+    pub fn shards(&self) -> impl Iterator<Item = &RwLock<Shard>> {
+        self.shards.iter()
+    }
+
+    /// How many keys there are, those expired but not deleted yet included.
+    pub fn dbsize(&self) -> usize {
+        self.shards().map(|shard| shard.read().len()).sum()
+    }
+
+    /// Lock the shard owning key for reading, after lazily expiring the key. The read counts
+    /// as a keyspace hit or miss.
+    pub fn read(&self, key: &str) -> RwLockReadGuard<'_, Shard> {
+        self.track_read(key);
+        let shard = self.shard(key);
+        let guard = shard.read();
+        if !guard.is_expired(key) {
+            self.count(match guard.contains_key(key) {
+                true => Stat::KeyspaceHit,
+                false => Stat::KeyspaceMiss,
+            });
+            return guard;
+        }
+        drop(guard);
+        let mut guard = shard.write();
+        if guard.expire_if_needed(key) {
+            self.expired(key);
+        }
+        drop(guard);
+        self.count(Stat::KeyspaceMiss);
+        shard.read()
+    }
+
+    /// Lock the shard owning key for writing, after lazily expiring the key. Multi-step
+    /// commands hold the guard for their whole read-modify-write so they can't interleave.
+    pub fn write(&self, key: &str) -> RwLockWriteGuard<'_, Shard> {
+        let mut guard = self.shard(key).write();
+        if guard.expire_if_needed(key) {
+            self.expired(key);
+        }
+        guard
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<RespFrame>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::String(v) => Ok(Some(v.clone())),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+
+    /// Store a string value, replacing any value of any type and its TTL.
     pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+        let mut shard = self.write(&key);
+        if shard.remove(&key).is_none() {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+        }
+        self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+        shard.insert(key, Value::String(value));
+    }
+
+    pub fn hget(&self, key: &str, field: &str) -> Result<Option<RespFrame>, BackendError> {
+        Ok(self
+            .read_hash(key, |hash| hash.get(field).cloned())?
+            .flatten())
+    }
+
+    pub fn hset(&self, key: String, field: String, value: RespFrame) -> Result<(), BackendError> {
+        self.hset_multiple(key, vec![(field, value)]).map(|_| ())
+    }
+
+    /// The fields of the hash with their values, which share their bytes with the stored ones
+    /// instead of being copied while the shard is locked.
+    pub fn hgetall(&self, key: &str) -> Result<Option<Vec<(String, RespFrame)>>, BackendError> {
+        self.read_hash(key, |hash| {
+            hash.iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect()
+        })
     }
 
-    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
-        self.hmap
-            .get(key)
-            .and_then(|v| v.get(field).map(|v| v.value().clone()))
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.read(key).contains_key(key)
     }
 
-    pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
-        hmap.insert(field, value);
+    /// Remove the key whatever its type is, with its TTL.
+    pub fn remove(&self, key: &str) -> bool {
+        let removed = self.write(key).remove(key).is_some();
+        if removed {
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        removed
     }
+}
+
+// stable for the life of the process, shards and SCAN cursors are derived from it
+pub(crate) fn key_hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_wrong_type() {
+        let backend = Backend::new();
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        let ret = backend.hset(
+            "k1".to_string(),
+            "f1".to_string(),
+            RespFrame::BulkString(b"v1".into()),
+        );
+        assert_eq!(ret, Err(BackendError::WrongType));
+        assert_eq!(backend.hget("k1", "f1"), Err(BackendError::WrongType));
+
+        // SET overwrites a value of any type
+        backend
+            .hset(
+                "k2".to_string(),
+                "f1".to_string(),
+                RespFrame::BulkString(b"v1".into()),
+            )
+            .unwrap();
+        assert_eq!(backend.get("k2"), Err(BackendError::WrongType));
+        backend.set("k2".to_string(), RespFrame::BulkString(b"v2".into()));
+        assert_eq!(
+            backend.get("k2"),
+            Ok(Some(RespFrame::BulkString(b"v2".into())))
+        );
+    }
+
+    #[test]
+    fn test_concurrent_hset_across_shards() {
+        let backend = Backend::with_shards(4);
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let backend = backend.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        backend
+                            .hset(
+                                format!("key{}", i % 10),
+                                format!("field{}-{}", t, i),
+                                RespFrame::Integer(i),
+                            )
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
 
-    pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|v| v.clone())
+        let total: usize = (0..10)
+            .map(|i| {
+                backend
+                    .hgetall(&format!("key{}", i))
+                    .unwrap()
+                    .unwrap()
+                    .len()
+            })
+            .sum();
+        assert_eq!(total, 800);
     }
 }
@@ -0,0 +1,292 @@
+use crate::{Backend, BackendError, NotifyFlags, Shard};
+use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
+
+/// Read locks on the shards owning a set of keys, see `Backend::read_many`.
+pub struct ShardsReadGuard<'a> {
+    backend: &'a Backend,
+    guards: Vec<(usize, RwLockReadGuard<'a, Shard>)>,
+    // the positions of the keys each shard owns, in the order of guards
+    batches: Vec<Vec<usize>>,
+}
+
+/// Write locks on the shards owning a set of keys, see `Backend::write_many`.
+pub struct ShardsWriteGuard<'a> {
+    backend: &'a Backend,
+    guards: Vec<(usize, RwLockWriteGuard<'a, Shard>)>,
+    batches: Vec<Vec<usize>>,
+}
+
+impl ShardsReadGuard<'_> {
+    /// The shard owning key, which must be one of the keys the guard was created with. Keys
+    /// whose TTL elapsed aren't deleted under a read lock, check `Shard::is_expired`.
+    pub fn shard(&self, key: &str) -> &Shard {
+        let index = self.backend.shard_index(key);
+        let i = self
+            .guards
+            .binary_search_by_key(&index, |(i, _)| *i)
+            .expect("key not locked by this guard");
+        &self.guards[i].1
+    }
+
+    /// Every locked shard with the positions, among the keys the guard was created with, of
+    /// the keys it owns in increasing order, to handle the keys of a shard together.
+    pub fn batches(&self) -> impl Iterator<Item = (&Shard, &[usize])> {
+        let shards = self.guards.iter().map(|(_, guard)| &**guard);
+        shards.zip(self.batches.iter().map(Vec::as_slice))
+    }
+}
+
+impl<'a> ShardsWriteGuard<'a> {
+    /// The shard owning key, which must be one of the keys the guard was created with.
+    pub fn shard(&mut self, key: &str) -> &mut Shard {
+        let i = self.position(key);
+        &mut self.guards[i].1
+    }
+
+    /// Shared access to the shard owning key, to read several keys at once.
+    pub fn shard_ref(&self, key: &str) -> &Shard {
+        &self.guards[self.position(key)].1
+    }
+
+    /// Every locked shard with the positions of the keys it owns, see
+    /// `ShardsReadGuard::batches`.
+    pub fn batches(&mut self) -> impl Iterator<Item = (&mut Shard, &[usize])> + use<'_, 'a> {
+        let shards = self.guards.iter_mut().map(|(_, guard)| &mut **guard);
+        shards.zip(self.batches.iter().map(Vec::as_slice))
+    }
+
+    fn position(&self, key: &str) -> usize {
+        let index = self.backend.shard_index(key);
+        self.guards
+            .binary_search_by_key(&index, |(i, _)| *i)
+            .expect("key not locked by this guard")
+    }
+}
+
+impl Backend {
+    // shard indexes of keys, sorted and deduplicated, with the positions of the keys each one
+    // owns: locks are always taken in index order so two multi-key commands can't deadlock
+    fn shard_batches<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k str>,
+    ) -> (Vec<usize>, Vec<Vec<usize>>) {
+        let mut owners: Vec<(usize, usize)> = keys
+            .into_iter()
+            .enumerate()
+            .map(|(position, k)| (self.shard_index(k), position))
+            .collect();
+        owners.sort_unstable();
+        let mut indexes = Vec::new();
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+        for (index, position) in owners {
+            match indexes.last() {
+                Some(&last) if last == index => batches.last_mut().unwrap().push(position),
+                _ => {
+                    indexes.push(index);
+                    batches.push(vec![position]);
+                }
+            }
+        }
+        (indexes, batches)
+    }
+
+    /// Read lock every shard owning one of keys, so a multi-key command sees them all at the
+    /// same point in time.
+    pub fn read_many<'k>(&self, keys: impl IntoIterator<Item = &'k str>) -> ShardsReadGuard<'_> {
+        let keys: Vec<_> = keys.into_iter().collect();
+        for key in &keys {
+            self.track_read(key);
+        }
+        let (indexes, batches) = self.shard_batches(keys);
+        let guards = indexes
+            .into_iter()
+            .map(|i| (i, self.shards[i].read()))
+            .collect();
+        ShardsReadGuard {
+            backend: self,
+            guards,
+            batches,
+        }
+    }
+
+    /// Write lock every shard owning one of keys after lazily expiring them, so a multi-key
+    /// command updates them all atomically.
+    pub fn write_many<'a, 'k>(
+        &'a self,
+        keys: impl IntoIterator<Item = &'k str>,
+    ) -> ShardsWriteGuard<'a> {
+        let keys: Vec<_> = keys.into_iter().collect();
+        let (indexes, batches) = self.shard_batches(keys.iter().copied());
+        let guards = indexes
+            .into_iter()
+            .map(|i| (i, self.shards[i].write()))
+            .collect();
+        let mut guard = ShardsWriteGuard {
+            backend: self,
+            guards,
+            batches,
+        };
+        for (shard, positions) in guard.batches() {
+            for &i in positions {
+                if shard.expire_if_needed(keys[i]) {
+                    self.expired(keys[i]);
+                }
+            }
+        }
+        guard
+    }
+
+    /// Delete keys, with the keys of every shard deleted under one lock. Returns the number
+    /// of keys that existed.
+    pub fn del(&self, keys: &[String]) -> usize {
+        let mut guard = self.write_many(keys.iter().map(String::as_str));
+        let mut deleted = 0;
+        for (shard, positions) in guard.batches() {
+            for &i in positions {
+                if shard.remove(&keys[i]).is_some() {
+                    self.notify_keyspace_event(NotifyFlags::GENERIC, "del", &keys[i]);
+                    deleted += 1;
+                }
+            }
+        }
+        deleted
+    }
+
+    /// Rename source to destination with its TTL, replacing destination unless nx is set.
+    /// Both shards stay locked so no command sees the key under both names, or neither.
+    /// Returns whether the key was renamed.
+    pub fn rename(&self, source: &str, destination: &str, nx: bool) -> Result<bool, BackendError> {
+        let mut guard = self.write_many([source, destination]);
+        if !guard.shard_ref(source).contains_key(source) {
+            return Err(BackendError::NoSuchKey);
+        }
+        if source == destination {
+            return Ok(!nx);
+        }
+        let exists = guard.shard_ref(destination).contains_key(destination);
+        if nx && exists {
+            return Ok(false);
+        }
+
+        let shard = guard.shard(source);
+        let expire_at = shard.expire_time(source);
+        let value = shard.remove(source).expect("source exists").into_inner();
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "rename_from", source);
+
+        let shard = guard.shard(destination);
+        if !exists {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", destination);
+        }
+        shard.remove(destination);
+        shard.insert(destination.to_string(), value);
+        if let Some(at) = expire_at {
+            shard.set_expire(destination.to_string(), at);
+        }
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "rename_to", destination);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::now_ms;
+    use crate::{Backend, BackendError, RespFrame};
+    use std::thread;
+
+    #[test]
+    fn test_rename() {
+        let backend = Backend::with_shards(4);
+        assert_eq!(
+            backend.rename("a", "b", false),
+            Err(BackendError::NoSuchKey)
+        );
+
+        backend.set("a".to_string(), RespFrame::Integer(1));
+        backend.expire_at("a", now_ms() + 10_000);
+        assert_eq!(backend.rename("a", "b", false), Ok(true));
+        assert!(!backend.contains_key("a"));
+        assert_eq!(backend.get("b"), Ok(Some(RespFrame::Integer(1))));
+        assert!(backend.expire_time("b").flatten().is_some());
+
+        // the value at destination is replaced, unless nx
+        backend.set("c".to_string(), RespFrame::Integer(2));
+        assert_eq!(backend.rename("c", "b", true), Ok(false));
+        assert_eq!(backend.rename("c", "b", false), Ok(true));
+        assert_eq!(backend.get("b"), Ok(Some(RespFrame::Integer(2))));
+        assert_eq!(backend.expire_time("b"), Some(None));
+        assert_eq!(backend.rename("b", "b", true), Ok(false));
+    }
+
+    #[test]
+    fn test_shard_batches() {
+        let backend = Backend::with_shards(4);
+        let keys: Vec<String> = (0..32).map(|i| format!("key{}", i)).collect();
+        let pairs = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.clone(), RespFrame::Integer(i as i64)))
+            .chain([(keys[3].clone(), RespFrame::Integer(-1))])
+            .collect();
+        backend.mset(pairs);
+
+        // every shard handles its keys, the replies are in the order of the keys
+        let guard = backend.read_many(keys.iter().map(String::as_str));
+        let mut positions: Vec<usize> = guard.batches().flat_map(|(_, p)| p.to_vec()).collect();
+        positions.sort_unstable();
+        assert_eq!(positions, (0..32).collect::<Vec<_>>());
+        drop(guard);
+        let values = backend.mget(&[keys[1].clone(), "missing".to_string(), keys[3].clone()]);
+        assert_eq!(
+            values,
+            vec![
+                Some(RespFrame::Integer(1)),
+                None,
+                Some(RespFrame::Integer(-1))
+            ]
+        );
+
+        assert_eq!(backend.del(&keys[..16]), 16);
+        assert_eq!(backend.del(&keys[..16]), 0);
+        assert_eq!(backend.dbsize(), 16);
+    }
+
+    #[test]
+    fn test_concurrent_renames() {
+        // keys moved back and forth between shards are never seen under both names or none
+        let backend = Backend::with_shards(4);
+        let keys: Vec<String> = (0..8).map(|i| format!("key{}", i)).collect();
+        backend.set(keys[0].clone(), RespFrame::Integer(1));
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let backend = backend.clone();
+                let keys = keys.clone();
+                thread::spawn(move || {
+                    for i in 0..500 {
+                        let from = &keys[(t + i) % keys.len()];
+                        let to = &keys[(t + i + 1) % keys.len()];
+                        let _ = backend.rename(from, to, true);
+                    }
+                })
+            })
+            .collect();
+        let checker = {
+            let backend = backend.clone();
+            let keys = keys.clone();
+            thread::spawn(move || {
+                for _ in 0..500 {
+                    let guard = backend.read_many(keys.iter().map(String::as_str));
+                    let found = keys
+                        .iter()
+                        .filter(|key| guard.shard(key).contains_key(key))
+                        .count();
+                    assert_eq!(found, 1);
+                }
+            })
+        };
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        checker.join().unwrap();
+    }
+}
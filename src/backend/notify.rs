@@ -0,0 +1,209 @@
+use crate::Backend;
+use std::fmt;
+use std::ops::BitOr;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+
+/// The `notify-keyspace-events` flags: which channels to publish on (K, E) and which classes
+/// of events to publish. Nothing is published unless K or E is set along with a class.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NotifyFlags(u32);
+
+// flag characters as accepted by `notify-keyspace-events`, in the order they're rendered
+const FLAG_CHARS: [(char, NotifyFlags); 12] = [
+    ('K', NotifyFlags::KEYSPACE),
+    ('E', NotifyFlags::KEYEVENT),
+    ('g', NotifyFlags::GENERIC),
+    ('$', NotifyFlags::STRING),
+    ('l', NotifyFlags::LIST),
+    ('s', NotifyFlags::SET),
+    ('h', NotifyFlags::HASH),
+    ('z', NotifyFlags::ZSET),
+    ('x', NotifyFlags::EXPIRED),
+    ('e', NotifyFlags::EVICTED),
+    ('t', NotifyFlags::STREAM),
+    ('n', NotifyFlags::NEW),
+];
+
+impl NotifyFlags {
+    pub const NONE: Self = Self(0);
+    pub const KEYSPACE: Self = Self(1 << 0);
+    pub const KEYEVENT: Self = Self(1 << 1);
+    pub const GENERIC: Self = Self(1 << 2);
+    pub const STRING: Self = Self(1 << 3);
+    pub const LIST: Self = Self(1 << 4);
+    pub const SET: Self = Self(1 << 5);
+    pub const HASH: Self = Self(1 << 6);
+    pub const ZSET: Self = Self(1 << 7);
+    pub const EXPIRED: Self = Self(1 << 8);
+    pub const EVICTED: Self = Self(1 << 9);
+    pub const STREAM: Self = Self(1 << 10);
+    pub const NEW: Self = Self(1 << 11);
+    /// The `A` alias, every class but `n`.
+    pub const ALL: Self = Self(
+        Self::GENERIC.0
+            | Self::STRING.0
+            | Self::LIST.0
+            | Self::SET.0
+            | Self::HASH.0
+            | Self::ZSET.0
+            | Self::EXPIRED.0
+            | Self::EVICTED.0
+            | Self::STREAM.0,
+    );
+
+    pub fn contains(&self, other: NotifyFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(&self, other: NotifyFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for NotifyFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl FromStr for NotifyFlags {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars().try_fold(NotifyFlags::NONE, |flags, c| {
+            if c == 'A' {
+                return Ok(flags | NotifyFlags::ALL);
+            }
+            match FLAG_CHARS.iter().find(|(ch, _)| *ch == c) {
+                Some((_, flag)) => Ok(flags | *flag),
+                None => Err(format!("invalid notify-keyspace-events flag: {}", c)),
+            }
+        })
+    }
+}
+
+impl fmt::Display for NotifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut classes = *self;
+        if self.contains(NotifyFlags::ALL) {
+            write!(f, "A")?;
+            classes = NotifyFlags(self.0 & !NotifyFlags::ALL.0);
+        }
+        for (c, flag) in FLAG_CHARS.iter() {
+            if classes.contains(*flag) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Backend {
+    pub fn notify_keyspace_events(&self) -> NotifyFlags {
+        NotifyFlags(self.notify_flags.load(Ordering::Relaxed))
+    }
+
+    pub fn set_notify_keyspace_events(&self, flags: NotifyFlags) {
+        self.notify_flags.store(flags.0, Ordering::Relaxed);
+    }
+
+    /// Publish a keyspace notification for an event of the given class on key. Every command
+    /// modifying the keyspace goes through here, the configured flags decide what's sent, and
+    /// the clients tracking key are sent an invalidation whatever they are. A new key comes
+    /// with another event, which invalidates it and counts as the change towards the save
+    /// rules. The key may be given the default TTL. Transactions watching key are aborted.
+    pub fn notify_keyspace_event(&self, class: NotifyFlags, event: &str, key: &str) {
+        self.touch_watched(key);
+        if class != NotifyFlags::NEW {
+            self.invalidate(key);
+            self.add_dirty(1);
+        }
+        // PERSIST asks for no TTL
+        if event != "persist" {
+            self.key_written(key);
+        }
+        let flags = self.notify_keyspace_events();
+        if !flags.intersects(class) {
+            return;
+        }
+
+        if flags.contains(NotifyFlags::KEYSPACE) {
+            self.publish(&format!("__keyspace@0__:{}", key), event);
+        }
+        if flags.contains(NotifyFlags::KEYEVENT) {
+            self.publish(&format!("__keyevent@0__:{}", event), key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+
+    #[test]
+    fn test_notify_flags() {
+        let flags: NotifyFlags = "KEA".parse().unwrap();
+        assert!(flags.contains(NotifyFlags::KEYSPACE | NotifyFlags::HASH));
+        assert!(!flags.contains(NotifyFlags::NEW));
+        assert_eq!(flags.to_string(), "AKE");
+        assert_eq!("Kh$".parse::<NotifyFlags>().unwrap().to_string(), "K$h");
+        assert_eq!("".parse::<NotifyFlags>().unwrap(), NotifyFlags::NONE);
+        assert!("Kq".parse::<NotifyFlags>().is_err());
+    }
+
+    #[test]
+    fn test_notify_keyspace_event() {
+        let backend = Backend::new();
+        let mut keyspace = backend.subscribe("__keyspace@0__:k1");
+        let mut keyevent = backend.subscribe("__keyevent@0__:set");
+
+        // disabled by default
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert!(keyspace.try_recv().is_err());
+
+        backend.set_notify_keyspace_events("K$".parse().unwrap());
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert_eq!(keyspace.try_recv().unwrap().payload, "set");
+        assert!(keyevent.try_recv().is_err());
+
+        backend.set_notify_keyspace_events("Eh".parse().unwrap());
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert!(keyevent.try_recv().is_err());
+
+        backend.set_notify_keyspace_events("KEA".parse().unwrap());
+        backend.set("k1".to_string(), RespFrame::BulkString(b"v1".into()));
+        assert_eq!(keyspace.try_recv().unwrap().payload, "set");
+        assert_eq!(keyevent.try_recv().unwrap().payload, "k1");
+    }
+
+    #[test]
+    fn test_notify_new_and_expired() {
+        let backend = Backend::new();
+        backend.set_notify_keyspace_events("KA".parse().unwrap());
+        let mut keyspace = backend.subscribe("__keyspace@0__:k1");
+
+        backend
+            .hset("k1".to_string(), "f1".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(keyspace.try_recv().unwrap().payload, "hset");
+
+        backend.set_notify_keyspace_events("Kgxn".parse().unwrap());
+        backend.remove("k1");
+        backend
+            .hset("k1".to_string(), "f1".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(keyspace.try_recv().unwrap().payload, "del");
+        assert_eq!(keyspace.try_recv().unwrap().payload, "new");
+
+        backend
+            .write("k1")
+            .set_expire("k1".to_string(), crate::backend::now_ms() - 1);
+        assert!(backend.hget("k1", "f1").unwrap().is_none());
+        assert_eq!(keyspace.try_recv().unwrap().payload, "expired");
+        assert!(keyspace.try_recv().is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::backend::now_ms;
+use crate::{Backend, RespFrame, Value};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+// same defaults as redis: lfu-log-factor 10, lfu-decay-time 1 (minute)
+const LFU_INIT_VAL: u8 = 5;
+const LFU_LOG_FACTOR: f64 = 10.0;
+const LFU_DECAY_MS: u64 = 60_000;
+
+const OBJ_ENCODING_EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// A value stored in the keyspace, together with the access metadata (last access time and
+/// logarithmic access frequency) used by OBJECT and by eviction.
+///
+/// The value is shared copy-on-write: a snapshot holds it without copying it, and a write to
+/// a value a snapshot still holds copies it first, leaving the snapshot's untouched.
+#[derive(Debug)]
+pub struct Object<T> {
+    value: Arc<T>,
+    access: AtomicU64,
+    freq: AtomicU8,
+}
+
+impl<T> Object<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Arc::new(value),
+            access: AtomicU64::new(now_ms() as u64),
+            freq: AtomicU8::new(LFU_INIT_VAL),
+        }
+    }
+
+    /// The value, shared with the object rather than copied until either is written.
+    pub fn share(&self) -> Arc<T> {
+        self.value.clone()
+    }
+
+    /// Record an access: refresh the LRU clock and bump the LFU counter.
+    pub fn touch(&self) {
+        let now = now_ms() as u64;
+        let counter = log_incr(decay(self.freq.load(Ordering::Relaxed), self.idle_ms(now)));
+        self.freq.store(counter, Ordering::Relaxed);
+        self.access.store(now, Ordering::Relaxed);
+    }
+
+    /// Seconds since the last access.
+    pub fn idle_time(&self) -> u64 {
+        self.idle_ms(now_ms() as u64) / 1000
+    }
+
+    /// The access frequency counter, decayed by the time since the last access.
+    pub fn freq(&self) -> u8 {
+        decay(
+            self.freq.load(Ordering::Relaxed),
+            self.idle_ms(now_ms() as u64),
+        )
+    }
+
+    fn idle_ms(&self, now: u64) -> u64 {
+        now.saturating_sub(self.access.load(Ordering::Relaxed))
+    }
+}
+
+impl<T: Clone> Object<T> {
+    /// The value, copied only if a snapshot still holds it.
+    pub fn into_inner(self) -> T {
+        Arc::unwrap_or_clone(self.value)
+    }
+}
+
+impl<T> Clone for Object<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            access: AtomicU64::new(self.access.load(Ordering::Relaxed)),
+            freq: AtomicU8::new(self.freq.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<T> Deref for Object<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: Clone> DerefMut for Object<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.value)
+    }
+}
+
+impl Backend {
+    /// The internal encoding of the value stored at key, as reported by OBJECT ENCODING.
+    pub fn object_encoding(&self, key: &str) -> Option<&'static str> {
+        let shard = self.read(key);
+        shard.get(key).map(|obj| match &**obj {
+            Value::String(v) => string_encoding(v),
+            Value::Hash(hash) => hash.encoding(),
+            Value::List(list) => list.encoding(),
+            Value::Set(set) => set.encoding(),
+            Value::ZSet(_) => "skiplist",
+            Value::Stream(_) => "stream",
+        })
+    }
+
+    pub fn object_idletime(&self, key: &str) -> Option<u64> {
+        self.read(key).get(key).map(|obj| obj.idle_time())
+    }
+
+    pub fn object_freq(&self, key: &str) -> Option<u8> {
+        self.read(key).get(key).map(|obj| obj.freq())
+    }
+}
+
+fn string_encoding(value: &RespFrame) -> &'static str {
+    match value {
+        RespFrame::BulkString(s) if s.len() <= 20 && is_integer(s) => "int",
+        RespFrame::Integer(_) => "int",
+        RespFrame::BulkString(s) if s.len() <= OBJ_ENCODING_EMBSTR_SIZE_LIMIT => "embstr",
+        _ => "raw",
+    }
+}
+
+fn is_integer(s: &[u8]) -> bool {
+    std::str::from_utf8(s).is_ok_and(|s| s.parse::<i64>().is_ok_and(|n| n.to_string() == s))
+}
+
+// the counter grows logarithmically: the higher it is, the less likely an access increments it
+fn log_incr(counter: u8) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+    let p = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+    if rand::random::<f64>() < p {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+// the counter is decremented once per decay period the key was not accessed
+fn decay(counter: u8, idle_ms: u64) -> u8 {
+    let periods = (idle_ms / LFU_DECAY_MS).min(u8::MAX as u64) as u8;
+    counter.saturating_sub(periods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    #[test]
+    fn test_object_access_metadata() {
+        let obj = Object::new(RespFrame::BulkString(b"v".into()));
+        assert_eq!(obj.idle_time(), 0);
+        assert_eq!(obj.freq(), LFU_INIT_VAL);
+
+        for _ in 0..100 {
+            obj.touch();
+        }
+        assert!(obj.freq() > LFU_INIT_VAL);
+        assert_eq!(decay(10, 3 * LFU_DECAY_MS), 7);
+        assert_eq!(decay(1, 3 * LFU_DECAY_MS), 0);
+    }
+
+    #[test]
+    fn test_object_encoding() {
+        let backend = Backend::new();
+        backend.set("int".to_string(), RespFrame::BulkString(b"12345".into()));
+        backend.set("emb".to_string(), RespFrame::BulkString(b"hello".into()));
+        backend.set(
+            "raw".to_string(),
+            RespFrame::BulkString(BulkString::new(vec![b'a'; 45])),
+        );
+        backend
+            .hset(
+                "hash".to_string(),
+                "f".to_string(),
+                RespFrame::BulkString(b"v".into()),
+            )
+            .unwrap();
+
+        assert_eq!(backend.object_encoding("int"), Some("int"));
+        assert_eq!(backend.object_encoding("emb"), Some("embstr"));
+        assert_eq!(backend.object_encoding("raw"), Some("raw"));
+        assert_eq!(backend.object_encoding("hash"), Some("listpack"));
+        assert_eq!(backend.object_encoding("missing"), None);
+
+        // a value past hash-max-listpack-value converts the hash
+        backend
+            .config_set(vec![(
+                "hash-max-listpack-value".to_string(),
+                "4".to_string(),
+            )])
+            .unwrap();
+        backend
+            .hset(
+                "big".to_string(),
+                "field".to_string(),
+                RespFrame::BulkString(b"v".into()),
+            )
+            .unwrap();
+        assert_eq!(backend.object_encoding("big"), Some("hashtable"));
+    }
+}
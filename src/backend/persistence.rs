@@ -0,0 +1,550 @@
+use crate::backend::now_ms;
+use crate::{
+    decode_dump, encode_dump, AofReport, Backend, BackendError, LatencyEvent, Snapshot,
+    SnapshotEntry,
+};
+use parking_lot::RwLock;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// The default dbfilename.
+pub const DEFAULT_DBFILENAME: &str = "dump.rdb";
+
+/// The default appendfilename.
+pub const DEFAULT_APPENDFILENAME: &str = "appendonly.aof";
+
+/// The default save rules: after an hour if a key changed, 5 minutes if 100 did, a minute if
+/// 10000 did.
+pub const DEFAULT_SAVE_RULES: &str = "3600 1 300 100 60 10000";
+
+// how long an autosave waits after a failed one
+const AUTOSAVE_RETRY_DELAY: i64 = 5;
+
+/// A `save <seconds> <changes>` rule: the keyspace is saved in the background once seconds
+/// passed since the last save if at least changes were made since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveRule {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+/// The save rules, parsed from pairs like `900 1 300 10`. None disable autosave.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveRules(pub Vec<SaveRule>);
+
+impl FromStr for SaveRules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split_whitespace()
+            .map(|value| value.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("invalid save rules: {}", s))?;
+        if values.len() % 2 != 0 {
+            return Err(format!("invalid save rules: {}", s));
+        }
+        Ok(SaveRules(
+            values
+                .chunks(2)
+                .map(|pair| SaveRule {
+                    seconds: pair[0],
+                    changes: pair[1],
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl fmt::Display for SaveRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, rule) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{} {}", rule.seconds, rule.changes)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of persistence, as INFO reports it. Durations are in seconds, -1 if there's
+/// none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistenceReport {
+    pub changes_since_last_save: u64,
+    pub bgsave_in_progress: bool,
+    pub last_save_time: i64,
+    pub last_bgsave_ok: bool,
+    pub last_bgsave_time_sec: i64,
+    pub current_bgsave_time_sec: i64,
+    pub aof: AofReport,
+}
+
+/// Where the keyspace is saved, and when it last was.
+#[derive(Debug)]
+pub struct Persistence {
+    dir: RwLock<PathBuf>,
+    dbfilename: RwLock<String>,
+    appendfilename: RwLock<String>,
+    // unix time in seconds of the last successful save, the start time before any
+    lastsave: AtomicI64,
+    saving: AtomicBool,
+    save_rules: RwLock<SaveRules>,
+    // changes to the keyspace since the last save
+    dirty: AtomicU64,
+    // whether the last save succeeded, and when it was tried
+    last_save_ok: AtomicBool,
+    last_save_try: AtomicI64,
+    // unix time in milliseconds the running save started, and how long the last one took in
+    // seconds, -1 before any
+    save_started: AtomicI64,
+    last_save_duration: AtomicI64,
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Self {
+            dir: RwLock::new(PathBuf::from(".")),
+            dbfilename: RwLock::new(DEFAULT_DBFILENAME.to_string()),
+            appendfilename: RwLock::new(DEFAULT_APPENDFILENAME.to_string()),
+            lastsave: AtomicI64::new(now_ms() / 1000),
+            saving: AtomicBool::new(false),
+            save_rules: RwLock::new(DEFAULT_SAVE_RULES.parse().unwrap()),
+            dirty: AtomicU64::new(0),
+            last_save_ok: AtomicBool::new(true),
+            last_save_try: AtomicI64::new(0),
+            save_started: AtomicI64::new(0),
+            last_save_duration: AtomicI64::new(-1),
+        }
+    }
+}
+
+impl Backend {
+    /// Set the directory the dump file is saved in and loaded from.
+    pub fn set_dir(&self, dir: impl Into<PathBuf>) {
+        *self.persistence.dir.write() = dir.into();
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.persistence.dir.read().clone()
+    }
+
+    pub fn dbfilename(&self) -> String {
+        self.persistence.dbfilename.read().clone()
+    }
+
+    pub fn set_dbfilename(&self, dbfilename: impl Into<String>) {
+        *self.persistence.dbfilename.write() = dbfilename.into();
+    }
+
+    /// The path of the dump file, `dbfilename` in `dir`.
+    pub fn dump_path(&self) -> PathBuf {
+        self.persistence
+            .dir
+            .read()
+            .join(&*self.persistence.dbfilename.read())
+    }
+
+    pub fn appendfilename(&self) -> String {
+        self.persistence.appendfilename.read().clone()
+    }
+
+    pub fn set_appendfilename(&self, appendfilename: impl Into<String>) {
+        *self.persistence.appendfilename.write() = appendfilename.into();
+    }
+
+    /// The path of the append only file, `appendfilename` in `dir`.
+    pub fn aof_path(&self) -> PathBuf {
+        self.persistence
+            .dir
+            .read()
+            .join(&*self.persistence.appendfilename.read())
+    }
+
+    /// Save the keyspace to the dump file, blocking until it's written.
+    pub fn save(&self) -> Result<(), BackendError> {
+        if self.persistence.saving.swap(true, Ordering::AcqRel) {
+            return Err(BackendError::SaveInProgress);
+        }
+        self.persistence
+            .save_started
+            .store(now_ms(), Ordering::Relaxed);
+        let dirty = self.dirty();
+        let saved = write_dump(&self.dump_path(), &self.snapshot());
+        self.finish_save(&saved, dirty);
+        saved.map_err(|e| BackendError::SaveFailed(e.to_string()))
+    }
+
+    /// Save the keyspace to the dump file in the background: a copy-on-write snapshot of the
+    /// keyspace is taken right away and written by another thread while writers proceed.
+    pub fn bgsave(&self) -> Result<(), BackendError> {
+        if self.persistence.saving.swap(true, Ordering::AcqRel) {
+            return Err(BackendError::SaveInProgress);
+        }
+        self.persistence
+            .save_started
+            .store(now_ms(), Ordering::Relaxed);
+        let dirty = self.dirty();
+        let started = Instant::now();
+        let snapshot = self.snapshot();
+        self.latency_sample(LatencyEvent::Fork, started.elapsed());
+        let path = self.dump_path();
+        let backend = self.clone();
+        thread::spawn(move || {
+            let saved = write_dump(&path, &snapshot);
+            match &saved {
+                Ok(()) => info!("background saving terminated with success"),
+                Err(e) => warn!("background saving failed: {}", e),
+            }
+            backend.finish_save(&saved, dirty);
+        });
+        Ok(())
+    }
+
+    /// Whether a SAVE or BGSAVE is in progress.
+    pub fn is_saving(&self) -> bool {
+        self.persistence.saving.load(Ordering::Acquire)
+    }
+
+    /// The unix time in seconds of the last successful save.
+    pub fn lastsave(&self) -> i64 {
+        self.persistence.lastsave.load(Ordering::Relaxed)
+    }
+
+    pub fn save_rules(&self) -> SaveRules {
+        self.persistence.save_rules.read().clone()
+    }
+
+    pub fn set_save_rules(&self, rules: SaveRules) {
+        *self.persistence.save_rules.write() = rules;
+    }
+
+    /// How many changes were made to the keyspace since the last save.
+    pub fn dirty(&self) -> u64 {
+        self.persistence.dirty.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_dirty(&self, changes: u64) {
+        self.persistence.dirty.fetch_add(changes, Ordering::Relaxed);
+    }
+
+    // what was loaded at startup is already saved
+    pub(crate) fn clear_dirty(&self) {
+        self.persistence.dirty.store(0, Ordering::Relaxed);
+    }
+
+    /// Start a BGSAVE if a save rule is met, and no save is in progress. After a failed save
+    /// another is only tried a few seconds later. Returns whether one was started.
+    pub fn autosave(&self) -> bool {
+        let persistence = &self.persistence;
+        let now = now_ms() / 1000;
+        if self.is_saving()
+            || !persistence.last_save_ok.load(Ordering::Relaxed)
+                && now - persistence.last_save_try.load(Ordering::Relaxed) < AUTOSAVE_RETRY_DELAY
+        {
+            return false;
+        }
+        let dirty = self.dirty();
+        let elapsed = now.saturating_sub(self.lastsave()).max(0) as u64;
+        let Some(rule) = persistence
+            .save_rules
+            .read()
+            .0
+            .iter()
+            .find(|rule| dirty >= rule.changes && elapsed >= rule.seconds)
+            .copied()
+        else {
+            return false;
+        };
+
+        info!(
+            "{} changes in {} seconds. Saving...",
+            rule.changes, rule.seconds
+        );
+        match self.bgsave() {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("can't start the autosave: {}", e);
+                false
+            }
+        }
+    }
+
+    /// The state of the dump and of the AOF.
+    pub fn persistence_report(&self) -> PersistenceReport {
+        let persistence = &self.persistence;
+        let saving = self.is_saving();
+        PersistenceReport {
+            changes_since_last_save: self.dirty(),
+            bgsave_in_progress: saving,
+            last_save_time: self.lastsave(),
+            last_bgsave_ok: persistence.last_save_ok.load(Ordering::Relaxed),
+            last_bgsave_time_sec: persistence.last_save_duration.load(Ordering::Relaxed),
+            current_bgsave_time_sec: match saving {
+                true => elapsed_sec(persistence.save_started.load(Ordering::Relaxed)),
+                false => -1,
+            },
+            aof: self.aof_report(),
+        }
+    }
+
+    /// Load the keys of the dump file, if there is one. Keys whose TTL elapsed are skipped.
+    /// Returns how many keys were loaded.
+    pub fn load_dump(&self) -> io::Result<usize> {
+        let data = match fs::read(self.dump_path()) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let entries = decode_dump(
+            &data,
+            || self.new_list(),
+            || self.new_set(),
+            || self.new_hash(),
+        )?;
+        Ok(self.insert_entries(entries))
+    }
+
+    /// Replace the keyspace with the one of the dump data, as a replica syncing with its master
+    /// does, with no command running meanwhile. The keys dropped are touched like deleted ones.
+    /// Returns how many keys were loaded.
+    pub fn replace_keyspace(&self, data: &[u8]) -> io::Result<usize> {
+        let entries = decode_dump(
+            data,
+            || self.new_list(),
+            || self.new_set(),
+            || self.new_hash(),
+        )?;
+        Ok(self.run_exclusive(|| self.load_keyspace(entries)))
+    }
+
+    /// Save the keyspace then load it back from the dump, as DEBUG RELOAD does to check every
+    /// value round-trips. No command may run meanwhile, or what it wrote after the save would
+    /// be lost.
+    pub fn reload(&self) -> Result<(), BackendError> {
+        self.save()?;
+        let reloaded = fs::read(self.dump_path()).and_then(|data| {
+            decode_dump(
+                &data,
+                || self.new_list(),
+                || self.new_set(),
+                || self.new_hash(),
+            )
+        });
+        match reloaded {
+            Ok(entries) => {
+                self.load_keyspace(entries);
+                Ok(())
+            }
+            Err(e) => Err(BackendError::ReloadFailed(e.to_string())),
+        }
+    }
+
+    // drop every key then insert the entries, returning how many were inserted
+    fn load_keyspace(&self, entries: Vec<SnapshotEntry>) -> usize {
+        for shard in self.shards() {
+            let mut shard = shard.write();
+            let keys: Vec<String> = shard.iter().map(|(key, _)| key.to_string()).collect();
+            for key in keys {
+                shard.remove(&key);
+                self.touch_watched(&key);
+                self.invalidate(&key);
+            }
+        }
+        self.insert_entries(entries)
+    }
+
+    // insert the entries not expired yet, returning how many
+    pub(crate) fn insert_entries(&self, entries: Vec<SnapshotEntry>) -> usize {
+        let now = now_ms();
+        let mut loaded = 0;
+        for entry in entries {
+            if entry.expire_at.is_some_and(|at| at <= now) {
+                continue;
+            }
+            let mut shard = self.shard(&entry.key).write();
+            shard.insert(entry.key.clone(), Arc::unwrap_or_clone(entry.value));
+            if let Some(at) = entry.expire_at {
+                shard.set_expire(entry.key, at);
+            }
+            loaded += 1;
+        }
+        loaded
+    }
+
+    // dirty is the count of changes when the snapshot was taken, which are now saved
+    fn finish_save(&self, saved: &io::Result<()>, dirty: u64) {
+        let persistence = &self.persistence;
+        let now = now_ms() / 1000;
+        let started = persistence.save_started.load(Ordering::Relaxed);
+        persistence
+            .last_save_duration
+            .store(elapsed_sec(started), Ordering::Relaxed);
+        if saved.is_ok() {
+            persistence.lastsave.store(now, Ordering::Relaxed);
+            persistence.dirty.fetch_sub(dirty, Ordering::Relaxed);
+        }
+        persistence
+            .last_save_ok
+            .store(saved.is_ok(), Ordering::Relaxed);
+        persistence.last_save_try.store(now, Ordering::Relaxed);
+        persistence.saving.store(false, Ordering::Release);
+    }
+}
+
+// the seconds since started, a unix time in milliseconds
+pub(crate) fn elapsed_sec(started: i64) -> i64 {
+    (now_ms() - started).max(0) / 1000
+}
+
+// write to a temporary file renamed over the dump, so a crash never leaves half a dump
+fn write_dump(path: &Path, snapshot: &Snapshot) -> io::Result<()> {
+    let temp = path.with_file_name(format!("temp-{}.rdb", std::process::id()));
+    fs::write(&temp, encode_dump(snapshot.iter()))?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::now_ms;
+    use crate::{Backend, RespFrame, SaveRule, SaveRules, StreamId, XAddId};
+    use std::time::Duration;
+    use std::{env, fs, thread};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("simple-redis-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_dir("save");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        backend.set("s".to_string(), RespFrame::BulkString(b"1".into()));
+        backend.expire_at("s", now_ms() + 10_000);
+        backend
+            .rpush("l", vec![RespFrame::BulkString(b"a".into())])
+            .unwrap();
+        backend.sadd("set", vec!["x".to_string()]).unwrap();
+        backend.zadd("z", vec![(1.5, "m".to_string())]).unwrap();
+        backend
+            .hset(
+                "h".to_string(),
+                "f".to_string(),
+                RespFrame::BulkString(b"2".into()),
+            )
+            .unwrap();
+        let fields = vec![("f".to_string(), RespFrame::BulkString(b"v".into()))];
+        backend
+            .xadd("x", XAddId::Explicit(StreamId::new(1, 1)), fields, false)
+            .unwrap();
+        let fields = vec![("other".to_string(), RespFrame::BulkString(b"w".into()))];
+        backend
+            .xadd("x", XAddId::Explicit(StreamId::new(5, 0)), fields, false)
+            .unwrap();
+        backend
+            .xgroup_create("x", "g", Some(StreamId::MIN), false)
+            .unwrap();
+        backend
+            .xreadgroup("g", "c", &["x".to_string()], &[None], None, false)
+            .unwrap();
+        backend.save().unwrap();
+
+        let loaded = Backend::new();
+        loaded.set_dir(&dir);
+        assert_eq!(loaded.load_dump().unwrap(), 6);
+        assert_eq!(loaded.digest(), backend.digest());
+        assert_eq!(
+            loaded.get("s"),
+            Ok(Some(RespFrame::BulkString(b"1".into())))
+        );
+        let summary = loaded.xpending("x", "g").unwrap();
+        assert_eq!(summary.consumers, vec![("c".to_string(), 2)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_bgsave() {
+        let dir = temp_dir("bgsave");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        backend.set_dbfilename("bg.rdb");
+        backend.set("k".to_string(), RespFrame::Integer(1));
+
+        backend.bgsave().unwrap();
+        while backend.is_saving() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(dir.join("bg.rdb").exists());
+        assert!(backend.lastsave() > 0);
+
+        let loaded = Backend::new();
+        loaded.set_dir(&dir);
+        assert_eq!(loaded.load_dump().unwrap(), 0);
+        loaded.set_dbfilename("bg.rdb");
+        assert_eq!(loaded.load_dump().unwrap(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_rules() {
+        let rules: SaveRules = "900 1  300 10".parse().unwrap();
+        assert_eq!(
+            rules.0,
+            vec![
+                SaveRule {
+                    seconds: 900,
+                    changes: 1
+                },
+                SaveRule {
+                    seconds: 300,
+                    changes: 10
+                }
+            ]
+        );
+        assert_eq!(rules.to_string(), "900 1 300 10");
+        assert_eq!("".parse::<SaveRules>().unwrap(), SaveRules::default());
+        assert!("900".parse::<SaveRules>().is_err());
+        assert!("900 x".parse::<SaveRules>().is_err());
+    }
+
+    #[test]
+    fn test_autosave() {
+        let dir = temp_dir("autosave");
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        backend.set_save_rules("3600 1 0 2".parse().unwrap());
+
+        backend.set("k".to_string(), RespFrame::Integer(1));
+        assert_eq!(backend.dirty(), 1);
+        assert!(!backend.autosave());
+        backend.set("k".to_string(), RespFrame::Integer(2));
+        assert!(backend.autosave());
+        while backend.is_saving() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(dir.join("dump.rdb").exists());
+        assert_eq!(backend.dirty(), 0);
+        assert!(!backend.autosave());
+
+        // the changes made by SAVE are counted
+        backend.set("k".to_string(), RespFrame::Integer(3));
+        backend.save().unwrap();
+        assert_eq!(backend.dirty(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
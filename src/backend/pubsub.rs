@@ -0,0 +1,349 @@
+use crate::{glob_match, Backend};
+use parking_lot::RwLock;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// A message published on a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The pattern the channel matched, None for a subscription to the channel itself.
+    pub pattern: Option<String>,
+    pub channel: String,
+    pub payload: String,
+    /// Published on a shard channel with SPUBLISH.
+    pub sharded: bool,
+}
+
+// the subscribers of each channel or pattern
+type Registry = RwLock<HashMap<String, HashMap<u64, UnboundedSender<Message>>>>;
+
+/// The subscriptions of a backend, by channel or pattern then subscriber. A subscriber whose
+/// receiver was dropped is unsubscribed on the next publish reaching it.
+#[derive(Debug, Default)]
+pub struct PubSub {
+    next_subscriber: AtomicU64,
+    channels: Registry,
+    patterns: Registry,
+    shard_channels: Registry,
+}
+
+/// The subscriptions of a connection. The messages of all of them are queued on the single
+/// outbound channel of the connection.
+#[derive(Debug)]
+pub struct Subscriptions {
+    id: u64,
+    tx: UnboundedSender<Message>,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+    shard_channels: BTreeSet<String>,
+}
+
+impl Subscriptions {
+    /// The ID of the connection, replied by CLIENT ID.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The number of channel and pattern subscriptions.
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// The number of shard channel subscriptions, counted apart from the others.
+    pub fn shard_count(&self) -> usize {
+        self.shard_channels.len()
+    }
+
+    /// Whether the connection has any subscription, which puts it in subscriber mode.
+    pub fn is_subscribed(&self) -> bool {
+        self.count() + self.shard_count() > 0
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.channels.iter().map(String::as_str)
+    }
+
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(String::as_str)
+    }
+
+    pub fn shard_channels(&self) -> impl Iterator<Item = &str> {
+        self.shard_channels.iter().map(String::as_str)
+    }
+}
+
+impl Backend {
+    /// New subscriptions for a connection, with the receiver of their messages.
+    pub fn subscriptions(&self) -> (Subscriptions, UnboundedReceiver<Message>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // client IDs start at 1
+        let id = self.pubsub.next_subscriber.fetch_add(1, Ordering::Relaxed) + 1;
+        let subscriptions = Subscriptions {
+            id,
+            tx,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+            shard_channels: BTreeSet::new(),
+        };
+        (subscriptions, rx)
+    }
+
+    /// Subscribe to channel, returns whether it wasn't subscribed to already.
+    pub fn subscribe_channel(&self, subscriptions: &mut Subscriptions, channel: &str) -> bool {
+        if !subscriptions.channels.insert(channel.to_string()) {
+            return false;
+        }
+        register(&self.pubsub.channels, channel, subscriptions);
+        true
+    }
+
+    /// Unsubscribe from channel, returns whether it was subscribed to.
+    pub fn unsubscribe_channel(&self, subscriptions: &mut Subscriptions, channel: &str) -> bool {
+        if !subscriptions.channels.remove(channel) {
+            return false;
+        }
+        unregister(&self.pubsub.channels, channel, subscriptions.id);
+        true
+    }
+
+    /// Subscribe to the channels matching the glob-style pattern, returns whether it wasn't
+    /// subscribed to already.
+    pub fn subscribe_pattern(&self, subscriptions: &mut Subscriptions, pattern: &str) -> bool {
+        if !subscriptions.patterns.insert(pattern.to_string()) {
+            return false;
+        }
+        register(&self.pubsub.patterns, pattern, subscriptions);
+        true
+    }
+
+    /// Unsubscribe from pattern, returns whether it was subscribed to.
+    pub fn unsubscribe_pattern(&self, subscriptions: &mut Subscriptions, pattern: &str) -> bool {
+        if !subscriptions.patterns.remove(pattern) {
+            return false;
+        }
+        unregister(&self.pubsub.patterns, pattern, subscriptions.id);
+        true
+    }
+
+    /// Subscribe to the shard channel, returns whether it wasn't subscribed to already.
+    pub fn subscribe_shard_channel(
+        &self,
+        subscriptions: &mut Subscriptions,
+        channel: &str,
+    ) -> bool {
+        if !subscriptions.shard_channels.insert(channel.to_string()) {
+            return false;
+        }
+        register(&self.pubsub.shard_channels, channel, subscriptions);
+        true
+    }
+
+    /// Unsubscribe from the shard channel, returns whether it was subscribed to.
+    pub fn unsubscribe_shard_channel(
+        &self,
+        subscriptions: &mut Subscriptions,
+        channel: &str,
+    ) -> bool {
+        if !subscriptions.shard_channels.remove(channel) {
+            return false;
+        }
+        unregister(&self.pubsub.shard_channels, channel, subscriptions.id);
+        true
+    }
+
+    /// Drop every subscription of a connection.
+    pub fn unsubscribe_all(&self, subscriptions: &mut Subscriptions) {
+        for channel in std::mem::take(&mut subscriptions.channels) {
+            unregister(&self.pubsub.channels, &channel, subscriptions.id);
+        }
+        for pattern in std::mem::take(&mut subscriptions.patterns) {
+            unregister(&self.pubsub.patterns, &pattern, subscriptions.id);
+        }
+        for channel in std::mem::take(&mut subscriptions.shard_channels) {
+            unregister(&self.pubsub.shard_channels, &channel, subscriptions.id);
+        }
+    }
+
+    /// Subscribe a new receiver to channel alone.
+    pub fn subscribe(&self, channel: &str) -> UnboundedReceiver<Message> {
+        let (mut subscriptions, rx) = self.subscriptions();
+        self.subscribe_channel(&mut subscriptions, channel);
+        rx
+    }
+
+    /// Deliver payload to every subscriber of channel, and of every pattern it matches.
+    /// Returns the number of receivers, a subscriber counting once per subscription.
+    pub fn publish(&self, channel: &str, payload: &str) -> usize {
+        let message = Message {
+            pattern: None,
+            channel: channel.to_string(),
+            payload: payload.to_string(),
+            sharded: false,
+        };
+        let mut receivers = deliver(&self.pubsub.channels, channel, &message);
+
+        let matched: Vec<_> = self
+            .pubsub
+            .patterns
+            .read()
+            .keys()
+            .filter(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes()))
+            .cloned()
+            .collect();
+        for pattern in matched {
+            let message = Message {
+                pattern: Some(pattern.clone()),
+                ..message.clone()
+            };
+            receivers += deliver(&self.pubsub.patterns, &pattern, &message);
+        }
+        receivers
+    }
+
+    /// Deliver payload to every subscriber of the shard channel, which patterns never match.
+    /// Returns the number of receivers.
+    pub fn spublish(&self, channel: &str, payload: &str) -> usize {
+        let message = Message {
+            pattern: None,
+            channel: channel.to_string(),
+            payload: payload.to_string(),
+            sharded: true,
+        };
+        deliver(&self.pubsub.shard_channels, channel, &message)
+    }
+}
+
+fn register(registry: &Registry, name: &str, subscriptions: &Subscriptions) {
+    registry
+        .write()
+        .entry(name.to_string())
+        .or_default()
+        .insert(subscriptions.id, subscriptions.tx.clone());
+}
+
+fn unregister(registry: &Registry, name: &str, id: u64) {
+    let mut registry = registry.write();
+    if let Some(subscribers) = registry.get_mut(name) {
+        subscribers.remove(&id);
+        if subscribers.is_empty() {
+            registry.remove(name);
+        }
+    }
+}
+
+// send message to the subscribers of name, returns how many got it
+fn deliver(registry: &Registry, name: &str, message: &Message) -> usize {
+    let mut closed = false;
+    let mut receivers = 0;
+    if let Some(subscribers) = registry.read().get(name) {
+        for tx in subscribers.values() {
+            match tx.send(message.clone()) {
+                Ok(()) => receivers += 1,
+                Err(_) => closed = true,
+            }
+        }
+    }
+
+    if closed {
+        let mut registry = registry.write();
+        if let Some(subscribers) = registry.get_mut(name) {
+            subscribers.retain(|_, tx| !tx.is_closed());
+            if subscribers.is_empty() {
+                registry.remove(name);
+            }
+        }
+    }
+    receivers
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Backend, Message};
+
+    #[test]
+    fn test_publish() {
+        let backend = Backend::new();
+        assert_eq!(backend.publish("ch", "hello"), 0);
+
+        let mut rx1 = backend.subscribe("ch");
+        let rx2 = backend.subscribe("ch");
+        assert_eq!(backend.publish("ch", "hello"), 2);
+        assert_eq!(
+            rx1.try_recv().unwrap(),
+            Message {
+                pattern: None,
+                channel: "ch".to_string(),
+                payload: "hello".to_string(),
+                sharded: false,
+            }
+        );
+
+        drop(rx2);
+        assert_eq!(backend.publish("ch", "world"), 1);
+        assert_eq!(rx1.try_recv().unwrap().payload, "world");
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let backend = Backend::new();
+        let (mut subscriptions, mut rx) = backend.subscriptions();
+        assert!(backend.subscribe_channel(&mut subscriptions, "a"));
+        assert!(backend.subscribe_channel(&mut subscriptions, "b"));
+        assert!(!backend.subscribe_channel(&mut subscriptions, "a"));
+        assert_eq!(subscriptions.count(), 2);
+
+        // one queue for every channel of the connection
+        backend.publish("a", "1");
+        backend.publish("b", "2");
+        assert_eq!(rx.try_recv().unwrap().channel, "a");
+        assert_eq!(rx.try_recv().unwrap().channel, "b");
+
+        assert!(backend.unsubscribe_channel(&mut subscriptions, "a"));
+        assert!(!backend.unsubscribe_channel(&mut subscriptions, "a"));
+        assert_eq!(backend.publish("a", "1"), 0);
+        assert_eq!(subscriptions.channels().collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    #[test]
+    fn test_pattern_subscriptions() {
+        let backend = Backend::new();
+        let (mut subscriptions, mut rx) = backend.subscriptions();
+        assert!(backend.subscribe_pattern(&mut subscriptions, "news.*"));
+        assert!(backend.subscribe_channel(&mut subscriptions, "news.tech"));
+        assert!(!backend.subscribe_pattern(&mut subscriptions, "news.*"));
+        assert_eq!(subscriptions.count(), 2);
+
+        // once for the channel, once for the pattern
+        assert_eq!(backend.publish("news.tech", "hi"), 2);
+        assert_eq!(rx.try_recv().unwrap().pattern, None);
+        let message = rx.try_recv().unwrap();
+        assert_eq!(message.pattern.as_deref(), Some("news.*"));
+        assert_eq!(message.channel, "news.tech");
+        assert_eq!(backend.publish("sports", "hi"), 0);
+
+        assert!(backend.unsubscribe_pattern(&mut subscriptions, "news.*"));
+        assert_eq!(backend.publish("news.art", "hi"), 0);
+        assert_eq!(subscriptions.patterns().count(), 0);
+    }
+
+    #[test]
+    fn test_shard_subscriptions() {
+        let backend = Backend::new();
+        let (mut subscriptions, mut rx) = backend.subscriptions();
+        assert!(backend.subscribe_shard_channel(&mut subscriptions, "orders"));
+        assert!(backend.subscribe_pattern(&mut subscriptions, "*"));
+        assert_eq!(subscriptions.count(), 1);
+        assert_eq!(subscriptions.shard_count(), 1);
+
+        // the registries are apart
+        assert_eq!(backend.spublish("orders", "1"), 1);
+        assert!(rx.try_recv().unwrap().sharded);
+        assert_eq!(backend.publish("orders", "2"), 1);
+        assert_eq!(rx.try_recv().unwrap().pattern.as_deref(), Some("*"));
+
+        assert!(backend.unsubscribe_shard_channel(&mut subscriptions, "orders"));
+        assert_eq!(backend.spublish("orders", "3"), 0);
+        assert!(subscriptions.is_subscribed());
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+/// The default list-max-listpack-size: nodes of at most 8kb.
+pub const DEFAULT_LIST_MAX_LISTPACK_SIZE: i64 = -2;
+
+// the byte limits of a node for list-max-listpack-size -1 to -5
+const NODE_SIZE_LIMITS: [usize; 5] = [4096, 8192, 16384, 32768, 65536];
+
+/// The elements of a list, stored as a deque of listpacks: contiguous buffers of length
+/// prefixed elements, so an element costs a couple of bytes on top of its content instead of
+/// a whole frame and its allocation.
+///
+/// `fill` bounds the nodes like list-max-listpack-size: a positive value is a number of
+/// elements, -1 to -5 a size of 4kb to 64kb. A list that fits a single node is reported with
+/// the listpack encoding, a longer one with the quicklist encoding.
+#[derive(Debug, Clone)]
+pub struct QuickList {
+    nodes: VecDeque<Listpack>,
+    len: usize,
+    fill: i64,
+}
+
+impl QuickList {
+    pub fn new(fill: i64) -> Self {
+        Self {
+            nodes: VecDeque::new(),
+            len: 0,
+            fill,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The encoding reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        if self.nodes.len() > 1 {
+            "quicklist"
+        } else {
+            "listpack"
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let (node, i) = self.locate(index)?;
+        self.nodes[node].get(i)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &[u8]> {
+        self.nodes.iter().flat_map(Listpack::iter)
+    }
+
+    /// Up to count elements from start on.
+    pub fn range(&self, start: usize, count: usize) -> impl Iterator<Item = &[u8]> {
+        let (node, i) = self.locate(start).unwrap_or((self.nodes.len(), 0));
+        self.nodes
+            .range(node..)
+            .flat_map(Listpack::iter)
+            .skip(i)
+            .take(count)
+    }
+
+    pub fn push_front(&mut self, value: &[u8]) {
+        let fits = self
+            .nodes
+            .front()
+            .is_some_and(|node| self.fits(node, value));
+        if !fits {
+            self.nodes.push_front(Listpack::default());
+        }
+        if let Some(node) = self.nodes.front_mut() {
+            node.insert(0, value);
+        }
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, value: &[u8]) {
+        let fits = self.nodes.back().is_some_and(|node| self.fits(node, value));
+        if !fits {
+            self.nodes.push_back(Listpack::default());
+        }
+        if let Some(node) = self.nodes.back_mut() {
+            node.insert(node.len, value);
+        }
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<Vec<u8>> {
+        self.remove(0)
+    }
+
+    pub fn pop_back(&mut self) -> Option<Vec<u8>> {
+        self.remove(self.len.checked_sub(1)?)
+    }
+
+    /// Insert value at index, shifting the following elements. Panics if index > len.
+    pub fn insert(&mut self, index: usize, value: &[u8]) {
+        assert!(index <= self.len, "insertion index out of bounds");
+        if index == self.len {
+            return self.push_back(value);
+        }
+
+        let (n, i) = self.locate(index).unwrap_or_default();
+        self.nodes[n].insert(i, value);
+        self.len += 1;
+        self.split_if_full(n);
+    }
+
+    /// Replace the element at index, false if it's out of range.
+    pub fn set(&mut self, index: usize, value: &[u8]) -> bool {
+        let Some((n, i)) = self.locate(index) else {
+            return false;
+        };
+        self.nodes[n].remove(i);
+        self.nodes[n].insert(i, value);
+        self.split_if_full(n);
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Vec<u8>> {
+        let (n, i) = self.locate(index)?;
+        let value = self.nodes[n].remove(i);
+        self.len -= 1;
+        if self.nodes[n].len == 0 {
+            self.nodes.remove(n);
+        } else {
+            // a node that shrunk may now fit with a neighbour
+            self.merge_next(n);
+            if n > 0 {
+                self.merge_next(n - 1);
+            }
+        }
+        Some(value)
+    }
+
+    /// Keep only the count elements from start on.
+    pub fn retain_range(&mut self, start: usize, count: usize) {
+        let end = start.saturating_add(count).min(self.len);
+        while self.len > end {
+            let excess = self.len - end;
+            let Some(node) = self.nodes.back_mut() else {
+                break;
+            };
+            if node.len <= excess {
+                self.len -= node.len;
+                self.nodes.pop_back();
+            } else {
+                node.truncate(node.len - excess);
+                self.len -= excess;
+            }
+        }
+
+        let mut excess = start.min(self.len);
+        while excess > 0 {
+            let Some(node) = self.nodes.front_mut() else {
+                break;
+            };
+            let n = node.len.min(excess);
+            if n == node.len {
+                self.nodes.pop_front();
+            } else {
+                node.drain_front(n);
+            }
+            self.len -= n;
+            excess -= n;
+        }
+        if !self.nodes.is_empty() {
+            self.merge_next(0);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.len = 0;
+    }
+
+    /// Bytes allocated by the nodes.
+    pub fn heap_size(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| size_of::<Listpack>() + node.buf.capacity())
+            .sum()
+    }
+
+    // the node holding index and the position of the element in it, walking from the closest end
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+
+        if index < self.len / 2 {
+            let mut index = index;
+            for (n, node) in self.nodes.iter().enumerate() {
+                if index < node.len {
+                    return Some((n, index));
+                }
+                index -= node.len;
+            }
+        } else {
+            let mut from_back = self.len - index;
+            for (n, node) in self.nodes.iter().enumerate().rev() {
+                if from_back <= node.len {
+                    return Some((n, node.len - from_back));
+                }
+                from_back -= node.len;
+            }
+        }
+        None
+    }
+
+    fn fits(&self, node: &Listpack, value: &[u8]) -> bool {
+        self.within_fill(node.len + 1, node.buf.len() + entry_size(value.len()))
+    }
+
+    fn within_fill(&self, len: usize, bytes: usize) -> bool {
+        if self.fill > 0 {
+            len <= self.fill as usize
+        } else {
+            let limit = self
+                .fill
+                .unsigned_abs()
+                .clamp(1, NODE_SIZE_LIMITS.len() as u64);
+            bytes <= NODE_SIZE_LIMITS[limit as usize - 1]
+        }
+    }
+
+    // split node n in two halves once it's over the limit
+    fn split_if_full(&mut self, n: usize) {
+        let node = &self.nodes[n];
+        if node.len > 1 && !self.within_fill(node.len, node.buf.len()) {
+            let half = node.len / 2;
+            let tail = self.nodes[n].split_off(half);
+            self.nodes.insert(n + 1, tail);
+        }
+    }
+
+    // merge node n + 1 into node n if they fit in a single node
+    fn merge_next(&mut self, n: usize) {
+        let (Some(node), Some(next)) = (self.nodes.get(n), self.nodes.get(n + 1)) else {
+            return;
+        };
+        if self.within_fill(node.len + next.len, node.buf.len() + next.buf.len()) {
+            if let Some(next) = self.nodes.remove(n + 1) {
+                self.nodes[n].append(next);
+            }
+        }
+    }
+}
+
+impl Default for QuickList {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIST_MAX_LISTPACK_SIZE)
+    }
+}
+
+// lists are equal when their elements are, however they are split in nodes
+impl PartialEq for QuickList {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: AsRef<[u8]>> FromIterator<T> for QuickList {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = QuickList::default();
+        for value in iter {
+            list.push_back(value.as_ref());
+        }
+        list
+    }
+}
+
+// a node of the list. An entry is the varint length of the element, its bytes, then the
+// length of the whole entry again with its bytes reversed, so the node can be walked from
+// either end.
+#[derive(Debug, Clone, Default)]
+struct Listpack {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+impl Listpack {
+    fn get(&self, index: usize) -> Option<&[u8]> {
+        (index < self.len).then(|| self.entry(self.offset(index)).0)
+    }
+
+    fn iter(&self) -> ListpackIter<'_> {
+        ListpackIter {
+            node: self,
+            front: 0,
+            back: self.buf.len(),
+            remaining: self.len,
+        }
+    }
+
+    fn insert(&mut self, index: usize, value: &[u8]) {
+        let offset = self.offset(index);
+        self.buf.splice(offset..offset, encode_entry(value));
+        self.len += 1;
+    }
+
+    fn remove(&mut self, index: usize) -> Vec<u8> {
+        let offset = self.offset(index);
+        let (value, next) = self.entry(offset);
+        let value = value.to_vec();
+        self.buf.drain(offset..next);
+        self.len -= 1;
+        value
+    }
+
+    fn split_off(&mut self, at: usize) -> Listpack {
+        let offset = self.offset(at);
+        let tail = Listpack {
+            buf: self.buf.split_off(offset),
+            len: self.len - at,
+        };
+        self.len = at;
+        tail
+    }
+
+    fn append(&mut self, other: Listpack) {
+        self.buf.extend_from_slice(&other.buf);
+        self.len += other.len;
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let offset = self.offset(len);
+        self.buf.truncate(offset);
+        self.len = len;
+    }
+
+    fn drain_front(&mut self, n: usize) {
+        let offset = self.offset(n);
+        self.buf.drain(..offset);
+        self.len -= n;
+    }
+
+    // the byte offset of the entry at index, index may be len for the end of the node
+    fn offset(&self, index: usize) -> usize {
+        if index <= self.len / 2 {
+            (0..index).fold(0, |offset, _| self.entry(offset).1)
+        } else {
+            (index..self.len).fold(self.buf.len(), |end, _| self.entry_start(end))
+        }
+    }
+
+    // the element of the entry at offset, and the offset of the next entry
+    fn entry(&self, offset: usize) -> (&[u8], usize) {
+        let (len, size) = read_varint(self.buf[offset..].iter());
+        let start = offset + size;
+        let end = start + len;
+        (&self.buf[start..end], end + varint_size(end - offset))
+    }
+
+    // the offset of the entry ending at end
+    fn entry_start(&self, end: usize) -> usize {
+        let (len, size) = read_varint(self.buf[..end].iter().rev());
+        end - size - len
+    }
+}
+
+struct ListpackIter<'a> {
+    node: &'a Listpack,
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for ListpackIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (value, next) = self.node.entry(self.front);
+        self.front = next;
+        self.remaining -= 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for ListpackIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.back = self.node.entry_start(self.back);
+        self.remaining -= 1;
+        Some(self.node.entry(self.back).0)
+    }
+}
+
+fn encode_entry(value: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(entry_size(value.len()));
+    write_varint(&mut buf, value.len());
+    buf.extend_from_slice(value);
+    let start = buf.len();
+    write_varint(&mut buf, start);
+    buf[start..].reverse();
+    buf
+}
+
+fn entry_size(len: usize) -> usize {
+    let len = varint_size(len) + len;
+    len + varint_size(len)
+}
+
+// 7 bits per byte, least significant first, the high bit set on all but the last byte
+fn write_varint(buf: &mut Vec<u8>, mut n: usize) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+// the value and the size of the varint starting the bytes
+fn read_varint<'a>(bytes: impl Iterator<Item = &'a u8>) -> (usize, usize) {
+    let mut n = 0;
+    for (i, byte) in bytes.enumerate() {
+        n |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return (n, i + 1);
+        }
+    }
+    unreachable!("truncated listpack entry")
+}
+
+fn varint_size(n: usize) -> usize {
+    let mut size = 1;
+    let mut n = n >> 7;
+    while n > 0 {
+        size += 1;
+        n >>= 7;
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elements(list: &QuickList) -> Vec<String> {
+        list.iter()
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_push_pop_both_ends() {
+        let mut list = QuickList::new(3);
+        for i in 0..5 {
+            list.push_back(i.to_string().as_bytes());
+        }
+        list.push_front(b"-1");
+        assert_eq!(list.len(), 6);
+        assert_eq!(elements(&list), ["-1", "0", "1", "2", "3", "4"]);
+        assert_eq!(list.encoding(), "quicklist");
+
+        let reversed: Vec<_> = list.iter().rev().collect();
+        assert_eq!(reversed.first(), Some(&&b"4"[..]));
+        assert_eq!(reversed.last(), Some(&&b"-1"[..]));
+
+        assert_eq!(list.pop_front(), Some(b"-1".to_vec()));
+        assert_eq!(list.pop_back(), Some(b"4".to_vec()));
+        assert_eq!(list.get(2), Some(&b"2"[..]));
+        assert_eq!(list.get(4), None);
+
+        // shrinking back to a single node goes back to listpack
+        list.pop_back();
+        assert_eq!(elements(&list), ["0", "1", "2"]);
+        assert_eq!(list.encoding(), "listpack");
+    }
+
+    #[test]
+    fn test_insert_set_remove() {
+        let mut list: QuickList = ["a", "c"].iter().collect();
+        list.insert(1, b"b");
+        list.insert(3, b"d");
+        list.insert(0, b"");
+        assert_eq!(elements(&list), ["", "a", "b", "c", "d"]);
+
+        // elements longer than a one byte varint
+        let big = vec![b'x'; 300];
+        assert!(list.set(2, &big));
+        assert!(!list.set(5, b"z"));
+        assert_eq!(list.get(2), Some(&big[..]));
+        assert_eq!(list.iter().rev().nth(2), Some(&big[..]));
+
+        assert_eq!(list.remove(2), Some(big));
+        assert_eq!(list.remove(9), None);
+        assert_eq!(elements(&list), ["", "a", "c", "d"]);
+    }
+
+    #[test]
+    fn test_node_size_limit() {
+        // 4kb nodes: 100 elements of 100 bytes don't fit in one
+        let mut list = QuickList::new(-1);
+        let value = vec![b'v'; 100];
+        for _ in 0..100 {
+            list.push_back(&value);
+        }
+        assert!(list.nodes.len() > 1);
+        assert!(list.nodes.iter().all(|node| node.buf.len() <= 4096));
+
+        // a single element larger than the limit still gets a node
+        let mut list = QuickList::new(-1);
+        list.push_back(&vec![b'v'; 10000]);
+        assert_eq!(list.encoding(), "listpack");
+        list.push_back(b"small");
+        assert_eq!(list.encoding(), "quicklist");
+    }
+
+    #[test]
+    fn test_split_and_range() {
+        let mut list = QuickList::new(4);
+        for i in 0..8 {
+            list.push_back(i.to_string().as_bytes());
+        }
+        // inserting into a full node splits it
+        list.insert(2, b"x");
+        assert!(list.nodes.iter().all(|node| node.len <= 4));
+        assert_eq!(
+            list.range(1, 4).collect::<Vec<_>>(),
+            [&b"1"[..], b"x", b"2", b"3"]
+        );
+        assert_eq!(list.range(8, 10).collect::<Vec<_>>(), [&b"7"[..]]);
+        assert_eq!(list.range(9, 10).count(), 0);
+
+        list.retain_range(2, 5);
+        assert_eq!(elements(&list), ["x", "2", "3", "4", "5"]);
+        list.retain_range(1, 100);
+        assert_eq!(elements(&list), ["2", "3", "4", "5"]);
+        assert_eq!(list.encoding(), "listpack");
+    }
+}
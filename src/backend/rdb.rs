@@ -0,0 +1,1377 @@
+use crate::backend::{now_ms, string_bytes};
+use crate::{
+    BulkString, ConsumerGroup, HashTable, PendingEntry, QuickList, SetMembers, SnapshotEntry,
+    SortedSet, Stream, StreamFields, StreamId, Value, DEFAULT_LIST_MAX_LISTPACK_SIZE,
+    DEFAULT_SET_MAX_INTSET_ENTRIES, STREAM_NODE_MAX_ENTRIES,
+};
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::sync::Arc;
+
+const MAGIC: &[u8] = b"REDIS";
+// the format of Redis 7.4, the one written, whose hashes keep the TTLs of their fields
+const RDB_VERSION: u32 = 12;
+// the newest format read
+const MAX_RDB_VERSION: u32 = 12;
+// the version that added the checksum after the end marker
+const CHECKSUM_VERSION: u32 = 5;
+const REDIS_VERSION: &str = "7.4.0";
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_LIST_ZIPLIST: u8 = 10;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_ZSET_ZIPLIST: u8 = 12;
+const TYPE_HASH_ZIPLIST: u8 = 13;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+const TYPE_STREAM_LISTPACKS: u8 = 15;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const TYPE_SET_LISTPACK: u8 = 20;
+const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+// hashes with field TTLs, as written by the release candidates of Redis 7.4 then by its
+// releases
+const TYPE_HASH_METADATA_PRE_GA: u8 = 22;
+const TYPE_HASH_LISTPACK_EX_PRE_GA: u8 = 23;
+const TYPE_HASH_METADATA: u8 = 24;
+const TYPE_HASH_LISTPACK_EX: u8 = 25;
+
+const OPCODE_SLOT_INFO: u8 = 0xf4;
+const OPCODE_FUNCTION2: u8 = 0xf5;
+const OPCODE_IDLE: u8 = 0xf8;
+const OPCODE_FREQ: u8 = 0xf9;
+const OPCODE_AUX: u8 = 0xfa;
+const OPCODE_RESIZEDB: u8 = 0xfb;
+const OPCODE_EXPIRETIME_MS: u8 = 0xfc;
+const OPCODE_EXPIRETIME: u8 = 0xfd;
+const OPCODE_SELECTDB: u8 = 0xfe;
+const OPCODE_EOF: u8 = 0xff;
+
+// the special encodings of a string, flagged by the two top bits of its length
+const ENC_INT8: u64 = 0;
+const ENC_INT16: u64 = 1;
+const ENC_INT32: u64 = 2;
+const ENC_LZF: u64 = 3;
+
+// the containers of a quicklist node
+const QUICKLIST_NODE_PLAIN: usize = 1;
+const QUICKLIST_NODE_PACKED: usize = 2;
+const QUICKLIST_NODE_ENTRIES: usize = 128;
+
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+/// Serialize the entries of a snapshot into a dump file in the RDB format of Redis 7.4, so
+/// it loads in Redis and reads with the usual RDB tooling. Strings, lists, sets, hashes with
+/// the TTLs of their fields, sorted sets and streams are kept.
+pub fn encode_dump<'a>(entries: impl IntoIterator<Item = &'a SnapshotEntry>) -> Vec<u8> {
+    let entries: Vec<_> = entries.into_iter().collect();
+    let mut w = Writer::default();
+    w.raw(format!("REDIS{:04}", RDB_VERSION).as_bytes());
+    w.aux("redis-ver", REDIS_VERSION.as_bytes());
+    w.aux("redis-bits", b"64");
+    w.aux("ctime", (now_ms() / 1000).to_string().as_bytes());
+
+    w.u8(OPCODE_SELECTDB);
+    w.len(0);
+    w.u8(OPCODE_RESIZEDB);
+    w.len(entries.len());
+    w.len(entries.iter().filter(|e| e.expire_at.is_some()).count());
+    for entry in entries {
+        if let Some(at) = entry.expire_at {
+            w.u8(OPCODE_EXPIRETIME_MS);
+            w.raw(&at.to_le_bytes());
+        }
+        write_value(&mut w, &entry.key, &entry.value);
+    }
+
+    w.u8(OPCODE_EOF);
+    let checksum = crc64(0, &w.buf);
+    w.raw(&checksum.to_le_bytes());
+    w.buf
+}
+
+/// Parse a dump file in the RDB format back into the entries it holds, with the lists, sets
+/// and hashes created by new_list, new_set and new_hash so they take the current encoding
+/// limits. Every
+/// encoding Redis 7.4 writes for strings, lists, sets, hashes, sorted sets and streams is
+/// read. The keys of all databases end up in the one keyspace, and the libraries of
+/// functions and the auxiliary fields are skipped.
+pub fn decode_dump(
+    data: &[u8],
+    new_list: impl Fn() -> QuickList,
+    new_set: impl Fn() -> SetMembers,
+    new_hash: impl Fn() -> HashTable,
+) -> io::Result<Vec<SnapshotEntry>> {
+    let (entries, len) = decode_dump_prefix(data, new_list, new_set, new_hash)?;
+    if len != data.len() {
+        return Err(invalid("trailing bytes after the end of the RDB file"));
+    }
+    Ok(entries)
+}
+
+/// Whether data starts as a dump file does, as an AOF rewritten with an RDB preamble.
+pub fn is_dump(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Parse the dump file data starts with, as `decode_dump` does, into its entries and its
+/// length, what follows it being left alone: the commands of an AOF with an RDB preamble.
+pub fn decode_dump_prefix(
+    data: &[u8],
+    new_list: impl Fn() -> QuickList,
+    new_set: impl Fn() -> SetMembers,
+    new_hash: impl Fn() -> HashTable,
+) -> io::Result<(Vec<SnapshotEntry>, usize)> {
+    let mut r = Reader::new(data);
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not an RDB file"));
+    }
+    let version = std::str::from_utf8(r.take(4)?)
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or_else(|| invalid("invalid RDB version"))?;
+    if !(1..=MAX_RDB_VERSION).contains(&version) {
+        return Err(invalid(format!("unsupported RDB version {}", version)));
+    }
+
+    let mut entries = Vec::new();
+    let mut expire_at = None;
+    loop {
+        let kind = match r.u8()? {
+            OPCODE_EOF => break,
+            OPCODE_EXPIRETIME_MS => {
+                expire_at = Some(r.int_le(8)?);
+                continue;
+            }
+            OPCODE_EXPIRETIME => {
+                expire_at = Some(r.int_le(4)? * 1000);
+                continue;
+            }
+            OPCODE_SELECTDB => {
+                r.len()?;
+                continue;
+            }
+            OPCODE_RESIZEDB => {
+                r.len()?;
+                r.len()?;
+                continue;
+            }
+            OPCODE_SLOT_INFO => {
+                r.len()?;
+                r.len()?;
+                r.len()?;
+                continue;
+            }
+            OPCODE_AUX => {
+                r.string()?;
+                r.string()?;
+                continue;
+            }
+            OPCODE_FUNCTION2 => {
+                r.string()?;
+                continue;
+            }
+            OPCODE_IDLE => {
+                r.len()?;
+                continue;
+            }
+            OPCODE_FREQ => {
+                r.u8()?;
+                continue;
+            }
+            kind => kind,
+        };
+        let key = r.text()?;
+        let value = read_value(&mut r, kind, &new_list, &new_set, &new_hash)?;
+        entries.push(SnapshotEntry {
+            key,
+            value: Arc::new(value),
+            expire_at: expire_at.take(),
+        });
+    }
+
+    if version >= CHECKSUM_VERSION {
+        let end = r.pos;
+        let expected = u64::from_le_bytes(r.take(8)?.try_into().expect("8 bytes"));
+        // a zero checksum is written when the checksum is disabled
+        if expected != 0 && crc64(0, &data[..end]) != expected {
+            return Err(invalid("wrong RDB checksum"));
+        }
+    }
+    Ok((entries, r.pos))
+}
+
+/// Serialize a value into the payload DUMP replies and RESTORE takes, as Redis 7.4 does: the
+/// value in the RDB format, then the RDB version and a checksum of both.
+pub fn encode_value_dump(value: &Value) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.u8(value_type(value));
+    write_object(&mut w, value);
+    w.raw(&(RDB_VERSION as u16).to_le_bytes());
+    let checksum = crc64(0, &w.buf);
+    w.raw(&checksum.to_le_bytes());
+    w.buf
+}
+
+/// Parse a payload of DUMP back into its value, see `decode_dump` for new_list, new_set and
+/// new_hash. A payload of a newer RDB version, or whose checksum is wrong, is rejected.
+pub fn decode_value_dump(
+    data: &[u8],
+    new_list: impl Fn() -> QuickList,
+    new_set: impl Fn() -> SetMembers,
+    new_hash: impl Fn() -> HashTable,
+) -> io::Result<Value> {
+    let Some(end) = data.len().checked_sub(10) else {
+        return Err(invalid("DUMP payload too short"));
+    };
+    let version = u16::from_le_bytes([data[end], data[end + 1]]) as u32;
+    let expected = u64::from_le_bytes(data[end + 2..].try_into().expect("8 bytes"));
+    if version > MAX_RDB_VERSION || crc64(0, &data[..end + 2]) != expected {
+        return Err(invalid("DUMP payload version or checksum are wrong"));
+    }
+    let mut r = Reader::new(&data[..end]);
+    let kind = r.u8()?;
+    let value = read_value(&mut r, kind, new_list, new_set, new_hash)?;
+    if r.pos != end {
+        return Err(invalid("trailing bytes after the DUMP payload value"));
+    }
+    Ok(value)
+}
+
+/// What `check_dump` found in a dump file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpCheck {
+    pub version: u32,
+    /// How many keys hold each type, by the name TYPE replies.
+    pub keys: BTreeMap<&'static str, usize>,
+    /// How many keys have a TTL.
+    pub expires: usize,
+    /// Whether a checksum was verified: there's none before version 5, nor when it was
+    /// disabled.
+    pub checksum: bool,
+}
+
+/// Check a dump file by decoding every value it holds, verifying its checksum if it has one.
+pub fn check_dump(data: &[u8]) -> io::Result<DumpCheck> {
+    let entries = decode_dump(
+        data,
+        || QuickList::new(DEFAULT_LIST_MAX_LISTPACK_SIZE),
+        || SetMembers::new(DEFAULT_SET_MAX_INTSET_ENTRIES),
+        HashTable::new,
+    )?;
+    // decoding checked the header
+    let version = std::str::from_utf8(&data[MAGIC.len()..MAGIC.len() + 4])
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or_default();
+    let mut keys = BTreeMap::new();
+    for entry in &entries {
+        *keys.entry(entry.value.type_name()).or_default() += 1;
+    }
+    Ok(DumpCheck {
+        version,
+        keys,
+        expires: entries.iter().filter(|e| e.expire_at.is_some()).count(),
+        checksum: version >= CHECKSUM_VERSION && data[data.len() - 8..] != [0; 8],
+    })
+}
+
+fn write_value(w: &mut Writer, key: &str, value: &Value) {
+    w.u8(value_type(value));
+    w.string(key.as_bytes());
+    write_object(w, value);
+}
+
+fn value_type(value: &Value) -> u8 {
+    match value {
+        Value::String(_) => TYPE_STRING,
+        Value::List(_) => TYPE_LIST_QUICKLIST_2,
+        Value::Set(_) => TYPE_SET,
+        Value::Hash(hash) if hash.expires().next().is_some() => TYPE_HASH_METADATA,
+        Value::Hash(_) => TYPE_HASH,
+        Value::ZSet(_) => TYPE_ZSET_2,
+        Value::Stream(_) => TYPE_STREAM_LISTPACKS,
+    }
+}
+
+// the value in the encoding value_type tells
+fn write_object(w: &mut Writer, value: &Value) {
+    match value {
+        Value::String(v) => w.string(&string_bytes(v)),
+        Value::List(list) => {
+            let items: Vec<_> = list.iter().collect();
+            let nodes = items.chunks(QUICKLIST_NODE_ENTRIES);
+            w.len(nodes.len());
+            for node in nodes {
+                let mut lp = ListpackWriter::default();
+                for item in node {
+                    lp.push(item);
+                }
+                w.len(QUICKLIST_NODE_PACKED);
+                w.string(&lp.finish());
+            }
+        }
+        Value::Set(set) => {
+            w.len(set.len());
+            for member in set.iter() {
+                w.string(member.as_bytes());
+            }
+        }
+        Value::Hash(hash) => {
+            // the TTL of each field goes ahead of it, relative to the earliest one plus 1, 0
+            // for none
+            let min_expire = hash.expires().map(|(_, at)| at).min();
+            if let Some(min) = min_expire {
+                w.raw(&min.to_le_bytes());
+            }
+            w.len(hash.len());
+            for (field, v) in hash.iter() {
+                if let Some(min) = min_expire {
+                    let ttl = hash.expire_time(field).map_or(0, |at| at - min + 1);
+                    w.len(ttl as usize);
+                }
+                w.string(field.as_bytes());
+                w.string(&string_bytes(v));
+            }
+        }
+        Value::ZSet(zset) => {
+            w.len(zset.len());
+            for (member, score) in zset.iter() {
+                w.string(member.as_bytes());
+                w.raw(&score.to_le_bytes());
+            }
+        }
+        Value::Stream(stream) => write_stream(w, stream),
+    }
+}
+
+fn read_value(
+    r: &mut Reader,
+    kind: u8,
+    new_list: impl Fn() -> QuickList,
+    new_set: impl Fn() -> SetMembers,
+    new_hash: impl Fn() -> HashTable,
+) -> io::Result<Value> {
+    let value = match kind {
+        TYPE_STRING => Value::String(BulkString::new(r.string()?).into()),
+        TYPE_LIST => {
+            let mut list = new_list();
+            for _ in 0..r.len()? {
+                list.push_back(&r.string()?);
+            }
+            Value::List(list)
+        }
+        TYPE_LIST_ZIPLIST => {
+            let mut list = new_list();
+            for item in ziplist_entries(&r.string()?)? {
+                list.push_back(&item);
+            }
+            Value::List(list)
+        }
+        TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2 => {
+            let mut list = new_list();
+            for _ in 0..r.len()? {
+                let container = match kind {
+                    TYPE_LIST_QUICKLIST_2 => r.len()?,
+                    _ => QUICKLIST_NODE_PACKED,
+                };
+                let node = r.string()?;
+                let items = match (kind, container) {
+                    (_, QUICKLIST_NODE_PLAIN) => vec![node],
+                    (TYPE_LIST_QUICKLIST, _) => ziplist_entries(&node)?,
+                    (_, QUICKLIST_NODE_PACKED) => listpack_entries(&node)?,
+                    _ => {
+                        return Err(invalid(format!(
+                            "unknown quicklist container {}",
+                            container
+                        )))
+                    }
+                };
+                for item in items {
+                    list.push_back(&item);
+                }
+            }
+            Value::List(list)
+        }
+        TYPE_SET => {
+            let mut set = new_set();
+            for _ in 0..r.len()? {
+                set.insert(r.text()?);
+            }
+            Value::Set(set)
+        }
+        TYPE_SET_INTSET | TYPE_SET_LISTPACK => {
+            let blob = r.string()?;
+            let members = match kind {
+                TYPE_SET_INTSET => intset_entries(&blob)?,
+                _ => listpack_entries(&blob)?,
+            };
+            let mut set = new_set();
+            for member in members {
+                set.insert(utf8(member)?);
+            }
+            Value::Set(set)
+        }
+        TYPE_HASH => {
+            let mut hash = new_hash();
+            for _ in 0..r.len()? {
+                let field = r.text()?;
+                hash.insert(field, BulkString::new(r.string()?).into());
+            }
+            Value::Hash(hash)
+        }
+        TYPE_HASH_ZIPLIST | TYPE_HASH_LISTPACK => {
+            let blob = r.string()?;
+            let items = match kind {
+                TYPE_HASH_ZIPLIST => ziplist_entries(&blob)?,
+                _ => listpack_entries(&blob)?,
+            };
+            let mut hash = new_hash();
+            for (field, v) in pairs(items)? {
+                hash.insert(utf8(field)?, BulkString::new(v).into());
+            }
+            Value::Hash(hash)
+        }
+        TYPE_HASH_METADATA_PRE_GA | TYPE_HASH_METADATA => {
+            // the TTLs were absolute before Redis 7.4 GA, then relative to the earliest one
+            let min_expire = match kind {
+                TYPE_HASH_METADATA => Some(r.int_le(8)?),
+                _ => None,
+            };
+            let mut hash = new_hash();
+            for _ in 0..r.len()? {
+                let ttl = r.len()? as i64;
+                let field = r.text()?;
+                hash.insert(field.clone(), BulkString::new(r.string()?).into());
+                match (ttl, min_expire) {
+                    (0, _) => {}
+                    (ttl, Some(min)) => {
+                        hash.set_expire(&field, ttl + min - 1);
+                    }
+                    (at, None) => {
+                        hash.set_expire(&field, at);
+                    }
+                }
+            }
+            Value::Hash(hash)
+        }
+        TYPE_HASH_LISTPACK_EX_PRE_GA | TYPE_HASH_LISTPACK_EX => {
+            // the earliest TTL, which the fields tell anyway
+            if kind == TYPE_HASH_LISTPACK_EX {
+                r.int_le(8)?;
+            }
+            let items = listpack_entries(&r.string()?)?;
+            if items.len() % 3 != 0 {
+                return Err(invalid("odd number of items in a hash listpack with TTLs"));
+            }
+            let mut hash = new_hash();
+            let mut items = items.into_iter();
+            while let (Some(field), Some(v), Some(at)) = (items.next(), items.next(), items.next())
+            {
+                let field = utf8(field)?;
+                hash.insert(field.clone(), BulkString::new(v).into());
+                // an absolute time, 0 for none
+                match parse_int(&at)? {
+                    0 => {}
+                    at => {
+                        hash.set_expire(&field, at);
+                    }
+                }
+            }
+            Value::Hash(hash)
+        }
+        TYPE_ZSET | TYPE_ZSET_2 => {
+            let mut zset = SortedSet::new();
+            for _ in 0..r.len()? {
+                let member = r.text()?;
+                let score = match kind {
+                    TYPE_ZSET_2 => f64::from_le_bytes(r.take(8)?.try_into().expect("8 bytes")),
+                    _ => r.legacy_double()?,
+                };
+                zset.insert(member, score);
+            }
+            Value::ZSet(zset)
+        }
+        TYPE_ZSET_ZIPLIST | TYPE_ZSET_LISTPACK => {
+            let blob = r.string()?;
+            let items = match kind {
+                TYPE_ZSET_ZIPLIST => ziplist_entries(&blob)?,
+                _ => listpack_entries(&blob)?,
+            };
+            let mut zset = SortedSet::new();
+            for (member, score) in pairs(items)? {
+                zset.insert(utf8(member)?, parse_score(&score)?);
+            }
+            Value::ZSet(zset)
+        }
+        TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+            Value::Stream(read_stream(r, kind)?)
+        }
+        kind => return Err(invalid(format!("unsupported RDB value type {}", kind))),
+    };
+    Ok(value)
+}
+
+// the entries of a stream go in listpacks of STREAM_NODE_MAX_ENTRIES entries, each starting
+// with a master entry whose fields the entries with the same ones don't repeat
+fn write_stream(w: &mut Writer, stream: &Stream) {
+    let entries: Vec<_> = stream.iter().collect();
+    let nodes = entries.chunks(STREAM_NODE_MAX_ENTRIES);
+    w.len(nodes.len());
+    for node in nodes {
+        let (master, master_fields) = node[0];
+        let mut lp = ListpackWriter::default();
+        lp.push_int(node.len() as i64);
+        lp.push_int(0);
+        lp.push_int(master_fields.len() as i64);
+        for (field, _) in master_fields {
+            lp.push(field.as_bytes());
+        }
+        lp.push_int(0);
+        for (id, fields) in node {
+            let same_fields = fields.len() == master_fields.len()
+                && fields
+                    .iter()
+                    .zip(master_fields)
+                    .all(|((a, _), (b, _))| a == b);
+            lp.push_int(match same_fields {
+                true => STREAM_ITEM_FLAG_SAMEFIELDS,
+                false => 0,
+            });
+            lp.push_int(id.ms.wrapping_sub(master.ms) as i64);
+            lp.push_int(id.seq.wrapping_sub(master.seq) as i64);
+            if !same_fields {
+                lp.push_int(fields.len() as i64);
+            }
+            for (field, v) in fields.iter() {
+                if !same_fields {
+                    lp.push(field.as_bytes());
+                }
+                lp.push(&string_bytes(v));
+            }
+            // how many items the entry spans, to walk the listpack backwards
+            let lp_count = match same_fields {
+                true => fields.len() + 3,
+                false => fields.len() * 2 + 4,
+            };
+            lp.push_int(lp_count as i64);
+        }
+        w.string(&raw_id(*master));
+        w.string(&lp.finish());
+    }
+    w.len(stream.len());
+    w.id(stream.last_id());
+
+    let groups: Vec<_> = stream.groups().collect();
+    w.len(groups.len());
+    for (name, group) in groups {
+        w.string(name.as_bytes());
+        w.id(group.last_delivered());
+        w.len(group.pending().len());
+        for (id, entry) in group.pending() {
+            w.raw(&raw_id(*id));
+            w.raw(&entry.delivered_at.to_le_bytes());
+            w.len(entry.deliveries as usize);
+        }
+        let consumers: Vec<_> = group.consumers().collect();
+        w.len(consumers.len());
+        for (consumer, seen_at) in consumers {
+            w.string(consumer.as_bytes());
+            w.raw(&seen_at.to_le_bytes());
+            let pending: Vec<_> = group
+                .pending()
+                .iter()
+                .filter(|(_, entry)| &entry.consumer == consumer)
+                .collect();
+            w.len(pending.len());
+            for (id, _) in pending {
+                w.raw(&raw_id(*id));
+            }
+        }
+    }
+}
+
+fn read_stream(r: &mut Reader, kind: u8) -> io::Result<Stream> {
+    let mut stream = Stream::new();
+    for _ in 0..r.len()? {
+        let master = parse_raw_id(&r.string()?)?;
+        let items = listpack_entries(&r.string()?)?;
+        read_stream_node(&mut stream, master, &mut items.into_iter())?;
+    }
+    r.len()?;
+    stream.restore_last_id(r.id()?);
+    if kind >= TYPE_STREAM_LISTPACKS_2 {
+        // the first ID, the greatest deleted one and how many entries were ever added
+        r.id()?;
+        r.id()?;
+        r.len()?;
+    }
+
+    for _ in 0..r.len()? {
+        let name = r.text()?;
+        let mut group = ConsumerGroup::restore(r.id()?);
+        if kind >= TYPE_STREAM_LISTPACKS_2 {
+            r.len()?;
+        }
+        // the group holds the delivery of every pending entry, the consumers which of them
+        // are theirs
+        let mut deliveries = BTreeMap::new();
+        for _ in 0..r.len()? {
+            let id = parse_raw_id(r.take(16)?)?;
+            let delivered_at = r.int_le(8)?;
+            deliveries.insert(id, (delivered_at, r.len()? as u64));
+        }
+        for _ in 0..r.len()? {
+            let consumer = r.text()?;
+            group.restore_consumer(&consumer, r.int_le(8)?);
+            if kind >= TYPE_STREAM_LISTPACKS_3 {
+                r.int_le(8)?;
+            }
+            for _ in 0..r.len()? {
+                let id = parse_raw_id(r.take(16)?)?;
+                let (delivered_at, deliveries) = deliveries
+                    .remove(&id)
+                    .ok_or_else(|| invalid("pending entry of a consumer missing in its group"))?;
+                let entry = PendingEntry {
+                    consumer: consumer.clone(),
+                    delivered_at,
+                    deliveries,
+                };
+                group.restore_pending(id, entry);
+            }
+        }
+        stream.restore_group(name, group);
+    }
+    Ok(stream)
+}
+
+fn read_stream_node(
+    stream: &mut Stream,
+    master: StreamId,
+    items: &mut impl Iterator<Item = Vec<u8>>,
+) -> io::Result<()> {
+    let mut next = || items.next().ok_or_else(|| invalid("truncated stream node"));
+    let count = parse_int(&next()?)?;
+    let deleted = parse_int(&next()?)?;
+    let master_fields = (0..parse_int(&next()?)?)
+        .map(|_| utf8(next()?))
+        .collect::<io::Result<Vec<_>>>()?;
+    next()?;
+
+    for _ in 0..count + deleted {
+        let flags = parse_int(&next()?)?;
+        let ms = master.ms.wrapping_add(parse_int(&next()?)? as u64);
+        let seq = master.seq.wrapping_add(parse_int(&next()?)? as u64);
+        let mut fields = StreamFields::new();
+        if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+            for field in &master_fields {
+                fields.push((field.clone(), BulkString::new(next()?).into()));
+            }
+        } else {
+            for _ in 0..parse_int(&next()?)? {
+                let field = utf8(next()?)?;
+                fields.push((field, BulkString::new(next()?).into()));
+            }
+        }
+        next()?;
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            stream.restore_entry(StreamId::new(ms, seq), fields);
+        }
+    }
+    Ok(())
+}
+
+// the big endian milliseconds then sequence number keying the nodes of a stream
+fn raw_id(id: StreamId) -> [u8; 16] {
+    let mut raw = [0; 16];
+    raw[..8].copy_from_slice(&id.ms.to_be_bytes());
+    raw[8..].copy_from_slice(&id.seq.to_be_bytes());
+    raw
+}
+
+fn parse_raw_id(raw: &[u8]) -> io::Result<StreamId> {
+    if raw.len() != 16 {
+        return Err(invalid("invalid stream ID"));
+    }
+    Ok(StreamId::new(
+        u64::from_be_bytes(raw[..8].try_into().expect("8 bytes")),
+        u64::from_be_bytes(raw[8..].try_into().expect("8 bytes")),
+    ))
+}
+
+fn pairs(items: Vec<Vec<u8>>) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    if !items.len().is_multiple_of(2) {
+        return Err(invalid("odd number of items in a pairs encoding"));
+    }
+    let mut items = items.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(a), Some(b)) = (items.next(), items.next()) {
+        pairs.push((a, b));
+    }
+    Ok(pairs)
+}
+
+// the items of a listpack, integers as their decimal text
+fn listpack_entries(lp: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let total = lp
+        .get(..4)
+        .map(|total| u32::from_le_bytes(total.try_into().expect("4 bytes")) as usize);
+    if total != Some(lp.len()) || lp.len() < 7 || lp[lp.len() - 1] != 0xff {
+        return Err(invalid("invalid listpack"));
+    }
+
+    let mut r = Reader::new(&lp[6..lp.len() - 1]);
+    let mut items = Vec::new();
+    while r.pos < r.data.len() {
+        let start = r.pos;
+        let b = r.u8()?;
+        let item = if b & 0x80 == 0 {
+            (b as i64).to_string().into_bytes()
+        } else if b & 0xc0 == 0x80 {
+            r.take((b & 0x3f) as usize)?.to_vec()
+        } else if b & 0xe0 == 0xc0 {
+            let n = ((b & 0x1f) as i64) << 8 | r.u8()? as i64;
+            let n = if n >= 1 << 12 { n - (1 << 13) } else { n };
+            n.to_string().into_bytes()
+        } else if b & 0xf0 == 0xe0 {
+            let len = ((b & 0x0f) as usize) << 8 | r.u8()? as usize;
+            r.take(len)?.to_vec()
+        } else {
+            match b {
+                0xf0 => {
+                    let len = r.int_le(4)? as u32 as usize;
+                    r.take(len)?.to_vec()
+                }
+                0xf1 => r.int_le(2)?.to_string().into_bytes(),
+                0xf2 => r.int_le(3)?.to_string().into_bytes(),
+                0xf3 => r.int_le(4)?.to_string().into_bytes(),
+                0xf4 => r.int_le(8)?.to_string().into_bytes(),
+                _ => return Err(invalid("invalid listpack entry encoding")),
+            }
+        };
+        r.take(backlen_size(r.pos - start))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+// the items of a ziplist, the encoding of lists, hashes and sorted sets before Redis 7
+fn ziplist_entries(zl: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut r = Reader::new(zl);
+    r.take(10)?;
+    let mut items = Vec::new();
+    loop {
+        match r.u8()? {
+            0xff => break,
+            0xfe => {
+                r.take(4)?;
+            }
+            _ => {}
+        }
+        let b = r.u8()?;
+        let item = match b >> 6 {
+            0 => r.take((b & 0x3f) as usize)?.to_vec(),
+            1 => {
+                let len = ((b & 0x3f) as usize) << 8 | r.u8()? as usize;
+                r.take(len)?.to_vec()
+            }
+            2 => {
+                let len = u32::from_be_bytes(r.take(4)?.try_into().expect("4 bytes"));
+                r.take(len as usize)?.to_vec()
+            }
+            _ => {
+                let n = match b {
+                    0xc0 => r.int_le(2)?,
+                    0xd0 => r.int_le(4)?,
+                    0xe0 => r.int_le(8)?,
+                    0xf0 => r.int_le(3)?,
+                    0xfe => r.int_le(1)?,
+                    0xf1..=0xfd => (b & 0x0f) as i64 - 1,
+                    _ => return Err(invalid("invalid ziplist entry encoding")),
+                };
+                n.to_string().into_bytes()
+            }
+        };
+        items.push(item);
+    }
+    Ok(items)
+}
+
+fn intset_entries(intset: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut r = Reader::new(intset);
+    let width = r.int_le(4)? as usize;
+    if !matches!(width, 2 | 4 | 8) {
+        return Err(invalid("invalid intset encoding"));
+    }
+    let len = r.int_le(4)? as u32;
+    (0..len)
+        .map(|_| Ok(r.int_le(width)?.to_string().into_bytes()))
+        .collect()
+}
+
+// the size of the back length closing a listpack entry of len bytes
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+// the integer an item is stored as if its text is the canonical form of one, as Redis does
+fn canonical_int(bytes: &[u8]) -> Option<i64> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let n: i64 = text.parse().ok()?;
+    (n.to_string() == text).then_some(n)
+}
+
+fn parse_int(bytes: &[u8]) -> io::Result<i64> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| invalid("invalid integer"))
+}
+
+fn parse_score(bytes: &[u8]) -> io::Result<f64> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| invalid("invalid sorted set score"))
+}
+
+fn utf8(bytes: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|_| invalid("invalid UTF-8 string"))
+}
+
+fn lzf_decompress(input: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let truncated = || invalid("truncated LZF string");
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 32 {
+            // a run of ctrl + 1 literal bytes
+            let literal = input.get(i..i + ctrl + 1).ok_or_else(truncated)?;
+            out.extend_from_slice(literal);
+            i += ctrl + 1;
+        } else {
+            // a copy of bytes already written, offset and length packed with ctrl
+            let mut run = ctrl >> 5;
+            if run == 7 {
+                run += *input.get(i).ok_or_else(truncated)? as usize;
+                i += 1;
+            }
+            let low = *input.get(i).ok_or_else(truncated)? as usize;
+            i += 1;
+            let start = out
+                .len()
+                .checked_sub(((ctrl & 0x1f) << 8) + low + 1)
+                .ok_or_else(|| invalid("invalid LZF back reference"))?;
+            for k in 0..run + 2 {
+                out.push(out[start + k]);
+            }
+        }
+    }
+    if out.len() != len {
+        return Err(invalid("LZF string of the wrong length"));
+    }
+    Ok(out)
+}
+
+// CRC-64/Jones, reflected, as Redis checksums its RDB files
+const CRC64_TABLE: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0x95ac9329ac4bc9b5,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc64(mut crc: u64, data: &[u8]) -> u64 {
+    for byte in data {
+        crc = CRC64_TABLE[((crc ^ *byte as u64) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, n: u8) {
+        self.buf.push(n);
+    }
+
+    fn raw(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    // 6 bits, 14 bits, then 32 or 64 bits big endian after a marker byte
+    fn len(&mut self, len: usize) {
+        let len = len as u64;
+        if len < 1 << 6 {
+            self.u8(len as u8);
+        } else if len < 1 << 14 {
+            self.u8(0x40 | (len >> 8) as u8);
+            self.u8(len as u8);
+        } else if len <= u32::MAX as u64 {
+            self.u8(0x80);
+            self.raw(&(len as u32).to_be_bytes());
+        } else {
+            self.u8(0x81);
+            self.raw(&len.to_be_bytes());
+        }
+    }
+
+    // short integers are written as such, the way Redis saves them
+    fn string(&mut self, bytes: &[u8]) {
+        if bytes.len() <= 11 {
+            match canonical_int(bytes) {
+                Some(n) if i8::try_from(n).is_ok() => {
+                    self.u8(0xc0 | ENC_INT8 as u8);
+                    self.raw(&(n as i8).to_le_bytes());
+                    return;
+                }
+                Some(n) if i16::try_from(n).is_ok() => {
+                    self.u8(0xc0 | ENC_INT16 as u8);
+                    self.raw(&(n as i16).to_le_bytes());
+                    return;
+                }
+                Some(n) if i32::try_from(n).is_ok() => {
+                    self.u8(0xc0 | ENC_INT32 as u8);
+                    self.raw(&(n as i32).to_le_bytes());
+                    return;
+                }
+                _ => {}
+            }
+        }
+        self.len(bytes.len());
+        self.raw(bytes);
+    }
+
+    fn aux(&mut self, key: &str, value: &[u8]) {
+        self.u8(OPCODE_AUX);
+        self.string(key.as_bytes());
+        self.string(value);
+    }
+
+    fn id(&mut self, id: StreamId) {
+        self.len(id.ms as usize);
+        self.len(id.seq as usize);
+    }
+}
+
+#[derive(Default)]
+struct ListpackWriter {
+    buf: Vec<u8>,
+    count: usize,
+}
+
+impl ListpackWriter {
+    fn push(&mut self, item: &[u8]) {
+        if let Some(n) = canonical_int(item) {
+            return self.push_int(n);
+        }
+        let start = self.buf.len();
+        let len = item.len();
+        if len < 1 << 6 {
+            self.buf.push(0x80 | len as u8);
+        } else if len < 1 << 12 {
+            self.buf.push(0xe0 | (len >> 8) as u8);
+            self.buf.push(len as u8);
+        } else {
+            self.buf.push(0xf0);
+            self.buf.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        self.buf.extend_from_slice(item);
+        self.end_entry(start);
+    }
+
+    fn push_int(&mut self, n: i64) {
+        let start = self.buf.len();
+        if (0..=127).contains(&n) {
+            self.buf.push(n as u8);
+        } else if (-4096..=4095).contains(&n) {
+            let n = (n & 0x1fff) as u16;
+            self.buf.push(0xc0 | (n >> 8) as u8);
+            self.buf.push(n as u8);
+        } else if i16::try_from(n).is_ok() {
+            self.buf.push(0xf1);
+            self.buf.extend_from_slice(&(n as i16).to_le_bytes());
+        } else if (-(1 << 23)..1 << 23).contains(&n) {
+            self.buf.push(0xf2);
+            self.buf.extend_from_slice(&n.to_le_bytes()[..3]);
+        } else if i32::try_from(n).is_ok() {
+            self.buf.push(0xf3);
+            self.buf.extend_from_slice(&(n as i32).to_le_bytes());
+        } else {
+            self.buf.push(0xf4);
+            self.buf.extend_from_slice(&n.to_le_bytes());
+        }
+        self.end_entry(start);
+    }
+
+    // the length of the entry, most significant 7 bits first, all but the first flagged
+    fn end_entry(&mut self, start: usize) {
+        let len = self.buf.len() - start;
+        let size = backlen_size(len);
+        for i in (0..size).rev() {
+            let byte = (len >> (7 * i)) as u8 & 0x7f;
+            self.buf
+                .push(if i == size - 1 { byte } else { byte | 0x80 });
+        }
+        self.count += 1;
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut lp = Vec::with_capacity(self.buf.len() + 7);
+        lp.extend_from_slice(&(self.buf.len() as u32 + 7).to_le_bytes());
+        lp.extend_from_slice(&(self.count.min(u16::MAX as usize) as u16).to_le_bytes());
+        lp.extend_from_slice(&self.buf);
+        lp.push(0xff);
+        lp
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid("unexpected end of the RDB file"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    // a signed little endian integer of width bytes
+    fn int_le(&mut self, width: usize) -> io::Result<i64> {
+        let mut n = 0i64;
+        for (i, byte) in self.take(width)?.iter().enumerate() {
+            n |= (*byte as i64) << (8 * i);
+        }
+        let shift = 64 - 8 * width as u32;
+        Ok((n << shift) >> shift)
+    }
+
+    // a length, or the special encoding of a string if flagged
+    fn length(&mut self) -> io::Result<(u64, bool)> {
+        let b = self.u8()?;
+        match b >> 6 {
+            0 => Ok(((b & 0x3f) as u64, false)),
+            1 => Ok((((b & 0x3f) as u64) << 8 | self.u8()? as u64, false)),
+            3 => Ok(((b & 0x3f) as u64, true)),
+            _ => match b {
+                0x80 => Ok((
+                    u32::from_be_bytes(self.take(4)?.try_into().expect("4 bytes")) as u64,
+                    false,
+                )),
+                0x81 => Ok((
+                    u64::from_be_bytes(self.take(8)?.try_into().expect("8 bytes")),
+                    false,
+                )),
+                _ => Err(invalid("invalid length encoding")),
+            },
+        }
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        match self.length()? {
+            (len, false) => usize::try_from(len).map_err(|_| invalid("length out of range")),
+            (_, true) => Err(invalid("unexpected string encoding")),
+        }
+    }
+
+    fn string(&mut self) -> io::Result<Vec<u8>> {
+        let (len, encoded) = self.length()?;
+        if !encoded {
+            let len = usize::try_from(len).map_err(|_| invalid("length out of range"))?;
+            return Ok(self.take(len)?.to_vec());
+        }
+        match len {
+            ENC_INT8 => Ok(self.int_le(1)?.to_string().into_bytes()),
+            ENC_INT16 => Ok(self.int_le(2)?.to_string().into_bytes()),
+            ENC_INT32 => Ok(self.int_le(4)?.to_string().into_bytes()),
+            ENC_LZF => {
+                let compressed = self.len()?;
+                let len = self.len()?;
+                lzf_decompress(self.take(compressed)?, len)
+            }
+            _ => Err(invalid(format!("unknown string encoding {}", len))),
+        }
+    }
+
+    fn text(&mut self) -> io::Result<String> {
+        utf8(self.string()?)
+    }
+
+    // a score as text after its length, or 253, 254 and 255 for nan, inf and -inf
+    fn legacy_double(&mut self) -> io::Result<f64> {
+        match self.u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_score(self.take(len as usize)?),
+        }
+    }
+
+    fn id(&mut self) -> io::Result<StreamId> {
+        let (ms, _) = self.length()?;
+        let (seq, _) = self.length()?;
+        Ok(StreamId::new(ms, seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the dump of an empty Redis 7.2 server, as sent to a replica
+    const EMPTY_REDIS_RDB: &str = "524544495330303131fa0972656469732d76657205372e322e30fa0a72656469732d62697473c040fa056374696d65c26d08bc65fa08757365642d6d656dc2b0c41000fa08616f662d62617365c000fff06e3bfec0ff5aa2";
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decode(data: &[u8]) -> io::Result<Vec<SnapshotEntry>> {
+        decode_dump(
+            data,
+            || QuickList::new(-2),
+            || SetMembers::new(512),
+            HashTable::new,
+        )
+    }
+
+    #[test]
+    fn test_crc64() {
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+    }
+
+    #[test]
+    fn test_decode_redis_dump() {
+        let mut data = unhex(EMPTY_REDIS_RDB);
+        assert!(decode(&data).unwrap().is_empty());
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(decode(&data).is_err());
+        data[last - 7..].fill(0);
+        assert!(decode(&data).is_ok());
+    }
+
+    #[test]
+    fn test_value_dump() {
+        let decode = |data: &[u8]| {
+            decode_value_dump(
+                data,
+                || QuickList::new(-2),
+                || SetMembers::new(512),
+                HashTable::new,
+            )
+        };
+        // DUMP of the string 10 by Redis 7.0
+        let mut data = b"\x00\xc0\n\n\x00n\x9fWE\x0e\xaec\xbb".to_vec();
+        let value = decode(&data).unwrap();
+        assert!(matches!(&value, Value::String(v) if string_bytes(v) == b"10"));
+        data[2] = 11;
+        assert!(decode(&data).is_err());
+        assert!(decode(b"short").is_err());
+
+        let mut zset = SortedSet::default();
+        zset.insert("a".to_string(), 1.5);
+        let value = Value::ZSet(zset);
+        let decoded = decode(&encode_value_dump(&value)).unwrap();
+        assert!(matches!(decoded, Value::ZSet(zset) if zset.score("a") == Some(1.5)));
+    }
+
+    #[test]
+    fn test_hash_field_ttls() {
+        let mut hash = HashTable::new();
+        for field in ["a", "b", "c"] {
+            hash.insert(field.to_string(), BulkString::from("v").into());
+        }
+        let at = now_ms() + 100_000;
+        hash.set_expire("a", at);
+        hash.set_expire("c", at + 5);
+        let entry = SnapshotEntry {
+            key: "h".to_string(),
+            value: Arc::new(Value::Hash(hash)),
+            expire_at: None,
+        };
+
+        let hashes = [
+            decode(&encode_dump([&entry])).unwrap().remove(0).value,
+            Arc::new(
+                decode_value_dump(
+                    &encode_value_dump(&entry.value),
+                    || QuickList::new(-2),
+                    || SetMembers::new(512),
+                    HashTable::new,
+                )
+                .unwrap(),
+            ),
+        ];
+        for value in hashes {
+            let Value::Hash(hash) = &*value else {
+                panic!("not a hash");
+            };
+            assert_eq!(hash.len(), 3);
+            assert_eq!(hash.expire_time("a"), Some(at));
+            assert_eq!(hash.expire_time("b"), None);
+            assert_eq!(hash.expire_time("c"), Some(at + 5));
+        }
+
+        // a small hash of Redis 7.4, a listpack of field, value and absolute TTL triplets
+        let mut lp = ListpackWriter::default();
+        for (field, ttl) in [("f", at), ("g", 0)] {
+            lp.push(field.as_bytes());
+            lp.push(b"v");
+            lp.push_int(ttl);
+        }
+        let mut data = at.to_le_bytes().to_vec();
+        let mut w = Writer::default();
+        w.string(&lp.finish());
+        data.extend(w.buf);
+        let value = read_value(
+            &mut Reader::new(&data),
+            TYPE_HASH_LISTPACK_EX,
+            || QuickList::new(-2),
+            || SetMembers::new(512),
+            HashTable::new,
+        )
+        .unwrap();
+        assert!(matches!(value, Value::Hash(hash)
+            if hash.expire_time("f") == Some(at) && hash.expire_time("g").is_none()));
+    }
+
+    #[test]
+    fn test_check_dump() {
+        let backend = crate::Backend::new();
+        backend.set("s".to_string(), BulkString::from("v").into());
+        let items = vec![BulkString::from("a").into(), BulkString::from("b").into()];
+        backend.rpush("l", items).unwrap();
+        backend.sadd("set", vec!["m".into()]).unwrap();
+        backend.expire_at("s", now_ms() + 100_000);
+        let mut data = encode_dump(backend.snapshot().iter());
+
+        let check = check_dump(&data).unwrap();
+        assert_eq!(check.version, RDB_VERSION);
+        let keys = BTreeMap::from([("list", 1), ("set", 1), ("string", 1)]);
+        assert_eq!(check.keys, keys);
+        assert_eq!(check.expires, 1);
+        assert!(check.checksum);
+
+        // cut short, or with a flipped byte
+        assert!(check_dump(&data[..data.len() - 12]).is_err());
+        data[12] ^= 0xff;
+        assert!(check_dump(&data).is_err());
+        assert!(check_dump(&unhex(EMPTY_REDIS_RDB)).unwrap().keys.is_empty());
+    }
+
+    #[test]
+    fn test_lengths_and_strings() {
+        let mut w = Writer::default();
+        for len in [0, 63, 64, 16383, 16384, 1 << 32] {
+            w.len(len);
+        }
+        for s in ["-1", "300", "-70000", "12345678901", "012", "text"] {
+            w.string(s.as_bytes());
+        }
+        // LZF: "abc" as literals then 6 bytes copied from 3 bytes back
+        w.raw(&[0xc3, 6, 9, 2, b'a', b'b', b'c', 0x80, 2]);
+
+        assert_eq!(&w.buf[..4], &[0, 63, 0x40, 64]);
+        let mut r = Reader::new(&w.buf);
+        for len in [0, 63, 64, 16383, 16384, 1 << 32] {
+            assert_eq!(r.len().unwrap(), len);
+        }
+        for s in ["-1", "300", "-70000", "12345678901", "012", "text"] {
+            assert_eq!(r.string().unwrap(), s.as_bytes());
+        }
+        assert_eq!(r.string().unwrap(), b"abcabcabc");
+        assert_eq!(r.pos, w.buf.len());
+    }
+
+    #[test]
+    fn test_listpack_and_compact_encodings() {
+        let items = [
+            "a",
+            "0",
+            "127",
+            "-1",
+            "4095",
+            "-4096",
+            "30000",
+            "-8000000",
+            "100000000",
+        ];
+        let long = "x".repeat(5000);
+        let mut lp = ListpackWriter::default();
+        for item in items.iter().chain([&long.as_str()]) {
+            lp.push(item.as_bytes());
+        }
+        lp.push_int(i64::MIN);
+        let mut expected: Vec<Vec<u8>> = items.iter().map(|i| i.as_bytes().to_vec()).collect();
+        expected.push(long.into_bytes());
+        expected.push(i64::MIN.to_string().into_bytes());
+        assert_eq!(listpack_entries(&lp.finish()).unwrap(), expected);
+
+        // a ziplist of "ab", 5 and 1000, then an intset of 16 bit integers
+        let zl = [
+            0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 2, b'a', b'b', 4, 0xf6, 2, 0xc0, 0xe8, 0x03, 0xff,
+        ];
+        assert_eq!(
+            ziplist_entries(&zl).unwrap(),
+            vec![b"ab".to_vec(), b"5".to_vec(), b"1000".to_vec()]
+        );
+        let intset = [2, 0, 0, 0, 2, 0, 0, 0, 0xff, 0xff, 7, 0];
+        assert_eq!(
+            intset_entries(&intset).unwrap(),
+            vec![b"-1".to_vec(), b"7".to_vec()]
+        );
+    }
+}
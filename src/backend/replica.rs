@@ -0,0 +1,362 @@
+use crate::backend::{command, now_ms};
+use crate::cmd::{Command, ReplConfOption, Transaction};
+use crate::{Backend, RespArray, RespDecode, RespEncode, RespError, RespFrame};
+use bytes::{Buf, BytesMut};
+use parking_lot::Mutex;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// How often a replica acknowledges the offset it processed to its master.
+pub const REPL_ACK_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long a replica waits for a silent master before dropping the connection.
+pub const REPL_TIMEOUT: Duration = Duration::from_secs(60);
+
+// how long a replica waits before connecting again to its master
+const REPL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The replica side of replication: the master this server replicates, if it does, by a task
+/// loading its keyspace then applying its stream of writes.
+#[derive(Debug, Default)]
+pub struct Master {
+    // set while there's a master, the server is read only
+    replica: AtomicBool,
+    link: Mutex<Option<MasterLink>>,
+    // the port announced to the master
+    listening_port: AtomicU16,
+}
+
+#[derive(Debug)]
+struct MasterLink {
+    host: String,
+    port: u16,
+    state: Arc<LinkState>,
+    task: JoinHandle<()>,
+}
+
+// updated by the task of the link only, a new link has a new one
+#[derive(Debug, Default)]
+struct LinkState {
+    // once the keyspace of the master is loaded, until the connection is lost
+    up: AtomicBool,
+    syncing: AtomicBool,
+    // when the master last sent something, as a unix time in milliseconds
+    last_io: AtomicI64,
+}
+
+/// The master of a replica, as INFO reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MasterReport {
+    pub host: String,
+    pub port: u16,
+    pub link_up: bool,
+    pub sync_in_progress: bool,
+    // seconds since the master last sent something, -1 if it never did
+    pub last_io_seconds_ago: i64,
+}
+
+impl Backend {
+    /// Whether the server replicates a master, in which case clients can't write.
+    pub fn is_replica(&self) -> bool {
+        self.master.replica.load(Ordering::Acquire)
+    }
+
+    /// Set the port the server listens on, announced to the master it replicates.
+    pub fn set_listening_port(&self, port: u16) {
+        self.master.listening_port.store(port, Ordering::Relaxed);
+    }
+
+    // the port the server listens on, as announced
+    pub(crate) fn listening_port(&self) -> u16 {
+        self.master.listening_port.load(Ordering::Relaxed)
+    }
+
+    /// Replicate the master at host:port, from a task spawned on handle, or stop replicating
+    /// with None and start a stream of writes of its own. The replicas are disconnected on
+    /// a new master, to sync with its keyspace. Returns false if nothing changed.
+    pub fn replicaof(&self, master: Option<(String, u16)>, handle: &Handle) -> bool {
+        let mut link = self.master.link.lock();
+        match (&*link, &master) {
+            (Some(current), Some((host, port)))
+                if current.host == *host && current.port == *port =>
+            {
+                return false
+            }
+            (None, None) => return false,
+            _ => {}
+        }
+        if let Some(current) = link.take() {
+            current.task.abort();
+        }
+        match master {
+            Some((host, port)) => {
+                info!("replicating master {}:{}", host, port);
+                self.master.replica.store(true, Ordering::Release);
+                self.drop_replicas();
+                let state = Arc::new(LinkState::default());
+                let task = handle.spawn(replicate(self.clone(), host.clone(), port, state.clone()));
+                *link = Some(MasterLink {
+                    host,
+                    port,
+                    state,
+                    task,
+                });
+            }
+            None => {
+                info!("no longer a replica, now a master");
+                self.master.replica.store(false, Ordering::Release);
+                self.new_stream();
+            }
+        }
+        true
+    }
+
+    pub fn master_report(&self) -> Option<MasterReport> {
+        let link = self.master.link.lock();
+        let link = link.as_ref()?;
+        let last_io = link.state.last_io.load(Ordering::Relaxed);
+        Some(MasterReport {
+            host: link.host.clone(),
+            port: link.port,
+            link_up: link.state.up.load(Ordering::Relaxed),
+            sync_in_progress: link.state.syncing.load(Ordering::Relaxed),
+            last_io_seconds_ago: match last_io {
+                0 => -1,
+                at => (now_ms() - at).max(0) / 1000,
+            },
+        })
+    }
+}
+
+// sync with the master again whenever the connection is lost
+async fn replicate(backend: Backend, host: String, port: u16, state: Arc<LinkState>) {
+    loop {
+        match sync_with_master(&backend, &host, port, &state).await {
+            Ok(()) => info!("connection with master {}:{} lost", host, port),
+            Err(e) => warn!("replication of master {}:{} failed: {}", host, port, e),
+        }
+        state.up.store(false, Ordering::Relaxed);
+        state.syncing.store(false, Ordering::Relaxed);
+        tokio::time::sleep(REPL_RETRY_DELAY).await;
+    }
+}
+
+// handshake with the master, load its keyspace then apply its stream of writes, until the
+// connection is lost
+async fn sync_with_master(
+    backend: &Backend,
+    host: &str,
+    port: u16,
+    state: &LinkState,
+) -> io::Result<()> {
+    let mut link = Link {
+        stream: TcpStream::connect((host, port)).await?,
+        buf: BytesMut::new(),
+        state,
+    };
+    let listening_port = backend.listening_port();
+    link.request(["REPLCONF", "listening-port", &listening_port.to_string()])
+        .await?;
+    link.request(["REPLCONF", "capa", "psync2"]).await?;
+
+    // the stream the replica followed, or its own, continued if the master has it
+    let replid = backend.replid();
+    let next = (backend.master_repl_offset() + 1).to_string();
+    let reply = match link.request(["PSYNC", &replid, &next]).await? {
+        RespFrame::SimpleString(reply) => reply,
+        reply => return Err(invalid(format!("unexpected reply to PSYNC: {:?}", reply))),
+    };
+    let mut parts = reply.split_whitespace();
+    let (replid, offset) = match (parts.next(), parts.next(), parts.next().map(str::parse)) {
+        (Some("FULLRESYNC"), Some(replid), Some(Ok(offset))) => (replid.to_string(), offset),
+        (Some("CONTINUE"), replid, None) => {
+            // the master was promoted, the stream goes on under its new ID
+            if let Some(replid) = replid {
+                backend.rename_stream(replid.to_string());
+            }
+            state.up.store(true, Ordering::Relaxed);
+            info!(
+                "continuing the stream of master {}:{} at offset {}",
+                host,
+                port,
+                backend.master_repl_offset()
+            );
+            return link.apply_stream(backend).await;
+        }
+        _ => return Err(invalid(format!("unexpected reply to PSYNC: {}", &*reply))),
+    };
+    state.syncing.store(true, Ordering::Relaxed);
+    let rdb = link.read_rdb().await?;
+    let loaded = backend.replace_keyspace(&rdb)?;
+    // the AOF no longer rebuilds the keyspace
+    if backend.is_aof_enabled() {
+        let _ = backend.bgrewriteaof();
+    }
+    backend.follow_stream(replid, offset);
+    state.syncing.store(false, Ordering::Relaxed);
+    state.up.store(true, Ordering::Relaxed);
+    info!(
+        "synced with master {}:{}, {} keys loaded at offset {}",
+        host, port, loaded, offset
+    );
+    link.apply_stream(backend).await
+}
+
+// the connection to the master, and what was read from it but not processed yet
+struct Link<'a> {
+    stream: TcpStream,
+    buf: BytesMut,
+    state: &'a LinkState,
+}
+
+impl Link<'_> {
+    // send a command, returning the reply, an error one as an error
+    async fn request<const N: usize>(&mut self, args: [&str; N]) -> io::Result<RespFrame> {
+        self.stream.write_all(&command(args).encode()).await?;
+        loop {
+            // a master preparing the keyspace sends newlines to keep the connection alive
+            while self.buf.first() == Some(&b'\n') {
+                self.buf.advance(1);
+            }
+            if self.buf.is_empty() {
+                self.read().await?;
+                continue;
+            }
+            match RespFrame::decode(&mut self.buf) {
+                Ok(RespFrame::Error(e)) => {
+                    return Err(invalid(format!("{} replied {}", args[0], &*e)))
+                }
+                Ok(reply) => return Ok(reply),
+                Err(RespError::NotComplete) => self.read().await?,
+                Err(e) => return Err(invalid(e.to_string())),
+            }
+        }
+    }
+
+    // the keyspace, sent as a bulk string without the trailing CRLF
+    async fn read_rdb(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            while self.buf.first() == Some(&b'\n') {
+                self.buf.advance(1);
+            }
+            if let Some(end) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                let len = std::str::from_utf8(&self.buf[..end])
+                    .ok()
+                    .and_then(|line| line.strip_prefix('$'))
+                    .and_then(|len| len.parse::<usize>().ok())
+                    .ok_or_else(|| invalid("bad length of the keyspace".to_string()))?;
+                while self.buf.len() < end + 2 + len {
+                    self.read().await?;
+                }
+                self.buf.advance(end + 2);
+                return Ok(self.buf.split_to(len).to_vec());
+            }
+            self.read().await?;
+        }
+    }
+
+    // apply the writes as they come, acknowledging the offset processed every
+    // REPL_ACK_PERIOD and when asked to
+    async fn apply_stream(&mut self, backend: &Backend) -> io::Result<()> {
+        // the master's transactions are applied as on a connection, the replies dropped
+        let (mut subscriptions, _messages) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let mut ack = tokio::time::interval(REPL_ACK_PERIOD);
+        let result = async {
+            loop {
+                while !self.buf.is_empty() {
+                    let len = match RespArray::expect_length(&self.buf) {
+                        Ok(len) => len,
+                        Err(RespError::NotComplete) => break,
+                        Err(e) => return Err(invalid(e.to_string())),
+                    };
+                    let data = self.buf.split_to(len);
+                    let request =
+                        RespArray::decode(&mut data.clone()).map_err(|e| invalid(e.to_string()))?;
+                    // the master's keepalive, there's nothing to apply
+                    let result = match is_ping(&request) {
+                        true => Ok(None),
+                        false => backend.parse_command(request.clone()).map(Some),
+                    };
+                    match result {
+                        Ok(None) => {}
+                        // the offset acknowledged doesn't count the request
+                        Ok(Some(Command::ReplConf(replconf)))
+                            if replconf.options.contains(&ReplConfOption::GetAck) =>
+                        {
+                            self.ack(backend).await?;
+                        }
+                        Ok(Some(cmd)) => {
+                            cmd.execute_on_connection(
+                                backend,
+                                Some(request),
+                                &mut subscriptions,
+                                &mut transaction,
+                            )
+                            .await;
+                        }
+                        Err(e) => warn!("can't apply a write from the master: {}", e),
+                    }
+                    backend.processed_stream(&data);
+                }
+                let acking = tokio::select! {
+                    read = self.read() => {
+                        read?;
+                        false
+                    }
+                    _ = ack.tick() => true,
+                };
+                if acking {
+                    let last_io = self.state.last_io.load(Ordering::Relaxed);
+                    if now_ms() - last_io > REPL_TIMEOUT.as_millis() as i64 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "timeout, the master sent nothing",
+                        ));
+                    }
+                    self.ack(backend).await?;
+                }
+            }
+        }
+        .await;
+        transaction.reset(backend);
+        result
+    }
+
+    // with the AOF fsynced up to the offset, it's acknowledged for WAITAOF too
+    async fn ack(&mut self, backend: &Backend) -> io::Result<()> {
+        let offset = backend.master_repl_offset().to_string();
+        let ack = match backend.is_aof_fsynced() {
+            true => command(["REPLCONF", "ACK", &offset, "FACK", &offset]),
+            false => command(["REPLCONF", "ACK", &offset]),
+        }
+        .encode();
+        self.stream.write_all(&ack).await
+    }
+
+    async fn read(&mut self) -> io::Result<()> {
+        if self.stream.read_buf(&mut self.buf).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the master closed the connection",
+            ));
+        }
+        self.state.last_io.store(now_ms(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn is_ping(request: &RespArray) -> bool {
+    matches!(request.first(), Some(RespFrame::BulkString(name)) if name.eq_ignore_ascii_case(b"ping"))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,542 @@
+use crate::backend::{command, now_ms};
+use crate::{encode_dump, Backend, BackendError, LatencyEvent, MasterReport, RespEncode, Stat};
+use bytes::Bytes;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// How often the replicas are sent a PING, so they can tell the master is alive.
+pub const REPL_PING_REPLICA_PERIOD: Duration = Duration::from_secs(10);
+
+/// How many bytes of the stream of writes are kept for the replicas reconnecting.
+pub const DEFAULT_REPL_BACKLOG_SIZE: usize = 1024 * 1024;
+
+/// The master side of replication: the replicas, sent the stream of the propagated writes
+/// after a copy of the keyspace, and the offset of that stream.
+#[derive(Debug)]
+pub struct Replication {
+    // set once a replica shows up, the writes are propagated from then on
+    enabled: AtomicBool,
+    replid: RwLock<String>,
+    // the ID of the stream of the former master of a promoted replica, and its offset then;
+    // its replicas continue with this stream
+    former: RwLock<Option<(String, u64)>>,
+    // how many bytes were sent down the stream, or processed by a replica
+    offset: AtomicU64,
+    backlog: Mutex<Backlog>,
+    replicas: Mutex<Vec<Replica>>,
+    // the addresses the connections about to be replicas told with REPLCONF
+    announced: Mutex<HashMap<u64, Announced>>,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            replid: RwLock::new(new_replid()),
+            former: RwLock::new(None),
+            offset: AtomicU64::new(0),
+            backlog: Mutex::new(Backlog {
+                data: VecDeque::new(),
+                start: 0,
+                size: DEFAULT_REPL_BACKLOG_SIZE,
+            }),
+            replicas: Mutex::new(Vec::new()),
+            announced: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// the end of the stream of writes, for the replicas to pick up where they left it
+#[derive(Debug)]
+struct Backlog {
+    data: VecDeque<u8>,
+    // the offset of the first byte kept
+    start: u64,
+    size: usize,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Announced {
+    ip: Option<String>,
+    port: u16,
+}
+
+#[derive(Debug)]
+struct Replica {
+    id: u64,
+    tx: mpsc::UnboundedSender<Bytes>,
+    ip: String,
+    port: u16,
+    // the offset the replica last acknowledged, and when, as a unix time in milliseconds
+    ack_offset: u64,
+    ack_time: i64,
+    // the offset up to which the replica's AOF is fsynced, as it last acknowledged
+    aof_ack_offset: u64,
+}
+
+/// A replica starting a synchronization: for a full one, the keyspace as an RDB dump, taken at
+/// offset of the stream of writes, which follows. A partial one only gets the stream, from
+/// where the replica left it.
+#[derive(Debug)]
+pub struct ReplicaSync {
+    pub replid: String,
+    pub offset: u64,
+    pub rdb: Option<Vec<u8>>,
+    pub stream: mpsc::UnboundedReceiver<Bytes>,
+}
+
+/// The state of replication, as INFO reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationReport {
+    pub replid: String,
+    pub offset: u64,
+    // the former ID of the stream and its offset then, on a promoted replica
+    pub former: Option<(String, u64)>,
+    // the master replicated, on a replica
+    pub master: Option<MasterReport>,
+    pub replicas: Vec<ReplicaReport>,
+    pub backlog_size: usize,
+    pub backlog_first_byte_offset: u64,
+    pub backlog_histlen: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaReport {
+    pub ip: String,
+    pub port: u16,
+    pub offset: u64,
+    // seconds since the last acknowledgement
+    pub lag: i64,
+}
+
+impl Backend {
+    pub(crate) fn is_replication_enabled(&self) -> bool {
+        self.replication.enabled.load(Ordering::Acquire)
+    }
+
+    /// Propagate the writes from now on, for the replica about to sync.
+    pub(crate) fn enable_replication(&self) {
+        self.replication.enabled.store(true, Ordering::Release);
+    }
+
+    /// The ID of the stream of writes, the master's on a replica.
+    pub fn replid(&self) -> String {
+        self.replication.replid.read().clone()
+    }
+
+    /// How many bytes were sent down the stream of writes, or processed on a replica.
+    pub fn master_repl_offset(&self) -> u64 {
+        self.replication.offset.load(Ordering::Acquire)
+    }
+
+    /// Keep size bytes of the stream of writes for the replicas reconnecting.
+    pub fn set_repl_backlog_size(&self, size: usize) {
+        let mut backlog = self.replication.backlog.lock();
+        backlog.size = size.max(1);
+        backlog.trim();
+    }
+
+    pub fn repl_backlog_size(&self) -> usize {
+        self.replication.backlog.lock().size
+    }
+
+    // follow the stream of writes of a master, from offset, after loading its keyspace
+    pub(crate) fn follow_stream(&self, replid: String, offset: u64) {
+        *self.replication.replid.write() = replid;
+        let mut backlog = self.replication.backlog.lock();
+        backlog.data.clear();
+        backlog.start = offset;
+        self.replication.offset.store(offset, Ordering::Release);
+    }
+
+    // the master continues the stream under another ID, once promoted from replica
+    pub(crate) fn rename_stream(&self, replid: String) {
+        *self.replication.replid.write() = replid;
+    }
+
+    // count data of the stream of the master as processed, kept in the backlog for the
+    // replicas of the former master to continue with this one once promoted
+    pub(crate) fn processed_stream(&self, data: &[u8]) {
+        self.replication.backlog.lock().feed(data);
+        self.replication
+            .offset
+            .fetch_add(data.len() as u64, Ordering::AcqRel);
+    }
+
+    // start a stream of writes of its own, as a replica promoted to master, continuing the
+    // stream of the former master
+    pub(crate) fn new_stream(&self) {
+        let mut replid = self.replication.replid.write();
+        let former = std::mem::replace(&mut *replid, new_replid());
+        *self.replication.former.write() = Some((former, self.master_repl_offset()));
+    }
+
+    // disconnect the replicas, which sync again when they reconnect
+    pub(crate) fn drop_replicas(&self) {
+        self.replication.replicas.lock().clear();
+    }
+
+    /// Remember the port the connection of ID id listens on, and its IP if it's not the one it
+    /// connected from, for when it becomes a replica.
+    pub fn announce_replica(&self, id: u64, ip: Option<String>, port: Option<u16>) {
+        self.enable_replication();
+        let mut announced = self.replication.announced.lock();
+        let announced = announced.entry(id).or_default();
+        if let Some(ip) = ip {
+            announced.ip = Some(ip);
+        }
+        if let Some(port) = port {
+            announced.port = port;
+        }
+    }
+
+    /// Make the connection of ID id, from ip, a replica. With psync, the ID and offset of the
+    /// stream it asks with PSYNC, it continues from there if the backlog still has it.
+    /// Otherwise no write runs while the keyspace is copied, so the stream picks up right
+    /// where the copy ends. The dump has no functions, the libraries loaded before are not
+    /// replicated.
+    pub fn add_replica(&self, id: u64, ip: String, psync: Option<(&str, i64)>) -> ReplicaSync {
+        self.enable_replication();
+        let announced = self
+            .replication
+            .announced
+            .lock()
+            .remove(&id)
+            .unwrap_or_default();
+        let (tx, stream) = mpsc::unbounded_channel();
+        let (snapshot, offset) = self.run_unlogged(|| {
+            let missed = psync.and_then(|(replid, offset)| self.backlog_since(replid, offset));
+            let snapshot = match missed {
+                Some(missed) => {
+                    self.count(Stat::SyncPartialOk);
+                    let _ = tx.send(Bytes::from(missed));
+                    None
+                }
+                None => {
+                    // asked to continue a stream, not to start over with "?"
+                    if psync.is_some_and(|(replid, _)| replid != "?") {
+                        self.count(Stat::SyncPartialErr);
+                    }
+                    self.count(Stat::SyncFull);
+                    let started = Instant::now();
+                    let snapshot = self.snapshot();
+                    self.latency_sample(LatencyEvent::Fork, started.elapsed());
+                    Some(snapshot)
+                }
+            };
+            self.replication.replicas.lock().push(Replica {
+                id,
+                tx,
+                ip: announced.ip.unwrap_or(ip),
+                port: announced.port,
+                ack_offset: 0,
+                ack_time: now_ms(),
+                aof_ack_offset: 0,
+            });
+            (snapshot, self.master_repl_offset())
+        });
+        ReplicaSync {
+            replid: self.replid(),
+            offset,
+            rdb: snapshot.map(|snapshot| encode_dump(snapshot.iter())),
+            stream,
+        }
+    }
+
+    // the stream from offset, counting from 1, as a replica of the stream replid asks it, if
+    // it's still in the backlog
+    fn backlog_since(&self, replid: &str, offset: i64) -> Option<Vec<u8>> {
+        let replication = &self.replication;
+        let from = u64::try_from(offset - 1).ok()?;
+        let ours = *replication.replid.read() == replid;
+        // the former stream, up to where it ended
+        let former = replication
+            .former
+            .read()
+            .as_ref()
+            .is_some_and(|(former, end)| former == replid && from <= *end);
+        if !ours && !former {
+            return None;
+        }
+        replication.backlog.lock().since(from)
+    }
+
+    /// Forget the replica, or the addresses announced, of the connection of ID id.
+    pub fn remove_replica(&self, id: u64) {
+        self.replication.announced.lock().remove(&id);
+        self.replication
+            .replicas
+            .lock()
+            .retain(|replica| replica.id != id);
+    }
+
+    /// Record that the replica of the connection of ID id processed the stream up to offset,
+    /// and fsynced it to its AOF up to aof_offset, if told.
+    pub fn replica_ack(&self, id: u64, offset: u64, aof_offset: Option<u64>) {
+        let mut replicas = self.replication.replicas.lock();
+        if let Some(replica) = replicas.iter_mut().find(|replica| replica.id == id) {
+            replica.ack_offset = offset;
+            replica.ack_time = now_ms();
+            if let Some(aof_offset) = aof_offset {
+                replica.aof_ack_offset = aof_offset;
+            }
+        }
+        drop(replicas);
+        self.wake_acks();
+    }
+
+    /// How many replicas processed the stream up to offset.
+    pub fn replicas_acked(&self, offset: u64) -> usize {
+        self.replication
+            .replicas
+            .lock()
+            .iter()
+            .filter(|replica| replica.ack_offset >= offset)
+            .count()
+    }
+
+    /// What WAIT waits the replicas to acknowledge: the offset of the writes so far.
+    pub fn wait_offset(&self) -> Result<u64, BackendError> {
+        if self.is_replica() {
+            return Err(BackendError::WaitReplica);
+        }
+        Ok(self.master_repl_offset())
+    }
+
+    /// How many replicas fsynced the stream to their AOF up to offset.
+    pub fn replicas_aof_acked(&self, offset: u64) -> usize {
+        self.replication
+            .replicas
+            .lock()
+            .iter()
+            .filter(|replica| replica.aof_ack_offset >= offset)
+            .count()
+    }
+
+    /// Ask the replicas to acknowledge the offset they're at right away.
+    pub(crate) fn request_replica_acks(&self) {
+        if !self.replication.replicas.lock().is_empty() {
+            self.run_unlogged(|| {
+                self.feed_replicas(&command(["REPLCONF", "GETACK", "*"]).encode())
+            });
+        }
+    }
+
+    /// Send the replicas a PING, so they can tell the master is alive. The cron does every
+    /// REPL_PING_REPLICA_PERIOD.
+    pub fn ping_replicas(&self) {
+        if !self.replication.replicas.lock().is_empty() {
+            self.run_unlogged(|| self.feed_replicas(&command(["PING"]).encode()));
+        }
+    }
+
+    pub fn replication_report(&self) -> ReplicationReport {
+        let now = now_ms();
+        let backlog = self.replication.backlog.lock();
+        ReplicationReport {
+            replid: self.replid(),
+            offset: self.master_repl_offset(),
+            former: self.replication.former.read().clone(),
+            master: self.master_report(),
+            replicas: self
+                .replication
+                .replicas
+                .lock()
+                .iter()
+                .map(|replica| ReplicaReport {
+                    ip: replica.ip.clone(),
+                    port: replica.port,
+                    offset: replica.ack_offset,
+                    lag: (now - replica.ack_time).max(0) / 1000,
+                })
+                .collect(),
+            backlog_size: backlog.size,
+            backlog_first_byte_offset: backlog.start + 1,
+            backlog_histlen: backlog.data.len(),
+        }
+    }
+
+    // send data down the stream of writes, to every replica; called with the AOF held so
+    // the writes are sent in order. A replica has none, its offset is the master's.
+    pub(crate) fn feed_replicas(&self, data: &[u8]) {
+        if data.is_empty() || !self.is_replication_enabled() || self.is_replica() {
+            return;
+        }
+        self.replication.backlog.lock().feed(data);
+        self.replication
+            .offset
+            .fetch_add(data.len() as u64, Ordering::AcqRel);
+        let mut replicas = self.replication.replicas.lock();
+        if replicas.is_empty() {
+            return;
+        }
+        let data = Bytes::copy_from_slice(data);
+        // the replicas whose connection is gone are dropped
+        replicas.retain(|replica| replica.tx.send(data.clone()).is_ok());
+    }
+}
+
+impl Backlog {
+    fn feed(&mut self, data: &[u8]) {
+        self.data.extend(data);
+        self.trim();
+    }
+
+    // drop what doesn't fit, the oldest first
+    fn trim(&mut self) {
+        let excess = self.data.len().saturating_sub(self.size);
+        self.data.drain(..excess);
+        self.start += excess as u64;
+    }
+
+    // the stream from offset from, counting from 0, if it's kept
+    fn since(&self, from: u64) -> Option<Vec<u8>> {
+        let skip = usize::try_from(from.checked_sub(self.start)?).ok()?;
+        (skip <= self.data.len()).then(|| self.data.range(skip..).copied().collect())
+    }
+}
+
+// 40 random hex digits
+fn new_replid() -> String {
+    (0..20)
+        .map(|_| format!("{:02x}", rand::random::<u8>()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::{decode_dump, RespFrame};
+
+    fn execute(backend: &Backend, args: &[&str]) -> RespFrame {
+        let request = command(args.iter().copied());
+        let cmd = Command::try_from(request.clone()).unwrap();
+        backend.run_logged(|| cmd.execute_propagating(backend, Some(request)))
+    }
+
+    #[test]
+    fn test_full_sync_and_stream() {
+        let backend = Backend::new();
+        assert!(!backend.is_propagating());
+        execute(&backend, &["SET", "a", "1"]);
+        assert_eq!(backend.master_repl_offset(), 0);
+
+        backend.announce_replica(7, None, Some(6380));
+        assert!(backend.is_propagating());
+        let mut sync = backend.add_replica(7, "127.0.0.1".to_string(), None);
+        assert_eq!(sync.replid, backend.replid());
+        assert_eq!(sync.replid.len(), 40);
+        assert_eq!(sync.offset, 0);
+        let rdb = sync.rdb.unwrap();
+        let entries = decode_dump(
+            &rdb,
+            || backend.new_list(),
+            || backend.new_set(),
+            || backend.new_hash(),
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "a");
+
+        execute(&backend, &["SET", "b", "2"]);
+        execute(&backend, &["GET", "b"]);
+        execute(&backend, &["EXPIRE", "b", "100"]);
+        let set = command(["SET", "b", "2"]).encode();
+        assert_eq!(sync.stream.try_recv().unwrap(), set);
+        let expire = sync.stream.try_recv().unwrap();
+        assert!(String::from_utf8_lossy(&expire).contains("PEXPIREAT"));
+        assert!(sync.stream.try_recv().is_err());
+        assert_eq!(
+            backend.master_repl_offset(),
+            (set.len() + expire.len()) as u64
+        );
+
+        backend.replica_ack(7, 10, None);
+        let report = backend.replication_report();
+        assert_eq!(report.replicas.len(), 1);
+        assert_eq!(report.replicas[0].port, 6380);
+        assert_eq!(report.replicas[0].offset, 10);
+
+        backend.remove_replica(7);
+        assert!(backend.replication_report().replicas.is_empty());
+    }
+
+    #[test]
+    fn test_expired_keys_are_deleted() {
+        let backend = Backend::new();
+        backend.enable_replication();
+        let mut sync = backend.add_replica(1, "127.0.0.1".to_string(), None);
+        execute(&backend, &["PSETEX", "k", "1", "v"]);
+        sync.stream.try_recv().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        // expired by a read, the DEL goes ahead of the next write
+        assert_eq!(backend.get("k").unwrap(), None);
+        assert!(sync.stream.try_recv().is_err());
+        backend.flush_propagated();
+        assert_eq!(
+            sync.stream.try_recv().unwrap(),
+            command(["DEL", "k"]).encode()
+        );
+
+        // expired by a write, the DEL goes ahead of it
+        execute(&backend, &["PSETEX", "k", "1", "v"]);
+        sync.stream.try_recv().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        execute(&backend, &["APPEND", "k", "w"]);
+        let mut expected = command(["MULTI"]).encode();
+        expected.extend(command(["DEL", "k"]).encode());
+        expected.extend(command(["APPEND", "k", "w"]).encode());
+        expected.extend(command(["EXEC"]).encode());
+        assert_eq!(sync.stream.try_recv().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_partial_resync() {
+        let backend = Backend::new();
+        backend.enable_replication();
+        let sync = backend.add_replica(1, "127.0.0.1".to_string(), None);
+        execute(&backend, &["SET", "a", "1"]);
+        let set = command(["SET", "b", "2"]).encode();
+        execute(&backend, &["SET", "b", "2"]);
+        let processed = backend.master_repl_offset() - set.len() as u64;
+        backend.remove_replica(1);
+
+        // the replica picks up right after what it processed
+        let replid = sync.replid;
+        let from = processed as i64 + 1;
+        let mut sync = backend.add_replica(2, "127.0.0.1".to_string(), Some((&replid, from)));
+        assert!(sync.rdb.is_none());
+        assert_eq!(sync.stream.try_recv().unwrap(), set);
+        backend.remove_replica(2);
+        let sync = backend.add_replica(3, "127.0.0.1".to_string(), Some(("?", -1)));
+        assert!(sync.rdb.is_some());
+        let sync = backend.add_replica(4, "127.0.0.1".to_string(), Some((&replid, 1000)));
+        assert!(sync.rdb.is_some());
+
+        // what the backlog dropped can't be sent
+        backend.set_repl_backlog_size(set.len() - 1);
+        let sync = backend.add_replica(5, "127.0.0.1".to_string(), Some((&replid, from)));
+        assert!(sync.rdb.is_some());
+        let report = backend.replication_report();
+        assert_eq!(report.backlog_histlen, set.len() - 1);
+        assert_eq!(
+            report.backlog_first_byte_offset,
+            backend.master_repl_offset() - set.len() as u64 + 2
+        );
+
+        // promoted, a replica continues the stream of its former master for the others
+        let end = backend.master_repl_offset();
+        backend.new_stream();
+        assert_ne!(backend.replid(), replid);
+        let from = end as i64 + 1;
+        let sync = backend.add_replica(6, "127.0.0.1".to_string(), Some((&replid, from)));
+        assert!(sync.rdb.is_none());
+        let sync = backend.add_replica(7, "127.0.0.1".to_string(), Some((&replid, from + 1)));
+        assert!(sync.rdb.is_some());
+    }
+}
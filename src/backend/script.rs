@@ -0,0 +1,233 @@
+use crate::{Backend, BackendError, Functions};
+use mlua::{Lua, LuaOptions, StdLib};
+use parking_lot::{Mutex, RwLock};
+use sha1_smol::Sha1;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// The default busy-script-time-limit, in milliseconds.
+pub const DEFAULT_BUSY_SCRIPT_TIME_LIMIT: u64 = 5000;
+
+/// The scripting state of a backend: the Lua interpreter scripts run in, one at a time, and
+/// the scripts EVAL ran, by the SHA1 of their body, for EVALSHA.
+#[derive(Debug)]
+pub struct Scripting {
+    lua: Mutex<Lua>,
+    scripts: RwLock<HashMap<String, String>>,
+    pub(super) functions: Mutex<Functions>,
+    // the script running, if any, and whether SCRIPT KILL asked it to stop
+    running: Mutex<Option<RunningScript>>,
+    killed: AtomicBool,
+    // notified when the script running returns
+    done: Notify,
+    // how long a script runs before the other connections are replied BUSY, in milliseconds
+    time_limit: AtomicU64,
+}
+
+#[derive(Debug)]
+struct RunningScript {
+    started: Instant,
+    // once it called a write command, killing it would leave its writes half done
+    wrote: bool,
+}
+
+impl Default for Scripting {
+    fn default() -> Self {
+        Self {
+            lua: Mutex::new(new_lua()),
+            scripts: RwLock::default(),
+            functions: Mutex::default(),
+            running: Mutex::new(None),
+            killed: AtomicBool::new(false),
+            done: Notify::new(),
+            time_limit: AtomicU64::new(DEFAULT_BUSY_SCRIPT_TIME_LIMIT),
+        }
+    }
+}
+
+fn new_lua() -> Lua {
+    // the libraries redis gives scripts, no io, os or package
+    let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH;
+    Lua::new_with(libs, LuaOptions::default()).expect("Lua libraries load")
+}
+
+/// The SHA1 of a script body, in lowercase hex, as EVALSHA takes it.
+pub fn script_sha1(body: &str) -> String {
+    Sha1::from(body).digest().to_string()
+}
+
+/// The message of a Lua error: the cause of an error raised by a Rust callback, the first
+/// line of any other one, without the traceback.
+pub fn lua_error_message(e: &mlua::Error) -> String {
+    match e {
+        mlua::Error::CallbackError { cause, .. } => lua_error_message(cause),
+        e => e.to_string().lines().next().unwrap_or_default().to_string(),
+    }
+}
+
+impl Backend {
+    /// Cache a script for EVALSHA, returns its SHA1.
+    pub fn cache_script(&self, body: &str) -> String {
+        let sha1 = script_sha1(body);
+        self.scripting
+            .scripts
+            .write()
+            .entry(sha1.clone())
+            .or_insert_with(|| body.to_string());
+        sha1
+    }
+
+    /// The cached script of SHA1 sha1, in any case.
+    pub fn cached_script(&self, sha1: &str) -> Option<String> {
+        self.scripting
+            .scripts
+            .read()
+            .get(&sha1.to_ascii_lowercase())
+            .cloned()
+    }
+
+    /// Empty the script cache, and start over with a new interpreter, dropping whatever
+    /// globals scripts left behind.
+    pub fn flush_scripts(&self) {
+        self.scripting.scripts.write().clear();
+        *self.scripting.lua.lock() = new_lua();
+    }
+
+    /// Run f with the Lua interpreter, locked for the time of a script.
+    pub fn with_lua<T>(&self, f: impl FnOnce(&Lua) -> T) -> T {
+        f(&self.scripting.lua.lock())
+    }
+
+    /// Run f, a script, as the one running: past busy-script-time-limit the other
+    /// connections are replied BUSY, and SCRIPT KILL may ask it to stop until it returns.
+    pub fn run_busy_script<T>(&self, f: impl FnOnce() -> T) -> T {
+        let scripting = &self.scripting;
+        scripting.killed.store(false, Ordering::Release);
+        *scripting.running.lock() = Some(RunningScript {
+            started: Instant::now(),
+            wrote: false,
+        });
+        let result = f();
+        *scripting.running.lock() = None;
+        scripting.killed.store(false, Ordering::Release);
+        scripting.done.notify_waiters();
+        result
+    }
+
+    /// Record that the script running called a write command, so it can no longer be killed.
+    pub fn script_wrote(&self) {
+        if let Some(running) = &mut *self.scripting.running.lock() {
+            running.wrote = true;
+        }
+    }
+
+    /// Whether a script has been running for longer than busy-script-time-limit.
+    pub fn is_script_busy(&self) -> bool {
+        self.script_busy_in().is_some_and(|left| left.is_zero())
+    }
+
+    /// Wait until no script runs, or the one running is busy, rather than for the commands it
+    /// holds back. Returns whether it's busy.
+    pub async fn wait_script_busy(&self) -> bool {
+        loop {
+            let done = self.scripting.done.notified();
+            tokio::pin!(done);
+            done.as_mut().enable();
+            match self.script_busy_in() {
+                None => return false,
+                Some(left) if left.is_zero() => return true,
+                Some(left) => {
+                    let _ = tokio::time::timeout(left, done).await;
+                }
+            }
+        }
+    }
+
+    // how long until the script running is busy, None if none runs
+    fn script_busy_in(&self) -> Option<Duration> {
+        let time_limit = Duration::from_millis(self.busy_script_time_limit());
+        self.scripting
+            .running
+            .lock()
+            .as_ref()
+            .map(|running| time_limit.saturating_sub(running.started.elapsed()))
+    }
+
+    /// Whether SCRIPT KILL asked the script running to stop.
+    pub fn is_script_killed(&self) -> bool {
+        self.scripting.killed.load(Ordering::Acquire)
+    }
+
+    /// Ask the script running to stop, unless it called a write command already.
+    pub fn script_kill(&self) -> Result<(), BackendError> {
+        match &*self.scripting.running.lock() {
+            None => Err(BackendError::NotBusy),
+            Some(running) if running.wrote => Err(BackendError::Unkillable),
+            Some(_) => {
+                self.scripting.killed.store(true, Ordering::Release);
+                Ok(())
+            }
+        }
+    }
+
+    /// Ask the script running to stop, whatever it wrote, as SHUTDOWN NOSAVE does.
+    pub(crate) fn script_abort(&self) {
+        if self.scripting.running.lock().is_some() {
+            self.scripting.killed.store(true, Ordering::Release);
+        }
+    }
+
+    /// How long a script runs before the other connections are replied BUSY, in milliseconds.
+    pub fn busy_script_time_limit(&self) -> u64 {
+        self.scripting.time_limit.load(Ordering::Relaxed)
+    }
+
+    pub fn set_busy_script_time_limit(&self, ms: u64) {
+        self.scripting.time_limit.store(ms, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Backend, BackendError};
+
+    #[test]
+    fn test_script_cache() {
+        let backend = Backend::new();
+        let sha1 = backend.cache_script("return 1");
+        assert_eq!(sha1, "e0e1f9fabfc9d4800c877a703b823ac0578ff8db");
+        assert_eq!(
+            backend.cached_script(&sha1.to_uppercase()).as_deref(),
+            Some("return 1")
+        );
+        assert_eq!(backend.cached_script("ffff"), None);
+
+        backend.flush_scripts();
+        assert_eq!(backend.cached_script(&sha1), None);
+    }
+
+    #[test]
+    fn test_script_kill() {
+        let backend = Backend::new();
+        assert_eq!(backend.script_kill(), Err(BackendError::NotBusy));
+        backend.set_busy_script_time_limit(0);
+
+        backend.run_busy_script(|| {
+            assert!(backend.is_script_busy());
+            assert_eq!(backend.script_kill(), Ok(()));
+            assert!(backend.is_script_killed());
+        });
+        assert!(!backend.is_script_busy());
+        assert!(!backend.is_script_killed());
+
+        backend.run_busy_script(|| {
+            backend.script_wrote();
+            assert_eq!(backend.script_kill(), Err(BackendError::Unkillable));
+            assert!(!backend.is_script_killed());
+            backend.script_abort();
+            assert!(backend.is_script_killed());
+        });
+    }
+}
@@ -0,0 +1,1343 @@
+use crate::backend::now_ms;
+use crate::client::{Client, Pipeline};
+use crate::{Backend, BackendError, RespFrame};
+use anyhow::{anyhow, Result};
+use futures::{future, StreamExt};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+/// The port a sentinel listens on by default.
+pub const SENTINEL_DEFAULT_PORT: u16 = 26379;
+
+/// How often a sentinel probes the instances of a master with PING and INFO, announcing
+/// itself on them too, unless down-after-milliseconds is shorter.
+pub const SENTINEL_PROBE_PERIOD: Duration = Duration::from_secs(1);
+
+// how often the sentinel looks for the masters due a probe
+const SENTINEL_TICK: Duration = Duration::from_millis(100);
+// how long a sentinel waits for an instance or another sentinel, to connect or to reply
+const SENTINEL_IO_TIMEOUT: Duration = Duration::from_secs(1);
+// how long a sentinel waits before subscribing again to the hellos of an instance
+const HELLO_RETRY_DELAY: Duration = Duration::from_secs(1);
+// the channel the sentinels of an instance announce themselves and their config on
+const HELLO_CHANNEL: &str = "__sentinel__:hello";
+// how long an election may take, failover-timeout if it's shorter
+const ELECTION_TIMEOUT: u64 = 10_000;
+// how much later than twice failover-timeout a sentinel tries a failover again, at random, so
+// the sentinels don't split their votes again
+const FAILOVER_MAX_DESYNC: u64 = 1000;
+const DEFAULT_DOWN_AFTER: u64 = 30_000;
+const DEFAULT_FAILOVER_TIMEOUT: u64 = 180_000;
+
+/// The sentinel mode of the server: the masters it monitors, with their replicas and the
+/// other sentinels monitoring them, and its votes in the elections of failover leaders.
+#[derive(Debug)]
+pub struct Sentinel {
+    enabled: AtomicBool,
+    myid: String,
+    // the highest epoch of an election seen, the next one this sentinel starts is after it
+    current_epoch: AtomicU64,
+    masters: Mutex<BTreeMap<String, MonitoredMaster>>,
+}
+
+/// The address of an instance a sentinel monitors, or of another sentinel.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InstanceAddr {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug)]
+struct MonitoredMaster {
+    addr: InstanceAddr,
+    quorum: usize,
+    down_after: u64,
+    failover_timeout: u64,
+    // the epoch of the failover that made it the master, 0 for the one monitored
+    config_epoch: u64,
+    // unix times in milliseconds it last replied to a probe, or since it's monitored, and
+    // it was last probed
+    last_ok: i64,
+    last_probe: i64,
+    replicas: BTreeMap<InstanceAddr, MonitoredReplica>,
+    // by run ID
+    sentinels: BTreeMap<String, SentinelPeer>,
+    // the sentinel this one voted for to lead the failover of an epoch
+    vote: Option<(String, u64)>,
+    failover: Option<Failover>,
+    // when a failover was last attempted, none again for twice failover-timeout
+    last_failover: i64,
+    // asked with SENTINEL FAILOVER, without the agreement of the others
+    forced: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MonitoredReplica {
+    last_ok: i64,
+    // what its INFO reports, the master it replicates none once promoted
+    offset: i64,
+    master: Option<InstanceAddr>,
+}
+
+#[derive(Debug, Clone)]
+struct SentinelPeer {
+    addr: InstanceAddr,
+    last_hello: i64,
+    // its reply to the last SENTINEL IS-MASTER-DOWN-BY-ADDR, and when
+    master_down: bool,
+    vote: Option<(String, u64)>,
+    last_reply: i64,
+}
+
+// an election this sentinel started, then the failover it leads once elected
+#[derive(Debug, Clone, Copy)]
+struct Failover {
+    epoch: u64,
+    started: i64,
+}
+
+/// A master a sentinel monitors, as SENTINEL MASTERS reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentinelMasterReport {
+    pub name: String,
+    pub addr: InstanceAddr,
+    /// `master`, with `s_down` while it's down to this sentinel, `o_down` while the quorum
+    /// agrees, and `failover_in_progress`.
+    pub flags: String,
+    /// Milliseconds since it last replied to a probe.
+    pub last_ok_ping_reply: i64,
+    pub quorum: usize,
+    pub down_after: u64,
+    pub failover_timeout: u64,
+    pub config_epoch: u64,
+    pub num_replicas: usize,
+    pub num_other_sentinels: usize,
+}
+
+/// A replica of a master, or another sentinel monitoring it, as SENTINEL REPLICAS and
+/// SENTINEL SENTINELS report them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentinelInstanceReport {
+    pub addr: InstanceAddr,
+    /// The run ID of a sentinel, empty for a replica.
+    pub runid: String,
+    /// `slave`, with `s_down` while it doesn't reply, or `sentinel`.
+    pub flags: String,
+    /// Milliseconds since a replica last replied to a probe, or a sentinel sent a hello.
+    pub last_seen: i64,
+    /// The offset of a replica, 0 for a sentinel.
+    pub offset: i64,
+}
+
+// what INFO replication reports of an instance
+#[derive(Debug, Default, PartialEq, Eq)]
+struct InstanceInfo {
+    master: Option<InstanceAddr>,
+    offset: i64,
+    replicas: Vec<InstanceAddr>,
+}
+
+// the announcement of a sentinel on the hello channel of the instances of a master, as
+// `ip,port,runid,current_epoch,name,master_ip,master_port,config_epoch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hello {
+    // the IP of the connection to the instance, if none is bound
+    addr: InstanceAddr,
+    runid: String,
+    current_epoch: u64,
+    name: String,
+    master: InstanceAddr,
+    config_epoch: u64,
+}
+
+// what the monitor does after a probe
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Nothing,
+    // ask the other sentinels whether the master is down, for their vote in an election of
+    // this one too
+    Ask {
+        epoch: u64,
+        candidate: bool,
+    },
+    // promote a replica, elected to, then have the others replicate it
+    Failover {
+        epoch: u64,
+        promote: InstanceAddr,
+        others: Vec<InstanceAddr>,
+    },
+    // have instances that don't replicate the master replicate it
+    Reconfigure(Vec<InstanceAddr>),
+}
+
+impl Default for Sentinel {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            myid: (0..20)
+                .map(|_| format!("{:02x}", rand::random::<u8>()))
+                .collect(),
+            current_epoch: AtomicU64::new(0),
+            masters: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl fmt::Display for InstanceAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+impl InstanceAddr {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+        }
+    }
+}
+
+impl MonitoredMaster {
+    fn new(addr: InstanceAddr, quorum: usize) -> Self {
+        Self {
+            addr,
+            quorum,
+            down_after: DEFAULT_DOWN_AFTER,
+            failover_timeout: DEFAULT_FAILOVER_TIMEOUT,
+            config_epoch: 0,
+            last_ok: now_ms(),
+            last_probe: 0,
+            replicas: BTreeMap::new(),
+            sentinels: BTreeMap::new(),
+            vote: None,
+            failover: None,
+            last_failover: 0,
+            forced: false,
+        }
+    }
+
+    fn is_sdown(&self, now: i64) -> bool {
+        now - self.last_ok > self.down_after as i64
+    }
+
+    // down to the quorum, this sentinel included, as the others last replied
+    fn is_odown(&self, now: i64) -> bool {
+        let fresh = (self.down_after as i64).max(SENTINEL_PROBE_PERIOD.as_millis() as i64) * 2;
+        let agreeing = self
+            .sentinels
+            .values()
+            .filter(|peer| peer.master_down && now - peer.last_reply <= fresh)
+            .count();
+        self.is_sdown(now) && 1 + agreeing >= self.quorum
+    }
+
+    // the reachable replica with the highest offset, then the lowest address
+    fn best_replica(&self, now: i64) -> Option<InstanceAddr> {
+        self.replicas
+            .iter()
+            .filter(|(_, replica)| now - replica.last_ok <= self.down_after as i64)
+            .max_by(|(a, ra), (b, rb)| ra.offset.cmp(&rb.offset).then(b.cmp(a)))
+            .map(|(addr, _)| addr.clone())
+    }
+
+    // the instance now replicated, the former master among the replicas, to replicate it
+    // once it's back
+    fn switch_to(&mut self, addr: InstanceAddr, config_epoch: u64) {
+        let former = std::mem::replace(&mut self.addr, addr);
+        self.replicas.remove(&self.addr);
+        self.replicas.insert(
+            former,
+            MonitoredReplica {
+                last_ok: 0,
+                offset: 0,
+                master: None,
+            },
+        );
+        self.config_epoch = config_epoch;
+        self.last_ok = now_ms();
+        self.failover = None;
+        self.forced = false;
+        for peer in self.sentinels.values_mut() {
+            peer.master_down = false;
+        }
+    }
+}
+
+impl Backend {
+    /// Run as a sentinel, monitoring masters rather than serving a keyspace.
+    pub fn enable_sentinel(&self) {
+        self.sentinel.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_sentinel(&self) -> bool {
+        self.sentinel.enabled.load(Ordering::Relaxed)
+    }
+
+    /// The run ID of the sentinel, 40 hex characters, new for each run.
+    pub fn sentinel_myid(&self) -> String {
+        self.sentinel.myid.clone()
+    }
+
+    /// Monitor the master at host:port as name, failed over once quorum sentinels agree it's
+    /// down.
+    pub fn sentinel_monitor(
+        &self,
+        name: &str,
+        host: &str,
+        port: u16,
+        quorum: usize,
+    ) -> Result<(), BackendError> {
+        if quorum == 0 {
+            return Err(BackendError::SentinelBadArgument(
+                "Quorum must be 1 or greater.".to_string(),
+            ));
+        }
+        let mut masters = self.sentinel.masters.lock();
+        if masters.contains_key(name) {
+            return Err(BackendError::DuplicatedMaster);
+        }
+        info!(
+            "+monitor master {} {}:{} quorum {}",
+            name, host, port, quorum
+        );
+        let master = MonitoredMaster::new(InstanceAddr::new(host, port), quorum);
+        masters.insert(name.to_string(), master);
+        Ok(())
+    }
+
+    /// Stop monitoring the master of name.
+    pub fn sentinel_remove(&self, name: &str) -> Result<(), BackendError> {
+        match self.sentinel.masters.lock().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(BackendError::NoSuchMaster),
+        }
+    }
+
+    /// Set an option of the master of name: down-after-milliseconds, failover-timeout or
+    /// quorum.
+    pub fn sentinel_set(&self, name: &str, option: &str, value: &str) -> Result<(), BackendError> {
+        let mut masters = self.sentinel.masters.lock();
+        let master = masters.get_mut(name).ok_or(BackendError::NoSuchMaster)?;
+        let invalid = || {
+            BackendError::SentinelBadArgument(format!(
+                "Invalid argument '{}' for SENTINEL SET '{}'",
+                value, option
+            ))
+        };
+        let positive = || {
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(invalid)
+        };
+        match option.to_ascii_lowercase().as_str() {
+            "down-after-milliseconds" => master.down_after = positive()?,
+            "failover-timeout" => master.failover_timeout = positive()?,
+            "quorum" => master.quorum = positive()? as usize,
+            _ => {
+                return Err(BackendError::SentinelBadArgument(format!(
+                    "Invalid argument '{}' to SENTINEL SET",
+                    option
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a `sentinel` directive of the config: `monitor name host port quorum`, or an
+    /// option of SENTINEL SET as `down-after-milliseconds name 5000`.
+    pub(crate) fn sentinel_directive(&self, value: &str) -> Result<(), BackendError> {
+        let invalid =
+            |reason: &str| BackendError::InvalidConfig("sentinel".to_string(), reason.to_string());
+        if !self.is_sentinel() {
+            return Err(invalid("sentinel directive while not in sentinel mode"));
+        }
+        let args: Vec<&str> = value.split_whitespace().collect();
+        match args[..] {
+            [option, name, host, port, quorum] if option.eq_ignore_ascii_case("monitor") => {
+                let port = port.parse().map_err(|_| invalid("invalid port"))?;
+                let quorum = quorum.parse().map_err(|_| invalid("invalid quorum"))?;
+                self.sentinel_monitor(name, host, port, quorum)
+            }
+            [option, name, value] => self.sentinel_set(name, option, value),
+            _ => Err(invalid("wrong number of arguments")),
+        }
+        .map_err(|e| match e {
+            BackendError::InvalidConfig(..) => e,
+            e => invalid(&e.to_string()),
+        })
+    }
+
+    /// The address of the master of name, the replica promoted in its place once failed
+    /// over.
+    pub fn sentinel_master_addr(&self, name: &str) -> Option<InstanceAddr> {
+        let masters = self.sentinel.masters.lock();
+        masters.get(name).map(|master| master.addr.clone())
+    }
+
+    /// The masters monitored, by name.
+    pub fn sentinel_masters(&self) -> Vec<SentinelMasterReport> {
+        let now = now_ms();
+        let masters = self.sentinel.masters.lock();
+        masters
+            .iter()
+            .map(|(name, master)| {
+                let mut flags = vec!["master"];
+                if master.is_sdown(now) {
+                    flags.push("s_down");
+                }
+                if master.is_odown(now) {
+                    flags.push("o_down");
+                }
+                if master.failover.is_some() {
+                    flags.push("failover_in_progress");
+                }
+                SentinelMasterReport {
+                    name: name.clone(),
+                    addr: master.addr.clone(),
+                    flags: flags.join(","),
+                    last_ok_ping_reply: now - master.last_ok,
+                    quorum: master.quorum,
+                    down_after: master.down_after,
+                    failover_timeout: master.failover_timeout,
+                    config_epoch: master.config_epoch,
+                    num_replicas: master.replicas.len(),
+                    num_other_sentinels: master.sentinels.len(),
+                }
+            })
+            .collect()
+    }
+
+    /// The replicas of the master of name, none if it isn't monitored.
+    pub fn sentinel_replicas(&self, name: &str) -> Option<Vec<SentinelInstanceReport>> {
+        let now = now_ms();
+        let masters = self.sentinel.masters.lock();
+        let master = masters.get(name)?;
+        let replicas = master
+            .replicas
+            .iter()
+            .map(|(addr, replica)| SentinelInstanceReport {
+                addr: addr.clone(),
+                runid: String::new(),
+                flags: match now - replica.last_ok > master.down_after as i64 {
+                    true => "slave,s_down".to_string(),
+                    false => "slave".to_string(),
+                },
+                last_seen: now - replica.last_ok,
+                offset: replica.offset,
+            })
+            .collect();
+        Some(replicas)
+    }
+
+    /// The other sentinels monitoring the master of name, none if it isn't monitored.
+    pub fn sentinel_sentinels(&self, name: &str) -> Option<Vec<SentinelInstanceReport>> {
+        let now = now_ms();
+        let masters = self.sentinel.masters.lock();
+        let master = masters.get(name)?;
+        let sentinels = master
+            .sentinels
+            .iter()
+            .map(|(runid, peer)| SentinelInstanceReport {
+                addr: peer.addr.clone(),
+                runid: runid.clone(),
+                flags: "sentinel".to_string(),
+                last_seen: now - peer.last_hello,
+                offset: 0,
+            })
+            .collect();
+        Some(sentinels)
+    }
+
+    /// Whether the master at host:port is down to this sentinel, as another asks, with the
+    /// leader this sentinel voted for in epoch and that epoch. The vote goes to runid, unless
+    /// it's `*` or this sentinel already voted in epoch.
+    pub fn sentinel_is_master_down_by_addr(
+        &self,
+        host: &str,
+        port: u16,
+        epoch: u64,
+        runid: &str,
+    ) -> (bool, Option<String>, u64) {
+        let now = now_ms();
+        let mut masters = self.sentinel.masters.lock();
+        let Some(master) = masters
+            .values_mut()
+            .find(|master| master.addr.host == host && master.addr.port == port)
+        else {
+            return (false, None, 0);
+        };
+        let down = master.is_sdown(now);
+        if runid == "*" {
+            return (down, None, 0);
+        }
+        self.sentinel
+            .current_epoch
+            .fetch_max(epoch, Ordering::Relaxed);
+        if master.vote.as_ref().is_none_or(|(_, voted)| *voted < epoch) {
+            info!("+vote-for-leader {} {}", runid, epoch);
+            master.vote = Some((runid.to_string(), epoch));
+        }
+        match &master.vote {
+            Some((leader, voted)) => (down, Some(leader.clone()), *voted),
+            None => (down, None, 0),
+        }
+    }
+
+    /// Fail the master of name over now, as if the others agreed it's down and elected this
+    /// sentinel to.
+    pub fn sentinel_failover(&self, name: &str) -> Result<(), BackendError> {
+        let now = now_ms();
+        let mut masters = self.sentinel.masters.lock();
+        let master = masters.get_mut(name).ok_or(BackendError::NoSuchMaster)?;
+        if master.failover.is_some() || master.forced {
+            return Err(BackendError::FailoverInProgress);
+        }
+        if master.best_replica(now).is_none() {
+            return Err(BackendError::NoGoodReplica);
+        }
+        master.forced = true;
+        Ok(())
+    }
+
+    /// Monitor the masters, from a task spawned on handle: probe their instances, announce
+    /// this sentinel to the others on them and learn of the others there, and fail a master
+    /// over once the quorum agrees it's down and this sentinel is elected to.
+    pub fn start_sentinel(&self, handle: &Handle) {
+        let backend = self.clone();
+        handle.spawn(async move {
+            let mut links = HashMap::new();
+            let mut hellos = HashMap::new();
+            let mut interval = tokio::time::interval(SENTINEL_TICK);
+            loop {
+                interval.tick().await;
+                for name in backend.masters_due() {
+                    monitor(&backend, &name, &mut links).await;
+                }
+                backend.receive_hellos(&mut hellos);
+            }
+        });
+    }
+
+    // the masters whose probe is due, about to be probed
+    fn masters_due(&self) -> Vec<String> {
+        let now = now_ms();
+        let mut masters = self.sentinel.masters.lock();
+        masters
+            .iter_mut()
+            .filter(|(_, master)| {
+                let period = master
+                    .down_after
+                    .min(SENTINEL_PROBE_PERIOD.as_millis() as u64);
+                now - master.last_probe >= period as i64
+            })
+            .map(|(name, master)| {
+                master.last_probe = now;
+                name.clone()
+            })
+            .collect()
+    }
+
+    // the instances of the master of name, itself first, and what to announce on them
+    fn instances(&self, name: &str) -> Option<(Vec<InstanceAddr>, Hello)> {
+        let masters = self.sentinel.masters.lock();
+        let master = masters.get(name)?;
+        let instances = std::iter::once(master.addr.clone())
+            .chain(master.replicas.keys().cloned())
+            .collect();
+        let ip = match self.bind().0.first() {
+            Some(ip) if !ip.is_unspecified() => ip.to_string(),
+            _ => String::new(),
+        };
+        let hello = Hello {
+            addr: InstanceAddr::new(ip, self.listening_port()),
+            runid: self.sentinel_myid(),
+            current_epoch: self.sentinel.current_epoch.load(Ordering::Relaxed),
+            name: name.to_string(),
+            master: master.addr.clone(),
+            config_epoch: master.config_epoch,
+        };
+        Some((instances, hello))
+    }
+
+    // what a probe of addr, an instance of the master of name, found, none if it failed
+    fn probed(&self, name: &str, addr: &InstanceAddr, info: Option<InstanceInfo>) {
+        let now = now_ms();
+        let mut masters = self.sentinel.masters.lock();
+        let Some(master) = masters.get_mut(name) else {
+            return;
+        };
+        let Some(info) = info else {
+            return;
+        };
+        if *addr == master.addr {
+            master.last_ok = now;
+            for replica in info.replicas {
+                if replica != master.addr && !master.replicas.contains_key(&replica) {
+                    info!("+slave {} of {}", replica, name);
+                    let found = MonitoredReplica {
+                        last_ok: now,
+                        offset: 0,
+                        master: Some(master.addr.clone()),
+                    };
+                    master.replicas.insert(replica, found);
+                }
+            }
+        } else if let Some(replica) = master.replicas.get_mut(addr) {
+            replica.last_ok = now;
+            replica.offset = info.offset;
+            replica.master = info.master;
+        }
+    }
+
+    // the reply of the sentinel of runid to SENTINEL IS-MASTER-DOWN-BY-ADDR about the master
+    // of name
+    fn peer_replied(&self, name: &str, runid: &str, reply: (bool, Option<String>, u64)) {
+        let (down, leader, epoch) = reply;
+        let mut masters = self.sentinel.masters.lock();
+        let Some(peer) = masters
+            .get_mut(name)
+            .and_then(|master| master.sentinels.get_mut(runid))
+        else {
+            return;
+        };
+        peer.master_down = down;
+        peer.vote = leader.map(|leader| (leader, epoch));
+        peer.last_reply = now_ms();
+        self.sentinel
+            .current_epoch
+            .fetch_max(epoch, Ordering::Relaxed);
+    }
+
+    // what to do about the master of name, after a probe
+    fn next_step(&self, name: &str) -> Step {
+        let now = now_ms();
+        let myid = &self.sentinel.myid;
+        let mut masters = self.sentinel.masters.lock();
+        let Some(master) = masters.get_mut(name) else {
+            return Step::Nothing;
+        };
+
+        if master.forced {
+            master.forced = false;
+            let epoch = self.sentinel.current_epoch.fetch_add(1, Ordering::Relaxed) + 1;
+            info!("+new-epoch {}, forced failover of {}", epoch, name);
+            master.failover = Some(Failover {
+                epoch,
+                started: now,
+            });
+            master.last_failover = now;
+            return self.failover_step(name, master, epoch, now);
+        }
+        if !master.is_sdown(now) {
+            if master.failover.take().is_some() {
+                info!("-failover-abort-master-back {}", name);
+            }
+            // those replicating another master, or none, like a former master back
+            let strays: Vec<InstanceAddr> = master
+                .replicas
+                .iter()
+                .filter(|(_, replica)| now - replica.last_ok <= master.down_after as i64)
+                .filter(|(_, replica)| replica.master.as_ref() != Some(&master.addr))
+                .map(|(addr, _)| addr.clone())
+                .collect();
+            return match strays.is_empty() {
+                true => Step::Nothing,
+                false => Step::Reconfigure(strays),
+            };
+        }
+        if !master.is_odown(now) {
+            return Step::Ask {
+                epoch: 0,
+                candidate: false,
+            };
+        }
+
+        let Some(failover) = master.failover else {
+            if now - master.last_failover < 2 * master.failover_timeout as i64 {
+                return Step::Ask {
+                    epoch: 0,
+                    candidate: false,
+                };
+            }
+            let epoch = self.sentinel.current_epoch.fetch_add(1, Ordering::Relaxed) + 1;
+            info!("+odown {}, +try-failover in epoch {}", name, epoch);
+            master.failover = Some(Failover {
+                epoch,
+                started: now,
+            });
+            master.last_failover = now + (rand::random::<u64>() % FAILOVER_MAX_DESYNC) as i64;
+            if master.vote.as_ref().is_none_or(|(_, voted)| *voted < epoch) {
+                master.vote = Some((myid.clone(), epoch));
+            }
+            return Step::Ask {
+                epoch,
+                candidate: true,
+            };
+        };
+        let epoch = failover.epoch;
+        let voted_for_me = |vote: &Option<(String, u64)>| {
+            vote.as_ref()
+                .is_some_and(|(leader, voted)| leader == myid && *voted == epoch)
+        };
+        let votes = voted_for_me(&master.vote) as usize
+            + master
+                .sentinels
+                .values()
+                .filter(|peer| voted_for_me(&peer.vote))
+                .count();
+        // of the sentinels known, this one included
+        let voters = master.sentinels.len() + 1;
+        let majority = voters / 2 + 1;
+        if votes >= master.quorum.max(majority) {
+            info!("+elected-leader {} in epoch {}", name, epoch);
+            return self.failover_step(name, master, epoch, now);
+        }
+        if now - failover.started > ELECTION_TIMEOUT.min(master.failover_timeout) as i64 {
+            info!("-failover-abort-not-elected {} in epoch {}", name, epoch);
+            master.failover = None;
+            return Step::Nothing;
+        }
+        Step::Ask {
+            epoch,
+            candidate: true,
+        }
+    }
+
+    fn failover_step(
+        &self,
+        name: &str,
+        master: &mut MonitoredMaster,
+        epoch: u64,
+        now: i64,
+    ) -> Step {
+        let Some(promote) = master.best_replica(now) else {
+            warn!("-failover-abort-no-good-slave {}", name);
+            master.failover = None;
+            return Step::Nothing;
+        };
+        let others = master
+            .replicas
+            .keys()
+            .filter(|addr| **addr != promote)
+            .cloned()
+            .collect();
+        Step::Failover {
+            epoch,
+            promote,
+            others,
+        }
+    }
+
+    // the replica promoted by the failover of epoch is the master of name
+    fn promoted(&self, name: &str, promoted: InstanceAddr, epoch: u64) {
+        let mut masters = self.sentinel.masters.lock();
+        if let Some(master) = masters.get_mut(name) {
+            info!(
+                "+switch-master {} {} {} in epoch {}",
+                name, master.addr, promoted, epoch
+            );
+            master.switch_to(promoted, epoch);
+        }
+    }
+
+    fn failover_aborted(&self, name: &str) {
+        if let Some(master) = self.sentinel.masters.lock().get_mut(name) {
+            master.failover = None;
+        }
+    }
+
+    // the other sentinels monitoring the master of name, by run ID
+    fn peers(&self, name: &str) -> Vec<(String, InstanceAddr)> {
+        let masters = self.sentinel.masters.lock();
+        let Some(master) = masters.get(name) else {
+            return Vec::new();
+        };
+        master
+            .sentinels
+            .iter()
+            .map(|(runid, peer)| (runid.clone(), peer.addr.clone()))
+            .collect()
+    }
+
+    // a hello received on an instance: the sentinel announcing it monitors the master of the
+    // name it gives, at the address of its config, which replaces ours if it's newer
+    fn hello(&self, message: &str) {
+        let Some(hello) = Hello::parse(message) else {
+            return;
+        };
+        if hello.runid == self.sentinel.myid {
+            return;
+        }
+        self.sentinel
+            .current_epoch
+            .fetch_max(hello.current_epoch, Ordering::Relaxed);
+        let mut masters = self.sentinel.masters.lock();
+        let Some(master) = masters.get_mut(&hello.name) else {
+            return;
+        };
+        // a sentinel restarted with a new run ID
+        master
+            .sentinels
+            .retain(|runid, peer| *runid == hello.runid || peer.addr != hello.addr);
+        let peer = master
+            .sentinels
+            .entry(hello.runid.clone())
+            .or_insert_with(|| {
+                info!(
+                    "+sentinel {} {} for {}",
+                    hello.runid, hello.addr, hello.name
+                );
+                SentinelPeer {
+                    addr: hello.addr.clone(),
+                    last_hello: 0,
+                    master_down: false,
+                    vote: None,
+                    last_reply: 0,
+                }
+            });
+        peer.addr = hello.addr.clone();
+        peer.last_hello = now_ms();
+        if hello.config_epoch > master.config_epoch && hello.master != master.addr {
+            info!(
+                "+switch-master {} {} {} in epoch {}, learned from {}",
+                hello.name, master.addr, hello.master, hello.config_epoch, hello.runid
+            );
+            master.switch_to(hello.master, hello.config_epoch);
+        }
+    }
+
+    // subscribe to the hellos of every instance monitored, from a task each
+    fn receive_hellos(&self, tasks: &mut HashMap<InstanceAddr, JoinHandle<()>>) {
+        let instances: Vec<InstanceAddr> = {
+            let masters = self.sentinel.masters.lock();
+            masters
+                .values()
+                .flat_map(|master| {
+                    std::iter::once(master.addr.clone()).chain(master.replicas.keys().cloned())
+                })
+                .collect()
+        };
+        tasks.retain(|addr, task| {
+            let kept = instances.contains(addr);
+            if !kept {
+                task.abort();
+            }
+            kept
+        });
+        for addr in instances {
+            tasks
+                .entry(addr.clone())
+                .or_insert_with(|| tokio::spawn(receive_hellos(self.clone(), addr)));
+        }
+    }
+}
+
+// probe the instances of the master of name, then take the next step
+async fn monitor(backend: &Backend, name: &str, links: &mut HashMap<InstanceAddr, Client>) {
+    let Some((instances, hello)) = backend.instances(name) else {
+        return;
+    };
+    let probes = instances.into_iter().map(|addr| {
+        let link = links.remove(&addr);
+        let hello = hello.clone();
+        async move {
+            let probed = timeout(SENTINEL_IO_TIMEOUT, probe(&addr, link, hello)).await;
+            match probed {
+                Ok(Ok((link, info))) => (addr, Some(link), Some(info)),
+                Ok(Err(e)) => {
+                    debug!("probing {} failed: {}", addr, e);
+                    (addr, None, None)
+                }
+                Err(_) => (addr, None, None),
+            }
+        }
+    });
+    for (addr, link, info) in future::join_all(probes).await {
+        if let Some(link) = link {
+            links.insert(addr.clone(), link);
+        }
+        backend.probed(name, &addr, info);
+    }
+
+    match backend.next_step(name) {
+        Step::Nothing => {}
+        Step::Ask { epoch, candidate } => ask_sentinels(backend, name, epoch, candidate).await,
+        Step::Failover {
+            epoch,
+            promote,
+            others,
+        } => {
+            info!(
+                "+promoted-slave {} for {} in epoch {}",
+                promote, name, epoch
+            );
+            let promoted = request(links, &promote, &["REPLICAOF", "NO", "ONE"]).await;
+            if !matches!(promoted, Ok(RespFrame::SimpleString(_))) {
+                warn!("-failover-abort-slave-timeout {} for {}", promote, name);
+                backend.failover_aborted(name);
+                return;
+            }
+            backend.promoted(name, promote.clone(), epoch);
+            reconfigure(links, &others, &promote).await;
+        }
+        Step::Reconfigure(strays) => {
+            if let Some(master) = backend.sentinel_master_addr(name) {
+                reconfigure(links, &strays, &master).await;
+            }
+        }
+    }
+}
+
+// PING, INFO replication and announce this sentinel on addr, over link unless there's none
+async fn probe(
+    addr: &InstanceAddr,
+    link: Option<Client>,
+    mut hello: Hello,
+) -> Result<(Client, InstanceInfo)> {
+    let mut link = match link {
+        Some(link) => link,
+        None => Client::connect((addr.host.as_str(), addr.port)).await?,
+    };
+    if hello.addr.host.is_empty() {
+        hello.addr.host = link.local_addr()?.ip().to_string();
+    }
+    let pipeline = Pipeline::new()
+        .cmd(["PING"])
+        .cmd(["INFO", "replication"])
+        .cmd(["PUBLISH", HELLO_CHANNEL, &hello.to_string()]);
+    let replies = link.pipeline(pipeline).await?;
+    match &replies[..] {
+        [RespFrame::SimpleString(_), RespFrame::BulkString(info), ..] => {
+            Ok((link, InstanceInfo::parse(&String::from_utf8_lossy(info))))
+        }
+        _ => Err(anyhow!("unexpected replies {:?}", replies)),
+    }
+}
+
+// ask the other sentinels whether the master of name is down, and to vote for this one in
+// the election of epoch if it's a candidate
+async fn ask_sentinels(backend: &Backend, name: &str, epoch: u64, candidate: bool) {
+    let Some(master) = backend.sentinel_master_addr(name) else {
+        return;
+    };
+    let runid = match candidate {
+        true => backend.sentinel_myid(),
+        false => "*".to_string(),
+    };
+    let epoch = epoch.to_string();
+    let port = master.port.to_string();
+    let asked = backend.peers(name).into_iter().map(|(peer, addr)| {
+        let args = [
+            "SENTINEL",
+            "is-master-down-by-addr",
+            &master.host,
+            &port,
+            &epoch,
+            &runid,
+        ];
+        async move {
+            let reply = timeout(SENTINEL_IO_TIMEOUT, async {
+                let mut client = Client::connect((addr.host.as_str(), addr.port)).await?;
+                client.request(args).await
+            })
+            .await;
+            (peer, reply)
+        }
+    });
+    for (peer, reply) in future::join_all(asked).await {
+        let reply = match reply {
+            Ok(Ok(RespFrame::Array(reply))) => reply.0,
+            _ => continue,
+        };
+        if let [RespFrame::Integer(down), leader, RespFrame::Integer(epoch)] = &reply[..] {
+            let leader = match leader {
+                RespFrame::BulkString(leader) if &leader[..] != b"*" => {
+                    Some(String::from_utf8_lossy(leader).into_owned())
+                }
+                _ => None,
+            };
+            backend.peer_replied(name, &peer, (*down == 1, leader, *epoch as u64));
+        }
+    }
+}
+
+// have every instance of addrs replicate master
+async fn reconfigure(
+    links: &mut HashMap<InstanceAddr, Client>,
+    addrs: &[InstanceAddr],
+    master: &InstanceAddr,
+) {
+    let port = master.port.to_string();
+    for addr in addrs {
+        info!("+slave-reconf-sent {} to {}", addr, master);
+        let args = ["REPLICAOF", master.host.as_str(), port.as_str()];
+        if let Err(e) = request(links, addr, &args).await {
+            debug!("reconfiguring {} failed: {}", addr, e);
+        }
+    }
+}
+
+// send a command to addr, over the link kept to it if there's one
+async fn request(
+    links: &mut HashMap<InstanceAddr, Client>,
+    addr: &InstanceAddr,
+    args: &[&str],
+) -> Result<RespFrame> {
+    let link = links.remove(addr);
+    let (link, reply) = timeout(SENTINEL_IO_TIMEOUT, async {
+        let mut link = match link {
+            Some(link) => link,
+            None => Client::connect((addr.host.as_str(), addr.port)).await?,
+        };
+        let reply = link.request(args).await?;
+        Ok::<_, anyhow::Error>((link, reply))
+    })
+    .await??;
+    links.insert(addr.clone(), link);
+    Ok(reply)
+}
+
+// pass the hellos published on addr to the backend, subscribing again whenever the
+// connection is lost
+async fn receive_hellos(backend: Backend, addr: InstanceAddr) {
+    loop {
+        let received = async {
+            let connect = Client::connect((addr.host.as_str(), addr.port));
+            let client = timeout(SENTINEL_IO_TIMEOUT, connect).await??;
+            let mut subscriber = client.subscribe(&[HELLO_CHANNEL]).await?;
+            while let Some(message) = subscriber.next().await {
+                backend.hello(&String::from_utf8_lossy(&message?.payload));
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+        if let Err(e) = received.await {
+            debug!("receiving the hellos of {} failed: {}", addr, e);
+        }
+        tokio::time::sleep(HELLO_RETRY_DELAY).await;
+    }
+}
+
+impl InstanceInfo {
+    // the fields of INFO replication: role, master_host, master_port, the offset and a
+    // slaveN line for each replica
+    fn parse(info: &str) -> Self {
+        let mut parsed = InstanceInfo::default();
+        let (mut slave, mut host, mut port) = (false, None, None);
+        for line in info.lines() {
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            match field {
+                "role" => slave = value == "slave",
+                "master_host" => host = Some(value.to_string()),
+                "master_port" => port = value.parse().ok(),
+                "master_repl_offset" | "slave_repl_offset" => {
+                    parsed.offset = value.parse().unwrap_or(parsed.offset)
+                }
+                field if field.starts_with("slave") && field[5..].parse::<u64>().is_ok() => {
+                    let entry = |name: &str| {
+                        value
+                            .split(',')
+                            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                    };
+                    let port = entry("port").and_then(|port| port.parse().ok());
+                    if let (Some(ip), Some(port)) = (entry("ip"), port) {
+                        parsed.replicas.push(InstanceAddr::new(ip, port));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (true, Some(host), Some(port)) = (slave, host, port) {
+            parsed.master = Some(InstanceAddr::new(host, port));
+        }
+        parsed
+    }
+}
+
+impl Hello {
+    fn parse(message: &str) -> Option<Self> {
+        let fields: Vec<&str> = message.split(',').collect();
+        let [ip, port, runid, current_epoch, name, master_ip, master_port, config_epoch] =
+            fields[..]
+        else {
+            return None;
+        };
+        Some(Hello {
+            addr: InstanceAddr::new(ip, port.parse().ok()?),
+            runid: runid.to_string(),
+            current_epoch: current_epoch.parse().ok()?,
+            name: name.to_string(),
+            master: InstanceAddr::new(master_ip, master_port.parse().ok()?),
+            config_epoch: config_epoch.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Hello {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.addr.host,
+            self.addr.port,
+            self.runid,
+            self.current_epoch,
+            self.name,
+            self.master.host,
+            self.master.port,
+            self.config_epoch
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Server;
+    use std::time::Instant;
+
+    fn sentinel() -> Backend {
+        let backend = Backend::new();
+        backend.enable_sentinel();
+        backend
+            .sentinel_monitor("mymaster", "127.0.0.1", 6380, 2)
+            .unwrap();
+        backend
+    }
+
+    fn hello(runid: &str, port: u16, master_port: u16, config_epoch: u64) -> String {
+        Hello {
+            addr: InstanceAddr::new("127.0.0.1", port),
+            runid: runid.to_string(),
+            current_epoch: config_epoch,
+            name: "mymaster".to_string(),
+            master: InstanceAddr::new("127.0.0.1", master_port),
+            config_epoch,
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn test_instance_info() {
+        let master = "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+            slave0:ip=127.0.0.1,port=6381,state=online,offset=10,lag=0\r\n\
+            slave1:ip=127.0.0.1,port=6382,state=online,offset=8,lag=1\r\n\
+            master_repl_offset:10\r\n";
+        assert_eq!(
+            InstanceInfo::parse(master),
+            InstanceInfo {
+                master: None,
+                offset: 10,
+                replicas: vec![
+                    InstanceAddr::new("127.0.0.1", 6381),
+                    InstanceAddr::new("127.0.0.1", 6382)
+                ],
+            }
+        );
+        let replica = "# Replication\r\nrole:slave\r\nmaster_host:127.0.0.1\r\n\
+            master_port:6380\r\nmaster_link_status:up\r\nslave_repl_offset:8\r\n";
+        let info = InstanceInfo::parse(replica);
+        assert_eq!(info.master, Some(InstanceAddr::new("127.0.0.1", 6380)));
+        assert_eq!(info.offset, 8);
+    }
+
+    #[test]
+    fn test_hello() {
+        let backend = sentinel();
+        let message = hello("a", 26380, 6380, 0);
+        assert_eq!(Hello::parse(&message).unwrap().to_string(), message);
+        assert_eq!(Hello::parse("127.0.0.1,26380,a"), None);
+
+        // ours, unknown masters and the others
+        backend.hello(&hello(&backend.sentinel_myid(), 26379, 6380, 0));
+        backend.hello(&message.replace("mymaster", "other"));
+        assert!(backend.sentinel_sentinels("mymaster").unwrap().is_empty());
+        backend.hello(&message);
+        let sentinels = backend.sentinel_sentinels("mymaster").unwrap();
+        assert_eq!(sentinels[0].runid, "a");
+        // restarted with a new run ID
+        backend.hello(&hello("b", 26380, 6380, 0));
+        let sentinels = backend.sentinel_sentinels("mymaster").unwrap();
+        assert_eq!(sentinels.len(), 1);
+        assert_eq!(sentinels[0].runid, "b");
+
+        // a newer config
+        backend.hello(&hello("b", 26380, 6381, 1));
+        let addr = backend.sentinel_master_addr("mymaster").unwrap();
+        assert_eq!(addr, InstanceAddr::new("127.0.0.1", 6381));
+        let replicas = backend.sentinel_replicas("mymaster").unwrap();
+        assert_eq!(replicas[0].addr, InstanceAddr::new("127.0.0.1", 6380));
+        assert_eq!(backend.sentinel_masters()[0].config_epoch, 1);
+        // not an older one
+        backend.hello(&hello("b", 26380, 6382, 1));
+        assert_eq!(backend.sentinel_master_addr("mymaster"), Some(addr));
+    }
+
+    #[test]
+    fn test_election() {
+        let backend = sentinel();
+        let myid = backend.sentinel_myid();
+        backend.hello(&hello("a", 26380, 6380, 0));
+        let replica = InstanceAddr::new("127.0.0.1", 6381);
+        let info = InstanceInfo {
+            replicas: vec![replica.clone()],
+            ..Default::default()
+        };
+        backend.probed(
+            "mymaster",
+            &InstanceAddr::new("127.0.0.1", 6380),
+            Some(info),
+        );
+        assert_eq!(backend.next_step("mymaster"), Step::Nothing);
+
+        // down to this sentinel only, then to the quorum
+        backend
+            .sentinel_set("mymaster", "down-after-milliseconds", "100")
+            .unwrap();
+        backend
+            .sentinel
+            .masters
+            .lock()
+            .get_mut("mymaster")
+            .unwrap()
+            .last_ok -= 1000;
+        let ask = Step::Ask {
+            epoch: 0,
+            candidate: false,
+        };
+        assert_eq!(backend.next_step("mymaster"), ask);
+        assert_eq!(backend.sentinel_masters()[0].flags, "master,s_down");
+        backend.peer_replied("mymaster", "a", (true, None, 0));
+        assert_eq!(backend.sentinel_masters()[0].flags, "master,s_down,o_down");
+        let candidate = Step::Ask {
+            epoch: 1,
+            candidate: true,
+        };
+        assert_eq!(backend.next_step("mymaster"), candidate);
+        // no other vote in the epoch
+        let voted = backend.sentinel_is_master_down_by_addr("127.0.0.1", 6380, 1, "a");
+        assert_eq!(voted, (true, Some(myid.clone()), 1));
+
+        // elected, the replica reachable is promoted
+        backend.peer_replied("mymaster", "a", (true, Some(myid), 1));
+        backend.probed("mymaster", &replica, Some(InstanceInfo::default()));
+        assert_eq!(
+            backend.next_step("mymaster"),
+            Step::Failover {
+                epoch: 1,
+                promote: replica.clone(),
+                others: vec![],
+            }
+        );
+        backend.promoted("mymaster", replica.clone(), 1);
+        assert_eq!(backend.sentinel_master_addr("mymaster"), Some(replica));
+        assert_eq!(backend.sentinel_masters()[0].flags, "master");
+    }
+
+    // the address of a server started on a runtime of its own, a multi-threaded one
+    async fn start(backend: Backend, runtime: Option<&tokio::runtime::Runtime>) -> Result<u16> {
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .backend(backend)
+            .build()?;
+        let port = server.local_addr()?.port();
+        match runtime {
+            Some(runtime) => runtime.spawn(server.run(std::future::pending())),
+            None => tokio::spawn(server.run(std::future::pending())),
+        };
+        Ok(port)
+    }
+
+    async fn until(timeout: Duration, f: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if f() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        false
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failover() -> Result<()> {
+        // the master on a runtime of its own, shut down to kill it
+        let master_runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let master_port = start(Backend::new(), Some(&master_runtime)).await?;
+        let replica = Backend::new();
+        let replica_port = start(replica.clone(), None).await?;
+        let mut client = Client::connect(("127.0.0.1", replica_port)).await?;
+        let port = master_port.to_string();
+        client.request(["REPLICAOF", "127.0.0.1", &port]).await?;
+
+        let mut sentinels = Vec::new();
+        for _ in 0..2 {
+            let sentinel = Backend::new();
+            sentinel.enable_sentinel();
+            sentinel.sentinel_monitor("mymaster", "127.0.0.1", master_port, 2)?;
+            sentinel.sentinel_set("mymaster", "down-after-milliseconds", "300")?;
+            sentinel.sentinel_set("mymaster", "failover-timeout", "1000")?;
+            let port = start(sentinel.clone(), None).await?;
+            sentinels.push((sentinel, port));
+        }
+        // they find the replica, and each other
+        let discovered = until(Duration::from_secs(10), || {
+            sentinels.iter().all(|(sentinel, _)| {
+                sentinel.sentinel_replicas("mymaster").unwrap().len() == 1
+                    && sentinel.sentinel_sentinels("mymaster").unwrap().len() == 1
+            })
+        })
+        .await;
+        assert!(discovered);
+
+        master_runtime.shutdown_background();
+        let promoted = InstanceAddr::new("127.0.0.1", replica_port);
+        let failed_over = until(Duration::from_secs(20), || {
+            sentinels.iter().all(|(sentinel, _)| {
+                sentinel.sentinel_master_addr("mymaster").as_ref() == Some(&promoted)
+            })
+        })
+        .await;
+        assert!(failed_over);
+        assert!(!replica.is_replica());
+
+        let mut client = Client::connect(("127.0.0.1", sentinels[0].1)).await?;
+        let addr = client
+            .request(["SENTINEL", "get-master-addr-by-name", "mymaster"])
+            .await?;
+        assert_eq!(
+            addr,
+            crate::RespArray::new(vec![
+                crate::BulkString::from("127.0.0.1").into(),
+                crate::BulkString::from(replica_port.to_string().as_str()).into(),
+            ])
+            .into()
+        );
+        let info = client.request(["INFO", "sentinel"]).await?;
+        let RespFrame::BulkString(info) = info else {
+            panic!("not a bulk string: {:?}", info);
+        };
+        let master = format!(
+            "master0:name=mymaster,status=ok,address=127.0.0.1:{},slaves=1,sentinels=2",
+            replica_port
+        );
+        assert!(String::from_utf8_lossy(&info).contains(&master));
+        // a sentinel has no keyspace
+        let get = client.request(["GET", "a"]).await?;
+        assert!(matches!(get, RespFrame::Error(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,542 @@
+use crate::backend::{key_hash, random_picks, scan_page};
+use crate::{Backend, BackendError, NotifyFlags, SetMembers, Value};
+use rand::seq::IteratorRandom;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+
+/// The operation of SINTER, SUNION and SDIFF and their STORE variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl SetOp {
+    fn store_event(self) -> &'static str {
+        match self {
+            SetOp::Inter => "sinterstore",
+            SetOp::Union => "sunionstore",
+            SetOp::Diff => "sdiffstore",
+        }
+    }
+}
+
+impl Backend {
+    /// The set-max-intset-entries the sets created from now on are converted past.
+    pub fn set_max_intset_entries(&self) -> usize {
+        self.set_max_intset_entries.load(Ordering::Relaxed)
+    }
+
+    pub fn set_set_max_intset_entries(&self, entries: usize) {
+        self.set_max_intset_entries
+            .store(entries, Ordering::Relaxed);
+    }
+
+    pub(crate) fn new_set(&self) -> SetMembers {
+        SetMembers::new(self.set_max_intset_entries())
+    }
+
+    /// Add members to the set at key, creating it if needed. Returns how many were new.
+    pub fn sadd(&self, key: &str, members: Vec<String>) -> Result<usize, BackendError> {
+        let mut shard = self.write(key);
+        let set = match shard.get_mut(key) {
+            None => {
+                self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+                shard.insert(key.to_string(), Value::Set(self.new_set()));
+                shard.get_mut(key).map(|obj| &mut **obj)
+            }
+            Some(obj) => {
+                obj.touch();
+                Some(&mut **obj)
+            }
+        };
+        let added = match set {
+            Some(Value::Set(set)) => members
+                .into_iter()
+                .filter(|member| set.insert(member.clone()))
+                .count(),
+            _ => return Err(BackendError::WrongType),
+        };
+
+        if added > 0 {
+            self.notify_keyspace_event(NotifyFlags::SET, "sadd", key);
+        }
+        Ok(added)
+    }
+
+    /// Remove members from the set at key. Returns how many were removed.
+    pub fn srem(&self, key: &str, members: &[String]) -> Result<usize, BackendError> {
+        Ok(self
+            .update_set(key, "srem", |set| {
+                let removed = members.iter().filter(|member| set.remove(member)).count();
+                (removed, removed > 0)
+            })?
+            .unwrap_or(0))
+    }
+
+    /// The members of the set at key, empty if it doesn't exist.
+    pub fn smembers(&self, key: &str) -> Result<Vec<String>, BackendError> {
+        Ok(self
+            .read_set(key, |set| set.iter().map(Cow::into_owned).collect())?
+            .unwrap_or_default())
+    }
+
+    pub fn scard(&self, key: &str) -> Result<usize, BackendError> {
+        Ok(self.read_set(key, |set| set.len())?.unwrap_or(0))
+    }
+
+    pub fn sismember(&self, key: &str, member: &str) -> Result<bool, BackendError> {
+        Ok(self
+            .read_set(key, |set| set.contains(member))?
+            .unwrap_or(false))
+    }
+
+    /// The membership of each of members, all false if the key doesn't exist.
+    pub fn smismember(&self, key: &str, members: &[String]) -> Result<Vec<bool>, BackendError> {
+        Ok(self
+            .read_set(key, |set| members.iter().map(|m| set.contains(m)).collect())?
+            .unwrap_or_else(|| vec![false; members.len()]))
+    }
+
+    /// Remove and return up to count random members of the set at key.
+    pub fn spop(&self, key: &str, count: usize) -> Result<Vec<String>, BackendError> {
+        Ok(self
+            .update_set(key, "spop", |set| {
+                let popped: Vec<String> = set
+                    .iter()
+                    .sample(&mut rand::rng(), count)
+                    .into_iter()
+                    .map(Cow::into_owned)
+                    .collect();
+                for member in popped.iter() {
+                    set.remove(member);
+                }
+                let changed = !popped.is_empty();
+                (popped, changed)
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Random members of the set at key, with the count semantics of `hrandfield`.
+    pub fn srandmember(&self, key: &str, count: i64) -> Result<Vec<String>, BackendError> {
+        Ok(self
+            .read_set(key, |set| {
+                random_picks(set.iter(), set.len(), count)
+                    .into_iter()
+                    .map(Cow::into_owned)
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Incrementally iterate the members of the set at key with the same cursors as `scan`.
+    pub fn sscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<String>), BackendError> {
+        Ok(self
+            .read_set(key, |set| {
+                let batch = set
+                    .iter()
+                    .map(|member| (key_hash(&member), member))
+                    .filter(|(hash, _)| *hash >= cursor)
+                    .collect();
+                let (next, page) = scan_page(batch, count);
+                (next, page.into_iter().map(Cow::into_owned).collect())
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Move member from the set at source to the set at destination, atomically. Returns
+    /// whether source had the member.
+    pub fn smove(
+        &self,
+        source: &str,
+        destination: &str,
+        member: &str,
+    ) -> Result<bool, BackendError> {
+        let mut guard = self.write_many([source, destination]);
+        let sets = sets_at(&[source.to_string(), destination.to_string()], |key| {
+            guard.shard_ref(key).get(key).map(|obj| &**obj)
+        })?;
+        if !sets[0].is_some_and(|set| set.contains(member)) {
+            return Ok(false);
+        }
+        if source == destination {
+            return Ok(true);
+        }
+
+        let shard = guard.shard(source);
+        if let Some(Value::Set(set)) = shard.get_mut(source).map(|obj| &mut **obj) {
+            set.remove(member);
+            let empty = set.is_empty();
+            self.notify_keyspace_event(NotifyFlags::SET, "srem", source);
+            if empty {
+                shard.remove(source);
+                self.notify_keyspace_event(NotifyFlags::GENERIC, "del", source);
+            }
+        }
+
+        let shard = guard.shard(destination);
+        if !shard.contains_key(destination) {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", destination);
+            shard.insert(destination.to_string(), Value::Set(self.new_set()));
+        }
+        if let Some(Value::Set(set)) = shard.get_mut(destination).map(|obj| &mut **obj) {
+            if set.insert(member.to_string()) {
+                self.notify_keyspace_event(NotifyFlags::SET, "sadd", destination);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The members resulting from op on the sets at keys, missing keys being empty sets.
+    pub fn set_op(&self, op: SetOp, keys: &[String]) -> Result<Vec<String>, BackendError> {
+        let guard = self.read_many(keys.iter().map(String::as_str));
+        let sets = sets_at(keys, |key| {
+            let shard = guard.shard(key);
+            if shard.is_expired(key) {
+                return None;
+            }
+            shard.get(key).map(|obj| {
+                obj.touch();
+                &**obj
+            })
+        })?;
+        Ok(combine(op, &sets))
+    }
+
+    /// Store the result of op on the sets at keys in destination, replacing its value and TTL,
+    /// atomically with reading them. An empty result deletes destination. Returns the size of
+    /// the result.
+    pub fn set_op_store(
+        &self,
+        op: SetOp,
+        destination: &str,
+        keys: &[String],
+    ) -> Result<usize, BackendError> {
+        let mut guard = self.write_many(keys.iter().map(String::as_str).chain([destination]));
+        let result = {
+            let sets = sets_at(keys, |key| guard.shard_ref(key).get(key).map(|obj| &**obj))?;
+            combine(op, &sets)
+        };
+
+        let len = result.len();
+        let shard = guard.shard(destination);
+        let existed = shard.remove(destination).is_some();
+        if result.is_empty() {
+            if existed {
+                self.notify_keyspace_event(NotifyFlags::GENERIC, "del", destination);
+            }
+            return Ok(0);
+        }
+        if !existed {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", destination);
+        }
+        let mut set = self.new_set();
+        for member in result {
+            set.insert(member);
+        }
+        shard.insert(destination.to_string(), Value::Set(set));
+        self.notify_keyspace_event(NotifyFlags::SET, op.store_event(), destination);
+        Ok(len)
+    }
+
+    // run f on the set at key, None if the key doesn't exist
+    pub(crate) fn read_set<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&SetMembers) -> R,
+    ) -> Result<Option<R>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::Set(set) => Ok(Some(f(set))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+
+    // run f on the set at key under the write lock, None if the key doesn't exist. f also
+    // tells whether it changed the set: the event is notified, and the key deleted once the
+    // set is empty.
+    fn update_set<R>(
+        &self,
+        key: &str,
+        event: &str,
+        f: impl FnOnce(&mut SetMembers) -> (R, bool),
+    ) -> Result<Option<R>, BackendError> {
+        let mut shard = self.write(key);
+        let (ret, changed, empty) = match shard.get_mut(key) {
+            None => return Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &mut **obj {
+                    Value::Set(set) => {
+                        let (ret, changed) = f(set);
+                        (ret, changed, set.is_empty())
+                    }
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+        };
+
+        if changed {
+            self.notify_keyspace_event(NotifyFlags::SET, event, key);
+        }
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        Ok(Some(ret))
+    }
+}
+
+// the sets at keys, None for the missing ones
+fn sets_at<'a>(
+    keys: &[String],
+    lookup: impl Fn(&str) -> Option<&'a Value>,
+) -> Result<Vec<Option<&'a SetMembers>>, BackendError> {
+    keys.iter()
+        .map(|key| match lookup(key) {
+            None => Ok(None),
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(BackendError::WrongType),
+        })
+        .collect()
+}
+
+fn combine(op: SetOp, sets: &[Option<&SetMembers>]) -> Vec<String> {
+    match op {
+        SetOp::Inter => {
+            let Some(mut sets) = sets.iter().copied().collect::<Option<Vec<_>>>() else {
+                return Vec::new();
+            };
+            // walk the smallest set, probing the others from the smallest on
+            sets.sort_by_key(|set| set.len());
+            let Some((smallest, others)) = sets.split_first() else {
+                return Vec::new();
+            };
+            smallest
+                .iter()
+                .filter(|member| others.iter().all(|set| set.contains(member)))
+                .map(Cow::into_owned)
+                .collect()
+        }
+        SetOp::Union => {
+            let union: HashSet<_> = sets.iter().flatten().flat_map(|set| set.iter()).collect();
+            union.into_iter().map(Cow::into_owned).collect()
+        }
+        SetOp::Diff => match sets.split_first() {
+            Some((Some(first), others)) => first
+                .iter()
+                .filter(|member| others.iter().flatten().all(|set| !set.contains(member)))
+                .map(Cow::into_owned)
+                .collect(),
+            _ => Vec::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+
+    fn members(members: &[&str]) -> Vec<String> {
+        members.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sadd_srem() {
+        let backend = Backend::new();
+        assert_eq!(backend.sadd("s", members(&["a", "b", "a"])), Ok(2));
+        assert_eq!(backend.sadd("s", members(&["b", "c"])), Ok(1));
+        assert_eq!(backend.scard("s"), Ok(3));
+        assert_eq!(backend.sismember("s", "c"), Ok(true));
+        assert_eq!(backend.sismember("s", "x"), Ok(false));
+        assert_eq!(
+            backend.smismember("s", &members(&["a", "x", "c"])),
+            Ok(vec![true, false, true])
+        );
+        assert_eq!(
+            backend.smismember("missing", &members(&["a", "b"])),
+            Ok(vec![false, false])
+        );
+
+        let mut all = backend.smembers("s").unwrap();
+        all.sort();
+        assert_eq!(all, members(&["a", "b", "c"]));
+
+        assert_eq!(backend.srem("s", &members(&["a", "x"])), Ok(1));
+        assert_eq!(backend.srem("missing", &members(&["a"])), Ok(0));
+
+        // removing the last members deletes the key
+        assert_eq!(backend.srem("s", &members(&["b", "c"])), Ok(2));
+        assert!(!backend.contains_key("s"));
+        assert_eq!(backend.smembers("s"), Ok(vec![]));
+    }
+
+    fn sorted(mut members: Vec<String>) -> Vec<String> {
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn test_set_op() {
+        let backend = Backend::new();
+        backend.sadd("s1", members(&["a", "b", "c", "d"])).unwrap();
+        backend.sadd("s2", members(&["c", "d", "e"])).unwrap();
+        backend.sadd("s3", members(&["a", "c", "e"])).unwrap();
+        let keys = members(&["s1", "s2", "s3"]);
+
+        let inter = backend.set_op(SetOp::Inter, &keys).unwrap();
+        assert_eq!(inter, members(&["c"]));
+        let union = backend.set_op(SetOp::Union, &keys).unwrap();
+        assert_eq!(sorted(union), members(&["a", "b", "c", "d", "e"]));
+        let diff = backend.set_op(SetOp::Diff, &keys).unwrap();
+        assert_eq!(sorted(diff), members(&["b"]));
+
+        // a missing key is an empty set
+        let keys = members(&["s1", "missing"]);
+        assert_eq!(backend.set_op(SetOp::Inter, &keys), Ok(vec![]));
+        assert_eq!(
+            backend.set_op(SetOp::Diff, &keys).map(sorted),
+            Ok(members(&["a", "b", "c", "d"]))
+        );
+    }
+
+    #[test]
+    fn test_set_op_store() {
+        let backend = Backend::new();
+        backend.sadd("s1", members(&["a", "b"])).unwrap();
+        backend.sadd("s2", members(&["b", "c"])).unwrap();
+        backend.set("dst".to_string(), RespFrame::BulkString(b"v".into()));
+
+        let keys = members(&["s1", "s2"]);
+        assert_eq!(backend.set_op_store(SetOp::Union, "dst", &keys), Ok(3));
+        assert_eq!(backend.scard("dst"), Ok(3));
+
+        // the destination may be one of the sources
+        assert_eq!(backend.set_op_store(SetOp::Inter, "s1", &keys), Ok(1));
+        assert_eq!(backend.smembers("s1"), Ok(members(&["b"])));
+
+        // an empty result deletes the destination
+        assert_eq!(
+            backend.set_op_store(SetOp::Diff, "dst", &members(&["s2", "s2"])),
+            Ok(0)
+        );
+        assert!(!backend.contains_key("dst"));
+    }
+
+    #[test]
+    fn test_spop_srandmember() {
+        let backend = Backend::new();
+        backend.sadd("s", members(&["a", "b", "c"])).unwrap();
+
+        let picked = backend.srandmember("s", 5).unwrap();
+        assert_eq!(sorted(picked), members(&["a", "b", "c"]));
+        // a negative count may repeat members
+        let picked = backend.srandmember("s", -10).unwrap();
+        assert_eq!(picked.len(), 10);
+        assert!(picked.iter().all(|m| ["a", "b", "c"].contains(&m.as_str())));
+        assert_eq!(backend.srandmember("missing", -3), Ok(vec![]));
+
+        let popped = backend.spop("s", 2).unwrap();
+        assert_eq!(popped.len(), 2);
+        assert_eq!(backend.scard("s"), Ok(1));
+        assert!(popped
+            .iter()
+            .all(|m| backend.sismember("s", m) == Ok(false)));
+
+        // popping the last member deletes the key
+        assert_eq!(backend.spop("s", 5).map(|p| p.len()), Ok(1));
+        assert!(!backend.contains_key("s"));
+        assert_eq!(backend.spop("s", 1), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_sscan() {
+        let backend = Backend::new();
+        assert_eq!(backend.sscan("s", 0, 10), Ok((0, vec![])));
+
+        let all: Vec<String> = (0..100).map(|i| format!("m{}", i)).collect();
+        backend.sadd("s", all.clone()).unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, page) = backend.sscan("s", cursor, 7).unwrap();
+            assert!(next == 0 || page.len() >= 7);
+            seen.extend(page);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, sorted(all));
+    }
+
+    #[test]
+    fn test_smove() {
+        let backend = Backend::new();
+        backend.sadd("src", members(&["a", "b"])).unwrap();
+
+        assert_eq!(backend.smove("src", "dst", "a"), Ok(true));
+        assert_eq!(backend.smembers("dst"), Ok(members(&["a"])));
+        assert_eq!(backend.smove("src", "dst", "a"), Ok(false));
+        assert_eq!(backend.smove("src", "src", "b"), Ok(true));
+
+        // moving the last member deletes the source
+        assert_eq!(backend.smove("src", "dst", "b"), Ok(true));
+        assert!(!backend.contains_key("src"));
+        assert_eq!(backend.scard("dst"), Ok(2));
+
+        backend.set("k".to_string(), RespFrame::BulkString(b"v".into()));
+        assert_eq!(backend.smove("dst", "k", "a"), Err(BackendError::WrongType));
+        assert_eq!(backend.scard("dst"), Ok(2));
+    }
+
+    #[test]
+    fn test_set_encoding() {
+        let backend = Backend::new();
+        backend.set_set_max_intset_entries(3);
+        backend.sadd("s", members(&["1", "2", "3"])).unwrap();
+        assert_eq!(backend.object_encoding("s"), Some("intset"));
+
+        backend.sadd("s", members(&["4"])).unwrap();
+        assert_eq!(backend.object_encoding("s"), Some("hashtable"));
+        assert_eq!(
+            sorted(backend.smembers("s").unwrap()),
+            members(&["1", "2", "3", "4"])
+        );
+
+        backend.sadd("t", members(&["1", "a"])).unwrap();
+        assert_eq!(backend.object_encoding("t"), Some("hashtable"));
+    }
+
+    #[test]
+    fn test_set_wrong_type() {
+        let backend = Backend::new();
+        backend.set("k".to_string(), RespFrame::BulkString(b"v".into()));
+        assert_eq!(
+            backend.sadd("k", members(&["a"])),
+            Err(BackendError::WrongType)
+        );
+        assert_eq!(backend.scard("k"), Err(BackendError::WrongType));
+        assert_eq!(
+            backend.set_op(SetOp::Union, &members(&["missing", "k"])),
+            Err(BackendError::WrongType)
+        );
+    }
+}
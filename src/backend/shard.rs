@@ -0,0 +1,115 @@
+use crate::backend::now_ms;
+use crate::{
+    HashTable, Object, QuickList, RespFrame, SetMembers, SortedSet, Storage, StorageEngine, Stream,
+};
+
+/// A value stored in the keyspace, a key holds exactly one value type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(RespFrame),
+    Hash(HashTable),
+    List(QuickList),
+    Set(SetMembers),
+    ZSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    /// The type name as reported by the TYPE command.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Hash(_) => "hash",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+}
+
+/// One shard of the keyspace. All the keys hashing to the shard, and their TTLs, are guarded
+/// by a single lock, so a command touching one key can check and update it atomically.
+#[derive(Debug)]
+pub struct Shard {
+    storage: Box<dyn Storage>,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self::new(StorageEngine::default().create())
+    }
+}
+
+impl Shard {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        Self { storage }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Object<Value>> {
+        self.storage.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Object<Value>> {
+        self.storage.get_mut(key)
+    }
+
+    /// Store value at key, keeping the TTL of a previous value.
+    pub fn insert(&mut self, key: String, value: Value) {
+        self.storage.set(key, Object::new(value));
+    }
+
+    /// Remove the key and its TTL.
+    pub fn remove(&mut self, key: &str) -> Option<Object<Value>> {
+        self.storage.del(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.storage.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Object<Value>)> {
+        self.storage.iter()
+    }
+
+    pub fn expire_time(&self, key: &str) -> Option<i64> {
+        self.storage.expire_time(key)
+    }
+
+    pub fn set_expire(&mut self, key: String, at_ms: i64) {
+        self.storage.set_expire(key, at_ms);
+    }
+
+    pub fn persist(&mut self, key: &str) -> Option<i64> {
+        self.storage.persist(key)
+    }
+
+    pub fn expires(&self) -> impl Iterator<Item = (&str, &i64)> {
+        self.storage.expires()
+    }
+
+    pub fn overhead(&self) -> usize {
+        self.storage.overhead()
+    }
+
+    pub fn is_expired(&self, key: &str) -> bool {
+        self.expire_time(key).is_some_and(|at| at <= now_ms())
+    }
+
+    /// Delete key if its TTL has elapsed, returns true if it was deleted.
+    pub fn expire_if_needed(&mut self, key: &str) -> bool {
+        let expired = self.is_expired(key);
+        if expired {
+            self.remove(key);
+        }
+        expired
+    }
+}
@@ -0,0 +1,80 @@
+use crate::CLUSTER_SLOTS;
+
+// the CRC16 of every byte, for the polynomial 0x1021
+const CRC16_TABLE: [u16; 256] = crc16_table();
+
+/// The hash slot of a key in a cluster, that of its hash tag: keys sharing one, such as
+/// `{user1000}.following` and `{user1000}.followers`, are in the same slot.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    crc16(hash_tag(key)) % CLUSTER_SLOTS as u16
+}
+
+/// What's between the first `{` of a key and the following `}`, if it isn't empty, or else
+/// the whole key.
+pub fn hash_tag(key: &[u8]) -> &[u8] {
+    let Some(open) = key.iter().position(|&b| b == b'{') else {
+        return key;
+    };
+    let rest = &key[open + 1..];
+    match rest.iter().position(|&b| b == b'}') {
+        Some(close) if close > 0 => &rest[..close],
+        _ => key,
+    }
+}
+
+/// The CRC16 of data as Redis computes it, the XMODEM variant: polynomial 0x1021, starting
+/// from 0, neither reflected nor inverted.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &b| {
+        (crc << 8) ^ CRC16_TABLE[((crc >> 8) as u8 ^ b) as usize]
+    })
+}
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b""), 0);
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(CRC16_TABLE[1], 0x1021);
+        assert_eq!(CRC16_TABLE[255], 0x1ef0);
+    }
+
+    #[test]
+    fn test_key_hash_slot() {
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(key_hash_slot(b"bar"), 5061);
+        assert_eq!(key_hash_slot(b""), 0);
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"{user1000}.followers")
+        );
+
+        assert_eq!(hash_tag(b"{user1000}.following"), b"user1000");
+        assert_eq!(hash_tag(b"foo{}{bar}"), b"foo{}{bar}");
+        assert_eq!(hash_tag(b"foo{{bar}}zap"), b"{bar");
+        assert_eq!(hash_tag(b"foo{bar}{zap}"), b"bar");
+        assert_eq!(hash_tag(b"foo{bar"), b"foo{bar");
+    }
+}
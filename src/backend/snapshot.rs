@@ -0,0 +1,198 @@
+use crate::backend::key_hash;
+use crate::{glob_match, Backend, Value};
+use std::sync::Arc;
+
+/// A key with its value and TTL, as copied by `Backend::snapshot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub key: String,
+    /// Shared with the keyspace until the key is written.
+    pub value: Arc<Value>,
+    /// Absolute expiration time in unix milliseconds.
+    pub expire_at: Option<i64>,
+}
+
+/// A copy of the keyspace. Every shard is copied atomically, one shard after the other, so
+/// the snapshot never holds more than one lock and may straddle writes to different shards.
+///
+/// The values aren't copied but shared copy-on-write, so a shard is only locked for as long
+/// as it takes to list its keys: writers proceed while the snapshot is serialized, copying a
+/// value the snapshot holds the first time they write it.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    entries: Vec<SnapshotEntry>,
+}
+
+impl Snapshot {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SnapshotEntry> {
+        self.entries.iter()
+    }
+}
+
+impl IntoIterator for Snapshot {
+    type Item = SnapshotEntry;
+    type IntoIter = std::vec::IntoIter<SnapshotEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl Backend {
+    /// Copy the live keys of the keyspace, the one way features walking the whole keyspace
+    /// (saving, full syncs, reloading) should read it. The values are shared, not copied.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut entries = Vec::new();
+        for shard in self.shards() {
+            let shard = shard.read();
+            entries.extend(shard.iter().filter(|(key, _)| !shard.is_expired(key)).map(
+                |(key, obj)| SnapshotEntry {
+                    key: key.to_string(),
+                    value: obj.share(),
+                    expire_at: shard.expire_time(key),
+                },
+            ));
+        }
+        Snapshot { entries }
+    }
+
+    /// The live keys matching the glob-style pattern, all at once.
+    pub fn keys(&self, pattern: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        for shard in self.shards() {
+            let shard = shard.read();
+            keys.extend(
+                shard
+                    .iter()
+                    .filter(|(key, _)| !shard.is_expired(key))
+                    .filter(|(key, _)| glob_match(pattern.as_bytes(), key.as_bytes()))
+                    .map(|(key, _)| key.to_string()),
+            );
+        }
+        keys
+    }
+
+    /// Incrementally iterate the keys, starting with cursor 0 and calling again with the
+    /// returned cursor until it's 0. Keys are visited in the order of their hash, so a key
+    /// present during the whole iteration is returned at least once however the keyspace
+    /// changes in between, and only one shard is locked at a time.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<String>) {
+        let mut batch: Vec<(u64, String)> = Vec::new();
+        for shard in self.shards() {
+            let shard = shard.read();
+            batch.extend(
+                shard
+                    .iter()
+                    .filter(|(key, _)| !shard.is_expired(key))
+                    .map(|(key, _)| (key_hash(key), key.to_string()))
+                    .filter(|(hash, _)| *hash >= cursor),
+            );
+        }
+        scan_page(batch, count)
+    }
+}
+
+/// Cut the first page of count elements out of the elements whose hash is at least the cursor,
+/// returning the cursor of the next page with it. Every SCAN-like command pages through its
+/// elements with this, so the cursors they return mean the same thing.
+pub(crate) fn scan_page<T>(mut batch: Vec<(u64, T)>, count: usize) -> (u64, Vec<T>) {
+    batch.sort_unstable_by_key(|(hash, _)| *hash);
+
+    // never split elements sharing a hash between two calls, the cursor couldn't tell them apart
+    let mut end = batch.len().min(count.max(1));
+    while end < batch.len() && batch[end].0 == batch[end - 1].0 {
+        end += 1;
+    }
+    let next = match batch.get(end) {
+        Some((hash, _)) => *hash,
+        None => 0,
+    };
+    batch.truncate(end);
+    (
+        next,
+        batch.into_iter().map(|(_, element)| element).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::now_ms;
+    use crate::{Backend, RespFrame, Value};
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshot() {
+        let backend = Backend::with_shards(4);
+        for i in 0..10 {
+            backend.set(format!("k{}", i), RespFrame::Integer(i));
+        }
+        backend.expire_at("k1", now_ms() + 10_000);
+        backend
+            .write("k2")
+            .set_expire("k2".to_string(), now_ms() - 1);
+
+        let snapshot = backend.snapshot();
+        backend.set("k10".to_string(), RespFrame::Integer(10));
+        assert_eq!(snapshot.len(), 9);
+        let k1 = snapshot.iter().find(|e| e.key == "k1").unwrap();
+        assert!(k1.expire_at.is_some());
+        assert!(snapshot.iter().all(|e| e.key != "k2" && e.key != "k10"));
+    }
+
+    #[test]
+    fn test_snapshot_copy_on_write() {
+        let backend = Backend::new();
+        backend
+            .rpush("list", vec![RespFrame::Integer(1), RespFrame::Integer(2)])
+            .unwrap();
+        backend.set("s".to_string(), RespFrame::Integer(1));
+
+        // values are shared until written, then only the keyspace sees the write
+        let snapshot = backend.snapshot();
+        let shared = |key: &str| backend.read(key).get(key).unwrap().share();
+        let entry = |key: &str| snapshot.iter().find(|e| e.key == key).unwrap();
+        assert!(Arc::ptr_eq(&entry("list").value, &shared("list")));
+        backend.rpush("list", vec![RespFrame::Integer(3)]).unwrap();
+        assert!(!Arc::ptr_eq(&entry("list").value, &shared("list")));
+        assert!(matches!(&*entry("list").value, Value::List(list) if list.len() == 2));
+        assert_eq!(backend.llen("list"), Ok(3));
+        assert!(Arc::ptr_eq(&entry("s").value, &shared("s")));
+    }
+
+    #[test]
+    fn test_scan() {
+        let backend = Backend::with_shards(4);
+        for i in 0..100 {
+            backend.set(format!("k{}", i), RespFrame::Integer(i));
+        }
+
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut calls = 0;
+        loop {
+            let (next, keys) = backend.scan(cursor, 10);
+            // keys written or deleted mid-scan don't affect the keys present throughout
+            backend.set(format!("new{}", calls), RespFrame::Integer(0));
+            backend.remove(&format!("k{}", 99 - calls));
+            seen.extend(keys);
+            calls += 1;
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        for i in 0..(100 - calls) {
+            assert!(seen.contains(&format!("k{}", i)));
+        }
+    }
+}
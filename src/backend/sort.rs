@@ -0,0 +1,289 @@
+use crate::backend::element_frame;
+use crate::backend::string::string_bytes;
+use crate::{Backend, BackendError, BulkString, NotifyFlags, RespFrame, Value};
+use std::cmp::Ordering;
+
+/// The options of SORT, see `Backend::sort`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortOptions {
+    /// Sort by the values of external keys, `*` is replaced by the element and `->field`
+    /// reads a hash field. A pattern without `*` skips sorting.
+    pub by: Option<String>,
+    /// Offset and count of the elements to return, a negative count returns all of them.
+    pub limit: Option<(i64, i64)>,
+    /// Return the values of external keys instead of the elements, `#` is the element itself.
+    pub get: Vec<String>,
+    pub desc: bool,
+    /// Compare lexicographically instead of as numbers.
+    pub alpha: bool,
+}
+
+// the value elements are compared by
+enum Weight {
+    Alpha(Option<Vec<u8>>),
+    Number(f64),
+}
+
+impl Backend {
+    /// Sort the elements of the list, set or zset at key. A missing key sorts as empty, `None`
+    /// stands for a GET pattern whose key doesn't exist.
+    pub fn sort(
+        &self,
+        key: &str,
+        options: &SortOptions,
+    ) -> Result<Vec<Option<RespFrame>>, BackendError> {
+        let (mut elements, is_zset) = {
+            let shard = self.read(key);
+            match shard.get(key).map(|obj| &**obj) {
+                None => (Vec::new(), false),
+                Some(Value::List(list)) => (list.iter().map(element_frame).collect(), false),
+                Some(Value::Set(set)) => (set.iter().map(|m| member_frame(&m)).collect(), false),
+                Some(Value::ZSet(zset)) => {
+                    (zset.iter().map(|(m, _)| member_frame(m)).collect(), true)
+                }
+                Some(_) => return Err(BackendError::WrongType),
+            }
+        };
+
+        let dont_sort = options.by.as_ref().is_some_and(|by| !by.contains('*'));
+        if dont_sort {
+            // a zset keeps its own order, reversed for DESC
+            if is_zset && options.desc {
+                elements.reverse();
+            }
+        } else {
+            let mut weighted = elements
+                .into_iter()
+                .map(|element| Ok((self.weight(&element, options)?, element)))
+                .collect::<Result<Vec<_>, BackendError>>()?;
+            weighted.sort_by(|(w1, e1), (w2, e2)| {
+                let ord = compare(w1, w2).then_with(|| string_bytes(e1).cmp(&string_bytes(e2)));
+                if options.desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            });
+            elements = weighted.into_iter().map(|(_, element)| element).collect();
+        }
+
+        let (start, count) = match options.limit {
+            Some((offset, count)) => (
+                offset.max(0) as usize,
+                if count < 0 {
+                    usize::MAX
+                } else {
+                    count as usize
+                },
+            ),
+            None => (0, usize::MAX),
+        };
+
+        let mut result = Vec::new();
+        for element in elements.into_iter().skip(start).take(count) {
+            if options.get.is_empty() {
+                result.push(Some(element));
+                continue;
+            }
+            for pattern in options.get.iter() {
+                result.push(self.lookup(pattern, &element));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Like `sort`, but store the result as a list at dest, missing values become empty
+    /// strings. Returns the length of the list, an empty result deletes dest.
+    pub fn sort_store(
+        &self,
+        key: &str,
+        options: &SortOptions,
+        dest: String,
+    ) -> Result<usize, BackendError> {
+        let result = self.sort(key, options)?;
+        let len = result.len();
+        if len == 0 {
+            self.remove(&dest);
+            return Ok(0);
+        }
+
+        let mut list = self.new_list();
+        for v in result {
+            list.push_back(&v.map(|v| string_bytes(&v)).unwrap_or_default());
+        }
+        let mut shard = self.write(&dest);
+        if shard.remove(&dest).is_none() {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", &dest);
+        }
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "sortstore", &dest);
+        shard.insert(dest, Value::List(list));
+        Ok(len)
+    }
+
+    fn weight(&self, element: &RespFrame, options: &SortOptions) -> Result<Weight, BackendError> {
+        let value = match options.by.as_ref() {
+            Some(by) => self.lookup(by, element),
+            None => Some(element.clone()),
+        };
+
+        if options.alpha {
+            return Ok(Weight::Alpha(value.as_ref().map(string_bytes)));
+        }
+        match value {
+            // a missing external key weighs 0
+            None => Ok(Weight::Number(0.0)),
+            Some(v) => std::str::from_utf8(&string_bytes(&v))
+                .ok()
+                .and_then(|s| s.trim().parse::<f64>().ok())
+                .filter(|n| !n.is_nan())
+                .map(Weight::Number)
+                .ok_or(BackendError::NotDouble),
+        }
+    }
+
+    // the value of the key pattern refers to for element, `key->field` reads a hash field
+    fn lookup(&self, pattern: &str, element: &RespFrame) -> Option<RespFrame> {
+        if pattern == "#" {
+            return Some(element.clone());
+        }
+
+        let star = pattern.find('*')?;
+        let (key_pattern, field) = match pattern[star..].find("->") {
+            Some(i) if star + i + 2 < pattern.len() => {
+                (&pattern[..star + i], Some(&pattern[star + i + 2..]))
+            }
+            _ => (pattern, None),
+        };
+        let key = key_pattern.replacen('*', &String::from_utf8_lossy(&string_bytes(element)), 1);
+
+        match field {
+            Some(field) => self.hget(&key, field).ok().flatten(),
+            None => self.get(&key).ok().flatten(),
+        }
+    }
+}
+
+fn compare(w1: &Weight, w2: &Weight) -> Ordering {
+    match (w1, w2) {
+        (Weight::Alpha(a), Weight::Alpha(b)) => a.cmp(b),
+        (Weight::Number(a), Weight::Number(b)) => a.total_cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+fn member_frame(member: &str) -> RespFrame {
+    BulkString::new(member.as_bytes().to_vec()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SetMembers;
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::new(s.as_bytes().to_vec()).into()
+    }
+
+    fn list(backend: &Backend, key: &str, elements: &[&str]) {
+        let list = elements.iter().collect();
+        backend
+            .write(key)
+            .insert(key.to_string(), Value::List(list));
+    }
+
+    #[test]
+    fn test_sort_numeric_and_alpha() {
+        let backend = Backend::new();
+        list(&backend, "l", &["3", "1", "10", "2"]);
+
+        let result = backend.sort("l", &SortOptions::default()).unwrap();
+        assert_eq!(
+            result,
+            ["1", "2", "3", "10"]
+                .iter()
+                .map(|e| Some(bulk(e)))
+                .collect::<Vec<_>>()
+        );
+
+        let options = SortOptions {
+            alpha: true,
+            desc: true,
+            limit: Some((1, 2)),
+            ..Default::default()
+        };
+        let result = backend.sort("l", &options).unwrap();
+        assert_eq!(result, vec![Some(bulk("2")), Some(bulk("10"))]);
+
+        list(&backend, "words", &["b", "a"]);
+        assert_eq!(
+            backend.sort("words", &SortOptions::default()),
+            Err(BackendError::NotDouble)
+        );
+        assert_eq!(backend.sort("missing", &SortOptions::default()), Ok(vec![]));
+
+        backend.set("s".to_string(), bulk("v"));
+        assert_eq!(
+            backend.sort("s", &SortOptions::default()),
+            Err(BackendError::WrongType)
+        );
+    }
+
+    #[test]
+    fn test_sort_by_and_get() {
+        let backend = Backend::new();
+        let set: SetMembers = ["a", "b", "c"].into_iter().collect();
+        backend
+            .write("users")
+            .insert("users".to_string(), Value::Set(set));
+        backend.set("weight_a".to_string(), bulk("3"));
+        backend.set("weight_b".to_string(), bulk("1"));
+        backend.set("weight_c".to_string(), bulk("2"));
+        backend
+            .hset("user_a".to_string(), "name".to_string(), bulk("Alice"))
+            .unwrap();
+        backend
+            .hset("user_b".to_string(), "name".to_string(), bulk("Bob"))
+            .unwrap();
+
+        let options = SortOptions {
+            by: Some("weight_*".to_string()),
+            get: vec!["#".to_string(), "user_*->name".to_string()],
+            ..Default::default()
+        };
+        let result = backend.sort("users", &options).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                Some(bulk("b")),
+                Some(bulk("Bob")),
+                Some(bulk("c")),
+                None,
+                Some(bulk("a")),
+                Some(bulk("Alice")),
+            ]
+        );
+
+        let len = backend
+            .sort_store("users", &options, "out".to_string())
+            .unwrap();
+        assert_eq!(len, 6);
+        assert_eq!(backend.object_encoding("out"), Some("listpack"));
+
+        // BY a pattern without `*` keeps the zset order
+        let mut zset = crate::SortedSet::new();
+        zset.insert("x".to_string(), 2.0);
+        zset.insert("y".to_string(), 1.0);
+        backend
+            .write("z")
+            .insert("z".to_string(), Value::ZSet(zset));
+        let options = SortOptions {
+            by: Some("nosort".to_string()),
+            desc: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            backend.sort("z", &options).unwrap(),
+            vec![Some(bulk("x")), Some(bulk("y"))]
+        );
+    }
+}
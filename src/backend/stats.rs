@@ -0,0 +1,249 @@
+use crate::Backend;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// The upper bounds of the buckets of the command durations, in microseconds.
+pub const COMMAND_DURATION_BUCKETS: [u64; 10] = [
+    10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 1_000_000,
+];
+
+// how many samples of the commands processed per second the instantaneous rate is the mean of
+const STATS_METRIC_SAMPLES: usize = 16;
+
+/// Counters of what the server did since it started, or since CONFIG RESETSTAT.
+#[derive(Debug, Default)]
+pub struct Stats {
+    connections: AtomicU64,
+    // connections refused by the limits of their address
+    rejected_connections: AtomicU64,
+    // closed for the memory of the clients
+    evicted_clients: AtomicU64,
+    commands: AtomicU64,
+    expired_keys: AtomicU64,
+    // active expiration cycles stopped for taking their whole time
+    expired_time_cap_reached: AtomicU64,
+    // nothing evicts keys yet, maxmemory is only reported
+    evicted_keys: AtomicU64,
+    keyspace_hits: AtomicU64,
+    keyspace_misses: AtomicU64,
+    sync_full: AtomicU64,
+    sync_partial_ok: AtomicU64,
+    sync_partial_err: AtomicU64,
+    // by command name
+    command_stats: Mutex<HashMap<String, CommandStats>>,
+    ops_samples: Mutex<OpsSamples>,
+}
+
+// the commands processed per second between the samplings of the cron, the last first
+#[derive(Debug, Default)]
+struct OpsSamples {
+    last: Option<(Instant, u64)>,
+    rates: VecDeque<u64>,
+}
+
+/// The calls of a command and how long they took, a blocking command's wait included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    pub calls: u64,
+    pub usec: u64,
+    pub max_usec: u64,
+    // the calls that took up to each of COMMAND_DURATION_BUCKETS, longer ones aside
+    pub buckets: [u64; COMMAND_DURATION_BUCKETS.len()],
+}
+
+/// The counters, as INFO reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsReport {
+    pub total_connections_received: u64,
+    pub rejected_connections: u64,
+    pub evicted_clients: u64,
+    pub total_commands_processed: u64,
+    // the mean of the last samples of the commands processed per second
+    pub instantaneous_ops_per_sec: u64,
+    pub expired_keys: u64,
+    pub expired_time_cap_reached_count: u64,
+    pub evicted_keys: u64,
+    pub keyspace_hits: u64,
+    pub keyspace_misses: u64,
+    pub sync_full: u64,
+    pub sync_partial_ok: u64,
+    pub sync_partial_err: u64,
+}
+
+// what a counter counts
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stat {
+    Connection,
+    RejectedConnection,
+    EvictedClient,
+    Command,
+    ExpiredKey,
+    ExpireCycleTimeCapReached,
+    // a key read, found or not
+    KeyspaceHit,
+    KeyspaceMiss,
+    SyncFull,
+    SyncPartialOk,
+    SyncPartialErr,
+}
+
+impl Backend {
+    pub(crate) fn count(&self, stat: Stat) {
+        let stats = &self.stats;
+        let counter = match stat {
+            Stat::Connection => &stats.connections,
+            Stat::RejectedConnection => &stats.rejected_connections,
+            Stat::EvictedClient => &stats.evicted_clients,
+            Stat::Command => &stats.commands,
+            Stat::ExpiredKey => &stats.expired_keys,
+            Stat::ExpireCycleTimeCapReached => &stats.expired_time_cap_reached,
+            Stat::KeyspaceHit => &stats.keyspace_hits,
+            Stat::KeyspaceMiss => &stats.keyspace_misses,
+            Stat::SyncFull => &stats.sync_full,
+            Stat::SyncPartialOk => &stats.sync_partial_ok,
+            Stat::SyncPartialErr => &stats.sync_partial_err,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats_report(&self) -> StatsReport {
+        let stats = &self.stats;
+        StatsReport {
+            total_connections_received: stats.connections.load(Ordering::Relaxed),
+            rejected_connections: stats.rejected_connections.load(Ordering::Relaxed),
+            evicted_clients: stats.evicted_clients.load(Ordering::Relaxed),
+            total_commands_processed: stats.commands.load(Ordering::Relaxed),
+            instantaneous_ops_per_sec: self.instantaneous_ops_per_sec(),
+            expired_keys: stats.expired_keys.load(Ordering::Relaxed),
+            expired_time_cap_reached_count: stats.expired_time_cap_reached.load(Ordering::Relaxed),
+            evicted_keys: stats.evicted_keys.load(Ordering::Relaxed),
+            keyspace_hits: stats.keyspace_hits.load(Ordering::Relaxed),
+            keyspace_misses: stats.keyspace_misses.load(Ordering::Relaxed),
+            sync_full: stats.sync_full.load(Ordering::Relaxed),
+            sync_partial_ok: stats.sync_partial_ok.load(Ordering::Relaxed),
+            sync_partial_err: stats.sync_partial_err.load(Ordering::Relaxed),
+        }
+    }
+
+    // sample the commands processed per second since the last sample, which the cron does
+    pub(crate) fn sample_ops(&self) {
+        let now = Instant::now();
+        let commands = self.stats.commands.load(Ordering::Relaxed);
+        let mut samples = self.stats.ops_samples.lock();
+        if let Some((at, last)) = samples.last {
+            let ms = now.duration_since(at).as_millis().max(1) as u64;
+            samples
+                .rates
+                .push_front(commands.saturating_sub(last) * 1000 / ms);
+            samples.rates.truncate(STATS_METRIC_SAMPLES);
+        }
+        samples.last = Some((now, commands));
+    }
+
+    fn instantaneous_ops_per_sec(&self) -> u64 {
+        let samples = self.stats.ops_samples.lock();
+        match samples.rates.len() {
+            0 => 0,
+            len => samples.rates.iter().sum::<u64>() / len as u64,
+        }
+    }
+
+    // count a call of the command, and how long it took
+    pub(crate) fn count_command(&self, name: &str, elapsed: Duration) {
+        let usec = elapsed.as_micros() as u64;
+        let mut command_stats = self.stats.command_stats.lock();
+        let stats = match command_stats.get_mut(name) {
+            Some(stats) => stats,
+            None => command_stats.entry(name.to_string()).or_default(),
+        };
+        stats.calls += 1;
+        stats.usec += usec;
+        stats.max_usec = stats.max_usec.max(usec);
+        if let Some(bucket) = COMMAND_DURATION_BUCKETS.iter().position(|&le| usec <= le) {
+            stats.buckets[bucket] += 1;
+        }
+    }
+
+    /// The stats of every command called, by name.
+    pub fn command_stats(&self) -> Vec<(String, CommandStats)> {
+        let mut command_stats: Vec<_> = self
+            .stats
+            .command_stats
+            .lock()
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.clone()))
+            .collect();
+        command_stats.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        command_stats
+    }
+
+    /// Set every counter back to 0.
+    pub fn reset_stats(&self) {
+        let stats = &self.stats;
+        stats.command_stats.lock().clear();
+        *stats.ops_samples.lock() = OpsSamples::default();
+        for counter in [
+            &stats.connections,
+            &stats.rejected_connections,
+            &stats.evicted_clients,
+            &stats.commands,
+            &stats.expired_keys,
+            &stats.expired_time_cap_reached,
+            &stats.evicted_keys,
+            &stats.keyspace_hits,
+            &stats.keyspace_misses,
+            &stats.sync_full,
+            &stats.sync_partial_ok,
+            &stats.sync_partial_err,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_stats() {
+        let backend = Backend::new();
+        backend.count_command("get", Duration::from_micros(30));
+        backend.count_command("get", Duration::from_millis(2));
+        backend.count_command("set", Duration::from_secs(2));
+        backend.count(Stat::KeyspaceHit);
+
+        let command_stats = backend.command_stats();
+        assert_eq!(command_stats.len(), 2);
+        let (name, get) = &command_stats[0];
+        assert_eq!(name, "get");
+        assert_eq!((get.calls, get.usec, get.max_usec), (2, 2030, 2000));
+        assert_eq!(get.buckets[1], 1);
+        assert_eq!(get.buckets[5], 1);
+        // longer than the last bucket
+        assert_eq!(command_stats[1].1.buckets.iter().sum::<u64>(), 0);
+        assert_eq!(backend.stats_report().keyspace_hits, 1);
+
+        backend.reset_stats();
+        assert!(backend.command_stats().is_empty());
+        assert_eq!(backend.stats_report(), StatsReport::default());
+    }
+
+    #[test]
+    fn test_instantaneous_ops_per_sec() {
+        let backend = Backend::new();
+        backend.sample_ops();
+        for _ in 0..100 {
+            backend.count(Stat::Command);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        backend.sample_ops();
+        let ops = backend.stats_report().instantaneous_ops_per_sec;
+        assert!(ops > 0 && ops <= 1000, "{}", ops);
+
+        backend.reset_stats();
+        assert_eq!(backend.stats_report().instantaneous_ops_per_sec, 0);
+    }
+}
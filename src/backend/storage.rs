@@ -0,0 +1,146 @@
+use crate::{CompactString, Object, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::str::FromStr;
+
+/// The engine storing the keys of one shard. Callers hold the shard lock, so an engine only
+/// needs to be `Send + Sync`, not internally synchronized.
+///
+/// The in-memory engine is the default, other engines (e.g. disk backed ones for datasets
+/// larger than RAM) plug in through `Backend::with_engine`.
+pub trait Storage: fmt::Debug + Send + Sync {
+    fn get(&self, key: &str) -> Option<&Object<Value>>;
+    fn get_mut(&mut self, key: &str) -> Option<&mut Object<Value>>;
+    /// Store value at key, keeping the TTL of a previous value. Returns the previous value.
+    fn set(&mut self, key: String, value: Object<Value>) -> Option<Object<Value>>;
+    /// Remove the key and its TTL. Returns the removed value.
+    fn del(&mut self, key: &str) -> Option<Object<Value>>;
+    fn len(&self) -> usize;
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, &Object<Value>)> + '_>;
+
+    /// The absolute expiration time of key in unix milliseconds.
+    fn expire_time(&self, key: &str) -> Option<i64>;
+    fn set_expire(&mut self, key: String, at_ms: i64);
+    /// Remove the TTL of key, returns the removed expiration time.
+    fn persist(&mut self, key: &str) -> Option<i64>;
+    fn expires(&self) -> Box<dyn Iterator<Item = (&str, &i64)> + '_>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes used by the engine's own bookkeeping, on top of the stored keys and values.
+    fn overhead(&self) -> usize {
+        0
+    }
+}
+
+/// Selects the storage engine of a backend, e.g. from the `storage-engine` config option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageEngine {
+    #[default]
+    Memory,
+}
+
+impl StorageEngine {
+    pub fn create(&self) -> Box<dyn Storage> {
+        match self {
+            StorageEngine::Memory => Box::<MemoryStorage>::default(),
+        }
+    }
+}
+
+impl FromStr for StorageEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" => Ok(StorageEngine::Memory),
+            _ => Err(format!("unknown storage engine: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    keys: HashMap<CompactString, Object<Value>>,
+    expires: HashMap<CompactString, i64>,
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<&Object<Value>> {
+        self.keys.get(key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Object<Value>> {
+        self.keys.get_mut(key)
+    }
+
+    fn set(&mut self, key: String, value: Object<Value>) -> Option<Object<Value>> {
+        self.keys.insert(key.into(), value)
+    }
+
+    fn del(&mut self, key: &str) -> Option<Object<Value>> {
+        self.expires.remove(key);
+        self.keys.remove(key)
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, &Object<Value>)> + '_> {
+        Box::new(self.keys.iter().map(|(key, value)| (key.as_str(), value)))
+    }
+
+    fn expire_time(&self, key: &str) -> Option<i64> {
+        self.expires.get(key).copied()
+    }
+
+    fn set_expire(&mut self, key: String, at_ms: i64) {
+        self.expires.insert(key.into(), at_ms);
+    }
+
+    fn persist(&mut self, key: &str) -> Option<i64> {
+        self.expires.remove(key)
+    }
+
+    fn expires(&self) -> Box<dyn Iterator<Item = (&str, &i64)> + '_> {
+        Box::new(self.expires.iter().map(|(key, at)| (key.as_str(), at)))
+    }
+
+    fn overhead(&self) -> usize {
+        // one hash table slot per key and per TTL
+        (self.keys.capacity() + self.expires.capacity()) * (size_of::<u64>() * 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+
+    #[test]
+    fn test_memory_storage() {
+        let mut storage = "Memory".parse::<StorageEngine>().unwrap().create();
+        assert!(storage.is_empty());
+
+        let value = Value::String(RespFrame::BulkString(b"v1".into()));
+        assert!(storage
+            .set("k1".to_string(), Object::new(value.clone()))
+            .is_none());
+        storage.set_expire("k1".to_string(), 1000);
+        assert_eq!(storage.get("k1").map(|v| &**v), Some(&value));
+        assert_eq!(storage.expire_time("k1"), Some(1000));
+
+        // overwriting keeps the TTL, deleting drops it
+        storage.set("k1".to_string(), Object::new(value));
+        assert_eq!(storage.expire_time("k1"), Some(1000));
+        assert!(storage.del("k1").is_some());
+        assert_eq!(storage.expire_time("k1"), None);
+        assert_eq!(storage.len(), 0);
+
+        assert!("sled".parse::<StorageEngine>().is_err());
+    }
+}
@@ -0,0 +1,1629 @@
+use crate::backend::now_ms;
+use crate::{
+    Backend, BackendError, BlockedPop, NotifyFlags, PopFrom, RespFrame, ShardsWriteGuard, Value,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Bound::{Excluded, Unbounded};
+
+/// The ID of a stream entry: the unix time in milliseconds it was added at, then a sequence
+/// number among the entries added within the same millisecond.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// The ID XADD gives the new entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XAddId {
+    /// `*`, generated from the current time.
+    Auto,
+    /// `ms-*`, with the next sequence number for ms.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// How many entries approximate trimming removes at a time, as the radix tree nodes of Redis
+/// hold by default.
+pub const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// Which entries trimming a stream removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimStrategy {
+    /// The oldest ones beyond the length.
+    MaxLen(usize),
+    /// The ones with smaller IDs.
+    MinId(StreamId),
+}
+
+/// The trimming of XTRIM, or of XADD after adding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTrim {
+    pub strategy: TrimStrategy,
+    /// `~`: only remove whole nodes of `STREAM_NODE_MAX_ENTRIES` entries, which may leave a
+    /// few entries that should go.
+    pub approx: bool,
+    /// The most entries an approximate trim removes, 0 for no limit, 100 nodes if None.
+    pub limit: Option<usize>,
+}
+
+/// The options of XADD.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XAddOptions {
+    /// Don't create the stream if the key doesn't exist.
+    pub nomkstream: bool,
+    pub trim: Option<StreamTrim>,
+}
+
+/// The fields of a stream entry, in the order they were given.
+pub type StreamFields = Vec<(String, RespFrame)>;
+
+/// The new entries XREAD read, by stream. Streams without new entries are left out.
+pub type StreamReads = Vec<(String, Vec<(StreamId, StreamFields)>)>;
+
+/// The entries XREADGROUP delivered from a stream. A pending entry deleted from the stream has
+/// no fields.
+pub type GroupEntries = Vec<(StreamId, Option<StreamFields>)>;
+
+/// The entries XREADGROUP delivered, by stream.
+pub type GroupReads = Vec<(String, GroupEntries)>;
+
+/// The outcome of `Backend::xread_or_block` and `Backend::xreadgroup_or_block`.
+#[derive(Debug)]
+pub enum BlockingRead<T = StreamReads> {
+    Ready(T),
+    /// Woken, with an empty pop, once an entry is added to one of the streams.
+    Blocked(BlockedPop),
+}
+
+/// An append-only log of entries ordered by ID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stream {
+    entries: BTreeMap<StreamId, StreamFields>,
+    // the greatest ID ever added, new IDs must be greater even once it is deleted
+    last_id: StreamId,
+    groups: BTreeMap<String, ConsumerGroup>,
+}
+
+/// A consumer group of a stream: how far it delivered the entries to its consumers, and which
+/// ones they have yet to acknowledge.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConsumerGroup {
+    last_delivered: StreamId,
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+/// An entry delivered to a consumer that didn't acknowledge it yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// Unix time in milliseconds of the last delivery.
+    pub delivered_at: i64,
+    pub deliveries: u64,
+}
+
+/// The summary of the pending entries of a group replied by XPENDING without a range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PendingSummary {
+    pub count: usize,
+    /// The smallest and the greatest pending IDs, None if nothing is pending.
+    pub bounds: Option<(StreamId, StreamId)>,
+    /// How many entries each consumer with some has pending.
+    pub consumers: Vec<(String, usize)>,
+}
+
+/// The pending entries XPENDING lists with a range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingFilter {
+    /// The minimum milliseconds since the last delivery.
+    pub min_idle: i64,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    pub consumer: Option<String>,
+}
+
+/// The options of XCLAIM.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClaimOptions {
+    /// Unix time in milliseconds to record as the last delivery, now if None.
+    pub delivered_at: Option<i64>,
+    /// The delivery count to set instead of incrementing it.
+    pub retry_count: Option<u64>,
+    /// Claim the IDs that aren't pending too, as long as they are in the stream.
+    pub force: bool,
+    /// Don't count the claim as a delivery.
+    pub justid: bool,
+    /// The last delivered ID of the group, if greater than the current one.
+    pub last_id: Option<StreamId>,
+}
+
+/// The outcome of XAUTOCLAIM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoClaimed {
+    /// The ID to resume the scan from, 0-0 once it went through every pending entry.
+    pub next: StreamId,
+    pub claimed: Vec<(StreamId, StreamFields)>,
+    /// The pending entries deleted from the stream, which are dropped from the group.
+    pub deleted: Vec<StreamId>,
+}
+
+/// What XINFO STREAM reports about a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub length: usize,
+    /// The greatest ID ever added.
+    pub last_generated: StreamId,
+    pub groups: usize,
+    pub first: Option<(StreamId, StreamFields)>,
+    pub last: Option<(StreamId, StreamFields)>,
+}
+
+/// What XINFO GROUPS reports about a consumer group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    pub name: String,
+    pub consumers: usize,
+    pub pending: usize,
+    pub last_delivered: StreamId,
+    /// How many entries of the stream were never delivered to the group.
+    pub lag: usize,
+}
+
+/// What XINFO CONSUMERS reports about a consumer of a group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerInfo {
+    pub name: String,
+    pub pending: usize,
+    /// Milliseconds since the consumer was last seen.
+    pub idle: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Consumer {
+    seen_at: i64,
+    pending: BTreeSet<StreamId>,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        Self { ms, seq }
+    }
+
+    /// The smallest ID greater than this one, None for MAX.
+    pub fn next(self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => self.ms.checked_add(1).map(|ms| Self::new(ms, 0)),
+        }
+    }
+
+    /// The greatest ID smaller than this one, None for MIN.
+    pub fn prev(self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => self.ms.checked_sub(1).map(|ms| Self::new(ms, u64::MAX)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl Stream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The greatest ID ever added to the stream.
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// Add an entry, with an ID greater than every one added before.
+    pub fn add(&mut self, id: XAddId, fields: StreamFields) -> Result<StreamId, BackendError> {
+        let id = self.resolve(id)?;
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        Ok(id)
+    }
+
+    /// Remove the entries trim selects, returns how many were removed.
+    pub fn trim(&mut self, trim: &StreamTrim) -> usize {
+        let mut removed = match trim.strategy {
+            TrimStrategy::MaxLen(len) => self.len().saturating_sub(len),
+            TrimStrategy::MinId(id) => self.entries.range(..id).count(),
+        };
+        if trim.approx {
+            let limit = match trim.limit {
+                None => STREAM_NODE_MAX_ENTRIES * 100,
+                Some(0) => usize::MAX,
+                Some(limit) => limit,
+            };
+            removed = removed.min(limit);
+            removed -= removed % STREAM_NODE_MAX_ENTRIES;
+        }
+        for _ in 0..removed {
+            self.entries.pop_first();
+        }
+        removed
+    }
+
+    /// Delete the entry id, returns whether it was there.
+    pub fn remove(&mut self, id: StreamId) -> bool {
+        self.entries.remove(&id).is_some()
+    }
+
+    /// The entries with IDs between start and end included, walking down from end if rev.
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        rev: bool,
+    ) -> Box<dyn Iterator<Item = (&StreamId, &StreamFields)> + '_> {
+        if start > end {
+            return Box::new(std::iter::empty());
+        }
+        let range = self.entries.range(start..=end);
+        if rev {
+            Box::new(range.rev())
+        } else {
+            Box::new(range)
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&StreamId, &StreamFields)> {
+        self.entries.iter()
+    }
+
+    pub fn group(&self, name: &str) -> Option<&ConsumerGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &ConsumerGroup)> {
+        self.groups.iter()
+    }
+
+    /// The summary of the stream XINFO STREAM reports.
+    pub fn info(&self) -> StreamInfo {
+        let entry = |(id, fields): (&StreamId, &StreamFields)| (*id, fields.clone());
+        StreamInfo {
+            length: self.len(),
+            last_generated: self.last_id,
+            groups: self.groups.len(),
+            first: self.entries.first_key_value().map(entry),
+            last: self.entries.last_key_value().map(entry),
+        }
+    }
+
+    /// The summary of every group XINFO GROUPS reports, by name.
+    pub fn groups_info(&self) -> Vec<GroupInfo> {
+        self.groups
+            .iter()
+            .map(|(name, group)| GroupInfo {
+                name: name.clone(),
+                consumers: group.consumers.len(),
+                pending: group.pending.len(),
+                last_delivered: group.last_delivered,
+                lag: self
+                    .entries
+                    .range((Excluded(group.last_delivered), Unbounded))
+                    .count(),
+            })
+            .collect()
+    }
+
+    // put back an entry of a saved stream as is, whatever the last ID
+    pub(crate) fn restore_entry(&mut self, id: StreamId, fields: StreamFields) {
+        self.entries.insert(id, fields);
+        self.last_id = self.last_id.max(id);
+    }
+
+    pub(crate) fn restore_last_id(&mut self, id: StreamId) {
+        self.last_id = self.last_id.max(id);
+    }
+
+    pub(crate) fn group_mut(&mut self, name: &str) -> Option<&mut ConsumerGroup> {
+        self.groups.get_mut(name)
+    }
+
+    pub(crate) fn restore_group(&mut self, name: String, group: ConsumerGroup) {
+        self.groups.insert(name, group);
+    }
+
+    // deliver entries to consumer of group: the new ones if after is None, else its pending ones
+    // past after. None if there is no such group, else the entries and whether the consumer was
+    // created.
+    fn deliver(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        after: Option<StreamId>,
+        count: Option<usize>,
+        noack: bool,
+    ) -> Option<(GroupEntries, bool)> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        let created = group.see_consumer(consumer, now);
+        let owner = group.consumers.entry(consumer.to_string()).or_default();
+        let count = count.unwrap_or(usize::MAX);
+
+        let entries = match after {
+            None => {
+                let Some(start) = group.last_delivered.next() else {
+                    return Some((Vec::new(), created));
+                };
+                let delivered: Vec<_> = self
+                    .entries
+                    .range(start..)
+                    .take(count)
+                    .map(|(id, fields)| (*id, Some(fields.clone())))
+                    .collect();
+                if let Some((last, _)) = delivered.last() {
+                    group.last_delivered = *last;
+                }
+                if !noack {
+                    for (id, _) in &delivered {
+                        owner.pending.insert(*id);
+                        let entry = PendingEntry {
+                            consumer: consumer.to_string(),
+                            delivered_at: now,
+                            deliveries: 1,
+                        };
+                        group.pending.insert(*id, entry);
+                    }
+                }
+                delivered
+            }
+            Some(after) => match after.next() {
+                Some(start) => owner
+                    .pending
+                    .range(start..)
+                    .take(count)
+                    .map(|id| (*id, self.entries.get(id).cloned()))
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        Some((entries, created))
+    }
+
+    // give consumer of group the pending entries ids idle for min_idle, see `Backend::xclaim`.
+    // None if there is no such group, else the claimed entries and whether the consumer was
+    // created.
+    fn claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: i64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> Option<(Vec<(StreamId, StreamFields)>, bool)> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        let created = group.see_consumer(consumer, now);
+        if let Some(last_id) = options.last_id {
+            group.last_delivered = group.last_delivered.max(last_id);
+        }
+
+        let mut claimed = Vec::new();
+        for id in ids {
+            let Some(fields) = self.entries.get(id) else {
+                // deleted from the stream, there is nothing left to deliver
+                group.ack(*id);
+                continue;
+            };
+            match group.pending.get(id) {
+                Some(entry) if now - entry.delivered_at < min_idle => continue,
+                Some(_) => {}
+                None if options.force => {}
+                None => continue,
+            }
+            let entry = group.transfer(*id, consumer);
+            entry.delivered_at = options.delivered_at.unwrap_or(now);
+            match options.retry_count {
+                Some(retry_count) => entry.deliveries = retry_count,
+                None if !options.justid => entry.deliveries += 1,
+                None => {}
+            }
+            claimed.push((*id, fields.clone()));
+        }
+        Some((claimed, created))
+    }
+
+    // scan the pending entries of group from start, giving consumer up to count of them idle for
+    // min_idle. None if there is no such group, else what was claimed and whether the consumer
+    // was created.
+    fn autoclaim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: i64,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> Option<(AutoClaimed, bool)> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        let created = group.see_consumer(consumer, now);
+
+        // bound the work of a call when few entries are idle enough
+        let mut attempts = count.saturating_mul(10);
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        let mut eligible = Vec::new();
+        let mut next = StreamId::MIN;
+        for (id, entry) in group.pending.range(start..) {
+            if attempts == 0 || eligible.len() + deleted.len() == count {
+                next = *id;
+                break;
+            }
+            attempts -= 1;
+            if !self.entries.contains_key(id) {
+                deleted.push(*id);
+            } else if now - entry.delivered_at >= min_idle {
+                eligible.push(*id);
+            }
+        }
+
+        for id in &deleted {
+            group.ack(*id);
+        }
+        for id in eligible {
+            let entry = group.transfer(id, consumer);
+            entry.delivered_at = now;
+            if !justid {
+                entry.deliveries += 1;
+            }
+            claimed.push((id, self.entries[&id].clone()));
+        }
+        let claimed = AutoClaimed {
+            next,
+            claimed,
+            deleted,
+        };
+        Some((claimed, created))
+    }
+
+    fn resolve(&self, id: XAddId) -> Result<StreamId, BackendError> {
+        let last = self.last_id;
+        let id = match id {
+            XAddId::Auto => {
+                let ms = now_ms().max(0) as u64;
+                if ms > last.ms {
+                    StreamId::new(ms, 0)
+                } else {
+                    last.next().ok_or(BackendError::StreamExhausted)?
+                }
+            }
+            XAddId::AutoSeq(ms) if ms == last.ms => {
+                let seq = last.seq.checked_add(1);
+                StreamId::new(ms, seq.ok_or(BackendError::StreamIdTooSmall)?)
+            }
+            // 0-0 is never a valid ID
+            XAddId::AutoSeq(ms) => StreamId::new(ms, if ms == 0 { 1 } else { 0 }),
+            XAddId::Explicit(id) => id,
+        };
+        if id == StreamId::MIN {
+            return Err(BackendError::StreamIdZero);
+        }
+        if id <= last {
+            return Err(BackendError::StreamIdTooSmall);
+        }
+        Ok(id)
+    }
+}
+
+impl ConsumerGroup {
+    fn new(last_delivered: StreamId) -> Self {
+        Self {
+            last_delivered,
+            ..Default::default()
+        }
+    }
+
+    /// The ID of the last entry delivered to a consumer of the group.
+    pub fn last_delivered(&self) -> StreamId {
+        self.last_delivered
+    }
+
+    /// The pending entries of every consumer, by ID.
+    pub fn pending(&self) -> &BTreeMap<StreamId, PendingEntry> {
+        &self.pending
+    }
+
+    /// The summary of the pending entries.
+    pub fn pending_summary(&self) -> PendingSummary {
+        let first = self.pending.keys().next();
+        let last = self.pending.keys().next_back();
+        let consumers = self
+            .consumers
+            .iter()
+            .filter(|(_, consumer)| !consumer.pending.is_empty())
+            .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+            .collect();
+        PendingSummary {
+            count: self.pending.len(),
+            bounds: first.zip(last).map(|(first, last)| (*first, *last)),
+            consumers,
+        }
+    }
+
+    /// The pending entries that pass filter, in ID order.
+    pub fn pending_range(&self, filter: &PendingFilter) -> Vec<(StreamId, PendingEntry)> {
+        if filter.start > filter.end {
+            return Vec::new();
+        }
+        let now = now_ms();
+        self.pending
+            .range(filter.start..=filter.end)
+            .filter(|(_, entry)| now - entry.delivered_at >= filter.min_idle)
+            .filter(|(_, entry)| {
+                filter
+                    .consumer
+                    .as_ref()
+                    .is_none_or(|consumer| entry.consumer == *consumer)
+            })
+            .take(filter.count)
+            .map(|(id, entry)| (*id, entry.clone()))
+            .collect()
+    }
+
+    /// The consumers of the group, with the unix time in milliseconds each was last seen at.
+    pub fn consumers(&self) -> impl Iterator<Item = (&String, i64)> {
+        self.consumers
+            .iter()
+            .map(|(name, consumer)| (name, consumer.seen_at))
+    }
+
+    /// The summary of every consumer XINFO CONSUMERS reports, by name.
+    pub fn consumers_info(&self) -> Vec<ConsumerInfo> {
+        let now = now_ms();
+        self.consumers
+            .iter()
+            .map(|(name, consumer)| ConsumerInfo {
+                name: name.clone(),
+                pending: consumer.pending.len(),
+                idle: (now - consumer.seen_at).max(0),
+            })
+            .collect()
+    }
+
+    // the group of a saved stream, before its consumers and pending entries are put back
+    pub(crate) fn restore(last_delivered: StreamId) -> Self {
+        Self::new(last_delivered)
+    }
+
+    pub(crate) fn restore_consumer(&mut self, consumer: &str, seen_at: i64) {
+        self.see_consumer(consumer, seen_at);
+    }
+
+    pub(crate) fn restore_pending(&mut self, id: StreamId, entry: PendingEntry) {
+        let consumer = entry.consumer.clone();
+        *self.transfer(id, &consumer) = entry;
+    }
+
+    // record that consumer was seen at now, returns whether it was created
+    fn see_consumer(&mut self, consumer: &str, now: i64) -> bool {
+        let created = !self.consumers.contains_key(consumer);
+        self.consumers
+            .entry(consumer.to_string())
+            .or_default()
+            .seen_at = now;
+        created
+    }
+
+    // make consumer the owner of the entry id, pending it if it wasn't
+    fn transfer(&mut self, id: StreamId, consumer: &str) -> &mut PendingEntry {
+        let entry = self.pending.entry(id).or_insert_with(|| PendingEntry {
+            consumer: consumer.to_string(),
+            delivered_at: 0,
+            deliveries: 0,
+        });
+        if entry.consumer != consumer {
+            if let Some(owner) = self.consumers.get_mut(&entry.consumer) {
+                owner.pending.remove(&id);
+            }
+            entry.consumer = consumer.to_string();
+        }
+        let owner = self.consumers.entry(consumer.to_string()).or_default();
+        owner.pending.insert(id);
+        entry
+    }
+
+    // delete consumer with its pending entries, returns how many it had, None if there was no
+    // such consumer
+    fn delete_consumer(&mut self, consumer: &str) -> Option<usize> {
+        let removed = self.consumers.remove(consumer)?;
+        for id in &removed.pending {
+            self.pending.remove(id);
+        }
+        Some(removed.pending.len())
+    }
+
+    pub(crate) fn set_last_delivered(&mut self, id: StreamId) {
+        self.last_delivered = id;
+    }
+
+    // acknowledge the pending entry id, returns whether it was pending
+    pub(crate) fn ack(&mut self, id: StreamId) -> bool {
+        let Some(entry) = self.pending.remove(&id) else {
+            return false;
+        };
+        if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+            consumer.pending.remove(&id);
+        }
+        true
+    }
+}
+
+impl Backend {
+    /// Add an entry to the stream at key, creating it unless nomkstream. Returns the ID of the
+    /// entry, None if the key doesn't exist and nomkstream.
+    pub fn xadd(
+        &self,
+        key: &str,
+        id: XAddId,
+        fields: StreamFields,
+        nomkstream: bool,
+    ) -> Result<Option<StreamId>, BackendError> {
+        let options = XAddOptions {
+            nomkstream,
+            trim: None,
+        };
+        self.xadd_with(key, id, fields, &options)
+    }
+
+    /// `xadd` with options, trimming the stream after adding the entry if asked to.
+    pub fn xadd_with(
+        &self,
+        key: &str,
+        id: XAddId,
+        fields: StreamFields,
+        options: &XAddOptions,
+    ) -> Result<Option<StreamId>, BackendError> {
+        let mut shard = self.write(key);
+        let created = match shard.get(key).map(|obj| &**obj) {
+            None if options.nomkstream => return Ok(None),
+            None => true,
+            Some(Value::Stream(_)) => false,
+            Some(_) => return Err(BackendError::WrongType),
+        };
+
+        let id = if created {
+            let mut stream = Stream::new();
+            let id = stream.add(id, fields)?;
+            shard.insert(key.to_string(), Value::Stream(stream));
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+            id
+        } else {
+            match shard.get_mut(key).map(|obj| {
+                obj.touch();
+                &mut **obj
+            }) {
+                Some(Value::Stream(stream)) => stream.add(id, fields)?,
+                _ => unreachable!("checked above"),
+            }
+        };
+        self.notify_keyspace_event(NotifyFlags::STREAM, "xadd", key);
+        if let Some(trim) = &options.trim {
+            let trimmed = match shard.get_mut(key).map(|obj| &mut **obj) {
+                Some(Value::Stream(stream)) => stream.trim(trim),
+                _ => 0,
+            };
+            if trimmed > 0 {
+                self.notify_keyspace_event(NotifyFlags::STREAM, "xtrim", key);
+            }
+        }
+        self.serve_blocked(&mut shard, key);
+        Ok(Some(id))
+    }
+
+    /// Trim the stream at key, returns how many entries were removed.
+    pub fn xtrim(&self, key: &str, trim: &StreamTrim) -> Result<usize, BackendError> {
+        let mut shard = self.write(key);
+        let trimmed = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Ok(0),
+            Some(Value::Stream(stream)) => stream.trim(trim),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        if trimmed > 0 {
+            self.notify_keyspace_event(NotifyFlags::STREAM, "xtrim", key);
+        }
+        Ok(trimmed)
+    }
+
+    /// Delete the entries ids of the stream at key, returns how many there were.
+    pub fn xdel(&self, key: &str, ids: &[StreamId]) -> Result<usize, BackendError> {
+        let mut shard = self.write(key);
+        let deleted = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Ok(0),
+            Some(Value::Stream(stream)) => ids.iter().filter(|id| stream.remove(**id)).count(),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        if deleted > 0 {
+            self.notify_keyspace_event(NotifyFlags::STREAM, "xdel", key);
+        }
+        Ok(deleted)
+    }
+
+    pub fn xlen(&self, key: &str) -> Result<usize, BackendError> {
+        Ok(self.read_stream(key, |stream| stream.len())?.unwrap_or(0))
+    }
+
+    /// Up to count entries with IDs between start and end included, from end down if rev.
+    pub fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        rev: bool,
+        count: Option<usize>,
+    ) -> Result<Vec<(StreamId, StreamFields)>, BackendError> {
+        Ok(self
+            .read_stream(key, |stream| {
+                stream
+                    .range(start, end, rev)
+                    .take(count.unwrap_or(usize::MAX))
+                    .map(|(id, fields)| (*id, fields.clone()))
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// The greatest ID ever added to the stream at key, what `$` stands for in XREAD. 0-0 if
+    /// the key doesn't exist.
+    pub fn xlast_id(&self, key: &str) -> Result<StreamId, BackendError> {
+        Ok(self
+            .read_stream(key, |stream| stream.last_id())?
+            .unwrap_or_default())
+    }
+
+    /// Up to count entries of each stream at keys with IDs greater than the matching ID in
+    /// after.
+    pub fn xread(
+        &self,
+        keys: &[String],
+        after: &[StreamId],
+        count: Option<usize>,
+    ) -> Result<StreamReads, BackendError> {
+        let guard = self.read_many(keys.iter().map(String::as_str));
+        read_after(keys, after, count, |key| {
+            let shard = guard.shard(key);
+            if shard.is_expired(key) {
+                return None;
+            }
+            shard.get(key).map(|obj| {
+                obj.touch();
+                &**obj
+            })
+        })
+    }
+
+    /// `xread`, queueing the client on every key when there is nothing new to read,
+    /// atomically with the check so no entry can be missed.
+    pub fn xread_or_block(
+        &self,
+        keys: &[String],
+        after: &[StreamId],
+        count: Option<usize>,
+    ) -> Result<BlockingRead, BackendError> {
+        let guard = self.write_many(keys.iter().map(String::as_str));
+        let reads = read_after(keys, after, count, |key| {
+            guard.shard_ref(key).get(key).map(|obj| &**obj)
+        })?;
+        if !reads.is_empty() {
+            return Ok(BlockingRead::Ready(reads));
+        }
+        Ok(BlockingRead::Blocked(self.block_on(
+            keys,
+            PopFrom::Stream,
+            0,
+        )))
+    }
+
+    /// Create group on the stream at key, delivering the entries after id, the last one if
+    /// None. A missing key is created as an empty stream with mkstream.
+    pub fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        mkstream: bool,
+    ) -> Result<(), BackendError> {
+        let mut shard = self.write(key);
+        if !shard.contains_key(key) {
+            if !mkstream {
+                return Err(BackendError::XGroupNoKey);
+            }
+            shard.insert(key.to_string(), Value::Stream(Stream::new()));
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+        }
+        match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::Stream(stream)) => {
+                if stream.groups.contains_key(group) {
+                    return Err(BackendError::BusyGroup);
+                }
+                let last_delivered = id.unwrap_or(stream.last_id);
+                let created = ConsumerGroup::new(last_delivered);
+                stream.groups.insert(group.to_string(), created);
+            }
+            _ => return Err(BackendError::WrongType),
+        }
+        self.notify_keyspace_event(NotifyFlags::STREAM, "xgroup-create", key);
+        Ok(())
+    }
+
+    /// Destroy group, returns whether it existed. Clients blocked reading it are woken to
+    /// find out.
+    pub fn xgroup_destroy(&self, key: &str, group: &str) -> Result<bool, BackendError> {
+        let mut shard = self.write(key);
+        let destroyed = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Err(BackendError::XGroupNoKey),
+            Some(Value::Stream(stream)) => stream.groups.remove(group).is_some(),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        if destroyed {
+            self.notify_keyspace_event(NotifyFlags::STREAM, "xgroup-destroy", key);
+            self.serve_blocked(&mut shard, key);
+        }
+        Ok(destroyed)
+    }
+
+    /// Set the greatest ID ever added to the stream at key, which can't be under the ID of an
+    /// entry of the stream.
+    pub fn xsetid(&self, key: &str, id: StreamId) -> Result<(), BackendError> {
+        let mut shard = self.write(key);
+        match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Err(BackendError::NoSuchKey),
+            Some(Value::Stream(stream)) => {
+                if stream
+                    .entries
+                    .last_key_value()
+                    .is_some_and(|(top, _)| id < *top)
+                {
+                    return Err(BackendError::XSetIdTooSmall);
+                }
+                stream.last_id = id;
+            }
+            Some(_) => return Err(BackendError::WrongType),
+        }
+        self.notify_keyspace_event(NotifyFlags::STREAM, "xsetid", key);
+        Ok(())
+    }
+
+    /// Set the last entry delivered to group, the last one of the stream if id is None.
+    pub fn xgroup_setid(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+    ) -> Result<(), BackendError> {
+        self.write_group(key, group, "xgroup-setid", |last_id, group| {
+            group.last_delivered = id.unwrap_or(last_id);
+            true
+        })
+    }
+
+    /// Create consumer in group, returns whether it didn't exist yet.
+    pub fn xgroup_createconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> Result<bool, BackendError> {
+        let mut created = false;
+        self.write_group(key, group, "xgroup-createconsumer", |_, group| {
+            created = !group.consumers.contains_key(consumer);
+            if created {
+                group.see_consumer(consumer, now_ms());
+            }
+            created
+        })?;
+        Ok(created)
+    }
+
+    /// Delete consumer from group along with its pending entries, returns how many it had.
+    pub fn xgroup_delconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> Result<usize, BackendError> {
+        let mut deleted = None;
+        self.write_group(key, group, "xgroup-delconsumer", |_, group| {
+            deleted = group.delete_consumer(consumer);
+            deleted.is_some()
+        })?;
+        Ok(deleted.unwrap_or(0))
+    }
+
+    /// Deliver entries of the streams at keys to consumer of group, creating the consumer if
+    /// needed. A None ID reads the entries never delivered to the group, adding them to the
+    /// consumer's pending entries unless noack. An ID reads the consumer's pending entries
+    /// after it.
+    pub fn xreadgroup(
+        &self,
+        group: &str,
+        consumer: &str,
+        keys: &[String],
+        ids: &[Option<StreamId>],
+        count: Option<usize>,
+        noack: bool,
+    ) -> Result<GroupReads, BackendError> {
+        let mut guard = self.write_many(keys.iter().map(String::as_str));
+        self.deliver_many(&mut guard, group, consumer, keys, ids, count, noack)
+    }
+
+    /// `xreadgroup`, queueing the client on every key when no entry was delivered,
+    /// atomically with the read so no entry can be missed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn xreadgroup_or_block(
+        &self,
+        group: &str,
+        consumer: &str,
+        keys: &[String],
+        ids: &[Option<StreamId>],
+        count: Option<usize>,
+        noack: bool,
+    ) -> Result<BlockingRead<GroupReads>, BackendError> {
+        let mut guard = self.write_many(keys.iter().map(String::as_str));
+        let reads = self.deliver_many(&mut guard, group, consumer, keys, ids, count, noack)?;
+        if reads.iter().any(|(_, entries)| !entries.is_empty()) {
+            return Ok(BlockingRead::Ready(reads));
+        }
+        Ok(BlockingRead::Blocked(self.block_on(
+            keys,
+            PopFrom::Stream,
+            0,
+        )))
+    }
+
+    /// Acknowledge the pending entries ids of group, returns how many were pending.
+    pub fn xack(&self, key: &str, group: &str, ids: &[StreamId]) -> Result<usize, BackendError> {
+        let mut shard = self.write(key);
+        match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => Ok(0),
+            Some(Value::Stream(stream)) => Ok(match stream.groups.get_mut(group) {
+                Some(group) => ids.iter().filter(|id| group.ack(**id)).count(),
+                None => 0,
+            }),
+            Some(_) => Err(BackendError::WrongType),
+        }
+    }
+
+    /// The summary of the pending entries of group.
+    pub fn xpending(&self, key: &str, group: &str) -> Result<PendingSummary, BackendError> {
+        self.read_group(key, group, ConsumerGroup::pending_summary)
+    }
+
+    /// The pending entries of group that pass filter.
+    pub fn xpending_range(
+        &self,
+        key: &str,
+        group: &str,
+        filter: &PendingFilter,
+    ) -> Result<Vec<(StreamId, PendingEntry)>, BackendError> {
+        self.read_group(key, group, |group| group.pending_range(filter))
+    }
+
+    /// Give consumer the pending entries ids of group that weren't delivered for min_idle
+    /// milliseconds, returning them. Pending entries deleted from the stream are dropped.
+    pub fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: i64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> Result<Vec<(StreamId, StreamFields)>, BackendError> {
+        let mut shard = self.write(key);
+        let (claimed, created) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::Stream(stream)) => stream.claim(group, consumer, min_idle, ids, options),
+            Some(_) => return Err(BackendError::WrongType),
+            None => None,
+        }
+        .ok_or_else(|| BackendError::NoGroup(key.to_string(), group.to_string()))?;
+        if created {
+            self.notify_keyspace_event(NotifyFlags::STREAM, "xgroup-createconsumer", key);
+        }
+        Ok(claimed)
+    }
+
+    /// `xclaim` the first count pending entries of group from start that weren't delivered for
+    /// min_idle milliseconds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn xautoclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: i64,
+        start: StreamId,
+        count: usize,
+        justid: bool,
+    ) -> Result<AutoClaimed, BackendError> {
+        let mut shard = self.write(key);
+        let (claimed, created) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::Stream(stream)) => {
+                stream.autoclaim(group, consumer, min_idle, start, count, justid)
+            }
+            Some(_) => return Err(BackendError::WrongType),
+            None => None,
+        }
+        .ok_or_else(|| BackendError::NoGroup(key.to_string(), group.to_string()))?;
+        if created {
+            self.notify_keyspace_event(NotifyFlags::STREAM, "xgroup-createconsumer", key);
+        }
+        Ok(claimed)
+    }
+
+    /// The summary of the stream at key.
+    pub fn xinfo_stream(&self, key: &str) -> Result<StreamInfo, BackendError> {
+        self.read_stream(key, Stream::info)?
+            .ok_or(BackendError::NoSuchKey)
+    }
+
+    /// The summary of every group of the stream at key.
+    pub fn xinfo_groups(&self, key: &str) -> Result<Vec<GroupInfo>, BackendError> {
+        self.read_stream(key, Stream::groups_info)?
+            .ok_or(BackendError::NoSuchKey)
+    }
+
+    /// The summary of every consumer of group.
+    pub fn xinfo_consumers(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> Result<Vec<ConsumerInfo>, BackendError> {
+        self.read_stream(key, |stream| {
+            stream.group(group).map(ConsumerGroup::consumers_info)
+        })?
+        .ok_or(BackendError::NoSuchKey)?
+        .ok_or_else(|| BackendError::NoGroup(key.to_string(), group.to_string()))
+    }
+
+    // change group of the stream at key with f, given the last ID of the stream, notifying
+    // event if f returns true
+    fn write_group(
+        &self,
+        key: &str,
+        group: &str,
+        event: &str,
+        f: impl FnOnce(StreamId, &mut ConsumerGroup) -> bool,
+    ) -> Result<(), BackendError> {
+        let mut shard = self.write(key);
+        let changed = match shard.get_mut(key).map(|obj| &mut **obj) {
+            None => return Err(BackendError::XGroupNoKey),
+            Some(Value::Stream(stream)) => {
+                let last_id = stream.last_id;
+                let found = stream.groups.get_mut(group).map(|group| f(last_id, group));
+                found.ok_or_else(|| BackendError::NoGroup(key.to_string(), group.to_string()))?
+            }
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        if changed {
+            self.notify_keyspace_event(NotifyFlags::STREAM, event, key);
+        }
+        Ok(())
+    }
+
+    // run f on group of the stream at key
+    fn read_group<R>(
+        &self,
+        key: &str,
+        group: &str,
+        f: impl FnOnce(&ConsumerGroup) -> R,
+    ) -> Result<R, BackendError> {
+        self.read_stream(key, |stream| stream.group(group).map(f))?
+            .flatten()
+            .ok_or_else(|| BackendError::NoGroup(key.to_string(), group.to_string()))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn deliver_many(
+        &self,
+        guard: &mut ShardsWriteGuard,
+        group: &str,
+        consumer: &str,
+        keys: &[String],
+        ids: &[Option<StreamId>],
+        count: Option<usize>,
+        noack: bool,
+    ) -> Result<GroupReads, BackendError> {
+        // every stream must have the group before any entry is delivered
+        for key in keys {
+            match guard.shard_ref(key).get(key).map(|obj| &**obj) {
+                Some(Value::Stream(stream)) if stream.groups.contains_key(group) => {}
+                Some(Value::Stream(_)) | None => {
+                    return Err(BackendError::NoGroup(key.clone(), group.to_string()))
+                }
+                Some(_) => return Err(BackendError::WrongType),
+            }
+        }
+
+        let mut reads = Vec::with_capacity(keys.len());
+        for (key, after) in keys.iter().zip(ids) {
+            let delivered = match guard.shard(key).get_mut(key).map(|obj| &mut **obj) {
+                Some(Value::Stream(stream)) => {
+                    stream.deliver(group, consumer, *after, count, noack)
+                }
+                _ => None,
+            };
+            let Some((entries, created)) = delivered else {
+                continue;
+            };
+            if created {
+                self.notify_keyspace_event(NotifyFlags::STREAM, "xgroup-createconsumer", key);
+            }
+            // a history read replies for every stream, a read of new entries only for those
+            // that had some
+            if after.is_some() || !entries.is_empty() {
+                reads.push((key.clone(), entries));
+            }
+        }
+        Ok(reads)
+    }
+
+    // run f on the stream at key, None if the key doesn't exist
+    pub(crate) fn read_stream<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&Stream) -> R,
+    ) -> Result<Option<R>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::Stream(stream) => Ok(Some(f(stream))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+}
+
+fn read_after<'a>(
+    keys: &[String],
+    after: &[StreamId],
+    count: Option<usize>,
+    lookup: impl Fn(&str) -> Option<&'a Value>,
+) -> Result<StreamReads, BackendError> {
+    let mut reads = Vec::new();
+    for (key, after) in keys.iter().zip(after) {
+        let stream = match lookup(key) {
+            None => continue,
+            Some(Value::Stream(stream)) => stream,
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        let Some(start) = after.next() else {
+            continue;
+        };
+        let entries: Vec<_> = stream
+            .range(start, StreamId::MAX, false)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect();
+        if !entries.is_empty() {
+            reads.push((key.clone(), entries));
+        }
+    }
+    Ok(reads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    fn fields(pairs: &[(&str, &str)]) -> StreamFields {
+        pairs
+            .iter()
+            .map(|(field, value)| (field.to_string(), BulkString::from(*value).into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_stream_ids() {
+        let mut stream = Stream::new();
+        assert_eq!(
+            stream.add(XAddId::Explicit(StreamId::MIN), fields(&[("f", "v")])),
+            Err(BackendError::StreamIdZero)
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(0), vec![]),
+            Ok(StreamId::new(0, 1))
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(0), vec![]),
+            Ok(StreamId::new(0, 2))
+        );
+        assert_eq!(
+            stream.add(XAddId::Explicit(StreamId::new(0, 2)), vec![]),
+            Err(BackendError::StreamIdTooSmall)
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(5), vec![]),
+            Ok(StreamId::new(5, 0))
+        );
+
+        // an auto ID is never smaller than the last one, even ahead of the clock
+        let ahead = StreamId::new(u64::MAX, 7);
+        assert_eq!(stream.add(XAddId::Explicit(ahead), vec![]), Ok(ahead));
+        assert_eq!(
+            stream.add(XAddId::Auto, vec![]),
+            Ok(StreamId::new(u64::MAX, 8))
+        );
+        assert_eq!(stream.len(), 5);
+
+        assert_eq!(StreamId::new(1, u64::MAX).next(), Some(StreamId::new(2, 0)));
+        assert_eq!(StreamId::new(2, 0).prev(), Some(StreamId::new(1, u64::MAX)));
+        assert_eq!(StreamId::MIN.prev(), None);
+        assert_eq!(StreamId::new(1, 2).to_string(), "1-2");
+    }
+
+    #[test]
+    fn test_xadd_xrange() {
+        let backend = Backend::new();
+        for ms in 1..=3 {
+            let id = XAddId::Explicit(StreamId::new(ms, 0));
+            backend.xadd("s", id, fields(&[("n", "v")]), false).unwrap();
+        }
+        assert_eq!(backend.xlen("s"), Ok(3));
+        assert_eq!(backend.xlen("missing"), Ok(0));
+        assert_eq!(
+            backend.xadd("missing", XAddId::Auto, fields(&[("n", "v")]), true),
+            Ok(None)
+        );
+        assert!(!backend.contains_key("missing"));
+
+        let ids = |entries: Vec<(StreamId, StreamFields)>| {
+            entries.into_iter().map(|(id, _)| id.ms).collect::<Vec<_>>()
+        };
+        let range = backend.xrange("s", StreamId::new(2, 0), StreamId::MAX, false, None);
+        assert_eq!(range.map(ids), Ok(vec![2, 3]));
+        let range = backend.xrange("s", StreamId::MIN, StreamId::MAX, true, Some(2));
+        assert_eq!(range.map(ids), Ok(vec![3, 2]));
+        let range = backend.xrange("s", StreamId::MAX, StreamId::MIN, false, None);
+        assert_eq!(range.map(ids), Ok(vec![]));
+
+        backend.set("str".to_string(), BulkString::from("v").into());
+        assert_eq!(backend.xlen("str"), Err(BackendError::WrongType));
+    }
+
+    #[tokio::test]
+    async fn test_xread_or_block() {
+        let backend = Backend::new();
+        let keys = ["s1".to_string(), "s2".to_string()];
+        let id = |ms| XAddId::Explicit(StreamId::new(ms, 0));
+        backend
+            .xadd("s1", id(1), fields(&[("f", "v")]), false)
+            .unwrap();
+
+        let after = [StreamId::MIN, StreamId::MIN];
+        let reads = backend.xread(&keys, &after, None).unwrap();
+        assert_eq!(reads.len(), 1);
+        assert_eq!(reads[0].0, "s1");
+
+        // nothing after the last IDs: block until an entry is added
+        let after = [
+            backend.xlast_id("s1").unwrap(),
+            backend.xlast_id("s2").unwrap(),
+        ];
+        let mut blocked = match backend.xread_or_block(&keys, &after, None) {
+            Ok(BlockingRead::Blocked(blocked)) => blocked,
+            other => panic!("expected a blocked read, got {:?}", other),
+        };
+        backend
+            .xadd("s2", id(2), fields(&[("f", "v")]), false)
+            .unwrap();
+        assert_eq!(blocked.wait().await, Some(("s2".to_string(), vec![])));
+        backend.unblock(blocked);
+
+        match backend.xread_or_block(&keys, &after, Some(1)) {
+            Ok(BlockingRead::Ready(reads)) => {
+                assert_eq!(reads[0].0, "s2");
+                assert_eq!(reads[0].1[0].0, StreamId::new(2, 0));
+            }
+            other => panic!("expected a ready read, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_consumer_groups() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.xgroup_create("s", "g", None, false),
+            Err(BackendError::XGroupNoKey)
+        );
+        backend.xgroup_create("s", "g", None, true).unwrap();
+        assert_eq!(
+            backend.xgroup_create("s", "g", None, false),
+            Err(BackendError::BusyGroup)
+        );
+        for ms in 1..=3 {
+            let id = XAddId::Explicit(StreamId::new(ms, 0));
+            backend.xadd("s", id, fields(&[("n", "v")]), false).unwrap();
+        }
+
+        let keys = ["s".to_string()];
+        let ids = |reads: GroupReads| {
+            reads
+                .into_iter()
+                .flat_map(|(_, entries)| entries)
+                .map(|(id, fields)| (id.ms, fields.is_some()))
+                .collect::<Vec<_>>()
+        };
+        let read = backend.xreadgroup("g", "alice", &keys, &[None], Some(2), false);
+        assert_eq!(read.map(ids), Ok(vec![(1, true), (2, true)]));
+        let read = backend.xreadgroup("g", "bob", &keys, &[None], None, false);
+        assert_eq!(read.map(ids), Ok(vec![(3, true)]));
+        // nothing new
+        assert_eq!(
+            backend.xreadgroup("g", "bob", &keys, &[None], None, false),
+            Ok(vec![])
+        );
+
+        // the history of alice, an acknowledged entry leaving it
+        assert_eq!(backend.xack("s", "g", &[StreamId::new(1, 0)]), Ok(1));
+        assert_eq!(backend.xack("s", "g", &[StreamId::new(1, 0)]), Ok(0));
+        let read = backend.xreadgroup("g", "alice", &keys, &[Some(StreamId::MIN)], None, false);
+        assert_eq!(read.map(ids), Ok(vec![(2, true)]));
+        let read = backend.xreadgroup("g", "carol", &keys, &[Some(StreamId::MIN)], None, false);
+        assert_eq!(read, Ok(vec![("s".to_string(), vec![])]));
+
+        let group = |backend: &Backend| {
+            backend
+                .read_stream("s", |stream| stream.group("g").cloned())
+                .unwrap()
+                .flatten()
+                .unwrap()
+        };
+        assert_eq!(group(&backend).last_delivered(), StreamId::new(3, 0));
+        assert_eq!(group(&backend).pending().len(), 2);
+        assert_eq!(
+            group(&backend).pending()[&StreamId::new(3, 0)].consumer,
+            "bob"
+        );
+
+        backend
+            .xadd("s", XAddId::Auto, fields(&[("n", "v")]), false)
+            .unwrap();
+        let info = backend.xinfo_stream("s").unwrap();
+        assert_eq!((info.length, info.groups), (4, 1));
+        assert_eq!(info.first.map(|(id, _)| id), Some(StreamId::new(1, 0)));
+        assert_eq!(info.last.map(|(id, _)| id), Some(info.last_generated));
+        let groups = backend.xinfo_groups("s").unwrap();
+        assert_eq!(
+            groups,
+            vec![GroupInfo {
+                name: "g".to_string(),
+                consumers: 3,
+                pending: 2,
+                last_delivered: StreamId::new(3, 0),
+                lag: 1,
+            }]
+        );
+        let consumers = backend.xinfo_consumers("s", "g").unwrap();
+        let pending: Vec<_> = consumers
+            .iter()
+            .map(|consumer| (consumer.name.as_str(), consumer.pending))
+            .collect();
+        assert_eq!(pending, vec![("alice", 1), ("bob", 1), ("carol", 0)]);
+        assert_eq!(backend.xinfo_stream("x"), Err(BackendError::NoSuchKey));
+        assert_eq!(
+            backend.xinfo_consumers("s", "nope"),
+            Err(BackendError::NoGroup("s".to_string(), "nope".to_string()))
+        );
+
+        assert_eq!(
+            backend.xreadgroup("nope", "alice", &keys, &[None], None, false),
+            Err(BackendError::NoGroup("s".to_string(), "nope".to_string()))
+        );
+        // repositioning, and the consumers made and deleted by hand
+        assert_eq!(backend.xgroup_setid("s", "g", Some(StreamId::MIN)), Ok(()));
+        assert_eq!(group(&backend).last_delivered(), StreamId::MIN);
+        assert_eq!(backend.xgroup_setid("s", "g", None), Ok(()));
+        assert_eq!(group(&backend).last_delivered(), info.last_generated);
+        assert_eq!(backend.xgroup_createconsumer("s", "g", "dave"), Ok(true));
+        assert_eq!(backend.xgroup_createconsumer("s", "g", "dave"), Ok(false));
+        assert_eq!(backend.xgroup_delconsumer("s", "g", "alice"), Ok(1));
+        assert_eq!(backend.xgroup_delconsumer("s", "g", "alice"), Ok(0));
+        assert_eq!(group(&backend).pending().len(), 1);
+        assert_eq!(group(&backend).consumers().count(), 3);
+        assert_eq!(
+            backend.xgroup_createconsumer("x", "g", "dave"),
+            Err(BackendError::XGroupNoKey)
+        );
+        assert_eq!(
+            backend.xgroup_setid("s", "nope", None),
+            Err(BackendError::NoGroup("s".to_string(), "nope".to_string()))
+        );
+
+        assert_eq!(backend.xgroup_destroy("s", "g"), Ok(true));
+        assert_eq!(backend.xgroup_destroy("s", "g"), Ok(false));
+    }
+
+    #[test]
+    fn test_claim_pending_entries() {
+        let backend = Backend::new();
+        backend.xgroup_create("s", "g", None, true).unwrap();
+        for ms in 1..=3 {
+            let id = XAddId::Explicit(StreamId::new(ms, 0));
+            backend.xadd("s", id, fields(&[("n", "v")]), false).unwrap();
+        }
+        let keys = ["s".to_string()];
+        backend
+            .xreadgroup("g", "alice", &keys, &[None], None, false)
+            .unwrap();
+
+        let summary = backend.xpending("s", "g").unwrap();
+        assert_eq!(summary.count, 3);
+        assert_eq!(
+            summary.bounds,
+            Some((StreamId::new(1, 0), StreamId::new(3, 0)))
+        );
+        assert_eq!(summary.consumers, vec![("alice".to_string(), 3)]);
+
+        // claimed by bob, counting as a delivery
+        let ids = [StreamId::new(1, 0), StreamId::new(9, 0)];
+        let claimed = backend.xclaim("s", "g", "bob", 0, &ids, &ClaimOptions::default());
+        assert_eq!(claimed.unwrap().len(), 1);
+        let filter = PendingFilter {
+            min_idle: 0,
+            start: StreamId::MIN,
+            end: StreamId::MAX,
+            count: 10,
+            consumer: Some("bob".to_string()),
+        };
+        let pending = backend.xpending_range("s", "g", &filter).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.deliveries, 2);
+
+        // an entry deleted from the stream is dropped from the group
+        let claim = ClaimOptions {
+            force: true,
+            retry_count: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(backend.xdel("s", &[StreamId::new(2, 0)]), Ok(1));
+        let claimed = backend.xautoclaim("s", "g", "carol", 0, StreamId::MIN, 1, false);
+        let claimed = claimed.unwrap();
+        assert_eq!(claimed.next, StreamId::new(2, 0));
+        assert_eq!(claimed.claimed[0].0, StreamId::new(1, 0));
+        let claimed = backend.xautoclaim("s", "g", "carol", 0, claimed.next, 1, false);
+        let claimed = claimed.unwrap();
+        assert_eq!(claimed.deleted, vec![StreamId::new(2, 0)]);
+        assert_eq!(claimed.next, StreamId::new(3, 0));
+        assert_eq!(backend.xpending("s", "g").unwrap().count, 2);
+
+        // FORCE claims an entry that isn't pending
+        backend.xack("s", "g", &[StreamId::new(3, 0)]).unwrap();
+        let claimed = backend.xclaim("s", "g", "dave", 0, &[StreamId::new(3, 0)], &claim);
+        assert_eq!(claimed.unwrap().len(), 1);
+        let pending = backend.xpending_range(
+            "s",
+            "g",
+            &PendingFilter {
+                consumer: Some("dave".to_string()),
+                ..filter
+            },
+        );
+        assert_eq!(pending.unwrap()[0].1.deliveries, 7);
+
+        assert_eq!(
+            backend.xpending("missing", "g"),
+            Err(BackendError::NoGroup(
+                "missing".to_string(),
+                "g".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_xsetid() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.xsetid("s", StreamId::new(1, 0)),
+            Err(BackendError::NoSuchKey)
+        );
+        for ms in 1..=3 {
+            let id = XAddId::Explicit(StreamId::new(ms, 0));
+            backend.xadd("s", id, fields(&[("n", "v")]), false).unwrap();
+        }
+        assert_eq!(
+            backend.xsetid("s", StreamId::new(2, 0)),
+            Err(BackendError::XSetIdTooSmall)
+        );
+        assert_eq!(backend.xsetid("s", StreamId::new(10, 0)), Ok(()));
+        assert_eq!(backend.xlast_id("s"), Ok(StreamId::new(10, 0)));
+        let id = XAddId::Explicit(StreamId::new(5, 0));
+        assert_eq!(
+            backend.xadd("s", id, fields(&[("n", "v")]), false),
+            Err(BackendError::StreamIdTooSmall)
+        );
+
+        // back to the top entry, so that a deleted one's ID can be reused
+        backend.xdel("s", &[StreamId::new(3, 0)]).unwrap();
+        assert_eq!(backend.xsetid("s", StreamId::new(2, 0)), Ok(()));
+        let id = XAddId::Explicit(StreamId::new(3, 0));
+        assert!(backend.xadd("s", id, fields(&[("n", "v")]), false).is_ok());
+    }
+
+    #[test]
+    fn test_trim() {
+        let backend = Backend::new();
+        for ms in 1..=250 {
+            let id = XAddId::Explicit(StreamId::new(ms, 0));
+            backend.xadd("s", id, vec![], false).unwrap();
+        }
+        let approx = StreamTrim {
+            strategy: TrimStrategy::MaxLen(10),
+            approx: true,
+            limit: None,
+        };
+        // only whole nodes go
+        assert_eq!(backend.xtrim("s", &approx), Ok(200));
+        let limited = StreamTrim {
+            limit: Some(30),
+            ..approx
+        };
+        assert_eq!(backend.xtrim("s", &limited), Ok(0));
+        let exact = StreamTrim {
+            approx: false,
+            ..approx
+        };
+        assert_eq!(backend.xtrim("s", &exact), Ok(40));
+        assert_eq!(backend.xlen("s"), Ok(10));
+
+        let min_id = StreamTrim {
+            strategy: TrimStrategy::MinId(StreamId::new(245, 0)),
+            approx: false,
+            limit: None,
+        };
+        assert_eq!(backend.xtrim("s", &min_id), Ok(4));
+
+        // XADD trims after adding
+        let options = XAddOptions {
+            nomkstream: false,
+            trim: Some(StreamTrim {
+                strategy: TrimStrategy::MaxLen(2),
+                approx: false,
+                limit: None,
+            }),
+        };
+        let id = XAddId::Explicit(StreamId::new(300, 0));
+        backend.xadd_with("s", id, vec![], &options).unwrap();
+        let range = backend.xrange("s", StreamId::MIN, StreamId::MAX, false, None);
+        let ids: Vec<_> = range.unwrap().into_iter().map(|(id, _)| id.ms).collect();
+        assert_eq!(ids, vec![250, 300]);
+
+        assert_eq!(
+            backend.xdel("s", &[StreamId::new(250, 0), StreamId::new(1, 0)]),
+            Ok(1)
+        );
+        assert_eq!(backend.xdel("missing", &[StreamId::new(1, 0)]), Ok(0));
+    }
+}
@@ -0,0 +1,543 @@
+use crate::backend::now_ms;
+use crate::{Backend, BackendError, BulkString, NotifyFlags, RespEncode, RespFrame, Value};
+
+/// How GETEX updates the TTL of the key it reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireUpdate {
+    Keep,
+    /// Absolute expiration time in unix milliseconds, a time in the past deletes the key.
+    At(i64),
+    Persist,
+}
+
+/// When SET writes the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    Always,
+    /// NX, only if it doesn't exist.
+    IfMissing,
+    /// XX, only if it exists.
+    IfExists,
+}
+
+impl Backend {
+    /// Add delta to the integer stored at key, a missing key counts as 0. The read-modify-write
+    /// happens under the shard lock, so concurrent increments never get lost.
+    pub fn incr_by(&self, key: &str, delta: i64) -> Result<i64, BackendError> {
+        let mut shard = self.write(key);
+        let current = match shard.get(key).map(|obj| &**obj) {
+            None => 0,
+            Some(Value::String(v)) => parse_integer(v).ok_or(BackendError::NotInteger)?,
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        let value = current.checked_add(delta).ok_or(BackendError::Overflow)?;
+
+        store_string(self, &mut shard, key, value.to_string().into_bytes());
+        self.notify_keyspace_event(NotifyFlags::STRING, "incrby", key);
+        Ok(value)
+    }
+
+    /// Add delta to the float stored at key, a missing key counts as 0.
+    pub fn incr_by_float(&self, key: &str, delta: f64) -> Result<f64, BackendError> {
+        let mut shard = self.write(key);
+        let current = match shard.get(key).map(|obj| &**obj) {
+            None => 0.0,
+            Some(Value::String(v)) => parse_float(v).ok_or(BackendError::NotFloat)?,
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        let value = current + delta;
+        if !value.is_finite() {
+            return Err(BackendError::NanOrInfinity);
+        }
+
+        store_string(self, &mut shard, key, value.to_string().into_bytes());
+        self.notify_keyspace_event(NotifyFlags::STRING, "incrbyfloat", key);
+        Ok(value)
+    }
+
+    /// Append bytes to the string at key, creating it if missing. Returns the new length.
+    pub fn append(&self, key: &str, bytes: &[u8]) -> Result<usize, BackendError> {
+        let mut shard = self.write(key);
+        let mut value = match shard.get(key).map(|obj| &**obj) {
+            None => Vec::new(),
+            Some(Value::String(v)) => string_bytes(v),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+        value.extend_from_slice(bytes);
+        let len = value.len();
+
+        store_string(self, &mut shard, key, value);
+        self.notify_keyspace_event(NotifyFlags::STRING, "append", key);
+        Ok(len)
+    }
+
+    /// Set key only if it doesn't exist. Returns true if the value was set.
+    pub fn setnx(&self, key: String, value: RespFrame) -> bool {
+        let mut shard = self.write(&key);
+        if shard.contains_key(&key) {
+            return false;
+        }
+        self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+        self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+        shard.insert(key, Value::String(value));
+        true
+    }
+
+    /// SET with its options: set key to value if condition holds, updating its TTL as expire
+    /// says, `Persist` for a plain SET. Return whether it was set and, with get, the previous
+    /// string value, then a value of another type is a WrongType error and nothing is set.
+    pub fn set_with(
+        &self,
+        key: String,
+        value: RespFrame,
+        condition: SetCondition,
+        expire: ExpireUpdate,
+        get: bool,
+    ) -> Result<(bool, Option<RespFrame>), BackendError> {
+        let mut shard = self.write(&key);
+        let old = match shard.get(&key).map(|obj| &**obj) {
+            Some(Value::String(v)) if get => Some(v.clone()),
+            Some(_) if get => return Err(BackendError::WrongType),
+            _ => None,
+        };
+        let exists = shard.contains_key(&key);
+        let set = match condition {
+            SetCondition::Always => true,
+            SetCondition::IfMissing => !exists,
+            SetCondition::IfExists => exists,
+        };
+        if !set {
+            return Ok((false, old));
+        }
+
+        let at = match expire {
+            ExpireUpdate::Keep => shard.expire_time(&key),
+            ExpireUpdate::At(at) => Some(at),
+            ExpireUpdate::Persist => None,
+        };
+        if shard.remove(&key).is_none() {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+        }
+        self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+        if let ExpireUpdate::At(_) = expire {
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "expire", &key);
+        }
+        if let Some(at) = at {
+            shard.set_expire(key.clone(), at);
+        }
+        shard.insert(key, Value::String(value));
+        Ok((true, old))
+    }
+
+    /// Set key and its absolute expiration time in unix milliseconds in one step, like
+    /// `SET key value PXAT at_ms`.
+    pub fn set_expire_at(&self, key: String, value: RespFrame, at_ms: i64) {
+        let mut shard = self.write(&key);
+        if shard.remove(&key).is_none() {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+        }
+        self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "expire", &key);
+        shard.set_expire(key.clone(), at_ms);
+        shard.insert(key, Value::String(value));
+    }
+
+    /// Set key to value and return its previous string value, dropping the TTL like SET.
+    pub fn getset(&self, key: String, value: RespFrame) -> Result<Option<RespFrame>, BackendError> {
+        let mut shard = self.write(&key);
+        let old = match shard.get(&key).map(|obj| &**obj) {
+            None => None,
+            Some(Value::String(v)) => Some(v.clone()),
+            Some(_) => return Err(BackendError::WrongType),
+        };
+
+        if shard.remove(&key).is_none() {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+        }
+        self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+        shard.insert(key, Value::String(value));
+        Ok(old)
+    }
+
+    /// Delete the string at key and return it.
+    pub fn getdel(&self, key: &str) -> Result<Option<RespFrame>, BackendError> {
+        let mut shard = self.write(key);
+        match shard.get(key).map(|obj| &**obj) {
+            None => return Ok(None),
+            Some(Value::String(_)) => {}
+            Some(_) => return Err(BackendError::WrongType),
+        }
+
+        let value = match shard.remove(key).map(|obj| obj.into_inner()) {
+            Some(Value::String(v)) => Some(v),
+            _ => None,
+        };
+        self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        Ok(value)
+    }
+
+    /// Return the string at key and update its TTL.
+    pub fn getex(
+        &self,
+        key: &str,
+        update: ExpireUpdate,
+    ) -> Result<Option<RespFrame>, BackendError> {
+        let mut shard = self.write(key);
+        let value = match shard.get(key) {
+            None => return Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::String(v) => v.clone(),
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+        };
+
+        match update {
+            ExpireUpdate::Keep => {}
+            ExpireUpdate::At(at_ms) if at_ms <= now_ms() => {
+                shard.remove(key);
+                self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+            }
+            ExpireUpdate::At(at_ms) => {
+                shard.set_expire(key.to_string(), at_ms);
+                self.notify_keyspace_event(NotifyFlags::GENERIC, "expire", key);
+            }
+            ExpireUpdate::Persist => {
+                if shard.persist(key).is_some() {
+                    self.notify_keyspace_event(NotifyFlags::GENERIC, "persist", key);
+                }
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// The string values of keys, `None` for missing keys and keys of another type.
+    pub fn mget(&self, keys: &[String]) -> Vec<Option<RespFrame>> {
+        let guard = self.read_many(keys.iter().map(|k| k.as_str()));
+        let mut values = vec![None; keys.len()];
+        for (shard, positions) in guard.batches() {
+            for &i in positions {
+                let key = &keys[i];
+                if shard.is_expired(key) {
+                    continue;
+                }
+                if let Some(obj) = shard.get(key) {
+                    obj.touch();
+                    if let Value::String(v) = &**obj {
+                        values[i] = Some(v.clone());
+                    }
+                }
+            }
+        }
+        values
+    }
+
+    /// Set all the pairs at once, replacing values of any type and their TTLs. The pairs of
+    /// every shard are set together, a later pair of a same key replacing an earlier one.
+    pub fn mset(&self, pairs: Vec<(String, RespFrame)>) {
+        let mut guard = self.write_many(pairs.iter().map(|(k, _)| k.as_str()));
+        let mut pairs: Vec<_> = pairs.into_iter().map(Some).collect();
+        for (shard, positions) in guard.batches() {
+            for &i in positions {
+                let (key, value) = pairs[i].take().expect("a key is in a single batch");
+                if shard.remove(&key).is_none() {
+                    self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+                }
+                self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+                shard.insert(key, Value::String(value));
+            }
+        }
+    }
+
+    /// Set all the pairs only if none of the keys exist. Returns true if they were set.
+    pub fn msetnx(&self, pairs: Vec<(String, RespFrame)>) -> bool {
+        let mut guard = self.write_many(pairs.iter().map(|(k, _)| k.as_str()));
+        if pairs
+            .iter()
+            .any(|(key, _)| guard.shard(key).contains_key(key))
+        {
+            return false;
+        }
+        for (key, value) in pairs {
+            let shard = guard.shard(&key);
+            if !shard.contains_key(&key) {
+                self.notify_keyspace_event(NotifyFlags::NEW, "new", &key);
+            }
+            self.notify_keyspace_event(NotifyFlags::STRING, "set", &key);
+            shard.insert(key, Value::String(value));
+        }
+        true
+    }
+
+    /// The length of the string at key, 0 if the key doesn't exist.
+    pub fn strlen(&self, key: &str) -> Result<usize, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key).map(|obj| &**obj) {
+            None => Ok(0),
+            Some(Value::String(RespFrame::BulkString(s))) => Ok(s.len()),
+            Some(Value::String(v)) => Ok(string_bytes(v).len()),
+            Some(_) => Err(BackendError::WrongType),
+        }
+    }
+}
+
+// replace the string at key keeping its TTL, like redis does for in place updates
+pub(crate) fn store_string(backend: &Backend, shard: &mut crate::Shard, key: &str, bytes: Vec<u8>) {
+    let value = RespFrame::BulkString(BulkString::new(bytes));
+    match shard.get_mut(key) {
+        Some(obj) => {
+            obj.touch();
+            **obj = Value::String(value);
+        }
+        None => {
+            backend.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+            shard.insert(key.to_string(), Value::String(value));
+        }
+    }
+}
+
+/// The raw bytes of a string value.
+pub(crate) fn string_bytes(frame: &RespFrame) -> Vec<u8> {
+    match frame {
+        RespFrame::BulkString(s) => s.to_vec(),
+        RespFrame::SimpleString(s) => s.as_bytes().to_vec(),
+        RespFrame::Integer(n) => n.to_string().into_bytes(),
+        frame => frame.clone().encode(),
+    }
+}
+
+// strict like redis: no sign but `-`, no whitespace, no leading zeros
+pub(crate) fn parse_integer(frame: &RespFrame) -> Option<i64> {
+    if let RespFrame::Integer(n) = frame {
+        return Some(*n);
+    }
+    let bytes = string_bytes(frame);
+    let s = std::str::from_utf8(&bytes).ok()?;
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || digits.starts_with('+') || (digits.starts_with('0') && s != "0") {
+        return None;
+    }
+    s.parse().ok()
+}
+
+pub(crate) fn parse_float(frame: &RespFrame) -> Option<f64> {
+    let bytes = string_bytes(frame);
+    let s = std::str::from_utf8(&bytes).ok()?;
+    if s.is_empty() || s.trim() != s {
+        return None;
+    }
+    s.parse::<f64>().ok().filter(|n| !n.is_nan())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_incr_by() {
+        let backend = Backend::new();
+        assert_eq!(backend.incr_by("k1", 5), Ok(5));
+        assert_eq!(backend.incr_by("k1", -7), Ok(-2));
+        assert_eq!(
+            backend.get("k1"),
+            Ok(Some(RespFrame::BulkString(b"-2".into())))
+        );
+
+        backend.set("k1".to_string(), RespFrame::BulkString(b"007".into()));
+        assert_eq!(backend.incr_by("k1", 1), Err(BackendError::NotInteger));
+        backend.set("k1".to_string(), RespFrame::BulkString(b"abc".into()));
+        assert_eq!(backend.incr_by("k1", 1), Err(BackendError::NotInteger));
+
+        backend.set(
+            "k1".to_string(),
+            RespFrame::BulkString(b"9223372036854775807".into()),
+        );
+        assert_eq!(backend.incr_by("k1", 1), Err(BackendError::Overflow));
+
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(backend.incr_by("h", 1), Err(BackendError::WrongType));
+    }
+
+    #[test]
+    fn test_incr_by_keeps_ttl() {
+        let backend = Backend::new();
+        backend.set("k1".to_string(), RespFrame::BulkString(b"1".into()));
+        let at = crate::backend::now_ms() + 10_000;
+        backend.expire_at("k1", at);
+        assert_eq!(backend.incr_by("k1", 1), Ok(2));
+        assert_eq!(backend.expire_time("k1"), Some(Some(at)));
+    }
+
+    #[test]
+    fn test_incr_by_float() {
+        let backend = Backend::new();
+        assert_eq!(backend.incr_by_float("k1", 10.5), Ok(10.5));
+        assert_eq!(backend.incr_by_float("k1", 0.1), Ok(10.6));
+        assert_eq!(
+            backend.get("k1"),
+            Ok(Some(RespFrame::BulkString(b"10.6".into())))
+        );
+
+        backend.set("k1".to_string(), RespFrame::BulkString(b"5.0e3".into()));
+        assert_eq!(backend.incr_by_float("k1", 200.0), Ok(5200.0));
+        assert_eq!(
+            backend.incr_by_float("k1", f64::INFINITY),
+            Err(BackendError::NanOrInfinity)
+        );
+        backend.set("k1".to_string(), RespFrame::BulkString(b" 1".into()));
+        assert_eq!(
+            backend.incr_by_float("k1", 1.0),
+            Err(BackendError::NotFloat)
+        );
+    }
+
+    #[test]
+    fn test_append_and_strlen() {
+        let backend = Backend::new();
+        assert_eq!(backend.strlen("k1"), Ok(0));
+        assert_eq!(backend.append("k1", b"hello"), Ok(5));
+        assert_eq!(backend.append("k1", b"\0\xff"), Ok(7));
+        assert_eq!(backend.strlen("k1"), Ok(7));
+        assert_eq!(
+            backend.get("k1"),
+            Ok(Some(RespFrame::BulkString(b"hello\0\xff".into())))
+        );
+
+        backend.set("k2".to_string(), RespFrame::Integer(42));
+        assert_eq!(backend.strlen("k2"), Ok(2));
+        assert_eq!(backend.append("k2", b"0"), Ok(3));
+        assert_eq!(backend.incr_by("k2", 1), Ok(421));
+
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(backend.append("h", b"x"), Err(BackendError::WrongType));
+        assert_eq!(backend.strlen("h"), Err(BackendError::WrongType));
+    }
+
+    #[test]
+    fn test_mget_mset_msetnx() {
+        let backend = Backend::with_shards(4);
+        let pairs: Vec<_> = (0..10)
+            .map(|i| (format!("k{}", i), RespFrame::Integer(i)))
+            .collect();
+        backend.mset(pairs);
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+
+        let keys: Vec<String> = ["k1", "missing", "h", "k9"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        assert_eq!(
+            backend.mget(&keys),
+            vec![
+                Some(RespFrame::Integer(1)),
+                None,
+                None,
+                Some(RespFrame::Integer(9))
+            ]
+        );
+
+        // all or nothing
+        let pairs = vec![
+            ("new1".to_string(), RespFrame::Integer(1)),
+            ("k1".to_string(), RespFrame::Integer(100)),
+        ];
+        assert!(!backend.msetnx(pairs));
+        assert!(!backend.contains_key("new1"));
+        assert_eq!(backend.get("k1"), Ok(Some(RespFrame::Integer(1))));
+
+        let pairs = vec![
+            ("new1".to_string(), RespFrame::Integer(1)),
+            ("new2".to_string(), RespFrame::Integer(2)),
+        ];
+        assert!(backend.msetnx(pairs));
+        assert!(backend.contains_key("new1") && backend.contains_key("new2"));
+    }
+
+    #[test]
+    fn test_setnx_and_set_expire_at() {
+        let backend = Backend::new();
+        assert!(backend.setnx("k1".to_string(), RespFrame::Integer(1)));
+        assert!(!backend.setnx("k1".to_string(), RespFrame::Integer(2)));
+        assert_eq!(backend.get("k1"), Ok(Some(RespFrame::Integer(1))));
+
+        let at = crate::backend::now_ms() + 10_000;
+        backend.set_expire_at("k1".to_string(), RespFrame::Integer(3), at);
+        assert_eq!(backend.get("k1"), Ok(Some(RespFrame::Integer(3))));
+        assert_eq!(backend.expire_time("k1"), Some(Some(at)));
+
+        // a plain SET drops the TTL again
+        backend.set("k1".to_string(), RespFrame::Integer(4));
+        assert_eq!(backend.expire_time("k1"), Some(None));
+    }
+
+    #[test]
+    fn test_getset_getdel_getex() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.getset("k1".to_string(), RespFrame::Integer(1)),
+            Ok(None)
+        );
+        let at = now_ms() + 10_000;
+        backend.expire_at("k1", at);
+        assert_eq!(
+            backend.getset("k1".to_string(), RespFrame::Integer(2)),
+            Ok(Some(RespFrame::Integer(1)))
+        );
+        assert_eq!(backend.expire_time("k1"), Some(None));
+
+        assert_eq!(
+            backend.getex("k1", ExpireUpdate::At(at)),
+            Ok(Some(RespFrame::Integer(2)))
+        );
+        assert_eq!(backend.expire_time("k1"), Some(Some(at)));
+        backend.getex("k1", ExpireUpdate::Persist).unwrap();
+        assert_eq!(backend.expire_time("k1"), Some(None));
+        assert_eq!(
+            backend.getex("k1", ExpireUpdate::At(now_ms() - 1)),
+            Ok(Some(RespFrame::Integer(2)))
+        );
+        assert!(!backend.contains_key("k1"));
+
+        backend.set("k1".to_string(), RespFrame::Integer(3));
+        assert_eq!(backend.getdel("k1"), Ok(Some(RespFrame::Integer(3))));
+        assert_eq!(backend.getdel("k1"), Ok(None));
+
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        assert_eq!(backend.getdel("h"), Err(BackendError::WrongType));
+        assert!(backend.contains_key("h"));
+        assert_eq!(
+            backend.getset("h".to_string(), RespFrame::Integer(1)),
+            Err(BackendError::WrongType)
+        );
+    }
+
+    #[test]
+    fn test_concurrent_incr() {
+        let backend = Backend::new();
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let backend = backend.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        backend.incr_by("counter", 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(backend.incr_by("counter", 0), Ok(8000));
+    }
+}
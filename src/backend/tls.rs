@@ -0,0 +1,74 @@
+use crate::Backend;
+use parking_lot::RwLock;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU16, Ordering};
+
+/// Whether the clients connecting over TLS present a certificate, as the `tls-auth-clients`
+/// of redis.conf.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TlsAuthClients {
+    No,
+    #[default]
+    Yes,
+    // checked if there's one, the connection is accepted without
+    Optional,
+}
+
+impl FromStr for TlsAuthClients {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "no" => Ok(TlsAuthClients::No),
+            "yes" => Ok(TlsAuthClients::Yes),
+            "optional" => Ok(TlsAuthClients::Optional),
+            _ => Err("argument(s) must be one of the following: no, yes, optional".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TlsAuthClients {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TlsAuthClients::No => "no",
+            TlsAuthClients::Yes => "yes",
+            TlsAuthClients::Optional => "optional",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The parameters of the TLS listener, set at startup. There's none while the port is 0.
+#[derive(Debug, Default)]
+pub struct Tls {
+    pub(super) port: AtomicU16,
+    pub(super) cert_file: RwLock<String>,
+    pub(super) key_file: RwLock<String>,
+    // the CA the certificates of the clients are checked against
+    pub(super) ca_cert_file: RwLock<String>,
+    pub(super) auth_clients: RwLock<TlsAuthClients>,
+}
+
+impl Backend {
+    /// The port TLS connections are accepted on, none if it's 0.
+    pub fn tls_port(&self) -> u16 {
+        self.tls.port.load(Ordering::Relaxed)
+    }
+
+    pub fn tls_cert_file(&self) -> String {
+        self.tls.cert_file.read().clone()
+    }
+
+    pub fn tls_key_file(&self) -> String {
+        self.tls.key_file.read().clone()
+    }
+
+    pub fn tls_ca_cert_file(&self) -> String {
+        self.tls.ca_cert_file.read().clone()
+    }
+
+    pub fn tls_auth_clients(&self) -> TlsAuthClients {
+        *self.tls.auth_clients.read()
+    }
+}
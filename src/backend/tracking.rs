@@ -0,0 +1,419 @@
+use crate::{Backend, BackendError};
+use parking_lot::{Mutex, RwLock};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+// what a key tracked for a client costs besides its name: its entry in the table, and the
+// client's ID in the set of its readers
+const TRACKED_KEY_OVERHEAD: u64 = 48;
+
+tokio::task_local! {
+    // the command a connection is running on the task
+    static RUNNING: Running;
+}
+
+#[derive(Debug)]
+struct Running {
+    client: u64,
+    // whether the keys it reads are to be tracked, and those it read
+    tracked: bool,
+    reads: RefCell<Vec<String>>,
+}
+
+/// Keys a tracking client must drop from its cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invalidation {
+    pub keys: Vec<String>,
+    /// Sent to the client another one redirects to, as a `__redis__:invalidate` message.
+    pub redirected: bool,
+}
+
+/// The command running on the task of a connection, carried to run part of it on another
+/// thread as if it ran on the task.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TrackingContext {
+    running: Option<(u64, bool)>,
+}
+
+impl TrackingContext {
+    // run f as the command, returning the keys it read to be tracked
+    pub(crate) fn run<T>(self, f: impl FnOnce() -> T) -> (T, Vec<String>) {
+        let Some((client, tracked)) = self.running else {
+            return (f(), Vec::new());
+        };
+        let running = Running {
+            client,
+            tracked,
+            reads: RefCell::new(Vec::new()),
+        };
+        RUNNING.sync_scope(running, || {
+            let output = f();
+            (output, RUNNING.with(|running| running.reads.take()))
+        })
+    }
+}
+
+/// The options of CLIENT TRACKING ON.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackingOptions {
+    /// The client to send the invalidations to instead, over `__redis__:invalidate`.
+    pub redirect: Option<u64>,
+    /// Invalidate every key starting with one of the prefixes, read or not. No prefix
+    /// matches every key.
+    pub bcast: bool,
+    pub prefixes: Vec<String>,
+    /// Track only the reads of a command following CLIENT CACHING yes.
+    pub optin: bool,
+    /// Track the reads of every command but one following CLIENT CACHING no.
+    pub optout: bool,
+    /// Don't invalidate the keys modified by the client itself.
+    pub noloop: bool,
+}
+
+/// The client-side caching state of a backend: the connected clients invalidations can be
+/// sent to, and which tracking ones may have cached each key. A key is forgotten once it's
+/// invalidated, until it's read again.
+#[derive(Debug, Default)]
+pub struct Tracking {
+    clients: RwLock<HashMap<u64, TrackedClient>>,
+    keys: Mutex<HashMap<String, HashSet<u64>>>,
+    // the clients with tracking on, so writes skip the tables while there's none
+    tracking: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct TrackedClient {
+    tx: UnboundedSender<Invalidation>,
+    options: Option<TrackingOptions>,
+    // set by CLIENT CACHING for the next command
+    caching: Option<bool>,
+}
+
+impl TrackedClient {
+    // whether the reads of the next command are to be tracked, given CLIENT CACHING
+    fn tracks_reads(&self, caching: Option<bool>) -> bool {
+        match &self.options {
+            Some(options) if options.bcast => false,
+            Some(options) if options.optin => caching == Some(true),
+            Some(options) if options.optout => caching != Some(false),
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
+impl Backend {
+    /// Register a connected client, with the receiver of the invalidations sent to it.
+    pub fn connect_client(&self, id: u64) -> UnboundedReceiver<Invalidation> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let client = TrackedClient {
+            tx,
+            options: None,
+            caching: None,
+        };
+        self.tracking.clients.write().insert(id, client);
+        rx
+    }
+
+    /// How many clients are connected.
+    pub fn connected_clients(&self) -> usize {
+        self.tracking.clients.read().len()
+    }
+
+    /// Forget a disconnected client. The keys it read are dropped from the table lazily.
+    pub fn disconnect_client(&self, id: u64) {
+        if let Some(client) = self.tracking.clients.write().remove(&id) {
+            if client.options.is_some() {
+                self.tracking.tracking.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Turn tracking on with options for the client, or off for None.
+    pub fn client_tracking(
+        &self,
+        id: u64,
+        options: Option<TrackingOptions>,
+    ) -> Result<(), BackendError> {
+        let mut clients = self.tracking.clients.write();
+        let redirect = options.as_ref().and_then(|options| options.redirect);
+        if redirect.is_some_and(|redirect| !clients.contains_key(&redirect)) {
+            return Err(BackendError::NoRedirectClient);
+        }
+        let Some(client) = clients.get_mut(&id) else {
+            return Ok(());
+        };
+
+        match (client.options.is_some(), options.is_some()) {
+            (false, true) => self.tracking.tracking.fetch_add(1, Ordering::Relaxed),
+            (true, false) => self.tracking.tracking.fetch_sub(1, Ordering::Relaxed),
+            _ => 0,
+        };
+        client.options = options;
+        client.caching = None;
+        Ok(())
+    }
+
+    /// Track, or not, the reads of the next command of an OPTIN, or OPTOUT, client.
+    pub fn client_caching(&self, id: u64, yes: bool) -> Result<(), BackendError> {
+        let mut clients = self.tracking.clients.write();
+        let Some(client) = clients.get_mut(&id) else {
+            return Ok(());
+        };
+        match &client.options {
+            Some(options) if options.optin && !yes => Err(BackendError::CachingNoNotOptout),
+            Some(options) if options.optout && yes => Err(BackendError::CachingYesNotOptin),
+            Some(options) if options.optin || options.optout => {
+                client.caching = Some(yes);
+                Ok(())
+            }
+            _ => Err(BackendError::CachingNotTracking),
+        }
+    }
+
+    /// Run the command of client, remembering the keys it reads if the client tracks them.
+    pub async fn track<F: Future>(&self, id: u64, command: F) -> F::Output {
+        let tracked = self.tracking.tracking.load(Ordering::Relaxed) > 0 && {
+            let caching = self.take_caching(id);
+            let clients = self.tracking.clients.read();
+            clients
+                .get(&id)
+                .is_some_and(|client| client.tracks_reads(caching))
+        };
+        let running = Running {
+            client: id,
+            tracked,
+            reads: RefCell::new(Vec::new()),
+        };
+
+        RUNNING
+            .scope(running, async {
+                let output = command.await;
+                RUNNING.with(|running| {
+                    let mut keys = self.tracking.keys.lock();
+                    for key in running.reads.take() {
+                        keys.entry(key).or_default().insert(id);
+                    }
+                });
+                output
+            })
+            .await
+    }
+
+    // CLIENT CACHING applies to the next command alone
+    fn take_caching(&self, id: u64) -> Option<bool> {
+        let pending = self
+            .tracking
+            .clients
+            .read()
+            .get(&id)
+            .is_some_and(|client| client.caching.is_some());
+        if !pending {
+            return None;
+        }
+        self.tracking
+            .clients
+            .write()
+            .get_mut(&id)
+            .and_then(|client| client.caching.take())
+    }
+
+    // the bytes the keys tracked for each client take in the table, counted towards
+    // maxmemory-clients
+    pub(crate) fn tracking_memory(&self) -> HashMap<u64, u64> {
+        let mut memory = HashMap::new();
+        if self.tracking.tracking.load(Ordering::Relaxed) == 0 {
+            return memory;
+        }
+        for (key, readers) in self.tracking.keys.lock().iter() {
+            for id in readers {
+                *memory.entry(*id).or_default() += key.len() as u64 + TRACKED_KEY_OVERHEAD;
+            }
+        }
+        memory
+    }
+
+    // the command running on the task, to run part of it elsewhere
+    pub(crate) fn tracking_context(&self) -> TrackingContext {
+        TrackingContext {
+            running: RUNNING
+                .try_with(|running| (running.client, running.tracked))
+                .ok(),
+        }
+    }
+
+    // remember the keys the running command read elsewhere, as returned by its context
+    pub(crate) fn track_reads(&self, reads: Vec<String>) {
+        let _ = RUNNING.try_with(|running| running.reads.borrow_mut().extend(reads));
+    }
+
+    // remember that the running command read key, if its client tracks it
+    pub(crate) fn track_read(&self, key: &str) {
+        let _ = RUNNING.try_with(|running| {
+            if running.tracked {
+                running.reads.borrow_mut().push(key.to_string());
+            }
+        });
+    }
+
+    // send an invalidation of the modified key to the clients that may have cached it
+    pub(crate) fn invalidate(&self, key: &str) {
+        if self.tracking.tracking.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let writer = RUNNING.try_with(|running| running.client).ok();
+        let readers = self.tracking.keys.lock().remove(key).unwrap_or_default();
+
+        let clients = self.tracking.clients.read();
+        let broadcast = clients.iter().filter_map(|(id, client)| {
+            let options = client.options.as_ref()?;
+            let matched = options.prefixes.is_empty()
+                || options
+                    .prefixes
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix));
+            (options.bcast && matched).then_some(*id)
+        });
+        for id in readers.into_iter().chain(broadcast) {
+            let Some(options) = clients.get(&id).and_then(|client| client.options.as_ref()) else {
+                continue;
+            };
+            if options.noloop && writer == Some(id) {
+                continue;
+            }
+            if let Some(target) = clients.get(&options.redirect.unwrap_or(id)) {
+                let _ = target.tx.send(Invalidation {
+                    keys: vec![key.to_string()],
+                    redirected: options.redirect.is_some(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespFrame};
+
+    fn value(s: &str) -> RespFrame {
+        BulkString::from(s).into()
+    }
+
+    #[tokio::test]
+    async fn test_tracking() {
+        let backend = Backend::new();
+        let mut rx = backend.connect_client(1);
+        backend
+            .client_tracking(1, Some(TrackingOptions::default()))
+            .unwrap();
+
+        backend.set("a".to_string(), value("1"));
+        assert!(rx.try_recv().is_err());
+
+        // once read, the key is invalidated on its next write only
+        backend.track(1, async { backend.get("a") }).await.unwrap();
+        backend.set("a".to_string(), value("2"));
+        assert_eq!(rx.try_recv().unwrap().keys, vec!["a"]);
+        backend.set("a".to_string(), value("3"));
+        assert!(rx.try_recv().is_err());
+
+        // off, nothing is tracked
+        backend.client_tracking(1, None).unwrap();
+        backend.track(1, async { backend.get("a") }).await.unwrap();
+        backend.set("a".to_string(), value("4"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tracking_bcast() {
+        let backend = Backend::new();
+        let mut rx = backend.connect_client(1);
+        let options = TrackingOptions {
+            bcast: true,
+            prefixes: vec!["user:".to_string()],
+            ..Default::default()
+        };
+        backend.client_tracking(1, Some(options)).unwrap();
+
+        // unread keys too, matching a prefix
+        backend.set("user:1".to_string(), value("1"));
+        backend.set("item:1".to_string(), value("1"));
+        assert_eq!(rx.try_recv().unwrap().keys, vec!["user:1"]);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tracking_redirect_and_noloop() {
+        let backend = Backend::new();
+        let mut rx1 = backend.connect_client(1);
+        let mut rx2 = backend.connect_client(2);
+        let options = TrackingOptions {
+            redirect: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            backend.client_tracking(1, Some(options)),
+            Err(BackendError::NoRedirectClient)
+        );
+
+        let options = TrackingOptions {
+            redirect: Some(2),
+            noloop: true,
+            ..Default::default()
+        };
+        backend.client_tracking(1, Some(options)).unwrap();
+        backend.set("a".to_string(), value("1"));
+        backend.track(1, async { backend.get("a") }).await.unwrap();
+        backend.set("a".to_string(), value("2"));
+        assert!(rx1.try_recv().is_err());
+        let invalidation = rx2.try_recv().unwrap();
+        assert_eq!(invalidation.keys, vec!["a"]);
+        assert!(invalidation.redirected);
+
+        // not for its own writes
+        backend.track(1, async { backend.get("a") }).await.unwrap();
+        backend
+            .track(1, async { backend.set("a".to_string(), value("3")) })
+            .await;
+        assert!(rx2.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tracking_optin() {
+        let backend = Backend::new();
+        let mut rx = backend.connect_client(1);
+        assert_eq!(
+            backend.client_caching(1, true),
+            Err(BackendError::CachingNotTracking)
+        );
+        let options = TrackingOptions {
+            optin: true,
+            ..Default::default()
+        };
+        backend.client_tracking(1, Some(options)).unwrap();
+        assert_eq!(
+            backend.client_caching(1, false),
+            Err(BackendError::CachingNoNotOptout)
+        );
+
+        backend.track(1, async { backend.get("a") }).await.unwrap();
+        backend.set("a".to_string(), value("1"));
+        assert!(rx.try_recv().is_err());
+
+        // for the next command only
+        backend
+            .track(1, async { backend.client_caching(1, true) })
+            .await
+            .unwrap();
+        backend.track(1, async { backend.get("a") }).await.unwrap();
+        backend.track(1, async { backend.get("b") }).await.unwrap();
+        backend.set("a".to_string(), value("2"));
+        backend.set("b".to_string(), value("2"));
+        assert_eq!(rx.try_recv().unwrap().keys, vec!["a"]);
+        assert!(rx.try_recv().is_err());
+    }
+}
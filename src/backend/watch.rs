@@ -0,0 +1,84 @@
+use crate::Backend;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The keys watched by some connection, with a version bumped on every modification of the
+/// key. A transaction aborts if the version of a key it watches changed since WATCH.
+#[derive(Debug, Default)]
+pub struct Watches {
+    keys: Mutex<HashMap<String, WatchedKey>>,
+    // the keys in the table, so writes skip it while there's none
+    len: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct WatchedKey {
+    watchers: usize,
+    version: u64,
+}
+
+impl Backend {
+    /// Watch key, returns its current version.
+    pub fn watch(&self, key: &str) -> u64 {
+        let mut keys = self.watches.keys.lock();
+        let watched = keys.entry(key.to_string()).or_default();
+        watched.watchers += 1;
+        self.watches.len.store(keys.len(), Ordering::Relaxed);
+        keys[key].version
+    }
+
+    /// Stop a watch of key, forgetting the key once no connection watches it.
+    pub fn unwatch(&self, key: &str) {
+        let mut keys = self.watches.keys.lock();
+        if let Some(watched) = keys.get_mut(key) {
+            watched.watchers -= 1;
+            if watched.watchers == 0 {
+                keys.remove(key);
+            }
+        }
+        self.watches.len.store(keys.len(), Ordering::Relaxed);
+    }
+
+    /// The current version of a watched key, None if it isn't watched.
+    pub fn watch_version(&self, key: &str) -> Option<u64> {
+        self.watches
+            .keys
+            .lock()
+            .get(key)
+            .map(|watched| watched.version)
+    }
+
+    // bump the version of the modified key, if watched
+    pub(crate) fn touch_watched(&self, key: &str) {
+        if self.watches.len.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        if let Some(watched) = self.watches.keys.lock().get_mut(key) {
+            watched.version += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Backend, BulkString};
+
+    #[test]
+    fn test_watch() {
+        let backend = Backend::new();
+        let version = backend.watch("k");
+        assert_eq!(backend.watch_version("k"), Some(version));
+
+        backend.set("k".to_string(), BulkString::from("v").into());
+        assert_ne!(backend.watch_version("k"), Some(version));
+        backend.set("other".to_string(), BulkString::from("v").into());
+
+        // watched twice, forgotten after the second unwatch
+        let version = backend.watch("k");
+        backend.unwatch("k");
+        assert_eq!(backend.watch_version("k"), Some(version));
+        backend.unwatch("k");
+        assert_eq!(backend.watch_version("k"), None);
+    }
+}
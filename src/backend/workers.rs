@@ -0,0 +1,182 @@
+use crate::backend::key_hash;
+use crate::Backend;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot};
+use tracing::info;
+
+/// The most worker threads worker-threads may ask for.
+pub const MAX_WORKER_THREADS: usize = 128;
+
+// how many commands may wait for a worker before the connections sending more wait too
+const WORKER_QUEUE_LEN: usize = 1024;
+
+tokio::task_local! {
+    // the worker the commands a connection runs on the task go to
+    static ROUTE: usize;
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The threads the connections run their commands on, apart from the runtime serving their
+/// sockets, so a command taking long, such as a SORT or a script, only holds back the commands
+/// on the same worker. Those on the same key always go to the same one. None unless
+/// worker-threads is set, the commands run on the tasks of the connections then.
+#[derive(Debug, Default)]
+pub struct Workers {
+    threads: AtomicUsize,
+    queues: OnceLock<Vec<mpsc::Sender<Job>>>,
+}
+
+impl Backend {
+    /// How many worker threads run the commands of the connections, 0 for none.
+    pub fn worker_threads(&self) -> usize {
+        self.workers.threads.load(Ordering::Relaxed)
+    }
+
+    pub fn set_worker_threads(&self, threads: usize) {
+        self.workers.threads.store(threads, Ordering::Relaxed);
+    }
+
+    /// Start the worker threads, the commands they run seeing the runtime of handle as
+    /// theirs, as they would on the tasks of the connections. Only once.
+    pub fn start_workers(&self, handle: &Handle) {
+        let threads = self.worker_threads();
+        if threads == 0 {
+            return;
+        }
+        self.workers.queues.get_or_init(|| {
+            info!("running the commands on {} worker threads", threads);
+            (0..threads)
+                .map(|i| {
+                    let (tx, mut rx) = mpsc::channel::<Job>(WORKER_QUEUE_LEN);
+                    let handle = handle.clone();
+                    std::thread::Builder::new()
+                        .name(format!("simple-redis-worker-{}", i))
+                        .spawn(move || {
+                            let _runtime = handle.enter();
+                            while let Some(job) = rx.blocking_recv() {
+                                job();
+                            }
+                        })
+                        .expect("failed to spawn a worker thread");
+                    tx
+                })
+                .collect()
+        });
+    }
+
+    /// Run command, the future of a connection running a command, with what it runs off the
+    /// task sent to the worker of its key, or of the connection of ID id without one.
+    pub async fn route<F: Future>(&self, key: Option<&str>, id: u64, command: F) -> F::Output {
+        let Some(queues) = self.workers.queues.get() else {
+            return command.await;
+        };
+        let hash = match key {
+            Some(key) => key_hash(key),
+            None => id,
+        };
+        ROUTE.scope(hash as usize % queues.len(), command).await
+    }
+
+    /// Run f on the worker the running command was routed to, after the commands queued
+    /// there, as if it ran on the task: the keys it reads are tracked for the client, and it
+    /// panics if f does. On the task itself if the command wasn't routed.
+    pub(crate) async fn run_on_worker<T, F>(&self, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let queue = match (self.workers.queues.get(), ROUTE.try_with(|route| *route)) {
+            (Some(queues), Ok(route)) => &queues[route],
+            _ => return f(),
+        };
+        let tracking = self.tracking_context();
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| tracking.run(f)));
+            let _ = tx.send(result);
+        });
+        if queue.send(job).await.is_err() {
+            unreachable!("the worker threads never stop");
+        }
+        match rx.await {
+            Ok(Ok((output, reads))) => {
+                self.track_reads(reads);
+                output
+            }
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(_) => unreachable!("the jobs always reply"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn backend(threads: usize) -> Backend {
+        let backend = Backend::new();
+        backend.set_worker_threads(threads);
+        backend.start_workers(&Handle::current());
+        backend
+    }
+
+    #[tokio::test]
+    async fn test_run_on_worker() {
+        let backend = backend(4);
+        let current = thread::current().id();
+        // not routed, on the task
+        let ran_on = backend.run_on_worker(|| thread::current().id()).await;
+        assert_eq!(ran_on, current);
+
+        let ran_on = |key: &'static str| {
+            let backend = backend.clone();
+            async move {
+                let run = backend.run_on_worker(|| thread::current().id());
+                backend.route(Some(key), 1, run).await
+            }
+        };
+        let a = ran_on("a").await;
+        assert_ne!(a, current);
+        // the same key on the same worker
+        assert_eq!(ran_on("a").await, a);
+        let name = backend
+            .route(
+                None,
+                1,
+                backend.run_on_worker(|| thread::current().name().map(String::from)),
+            )
+            .await;
+        assert!(name.unwrap().starts_with("simple-redis-worker-"));
+    }
+
+    #[tokio::test]
+    async fn test_worker_panics() {
+        let backend = backend(1);
+        let panicked = tokio::spawn({
+            let backend = backend.clone();
+            async move {
+                let run = backend.run_on_worker(|| panic!("on the worker"));
+                backend.route(None, 1, run).await
+            }
+        })
+        .await;
+        assert!(panicked.unwrap_err().is_panic());
+        // the worker is still there
+        let run = backend.run_on_worker(|| 1);
+        assert_eq!(backend.route(None, 1, run).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_workers() {
+        let backend = backend(0);
+        let current = thread::current().id();
+        let run = backend.run_on_worker(|| thread::current().id());
+        assert_eq!(backend.route(Some("a"), 1, run).await, current);
+    }
+}
@@ -0,0 +1,1319 @@
+use crate::backend::{key_hash, random_picks, scan_page};
+use crate::{Backend, BackendError, NotifyFlags, SetMembers, SetOp, Shard, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+
+/// A bound of a score range, `(` makes it exclusive as in `ZRANGEBYSCORE key (1 +inf`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+/// A bound of a lexicographic range: `-` and `+` for the smallest and greatest strings, then
+/// `[` and `(` for inclusive and exclusive bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(String),
+    Exclusive(String),
+}
+
+/// What the members of a ZRANGE are selected by.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZRangeBy {
+    /// Start and stop ranks, negative ones counting from the end.
+    Rank(i64, i64),
+    /// Min and max scores.
+    Score(ScoreBound, ScoreBound),
+    /// Min and max members, for a zset whose members all have the same score.
+    Lex(LexBound, LexBound),
+}
+
+/// The options of ZRANGE, see `Backend::zrange`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRangeOptions {
+    pub by: ZRangeBy,
+    /// Walk from the highest score down, ranks count from the highest as well.
+    pub rev: bool,
+    /// Offset and count of the members to return, a negative count returns all of them.
+    pub limit: Option<(i64, i64)>,
+}
+
+/// The flags of ZADD, see `Backend::zadd_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZAddOptions {
+    /// Only add new members.
+    pub nx: bool,
+    /// Only update existing members.
+    pub xx: bool,
+    /// Only update a score to a greater one, new members are still added.
+    pub gt: bool,
+    /// Only update a score to a lesser one, new members are still added.
+    pub lt: bool,
+    /// Count the updated members along with the added ones.
+    pub ch: bool,
+}
+
+// what ZADD did to a member
+enum ZAdded {
+    Skipped,
+    Added(f64),
+    Updated(f64),
+    Unchanged(f64),
+}
+
+/// How ZUNIONSTORE and ZINTERSTORE combine the scores a member has in several inputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // inf + -inf is 0 rather than NaN
+            Aggregate::Sum => not_nan(a + b),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+impl ScoreBound {
+    fn above_min(self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(min) => score >= min,
+            ScoreBound::Exclusive(min) => score > min,
+        }
+    }
+
+    fn below_max(self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(max) => score <= max,
+            ScoreBound::Exclusive(max) => score < max,
+        }
+    }
+
+    fn value(self) -> f64 {
+        match self {
+            ScoreBound::Inclusive(v) | ScoreBound::Exclusive(v) => v,
+        }
+    }
+}
+
+impl LexBound {
+    fn above_min(&self, member: &str) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(min) => member >= min.as_str(),
+            LexBound::Exclusive(min) => member > min.as_str(),
+        }
+    }
+
+    fn below_max(&self, member: &str) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(max) => member <= max.as_str(),
+            LexBound::Exclusive(max) => member < max.as_str(),
+        }
+    }
+
+    fn value(&self) -> Option<&str> {
+        match self {
+            LexBound::Min | LexBound::Max => None,
+            LexBound::Inclusive(v) | LexBound::Exclusive(v) => Some(v),
+        }
+    }
+}
+
+type Members<'a> = Box<dyn Iterator<Item = (&'a String, f64)> + 'a>;
+
+/// A sorted set: members are unique and ordered by score, then lexicographically.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedSet {
+    scores: HashMap<String, f64>,
+    ordered: BTreeSet<ScoredMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ScoredMember(f64, String);
+
+impl Eq for ScoredMember {}
+
+impl PartialOrd for ScoredMember {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredMember {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .total_cmp(&other.0)
+            .then_with(|| self.1.cmp(&other.1))
+    }
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add member or update its score, returns true if the member is new.
+    pub fn insert(&mut self, member: String, score: f64) -> bool {
+        // -0 and 0 are the same score, but not for the total order of the index
+        let score = score + 0.0;
+        match self.scores.insert(member.clone(), score) {
+            Some(old) => {
+                self.ordered.remove(&ScoredMember(old, member.clone()));
+                self.ordered.insert(ScoredMember(score, member));
+                false
+            }
+            None => {
+                self.ordered.insert(ScoredMember(score, member));
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &str) -> Option<f64> {
+        let score = self.scores.remove(member)?;
+        self.ordered
+            .remove(&ScoredMember(score, member.to_string()));
+        Some(score)
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Members and their scores from the lowest score to the highest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&String, f64)> {
+        self.ordered.iter().map(|m| (&m.1, m.0))
+    }
+
+    /// Remove and return the member with the lowest score, or the highest one if max.
+    pub fn pop(&mut self, max: bool) -> Option<(String, f64)> {
+        let ScoredMember(score, member) = if max {
+            self.ordered.pop_last()
+        } else {
+            self.ordered.pop_first()
+        }?;
+        self.scores.remove(&member);
+        Some((member, score))
+    }
+
+    /// The 0-based rank of member from the lowest score, or from the highest one if rev.
+    pub fn rank(&self, member: &str, rev: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let below = self
+            .ordered
+            .range(..ScoredMember(score, member.to_string()))
+            .count();
+        Some(if rev { self.len() - 1 - below } else { below })
+    }
+
+    /// The members from rank start to stop included, negative ranks counting from the end.
+    pub fn range_by_rank(&self, start: i64, stop: i64, rev: bool) -> Members<'_> {
+        let len = self.len() as i64;
+        let start = if start < 0 { start + len } else { start }.max(0);
+        let stop = if stop < 0 { stop + len } else { stop }.min(len - 1);
+        if start > stop {
+            return Box::new(std::iter::empty());
+        }
+        let (skip, take) = (start as usize, (stop - start + 1) as usize);
+        if rev {
+            Box::new(self.iter().rev().skip(skip).take(take))
+        } else {
+            Box::new(self.iter().skip(skip).take(take))
+        }
+    }
+
+    /// The members scored between min and max, seeking the first one in the index.
+    pub fn range_by_score(&self, min: ScoreBound, max: ScoreBound, rev: bool) -> Members<'_> {
+        if rev {
+            // everything below the first member scored past max
+            let upper = match max.value() {
+                f64::INFINITY => Bound::Unbounded,
+                max => Bound::Excluded(ScoredMember(max.next_up(), String::new())),
+            };
+            let range = self.ordered.range((Bound::Unbounded, upper));
+            Box::new(
+                range
+                    .rev()
+                    .map(|m| (&m.1, m.0))
+                    .skip_while(move |(_, score)| !max.below_max(*score))
+                    .take_while(move |(_, score)| min.above_min(*score)),
+            )
+        } else {
+            let lower = ScoredMember(min.value(), String::new());
+            let range = self
+                .ordered
+                .range((Bound::Included(lower), Bound::Unbounded));
+            Box::new(
+                range
+                    .map(|m| (&m.1, m.0))
+                    .skip_while(move |(_, score)| !min.above_min(*score))
+                    .take_while(move |(_, score)| max.below_max(*score)),
+            )
+        }
+    }
+
+    /// The members between min and max, assuming they all have the same score like Redis does.
+    pub fn range_by_lex<'a>(&'a self, min: &LexBound, max: &LexBound, rev: bool) -> Members<'a> {
+        let (min, max) = (min.clone(), max.clone());
+        let bound = if rev { &max } else { &min };
+        // the score of the members, taken from the end the walk starts at
+        let end = if rev {
+            self.ordered.last()
+        } else {
+            self.ordered.first()
+        };
+        let Some(score) = end.map(|m| m.0) else {
+            return Box::new(std::iter::empty());
+        };
+        let pivot = bound
+            .value()
+            .map(|v| Bound::Included(ScoredMember(score, v.to_string())));
+        if rev {
+            let range = self
+                .ordered
+                .range((Bound::Unbounded, pivot.unwrap_or(Bound::Unbounded)));
+            Box::new(
+                range
+                    .rev()
+                    .map(|m| (&m.1, m.0))
+                    .skip_while(move |(member, _)| !max.below_max(member))
+                    .take_while(move |(member, _)| min.above_min(member)),
+            )
+        } else {
+            let range = self
+                .ordered
+                .range((pivot.unwrap_or(Bound::Unbounded), Bound::Unbounded));
+            Box::new(
+                range
+                    .map(|m| (&m.1, m.0))
+                    .skip_while(move |(member, _)| !min.above_min(member))
+                    .take_while(move |(member, _)| max.below_max(member)),
+            )
+        }
+    }
+}
+
+impl Backend {
+    /// Add members with their scores to the zset at key, creating it if needed, or update the
+    /// scores of the existing ones. A member given twice gets the last score. Returns how many
+    /// members were new.
+    pub fn zadd(&self, key: &str, members: Vec<(f64, String)>) -> Result<usize, BackendError> {
+        self.zadd_with(key, members, &ZAddOptions::default())
+    }
+
+    /// `zadd` with flags: members are skipped as options say. Returns how many members were
+    /// added, plus how many were updated with `ch`.
+    pub fn zadd_with(
+        &self,
+        key: &str,
+        members: Vec<(f64, String)>,
+        options: &ZAddOptions,
+    ) -> Result<usize, BackendError> {
+        self.upsert_zset(key, "zadd", |zset| {
+            let (mut added, mut updated) = (0, 0);
+            for (score, member) in members {
+                match zadd_member(zset, member, score, false, options)? {
+                    ZAdded::Added(_) => added += 1,
+                    ZAdded::Updated(_) => updated += 1,
+                    ZAdded::Skipped | ZAdded::Unchanged(_) => {}
+                }
+            }
+            let reply = if options.ch { added + updated } else { added };
+            Ok((reply, added + updated > 0))
+        })
+    }
+
+    /// ZADD INCR: add increment to the score of member as options allow, creating both if
+    /// needed. Returns the new score, None if the member was skipped.
+    pub fn zadd_incr(
+        &self,
+        key: &str,
+        increment: f64,
+        member: &str,
+        options: &ZAddOptions,
+    ) -> Result<Option<f64>, BackendError> {
+        self.upsert_zset(key, "zincr", |zset| {
+            match zadd_member(zset, member.to_string(), increment, true, options)? {
+                ZAdded::Skipped => Ok((None, false)),
+                ZAdded::Unchanged(score) => Ok((Some(score), false)),
+                ZAdded::Added(score) | ZAdded::Updated(score) => Ok((Some(score), true)),
+            }
+        })
+    }
+
+    /// Add increment to the score of member in the zset at key, creating both if needed.
+    /// Returns the new score.
+    pub fn zincrby(&self, key: &str, increment: f64, member: &str) -> Result<f64, BackendError> {
+        self.upsert_zset(key, "zincr", |zset| {
+            let score = zset.score(member).unwrap_or(0.0) + increment;
+            if score.is_nan() {
+                return Err(BackendError::ScoreNan);
+            }
+            zset.insert(member.to_string(), score);
+            Ok((score, true))
+        })
+    }
+
+    /// Remove members from the zset at key. Returns how many were removed.
+    pub fn zrem(&self, key: &str, members: &[String]) -> Result<usize, BackendError> {
+        Ok(self
+            .update_zset(key, "zrem", |zset| {
+                let removed = members
+                    .iter()
+                    .filter(|member| zset.remove(member).is_some())
+                    .count();
+                (removed, removed > 0)
+            })?
+            .unwrap_or(0))
+    }
+
+    /// The members selected by options with their scores, in the order of the walk.
+    pub fn zrange(
+        &self,
+        key: &str,
+        options: &ZRangeOptions,
+    ) -> Result<Vec<(String, f64)>, BackendError> {
+        let (offset, count) = match options.limit {
+            None => (0, usize::MAX),
+            Some((offset, _)) if offset < 0 => return Ok(vec![]),
+            Some((offset, count)) => (
+                offset as usize,
+                usize::try_from(count).unwrap_or(usize::MAX),
+            ),
+        };
+        Ok(self
+            .read_zset(key, |zset| {
+                let members = match &options.by {
+                    ZRangeBy::Rank(start, stop) => zset.range_by_rank(*start, *stop, options.rev),
+                    ZRangeBy::Score(min, max) => zset.range_by_score(*min, *max, options.rev),
+                    ZRangeBy::Lex(min, max) => zset.range_by_lex(min, max, options.rev),
+                };
+                members
+                    .skip(offset)
+                    .take(count)
+                    .map(|(member, score)| (member.clone(), score))
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Remove and return up to count members with the lowest scores, or the highest ones if
+    /// max, in pop order.
+    pub fn zpop(
+        &self,
+        key: &str,
+        count: usize,
+        max: bool,
+    ) -> Result<Vec<(String, f64)>, BackendError> {
+        let event = if max { "zpopmax" } else { "zpopmin" };
+        Ok(self
+            .update_zset(key, event, |zset| {
+                let popped: Vec<_> = (0..count).map_while(|_| zset.pop(max)).collect();
+                let changed = !popped.is_empty();
+                (popped, changed)
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Remove the members selected by `by`, deleting the key once empty. Returns how many were
+    /// removed.
+    pub fn zremrange(&self, key: &str, by: &ZRangeBy) -> Result<usize, BackendError> {
+        let event = match by {
+            ZRangeBy::Rank(..) => "zremrangebyrank",
+            ZRangeBy::Score(..) => "zremrangebyscore",
+            ZRangeBy::Lex(..) => "zremrangebylex",
+        };
+        Ok(self
+            .update_zset(key, event, |zset| {
+                let members: Vec<String> = match by {
+                    ZRangeBy::Rank(start, stop) => zset.range_by_rank(*start, *stop, false),
+                    ZRangeBy::Score(min, max) => zset.range_by_score(*min, *max, false),
+                    ZRangeBy::Lex(min, max) => zset.range_by_lex(min, max, false),
+                }
+                .map(|(member, _)| member.clone())
+                .collect();
+                for member in &members {
+                    zset.remove(member);
+                }
+                (members.len(), !members.is_empty())
+            })?
+            .unwrap_or(0))
+    }
+
+    // pop up to count members from the zset at key in the locked shard, deleting the key once
+    // empty
+    pub(crate) fn zpop_many(
+        &self,
+        shard: &mut Shard,
+        key: &str,
+        count: usize,
+        max: bool,
+    ) -> Vec<(String, f64)> {
+        let (popped, empty) = match shard.get_mut(key).map(|obj| &mut **obj) {
+            Some(Value::ZSet(zset)) => {
+                let popped: Vec<_> = (0..count).map_while(|_| zset.pop(max)).collect();
+                (popped, zset.is_empty())
+            }
+            _ => return Vec::new(),
+        };
+        if popped.is_empty() {
+            return popped;
+        }
+
+        let event = if max { "zpopmax" } else { "zpopmin" };
+        self.notify_keyspace_event(NotifyFlags::ZSET, event, key);
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        popped
+    }
+
+    /// The rank of member with its score, see `SortedSet::rank`.
+    pub fn zrank(
+        &self,
+        key: &str,
+        member: &str,
+        rev: bool,
+    ) -> Result<Option<(usize, f64)>, BackendError> {
+        Ok(self
+            .read_zset(key, |zset| {
+                let rank = zset.rank(member, rev)?;
+                zset.score(member).map(|score| (rank, score))
+            })?
+            .flatten())
+    }
+
+    /// How many members are scored between min and max.
+    pub fn zcount(
+        &self,
+        key: &str,
+        min: ScoreBound,
+        max: ScoreBound,
+    ) -> Result<usize, BackendError> {
+        Ok(self
+            .read_zset(key, |zset| zset.range_by_score(min, max, false).count())?
+            .unwrap_or(0))
+    }
+
+    /// How many members are between min and max, see `SortedSet::range_by_lex`.
+    pub fn zlexcount(
+        &self,
+        key: &str,
+        min: &LexBound,
+        max: &LexBound,
+    ) -> Result<usize, BackendError> {
+        Ok(self
+            .read_zset(key, |zset| zset.range_by_lex(min, max, false).count())?
+            .unwrap_or(0))
+    }
+
+    /// The scores of members in the zset at key, None for the missing ones.
+    pub fn zmscore(&self, key: &str, members: &[String]) -> Result<Vec<Option<f64>>, BackendError> {
+        Ok(self
+            .read_zset(key, |zset| {
+                members.iter().map(|member| zset.score(member)).collect()
+            })?
+            .unwrap_or_else(|| vec![None; members.len()]))
+    }
+
+    /// Random members of the zset at key with their scores, see `random_picks` for count.
+    pub fn zrandmember(&self, key: &str, count: i64) -> Result<Vec<(String, f64)>, BackendError> {
+        Ok(self
+            .read_zset(key, |zset| {
+                random_picks(zset.iter(), zset.len(), count)
+                    .into_iter()
+                    .map(|(member, score)| (member.clone(), score))
+                    .collect()
+            })?
+            .unwrap_or_default())
+    }
+
+    /// Incrementally iterate the members of the zset at key with the same cursors as `scan`.
+    pub fn zscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<(String, f64)>), BackendError> {
+        Ok(self
+            .read_zset(key, |zset| {
+                let batch = zset
+                    .iter()
+                    .map(|entry| (key_hash(entry.0), entry))
+                    .filter(|(hash, _)| *hash >= cursor)
+                    .collect();
+                let (next, page) = scan_page(batch, count);
+                let page = page
+                    .into_iter()
+                    .map(|(member, score)| (member.clone(), score))
+                    .collect();
+                (next, page)
+            })?
+            .unwrap_or_default())
+    }
+
+    pub fn zscore(&self, key: &str, member: &str) -> Result<Option<f64>, BackendError> {
+        Ok(self.read_zset(key, |zset| zset.score(member))?.flatten())
+    }
+
+    pub fn zcard(&self, key: &str) -> Result<usize, BackendError> {
+        Ok(self.read_zset(key, |zset| zset.len())?.unwrap_or(0))
+    }
+
+    /// The result of op on the sets and zsets at keys, ordered by score. Each input's scores
+    /// are multiplied by its weight, 1 past the end of weights, and plain sets score their
+    /// members 1. A union or an intersection combines the scores of a member with aggregate,
+    /// a difference keeps the unweighted scores of the first input.
+    pub fn zset_op(
+        &self,
+        op: SetOp,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<Vec<(String, f64)>, BackendError> {
+        let guard = self.read_many(keys.iter().map(String::as_str));
+        let inputs = inputs_at(keys, |key| {
+            let shard = guard.shard(key);
+            if shard.is_expired(key) {
+                return None;
+            }
+            shard.get(key).map(|obj| {
+                obj.touch();
+                &**obj
+            })
+        })?;
+        let result = combine_zsets(op, &inputs, weights, aggregate);
+        Ok(result
+            .iter()
+            .map(|(member, score)| (member.clone(), score))
+            .collect())
+    }
+
+    /// Store the result of `zset_op` in destination, replacing its value and TTL, atomically
+    /// with reading the inputs. An empty result deletes destination. Returns the size of the
+    /// result.
+    pub fn zset_op_store(
+        &self,
+        op: SetOp,
+        destination: &str,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<usize, BackendError> {
+        let mut guard = self.write_many(keys.iter().map(String::as_str).chain([destination]));
+        let result = {
+            let inputs = inputs_at(keys, |key| guard.shard_ref(key).get(key).map(|obj| &**obj))?;
+            combine_zsets(op, &inputs, weights, aggregate)
+        };
+
+        let len = result.len();
+        let shard = guard.shard(destination);
+        let existed = shard.remove(destination).is_some();
+        if result.is_empty() {
+            if existed {
+                self.notify_keyspace_event(NotifyFlags::GENERIC, "del", destination);
+            }
+            return Ok(0);
+        }
+        if !existed {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", destination);
+        }
+        shard.insert(destination.to_string(), Value::ZSet(result));
+        let event = match op {
+            SetOp::Inter => "zinterstore",
+            SetOp::Union => "zunionstore",
+            SetOp::Diff => "zdiffstore",
+        };
+        self.notify_keyspace_event(NotifyFlags::ZSET, event, destination);
+        self.serve_blocked(shard, destination);
+        Ok(len)
+    }
+
+    // run f on the zset at key, None if the key doesn't exist
+    pub(crate) fn read_zset<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&SortedSet) -> R,
+    ) -> Result<Option<R>, BackendError> {
+        let shard = self.read(key);
+        match shard.get(key) {
+            None => Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &**obj {
+                    Value::ZSet(zset) => Ok(Some(f(zset))),
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+        }
+    }
+
+    // run f on the zset at key under the write lock, creating it if needed. A created zset is
+    // only kept, and notified as new, if f added members to it. f tells whether it changed the
+    // zset, to notify the event.
+    fn upsert_zset<R>(
+        &self,
+        key: &str,
+        event: &str,
+        f: impl FnOnce(&mut SortedSet) -> Result<(R, bool), BackendError>,
+    ) -> Result<R, BackendError> {
+        let mut shard = self.write(key);
+        let created = !shard.contains_key(key);
+        if created {
+            shard.insert(key.to_string(), Value::ZSet(SortedSet::new()));
+        }
+        let result = match shard.get_mut(key) {
+            Some(obj) => {
+                obj.touch();
+                match &mut **obj {
+                    Value::ZSet(zset) => {
+                        f(zset).map(|(ret, changed)| (ret, changed, zset.is_empty()))
+                    }
+                    _ => Err(BackendError::WrongType),
+                }
+            }
+            None => unreachable!("created above"),
+        };
+        let (ret, changed, empty) = match result {
+            Ok(done) => done,
+            Err(e) => {
+                if created {
+                    shard.remove(key);
+                }
+                return Err(e);
+            }
+        };
+
+        if created && empty {
+            shard.remove(key);
+            return Ok(ret);
+        }
+        if created {
+            self.notify_keyspace_event(NotifyFlags::NEW, "new", key);
+        }
+        if changed {
+            self.notify_keyspace_event(NotifyFlags::ZSET, event, key);
+            self.serve_blocked(&mut shard, key);
+        }
+        Ok(ret)
+    }
+
+    // run f on the zset at key under the write lock, None if the key doesn't exist. f also
+    // tells whether it changed the zset: the event is notified, and the key deleted once the
+    // zset is empty.
+    fn update_zset<R>(
+        &self,
+        key: &str,
+        event: &str,
+        f: impl FnOnce(&mut SortedSet) -> (R, bool),
+    ) -> Result<Option<R>, BackendError> {
+        let mut shard = self.write(key);
+        let (ret, changed, empty) = match shard.get_mut(key) {
+            None => return Ok(None),
+            Some(obj) => {
+                obj.touch();
+                match &mut **obj {
+                    Value::ZSet(zset) => {
+                        let (ret, changed) = f(zset);
+                        (ret, changed, zset.is_empty())
+                    }
+                    _ => return Err(BackendError::WrongType),
+                }
+            }
+        };
+
+        if changed {
+            self.notify_keyspace_event(NotifyFlags::ZSET, event, key);
+        }
+        if empty {
+            shard.remove(key);
+            self.notify_keyspace_event(NotifyFlags::GENERIC, "del", key);
+        }
+        Ok(Some(ret))
+    }
+}
+
+// add or update member as ZADD does, score being an increment with incr
+fn zadd_member(
+    zset: &mut SortedSet,
+    member: String,
+    score: f64,
+    incr: bool,
+    options: &ZAddOptions,
+) -> Result<ZAdded, BackendError> {
+    let Some(old) = zset.score(&member) else {
+        if options.xx {
+            return Ok(ZAdded::Skipped);
+        }
+        zset.insert(member, score);
+        return Ok(ZAdded::Added(score));
+    };
+    if options.nx {
+        return Ok(ZAdded::Skipped);
+    }
+    let score = if incr { old + score } else { score };
+    if score.is_nan() {
+        return Err(BackendError::ScoreNan);
+    }
+    if (options.gt && score <= old) || (options.lt && score >= old) {
+        return Ok(ZAdded::Skipped);
+    }
+    if score == old {
+        return Ok(ZAdded::Unchanged(score));
+    }
+    zset.insert(member, score);
+    Ok(ZAdded::Updated(score))
+}
+
+// an input of the zset algebra, plain sets score their members 1
+#[derive(Clone, Copy)]
+enum Input<'a> {
+    Set(&'a SetMembers),
+    ZSet(&'a SortedSet),
+}
+
+impl<'a> Input<'a> {
+    fn len(self) -> usize {
+        match self {
+            Input::Set(set) => set.len(),
+            Input::ZSet(zset) => zset.len(),
+        }
+    }
+
+    fn score(self, member: &str) -> Option<f64> {
+        match self {
+            Input::Set(set) => set.contains(member).then_some(1.0),
+            Input::ZSet(zset) => zset.score(member),
+        }
+    }
+
+    fn members(self) -> Box<dyn Iterator<Item = (Cow<'a, str>, f64)> + 'a> {
+        match self {
+            Input::Set(set) => Box::new(set.iter().map(|member| (member, 1.0))),
+            Input::ZSet(zset) => Box::new(
+                zset.iter()
+                    .map(|(member, score)| (Cow::Borrowed(member.as_str()), score)),
+            ),
+        }
+    }
+}
+
+fn inputs_at<'a>(
+    keys: &[String],
+    lookup: impl Fn(&str) -> Option<&'a Value>,
+) -> Result<Vec<Option<Input<'a>>>, BackendError> {
+    keys.iter()
+        .map(|key| match lookup(key) {
+            None => Ok(None),
+            Some(Value::Set(set)) => Ok(Some(Input::Set(set))),
+            Some(Value::ZSet(zset)) => Ok(Some(Input::ZSet(zset))),
+            Some(_) => Err(BackendError::WrongType),
+        })
+        .collect()
+}
+
+fn combine_zsets(
+    op: SetOp,
+    inputs: &[Option<Input>],
+    weights: &[f64],
+    aggregate: Aggregate,
+) -> SortedSet {
+    // 0 * inf is 0 rather than NaN
+    let weighted = |i: usize, score: f64| not_nan(score * weights.get(i).copied().unwrap_or(1.0));
+    let mut result = SortedSet::new();
+    match op {
+        SetOp::Union => {
+            let mut scores: HashMap<Cow<str>, f64> = HashMap::new();
+            for (i, input) in inputs.iter().enumerate() {
+                for (member, score) in input.iter().flat_map(|input| input.members()) {
+                    let score = weighted(i, score);
+                    scores
+                        .entry(member)
+                        .and_modify(|acc| *acc = aggregate.apply(*acc, score))
+                        .or_insert(score);
+                }
+            }
+            for (member, score) in scores {
+                result.insert(member.into_owned(), score);
+            }
+        }
+        SetOp::Inter => {
+            let Some(inputs) = inputs.iter().copied().collect::<Option<Vec<_>>>() else {
+                return result;
+            };
+            // walk the smallest input, probing the others
+            let Some((first, smallest)) = inputs
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, input)| input.len())
+            else {
+                return result;
+            };
+            'members: for (member, score) in smallest.members() {
+                let mut acc = weighted(first, score);
+                for (i, input) in inputs.iter().enumerate().filter(|(i, _)| *i != first) {
+                    match input.score(&member) {
+                        Some(score) => acc = aggregate.apply(acc, weighted(i, score)),
+                        None => continue 'members,
+                    }
+                }
+                result.insert(member.into_owned(), acc);
+            }
+        }
+        SetOp::Diff => {
+            if let Some((Some(first), others)) = inputs.split_first() {
+                for (member, score) in first.members() {
+                    if others
+                        .iter()
+                        .flatten()
+                        .all(|input| input.score(&member).is_none())
+                    {
+                        result.insert(member.into_owned(), score);
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+fn not_nan(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+
+    fn members(members: &[&str]) -> Vec<String> {
+        members.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sorted_set() {
+        let mut zset = SortedSet::new();
+        assert!(zset.insert("b".to_string(), 1.0));
+        assert!(zset.insert("a".to_string(), 1.0));
+        assert!(zset.insert("c".to_string(), 0.5));
+        assert!(!zset.insert("c".to_string(), 2.0));
+
+        let members: Vec<_> = zset.iter().map(|(m, _)| m.as_str()).collect();
+        assert_eq!(members, vec!["a", "b", "c"]);
+        assert_eq!(zset.score("c"), Some(2.0));
+
+        assert_eq!(zset.remove("a"), Some(1.0));
+        assert_eq!(zset.remove("a"), None);
+        assert_eq!(zset.len(), 2);
+    }
+
+    fn scored(members: &[(&str, f64)]) -> Vec<(String, f64)> {
+        members.iter().map(|(m, s)| (m.to_string(), *s)).collect()
+    }
+
+    fn zset(members: &[(&str, f64)]) -> SortedSet {
+        let mut zset = SortedSet::new();
+        for (member, score) in members {
+            zset.insert(member.to_string(), *score);
+        }
+        zset
+    }
+
+    fn collect(members: Members) -> Vec<(String, f64)> {
+        members.map(|(m, s)| (m.clone(), s)).collect()
+    }
+
+    #[test]
+    fn test_range_by_rank() {
+        let zset = zset(&[("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+        assert_eq!(
+            collect(zset.range_by_rank(0, -1, false)),
+            scored(&[("a", 1.0), ("b", 2.0), ("c", 3.0)])
+        );
+        assert_eq!(
+            collect(zset.range_by_rank(-2, 10, false)),
+            scored(&[("b", 2.0), ("c", 3.0)])
+        );
+        assert_eq!(
+            collect(zset.range_by_rank(0, 0, true)),
+            scored(&[("c", 3.0)])
+        );
+        assert_eq!(collect(zset.range_by_rank(2, 1, false)), vec![]);
+        assert_eq!(
+            collect(SortedSet::new().range_by_rank(0, -1, false)),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_range_by_score() {
+        use ScoreBound::*;
+        let zset = zset(&[
+            ("a", 1.0),
+            ("b", 1.5),
+            ("c", 1.5),
+            ("d", f64::INFINITY),
+            ("e", -0.0),
+        ]);
+        let range = zset.range_by_score(Exclusive(1.0), Inclusive(1.5), false);
+        assert_eq!(collect(range), scored(&[("b", 1.5), ("c", 1.5)]));
+        let range = zset.range_by_score(Inclusive(f64::NEG_INFINITY), Exclusive(1.5), false);
+        assert_eq!(collect(range), scored(&[("e", 0.0), ("a", 1.0)]));
+        let range = zset.range_by_score(Inclusive(1.5), Inclusive(f64::INFINITY), true);
+        assert_eq!(
+            collect(range),
+            scored(&[("d", f64::INFINITY), ("c", 1.5), ("b", 1.5)])
+        );
+        let range = zset.range_by_score(Exclusive(1.0), Exclusive(1.5), true);
+        assert_eq!(collect(range), vec![]);
+        // min above max
+        let range = zset.range_by_score(Inclusive(3.0), Inclusive(1.0), false);
+        assert_eq!(collect(range), vec![]);
+    }
+
+    #[test]
+    fn test_range_by_lex() {
+        use LexBound::*;
+        let zset = zset(&[("a", 0.0), ("b", 0.0), ("c", 0.0), ("d", 0.0)]);
+        let range = zset.range_by_lex(&Exclusive("a".into()), &Inclusive("c".into()), false);
+        assert_eq!(collect(range), scored(&[("b", 0.0), ("c", 0.0)]));
+        let range = zset.range_by_lex(&Min, &Exclusive("c".into()), true);
+        assert_eq!(collect(range), scored(&[("b", 0.0), ("a", 0.0)]));
+        let range = zset.range_by_lex(&Inclusive("bb".into()), &Max, false);
+        assert_eq!(collect(range), scored(&[("c", 0.0), ("d", 0.0)]));
+        assert_eq!(collect(zset.range_by_lex(&Max, &Min, false)), vec![]);
+    }
+
+    #[test]
+    fn test_zincrby_zrank_zcount() {
+        let backend = Backend::new();
+        assert_eq!(backend.zincrby("z", 2.5, "a"), Ok(2.5));
+        assert_eq!(backend.zincrby("z", -1.0, "a"), Ok(1.5));
+        backend
+            .zadd("z", vec![(1.0, "b".to_string()), (3.0, "c".to_string())])
+            .unwrap();
+
+        assert_eq!(backend.zrank("z", "a", false), Ok(Some((1, 1.5))));
+        assert_eq!(backend.zrank("z", "c", true), Ok(Some((0, 3.0))));
+        assert_eq!(backend.zrank("z", "x", false), Ok(None));
+        assert_eq!(backend.zrank("missing", "a", false), Ok(None));
+
+        use ScoreBound::*;
+        assert_eq!(backend.zcount("z", Exclusive(1.0), Inclusive(3.0)), Ok(2));
+        assert_eq!(
+            backend.zcount("z", Inclusive(f64::NEG_INFINITY), Inclusive(f64::INFINITY)),
+            Ok(3)
+        );
+
+        backend
+            .zadd("z", vec![(f64::INFINITY, "inf".to_string())])
+            .unwrap();
+        assert_eq!(
+            backend.zincrby("z", f64::NEG_INFINITY, "inf"),
+            Err(BackendError::ScoreNan)
+        );
+        assert_eq!(backend.zscore("z", "inf"), Ok(Some(f64::INFINITY)));
+    }
+
+    #[test]
+    fn test_zpop() {
+        let backend = Backend::new();
+        let members = vec![
+            (1.0, "a".to_string()),
+            (2.0, "b".to_string()),
+            (3.0, "c".to_string()),
+        ];
+        backend.zadd("z", members).unwrap();
+        assert_eq!(
+            backend.zpop("z", 2, true),
+            Ok(scored(&[("c", 3.0), ("b", 2.0)]))
+        );
+        assert_eq!(backend.zpop("z", 5, false), Ok(scored(&[("a", 1.0)])));
+        assert!(!backend.contains_key("z"));
+        assert_eq!(backend.zpop("z", 1, false), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_zlexcount() {
+        use LexBound::*;
+        let backend = Backend::new();
+        let members = ["apple", "apricot", "banana", "cherry"]
+            .iter()
+            .map(|m| (0.0, m.to_string()))
+            .collect();
+        backend.zadd("z", members).unwrap();
+        // autocomplete: everything starting with "ap"
+        assert_eq!(
+            backend.zlexcount("z", &Inclusive("ap".into()), &Exclusive("aq".into())),
+            Ok(2)
+        );
+        assert_eq!(backend.zlexcount("z", &Min, &Max), Ok(4));
+        assert_eq!(backend.zlexcount("missing", &Min, &Max), Ok(0));
+    }
+
+    #[test]
+    fn test_zrange_limit() {
+        let backend = Backend::new();
+        let members = (0..10).map(|i| (i as f64, format!("m{}", i))).collect();
+        backend.zadd("z", members).unwrap();
+        let mut options = ZRangeOptions {
+            by: ZRangeBy::Score(ScoreBound::Inclusive(2.0), ScoreBound::Inclusive(8.0)),
+            rev: true,
+            limit: Some((1, 2)),
+        };
+        assert_eq!(
+            backend.zrange("z", &options),
+            Ok(scored(&[("m7", 7.0), ("m6", 6.0)]))
+        );
+        options.limit = Some((5, -1));
+        assert_eq!(
+            backend.zrange("z", &options),
+            Ok(scored(&[("m3", 3.0), ("m2", 2.0)]))
+        );
+        options.limit = Some((-1, 2));
+        assert_eq!(backend.zrange("z", &options), Ok(vec![]));
+        assert_eq!(backend.zrange("missing", &options), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_zadd_zrem() {
+        let backend = Backend::new();
+        let added = backend.zadd(
+            "z",
+            vec![
+                (1.0, "a".to_string()),
+                (2.0, "b".to_string()),
+                (3.0, "a".to_string()),
+            ],
+        );
+        // the last score of a member given twice wins
+        assert_eq!(added, Ok(2));
+        assert_eq!(backend.zscore("z", "a"), Ok(Some(3.0)));
+        assert_eq!(
+            backend.zadd("z", vec![(f64::INFINITY, "b".to_string())]),
+            Ok(0)
+        );
+        assert_eq!(backend.zscore("z", "b"), Ok(Some(f64::INFINITY)));
+        assert_eq!(backend.zscore("z", "x"), Ok(None));
+        assert_eq!(backend.zscore("missing", "a"), Ok(None));
+        assert_eq!(backend.zcard("z"), Ok(2));
+
+        assert_eq!(backend.zrem("z", &members(&["a", "x"])), Ok(1));
+        assert_eq!(backend.zrem("missing", &members(&["a"])), Ok(0));
+
+        // removing the last member deletes the key
+        assert_eq!(backend.zrem("z", &members(&["b"])), Ok(1));
+        assert!(!backend.contains_key("z"));
+        assert_eq!(backend.zcard("z"), Ok(0));
+
+        backend.set("s".to_string(), RespFrame::BulkString(b"v".into()));
+        assert_eq!(
+            backend.zadd("s", vec![(1.0, "a".to_string())]),
+            Err(BackendError::WrongType)
+        );
+        assert_eq!(backend.zscore("s", "a"), Err(BackendError::WrongType));
+    }
+
+    #[test]
+    fn test_zset_op() {
+        let backend = Backend::new();
+        backend
+            .zadd("z1", vec![(1.0, "a".to_string()), (2.0, "b".to_string())])
+            .unwrap();
+        backend
+            .zadd("z2", vec![(3.0, "b".to_string()), (4.0, "c".to_string())])
+            .unwrap();
+        backend.sadd("s", members(&["c"])).unwrap();
+
+        let keys = members(&["z1", "z2", "s"]);
+        let union = backend.zset_op(SetOp::Union, &keys, &[], Aggregate::Sum);
+        assert_eq!(union, Ok(scored(&[("a", 1.0), ("b", 5.0), ("c", 5.0)])));
+
+        // weights past the given ones are 1
+        let keys = members(&["z1", "z2"]);
+        let inter = backend.zset_op(SetOp::Inter, &keys, &[2.0], Aggregate::Max);
+        assert_eq!(inter, Ok(scored(&[("b", 4.0)])));
+        let inter = backend.zset_op(SetOp::Inter, &members(&["z1", "x"]), &[], Aggregate::Sum);
+        assert_eq!(inter, Ok(vec![]));
+
+        let diff = backend.zset_op(SetOp::Diff, &keys, &[], Aggregate::Sum);
+        assert_eq!(diff, Ok(scored(&[("a", 1.0)])));
+
+        // inf + -inf and 0 * inf are 0
+        backend
+            .zadd("inf", vec![(f64::INFINITY, "a".to_string())])
+            .unwrap();
+        backend
+            .zadd("ninf", vec![(f64::NEG_INFINITY, "a".to_string())])
+            .unwrap();
+        let union = backend.zset_op(
+            SetOp::Union,
+            &members(&["inf", "ninf"]),
+            &[],
+            Aggregate::Sum,
+        );
+        assert_eq!(union, Ok(scored(&[("a", 0.0)])));
+        let union = backend.zset_op(SetOp::Union, &members(&["inf"]), &[0.0], Aggregate::Sum);
+        assert_eq!(union, Ok(scored(&[("a", 0.0)])));
+
+        backend.set("str".to_string(), RespFrame::BulkString(b"v".into()));
+        assert_eq!(
+            backend.zset_op(SetOp::Union, &members(&["z1", "str"]), &[], Aggregate::Sum),
+            Err(BackendError::WrongType)
+        );
+    }
+
+    #[test]
+    fn test_zset_op_store() {
+        let backend = Backend::new();
+        backend
+            .zadd("z1", vec![(1.0, "a".to_string()), (2.0, "b".to_string())])
+            .unwrap();
+        backend.zadd("z2", vec![(3.0, "b".to_string())]).unwrap();
+
+        let keys = members(&["z1", "z2"]);
+        let stored = backend.zset_op_store(SetOp::Union, "dst", &keys, &[], Aggregate::Min);
+        assert_eq!(stored, Ok(2));
+        assert_eq!(backend.zscore("dst", "b"), Ok(Some(2.0)));
+
+        // the destination may be one of the inputs
+        let stored = backend.zset_op_store(SetOp::Inter, "z1", &keys, &[], Aggregate::Sum);
+        assert_eq!(stored, Ok(1));
+        assert_eq!(backend.zscore("z1", "b"), Ok(Some(5.0)));
+
+        // an empty result deletes the destination
+        let keys = members(&["z2", "z1"]);
+        let stored = backend.zset_op_store(SetOp::Diff, "dst", &keys, &[], Aggregate::Sum);
+        assert_eq!(stored, Ok(0));
+        assert!(!backend.contains_key("dst"));
+    }
+
+    #[test]
+    fn test_zremrange() {
+        let backend = Backend::new();
+        let members = [("a", 1.0), ("b", 2.0), ("c", 3.0), ("d", 4.0)];
+        backend
+            .zadd("z", members.map(|(m, s)| (s, m.to_string())).to_vec())
+            .unwrap();
+
+        assert_eq!(backend.zremrange("z", &ZRangeBy::Rank(-1, -1)), Ok(1));
+        let by = ZRangeBy::Score(ScoreBound::Exclusive(1.0), ScoreBound::Inclusive(2.0));
+        assert_eq!(backend.zremrange("z", &by), Ok(1));
+        assert_eq!(backend.zremrange("z", &by), Ok(0));
+        assert_eq!(backend.zremrange("missing", &by), Ok(0));
+
+        // removing the last members deletes the key
+        let by = ZRangeBy::Lex(LexBound::Min, LexBound::Max);
+        assert_eq!(backend.zremrange("z", &by), Ok(2));
+        assert!(!backend.contains_key("z"));
+    }
+
+    #[test]
+    fn test_zmscore_zrandmember_zscan() {
+        let backend = Backend::new();
+        let entries = [("a", 1.0), ("b", 2.0), ("c", 3.0)];
+        backend
+            .zadd("z", entries.map(|(m, s)| (s, m.to_string())).to_vec())
+            .unwrap();
+
+        let scores = backend.zmscore("z", &members(&["b", "x"]));
+        assert_eq!(scores, Ok(vec![Some(2.0), None]));
+        let scores = backend.zmscore("missing", &members(&["a"]));
+        assert_eq!(scores, Ok(vec![None]));
+
+        let mut picked = backend.zrandmember("z", 5).unwrap();
+        picked.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(picked, scored(&entries));
+        // a negative count may repeat members
+        assert_eq!(backend.zrandmember("z", -7).unwrap().len(), 7);
+        assert_eq!(backend.zrandmember("missing", -7), Ok(vec![]));
+
+        let mut cursor = 0;
+        let mut scanned = Vec::new();
+        loop {
+            let (next, page) = backend.zscan("z", cursor, 1).unwrap();
+            scanned.extend(page);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        scanned.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(scanned, scored(&entries));
+    }
+
+    #[test]
+    fn test_zadd_options() {
+        let backend = Backend::new();
+        backend.zadd("z", vec![(1.0, "a".to_string())]).unwrap();
+        let pairs = |pairs: &[(f64, &str)]| {
+            pairs
+                .iter()
+                .map(|(score, member)| (*score, member.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let xx = ZAddOptions {
+            xx: true,
+            ch: true,
+            ..Default::default()
+        };
+        let added = backend.zadd_with("z", pairs(&[(2.0, "a"), (1.0, "b")]), &xx);
+        assert_eq!(added, Ok(1));
+        assert_eq!(backend.zscore("z", "b"), Ok(None));
+
+        let gt = ZAddOptions {
+            gt: true,
+            ..Default::default()
+        };
+        let added = backend.zadd_with("z", pairs(&[(0.0, "a"), (1.0, "b")]), &gt);
+        assert_eq!(added, Ok(1));
+        assert_eq!(backend.zscore("z", "a"), Ok(Some(2.0)));
+
+        // INCR returns None for a skipped member
+        let nx = ZAddOptions {
+            nx: true,
+            ..Default::default()
+        };
+        assert_eq!(backend.zadd_incr("z", 1.0, "a", &nx), Ok(None));
+        let lt = ZAddOptions {
+            lt: true,
+            ..Default::default()
+        };
+        assert_eq!(backend.zadd_incr("z", -1.5, "a", &lt), Ok(Some(0.5)));
+        assert_eq!(backend.zadd_incr("z", 1.0, "a", &lt), Ok(None));
+
+        // nothing to update creates no key
+        assert_eq!(backend.zadd_with("x", pairs(&[(1.0, "a")]), &xx), Ok(0));
+        assert!(!backend.contains_key("x"));
+    }
+}
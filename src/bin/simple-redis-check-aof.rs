@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use simple_redis::check_aof;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Check an append only file offline, counting its commands by name, and the keys of its RDB
+/// preamble if it has one, and finding where a replay would stop: at a bad command, or at
+/// what a crash cut short at the end.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Truncate the file to the part that replays
+    #[arg(long)]
+    fix: bool,
+    /// The append only file, e.g. appendonly.aof
+    file: PathBuf,
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let data = std::fs::read(&args.file)
+        .with_context(|| format!("failed to read {}", args.file.display()))?;
+    let check = check_aof(&data);
+    if let Some(keys) = check.preamble_keys {
+        println!("RDB preamble: {} keys", keys);
+    }
+    for (name, count) in &check.commands {
+        println!("{}: {}", name, count);
+    }
+    println!(
+        "AOF analyzed: filename={}, size={}, ok_up_to={}, diff={}",
+        args.file.display(),
+        data.len(),
+        check.valid_len,
+        data.len() - check.valid_len
+    );
+    let Some(error) = check.error else {
+        println!("AOF is valid");
+        return Ok(ExitCode::SUCCESS);
+    };
+    println!("{}", error);
+    if !args.fix {
+        println!(
+            "AOF {} is not valid. Use the --fix option to try fixing it.",
+            args.file.display()
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+    OpenOptions::new()
+        .write(true)
+        .open(&args.file)?
+        .set_len(check.valid_len as u64)
+        .with_context(|| format!("failed to truncate {}", args.file.display()))?;
+    println!("Successfully truncated AOF {}", args.file.display());
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use simple_redis::check_dump;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Check a dump file offline: every value is decoded and the checksum verified, then the keys
+/// are counted by type.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// The dump file, e.g. dump.rdb
+    file: PathBuf,
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let data = std::fs::read(&args.file)
+        .with_context(|| format!("failed to read {}", args.file.display()))?;
+    println!(
+        "Checking RDB file {} ({} bytes)",
+        args.file.display(),
+        data.len()
+    );
+    let check = match check_dump(&data) {
+        Ok(check) => check,
+        Err(e) => {
+            println!("--- RDB ERROR DETECTED ---");
+            println!("{}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    println!("RDB version {}", check.version);
+    for (type_name, count) in &check.keys {
+        println!("{} keys: {}", type_name, count);
+    }
+    println!("keys with a TTL: {}", check.expires);
+    match check.checksum {
+        true => println!("checksum OK"),
+        false => println!("no checksum to verify"),
+    }
+    println!("\\o/ RDB looks OK! \\o/");
+    Ok(ExitCode::SUCCESS)
+}
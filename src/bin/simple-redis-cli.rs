@@ -0,0 +1,164 @@
+use anyhow::{bail, Result};
+use bytes::{Bytes, BytesMut};
+use clap::Parser;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use simple_redis::client::{Client, Pipeline};
+use simple_redis::{RespArray, RespDecode, RespError, RespFrame};
+use std::io::Read;
+use std::path::PathBuf;
+
+// the requests of --pipe sent with each write
+const PIPE_BATCH: usize = 1000;
+
+/// A command line client of simple-redis: runs the command given, or prompts for commands
+/// and prints their replies as redis-cli does.
+#[derive(Debug, Parser)]
+#[command(version, disable_help_flag = true)]
+struct Args {
+    /// The host of the server
+    #[arg(short = 'h', default_value = "127.0.0.1")]
+    host: String,
+    /// The port of the server
+    #[arg(short = 'p', default_value_t = 6379)]
+    port: u16,
+    /// The password to AUTH with
+    #[arg(short = 'a')]
+    password: Option<String>,
+    /// The user to AUTH as, with the password
+    #[arg(long)]
+    user: Option<String>,
+    /// Send the commands read from stdin, in RESP or inline, and report how many replies
+    /// were errors
+    #[arg(long)]
+    pipe: bool,
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+    /// The command to run instead of prompting, e.g. SET k v
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let addr = format!("{}:{}", args.host, args.port);
+    let mut client = Client::connect(&addr).await?;
+    if let Some(password) = &args.password {
+        let mut auth = vec!["AUTH"];
+        auth.extend(args.user.as_deref());
+        auth.push(password);
+        if let RespFrame::Error(e) = client.request(auth).await? {
+            bail!("AUTH failed: {}", e.as_str());
+        }
+    }
+
+    if args.pipe {
+        pipe(&mut client).await
+    } else if !args.command.is_empty() {
+        println!("{}", client.request(&args.command).await?);
+        Ok(())
+    } else {
+        repl(&mut client, &addr).await
+    }
+}
+
+// prompt for commands until EOF or QUIT, keeping them in a history file
+async fn repl(client: &mut Client, addr: &str) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // there's none yet the first time
+        let _ = editor.load_history(path);
+    }
+    loop {
+        let line = match editor.readline(&format!("{}> ", addr)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let mut buf = BytesMut::from(format!("{}\n", line).as_bytes());
+        let args = match RespArray::decode_inline(&mut buf) {
+            Ok(array) if array.is_empty() => continue,
+            Ok(array) => arguments(array)?,
+            Err(e) => {
+                println!("Invalid argument(s): {}", e);
+                continue;
+            }
+        };
+        editor.add_history_entry(line.as_str())?;
+        let quit = args[0].eq_ignore_ascii_case(b"quit") || args[0].eq_ignore_ascii_case(b"exit");
+        if quit {
+            break;
+        }
+        println!("{}", client.request(&args).await?);
+    }
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+// send the requests read from stdin in batches, then print how many replies were errors
+async fn pipe(client: &mut Client) -> Result<()> {
+    let mut input = Vec::new();
+    std::io::stdin().read_to_end(&mut input)?;
+    let mut buf = BytesMut::from(&input[..]);
+    let mut pipeline = Pipeline::new();
+    let (mut errors, mut replies) = (0, 0);
+    loop {
+        let request = match buf.first() {
+            None => None,
+            Some(b'*') => Some(RespArray::decode(&mut buf)),
+            Some(_) => Some(RespArray::decode_inline(&mut buf)),
+        };
+        let request = match request {
+            Some(Ok(array)) if array.is_empty() => continue,
+            Some(Ok(array)) => Some(arguments(array)?),
+            // a last line without a newline
+            Some(Err(RespError::NotComplete)) if !buf.starts_with(b"*") => {
+                buf.extend_from_slice(b"\n");
+                continue;
+            }
+            Some(Err(e)) => bail!("invalid request: {}", e),
+            None => None,
+        };
+        let done = request.is_none();
+        if let Some(args) = request {
+            pipeline = pipeline.cmd(args);
+        }
+        if pipeline.len() == PIPE_BATCH || (done && !pipeline.is_empty()) {
+            for reply in client.pipeline(std::mem::take(&mut pipeline)).await? {
+                if let RespFrame::Error(e) = &reply {
+                    eprintln!("{}", e.as_str());
+                    errors += 1;
+                }
+                replies += 1;
+            }
+        }
+        if done {
+            break;
+        }
+    }
+    println!(
+        "All data transferred. errors: {}, replies: {}",
+        errors, replies
+    );
+    Ok(())
+}
+
+// the arguments of a request, which are bulk strings
+fn arguments(array: RespArray) -> Result<Vec<Bytes>> {
+    array
+        .iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(s) => Ok(Bytes::copy_from_slice(s)),
+            arg => bail!("expected a bulk string argument, got {:?}", arg),
+        })
+        .collect()
+}
+
+fn history_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".simple_redis_cli_history"))
+}
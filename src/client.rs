@@ -0,0 +1,537 @@
+use crate::network::RespFrameCodec;
+use crate::{BulkString, RespArray, RespFrame};
+use anyhow::{anyhow, bail, Result};
+use bytes::Bytes;
+use futures::{SinkExt, Stream, StreamExt};
+use std::collections::{HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::Framed;
+
+/// An async client of a simple-redis (or Redis) server, speaking RESP over the same codec as
+/// the server.
+///
+/// The typed methods turn error replies into errors; `request` returns any reply as it is.
+#[derive(Debug)]
+pub struct Client {
+    framed: Framed<TcpStream, RespFrameCodec>,
+}
+
+/// Requests sent together by `Client::pipeline`, their replies read once they're all sent.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    requests: Vec<RespFrame>,
+}
+
+/// A message published to a channel the `Subscriber` subscribed to, or matching one of its
+/// patterns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub channel: String,
+    pub payload: Bytes,
+    /// The pattern the channel matched, for pattern subscriptions.
+    pub pattern: Option<String>,
+}
+
+/// A connection in subscribed mode, a stream of the messages published to its channels.
+#[derive(Debug)]
+pub struct Subscriber {
+    framed: Framed<TcpStream, RespFrameCodec>,
+    // messages read while waiting for the confirmation of a subscription
+    pending: VecDeque<Message>,
+    channels: HashSet<String>,
+    patterns: HashSet<String>,
+}
+
+impl Client {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Ok(Self {
+            framed: Framed::new(stream, RespFrameCodec::client()),
+        })
+    }
+
+    /// The local address of the connection.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.framed.get_ref().local_addr()?)
+    }
+
+    /// Send a command, e.g. `["SET", "k", "v"]`, and return its reply, an error reply too.
+    pub async fn request<I, A>(&mut self, args: I) -> Result<RespFrame>
+    where
+        I: IntoIterator<Item = A>,
+        A: AsRef<[u8]>,
+    {
+        self.framed.send(RespFrame::from(command(args))).await?;
+        self.read().await
+    }
+
+    /// Send the requests of the pipeline with a single write, then read their replies, in
+    /// the same order, error replies included.
+    pub async fn pipeline(&mut self, pipeline: Pipeline) -> Result<Vec<RespFrame>> {
+        let len = pipeline.requests.len();
+        for request in pipeline.requests {
+            self.framed.feed(request).await?;
+        }
+        SinkExt::<RespFrame>::flush(&mut self.framed).await?;
+        let mut replies = Vec::with_capacity(len);
+        for _ in 0..len {
+            replies.push(self.read().await?);
+        }
+        Ok(replies)
+    }
+
+    pub async fn ping(&mut self) -> Result<()> {
+        let reply = self.call(["PING"]).await?;
+        match reply {
+            RespFrame::SimpleString(s) if s.as_str() == "PONG" => Ok(()),
+            reply => unexpected(reply),
+        }
+    }
+
+    pub async fn get(&mut self, key: &str) -> Result<Option<Bytes>> {
+        bulk(self.call(["GET", key]).await?)
+    }
+
+    pub async fn set(&mut self, key: &str, value: impl AsRef<[u8]>) -> Result<()> {
+        ok(self.call([b"SET", key.as_bytes(), value.as_ref()]).await?)
+    }
+
+    pub async fn del(&mut self, keys: &[&str]) -> Result<i64> {
+        integer(self.call(["DEL"].iter().chain(keys)).await?)
+    }
+
+    pub async fn incr(&mut self, key: &str) -> Result<i64> {
+        integer(self.call(["INCR", key]).await?)
+    }
+
+    /// Set the TTL of key in seconds, returns whether the key exists.
+    pub async fn expire(&mut self, key: &str, seconds: i64) -> Result<bool> {
+        let seconds = seconds.to_string();
+        Ok(integer(self.call(["EXPIRE", key, &seconds]).await?)? == 1)
+    }
+
+    /// The TTL of key in seconds, -1 if it has none and -2 if it doesn't exist.
+    pub async fn ttl(&mut self, key: &str) -> Result<i64> {
+        integer(self.call(["TTL", key]).await?)
+    }
+
+    /// Set a field of a hash, returns how many fields were added.
+    pub async fn hset(&mut self, key: &str, field: &str, value: impl AsRef<[u8]>) -> Result<i64> {
+        let args = [b"HSET", key.as_bytes(), field.as_bytes(), value.as_ref()];
+        integer(self.call(args).await?)
+    }
+
+    pub async fn hget(&mut self, key: &str, field: &str) -> Result<Option<Bytes>> {
+        bulk(self.call(["HGET", key, field]).await?)
+    }
+
+    pub async fn hgetall(&mut self, key: &str) -> Result<Vec<(String, Bytes)>> {
+        field_values(self.call(["HGETALL", key]).await?)
+    }
+
+    /// Push values to the head of a list, returns its length.
+    pub async fn lpush<V: AsRef<[u8]>>(&mut self, key: &str, values: &[V]) -> Result<i64> {
+        integer(self.call(push_args("LPUSH", key, values)).await?)
+    }
+
+    /// Push values to the tail of a list, returns its length.
+    pub async fn rpush<V: AsRef<[u8]>>(&mut self, key: &str, values: &[V]) -> Result<i64> {
+        integer(self.call(push_args("RPUSH", key, values)).await?)
+    }
+
+    pub async fn lrange(&mut self, key: &str, start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let (start, stop) = (start.to_string(), stop.to_string());
+        let reply = self.call(["LRANGE", key, &start, &stop]).await?;
+        elements(reply)?.into_iter().map(bulk_value).collect()
+    }
+
+    /// Add members to a set, returns how many weren't members already.
+    pub async fn sadd<M: AsRef<[u8]>>(&mut self, key: &str, members: &[M]) -> Result<i64> {
+        integer(self.call(push_args("SADD", key, members)).await?)
+    }
+
+    pub async fn smembers(&mut self, key: &str) -> Result<Vec<Bytes>> {
+        let reply = self.call(["SMEMBERS", key]).await?;
+        elements(reply)?.into_iter().map(bulk_value).collect()
+    }
+
+    /// Publish a message to a channel, returns how many subscribers received it.
+    pub async fn publish(&mut self, channel: &str, message: impl AsRef<[u8]>) -> Result<i64> {
+        let args = [b"PUBLISH", channel.as_bytes(), message.as_ref()];
+        integer(self.call(args).await?)
+    }
+
+    /// Subscribe to channels, turning the connection into a stream of the messages published
+    /// to them.
+    pub async fn subscribe(self, channels: &[&str]) -> Result<Subscriber> {
+        let mut subscriber = Subscriber {
+            framed: self.framed,
+            pending: VecDeque::new(),
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+        };
+        subscriber.subscribe(channels).await?;
+        Ok(subscriber)
+    }
+
+    // send a command, an error reply is an error
+    async fn call<I, A>(&mut self, args: I) -> Result<RespFrame>
+    where
+        I: IntoIterator<Item = A>,
+        A: AsRef<[u8]>,
+    {
+        match self.request(args).await? {
+            RespFrame::Error(e) => Err(anyhow!("{}", e.as_str())),
+            reply => Ok(reply),
+        }
+    }
+
+    async fn read(&mut self) -> Result<RespFrame> {
+        match self.framed.next().await {
+            Some(reply) => reply,
+            None => bail!("connection closed"),
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a command, e.g. `["INCR", "k"]`.
+    pub fn cmd<I, A>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: AsRef<[u8]>,
+    {
+        self.requests.push(command(args).into());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+impl Subscriber {
+    pub async fn subscribe(&mut self, channels: &[&str]) -> Result<()> {
+        self.confirmed("subscribe", channels).await
+    }
+
+    pub async fn psubscribe(&mut self, patterns: &[&str]) -> Result<()> {
+        self.confirmed("psubscribe", patterns).await
+    }
+
+    pub async fn unsubscribe(&mut self, channels: &[&str]) -> Result<()> {
+        self.confirmed("unsubscribe", channels).await
+    }
+
+    pub async fn punsubscribe(&mut self, patterns: &[&str]) -> Result<()> {
+        self.confirmed("punsubscribe", patterns).await
+    }
+
+    // send the command then wait for the confirmation of each of the channels, all of those
+    // subscribed to if there are none, keeping the messages received meanwhile for the stream
+    async fn confirmed(&mut self, kind: &str, channels: &[&str]) -> Result<()> {
+        self.framed
+            .send(RespFrame::from(command([kind].iter().chain(channels))))
+            .await?;
+        let mut confirmations = 0;
+        loop {
+            let frame = match self.framed.next().await {
+                Some(frame) => frame?,
+                None => bail!("connection closed"),
+            };
+            let elements = match frame {
+                RespFrame::Error(e) => bail!("{}", e.as_str()),
+                frame => elements(frame)?,
+            };
+            if let Some(message) = message(&elements)? {
+                self.pending.push_back(message);
+                continue;
+            }
+            let [name, channel, RespFrame::Integer(_)] = &elements[..] else {
+                return unexpected(RespArray::new(elements).into());
+            };
+            if string(bulk_value(name.clone())?)? != kind {
+                continue;
+            }
+            let subscribed = match kind {
+                "subscribe" | "unsubscribe" => &mut self.channels,
+                _ => &mut self.patterns,
+            };
+            // a null channel when unsubscribing from all of them while there were none
+            let Some(channel) = bulk(channel.clone())? else {
+                return Ok(());
+            };
+            let channel = string(channel)?;
+            if kind.ends_with("unsubscribe") {
+                subscribed.remove(&channel);
+            } else {
+                subscribed.insert(channel);
+            }
+            confirmations += 1;
+            let done = match channels.len() {
+                0 => subscribed.is_empty(),
+                len => confirmations >= len,
+            };
+            if done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Stream for Subscriber {
+    type Item = Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(message) = self.pending.pop_front() {
+            return Poll::Ready(Some(Ok(message)));
+        }
+        loop {
+            let frame = match self.framed.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            // confirmations of later subscriptions are skipped
+            let message = elements(frame).and_then(|elements| message(&elements));
+            match message {
+                Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => continue,
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+// a request, as an array of bulk strings
+pub(crate) fn command<I, A>(args: I) -> RespArray
+where
+    I: IntoIterator<Item = A>,
+    A: AsRef<[u8]>,
+{
+    let args = args
+        .into_iter()
+        .map(|arg| BulkString::from(arg.as_ref()).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(args)
+}
+
+pub(crate) fn push_args<'a, V: AsRef<[u8]>>(
+    name: &'a str,
+    key: &'a str,
+    values: &'a [V],
+) -> Vec<&'a [u8]> {
+    [name.as_bytes(), key.as_bytes()]
+        .into_iter()
+        .chain(values.iter().map(AsRef::as_ref))
+        .collect()
+}
+
+// a published message, None for the confirmation of a subscription
+fn message(elements: &[RespFrame]) -> Result<Option<Message>> {
+    let text = |frame: &RespFrame| string(bulk_value(frame.clone())?);
+    let message = match elements {
+        [kind, channel, payload] if text(kind)? == "message" => Message {
+            channel: text(channel)?,
+            payload: bulk_value(payload.clone())?,
+            pattern: None,
+        },
+        [kind, pattern, channel, payload] if text(kind)? == "pmessage" => Message {
+            channel: text(channel)?,
+            payload: bulk_value(payload.clone())?,
+            pattern: Some(text(pattern)?),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(message))
+}
+
+fn unexpected<T>(reply: RespFrame) -> Result<T> {
+    bail!("unexpected reply: {:?}", reply)
+}
+
+pub(crate) fn ok(reply: RespFrame) -> Result<()> {
+    match reply {
+        RespFrame::SimpleString(s) if s.as_str() == "OK" => Ok(()),
+        reply => unexpected(reply),
+    }
+}
+
+pub(crate) fn integer(reply: RespFrame) -> Result<i64> {
+    match reply {
+        RespFrame::Integer(n) => Ok(n),
+        reply => unexpected(reply),
+    }
+}
+
+// a bulk string reply, None for the null of a missing key
+pub(crate) fn bulk(reply: RespFrame) -> Result<Option<Bytes>> {
+    match reply {
+        RespFrame::NullBulkString(_) | RespFrame::Null(_) => Ok(None),
+        reply => bulk_value(reply).map(Some),
+    }
+}
+
+// the bytes of a string element, simple or bulk
+pub(crate) fn bulk_value(reply: RespFrame) -> Result<Bytes> {
+    match reply {
+        RespFrame::BulkString(s) => Ok(s.0),
+        RespFrame::SimpleString(s) => Ok(Bytes::from(s.as_str().to_string())),
+        reply => unexpected(reply),
+    }
+}
+
+// the fields of a hash with their values, a map in RESP3
+pub(crate) fn field_values(reply: RespFrame) -> Result<Vec<(String, Bytes)>> {
+    match reply {
+        RespFrame::Map(map) => map
+            .0
+            .into_iter()
+            .map(|(field, value)| Ok((field, bulk_value(value)?)))
+            .collect(),
+        // the fields and the values one after the other in RESP2
+        reply => {
+            let elements = elements(reply)?;
+            elements
+                .chunks(2)
+                .map(|pair| match pair {
+                    [field, value] => Ok((
+                        string(bulk_value(field.clone())?)?,
+                        bulk_value(value.clone())?,
+                    )),
+                    _ => bail!("odd number of elements in a HGETALL reply"),
+                })
+                .collect()
+        }
+    }
+}
+
+fn string(bytes: Bytes) -> Result<String> {
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+// the elements of an aggregate reply: arrays, RESP3 sets and pushes
+pub(crate) fn elements(reply: RespFrame) -> Result<Vec<RespFrame>> {
+    match reply {
+        RespFrame::Array(array) => Ok(array.0),
+        RespFrame::Set(set) => Ok(set.0),
+        RespFrame::Push(push) => Ok(push.0),
+        reply => unexpected(reply),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Server};
+
+    async fn spawn_server() -> Result<(std::net::SocketAddr, tokio::task::JoinHandle<Result<()>>)> {
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .backend(Backend::new())
+            .build()?;
+        let addr = server.local_addr()?;
+        Ok((addr, tokio::spawn(server.run(std::future::pending()))))
+    }
+
+    #[tokio::test]
+    async fn test_client_commands() -> Result<()> {
+        let (addr, handle) = spawn_server().await?;
+        let mut client = Client::connect(addr).await?;
+
+        client.ping().await?;
+        assert_eq!(client.get("k").await?, None);
+        client.set("k", "v").await?;
+        assert_eq!(client.get("k").await?, Some(Bytes::from("v")));
+        assert_eq!(client.incr("n").await?, 1);
+        assert!(client.expire("n", 100).await?);
+        assert!(client.ttl("n").await? > 0);
+        assert_eq!(client.del(&["k", "n", "missing"]).await?, 2);
+
+        assert_eq!(client.hset("h", "f", "1").await?, 1);
+        assert_eq!(client.hget("h", "f").await?, Some(Bytes::from("1")));
+        let fields = client.hgetall("h").await?;
+        assert_eq!(fields, vec![("f".to_string(), Bytes::from("1"))]);
+        assert_eq!(client.rpush("l", &["a", "b"]).await?, 2);
+        assert_eq!(client.lpush("l", &["c"]).await?, 3);
+        assert_eq!(
+            client.lrange("l", 0, -1).await?,
+            ["c", "a", "b"].map(Bytes::from)
+        );
+        assert_eq!(client.sadd("s", &["x"]).await?, 1);
+        assert_eq!(client.smembers("s").await?, vec![Bytes::from("x")]);
+
+        // error replies are errors, or frames for the untyped requests
+        let err = client.incr("l").await.unwrap_err();
+        assert!(err.to_string().starts_with("WRONGTYPE"));
+        let reply = client.request(["HGETALL", "h"]).await?;
+        assert!(matches!(reply, RespFrame::Array(array) if array.len() == 2));
+        client.request(["HELLO", "3"]).await?;
+        let fields = client.hgetall("h").await?;
+        assert_eq!(fields, vec![("f".to_string(), Bytes::from("1"))]);
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_pipeline() -> Result<()> {
+        let (addr, handle) = spawn_server().await?;
+        let mut client = Client::connect(addr).await?;
+
+        let pipeline = Pipeline::new()
+            .cmd(["INCR", "n"])
+            .cmd(["INCR", "n"])
+            .cmd(["LPUSH", "n", "x"])
+            .cmd(["GET", "n"]);
+        assert_eq!(pipeline.len(), 4);
+        let replies = client.pipeline(pipeline).await?;
+        assert_eq!(replies[..2], [RespFrame::Integer(1), RespFrame::Integer(2)]);
+        assert!(matches!(&replies[2], RespFrame::Error(_)));
+        assert_eq!(replies[3], BulkString::from("2").into());
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_stream() -> Result<()> {
+        let (addr, handle) = spawn_server().await?;
+        let mut publisher = Client::connect(addr).await?;
+        let mut subscriber = Client::connect(addr).await?.subscribe(&["a", "b"]).await?;
+        subscriber.psubscribe(&["c*"]).await?;
+
+        assert_eq!(publisher.publish("a", "1").await?, 1);
+        assert_eq!(publisher.publish("cc", "2").await?, 1);
+        let message = subscriber.next().await.unwrap()?;
+        assert_eq!(
+            (message.channel.as_str(), &message.payload[..]),
+            ("a", &b"1"[..])
+        );
+        let message = subscriber.next().await.unwrap()?;
+        assert_eq!(message.pattern.as_deref(), Some("c*"));
+        assert_eq!(message.channel, "cc");
+
+        subscriber.unsubscribe(&[]).await?;
+        assert_eq!(publisher.publish("b", "3").await?, 0);
+        // the pattern is still subscribed to
+        assert_eq!(publisher.publish("cd", "4").await?, 1);
+        subscriber.punsubscribe(&[]).await?;
+        subscriber.unsubscribe(&[]).await?;
+
+        handle.abort();
+        Ok(())
+    }
+}
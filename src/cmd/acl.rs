@@ -0,0 +1,307 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_command, validate_variadic_command, AclDelUser,
+    AclGetUser, AclList, AclLoad, AclSave, AclSetUser, AclWhoAmI, Command, CommandError,
+    CommandExecutor, CommandFlag, RESP_OK,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespMap, RespNull, SimpleError};
+
+impl CommandExecutor for AclSetUser {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.acl_setuser(&self.username, &self.rules) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for AclGetUser {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let Some(user) = backend.acl_getuser(&self.username) else {
+            return RespFrame::Null(RespNull);
+        };
+        let mut flags = vec![BulkString::from(if user.enabled { "on" } else { "off" }).into()];
+        if user.nopass {
+            flags.push(BulkString::from("nopass").into());
+        }
+        let passwords: Vec<RespFrame> = user
+            .passwords
+            .iter()
+            .map(|hash| BulkString::from(hash.as_str()).into())
+            .collect();
+        let keys: Vec<String> = user.keys.iter().map(|key| format!("~{}", key)).collect();
+        let mut map = RespMap::new();
+        map.insert("flags".to_string(), RespArray::new(flags).into());
+        map.insert("passwords".to_string(), RespArray::new(passwords).into());
+        map.insert(
+            "commands".to_string(),
+            BulkString::new(user.command_rules()).into(),
+        );
+        map.insert("keys".to_string(), BulkString::new(keys.join(" ")).into());
+        map.into()
+    }
+}
+
+impl CommandExecutor for AclDelUser {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.acl_deluser(&self.usernames) {
+            Ok(deleted) => RespFrame::Integer(deleted as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for AclList {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let users: Vec<RespFrame> = backend
+            .acl_list()
+            .into_iter()
+            .map(|line| BulkString::new(line).into())
+            .collect();
+        RespArray::new(users).into()
+    }
+}
+
+impl CommandExecutor for AclWhoAmI {
+    // the user is the connection's
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR ACL WHOAMI is not allowed in this context").into()
+    }
+}
+
+impl AclWhoAmI {
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        match backend.client_user(id) {
+            Some(username) => BulkString::new(username).into(),
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for AclSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.acl_save() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for AclLoad {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.acl_load() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Command {
+    /// Whether the command is in the ACL category: all, read for those only reading the
+    /// keyspace, write for those changing it, or admin for those administering the server.
+    pub fn in_acl_category(&self, category: &str) -> bool {
+        match category {
+            "all" => true,
+            "read" => self.is_read_only(),
+            "write" => {
+                self.is_propagated() && !matches!(self, Command::Publish(_) | Command::SPublish(_))
+            }
+            "admin" => self.is_admin(),
+            _ => false,
+        }
+    }
+
+    fn is_admin(&self) -> bool {
+        match self {
+            Command::Custom(cmd) => cmd.has_flag(CommandFlag::Admin),
+            cmd => matches!(
+                cmd,
+                Command::Save(_)
+                    | Command::BgSave(_)
+                    | Command::LastSave(_)
+                    | Command::BgRewriteAof(_)
+                    | Command::Shutdown(_)
+                    | Command::ConfigGet(_)
+                    | Command::ConfigSet(_)
+                    | Command::ConfigRewrite(_)
+                    | Command::ConfigResetStat(_)
+                    | Command::LatencyLatest(_)
+                    | Command::LatencyHistory(_)
+                    | Command::LatencyReset(_)
+                    | Command::AclSetUser(_)
+                    | Command::AclGetUser(_)
+                    | Command::AclDelUser(_)
+                    | Command::AclList(_)
+                    | Command::AclSave(_)
+                    | Command::AclLoad(_)
+                    | Command::ReplConf(_)
+                    | Command::PSync(_)
+                    | Command::Sync(_)
+                    | Command::ReplicaOf(_)
+                    | Command::ClusterSetSlot(_)
+                    | Command::ClusterMeet(_)
+                    | Command::ClusterForget(_)
+                    | Command::DebugDigest(_)
+                    | Command::DebugReload(_)
+            ),
+        }
+    }
+}
+
+// ACL SETUSER username [rule [rule ...]]
+impl TryFrom<RespArray> for AclSetUser {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["acl", "setuser"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        let username = extract_string(args.next(), "username")?;
+        let rules = args
+            .map(|frame| extract_string(Some(frame), "rule"))
+            .collect::<Result<_, _>>()?;
+        Ok(AclSetUser { username, rules })
+    }
+}
+
+// ACL GETUSER username
+impl TryFrom<RespArray> for AclGetUser {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["acl", "getuser"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(AclGetUser {
+            username: extract_string(args.next(), "username")?,
+        })
+    }
+}
+
+// ACL DELUSER username [username ...]
+impl TryFrom<RespArray> for AclDelUser {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["acl", "deluser"], 1)?;
+        let usernames = extract_args(value, 2)?
+            .into_iter()
+            .map(|frame| extract_string(Some(frame), "username"))
+            .collect::<Result<_, _>>()?;
+        Ok(AclDelUser { usernames })
+    }
+}
+
+// ACL LIST
+impl TryFrom<RespArray> for AclList {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["acl", "list"], 0)?;
+        Ok(AclList)
+    }
+}
+
+// ACL WHOAMI
+impl TryFrom<RespArray> for AclWhoAmI {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["acl", "whoami"], 0)?;
+        Ok(AclWhoAmI)
+    }
+}
+
+// ACL SAVE
+impl TryFrom<RespArray> for AclSave {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["acl", "save"], 0)?;
+        Ok(AclSave)
+    }
+}
+
+// ACL LOAD
+impl TryFrom<RespArray> for AclLoad {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["acl", "load"], 0)?;
+        Ok(AclLoad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_password, RespDecode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_acl_commands() -> Result<()> {
+        let backend = Backend::new();
+        let frame = decode(
+            "*5\r\n$3\r\nacl\r\n$7\r\nSETUSER\r\n$5\r\nalice\r\n$2\r\non\r\n$7\r\n>secret\r\n",
+        )?;
+        let setuser: AclSetUser = frame.try_into()?;
+        assert_eq!(setuser.rules, vec!["on", ">secret"]);
+        assert_eq!(setuser.execute(&backend), RESP_OK.clone());
+
+        let frame = decode("*3\r\n$3\r\nacl\r\n$7\r\ngetuser\r\n$5\r\nalice\r\n")?;
+        let getuser: AclGetUser = frame.try_into()?;
+        let mut map = RespMap::new();
+        map.insert(
+            "flags".to_string(),
+            RespArray::new(vec![BulkString::from("on").into()]).into(),
+        );
+        map.insert(
+            "passwords".to_string(),
+            RespArray::new(vec![BulkString::new(hash_password("secret")).into()]).into(),
+        );
+        map.insert("commands".to_string(), BulkString::from("-@all").into());
+        map.insert("keys".to_string(), BulkString::from("").into());
+        assert_eq!(getuser.execute(&backend), map.into());
+
+        let frame = decode("*2\r\n$3\r\nacl\r\n$4\r\nlist\r\n")?;
+        let list: AclList = frame.try_into()?;
+        assert_eq!(
+            list.execute(&backend),
+            RespArray::new(vec![
+                BulkString::new(format!("user alice on #{} -@all", hash_password("secret"))).into(),
+                BulkString::from("user default on nopass ~* +@all").into(),
+            ])
+            .into()
+        );
+
+        backend.auth_client(1, Some("alice"), "secret")?;
+        assert_eq!(
+            AclWhoAmI.apply(&backend, 1),
+            BulkString::from("alice").into()
+        );
+
+        let frame = decode("*4\r\n$3\r\nacl\r\n$7\r\ndeluser\r\n$5\r\nalice\r\n$3\r\nbob\r\n")?;
+        let deluser: AclDelUser = frame.try_into()?;
+        assert_eq!(deluser.execute(&backend), RespFrame::Integer(1));
+        assert_eq!(AclWhoAmI.apply(&backend, 1), RespFrame::Null(RespNull));
+
+        let frame = decode("*3\r\n$3\r\nacl\r\n$7\r\nsetuser\r\n$5\r\nalice\r\n")?;
+        assert!(AclSetUser::try_from(frame).is_ok());
+        let frame = decode("*2\r\n$3\r\nacl\r\n$7\r\ngetuser\r\n")?;
+        assert!(AclGetUser::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_categories() -> Result<()> {
+        let get = Command::try_from(decode("*2\r\n$3\r\nget\r\n$1\r\nk\r\n")?)?;
+        assert!(get.in_acl_category("read") && !get.in_acl_category("write"));
+        let config = Command::try_from(decode("*3\r\n$6\r\nconfig\r\n$3\r\nget\r\n$1\r\n*\r\n")?)?;
+        assert!(config.in_acl_category("admin") && config.in_acl_category("all"));
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    BitCount, BitField, BitPos, CommandError, CommandExecutor, GetBit, SetBit,
+};
+use crate::{
+    Backend, BitFieldOp, BitFieldOverflow, BitFieldType, BitUnit, RespArray, RespFrame, RespNull,
+    MAX_BIT_OFFSET,
+};
+
+impl CommandExecutor for SetBit {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.setbit(&self.key, self.offset, self.bit) {
+            Ok(old) => RespFrame::Integer(old as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GetBit {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.getbit(&self.key, self.offset) {
+            Ok(bit) => RespFrame::Integer(bit as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitcount(&self.key, self.range) {
+            Ok(count) => RespFrame::Integer(count as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitpos(&self.key, self.bit, self.start, self.end, self.unit) {
+            Ok(pos) => RespFrame::Integer(pos),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BitField {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bitfield(&self.key, &self.ops) {
+            Ok(results) => RespArray::new(
+                results
+                    .into_iter()
+                    .map(|v| v.map_or(RespFrame::Null(RespNull), RespFrame::Integer))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for SetBit {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setbit"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let offset = extract_bit_offset(args.next())?;
+        let bit = match extract_int(args.next()) {
+            Ok(0) => false,
+            Ok(1) => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "bit is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+        Ok(SetBit { key, offset, bit })
+    }
+}
+
+impl TryFrom<RespArray> for GetBit {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getbit"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let offset = extract_bit_offset(args.next())?;
+        Ok(GetBit { key, offset })
+    }
+}
+
+// BITCOUNT key [start end [BYTE | BIT]]
+impl TryFrom<RespArray> for BitCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["bitcount"], 1)?;
+        if value.len() == 3 || value.len() > 5 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let range = match args.next() {
+            None => None,
+            start => Some((
+                extract_int(start)?,
+                extract_int(args.next())?,
+                extract_bit_unit(args.next())?,
+            )),
+        };
+        Ok(BitCount { key, range })
+    }
+}
+
+// BITPOS key bit [start [end [BYTE | BIT]]]
+impl TryFrom<RespArray> for BitPos {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["bitpos"], 2)?;
+        if value.len() > 6 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let bit = match extract_int(args.next()) {
+            Ok(0) => false,
+            Ok(1) => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "The bit argument must be 1 or 0.".to_string(),
+                ))
+            }
+        };
+        let start = args.next().map(|v| extract_int(Some(v))).transpose()?;
+        let end = args.next().map(|v| extract_int(Some(v))).transpose()?;
+        let unit = extract_bit_unit(args.next())?;
+        Ok(BitPos {
+            key,
+            bit,
+            start,
+            end,
+            unit,
+        })
+    }
+}
+
+// BITFIELD key [GET encoding offset | [OVERFLOW <WRAP | SAT | FAIL>]
+//   <SET encoding offset value | INCRBY encoding offset increment> ...]
+impl TryFrom<RespArray> for BitField {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["bitfield"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let mut ops = Vec::new();
+        let mut overflow = BitFieldOverflow::default();
+        while let Some(arg) = args.next() {
+            let op = match arg {
+                RespFrame::BulkString(op) => op.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            if op == b"overflow" {
+                overflow = match extract_string(args.next(), "overflow")?
+                    .to_ascii_lowercase()
+                    .as_str()
+                {
+                    "wrap" => BitFieldOverflow::Wrap,
+                    "sat" => BitFieldOverflow::Sat,
+                    "fail" => BitFieldOverflow::Fail,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid OVERFLOW type specified".to_string(),
+                        ))
+                    }
+                };
+                continue;
+            }
+
+            let field = extract_bitfield_type(args.next())?;
+            let offset = extract_bitfield_offset(args.next(), field)?;
+            ops.push(match op.as_slice() {
+                b"get" => BitFieldOp::Get { field, offset },
+                b"set" => BitFieldOp::Set {
+                    field,
+                    offset,
+                    value: extract_int(args.next())?,
+                    overflow,
+                },
+                b"incrby" => BitFieldOp::IncrBy {
+                    field,
+                    offset,
+                    increment: extract_int(args.next())?,
+                    overflow,
+                },
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            });
+        }
+        Ok(BitField { key, ops })
+    }
+}
+
+// i1 to i64 or u1 to u63
+fn extract_bitfield_type(frame: Option<RespFrame>) -> Result<BitFieldType, CommandError> {
+    let invalid = || {
+        CommandError::InvalidArgument(
+            "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                .to_string(),
+        )
+    };
+
+    let s = extract_string(frame, "bitfield type").map_err(|_| invalid())?;
+    let signed = match s.as_bytes().first() {
+        Some(b'i') | Some(b'I') => true,
+        Some(b'u') | Some(b'U') => false,
+        _ => return Err(invalid()),
+    };
+    let bits: u32 = s[1..].parse().map_err(|_| invalid())?;
+    let max_bits = if signed { 64 } else { 63 };
+    if bits == 0 || bits > max_bits {
+        return Err(invalid());
+    }
+    Ok(BitFieldType { signed, bits })
+}
+
+// a bit offset, or `#n` for the n-th field of the given type
+fn extract_bitfield_offset(
+    frame: Option<RespFrame>,
+    field: BitFieldType,
+) -> Result<u64, CommandError> {
+    let invalid = || {
+        CommandError::InvalidArgument("bit offset is not an integer or out of range".to_string())
+    };
+
+    let s = extract_string(frame, "offset").map_err(|_| invalid())?;
+    let offset = match s.strip_prefix('#') {
+        Some(n) => n
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(field.bits as u64)),
+        None => s.parse::<u64>().ok(),
+    }
+    .ok_or_else(invalid)?;
+
+    if offset + field.bits as u64 - 1 > MAX_BIT_OFFSET {
+        return Err(invalid());
+    }
+    Ok(offset)
+}
+
+fn extract_bit_unit(frame: Option<RespFrame>) -> Result<BitUnit, CommandError> {
+    match frame {
+        None => Ok(BitUnit::Byte),
+        Some(RespFrame::BulkString(unit)) if unit.eq_ignore_ascii_case(b"byte") => {
+            Ok(BitUnit::Byte)
+        }
+        Some(RespFrame::BulkString(unit)) if unit.eq_ignore_ascii_case(b"bit") => Ok(BitUnit::Bit),
+        _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+    }
+}
+
+fn extract_bit_offset(frame: Option<RespFrame>) -> Result<u64, CommandError> {
+    match extract_int(frame) {
+        Ok(offset) if (0..=MAX_BIT_OFFSET as i64).contains(&offset) => Ok(offset as u64),
+        _ => Err(CommandError::InvalidArgument(
+            "bit offset is not an integer or out of range".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_setbit_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$6\r\nsetbit\r\n$5\r\nhello\r\n$2\r\n10\r\n$1\r\n1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: SetBit = frame.try_into()?;
+        assert_eq!(cmd.offset, 10);
+        assert!(cmd.bit);
+
+        buf.extend_from_slice(b"*4\r\n$6\r\nsetbit\r\n$5\r\nhello\r\n$2\r\n10\r\n$1\r\n2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<SetBit, _> = frame.try_into();
+        assert!(result.is_err());
+
+        buf.extend_from_slice(b"*3\r\n$6\r\ngetbit\r\n$5\r\nhello\r\n$10\r\n4294967296\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<GetBit, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_setbit_getbit_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = SetBit {
+            key: "hello".to_string(),
+            offset: 10,
+            bit: true,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = GetBit {
+            key: "hello".to_string(),
+            offset: 10,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitcount_bitpos_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$8\r\nbitcount\r\n$5\r\nhello\r\n$1\r\n5\r\n$2\r\n30\r\n$3\r\nBIT\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: BitCount = frame.try_into()?;
+        assert_eq!(cmd.range, Some((5, 30, BitUnit::Bit)));
+
+        buf.extend_from_slice(b"*3\r\n$8\r\nbitcount\r\n$5\r\nhello\r\n$1\r\n5\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<BitCount, _> = frame.try_into();
+        assert!(result.is_err());
+
+        buf.extend_from_slice(b"*4\r\n$6\r\nbitpos\r\n$5\r\nhello\r\n$1\r\n0\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: BitPos = frame.try_into()?;
+        assert!(!cmd.bit);
+        assert_eq!(
+            (cmd.start, cmd.end, cmd.unit),
+            (Some(-1), None, BitUnit::Byte)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitcount_bitpos_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("hello".to_string(), RespFrame::BulkString(b"foobar".into()));
+
+        let cmd = BitCount {
+            key: "hello".to_string(),
+            range: None,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(26));
+
+        let cmd = BitPos {
+            key: "hello".to_string(),
+            bit: true,
+            start: Some(1),
+            end: None,
+            unit: BitUnit::Byte,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(9));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitfield_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*11\r\n$8\r\nbitfield\r\n$5\r\nhello\r\n$3\r\nGET\r\n$2\r\nu4\r\n$2\r\n#2\r\n$8\r\nOVERFLOW\r\n$3\r\nSAT\r\n$6\r\nINCRBY\r\n$3\r\ni64\r\n$1\r\n0\r\n$2\r\n10\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: BitField = frame.try_into()?;
+        let u4 = BitFieldType {
+            signed: false,
+            bits: 4,
+        };
+        let i64_field = BitFieldType {
+            signed: true,
+            bits: 64,
+        };
+        assert_eq!(
+            cmd.ops,
+            vec![
+                BitFieldOp::Get {
+                    field: u4,
+                    offset: 8
+                },
+                BitFieldOp::IncrBy {
+                    field: i64_field,
+                    offset: 0,
+                    increment: 10,
+                    overflow: BitFieldOverflow::Sat
+                },
+            ]
+        );
+
+        buf.extend_from_slice(
+            b"*5\r\n$8\r\nbitfield\r\n$5\r\nhello\r\n$3\r\nget\r\n$3\r\nu64\r\n$1\r\n0\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<BitField, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitfield_command() -> Result<()> {
+        let backend = Backend::new();
+        let u8_field = BitFieldType {
+            signed: false,
+            bits: 8,
+        };
+        let cmd = BitField {
+            key: "hello".to_string(),
+            ops: vec![
+                BitFieldOp::Set {
+                    field: u8_field,
+                    offset: 0,
+                    value: 300,
+                    overflow: BitFieldOverflow::Fail,
+                },
+                BitFieldOp::Get {
+                    field: u8_field,
+                    offset: 0,
+                },
+            ],
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new(vec![RespFrame::Null(RespNull), RespFrame::Integer(0)]).into()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,368 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_command, validate_variadic_command, ClientCaching,
+    ClientId, ClientList, ClientNoEvict, ClientTracking, CommandError, CommandExecutor, RESP_OK,
+};
+use crate::{
+    Backend, BulkString, ClientInfo, Invalidation, RespArray, RespFrame, RespPush, SimpleError,
+    TrackingOptions,
+};
+use std::fmt::Write;
+
+impl CommandExecutor for ClientId {
+    // the ID is the connection's
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR CLIENT ID is not allowed in this context").into()
+    }
+}
+
+impl ClientId {
+    pub fn apply(self, id: u64) -> RespFrame {
+        RespFrame::Integer(id as i64)
+    }
+}
+
+impl CommandExecutor for ClientTracking {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR CLIENT TRACKING is not allowed in this context").into()
+    }
+}
+
+impl ClientTracking {
+    /// Turn tracking on or off for the connection of ID id.
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        match backend.client_tracking(id, self.options) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ClientCaching {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR CLIENT CACHING is not allowed in this context").into()
+    }
+}
+
+impl ClientCaching {
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        match backend.client_caching(id, self.yes) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ClientList {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut list = String::new();
+        for client in backend.client_list() {
+            if self.ids.is_empty() || self.ids.contains(&client.id) {
+                let _ = writeln!(list, "{}", client_line(&client));
+            }
+        }
+        BulkString::new(list).into()
+    }
+}
+
+// the line of a client in CLIENT LIST, its fields as Redis names them
+fn client_line(client: &ClientInfo) -> String {
+    format!(
+        "id={} addr={} age={} idle={} flags={} qbuf={} qbuf-free={} omem={} tot-mem={}",
+        client.id,
+        client.addr,
+        client.age,
+        client.idle,
+        if client.no_evict { "e" } else { "N" },
+        client.qbuf,
+        client.qbuf_free,
+        client.omem,
+        client.tot_mem,
+    )
+}
+
+impl CommandExecutor for ClientNoEvict {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR CLIENT NO-EVICT is not allowed in this context").into()
+    }
+}
+
+impl ClientNoEvict {
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        backend.client_no_evict(id, self.on);
+        RESP_OK.clone()
+    }
+}
+
+// an `invalidate` push, or a message on `__redis__:invalidate` when redirected
+impl From<Invalidation> for RespFrame {
+    fn from(invalidation: Invalidation) -> Self {
+        let keys: Vec<RespFrame> = invalidation
+            .keys
+            .into_iter()
+            .map(|key| BulkString::new(key).into())
+            .collect();
+        let frames: Vec<RespFrame> = if invalidation.redirected {
+            vec![
+                BulkString::from("message").into(),
+                BulkString::from("__redis__:invalidate").into(),
+                RespArray::new(keys).into(),
+            ]
+        } else {
+            vec![
+                BulkString::from("invalidate").into(),
+                RespArray::new(keys).into(),
+            ]
+        };
+        RespPush::new(frames).into()
+    }
+}
+
+impl TryFrom<RespArray> for ClientId {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["client", "id"], 0)?;
+        Ok(ClientId)
+    }
+}
+
+// CLIENT TRACKING on|off [REDIRECT id] [PREFIX prefix ...] [BCAST] [OPTIN] [OPTOUT] [NOLOOP]
+impl TryFrom<RespArray> for ClientTracking {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["client", "tracking"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let on = match extract_string(args.next(), "switch")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "on" => true,
+            "off" => false,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+
+        let mut options = TrackingOptions::default();
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "redirect" => {
+                    let id = extract_string(args.next(), "client ID")?;
+                    let id = id.parse().map_err(|_| {
+                        CommandError::InvalidArgument("Invalid client ID".to_string())
+                    })?;
+                    options.redirect = Some(id);
+                }
+                "prefix" => options
+                    .prefixes
+                    .push(extract_string(args.next(), "prefix")?),
+                "bcast" => options.bcast = true,
+                "optin" => options.optin = true,
+                "optout" => options.optout = true,
+                "noloop" => options.noloop = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+
+        if !options.bcast && !options.prefixes.is_empty() {
+            return Err(CommandError::InvalidArgument(
+                "PREFIX option requires BCAST mode to be enabled".to_string(),
+            ));
+        }
+        if options.optin && options.optout {
+            return Err(CommandError::InvalidArgument(
+                "You can't use OPTIN and OPTOUT at the same time".to_string(),
+            ));
+        }
+        if options.bcast && (options.optin || options.optout) {
+            return Err(CommandError::InvalidArgument(
+                "OPTIN and OPTOUT are not compatible with BCAST".to_string(),
+            ));
+        }
+        Ok(ClientTracking {
+            options: on.then_some(options),
+        })
+    }
+}
+
+// CLIENT CACHING yes|no
+impl TryFrom<RespArray> for ClientCaching {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["client", "caching"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let yes = match extract_string(args.next(), "mode")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "yes" => true,
+            "no" => false,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(ClientCaching { yes })
+    }
+}
+
+// CLIENT LIST [ID client-id [client-id ...]]
+impl TryFrom<RespArray> for ClientList {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["client", "list"], 0)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let mut ids = Vec::new();
+        if let Some(arg) = args.next() {
+            if !extract_string(Some(arg), "option")?.eq_ignore_ascii_case("id") {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            }
+            for id in args {
+                let id = extract_string(Some(id), "client ID")?;
+                ids.push(
+                    id.parse().map_err(|_| {
+                        CommandError::InvalidArgument("Invalid client ID".to_string())
+                    })?,
+                );
+            }
+            if ids.is_empty() {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            }
+        }
+        Ok(ClientList { ids })
+    }
+}
+
+// CLIENT NO-EVICT on|off
+impl TryFrom<RespArray> for ClientNoEvict {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["client", "no-evict"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let on = match extract_string(args.next(), "switch")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "on" => true,
+            "off" => false,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(ClientNoEvict { on })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_client_tracking_from_resp_array() -> Result<()> {
+        let frame = decode("*7\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$2\r\non\r\n$5\r\nBCAST\r\n$6\r\nPREFIX\r\n$5\r\nuser:\r\n$6\r\nnoloop\r\n")?;
+        let result: ClientTracking = frame.try_into()?;
+        let options = result.options.unwrap();
+        assert!(options.bcast && options.noloop);
+        assert_eq!(options.prefixes, vec!["user:"]);
+
+        let frame = decode("*3\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$3\r\noff\r\n")?;
+        let result: ClientTracking = frame.try_into()?;
+        assert!(result.options.is_none());
+
+        // PREFIX without BCAST
+        let frame = decode(
+            "*5\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$2\r\non\r\n$6\r\nprefix\r\n$1\r\na\r\n",
+        )?;
+        assert!(ClientTracking::try_from(frame).is_err());
+
+        let frame = decode(
+            "*5\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$2\r\non\r\n$5\r\noptin\r\n$6\r\noptout\r\n",
+        )?;
+        assert!(ClientTracking::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_tracking_command() {
+        let backend = Backend::new();
+        let _rx = backend.connect_client(1);
+        let options = TrackingOptions {
+            redirect: Some(2),
+            ..Default::default()
+        };
+        let cmd = ClientTracking {
+            options: Some(options),
+        };
+        assert_eq!(
+            cmd.apply(&backend, 1),
+            SimpleError::new("ERR The client ID you want redirect to does not exist").into()
+        );
+
+        let cmd = ClientTracking {
+            options: Some(TrackingOptions::default()),
+        };
+        assert_eq!(cmd.apply(&backend, 1), RESP_OK.clone());
+        assert_eq!(ClientId.apply(1), RespFrame::Integer(1));
+    }
+
+    #[test]
+    fn test_client_list_and_no_evict() -> Result<()> {
+        let backend = Backend::new();
+        let addr = "127.0.0.1:5000".parse()?;
+        let _first = backend.register_client(1, addr);
+        let _second = backend.register_client(2, addr);
+
+        let frame = decode("*3\r\n$6\r\nclient\r\n$8\r\nno-evict\r\n$2\r\non\r\n")?;
+        let cmd: ClientNoEvict = frame.try_into()?;
+        assert_eq!(cmd.apply(&backend, 2), RESP_OK.clone());
+
+        let frame = decode("*4\r\n$6\r\nclient\r\n$4\r\nlist\r\n$2\r\nID\r\n$1\r\n2\r\n")?;
+        let cmd: ClientList = frame.try_into()?;
+        assert_eq!(
+            cmd.execute(&backend),
+            BulkString::from(
+                "id=2 addr=127.0.0.1:5000 age=0 idle=0 flags=e qbuf=0 qbuf-free=0 omem=0 tot-mem=0\n"
+            )
+            .into()
+        );
+        let cmd = ClientList { ids: vec![] };
+        let RespFrame::BulkString(list) = cmd.execute(&backend) else {
+            panic!("CLIENT LIST should reply a bulk string");
+        };
+        assert_eq!(String::from_utf8_lossy(&list).lines().count(), 2);
+
+        let frame = decode("*3\r\n$6\r\nclient\r\n$4\r\nlist\r\n$2\r\nID\r\n")?;
+        assert!(ClientList::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalidation_frame() {
+        let invalidation = Invalidation {
+            keys: vec!["a".to_string()],
+            redirected: false,
+        };
+        let frame: RespFrame = invalidation.into();
+        assert_eq!(
+            frame,
+            RespPush::new(vec![
+                BulkString::from("invalidate").into(),
+                RespArray::new(vec![BulkString::from("a").into()]).into(),
+            ])
+            .into()
+        );
+    }
+}
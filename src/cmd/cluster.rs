@@ -0,0 +1,597 @@
+use crate::cmd::{
+    command_keys, extract_args, extract_int, extract_string, validate_command,
+    validate_variadic_command, Asking, ClusterForget, ClusterInfo, ClusterKeySlot, ClusterMeet,
+    ClusterMyId, ClusterNodes, ClusterSetSlot, ClusterShards, ClusterSlots, CommandError,
+    CommandExecutor, RESP_OK,
+};
+use crate::{
+    key_hash_slot, Backend, BackendError, BulkString, ClusterNode, RespArray, RespFrame, RespMap,
+    SlotState, CLUSTER_PORT_INCR, CLUSTER_SLOTS,
+};
+
+// Standalone, CLUSTER tells the clients probing the server so, rather than failing as an
+// unknown command: a cluster of a single node serving no slot.
+
+impl CommandExecutor for ClusterInfo {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let enabled = backend.is_cluster_enabled();
+        let nodes = backend.cluster_nodes();
+        let assigned: usize = nodes.iter().map(|node| node.slots.count()).sum();
+        let state = match !enabled || assigned == CLUSTER_SLOTS {
+            true => "ok",
+            false => "fail",
+        };
+        let pfail: usize = nodes
+            .iter()
+            .filter(|node| node.failing)
+            .map(|node| node.slots.count())
+            .sum();
+        let serving = nodes.iter().filter(|node| node.slots.count() > 0).count();
+        let fields = [
+            ("cluster_enabled", enabled as usize),
+            ("cluster_slots_assigned", assigned),
+            ("cluster_slots_ok", assigned - pfail),
+            ("cluster_slots_pfail", pfail),
+            ("cluster_slots_fail", 0),
+            ("cluster_known_nodes", nodes.len()),
+            ("cluster_size", serving),
+            ("cluster_current_epoch", 0),
+            ("cluster_my_epoch", 0),
+        ];
+        let mut info = format!("cluster_state:{}\r\n", state);
+        for (field, value) in fields {
+            info.push_str(&format!("{}:{}\r\n", field, value));
+        }
+        BulkString::from(info.as_str()).into()
+    }
+}
+
+impl CommandExecutor for ClusterMyId {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        BulkString::from(backend.node_id().as_str()).into()
+    }
+}
+
+impl CommandExecutor for ClusterSlots {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut ranges: Vec<(u16, u16, RespFrame)> = Vec::new();
+        for node in backend.cluster_nodes() {
+            for &(start, end) in &node.slots.0 {
+                ranges.push((start, end, endpoint(&node)));
+            }
+        }
+        ranges.sort_by_key(|&(start, _, _)| start);
+        let ranges: Vec<RespFrame> = ranges
+            .into_iter()
+            .map(|(start, end, endpoint)| {
+                RespArray::new(vec![
+                    RespFrame::Integer(start as i64),
+                    RespFrame::Integer(end as i64),
+                    endpoint,
+                ])
+                .into()
+            })
+            .collect();
+        RespArray::new(ranges).into()
+    }
+}
+
+// ip, port, ID
+fn endpoint(node: &ClusterNode) -> RespFrame {
+    RespArray::new(vec![
+        BulkString::from(node.ip.as_str()).into(),
+        RespFrame::Integer(node.port as i64),
+        BulkString::from(node.id.as_str()).into(),
+    ])
+    .into()
+}
+
+impl CommandExecutor for ClusterShards {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !backend.is_cluster_enabled() {
+            return RespArray::new(vec![]).into();
+        }
+        let shards: Vec<RespFrame> = backend
+            .cluster_nodes()
+            .into_iter()
+            .map(|node| {
+                let slots: Vec<RespFrame> = node
+                    .slots
+                    .0
+                    .iter()
+                    .flat_map(|&(start, end)| [start, end])
+                    .map(|slot| RespFrame::Integer(slot as i64))
+                    .collect();
+                let mut info = RespMap::new();
+                info.insert("id".to_string(), BulkString::from(node.id.as_str()).into());
+                info.insert("port".to_string(), RespFrame::Integer(node.port as i64));
+                info.insert("ip".to_string(), BulkString::from(node.ip.as_str()).into());
+                info.insert(
+                    "endpoint".to_string(),
+                    BulkString::from(node.ip.as_str()).into(),
+                );
+                info.insert("role".to_string(), BulkString::from("master").into());
+                info.insert("replication-offset".to_string(), RespFrame::Integer(0));
+                let health = match node.failing {
+                    true => "failed",
+                    false => "online",
+                };
+                info.insert("health".to_string(), BulkString::from(health).into());
+                let mut shard = RespMap::new();
+                shard.insert("slots".to_string(), RespArray::new(slots).into());
+                shard.insert(
+                    "nodes".to_string(),
+                    RespArray::new(vec![info.into()]).into(),
+                );
+                shard.into()
+            })
+            .collect();
+        RespArray::new(shards).into()
+    }
+}
+
+impl CommandExecutor for ClusterNodes {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let migrations = backend.cluster_migrations();
+        let mut nodes = String::new();
+        for node in backend.cluster_nodes() {
+            let flags = match (node.myself, node.handshake, node.failing) {
+                (true, _, _) => "myself,master",
+                (_, true, _) => "handshake",
+                (_, _, true) => "master,fail?",
+                _ => "master",
+            };
+            // this node is always connected to itself, the others once they answered
+            let link = match node.myself || node.pong_received > 0 && !node.failing {
+                true => "connected",
+                false => "disconnected",
+            };
+            nodes.push_str(&format!(
+                "{} {}:{}@{} {} - 0 {} 0 {}",
+                node.id, node.ip, node.port, node.cport, flags, node.pong_received, link
+            ));
+            for &(start, end) in &node.slots.0 {
+                match start == end {
+                    true => nodes.push_str(&format!(" {}", start)),
+                    false => nodes.push_str(&format!(" {}-{}", start, end)),
+                }
+            }
+            // the slots moving are listed for this node only
+            if node.myself {
+                for (slot, id) in &migrations.migrating {
+                    nodes.push_str(&format!(" [{}->-{}]", slot, id));
+                }
+                for (slot, id) in &migrations.importing {
+                    nodes.push_str(&format!(" [{}-<-{}]", slot, id));
+                }
+            }
+            nodes.push('\n');
+        }
+        BulkString::from(nodes.as_str()).into()
+    }
+}
+
+impl CommandExecutor for ClusterKeySlot {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        RespFrame::Integer(key_hash_slot(self.key.as_bytes()) as i64)
+    }
+}
+
+/// What the slot of a command is computed from in cluster mode: its keys, or the shard
+/// channels of SPUBLISH, SSUBSCRIBE and SUNSUBSCRIBE, which are in the slot of their name so
+/// the messages stay within the node serving it.
+pub fn slot_keys(backend: &Backend, name: &str, array: &RespArray) -> Vec<String> {
+    let args = |count: usize| {
+        array
+            .iter()
+            .skip(1)
+            .take(count)
+            .filter_map(|arg| match arg {
+                RespFrame::BulkString(arg) => Some(String::from_utf8_lossy(arg).into_owned()),
+                _ => None,
+            })
+            .collect()
+    };
+    match name {
+        "spublish" => args(1),
+        "ssubscribe" | "sunsubscribe" => args(array.len()),
+        name => command_keys(backend, name, array),
+    }
+}
+
+impl CommandExecutor for ClusterSetSlot {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.cluster_setslot(self.slot, self.state) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ClusterMeet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.cluster_meet(&self.ip, self.port, self.cport) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ClusterForget {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.cluster_forget(&self.id) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Asking {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        BackendError::ClusterDisabled.into()
+    }
+}
+
+impl Asking {
+    /// Let the next command of the connection of ID id run on a slot being imported.
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        if !backend.is_cluster_enabled() {
+            return BackendError::ClusterDisabled.into();
+        }
+        backend.cluster_asking(id);
+        RESP_OK.clone()
+    }
+}
+
+// CLUSTER INFO
+impl TryFrom<RespArray> for ClusterInfo {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "info"], 0)?;
+        Ok(ClusterInfo)
+    }
+}
+
+// CLUSTER MYID
+impl TryFrom<RespArray> for ClusterMyId {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "myid"], 0)?;
+        Ok(ClusterMyId)
+    }
+}
+
+// CLUSTER SLOTS
+impl TryFrom<RespArray> for ClusterSlots {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "slots"], 0)?;
+        Ok(ClusterSlots)
+    }
+}
+
+// CLUSTER SHARDS
+impl TryFrom<RespArray> for ClusterShards {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "shards"], 0)?;
+        Ok(ClusterShards)
+    }
+}
+
+// CLUSTER NODES
+impl TryFrom<RespArray> for ClusterNodes {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "nodes"], 0)?;
+        Ok(ClusterNodes)
+    }
+}
+
+// CLUSTER KEYSLOT key
+impl TryFrom<RespArray> for ClusterKeySlot {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "keyslot"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(ClusterKeySlot {
+            key: extract_string(args.next(), "key")?,
+        })
+    }
+}
+
+// CLUSTER SETSLOT slot IMPORTING node-id | MIGRATING node-id | NODE node-id | STABLE
+impl TryFrom<RespArray> for ClusterSetSlot {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["cluster", "setslot"], 2)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        let slot = match extract_int(args.next()) {
+            Ok(slot) if (0..CLUSTER_SLOTS as i64).contains(&slot) => slot as u16,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid or out of range slot".to_string(),
+                ))
+            }
+        };
+        let subcommand = extract_string(args.next(), "subcommand")?.to_ascii_lowercase();
+        let node = args.next();
+        let state = match (subcommand.as_str(), node) {
+            ("stable", None) => SlotState::Stable,
+            ("importing", node @ Some(_)) => SlotState::Importing(extract_string(node, "node")?),
+            ("migrating", node @ Some(_)) => SlotState::Migrating(extract_string(node, "node")?),
+            ("node", node @ Some(_)) => SlotState::Node(extract_string(node, "node")?),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid CLUSTER SETSLOT action or number of arguments".to_string(),
+                ))
+            }
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument(
+                "Invalid CLUSTER SETSLOT action or number of arguments".to_string(),
+            ));
+        }
+        Ok(ClusterSetSlot { slot, state })
+    }
+}
+
+// CLUSTER MEET ip port [cluster-bus-port]
+impl TryFrom<RespArray> for ClusterMeet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["cluster", "meet"], 2)?;
+        if value.len() > 5 {
+            return Err(CommandError::InvalidArgument(
+                "Invalid number of arguments for CLUSTER MEET".to_string(),
+            ));
+        }
+        let mut args = extract_args(value, 2)?.into_iter();
+        let ip = extract_string(args.next(), "ip")?;
+        let parse_port = |frame: Option<RespFrame>, name: &str| match extract_int(frame) {
+            Ok(port) if (1..=u16::MAX as i64).contains(&port) => Ok(port as u16),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "Invalid {} port specified",
+                name
+            ))),
+        };
+        let port = parse_port(args.next(), "base")?;
+        let cport = match args.next() {
+            Some(frame) => parse_port(Some(frame), "bus")?,
+            None => port.wrapping_add(CLUSTER_PORT_INCR),
+        };
+        Ok(ClusterMeet { ip, port, cport })
+    }
+}
+
+// CLUSTER FORGET node-id
+impl TryFrom<RespArray> for ClusterForget {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["cluster", "forget"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(ClusterForget {
+            id: extract_string(args.next(), "node")?,
+        })
+    }
+}
+
+// ASKING
+impl TryFrom<RespArray> for Asking {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["asking"], 0)?;
+        Ok(Asking)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    fn bulk(frame: RespFrame) -> String {
+        match frame {
+            RespFrame::BulkString(s) => String::from_utf8_lossy(&s).into_owned(),
+            frame => panic!("not a bulk string: {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_cluster_standalone() -> Result<()> {
+        let backend = Backend::new();
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$4\r\nINFO\r\n")?;
+        let info = bulk(Command::try_from(frame)?.execute(&backend));
+        assert!(info.starts_with("cluster_state:ok\r\n"));
+        assert!(info.contains("cluster_enabled:0\r\n"));
+        assert!(info.contains("cluster_known_nodes:1\r\n"));
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$4\r\nmyid\r\n")?;
+        let id = bulk(Command::try_from(frame)?.execute(&backend));
+        assert_eq!(id, backend.node_id());
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$5\r\nslots\r\n")?;
+        let slots = Command::try_from(frame)?.execute(&backend);
+        assert_eq!(slots, RespArray::new(vec![]).into());
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$6\r\nshards\r\n")?;
+        let shards = Command::try_from(frame)?.execute(&backend);
+        assert_eq!(shards, RespArray::new(vec![]).into());
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$5\r\nnodes\r\n")?;
+        let nodes = bulk(Command::try_from(frame)?.execute(&backend));
+        assert_eq!(
+            nodes,
+            format!(
+                "{} :6379@16379 myself,master - 0 0 0 connected\n",
+                backend.node_id()
+            )
+        );
+
+        let frame = decode("*3\r\n$7\r\ncluster\r\n$4\r\ninfo\r\n$1\r\nx\r\n")?;
+        assert!(Command::try_from(frame).is_err());
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$7\r\nfailover\r\n")?;
+        assert!(Command::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_mode() -> Result<()> {
+        let backend = Backend::new();
+        backend.config_load(vec![
+            ("cluster-enabled".to_string(), "yes".to_string()),
+            ("cluster-slots".to_string(), "0-8191".to_string()),
+            (
+                "cluster-peers".to_string(),
+                "127.0.0.1:7001=8192-16383".to_string(),
+            ),
+        ])?;
+        let peer = backend.cluster_nodes()[1].id.clone();
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$4\r\ninfo\r\n")?;
+        let info = bulk(Command::try_from(frame)?.execute(&backend));
+        assert!(info.contains("cluster_enabled:1\r\n"));
+        assert!(info.contains("cluster_slots_assigned:16384\r\n"));
+        assert!(info.contains("cluster_size:2\r\n"));
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$5\r\nslots\r\n")?;
+        let RespFrame::Array(slots) = Command::try_from(frame)?.execute(&backend) else {
+            panic!("CLUSTER SLOTS isn't an array");
+        };
+        assert_eq!(slots.len(), 2);
+        assert_eq!(
+            slots[1],
+            RespArray::new(vec![
+                RespFrame::Integer(8192),
+                RespFrame::Integer(16383),
+                RespArray::new(vec![
+                    BulkString::from("127.0.0.1").into(),
+                    RespFrame::Integer(7001),
+                    BulkString::from(peer.as_str()).into(),
+                ])
+                .into(),
+            ])
+            .into()
+        );
+
+        let frame = decode(&format!(
+            "*5\r\n$7\r\ncluster\r\n$7\r\nsetslot\r\n$1\r\n0\r\n$9\r\nmigrating\r\n$40\r\n{}\r\n",
+            peer
+        ))?;
+        let setslot = Command::try_from(frame)?;
+        assert_eq!(setslot.execute(&backend), RESP_OK.clone());
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$5\r\nnodes\r\n")?;
+        let nodes = bulk(Command::try_from(frame)?.execute(&backend));
+        assert_eq!(
+            nodes,
+            format!(
+                "{} :6379@16379 myself,master - 0 0 0 connected 0-8191 [0->-{}]\n\
+                 {} 127.0.0.1:7001@17001 handshake - 0 0 0 disconnected 8192-16383\n",
+                backend.node_id(),
+                peer,
+                peer
+            )
+        );
+
+        let frame = decode("*2\r\n$7\r\ncluster\r\n$6\r\nshards\r\n")?;
+        let RespFrame::Array(shards) = Command::try_from(frame)?.execute(&backend) else {
+            panic!("CLUSTER SHARDS isn't an array");
+        };
+        assert_eq!(shards.len(), 2);
+
+        for s in [
+            "*4\r\n$7\r\ncluster\r\n$7\r\nsetslot\r\n$5\r\n16384\r\n$6\r\nstable\r\n",
+            "*4\r\n$7\r\ncluster\r\n$7\r\nsetslot\r\n$1\r\n0\r\n$4\r\nnode\r\n",
+            "*5\r\n$7\r\ncluster\r\n$7\r\nsetslot\r\n$1\r\n0\r\n$6\r\nstable\r\n$1\r\nx\r\n",
+        ] {
+            assert!(Command::try_from(decode(s)?).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_meet_forget() -> Result<()> {
+        let backend = Backend::new();
+        let frame =
+            decode("*4\r\n$7\r\ncluster\r\n$4\r\nmeet\r\n$9\r\n127.0.0.1\r\n$4\r\n7001\r\n")?;
+        let meet = Command::try_from(frame)?;
+        assert!(matches!(meet.execute(&backend), RespFrame::Error(_)));
+
+        backend.config_load(vec![("cluster-enabled".to_string(), "yes".to_string())])?;
+        let frame =
+            decode("*4\r\n$7\r\ncluster\r\n$4\r\nmeet\r\n$9\r\n127.0.0.1\r\n$4\r\n7001\r\n")?;
+        let meet = Command::try_from(frame)?;
+        assert_eq!(meet.execute(&backend), RESP_OK.clone());
+        let frame = decode(
+            "*5\r\n$7\r\ncluster\r\n$4\r\nmeet\r\n$9\r\n127.0.0.1\r\n$4\r\n7002\r\n$4\r\n7012\r\n",
+        )?;
+        let meet = Command::try_from(frame)?;
+        assert_eq!(meet.execute(&backend), RESP_OK.clone());
+        let nodes = backend.cluster_nodes();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!((nodes[1].port, nodes[1].cport), (7001, 17001));
+        assert_eq!((nodes[2].port, nodes[2].cport), (7002, 7012));
+        assert!(nodes[1].handshake);
+
+        let frame = decode(&format!(
+            "*3\r\n$7\r\ncluster\r\n$6\r\nforget\r\n$40\r\n{}\r\n",
+            nodes[1].id
+        ))?;
+        let forget = Command::try_from(frame)?;
+        assert_eq!(forget.execute(&backend), RESP_OK.clone());
+        assert_eq!(backend.cluster_nodes().len(), 2);
+
+        for s in [
+            "*4\r\n$7\r\ncluster\r\n$4\r\nmeet\r\n$9\r\n127.0.0.1\r\n$1\r\n0\r\n",
+            "*3\r\n$7\r\ncluster\r\n$4\r\nmeet\r\n$9\r\n127.0.0.1\r\n",
+            "*2\r\n$7\r\ncluster\r\n$6\r\nforget\r\n",
+        ] {
+            assert!(Command::try_from(decode(s)?).is_err());
+        }
+        let frame = decode("*4\r\n$7\r\ncluster\r\n$4\r\nmeet\r\n$4\r\nhost\r\n$4\r\n7001\r\n")?;
+        let meet = Command::try_from(frame)?;
+        assert!(matches!(meet.execute(&backend), RespFrame::Error(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_keyslot() -> Result<()> {
+        let backend = Backend::new();
+        let frame = decode("*3\r\n$7\r\ncluster\r\n$7\r\nkeyslot\r\n$3\r\nfoo\r\n")?;
+        let keyslot = Command::try_from(frame)?;
+        assert_eq!(keyslot.execute(&backend), RespFrame::Integer(12182));
+        let frame = decode("*3\r\n$7\r\ncluster\r\n$7\r\nkeyslot\r\n$8\r\n{bar}zap\r\n")?;
+        let keyslot = Command::try_from(frame)?;
+        assert_eq!(keyslot.execute(&backend), RespFrame::Integer(5061));
+
+        let frame = decode("*3\r\n$8\r\nspublish\r\n$1\r\nc\r\n$1\r\nm\r\n")?;
+        assert_eq!(slot_keys(&backend, "spublish", &frame), vec!["c"]);
+        let frame = decode("*3\r\n$10\r\nssubscribe\r\n$1\r\na\r\n$1\r\nb\r\n")?;
+        assert_eq!(slot_keys(&backend, "ssubscribe", &frame), vec!["a", "b"]);
+        let frame = decode("*3\r\n$7\r\npublish\r\n$1\r\nc\r\n$1\r\nm\r\n")?;
+        assert!(slot_keys(&backend, "publish", &frame).is_empty());
+        let frame = decode("*2\r\n$3\r\nget\r\n$1\r\nk\r\n")?;
+        assert_eq!(slot_keys(&backend, "get", &frame), vec!["k"]);
+        Ok(())
+    }
+}
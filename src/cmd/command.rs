@@ -0,0 +1,477 @@
+use crate::cmd::{
+    built_in, extract_args, validate_variadic_command, CommandError, CommandExecutor,
+    CommandGetKeys,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, SimpleError};
+
+/// Where a command takes some of its keys, as Redis describes them: where the search for
+/// them begins, then how they're found from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySpec {
+    pub begin: BeginSearch,
+    pub find: FindKeys,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeginSearch {
+    /// At the argument at the index, the name of the command being at 0.
+    Index(usize),
+    /// After the first argument matching the keyword, from the one at start_from on.
+    Keyword {
+        keyword: &'static str,
+        start_from: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindKeys {
+    /// The arguments from where the search began to last_key, relative to it, or counting
+    /// back from the last argument when negative, every step. Only the first 1/limit of them
+    /// unless limit is 0, as the keys of XREAD take the first half of what follows STREAMS.
+    Range {
+        last_key: isize,
+        step: usize,
+        limit: usize,
+    },
+    /// The count of keys at keynum_idx, then the keys from first_key on, every step, both
+    /// relative to where the search began.
+    KeyNum {
+        keynum_idx: usize,
+        first_key: usize,
+        step: usize,
+    },
+}
+
+impl KeySpec {
+    /// The arguments from index to last_key relative to it, every step.
+    pub const fn range(index: usize, last_key: isize, step: usize) -> Self {
+        KeySpec {
+            begin: BeginSearch::Index(index),
+            find: FindKeys::Range {
+                last_key,
+                step,
+                limit: 0,
+            },
+        }
+    }
+
+    /// The count of keys at index, then the keys.
+    pub const fn keynum(index: usize) -> Self {
+        KeySpec {
+            begin: BeginSearch::Index(index),
+            find: FindKeys::KeyNum {
+                keynum_idx: 0,
+                first_key: 1,
+                step: 1,
+            },
+        }
+    }
+
+    /// The arguments after keyword, from start_from on, to last_key relative to it.
+    pub const fn after(
+        keyword: &'static str,
+        start_from: usize,
+        last_key: isize,
+        limit: usize,
+    ) -> Self {
+        KeySpec {
+            begin: BeginSearch::Keyword {
+                keyword,
+                start_from,
+            },
+            find: FindKeys::Range {
+                last_key,
+                step: 1,
+                limit,
+            },
+        }
+    }
+
+    /// The indices of the keys in the arguments of a command, its name included.
+    pub fn key_indices(&self, args: &[&[u8]]) -> Vec<usize> {
+        let len = args.len();
+        let begin = match self.begin {
+            BeginSearch::Index(index) => index,
+            BeginSearch::Keyword {
+                keyword,
+                start_from,
+            } => {
+                let found =
+                    (start_from..len).find(|&i| args[i].eq_ignore_ascii_case(keyword.as_bytes()));
+                match found {
+                    Some(i) => i + 1,
+                    None => return vec![],
+                }
+            }
+        };
+        if begin >= len {
+            return vec![];
+        }
+        match self.find {
+            FindKeys::Range {
+                last_key,
+                step,
+                limit,
+            } => {
+                let end = match last_key >= 0 {
+                    true => (begin + last_key as usize + 1).min(len),
+                    false => match len.checked_sub(last_key.unsigned_abs() - 1) {
+                        Some(end) if end > begin => end,
+                        _ => return vec![],
+                    },
+                };
+                let end = match limit {
+                    0 | 1 => end,
+                    limit => begin + (end - begin) / limit,
+                };
+                (begin..end).step_by(step).collect()
+            }
+            FindKeys::KeyNum {
+                keynum_idx,
+                first_key,
+                step,
+            } => {
+                let count = args
+                    .get(begin + keynum_idx)
+                    .and_then(|count| std::str::from_utf8(count).ok())
+                    .and_then(|count| count.parse::<usize>().ok())
+                    .unwrap_or(0);
+                let first = begin + first_key;
+                (0..count)
+                    .map(|i| first + i * step)
+                    .take_while(|&i| i < len)
+                    .collect()
+            }
+        }
+    }
+}
+
+// the commands with a single key, their first argument
+const SINGLE_KEY: &[&str] = &[
+    "get",
+    "dump",
+    "restore",
+    "set",
+    "setnx",
+    "setex",
+    "psetex",
+    "getset",
+    "getdel",
+    "getex",
+    "incr",
+    "decr",
+    "incrby",
+    "decrby",
+    "incrbyfloat",
+    "append",
+    "setbit",
+    "getbit",
+    "bitcount",
+    "bitpos",
+    "bitfield",
+    "strlen",
+    "hget",
+    "hset",
+    "hgetall",
+    "hdel",
+    "hexists",
+    "hlen",
+    "hkeys",
+    "hvals",
+    "hstrlen",
+    "hmget",
+    "hincrby",
+    "hincrbyfloat",
+    "hrandfield",
+    "hscan",
+    "hexpire",
+    "hpexpire",
+    "hexpireat",
+    "hpexpireat",
+    "httl",
+    "hpttl",
+    "hpersist",
+    "lpush",
+    "rpush",
+    "lpop",
+    "rpop",
+    "llen",
+    "lrange",
+    "lindex",
+    "lset",
+    "linsert",
+    "lrem",
+    "ltrim",
+    "lpos",
+    "sadd",
+    "srem",
+    "smembers",
+    "scard",
+    "sismember",
+    "smismember",
+    "spop",
+    "srandmember",
+    "sscan",
+    "zadd",
+    "zrem",
+    "zscore",
+    "zcard",
+    "zrange",
+    "zrevrange",
+    "zrangebyscore",
+    "zrevrangebyscore",
+    "zrangebylex",
+    "zrevrangebylex",
+    "zlexcount",
+    "zincrby",
+    "zrank",
+    "zrevrank",
+    "zcount",
+    "zpopmin",
+    "zpopmax",
+    "zremrangebyrank",
+    "zremrangebyscore",
+    "zremrangebylex",
+    "zrandmember",
+    "zmscore",
+    "zscan",
+    "geoadd",
+    "geopos",
+    "geodist",
+    "geosearch",
+    "pfadd",
+    "xadd",
+    "xlen",
+    "xrange",
+    "xrevrange",
+    "xack",
+    "xpending",
+    "xclaim",
+    "xautoclaim",
+    "xtrim",
+    "xdel",
+    "xsetid",
+    "expire",
+    "pexpire",
+    "expireat",
+    "pexpireat",
+    "ttl",
+    "pttl",
+    "persist",
+    "expiretime",
+    "pexpiretime",
+];
+
+const SINGLE: &[KeySpec] = &[KeySpec::range(1, 0, 1)];
+const ALL: &[KeySpec] = &[KeySpec::range(1, -1, 1)];
+const FIRST_TWO: &[KeySpec] = &[KeySpec::range(1, 1, 1)];
+const SUBCOMMAND_KEY: &[KeySpec] = &[KeySpec::range(2, 0, 1)];
+const PAIRS: &[KeySpec] = &[KeySpec::range(1, -1, 2)];
+// the timeout last
+const BEFORE_TIMEOUT: &[KeySpec] = &[KeySpec::range(1, -2, 1)];
+const NUMKEYS_FIRST: &[KeySpec] = &[KeySpec::keynum(1)];
+const NUMKEYS_SECOND: &[KeySpec] = &[KeySpec::keynum(2)];
+const DESTINATION_NUMKEYS: &[KeySpec] = &[KeySpec::range(1, 0, 1), KeySpec::keynum(2)];
+// the first half of what follows STREAMS, the IDs being the other
+const STREAMS: &[KeySpec] = &[KeySpec::after("streams", 1, -1, 2)];
+// and the destination of STORE
+const SORT: &[KeySpec] = &[KeySpec::range(1, 0, 1), KeySpec::after("store", 2, 0, 0)];
+// the key, empty when there are KEYS, or those after KEYS
+const MIGRATE: &[KeySpec] = &[KeySpec::range(3, 0, 1), KeySpec::after("keys", 6, -1, 0)];
+
+/// The key specs of the built in command called name, `name|subcommand` for the subcommands
+/// taking keys. None for those without keys.
+pub fn key_specs(name: &str) -> &'static [KeySpec] {
+    match name {
+        name if SINGLE_KEY.contains(&name) => SINGLE,
+        "mget" | "del" | "sinter" | "sunion" | "sdiff" | "sinterstore" | "sunionstore"
+        | "sdiffstore" | "watch" | "pfcount" | "pfmerge" => ALL,
+        "mset" | "msetnx" => PAIRS,
+        "lcs" | "rename" | "renamenx" | "lmove" | "rpoplpush" | "smove" | "blmove"
+        | "brpoplpush" => FIRST_TWO,
+        "blpop" | "brpop" | "bzpopmin" | "bzpopmax" => BEFORE_TIMEOUT,
+        "lmpop" | "zunion" | "zinter" | "zdiff" => NUMKEYS_FIRST,
+        "blmpop" | "eval" | "evalsha" | "fcall" | "fcall_ro" => NUMKEYS_SECOND,
+        "zunionstore" | "zinterstore" | "zdiffstore" => DESTINATION_NUMKEYS,
+        "xread" | "xreadgroup" => STREAMS,
+        "sort" => SORT,
+        "migrate" => MIGRATE,
+        "xgroup|create"
+        | "xgroup|setid"
+        | "xgroup|destroy"
+        | "xgroup|createconsumer"
+        | "xgroup|delconsumer"
+        | "xinfo|stream"
+        | "xinfo|groups"
+        | "xinfo|consumers"
+        | "object|encoding"
+        | "object|refcount"
+        | "object|idletime"
+        | "object|freq"
+        | "memory|usage" => SUBCOMMAND_KEY,
+        _ => &[],
+    }
+}
+
+/// The keys of the command in array, by its key specs or those of the custom command the
+/// backend has, for the ACL to check, the cluster to route it and the commands to be run on
+/// the worker of their first key.
+pub fn command_keys(backend: &Backend, name: &str, array: &RespArray) -> Vec<String> {
+    let args: Vec<&[u8]> = array
+        .iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => arg.as_ref(),
+            _ => &[],
+        })
+        .collect();
+    let specs = match (key_specs(name), args.get(1)) {
+        ([], _) if built_in(name.as_bytes()).is_none() => backend.custom_key_specs(name),
+        ([], Some(sub)) => key_specs(&format!(
+            "{}|{}",
+            name,
+            String::from_utf8_lossy(sub).to_lowercase()
+        )),
+        (specs, _) => specs,
+    };
+    specs
+        .iter()
+        .flat_map(|spec| spec.key_indices(&args))
+        .map(|i| String::from_utf8_lossy(args[i]).into_owned())
+        // not a key, the keys follow KEYS
+        .filter(|key| name != "migrate" || !key.is_empty())
+        .collect()
+}
+
+impl CommandExecutor for CommandGetKeys {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let name = match self.command.first() {
+            Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name).to_lowercase(),
+            _ => return SimpleError::new("ERR Invalid command specified").into(),
+        };
+        let keys = command_keys(backend, &name, &self.command);
+        match backend.parse_command(self.command) {
+            Err(CommandError::InvalidCommand(_)) => {
+                SimpleError::new("ERR Invalid command specified").into()
+            }
+            Err(_) => SimpleError::new("ERR Invalid arguments specified for command").into(),
+            Ok(_) if keys.is_empty() => {
+                SimpleError::new("ERR The command has no key arguments").into()
+            }
+            Ok(_) => {
+                let keys: Vec<RespFrame> = keys
+                    .into_iter()
+                    .map(|key| BulkString::new(key).into())
+                    .collect();
+                RespArray::new(keys).into()
+            }
+        }
+    }
+}
+
+// COMMAND GETKEYS command [arg [arg ...]]
+impl TryFrom<RespArray> for CommandGetKeys {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["command", "getkeys"], 1)?;
+        Ok(CommandGetKeys {
+            command: RespArray::new(extract_args(value, 2)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_command_keys() -> Result<()> {
+        let backend = Backend::new();
+        let keys = |s: &str| -> Result<Vec<String>> {
+            let array = decode(s)?;
+            let name = match array.first() {
+                Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name).to_lowercase(),
+                _ => String::new(),
+            };
+            Ok(command_keys(&backend, &name, &array))
+        };
+        assert_eq!(keys("*2\r\n$3\r\nget\r\n$1\r\nk\r\n")?, vec!["k"]);
+        assert_eq!(
+            keys("*5\r\n$4\r\nmset\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n")?,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys("*5\r\n$4\r\neval\r\n$1\r\nx\r\n$1\r\n1\r\n$1\r\na\r\n$1\r\nb\r\n")?,
+            vec!["a"]
+        );
+        assert_eq!(
+            keys("*6\r\n$5\r\nxread\r\n$7\r\nSTREAMS\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\n0\r\n$1\r\n0\r\n")?,
+            vec!["a", "b"]
+        );
+        assert!(keys("*2\r\n$4\r\necho\r\n$1\r\nk\r\n")?.is_empty());
+        assert_eq!(
+            keys("*4\r\n$5\r\nblpop\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\n0\r\n")?,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys("*6\r\n$11\r\nzunionstore\r\n$1\r\nd\r\n$1\r\n2\r\n$1\r\na\r\n$1\r\nb\r\n$7\r\nweights\r\n")?,
+            vec!["d", "a", "b"]
+        );
+        assert_eq!(
+            keys("*5\r\n$4\r\nsort\r\n$1\r\nl\r\n$5\r\nalpha\r\n$5\r\nSTORE\r\n$1\r\nd\r\n")?,
+            vec!["l", "d"]
+        );
+        assert_eq!(
+            keys("*10\r\n$7\r\nmigrate\r\n$4\r\nhost\r\n$4\r\n6379\r\n$0\r\n\r\n$1\r\n0\r\n$4\r\n5000\r\n$4\r\nCOPY\r\n$4\r\nKEYS\r\n$1\r\na\r\n$1\r\nb\r\n")?,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys("*3\r\n$6\r\nOBJECT\r\n$8\r\nENCODING\r\n$1\r\nk\r\n")?,
+            vec!["k"]
+        );
+        assert!(keys("*2\r\n$6\r\nmemory\r\n$5\r\nstats\r\n")?.is_empty());
+        // the count of keys is past the arguments
+        assert_eq!(
+            keys("*4\r\n$4\r\neval\r\n$1\r\nx\r\n$1\r\n3\r\n$1\r\na\r\n")?,
+            vec!["a"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys() -> Result<()> {
+        let backend = Backend::new();
+        let getkeys = |s: &str| -> Result<RespFrame> {
+            let cmd = CommandGetKeys::try_from(decode(s)?)?;
+            Ok(cmd.execute(&backend))
+        };
+        assert_eq!(
+            getkeys("*5\r\n$7\r\ncommand\r\n$7\r\ngetkeys\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n")?,
+            RespArray::new(vec![BulkString::from("k").into()]).into()
+        );
+        assert_eq!(
+            getkeys("*3\r\n$7\r\ncommand\r\n$7\r\ngetkeys\r\n$4\r\nping\r\n")?,
+            SimpleError::new("ERR The command has no key arguments").into()
+        );
+        assert_eq!(
+            getkeys("*3\r\n$7\r\ncommand\r\n$7\r\ngetkeys\r\n$3\r\nget\r\n")?,
+            SimpleError::new("ERR Invalid arguments specified for command").into()
+        );
+        assert_eq!(
+            getkeys("*3\r\n$7\r\ncommand\r\n$7\r\ngetkeys\r\n$4\r\nnope\r\n")?,
+            SimpleError::new("ERR Invalid command specified").into()
+        );
+        assert!(
+            CommandGetKeys::try_from(decode("*2\r\n$7\r\ncommand\r\n$7\r\ngetkeys\r\n")?).is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_command, validate_variadic_command, CommandError,
+    CommandExecutor, ConfigGet, ConfigResetStat, ConfigRewrite, ConfigSet, RESP_OK,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespMap};
+
+impl CommandExecutor for ConfigGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let mut map = RespMap::new();
+        for (name, value) in backend.config_get(&self.patterns) {
+            map.insert(name, BulkString::from(value.as_str()).into());
+        }
+        map.into()
+    }
+}
+
+impl CommandExecutor for ConfigSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.config_set(self.pairs) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ConfigRewrite {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.config_rewrite() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ConfigResetStat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.reset_stats();
+        RESP_OK.clone()
+    }
+}
+
+// CONFIG GET parameter [parameter ...]
+impl TryFrom<RespArray> for ConfigGet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["config", "get"], 1)?;
+        let patterns = extract_args(value, 2)?
+            .into_iter()
+            .map(|frame| extract_string(Some(frame), "parameter"))
+            .collect::<Result<_, _>>()?;
+        Ok(ConfigGet { patterns })
+    }
+}
+
+// CONFIG SET parameter value [parameter value ...]
+impl TryFrom<RespArray> for ConfigSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["config", "set"], 2)?;
+        if !(value.len() - 2).is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'config|set' command".to_string(),
+            ));
+        }
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let mut pairs = Vec::new();
+        while let (Some(name), Some(value)) = (args.next(), args.next()) {
+            pairs.push((
+                extract_string(Some(name), "parameter")?,
+                extract_string(Some(value), "value")?,
+            ));
+        }
+        Ok(ConfigSet { pairs })
+    }
+}
+
+impl TryFrom<RespArray> for ConfigRewrite {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["config", "rewrite"], 0)?;
+        Ok(ConfigRewrite)
+    }
+}
+
+impl TryFrom<RespArray> for ConfigResetStat {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["config", "resetstat"], 0)?;
+        Ok(ConfigResetStat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_config_set_and_get() -> Result<()> {
+        let backend = Backend::new();
+        let frame = decode("*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$4\r\nsave\r\n$5\r\n900 1\r\n")?;
+        let set: ConfigSet = frame.try_into()?;
+        assert_eq!(set.execute(&backend), RESP_OK.clone());
+
+        let frame = decode("*3\r\n$6\r\nconfig\r\n$3\r\nget\r\n$3\r\nsa*\r\n")?;
+        let get: ConfigGet = frame.try_into()?;
+        let mut expected = RespMap::new();
+        expected.insert("save".to_string(), BulkString::from("900 1").into());
+        assert_eq!(get.execute(&backend), expected.into());
+
+        let frame = decode("*4\r\n$6\r\nconfig\r\n$3\r\nset\r\n$4\r\nsave\r\n$3\r\n900\r\n")?;
+        let set: ConfigSet = frame.try_into()?;
+        let RespFrame::Error(e) = set.execute(&backend) else {
+            panic!("expected an error");
+        };
+        assert!(e.starts_with("ERR CONFIG SET failed"));
+
+        // a parameter without its value
+        let frame = decode("*3\r\n$6\r\nconfig\r\n$3\r\nset\r\n$4\r\nsave\r\n")?;
+        assert!(ConfigSet::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_rewrite_and_resetstat() -> Result<()> {
+        let backend = Backend::new();
+        let frame = decode("*2\r\n$6\r\nconfig\r\n$7\r\nREWRITE\r\n")?;
+        let rewrite: ConfigRewrite = frame.try_into()?;
+        let RespFrame::Error(e) = rewrite.execute(&backend) else {
+            panic!("expected an error");
+        };
+        assert!(e.contains("without a config file"));
+
+        backend.count(crate::backend::Stat::Command);
+        let frame = decode("*2\r\n$6\r\nconfig\r\n$9\r\nresetstat\r\n")?;
+        let resetstat: ConfigResetStat = frame.try_into()?;
+        assert_eq!(resetstat.execute(&backend), RESP_OK.clone());
+        assert_eq!(backend.stats_report().total_commands_processed, 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command, Auth,
+    CommandError, CommandExecutor, Hello, Ping, Quit, Select, RESP_OK,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespMap, SimpleError, SimpleString};
+
+impl CommandExecutor for Hello {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        let mut map = RespMap::new();
+        map.insert("server".to_string(), BulkString::from("redis").into());
+        map.insert(
+            "version".to_string(),
+            BulkString::from(env!("CARGO_PKG_VERSION")).into(),
+        );
+        map.insert(
+            "proto".to_string(),
+            RespFrame::Integer(self.protover.unwrap_or(2) as i64),
+        );
+        map.insert("mode".to_string(), BulkString::from("standalone").into());
+        map.insert("role".to_string(), BulkString::from("master").into());
+        map.insert("modules".to_string(), RespArray::new([]).into());
+        map.into()
+    }
+}
+
+impl Hello {
+    /// Authenticate the connection of ID id first if there are credentials, HELLO failing
+    /// as a whole if they're wrong.
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        if let Some((username, password)) = &self.auth {
+            if let Err(e) = backend.auth_client(id, Some(username), password) {
+                return e.into();
+            }
+        }
+        self.execute(backend)
+    }
+}
+
+impl CommandExecutor for Auth {
+    // the user is authenticated for the connection
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR AUTH is not allowed in this context").into()
+    }
+}
+
+impl Auth {
+    pub fn apply(self, backend: &Backend, id: u64) -> RespFrame {
+        match backend.auth_client(id, self.username.as_deref(), &self.password) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// the connection is closed once the reply is sent
+impl CommandExecutor for Quit {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Ping {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.message {
+            Some(message) => message.into(),
+            None => SimpleString::new("PONG").into(),
+        }
+    }
+}
+
+impl Ping {
+    /// A subscribed connection is answered like a message, as it could be mistaken for one.
+    pub fn apply(self, backend: &Backend, subscribed: bool) -> RespFrame {
+        match subscribed {
+            true => RespArray::new([
+                BulkString::from("pong").into(),
+                self.message.unwrap_or_else(|| BulkString::from("")).into(),
+            ])
+            .into(),
+            false => self.execute(backend),
+        }
+    }
+}
+
+impl CommandExecutor for Select {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.index {
+            0 => RESP_OK.clone(),
+            _ => SimpleError::new("ERR DB index is out of range").into(),
+        }
+    }
+}
+
+// HELLO [protover [AUTH username password]]
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hello"], 0)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let protover = match args.next() {
+            None => None,
+            Some(frame) => match extract_int(Some(frame))? {
+                2 => Some(2),
+                3 => Some(3),
+                _ => {
+                    return Err(CommandError::InvalidArgument(
+                        "unsupported protocol version".to_string(),
+                    ))
+                }
+            },
+        };
+        let auth = match args.next() {
+            None => None,
+            Some(RespFrame::BulkString(option)) if option.eq_ignore_ascii_case(b"auth") => {
+                let username = extract_string(args.next(), "username")?;
+                let password = extract_string(args.next(), "password")?;
+                Some((username, password))
+            }
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(Hello { protover, auth })
+    }
+}
+
+// AUTH [username] password
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["auth"], 1)?;
+        let mut args = extract_args(value, 1)?;
+        if args.len() > 2 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let password = extract_string(args.pop(), "password")?;
+        let username = args
+            .pop()
+            .map(|frame| extract_string(Some(frame), "username"))
+            .transpose()?;
+        Ok(Auth { username, password })
+    }
+}
+
+// QUIT
+impl TryFrom<RespArray> for Quit {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["quit"], 0)?;
+        Ok(Quit)
+    }
+}
+
+// PING [message]
+impl TryFrom<RespArray> for Ping {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["ping"], 0)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let message = match args.next() {
+            None => None,
+            Some(RespFrame::BulkString(message)) => Some(message),
+            Some(_) => return Err(CommandError::InvalidArgument("Invalid message".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'ping' command".to_string(),
+            ));
+        }
+        Ok(Ping { message })
+    }
+}
+
+// SELECT index
+impl TryFrom<RespArray> for Select {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["select"], 1)?;
+        let index = extract_int(extract_args(value, 1)?.into_iter().next())?;
+        Ok(Select { index })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackendError, RespDecode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_hello_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Hello = frame.try_into()?;
+        assert_eq!(result.protover, Some(3));
+
+        buf.extend_from_slice(b"*2\r\n$5\r\nhello\r\n$1\r\n4\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Hello::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_command() {
+        let backend = Backend::new();
+        let reply = Hello {
+            protover: Some(3),
+            auth: None,
+        }
+        .execute(&backend);
+        let RespFrame::Map(map) = reply else {
+            panic!("expected a map, got {:?}", reply);
+        };
+        assert_eq!(map.get("proto"), Some(&RespFrame::Integer(3)));
+        assert_eq!(map.get("role"), Some(&BulkString::from("master").into()));
+    }
+
+    #[test]
+    fn test_ping_select_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = Ping::try_from(RespArray::new([b"ping".into()]))?;
+        assert_eq!(cmd.execute(&backend), SimpleString::new("PONG").into());
+        let cmd = Ping::try_from(RespArray::new([b"ping".into(), b"hi".into()]))?;
+        assert_eq!(cmd.apply(&backend, false), BulkString::from("hi").into());
+        let cmd = Ping::try_from(RespArray::new([b"ping".into()]))?;
+        assert_eq!(
+            cmd.apply(&backend, true),
+            RespArray::new([b"pong".into(), b"".into()]).into()
+        );
+
+        let cmd = Select::try_from(RespArray::new([b"select".into(), b"0".into()]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        let cmd = Select::try_from(RespArray::new([b"select".into(), b"1".into()]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR DB index is out of range").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth_command() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::from("*2\r\n$4\r\nauth\r\n$6\r\nsecret\r\n");
+        let auth: Auth = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            (auth.username.as_deref(), auth.password.as_str()),
+            (None, "secret")
+        );
+        // no password to check
+        assert_eq!(auth.apply(&backend, 1), BackendError::NoPassword.into());
+
+        backend.set_requirepass("secret");
+        let auth = |username: Option<&str>, password: &str| {
+            Auth {
+                username: username.map(String::from),
+                password: password.to_string(),
+            }
+            .apply(&backend, 1)
+        };
+        assert_eq!(auth(None, "secret"), RESP_OK.clone());
+        assert_eq!(auth(Some("default"), "secret"), RESP_OK.clone());
+        assert_eq!(backend.client_user(1).as_deref(), Some("default"));
+        assert_eq!(auth(None, "wrong"), BackendError::WrongPass.into());
+        assert_eq!(
+            auth(Some("alice"), "secret"),
+            BackendError::WrongPass.into()
+        );
+
+        buf.extend_from_slice(
+            b"*5\r\n$5\r\nhello\r\n$1\r\n3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$5\r\nwrong\r\n",
+        );
+        let hello: Hello = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            hello.auth,
+            Some(("default".to_string(), "wrong".to_string()))
+        );
+        assert_eq!(hello.apply(&backend, 2), BackendError::WrongPass.into());
+        assert!(!backend.is_authenticated(2));
+
+        buf.extend_from_slice(b"*4\r\n$4\r\nauth\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n");
+        assert!(Auth::try_from(RespArray::decode(&mut buf)?).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use crate::cmd::{CommandError, CommandExecutor, KeySpec};
+use crate::{Backend, RespArray, RespFrame};
+use std::fmt;
+use std::sync::Arc;
+
+/// How the server treats a custom command, beyond running it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandFlag {
+    /// It changes the keyspace: propagated to the AOF and the replicas, refused by a replica,
+    /// in the write ACL category.
+    Write,
+    /// It only reads the keyspace: in the read ACL category, read-only scripts may call it.
+    ReadOnly,
+    /// It administers the server, in the admin ACL category.
+    Admin,
+    /// Scripts can't call it.
+    NoScript,
+}
+
+type Parse = fn(RespArray) -> Result<Box<dyn CustomExecutor>, CommandError>;
+
+/// A command implemented outside of the dispatcher, run once registered with
+/// `Backend::register_command` as the others are: checked by the ACL, routed by its keys,
+/// propagated when it writes.
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub(crate) name: String,
+    pub(crate) arity: i64,
+    flags: Vec<CommandFlag>,
+    pub(crate) key_specs: &'static [KeySpec],
+    parse: Parse,
+    // the module implementing it, held loaded for as long as a command it parsed is around
+    #[cfg(feature = "modules")]
+    #[allow(dead_code)]
+    pub(crate) module: Option<Arc<libloading::Library>>,
+}
+impl CommandSpec {
+    /// The command called name, parsed into a T from the requests to execute it. arity
+    /// counts the name too: N for exactly N arguments, -N for N or more, as Redis has it.
+    pub fn new<T>(name: impl Into<String>, arity: i64) -> Self
+    where
+        T: TryFrom<RespArray, Error = CommandError>
+            + CommandExecutor
+            + fmt::Debug
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        CommandSpec {
+            name: name.into().to_lowercase(),
+            arity,
+            flags: Vec::new(),
+            key_specs: &[],
+            parse: parse::<T>,
+            #[cfg(feature = "modules")]
+            module: None,
+        }
+    }
+
+    pub fn flag(mut self, flag: CommandFlag) -> Self {
+        self.flags.push(flag);
+        self
+    }
+
+    /// Where the command takes its keys, none by default.
+    pub fn keys(mut self, key_specs: &'static [KeySpec]) -> Self {
+        self.key_specs = key_specs;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn parse<T>(value: RespArray) -> Result<Box<dyn CustomExecutor>, CommandError>
+where
+    T: TryFrom<RespArray, Error = CommandError>
+        + CommandExecutor
+        + fmt::Debug
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    Ok(Box::new(T::try_from(value)?))
+}
+
+// a custom command parsed, executed as the CommandExecutor it is
+trait CustomExecutor: fmt::Debug + Send + Sync {
+    fn execute_boxed(self: Box<Self>, backend: &Backend) -> RespFrame;
+
+    fn clone_boxed(&self) -> Box<dyn CustomExecutor>;
+}
+
+impl<T: CommandExecutor + fmt::Debug + Clone + Send + Sync + 'static> CustomExecutor for T {
+    fn execute_boxed(self: Box<Self>, backend: &Backend) -> RespFrame {
+        (*self).execute(backend)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn CustomExecutor> {
+        Box::new(self.clone())
+    }
+}
+
+/// A registered command, as parsed from a request.
+#[derive(Debug)]
+pub struct CustomCommand {
+    // dropped first, its code may be the module the spec keeps loaded
+    executor: Box<dyn CustomExecutor>,
+    spec: Arc<CommandSpec>,
+}
+
+impl Clone for CustomCommand {
+    fn clone(&self) -> Self {
+        CustomCommand {
+            executor: self.executor.clone_boxed(),
+            spec: self.spec.clone(),
+        }
+    }
+}
+
+impl CustomCommand {
+    pub fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    pub fn has_flag(&self, flag: CommandFlag) -> bool {
+        self.spec.flags.contains(&flag)
+    }
+
+    // the registered command named as the request value, checking its arity
+    pub(crate) fn parse(spec: Arc<CommandSpec>, value: RespArray) -> Result<Self, CommandError> {
+        let len = value.len() as i64;
+        if (spec.arity > 0 && len != spec.arity) || len < -spec.arity {
+            return Err(CommandError::InvalidArgument(format!(
+                "wrong number of arguments for '{}' command",
+                spec.name
+            )));
+        }
+        let executor = (spec.parse)(value)?;
+        Ok(CustomCommand { executor, spec })
+    }
+}
+
+impl CommandExecutor for CustomCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.executor.execute_boxed(backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{command_keys, extract_args, extract_string, validate_command, Command};
+    use crate::{RespDecode, RespEncode, SimpleError};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    // TEST.INCRTWICE key
+    #[derive(Debug, Clone)]
+    struct IncrTwice {
+        key: String,
+    }
+
+    impl TryFrom<RespArray> for IncrTwice {
+        type Error = CommandError;
+
+        fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+            validate_command(&value, &["test.incrtwice"], 1)?;
+            let mut args = extract_args(value, 1)?.into_iter();
+            let key = extract_string(args.next(), "key")?;
+            Ok(IncrTwice { key })
+        }
+    }
+
+    impl CommandExecutor for IncrTwice {
+        fn execute(self, backend: &Backend) -> RespFrame {
+            match backend.incr_by(&self.key, 2) {
+                Ok(n) => RespFrame::Integer(n),
+                Err(e) => SimpleError::new(e.to_string()).into(),
+            }
+        }
+    }
+
+    const KEY: &[KeySpec] = &[KeySpec::range(1, 0, 1)];
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_custom_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.register_command(
+            CommandSpec::new::<IncrTwice>("TEST.INCRTWICE", 2)
+                .flag(CommandFlag::Write)
+                .flag(CommandFlag::NoScript)
+                .keys(KEY),
+        )?;
+        let frame = decode("*2\r\n$14\r\ntest.incrtwice\r\n$1\r\nk\r\n")?;
+        assert_eq!(command_keys(&backend, "test.incrtwice", &frame), vec!["k"]);
+        // only the backend it's registered with knows it
+        assert!(Command::try_from(frame.clone()).is_err());
+        assert!(Backend::new().parse_command(frame.clone()).is_err());
+        let cmd = backend.parse_command(frame)?;
+        assert!(cmd.is_propagated());
+        assert!(cmd.in_acl_category("write"));
+        assert!(!cmd.is_read_only());
+        assert!(!cmd.is_allowed_in_script());
+        assert_eq!(cmd.clone().execute(&backend), RespFrame::Integer(2));
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(4));
+
+        let frame = decode("*1\r\n$14\r\nTEST.INCRTWICE\r\n")?;
+        let Err(e) = backend.parse_command(frame) else {
+            panic!("parsed with too few arguments");
+        };
+        assert_eq!(
+            e.to_string(),
+            "Invalid argument: wrong number of arguments for 'test.incrtwice' command"
+        );
+        let frame =
+            decode("*4\r\n$7\r\ncommand\r\n$7\r\ngetkeys\r\n$14\r\ntest.incrtwice\r\n$1\r\nk\r\n")?;
+        assert_eq!(
+            backend.parse_command(frame)?.execute(&backend).encode(),
+            b"*1\r\n$1\r\nk\r\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_command() {
+        let backend = Backend::new();
+        // taken
+        let spec = CommandSpec::new::<IncrTwice>("get", 2);
+        assert!(backend.register_command(spec).is_err());
+        let spec = CommandSpec::new::<IncrTwice>("config", -2);
+        assert!(backend.register_command(spec).is_err());
+        let spec = CommandSpec::new::<IncrTwice>("test.twice", 2);
+        assert!(backend.register_command(spec.clone()).is_ok());
+        assert!(backend.register_command(spec.clone()).is_err());
+        assert!(Backend::new().register_command(spec).is_ok());
+
+        let spec = CommandSpec::new::<IncrTwice>("test.none", 0);
+        assert!(backend.register_command(spec).is_err());
+        assert!(backend.custom_command(b"test.none").is_none());
+    }
+}
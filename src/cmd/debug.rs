@@ -0,0 +1,151 @@
+use crate::cmd::{
+    validate_command, CommandError, CommandExecutor, DebugDigest, DebugReload, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame, SimpleString};
+
+impl CommandExecutor for DebugDigest {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        SimpleString::new(backend.digest()).into()
+    }
+}
+
+impl CommandExecutor for DebugReload {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.reload() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for DebugDigest {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["debug", "digest"], 0)?;
+        Ok(DebugDigest)
+    }
+}
+
+impl TryFrom<RespArray> for DebugReload {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["debug", "reload"], 0)?;
+        Ok(DebugReload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::{Command, CommandExecutor, DebugDigest, DebugReload, RESP_OK};
+    use crate::RespDecode;
+    use crate::{Backend, BulkString, RespArray, RespFrame, SimpleString};
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use std::{env, fs};
+
+    #[test]
+    fn test_debug_digest_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$5\r\ndebug\r\n$6\r\nDIGEST\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let _: DebugDigest = frame.try_into()?;
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$5\r\ndebug\r\n$6\r\nreload\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let ret: Result<DebugDigest, _> = frame.try_into();
+        assert!(ret.is_err());
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$5\r\ndebug\r\n$5\r\nsleep\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let Err(e) = Command::try_from(frame) else {
+            panic!("parsed an unknown subcommand");
+        };
+        assert!(e
+            .to_string()
+            .ends_with("unknown subcommand for debug: sleep"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_digest_command() -> Result<()> {
+        let backend = Backend::new();
+        let result = DebugDigest.execute(&backend);
+        assert_eq!(result, SimpleString::new("0".repeat(40)).into());
+
+        backend.set("hello".to_string(), RespFrame::BulkString(b"world".into()));
+        let result = DebugDigest.execute(&backend);
+        assert_eq!(result, SimpleString::new(backend.digest()).into());
+        assert_ne!(result, SimpleString::new("0".repeat(40)).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_reload_command() -> Result<()> {
+        let mut buf = BytesMut::from("*2\r\n$5\r\nDEBUG\r\n$6\r\nRELOAD\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let reload: DebugReload = frame.try_into()?;
+
+        let dir = env::temp_dir().join(format!("simple-redis-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        let run = |args: &[&str]| -> Result<RespFrame> {
+            let args = args.iter().map(|arg| BulkString::new(*arg).into());
+            Ok(Command::try_from(RespArray::new(args.collect::<Vec<_>>()))?.execute(&backend))
+        };
+        run(&["SET", "string", "v"])?;
+        run(&["SETEX", "volatile", "100", "v"])?;
+        run(&["HSET", "hash", "f", "v", "g", "1"])?;
+        run(&["HPEXPIRE", "hash", "100000", "FIELDS", "1", "g"])?;
+        run(&["RPUSH", "list", "a", "b"])?;
+        run(&["SADD", "set", "1", "2"])?;
+        run(&["SADD", "intset", "1", "2"])?;
+        run(&["ZADD", "zset", "1.5", "m"])?;
+        run(&["XADD", "stream", "1-1", "f", "v"])?;
+        run(&["XADD", "stream", "1-2", "f", "w"])?;
+        run(&["XGROUP", "CREATE", "stream", "group", "0"])?;
+        run(&[
+            "XREADGROUP",
+            "GROUP",
+            "group",
+            "alice",
+            "COUNT",
+            "1",
+            "STREAMS",
+            "stream",
+            ">",
+        ])?;
+        run(&["XGROUP", "CREATECONSUMER", "stream", "group", "bob"])?;
+        let digest = backend.digest();
+        let before = DebugDigest.execute(&backend);
+
+        assert_eq!(reload.execute(&backend), RESP_OK.clone());
+        assert_eq!(DebugDigest.execute(&backend), before);
+        assert_eq!(backend.digest(), digest);
+        assert!(run(&["TTL", "volatile"])? > RespFrame::Integer(0));
+        let RespFrame::Array(ttls) = run(&["HTTL", "hash", "FIELDS", "1", "g"])? else {
+            panic!("expected an array");
+        };
+        assert!(ttls[0] > RespFrame::Integer(0));
+        // the digest tells a stream whose delivery was acknowledged apart
+        run(&["XACK", "stream", "group", "1-1"])?;
+        assert_ne!(backend.digest(), digest);
+        let digest = backend.digest();
+
+        fs::remove_dir_all(&dir)?;
+        let RespFrame::Error(e) = DebugReload.execute(&backend) else {
+            panic!("expected an error");
+        };
+        assert!(e.starts_with("ERR "));
+        assert_eq!(backend.digest(), digest);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use crate::backend::now_ms;
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    CommandError, CommandExecutor, Expire, ExpireAt, ExpireTime, PExpire, PExpireAt, PExpireTime,
+    PTtl, Persist, Ttl,
+};
+use crate::{Backend, ExpireOptions, RespArray, RespFrame, SimpleError};
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self
+            .seconds
+            .checked_mul(1000)
+            .and_then(|ms| ms.checked_add(now_ms()));
+        expire_at(backend, &self.key, at, &self.options, "expire")
+    }
+}
+
+impl CommandExecutor for PExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.milliseconds.checked_add(now_ms());
+        expire_at(backend, &self.key, at, &self.options, "pexpire")
+    }
+}
+
+impl CommandExecutor for ExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.timestamp.checked_mul(1000);
+        expire_at(backend, &self.key, at, &self.options, "expireat")
+    }
+}
+
+impl CommandExecutor for PExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = Some(self.timestamp_ms);
+        expire_at(backend, &self.key, at, &self.options, "pexpireat")
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        ttl_reply(backend.expire_time(&self.key), |ms| {
+            (ms - now_ms() + 500) / 1000
+        })
+    }
+}
+
+impl CommandExecutor for PTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        ttl_reply(backend.expire_time(&self.key), |ms| ms - now_ms())
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.persist(&self.key) as i64)
+    }
+}
+
+impl CommandExecutor for ExpireTime {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        ttl_reply(backend.expire_time(&self.key), |ms| ms / 1000)
+    }
+}
+
+impl CommandExecutor for PExpireTime {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        ttl_reply(backend.expire_time(&self.key), |ms| ms)
+    }
+}
+
+fn expire_at(
+    backend: &Backend,
+    key: &str,
+    at: Option<i64>,
+    options: &ExpireOptions,
+    name: &str,
+) -> RespFrame {
+    match at {
+        Some(at) => RespFrame::Integer(backend.expire_at_with(key, at, options) as i64),
+        None => SimpleError::new(format!("ERR invalid expire time in '{}' command", name)).into(),
+    }
+}
+
+// -2 if the key doesn't exist, -1 if it has no TTL, otherwise the converted expire time
+fn ttl_reply(expire_time: Option<Option<i64>>, convert: impl Fn(i64) -> i64) -> RespFrame {
+    match expire_time {
+        None => RespFrame::Integer(-2),
+        Some(None) => RespFrame::Integer(-1),
+        Some(Some(ms)) => RespFrame::Integer(convert(ms).max(0)),
+    }
+}
+
+// the key, the time, then the conditions
+fn extract_expire_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, i64, ExpireOptions), CommandError> {
+    validate_variadic_command(&value, &[name], 2)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let n = extract_int(args.next())?;
+    let mut options = ExpireOptions::default();
+    for arg in args {
+        let arg = extract_string(Some(arg), "option")?;
+        match arg.to_ascii_lowercase().as_str() {
+            "nx" => options.nx = true,
+            "xx" => options.xx = true,
+            "gt" => options.gt = true,
+            "lt" => options.lt = true,
+            _ => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Unsupported option {}",
+                    arg
+                )))
+            }
+        }
+    }
+    if options.nx && (options.xx || options.gt || options.lt) {
+        return Err(CommandError::InvalidArgument(
+            "NX and XX, GT or LT options at the same time are not compatible".to_string(),
+        ));
+    }
+    if options.gt && options.lt {
+        return Err(CommandError::InvalidArgument(
+            "GT and LT options at the same time are not compatible".to_string(),
+        ));
+    }
+    Ok((key, n, options))
+}
+
+fn extract_key(value: RespArray, name: &'static str) -> Result<String, CommandError> {
+    validate_command(&value, &[name], 1)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    extract_string(args.next(), "key")
+}
+
+impl TryFrom<RespArray> for Expire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, seconds, options) = extract_expire_args(value, "expire")?;
+        Ok(Expire {
+            key,
+            seconds,
+            options,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PExpire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, milliseconds, options) = extract_expire_args(value, "pexpire")?;
+        Ok(PExpire {
+            key,
+            milliseconds,
+            options,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ExpireAt {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, timestamp, options) = extract_expire_args(value, "expireat")?;
+        Ok(ExpireAt {
+            key,
+            timestamp,
+            options,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PExpireAt {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, timestamp_ms, options) = extract_expire_args(value, "pexpireat")?;
+        Ok(PExpireAt {
+            key,
+            timestamp_ms,
+            options,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Ttl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Ttl {
+            key: extract_key(value, "ttl")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PTtl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PTtl {
+            key: extract_key(value, "pttl")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Persist {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(Persist {
+            key: extract_key(value, "persist")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ExpireTime {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(ExpireTime {
+            key: extract_key(value, "expiretime")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PExpireTime {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(PExpireTime {
+            key: extract_key(value, "pexpiretime")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::now_ms;
+    use crate::cmd::{
+        CommandExecutor, Expire, ExpireAt, ExpireTime, PExpire, PExpireAt, PExpireTime, PTtl,
+        Persist, Ttl,
+    };
+    use crate::RespDecode;
+    use crate::{Backend, ExpireOptions, RespArray, RespFrame};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_expire_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nexpire\r\n$5\r\nhello\r\n$2\r\n10\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Expire = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+        assert_eq!(result.seconds, 10);
+
+        buf.extend_from_slice(b"*3\r\n$9\r\npexpireat\r\n$5\r\nhello\r\n$3\r\nabc\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<PExpireAt, _> = frame.try_into();
+        assert!(result.is_err());
+
+        buf.extend_from_slice(b"*4\r\n$6\r\nexpire\r\n$5\r\nhello\r\n$2\r\n10\r\n$2\r\ngt\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Expire = frame.try_into()?;
+        assert!(result.options.gt && !result.options.xx);
+        for options in [&b"nx\r\n$2\r\nxx"[..], b"gt\r\n$2\r\nlt", b"gt\r\n$2\r\nab"] {
+            buf.extend_from_slice(b"*5\r\n$6\r\nexpire\r\n$5\r\nhello\r\n$2\r\n10\r\n$2\r\n");
+            buf.extend_from_slice(options);
+            buf.extend_from_slice(b"\r\n");
+            let frame = RespArray::decode(&mut buf)?;
+            let result: Result<Expire, _> = frame.try_into();
+            assert!(result.is_err());
+        }
+
+        buf.extend_from_slice(b"*2\r\n$11\r\npexpiretime\r\n$5\r\nhello\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: PExpireTime = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expiretime_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = ExpireTime {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-2));
+
+        backend.set("hello".to_string(), RespFrame::BulkString(b"world".into()));
+        let cmd = PExpireTime {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-1));
+
+        let at = now_ms() / 1000 + 100;
+        let cmd = ExpireAt {
+            key: "hello".to_string(),
+            timestamp: at,
+            options: ExpireOptions::default(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = ExpireTime {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(at));
+        let cmd = PExpireTime {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(at * 1000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_ttl_persist_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = Expire {
+            key: "hello".to_string(),
+            seconds: 100,
+            options: ExpireOptions::default(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        backend.set("hello".to_string(), RespFrame::BulkString(b"world".into()));
+        let cmd = Expire {
+            key: "hello".to_string(),
+            seconds: 100,
+            options: ExpireOptions::default(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = Ttl {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(100));
+
+        let cmd = PExpire {
+            key: "hello".to_string(),
+            milliseconds: 50_000,
+            options: ExpireOptions::default(),
+        };
+        cmd.execute(&backend);
+        let cmd = PTtl {
+            key: "hello".to_string(),
+        };
+        assert!(
+            matches!(cmd.execute(&backend), RespFrame::Integer(ms) if ms > 49_000 && ms <= 50_000)
+        );
+
+        let cmd = Expire {
+            key: "hello".to_string(),
+            seconds: i64::MAX,
+            options: ExpireOptions::default(),
+        };
+        assert!(matches!(cmd.execute(&backend), RespFrame::Error(_)));
+
+        let cmd = Expire {
+            key: "hello".to_string(),
+            seconds: 10,
+            options: ExpireOptions {
+                gt: true,
+                ..Default::default()
+            },
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = Persist {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd = Ttl {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-1));
+
+        let cmd = PExpire {
+            key: "hello".to_string(),
+            milliseconds: -1,
+            options: ExpireOptions::default(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        assert_eq!(backend.get("hello"), Ok(None));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+use crate::cmd::{
+    extract_args, extract_float, extract_int, extract_string, validate_variadic_command,
+    CommandError, CommandExecutor, GeoAdd, GeoDist, GeoPos, GeoSearch,
+};
+use crate::{
+    Backend, BulkString, GeoOrigin, GeoSearchOptions, GeoShape, GeoSort, GeoUnit, RespArray,
+    RespFrame, RespNull, RespNullArray, ZAddOptions,
+};
+
+const ONE_ORIGIN: &str = "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH";
+const ONE_SHAPE: &str = "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH";
+
+impl CommandExecutor for GeoAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.geoadd(&self.key, self.points, &self.options) {
+            Ok(count) => RespFrame::Integer(count as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GeoPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.geopos(&self.key, &self.members) {
+            Ok(points) => {
+                let points: Vec<RespFrame> = points
+                    .into_iter()
+                    .map(|point| match point {
+                        Some((lon, lat)) => coordinates(lon, lat),
+                        None => RespFrame::NullArray(RespNullArray),
+                    })
+                    .collect();
+                RespArray::new(points).into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GeoDist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.geodist(&self.key, &self.member1, &self.member2) {
+            Ok(Some(distance)) => distance_reply(distance, self.unit),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for GeoSearch {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let found = match backend.geosearch(&self.key, &self.options) {
+            Ok(found) => found,
+            Err(e) => return e.into(),
+        };
+        let with_any = self.with_dist || self.with_hash || self.with_coord;
+        let found: Vec<RespFrame> = found
+            .into_iter()
+            .map(|found| {
+                let member = BulkString::from(found.member.as_str()).into();
+                if !with_any {
+                    return member;
+                }
+                // the member, then what was asked for in this order
+                let mut item = vec![member];
+                if self.with_dist {
+                    item.push(distance_reply(found.distance, self.unit));
+                }
+                if self.with_hash {
+                    item.push(RespFrame::Integer(found.hash as i64));
+                }
+                if self.with_coord {
+                    item.push(coordinates(found.lon, found.lat));
+                }
+                RespArray::new(item).into()
+            })
+            .collect();
+        RespArray::new(found).into()
+    }
+}
+
+fn coordinates(lon: f64, lat: f64) -> RespFrame {
+    RespArray::new(vec![RespFrame::Double(lon), RespFrame::Double(lat)]).into()
+}
+
+// a distance in unit, with 4 decimals as Redis replies it
+fn distance_reply(meters: f64, unit: GeoUnit) -> RespFrame {
+    BulkString::from(format!("{:.4}", meters / unit.meters()).as_str()).into()
+}
+
+fn extract_unit(frame: Option<RespFrame>) -> Result<GeoUnit, CommandError> {
+    extract_string(frame, "unit")?
+        .parse()
+        .map_err(CommandError::InvalidArgument)
+}
+
+fn extract_distance(frame: Option<RespFrame>, unit: GeoUnit) -> Result<f64, CommandError> {
+    match extract_float(frame)? {
+        distance if distance < 0.0 => Err(CommandError::InvalidArgument(
+            "radius cannot be negative".to_string(),
+        )),
+        distance => Ok(distance * unit.meters()),
+    }
+}
+
+// GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]
+impl TryFrom<RespArray> for GeoAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["geoadd"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let mut options = ZAddOptions::default();
+        while let Some(RespFrame::BulkString(opt)) = args.peek() {
+            match opt.to_ascii_lowercase().as_slice() {
+                b"nx" => options.nx = true,
+                b"xx" => options.xx = true,
+                b"ch" => options.ch = true,
+                _ => break,
+            }
+            args.next();
+        }
+        if options.nx && options.xx {
+            return Err(CommandError::InvalidArgument(
+                "XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        let args: Vec<_> = args.collect();
+        if args.is_empty() || args.len() % 3 != 0 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let mut points = Vec::with_capacity(args.len() / 3);
+        let mut args = args.into_iter();
+        while let Some(lon) = args.next() {
+            let lon = extract_float(Some(lon))?;
+            let lat = extract_float(args.next())?;
+            points.push((lon, lat, extract_string(args.next(), "member")?));
+        }
+        Ok(GeoAdd {
+            key,
+            options,
+            points,
+        })
+    }
+}
+
+// GEOPOS key [member [member ...]]
+impl TryFrom<RespArray> for GeoPos {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["geopos"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let members = args
+            .map(|member| extract_string(Some(member), "member"))
+            .collect::<Result<_, _>>()?;
+        Ok(GeoPos { key, members })
+    }
+}
+
+// GEODIST key member1 member2 [M | KM | FT | MI]
+impl TryFrom<RespArray> for GeoDist {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["geodist"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let member1 = extract_string(args.next(), "member")?;
+        let member2 = extract_string(args.next(), "member")?;
+        let unit = match args.next() {
+            Some(unit) => extract_unit(Some(unit))?,
+            None => GeoUnit::Meters,
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(GeoDist {
+            key,
+            member1,
+            member2,
+            unit,
+        })
+    }
+}
+
+// GEOSEARCH key FROMMEMBER member | FROMLONLAT longitude latitude
+//   BYRADIUS radius M | KM | FT | MI | BYBOX width height M | KM | FT | MI
+//   [ASC | DESC] [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]
+impl TryFrom<RespArray> for GeoSearch {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["geosearch"], 5)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let (mut origin, mut shape, mut unit) = (None, None, GeoUnit::Meters);
+        let mut sort = GeoSort::Unsorted;
+        let (mut count, mut any) = (None, false);
+        let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+        while let Some(opt) = args.next() {
+            let opt = extract_string(Some(opt), "option")?.to_ascii_lowercase();
+            match opt.as_str() {
+                "frommember" | "fromlonlat" if origin.is_some() => {
+                    return Err(CommandError::InvalidArgument(ONE_ORIGIN.to_string()))
+                }
+                "frommember" => {
+                    origin = Some(GeoOrigin::Member(extract_string(args.next(), "member")?));
+                }
+                "fromlonlat" => {
+                    let lon = extract_float(args.next())?;
+                    let lat = extract_float(args.next())?;
+                    origin = Some(GeoOrigin::LonLat(lon, lat));
+                }
+                "byradius" | "bybox" if shape.is_some() => {
+                    return Err(CommandError::InvalidArgument(ONE_SHAPE.to_string()))
+                }
+                "byradius" => {
+                    let radius = args.next();
+                    unit = extract_unit(args.next())?;
+                    shape = Some(GeoShape::Radius(extract_distance(radius, unit)?));
+                }
+                "bybox" => {
+                    let (width, height) = (args.next(), args.next());
+                    unit = extract_unit(args.next())?;
+                    shape = Some(GeoShape::Box(
+                        extract_distance(width, unit)?,
+                        extract_distance(height, unit)?,
+                    ));
+                }
+                "asc" => sort = GeoSort::Asc,
+                "desc" => sort = GeoSort::Desc,
+                "count" => {
+                    count = match extract_int(args.next())? {
+                        count if count > 0 => Some(count as usize),
+                        _ => {
+                            return Err(CommandError::InvalidArgument(
+                                "COUNT must be > 0".to_string(),
+                            ))
+                        }
+                    };
+                    if let Some(RespFrame::BulkString(opt)) = args.peek() {
+                        if opt.eq_ignore_ascii_case(b"any") {
+                            any = true;
+                            args.next();
+                        }
+                    }
+                }
+                "withcoord" => with_coord = true,
+                "withdist" => with_dist = true,
+                "withhash" => with_hash = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        let Some(origin) = origin else {
+            return Err(CommandError::InvalidArgument(ONE_ORIGIN.to_string()));
+        };
+        let Some(shape) = shape else {
+            return Err(CommandError::InvalidArgument(ONE_SHAPE.to_string()));
+        };
+        Ok(GeoSearch {
+            key,
+            options: GeoSearchOptions {
+                origin,
+                shape,
+                sort,
+                count: count.map(|count| (count, any)),
+            },
+            unit,
+            with_coord,
+            with_dist,
+            with_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn request(args: &[&str]) -> Result<Command> {
+        let mut s = format!("*{}\r\n", args.len());
+        for arg in args {
+            s.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        let mut buf = BytesMut::from(s.as_str());
+        Ok(Command::try_from(RespArray::decode(&mut buf)?)?)
+    }
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::from(s).into()
+    }
+
+    #[test]
+    fn test_geo_commands() -> Result<()> {
+        let backend = Backend::new();
+        let geoadd = [
+            "GEOADD",
+            "Sicily",
+            "13.361389",
+            "38.115556",
+            "Palermo",
+            "15.087269",
+            "37.502669",
+            "Catania",
+        ];
+        assert_eq!(request(&geoadd)?.execute(&backend), RespFrame::Integer(2));
+        let geoadd = ["GEOADD", "Sicily", "NX", "CH", "1", "1", "Palermo"];
+        assert_eq!(request(&geoadd)?.execute(&backend), RespFrame::Integer(0));
+
+        let geodist = ["GEODIST", "Sicily", "Palermo", "Catania", "km"];
+        assert_eq!(request(&geodist)?.execute(&backend), bulk("166.2742"));
+        let geodist = ["GEODIST", "Sicily", "Palermo", "x"];
+        assert_eq!(
+            request(&geodist)?.execute(&backend),
+            RespFrame::Null(RespNull)
+        );
+
+        let RespFrame::Array(points) =
+            request(&["GEOPOS", "Sicily", "Palermo", "x"])?.execute(&backend)
+        else {
+            panic!("GEOPOS isn't an array");
+        };
+        assert!(matches!(&points[0], RespFrame::Array(point) if point.len() == 2));
+        assert_eq!(points[1], RespFrame::NullArray(RespNullArray));
+
+        let geosearch = [
+            "GEOSEARCH",
+            "Sicily",
+            "FROMLONLAT",
+            "15",
+            "37",
+            "BYRADIUS",
+            "200",
+            "km",
+            "ASC",
+        ];
+        assert_eq!(
+            request(&geosearch)?.execute(&backend),
+            RespArray::new(vec![bulk("Catania"), bulk("Palermo")]).into()
+        );
+        let geosearch = [
+            "GEOSEARCH",
+            "Sicily",
+            "FROMMEMBER",
+            "Palermo",
+            "BYBOX",
+            "400",
+            "400",
+            "km",
+            "COUNT",
+            "1",
+            "WITHHASH",
+            "WITHDIST",
+        ];
+        assert_eq!(
+            request(&geosearch)?.execute(&backend),
+            RespArray::new(vec![RespArray::new(vec![
+                bulk("Palermo"),
+                bulk("0.0000"),
+                RespFrame::Integer(3479099956230698),
+            ])
+            .into()])
+            .into()
+        );
+        let geosearch = [
+            "GEOSEARCH",
+            "Sicily",
+            "FROMMEMBER",
+            "x",
+            "BYRADIUS",
+            "1",
+            "m",
+        ];
+        assert!(matches!(
+            request(&geosearch)?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        let geoadd = ["GEOADD", "Sicily", "181", "0", "x"];
+        assert!(matches!(
+            request(&geoadd)?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_geo_syntax() -> Result<()> {
+        for args in [
+            &["GEOADD", "k", "1", "2"][..],
+            &["GEOADD", "k", "NX", "XX", "1", "2", "m"],
+            &["GEOADD", "k", "GT", "1", "2", "m"],
+            &["GEODIST", "k", "a", "b", "yd"],
+            &["GEOSEARCH", "k", "FROMLONLAT", "1", "2", "BYRADIUS", "1"],
+            &["GEOSEARCH", "k", "FROMLONLAT", "1", "2", "COUNT", "1"],
+            &["GEOSEARCH", "k", "BYRADIUS", "1", "m", "COUNT", "1"],
+            &["GEOSEARCH", "k", "FROMMEMBER", "a", "BYRADIUS", "-1", "m"],
+            &[
+                "GEOSEARCH",
+                "k",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "1",
+                "m",
+                "COUNT",
+                "0",
+            ],
+            &[
+                "GEOSEARCH",
+                "k",
+                "FROMMEMBER",
+                "a",
+                "FROMLONLAT",
+                "1",
+                "2",
+                "BYBOX",
+                "1",
+                "1",
+                "m",
+            ],
+        ] {
+            assert!(request(args).is_err(), "{:?} parsed", args);
+        }
+        let Command::GeoSearch(search) = request(&[
+            "GEOSEARCH",
+            "k",
+            "FROMMEMBER",
+            "a",
+            "BYBOX",
+            "2",
+            "1",
+            "km",
+            "DESC",
+            "COUNT",
+            "3",
+            "ANY",
+            "WITHCOORD",
+        ])?
+        else {
+            panic!("not a GEOSEARCH");
+        };
+        assert_eq!(search.options.shape, GeoShape::Box(2000.0, 1000.0));
+        assert_eq!(search.options.count, Some((3, true)));
+        assert_eq!(search.options.sort, GeoSort::Desc);
+        assert_eq!(search.unit, GeoUnit::Kilometers);
+        assert!(search.with_coord && !search.with_dist);
+        Ok(())
+    }
+}
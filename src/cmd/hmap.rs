@@ -1,42 +1,297 @@
+use crate::backend::now_ms;
 use crate::cmd::{
-    extract_args, validate_command, CommandError, CommandExecutor, HGet, HGetAll, HSet, RESP_OK,
+    extract_args, extract_cursor, extract_float, extract_int, extract_string, scan_reply,
+    validate_command, validate_variadic_command, CommandError, CommandExecutor, HDel, HExists,
+    HExpire, HExpireAt, HGet, HGetAll, HIncrBy, HIncrByFloat, HKeys, HLen, HMGet, HPExpire,
+    HPExpireAt, HPTtl, HPersist, HRandField, HScan, HSet, HStrLen, HTtl, HVals,
+};
+use crate::{
+    glob_match, Backend, BackendError, BulkString, FieldExpire, RespArray, RespFrame, RespMap,
+    RespNull, SimpleError,
 };
-use crate::{Backend, RespArray, RespFrame, RespMap, RespNull};
 
 impl CommandExecutor for HGet {
     fn execute(self, backend: &Backend) -> RespFrame {
         match backend.hget(&self.key, &self.field) {
-            None => RespFrame::Null(RespNull),
-            Some(value) => value,
+            Ok(None) => RespFrame::Null(RespNull),
+            Ok(Some(value)) => value,
+            Err(e) => e.into(),
         }
     }
 }
 
 impl CommandExecutor for HSet {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend.hset(self.key, self.field, self.value);
-        RESP_OK.clone()
+        match backend.hset_multiple(self.key, self.fields) {
+            Ok(created) => RespFrame::Integer(created as i64),
+            Err(e) => e.into(),
+        }
     }
 }
 
-impl CommandExecutor for HGetAll {
+impl CommandExecutor for HMGet {
     fn execute(self, backend: &Backend) -> RespFrame {
-        let hmap = backend.hmap.get(&self.key);
+        match backend.hmget(&self.key, &self.fields) {
+            Ok(values) => RespArray::new(
+                values
+                    .into_iter()
+                    .map(|v| v.unwrap_or(RespFrame::Null(RespNull)))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
 
-        match hmap {
-            Some(hmap) => {
+impl CommandExecutor for HGetAll {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hgetall(&self.key) {
+            Ok(Some(hmap)) => {
                 let mut resp_map = RespMap::new();
-                for v in hmap.iter() {
-                    let key = v.key().to_owned();
-                    resp_map.insert(key, v.value().clone());
+                for (key, value) in hmap.into_iter() {
+                    resp_map.insert(key, value);
                 }
                 resp_map.into()
             }
-            None => RespArray::new([]).into(),
+            Ok(None) => RespArray::new([]).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hdel(&self.key, &self.fields) {
+            Ok(removed) => RespFrame::Integer(removed as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HExists {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hexists(&self.key, &self.field) {
+            Ok(exists) => RespFrame::Integer(exists as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hlen(&self.key) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HKeys {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hkeys(&self.key) {
+            Ok(fields) => RespArray::new(
+                fields
+                    .into_iter()
+                    .map(|field| BulkString::new(field).into())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HVals {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hvals(&self.key) {
+            Ok(values) => RespArray::new(values).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HStrLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hstrlen(&self.key, &self.field) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hincr_by(&self.key, &self.field, self.increment) {
+            Ok(value) => RespFrame::Integer(value),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HIncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hincr_by_float(&self.key, &self.field, self.increment) {
+            Ok(value) => BulkString::new(value.to_string()).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HRandField {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let picked = match backend.hrandfield(&self.key, self.count.unwrap_or(1)) {
+            Ok(picked) => picked,
+            Err(e) => return e.into(),
+        };
+        if self.count.is_none() {
+            return match picked.into_iter().next() {
+                Some((field, _)) => BulkString::new(field).into(),
+                None => RespFrame::Null(RespNull),
+            };
+        }
+
+        let mut reply = Vec::with_capacity(picked.len() * 2);
+        for (field, value) in picked {
+            reply.push(BulkString::new(field).into());
+            if self.with_values {
+                reply.push(value);
+            }
+        }
+        RespArray::new(reply).into()
+    }
+}
+
+impl CommandExecutor for HScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let (next, page) = match backend.hscan(&self.key, self.cursor, self.count) {
+            Ok(scanned) => scanned,
+            Err(e) => return e.into(),
+        };
+
+        let mut elements = Vec::with_capacity(page.len() * 2);
+        for (field, value) in page {
+            if let Some(pattern) = &self.pattern {
+                if !glob_match(pattern.as_bytes(), field.as_bytes()) {
+                    continue;
+                }
+            }
+            elements.push(BulkString::new(field).into());
+            if !self.no_values {
+                elements.push(value);
+            }
+        }
+        scan_reply(next, elements)
+    }
+}
+
+impl CommandExecutor for HExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self
+            .seconds
+            .checked_mul(1000)
+            .and_then(|ms| ms.checked_add(now_ms()));
+        hexpire_at(backend, &self.key, &self.fields, at, "hexpire")
+    }
+}
+
+impl CommandExecutor for HPExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.milliseconds.checked_add(now_ms());
+        hexpire_at(backend, &self.key, &self.fields, at, "hpexpire")
+    }
+}
+
+impl CommandExecutor for HExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.timestamp.checked_mul(1000);
+        hexpire_at(backend, &self.key, &self.fields, at, "hexpireat")
+    }
+}
+
+impl CommandExecutor for HPExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        hexpire_at(
+            backend,
+            &self.key,
+            &self.fields,
+            Some(self.timestamp_ms),
+            "hpexpireat",
+        )
+    }
+}
+
+impl CommandExecutor for HTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        httl_reply(backend.hexpire_time(&self.key, &self.fields), |ms| {
+            (ms - now_ms() + 500) / 1000
+        })
+    }
+}
+
+impl CommandExecutor for HPTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        httl_reply(backend.hexpire_time(&self.key, &self.fields), |ms| {
+            ms - now_ms()
+        })
+    }
+}
+
+impl CommandExecutor for HPersist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.hpersist(&self.key, &self.fields) {
+            Ok(results) => integers_reply(results.into_iter().map(|persisted| match persisted {
+                None => -2,
+                Some(false) => -1,
+                Some(true) => 1,
+            })),
+            Err(e) => e.into(),
         }
     }
 }
 
+// one integer per field: -2 if the field doesn't exist, 1 if the TTL was set, 2 if the field
+// was deleted because the time is in the past
+fn hexpire_at(
+    backend: &Backend,
+    key: &str,
+    fields: &[String],
+    at: Option<i64>,
+    name: &str,
+) -> RespFrame {
+    let Some(at) = at else {
+        return SimpleError::new(format!("ERR invalid expire time in '{}' command", name)).into();
+    };
+    match backend.hexpire_at(key, fields, at) {
+        Ok(results) => integers_reply(results.into_iter().map(|result| match result {
+            FieldExpire::NoField => -2,
+            FieldExpire::Set => 1,
+            FieldExpire::Deleted => 2,
+        })),
+        Err(e) => e.into(),
+    }
+}
+
+// one integer per field: -2 if the field doesn't exist, -1 if it has no TTL, otherwise the
+// converted expire time
+fn httl_reply(
+    expire_times: Result<Vec<Option<Option<i64>>>, BackendError>,
+    convert: impl Fn(i64) -> i64,
+) -> RespFrame {
+    match expire_times {
+        Ok(times) => integers_reply(times.into_iter().map(|time| match time {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(ms)) => convert(ms).max(0),
+        })),
+        Err(e) => e.into(),
+    }
+}
+
+fn integers_reply(values: impl Iterator<Item = i64>) -> RespFrame {
+    RespArray::new(values.map(RespFrame::Integer).collect::<Vec<_>>()).into()
+}
+
 impl TryFrom<RespArray> for HGet {
     type Error = CommandError;
 
@@ -46,8 +301,8 @@ impl TryFrom<RespArray> for HGet {
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HGet {
-                key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                key: String::from_utf8(key.0.into())?,
+                field: String::from_utf8(field.0.into())?,
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or field".to_string(),
@@ -65,7 +320,7 @@ impl TryFrom<RespArray> for HGetAll {
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(HGetAll {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.0.into())?,
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
@@ -76,29 +331,360 @@ impl TryFrom<RespArray> for HSet {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["hset"], 3)?;
+        validate_variadic_command(&value, &["hset"], 3)?;
+        if !(value.len() - 2).is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'hset' command".to_string(),
+            ));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let mut fields = Vec::new();
+        while let (Some(field), Some(value)) = (args.next(), args.next()) {
+            fields.push((extract_string(Some(field), "field")?, value));
+        }
+        Ok(HSet { key, fields })
+    }
+}
+
+impl TryFrom<RespArray> for HMGet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hmget"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let fields = args
+            .map(|field| extract_string(Some(field), "field"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(HMGet { key, fields })
+    }
+}
+
+impl TryFrom<RespArray> for HDel {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hdel"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let fields = args
+            .map(|field| extract_string(Some(field), "field"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(HDel { key, fields })
+    }
+}
+
+impl TryFrom<RespArray> for HExists {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hexists"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let field = extract_string(args.next(), "field")?;
+        Ok(HExists { key, field })
+    }
+}
+
+impl TryFrom<RespArray> for HLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hlen"], 1)?;
 
         let mut args = extract_args(value, 1)?.into_iter();
-        match (args.next(), args.next(), args.next()) {
-            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field)), Some(value)) => {
-                Ok(HSet {
-                    key: String::from_utf8(key.0)?,
-                    field: String::from_utf8(field.0)?,
-                    value,
-                })
+        let key = extract_string(args.next(), "key")?;
+        Ok(HLen { key })
+    }
+}
+
+impl TryFrom<RespArray> for HKeys {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hkeys"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(HKeys { key })
+    }
+}
+
+impl TryFrom<RespArray> for HVals {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hvals"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(HVals { key })
+    }
+}
+
+impl TryFrom<RespArray> for HStrLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hstrlen"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let field = extract_string(args.next(), "field")?;
+        Ok(HStrLen { key, field })
+    }
+}
+
+impl TryFrom<RespArray> for HIncrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hincrby"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let field = extract_string(args.next(), "field")?;
+        let increment = extract_int(args.next())?;
+        Ok(HIncrBy {
+            key,
+            field,
+            increment,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HIncrByFloat {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["hincrbyfloat"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let field = extract_string(args.next(), "field")?;
+        let increment = extract_float(args.next())?;
+        Ok(HIncrByFloat {
+            key,
+            field,
+            increment,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HRandField {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hrandfield"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let count = args
+            .next()
+            .map(|count| extract_int(Some(count)))
+            .transpose()?;
+        let with_values = match args.next() {
+            None => false,
+            Some(RespFrame::BulkString(opt)) if opt.eq_ignore_ascii_case(b"withvalues") => true,
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HScan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hscan"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut scan = HScan {
+            key: extract_string(args.next(), "key")?,
+            cursor: extract_cursor(args.next())?,
+            pattern: None,
+            count: 10,
+            no_values: false,
+        };
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"match" => scan.pattern = Some(extract_string(args.next(), "pattern")?),
+                b"count" => match extract_int(args.next())? {
+                    count if count >= 1 => scan.count = count as usize,
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                },
+                b"novalues" => scan.no_values = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
             }
-            _ => Err(CommandError::InvalidArgument(
-                "Invalid key, field or value".to_string(),
-            )),
         }
+        Ok(scan)
     }
 }
 
+impl TryFrom<RespArray> for HExpire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hexpire"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let seconds = extract_int(args.next())?;
+        let fields = extract_fields(args)?;
+        Ok(HExpire {
+            key,
+            seconds,
+            fields,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HPExpire {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hpexpire"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let milliseconds = extract_int(args.next())?;
+        let fields = extract_fields(args)?;
+        Ok(HPExpire {
+            key,
+            milliseconds,
+            fields,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HExpireAt {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hexpireat"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let timestamp = extract_int(args.next())?;
+        let fields = extract_fields(args)?;
+        Ok(HExpireAt {
+            key,
+            timestamp,
+            fields,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HPExpireAt {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["hpexpireat"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let timestamp_ms = extract_int(args.next())?;
+        let fields = extract_fields(args)?;
+        Ok(HPExpireAt {
+            key,
+            timestamp_ms,
+            fields,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for HTtl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = extract_key_and_fields(value, "httl")?;
+        Ok(HTtl { key, fields })
+    }
+}
+
+impl TryFrom<RespArray> for HPTtl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = extract_key_and_fields(value, "hpttl")?;
+        Ok(HPTtl { key, fields })
+    }
+}
+
+impl TryFrom<RespArray> for HPersist {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, fields) = extract_key_and_fields(value, "hpersist")?;
+        Ok(HPersist { key, fields })
+    }
+}
+
+fn extract_key_and_fields(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Vec<String>), CommandError> {
+    validate_variadic_command(&value, &[name], 3)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let fields = extract_fields(args)?;
+    Ok((key, fields))
+}
+
+// the trailing `FIELDS numfields field [field ...]` arguments of the field TTL commands
+fn extract_fields(mut args: impl Iterator<Item = RespFrame>) -> Result<Vec<String>, CommandError> {
+    match args.next() {
+        Some(RespFrame::BulkString(arg)) if arg.eq_ignore_ascii_case(b"fields") => {}
+        _ => {
+            return Err(CommandError::InvalidArgument(
+                "Mandatory argument FIELDS is missing or not at the right position".to_string(),
+            ))
+        }
+    }
+    let n = extract_int(args.next())?;
+    if n <= 0 {
+        return Err(CommandError::InvalidArgument(
+            "Parameter `numFields` should be greater than 0".to_string(),
+        ));
+    }
+
+    let fields = args
+        .map(|field| extract_string(Some(field), "field"))
+        .collect::<Result<Vec<_>, _>>()?;
+    if fields.len() as i64 != n {
+        return Err(CommandError::InvalidArgument(
+            "The `numfields` parameter must match the number of arguments".to_string(),
+        ));
+    }
+    Ok(fields)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::cmd::{CommandExecutor, HGet, HGetAll, HSet, RESP_OK};
-    use crate::RespDecode;
-    use crate::{Backend, RespArray, RespFrame, RespMap};
+    use crate::backend::now_ms;
+    use crate::cmd::{
+        CommandExecutor, HDel, HExists, HExpire, HExpireAt, HGet, HGetAll, HIncrBy, HIncrByFloat,
+        HKeys, HLen, HMGet, HPExpireAt, HPersist, HRandField, HScan, HSet, HStrLen, HTtl, HVals,
+    };
+    use crate::{Backend, RespArray, RespFrame, RespMap, RespNull};
+    use crate::{BackendError, RespDecode};
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -136,8 +722,26 @@ mod tests {
         let frame = RespArray::decode(&mut buf)?;
         let result: HSet = frame.try_into()?;
         assert_eq!(result.key, "key");
-        assert_eq!(result.field, "field");
-        assert_eq!(result.value, RespFrame::BulkString(b"value".into()));
+        assert_eq!(
+            result.fields,
+            vec![("field".to_string(), RespFrame::BulkString(b"value".into()))]
+        );
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$4\r\nhset\r\n$3\r\nkey\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: HSet = frame.try_into()?;
+        assert_eq!(result.fields.len(), 2);
+
+        // a field without a value
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$4\r\nhset\r\n$3\r\nkey\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(HSet::try_from(frame).is_err());
 
         Ok(())
     }
@@ -147,19 +751,33 @@ mod tests {
         let backend = Backend::new();
         let cmd = HSet {
             key: "k1".to_string(),
-            field: "f1".to_string(),
-            value: RespFrame::BulkString(b"hhhhhh".into()),
+            fields: vec![("f1".to_string(), RespFrame::BulkString(b"hhhhhh".into()))],
         };
 
         let result = cmd.execute(&backend);
-        assert_eq!(result, RESP_OK.clone());
+        assert_eq!(result, RespFrame::Integer(1));
 
         let cmd = HSet {
             key: "k1".to_string(),
-            field: "f2".to_string(),
-            value: RespFrame::BulkString(b"iiiiii".into()),
+            fields: vec![
+                ("f1".to_string(), RespFrame::BulkString(b"hhhhhh".into())),
+                ("f2".to_string(), RespFrame::BulkString(b"iiiiii".into())),
+            ],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = HMGet {
+            key: "k1".to_string(),
+            fields: vec!["f2".to_string(), "f3".to_string()],
         };
-        cmd.execute(&backend);
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([
+                RespFrame::BulkString(b"iiiiii".into()),
+                RespFrame::Null(RespNull)
+            ])
+            .into()
+        );
 
         let cmd = HGet {
             key: "k1".to_string(),
@@ -179,4 +797,292 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_hdel_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$4\r\nhdel\r\n$3\r\nkey\r\n$2\r\nf1\r\n$2\r\nf2\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: HDel = frame.try_into()?;
+        assert_eq!(result.key, "key");
+        assert_eq!(result.fields, vec!["f1", "f2"]);
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$4\r\nhdel\r\n$3\r\nkey\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(HDel::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdel_hexists_hlen_command() -> Result<()> {
+        let backend = Backend::new();
+        for field in ["f1", "f2"] {
+            backend.hset("k1".to_string(), field.to_string(), RespFrame::Integer(1))?;
+        }
+
+        let cmd = HLen {
+            key: "k1".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd = HDel {
+            key: "k1".to_string(),
+            fields: vec!["f1".to_string(), "f3".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = HExists {
+            key: "k1".to_string(),
+            field: "f1".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+        let cmd = HExists {
+            key: "k1".to_string(),
+            field: "f2".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hkeys_hvals_hstrlen_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset(
+            "k1".to_string(),
+            "f1".to_string(),
+            RespFrame::BulkString(b"abc".into()),
+        )?;
+
+        let cmd = HKeys {
+            key: "k1".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::BulkString(b"f1".into())]).into()
+        );
+
+        let cmd = HVals {
+            key: "k1".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::BulkString(b"abc".into())]).into()
+        );
+
+        let cmd = HVals {
+            key: "missing".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespArray::new([]).into());
+
+        let cmd = HStrLen {
+            key: "k1".to_string(),
+            field: "f1".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hincrby_hincrbyfloat_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$7\r\nhincrby\r\n$3\r\nkey\r\n$1\r\nf\r\n$2\r\n-3\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: HIncrBy = frame.try_into()?;
+        assert_eq!(cmd.increment, -3);
+
+        let backend = Backend::new();
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-3));
+
+        let cmd = HIncrByFloat {
+            key: "key".to_string(),
+            field: "f".to_string(),
+            increment: 0.5,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::BulkString(b"-2.5".into()));
+
+        let cmd = HIncrBy {
+            key: "key".to_string(),
+            field: "f".to_string(),
+            increment: 1,
+        };
+        assert_eq!(cmd.execute(&backend), BackendError::HashNotInteger.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hrandfield_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*4\r\n$10\r\nhrandfield\r\n$3\r\nkey\r\n$2\r\n-2\r\n$10\r\nWITHVALUES\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: HRandField = frame.try_into()?;
+        assert_eq!(cmd.count, Some(-2));
+        assert!(cmd.with_values);
+
+        let backend = Backend::new();
+        backend.hset("key".to_string(), "f".to_string(), RespFrame::Integer(1))?;
+        let f = RespFrame::BulkString(b"f".into());
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([
+                f.clone(),
+                RespFrame::Integer(1),
+                f.clone(),
+                RespFrame::Integer(1)
+            ])
+            .into()
+        );
+
+        let cmd = HRandField {
+            key: "key".to_string(),
+            count: None,
+            with_values: false,
+        };
+        assert_eq!(cmd.execute(&backend), f);
+        let cmd = HRandField {
+            key: "missing".to_string(),
+            count: None,
+            with_values: false,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hscan_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*7\r\n$5\r\nhscan\r\n$3\r\nkey\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$2\r\na*\r\n$8\r\nNOVALUES\r\n$5\r\ncount\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        // COUNT without its value
+        assert!(HScan::try_from(frame).is_err());
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$5\r\nhscan\r\n$3\r\nkey\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$2\r\na*\r\n$8\r\nNOVALUES\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: HScan = frame.try_into()?;
+        assert_eq!(cmd.pattern.as_deref(), Some("a*"));
+        assert!(cmd.no_values);
+
+        let backend = Backend::new();
+        for field in ["a1", "b1"] {
+            backend.hset("key".to_string(), field.to_string(), RespFrame::Integer(1))?;
+        }
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([
+                RespFrame::BulkString(b"0".into()),
+                RespArray::new([RespFrame::BulkString(b"a1".into())]).into(),
+            ])
+            .into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexpire_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*7\r\n$7\r\nhexpire\r\n$3\r\nkey\r\n$3\r\n100\r\n$6\r\nFIELDS\r\n$1\r\n2\r\n$2\r\nf1\r\n$2\r\nf2\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: HExpire = frame.try_into()?;
+        assert_eq!(result.seconds, 100);
+        assert_eq!(result.fields, vec!["f1", "f2"]);
+
+        // numfields must match the fields given
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$7\r\nhexpire\r\n$3\r\nkey\r\n$3\r\n100\r\n$6\r\nFIELDS\r\n$1\r\n2\r\n$2\r\nf1\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(HExpire::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexpire_httl_hpersist_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset("k1".to_string(), "f1".to_string(), RespFrame::Integer(1))?;
+        let fields = vec!["f1".to_string(), "f2".to_string()];
+
+        let cmd = HExpire {
+            key: "k1".to_string(),
+            seconds: 100,
+            fields: fields.clone(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::Integer(1), RespFrame::Integer(-2)]).into()
+        );
+
+        let cmd = HTtl {
+            key: "k1".to_string(),
+            fields: fields.clone(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::Integer(100), RespFrame::Integer(-2)]).into()
+        );
+
+        let cmd = HPersist {
+            key: "k1".to_string(),
+            fields,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::Integer(1), RespFrame::Integer(-2)]).into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hpexpireat_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset("k1".to_string(), "f1".to_string(), RespFrame::Integer(1))?;
+        backend.hset("k1".to_string(), "f2".to_string(), RespFrame::Integer(2))?;
+        let at = now_ms() + 100_000;
+
+        let cmd = HPExpireAt {
+            key: "k1".to_string(),
+            timestamp_ms: at,
+            fields: vec!["f1".to_string(), "f3".to_string()],
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::Integer(1), RespFrame::Integer(-2)]).into()
+        );
+        assert_eq!(
+            backend.hexpire_time("k1", &["f1".to_string()]),
+            Ok(vec![Some(Some(at))])
+        );
+
+        // a time in the past deletes the field
+        let cmd = HExpireAt {
+            key: "k1".to_string(),
+            timestamp: 1,
+            fields: vec!["f2".to_string()],
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::Integer(2)]).into()
+        );
+        assert_eq!(backend.hexists("k1", "f2"), Ok(false));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,136 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_variadic_command, CommandError, CommandExecutor, PfAdd,
+    PfCount, PfMerge, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame};
+
+impl CommandExecutor for PfAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.pfadd(&self.key, &self.elements) {
+            Ok(changed) => RespFrame::Integer(changed as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for PfCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.pfcount(&self.keys) {
+            Ok(count) => RespFrame::Integer(count as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for PfMerge {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.pfmerge(&self.destination, &self.sources) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+fn extract_keys(args: impl Iterator<Item = RespFrame>) -> Result<Vec<String>, CommandError> {
+    args.map(|key| extract_string(Some(key), "key")).collect()
+}
+
+// PFADD key [element [element ...]]
+impl TryFrom<RespArray> for PfAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["pfadd"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let elements = args
+            .map(|element| match element {
+                RespFrame::BulkString(element) => Ok(element.to_vec()),
+                _ => Err(CommandError::InvalidArgument("Invalid element".to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(PfAdd { key, elements })
+    }
+}
+
+// PFCOUNT key [key ...]
+impl TryFrom<RespArray> for PfCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["pfcount"], 1)?;
+
+        let keys = extract_keys(extract_args(value, 1)?.into_iter())?;
+        Ok(PfCount { keys })
+    }
+}
+
+// PFMERGE destkey [sourcekey [sourcekey ...]]
+impl TryFrom<RespArray> for PfMerge {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["pfmerge"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let destination = extract_string(args.next(), "key")?;
+        let sources = extract_keys(args)?;
+        Ok(PfMerge {
+            destination,
+            sources,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::{BulkString, RespDecode};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn request(args: &[&str]) -> Result<Command> {
+        let mut s = format!("*{}\r\n", args.len());
+        for arg in args {
+            s.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        let mut buf = BytesMut::from(s.as_str());
+        Ok(Command::try_from(RespArray::decode(&mut buf)?)?)
+    }
+
+    #[test]
+    fn test_hyperloglog_commands() -> Result<()> {
+        let backend = Backend::new();
+        let pfadd = ["PFADD", "h1", "a", "b", "c"];
+        assert_eq!(request(&pfadd)?.execute(&backend), RespFrame::Integer(1));
+        assert_eq!(request(&pfadd)?.execute(&backend), RespFrame::Integer(0));
+        let pfadd = ["PFADD", "h2", "c", "d"];
+        assert_eq!(request(&pfadd)?.execute(&backend), RespFrame::Integer(1));
+
+        let pfcount = ["PFCOUNT", "h1"];
+        assert_eq!(request(&pfcount)?.execute(&backend), RespFrame::Integer(3));
+        let pfcount = ["PFCOUNT", "h1", "h2", "h3"];
+        assert_eq!(request(&pfcount)?.execute(&backend), RespFrame::Integer(4));
+
+        let pfmerge = ["PFMERGE", "h3", "h1", "h2"];
+        assert_eq!(request(&pfmerge)?.execute(&backend), RESP_OK.clone());
+        let pfcount = ["PFCOUNT", "h3"];
+        assert_eq!(request(&pfcount)?.execute(&backend), RespFrame::Integer(4));
+
+        backend.set("s".to_string(), BulkString::from("v").into());
+        assert!(matches!(
+            request(&["PFCOUNT", "h1", "s"])?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hyperloglog_syntax() {
+        for args in [&["PFADD"][..], &["PFCOUNT"], &["PFMERGE"]] {
+            assert!(request(args).is_err(), "{:?} should be rejected", args);
+        }
+    }
+}
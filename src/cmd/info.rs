@@ -0,0 +1,369 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_variadic_command, CommandError, CommandExecutor, Info,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame};
+use std::fmt::Write;
+
+// the fields of a section, by name
+type Fields = Vec<(String, String)>;
+
+// the sections INFO reports, in order
+const SECTIONS: &[&str] = &[
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cluster",
+    "sentinel",
+    "commandstats",
+];
+
+// the sections only reported when asked for, by name or with all
+const EXTRA_SECTIONS: &[&str] = &["commandstats"];
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let asked = |names: &[&str]| {
+            self.sections
+                .iter()
+                .any(|section| names.contains(&section.as_str()))
+        };
+        let all = asked(&["all", "everything"]);
+        let default = self.sections.is_empty() || asked(&["default"]);
+        let mut info = String::new();
+        for name in SECTIONS {
+            let reported = all || asked(&[name]) || (default && !EXTRA_SECTIONS.contains(name));
+            // a sentinel only
+            if !reported || *name == "sentinel" && !backend.is_sentinel() {
+                continue;
+            }
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+            let _ = write!(info, "# {}{}\r\n", name[..1].to_uppercase(), &name[1..]);
+            for (field, value) in fields(backend, name) {
+                let _ = write!(info, "{}:{}\r\n", field, value);
+            }
+        }
+        BulkString::from(info.as_str()).into()
+    }
+}
+
+fn fields(backend: &Backend, section: &str) -> Fields {
+    match section {
+        "memory" => memory(backend),
+        "persistence" => persistence(backend),
+        "stats" => stats(backend),
+        "replication" => replication(backend),
+        "cluster" => vec![field("cluster_enabled", backend.is_cluster_enabled() as u8)],
+        "sentinel" => sentinel(backend),
+        "commandstats" => commandstats(backend),
+        _ => unreachable!("unknown section {}", section),
+    }
+}
+
+fn memory(backend: &Backend) -> Fields {
+    let mut fields = Vec::new();
+    let allocator = backend.allocator_stats();
+    if let Some(allocator) = allocator {
+        fields.push(field("used_memory", allocator.allocated));
+    }
+    fields.push(field("maxmemory", backend.maxmemory()));
+    fields.push(field("mem_allocator", backend.allocator_name()));
+    if let Some(allocator) = allocator {
+        fields.push(field("allocator_allocated", allocator.allocated));
+        fields.push(field("allocator_active", allocator.active));
+        fields.push(field("allocator_resident", allocator.resident));
+        fields.push(field("allocator_retained", allocator.retained));
+        fields.push(field(
+            "allocator_frag_ratio",
+            format!("{:.2}", allocator.frag_ratio()),
+        ));
+        fields.push(field("allocator_frag_bytes", allocator.frag_bytes()));
+        fields.push(field(
+            "allocator_rss_ratio",
+            format!("{:.2}", allocator.rss_ratio()),
+        ));
+        fields.push(field("allocator_rss_bytes", allocator.rss_bytes()));
+    }
+    fields
+}
+
+fn sentinel(backend: &Backend) -> Fields {
+    let masters = backend.sentinel_masters();
+    let mut fields = vec![field("sentinel_masters", masters.len())];
+    for (i, master) in masters.iter().enumerate() {
+        let status = match (
+            master.flags.contains("s_down"),
+            master.flags.contains("o_down"),
+        ) {
+            (_, true) => "odown",
+            (true, false) => "sdown",
+            _ => "ok",
+        };
+        fields.push(field(
+            &format!("master{}", i),
+            format!(
+                "name={},status={},address={},slaves={},sentinels={}",
+                master.name,
+                status,
+                master.addr,
+                master.num_replicas,
+                master.num_other_sentinels + 1
+            ),
+        ));
+    }
+    fields
+}
+
+fn persistence(backend: &Backend) -> Fields {
+    let report = backend.persistence_report();
+    let status = |ok: bool| if ok { "ok" } else { "err" };
+    let mut fields = vec![
+        field("loading", backend.is_loading() as u8),
+        field(
+            "rdb_changes_since_last_save",
+            report.changes_since_last_save,
+        ),
+        field("rdb_bgsave_in_progress", report.bgsave_in_progress as u8),
+        field("rdb_last_save_time", report.last_save_time),
+        field("rdb_last_bgsave_status", status(report.last_bgsave_ok)),
+        field("rdb_last_bgsave_time_sec", report.last_bgsave_time_sec),
+        field(
+            "rdb_current_bgsave_time_sec",
+            report.current_bgsave_time_sec,
+        ),
+        field("aof_enabled", report.aof.enabled as u8),
+        field(
+            "aof_rewrite_in_progress",
+            report.aof.rewrite_in_progress as u8,
+        ),
+        field(
+            "aof_last_rewrite_time_sec",
+            report.aof.last_rewrite_time_sec,
+        ),
+        field(
+            "aof_current_rewrite_time_sec",
+            report.aof.current_rewrite_time_sec,
+        ),
+        field(
+            "aof_last_bgrewrite_status",
+            status(report.aof.last_bgrewrite_ok),
+        ),
+        field("aof_last_write_status", status(report.aof.last_write_ok)),
+    ];
+    if report.aof.enabled {
+        fields.push(field("aof_current_size", report.aof.current_size));
+        fields.push(field("aof_base_size", report.aof.base_size));
+    }
+    fields
+}
+
+fn stats(backend: &Backend) -> Fields {
+    let report = backend.stats_report();
+    vec![
+        field(
+            "total_connections_received",
+            report.total_connections_received,
+        ),
+        field("total_commands_processed", report.total_commands_processed),
+        field("rejected_connections", report.rejected_connections),
+        field(
+            "instantaneous_ops_per_sec",
+            report.instantaneous_ops_per_sec,
+        ),
+        field("expired_keys", report.expired_keys),
+        field(
+            "expired_time_cap_reached_count",
+            report.expired_time_cap_reached_count,
+        ),
+        field("evicted_keys", report.evicted_keys),
+        field("evicted_clients", report.evicted_clients),
+        field("keyspace_hits", report.keyspace_hits),
+        field("keyspace_misses", report.keyspace_misses),
+        field("sync_full", report.sync_full),
+        field("sync_partial_ok", report.sync_partial_ok),
+        field("sync_partial_err", report.sync_partial_err),
+    ]
+}
+
+fn commandstats(backend: &Backend) -> Fields {
+    backend
+        .command_stats()
+        .into_iter()
+        .map(|(name, stats)| {
+            let per_call = stats.usec as f64 / stats.calls as f64;
+            field(
+                &format!("cmdstat_{}", name),
+                format!(
+                    "calls={},usec={},usec_per_call={:.2},max_usec={}",
+                    stats.calls, stats.usec, per_call, stats.max_usec
+                ),
+            )
+        })
+        .collect()
+}
+
+fn replication(backend: &Backend) -> Fields {
+    let report = backend.replication_report();
+    let mut fields = Vec::new();
+    match &report.master {
+        Some(master) => {
+            fields.push(field("role", "slave"));
+            fields.push(field("master_host", &master.host));
+            fields.push(field("master_port", master.port));
+            let status = if master.link_up { "up" } else { "down" };
+            fields.push(field("master_link_status", status));
+            fields.push(field(
+                "master_last_io_seconds_ago",
+                master.last_io_seconds_ago,
+            ));
+            fields.push(field(
+                "master_sync_in_progress",
+                master.sync_in_progress as u8,
+            ));
+            fields.push(field("slave_repl_offset", report.offset));
+            fields.push(field("slave_read_only", 1));
+        }
+        None => fields.push(field("role", "master")),
+    }
+    fields.push(field("connected_slaves", report.replicas.len()));
+    for (i, replica) in report.replicas.iter().enumerate() {
+        fields.push(field(
+            &format!("slave{}", i),
+            format!(
+                "ip={},port={},state=online,offset={},lag={}",
+                replica.ip, replica.port, replica.offset, replica.lag
+            ),
+        ));
+    }
+    let (replid2, second_offset) = match report.former {
+        Some((replid, end)) => (replid, (end + 1) as i64),
+        None => ("0".repeat(40), -1),
+    };
+    let active = backend.is_replication_enabled() || backend.is_replica();
+    fields.push(field("master_replid", report.replid));
+    fields.push(field("master_replid2", replid2));
+    fields.push(field("master_repl_offset", report.offset));
+    fields.push(field("second_repl_offset", second_offset));
+    fields.push(field("repl_backlog_active", active as u8));
+    fields.push(field("repl_backlog_size", report.backlog_size));
+    fields.push(field(
+        "repl_backlog_first_byte_offset",
+        report.backlog_first_byte_offset,
+    ));
+    fields.push(field("repl_backlog_histlen", report.backlog_histlen));
+    fields
+}
+
+fn field(name: &str, value: impl ToString) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+// INFO [section ...]
+impl TryFrom<RespArray> for Info {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["info"], 0)?;
+        let sections = extract_args(value, 1)?
+            .into_iter()
+            .map(|frame| Ok(extract_string(Some(frame), "section")?.to_ascii_lowercase()))
+            .collect::<Result<_, CommandError>>()?;
+        Ok(Info { sections })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn info(backend: &Backend, s: &str) -> Result<String> {
+        let mut buf = BytesMut::from(s);
+        let info: Info = RespArray::decode(&mut buf)?.try_into()?;
+        let RespFrame::BulkString(info) = info.execute(backend) else {
+            panic!("expected a bulk string");
+        };
+        Ok(String::from_utf8(info.0.into())?)
+    }
+
+    #[test]
+    fn test_info_persistence() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("k".to_string(), RespFrame::Integer(1));
+
+        let persistence = info(&backend, "*2\r\n$4\r\ninfo\r\n$11\r\nPERSISTENCE\r\n")?;
+        assert!(persistence.starts_with("# Persistence\r\n"));
+        assert!(persistence.contains("\r\nrdb_changes_since_last_save:1\r\n"));
+        assert!(persistence.contains("\r\nrdb_bgsave_in_progress:0\r\n"));
+        assert!(persistence.contains(&format!(
+            "\r\nrdb_last_save_time:{}\r\n",
+            backend.lastsave()
+        )));
+        assert!(persistence.contains("\r\nrdb_last_bgsave_time_sec:-1\r\n"));
+        assert!(persistence.contains("\r\naof_enabled:0\r\n"));
+        assert!(!persistence.contains("aof_current_size"));
+
+        let all = info(&backend, "*1\r\n$4\r\ninfo\r\n")?;
+        assert!(all.contains(&format!("\r\n{}", persistence)));
+        assert!(all.contains("\r\n# Replication\r\nrole:master\r\n"));
+        assert_eq!(info(&backend, "*2\r\n$4\r\ninfo\r\n$7\r\nunknown\r\n")?, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_memory() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_maxmemory(1024);
+
+        let memory = info(&backend, "*2\r\n$4\r\ninfo\r\n$6\r\nmemory\r\n")?;
+        assert!(memory.starts_with("# Memory\r\n"));
+        assert!(memory.contains("\r\nmaxmemory:1024\r\n"));
+        assert!(memory.contains(&format!(
+            "\r\nmem_allocator:{}\r\n",
+            backend.allocator_name()
+        )));
+        // reported by jemalloc only
+        assert_eq!(
+            memory.contains("\r\nallocator_frag_ratio:"),
+            backend.allocator_stats().is_some()
+        );
+        assert!(info(&backend, "*1\r\n$4\r\ninfo\r\n")?.starts_with("# Memory\r\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_commandstats() -> Result<()> {
+        let backend = Backend::new();
+        backend.count_command("get", std::time::Duration::from_micros(10));
+        backend.count_command("get", std::time::Duration::from_micros(20));
+        backend.get("nope")?;
+
+        let stats = info(&backend, "*2\r\n$4\r\ninfo\r\n$5\r\nstats\r\n")?;
+        assert!(stats.contains("\r\nkeyspace_misses:1\r\n"));
+        assert!(stats.contains("\r\nkeyspace_hits:0\r\n"));
+
+        // only reported when asked for
+        assert!(!info(&backend, "*1\r\n$4\r\ninfo\r\n")?.contains("cmdstat"));
+        let commandstats =
+            "# Commandstats\r\ncmdstat_get:calls=2,usec=30,usec_per_call=15.00,max_usec=20\r\n";
+        assert_eq!(
+            info(&backend, "*2\r\n$4\r\ninfo\r\n$12\r\ncommandstats\r\n")?,
+            commandstats
+        );
+        assert!(info(&backend, "*2\r\n$4\r\ninfo\r\n$3\r\nall\r\n")?.ends_with(commandstats));
+
+        backend.reset_stats();
+        assert_eq!(
+            info(&backend, "*2\r\n$4\r\ninfo\r\n$12\r\ncommandstats\r\n")?,
+            "# Commandstats\r\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,462 @@
+use crate::cmd::{
+    extract_args, extract_cursor, extract_int, extract_string, hold_thread, scan_reply,
+    validate_command, validate_variadic_command, CommandError, CommandExecutor, Del, Dump, Keys,
+    Migrate, Rename, RenameNx, Restore, Scan, RESP_OK,
+};
+use crate::{
+    glob_match, Backend, BulkString, MigrateOptions, RespArray, RespFrame, RespNull,
+    RestoreOptions, SimpleString,
+};
+
+impl CommandExecutor for Del {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.del(&self.keys) as i64)
+    }
+}
+
+impl CommandExecutor for Keys {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let keys: Vec<RespFrame> = backend
+            .keys(&self.pattern)
+            .into_iter()
+            .map(|key| BulkString::new(key).into())
+            .collect();
+        RespArray::new(keys).into()
+    }
+}
+
+impl CommandExecutor for Scan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let (next, keys) = backend.scan(self.cursor, self.count);
+        let keys = keys
+            .into_iter()
+            .filter(|key| {
+                self.pattern
+                    .as_ref()
+                    .is_none_or(|pattern| glob_match(pattern.as_bytes(), key.as_bytes()))
+            })
+            .map(|key| BulkString::new(key).into())
+            .collect();
+        scan_reply(next, keys)
+    }
+}
+
+impl CommandExecutor for Rename {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rename(&self.key, &self.new_key, false) {
+            Ok(_) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for RenameNx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rename(&self.key, &self.new_key, true) {
+            Ok(renamed) => RespFrame::Integer(renamed as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Dump {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.dump(&self.key) {
+            Some(payload) => BulkString::new(payload).into(),
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl CommandExecutor for Restore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.restore(self.key, &self.payload, self.options) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Migrate {
+    // the connection waits for the target, with its thread
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match hold_thread(|| backend.migrate(&self.keys, &self.options)) {
+            Ok(true) => RESP_OK.clone(),
+            Ok(false) => SimpleString::new("NOKEY").into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// DEL key [key ...]
+impl TryFrom<RespArray> for Del {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["del"], 1)?;
+
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|key| extract_string(Some(key), "key"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Del { keys })
+    }
+}
+
+// KEYS pattern
+impl TryFrom<RespArray> for Keys {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["keys"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Keys {
+            pattern: extract_string(args.next(), "pattern")?,
+        })
+    }
+}
+
+// SCAN cursor [MATCH pattern] [COUNT count]
+impl TryFrom<RespArray> for Scan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["scan"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut scan = Scan {
+            cursor: extract_cursor(args.next())?,
+            pattern: None,
+            count: 10,
+        };
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"match" => scan.pattern = Some(extract_string(args.next(), "pattern")?),
+                b"count" => match extract_int(args.next())? {
+                    count if count >= 1 => scan.count = count as usize,
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                },
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+// RENAME key newkey
+impl TryFrom<RespArray> for Rename {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, new_key) = extract_keys(value, "rename")?;
+        Ok(Rename { key, new_key })
+    }
+}
+
+// RENAMENX key newkey
+impl TryFrom<RespArray> for RenameNx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, new_key) = extract_keys(value, "renamenx")?;
+        Ok(RenameNx { key, new_key })
+    }
+}
+
+// DUMP key
+impl TryFrom<RespArray> for Dump {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dump"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Dump {
+            key: extract_string(args.next(), "key")?,
+        })
+    }
+}
+
+// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+impl TryFrom<RespArray> for Restore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["restore"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let mut options = RestoreOptions {
+            ttl: extract_int(args.next())?,
+            ..Default::default()
+        };
+        let payload = match args.next() {
+            Some(RespFrame::BulkString(payload)) => payload.to_vec(),
+            _ => return Err(CommandError::InvalidArgument("Invalid payload".to_string())),
+        };
+        for arg in args {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "replace" => options.replace = true,
+                "absttl" => options.absttl = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(Restore {
+            key,
+            payload,
+            options,
+        })
+    }
+}
+
+// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE]
+//   [AUTH password | AUTH2 username password] [KEYS key [key ...]]
+impl TryFrom<RespArray> for Migrate {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["migrate"], 5)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let host = extract_string(args.next(), "host")?;
+        let port = u16::try_from(extract_int(args.next())?)
+            .map_err(|_| CommandError::InvalidArgument("Invalid port".to_string()))?;
+        let key = extract_string(args.next(), "key")?;
+        let db = extract_int(args.next())?;
+        // at least a millisecond
+        let timeout = extract_int(args.next())?.max(1) as u64;
+        let mut options = MigrateOptions {
+            host,
+            port,
+            db,
+            timeout,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        let mut keys = vec![key];
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "copy" => options.copy = true,
+                "replace" => options.replace = true,
+                "auth" => options.auth = Some((None, extract_string(args.next(), "password")?)),
+                "auth2" => {
+                    let username = extract_string(args.next(), "username")?;
+                    let password = extract_string(args.next(), "password")?;
+                    options.auth = Some((Some(username), password));
+                }
+                "keys" => {
+                    if !keys[0].is_empty() {
+                        return Err(CommandError::InvalidArgument(
+                            "When using MIGRATE KEYS option, the key argument must be set to the empty string".to_string(),
+                        ));
+                    }
+                    keys = args
+                        .by_ref()
+                        .map(|key| extract_string(Some(key), "key"))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(Migrate { keys, options })
+    }
+}
+
+fn extract_keys(value: RespArray, name: &'static str) -> Result<(String, String), CommandError> {
+    validate_command(&value, &[name], 2)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let new_key = extract_string(args.next(), "key")?;
+    Ok((key, new_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RespDecode, SimpleError};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_rename_command() -> Result<()> {
+        let mut buf = BytesMut::from("*3\r\n$6\r\nrename\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Rename = frame.try_into()?;
+        assert_eq!((cmd.key.as_str(), cmd.new_key.as_str()), ("a", "b"));
+
+        let backend = Backend::new();
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR no such key").into()
+        );
+        backend.set("a".to_string(), RespFrame::Integer(1));
+        backend.set("c".to_string(), RespFrame::Integer(2));
+        let renamenx = RenameNx {
+            key: "a".to_string(),
+            new_key: "c".to_string(),
+        };
+        assert_eq!(renamenx.execute(&backend), RespFrame::Integer(0));
+        let rename = Rename {
+            key: "a".to_string(),
+            new_key: "c".to_string(),
+        };
+        assert_eq!(rename.execute(&backend), RESP_OK.clone());
+        assert_eq!(backend.get("c")?, Some(RespFrame::Integer(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keys_and_scan_commands() -> Result<()> {
+        let backend = Backend::new();
+        for key in ["user:1", "user:2", "user:3", "session:1"] {
+            backend.set(key.to_string(), RespFrame::Integer(1));
+        }
+        let mut buf = BytesMut::from("*2\r\n$4\r\nkeys\r\n$6\r\nuser:*\r\n");
+        let cmd: Keys = RespArray::decode(&mut buf)?.try_into()?;
+        let RespFrame::Array(keys) = cmd.execute(&backend) else {
+            panic!("KEYS replied no array");
+        };
+        let mut keys: Vec<RespFrame> = keys.0;
+        keys.sort_by_key(|key| format!("{:?}", key));
+        let expected: Vec<RespFrame> = ["user:1", "user:2", "user:3"]
+            .into_iter()
+            .map(|key| BulkString::new(key).into())
+            .collect();
+        assert_eq!(keys, expected);
+
+        // the pattern filters the keys of each call, COUNT bounds how many it visits
+        let mut cursor = "0".to_string();
+        let mut scanned = Vec::new();
+        loop {
+            let request = format!(
+                "*6\r\n$4\r\nscan\r\n${}\r\n{}\r\n$5\r\nMATCH\r\n$6\r\nuser:*\r\n$5\r\nCOUNT\r\n$1\r\n1\r\n",
+                cursor.len(),
+                cursor
+            );
+            let cmd: Scan = RespArray::decode(&mut BytesMut::from(request.as_str()))?.try_into()?;
+            assert_eq!((cmd.count, cmd.pattern.as_deref()), (1, Some("user:*")));
+            let RespFrame::Array(reply) = cmd.execute(&backend) else {
+                panic!("SCAN replied no array");
+            };
+            let mut reply = reply.0.into_iter();
+            let (Some(RespFrame::BulkString(next)), Some(RespFrame::Array(keys))) =
+                (reply.next(), reply.next())
+            else {
+                panic!("SCAN replied no cursor and keys");
+            };
+            assert!(keys.len() <= 1);
+            scanned.extend(keys.0);
+            cursor = String::from_utf8(next.to_vec())?;
+            if cursor == "0" {
+                break;
+            }
+        }
+        scanned.sort_by_key(|key| format!("{:?}", key));
+        assert_eq!(scanned, expected);
+
+        let mut buf = BytesMut::from("*3\r\n$4\r\nscan\r\n$1\r\n0\r\n$5\r\nCOUNT\r\n");
+        let result: Result<Scan, _> = RespArray::decode(&mut buf)?.try_into();
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_del_command() -> Result<()> {
+        let mut buf = BytesMut::from("*4\r\n$3\r\ndel\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\na\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Del = frame.try_into()?;
+        assert_eq!(cmd.keys, ["a", "b", "a"]);
+
+        let backend = Backend::new();
+        backend.set("a".to_string(), RespFrame::Integer(1));
+        // a key given twice is deleted once
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        assert!(!backend.contains_key("a"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_and_restore_commands() -> Result<()> {
+        let backend = Backend::new();
+        let dump = || Dump {
+            key: "a".to_string(),
+        };
+        assert_eq!(dump().execute(&backend), RespFrame::Null(RespNull));
+        backend.set("a".to_string(), RespFrame::BulkString("v".into()));
+        let RespFrame::BulkString(payload) = dump().execute(&backend) else {
+            panic!("DUMP should reply a bulk string");
+        };
+
+        let mut restore = b"*6\r\n$7\r\nrestore\r\n$1\r\nb\r\n$3\r\n100\r\n".to_vec();
+        restore.extend(format!("${}\r\n", payload.len()).as_bytes());
+        restore.extend(payload.as_ref());
+        restore.extend(b"\r\n$7\r\nreplace\r\n$6\r\nabsttl\r\n");
+        let frame = RespArray::decode(&mut BytesMut::from(&restore[..]))?;
+        let cmd: Restore = frame.try_into()?;
+        assert_eq!(
+            cmd.options,
+            RestoreOptions {
+                ttl: 100,
+                replace: true,
+                absttl: true
+            }
+        );
+        // the TTL elapsed long ago
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert!(!backend.contains_key("b"));
+
+        let restore = Restore {
+            key: "a".to_string(),
+            payload: payload.to_vec(),
+            options: RestoreOptions::default(),
+        };
+        assert_eq!(
+            restore.execute(&backend),
+            SimpleError::new("BUSYKEY Target key name already exists.").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_command() -> Result<()> {
+        let mut buf = BytesMut::from(
+            "*10\r\n$7\r\nmigrate\r\n$9\r\nlocalhost\r\n$4\r\n6380\r\n$0\r\n\r\n$1\r\n2\r\n$4\r\n5000\r\n$4\r\ncopy\r\n$4\r\nkeys\r\n$1\r\na\r\n$1\r\nb\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Migrate = frame.try_into()?;
+        assert_eq!(cmd.keys, ["a", "b"]);
+        assert_eq!(
+            (cmd.options.host.as_str(), cmd.options.port),
+            ("localhost", 6380)
+        );
+        assert_eq!((cmd.options.db, cmd.options.timeout), (2, 5000));
+        assert!(cmd.options.copy && !cmd.options.replace);
+
+        // KEYS with a key
+        let mut buf = BytesMut::from(
+            "*8\r\n$7\r\nmigrate\r\n$9\r\nlocalhost\r\n$4\r\n6380\r\n$1\r\na\r\n$1\r\n0\r\n$4\r\n5000\r\n$4\r\nkeys\r\n$1\r\nb\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Migrate::try_from(frame).is_err());
+
+        let backend = Backend::new();
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::SimpleString(SimpleString::new("NOKEY"))
+        );
+        Ok(())
+    }
+}
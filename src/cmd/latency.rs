@@ -0,0 +1,121 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_command, validate_variadic_command, CommandError,
+    CommandExecutor, LatencyHistory, LatencyLatest, LatencyReset,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame};
+
+impl CommandExecutor for LatencyLatest {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let latest: Vec<RespFrame> = backend
+            .latency_latest()
+            .into_iter()
+            .map(|latest| {
+                RespArray::new(vec![
+                    BulkString::from(latest.event.as_str()).into(),
+                    RespFrame::Integer(latest.time),
+                    RespFrame::Integer(latest.latency as i64),
+                    RespFrame::Integer(latest.max as i64),
+                ])
+                .into()
+            })
+            .collect();
+        RespArray::new(latest).into()
+    }
+}
+
+impl CommandExecutor for LatencyHistory {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let history: Vec<RespFrame> = backend
+            .latency_history(&self.event)
+            .into_iter()
+            .map(|sample| {
+                RespArray::new(vec![
+                    RespFrame::Integer(sample.time),
+                    RespFrame::Integer(sample.latency as i64),
+                ])
+                .into()
+            })
+            .collect();
+        RespArray::new(history).into()
+    }
+}
+
+impl CommandExecutor for LatencyReset {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.latency_reset(&self.events) as i64)
+    }
+}
+
+// LATENCY LATEST
+impl TryFrom<RespArray> for LatencyLatest {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["latency", "latest"], 0)?;
+        Ok(LatencyLatest)
+    }
+}
+
+// LATENCY HISTORY event
+impl TryFrom<RespArray> for LatencyHistory {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["latency", "history"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(LatencyHistory {
+            event: extract_string(args.next(), "event")?.to_ascii_lowercase(),
+        })
+    }
+}
+
+// LATENCY RESET [event [event ...]]
+impl TryFrom<RespArray> for LatencyReset {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["latency", "reset"], 0)?;
+        let events = extract_args(value, 2)?
+            .into_iter()
+            .map(|frame| extract_string(Some(frame), "event"))
+            .collect::<Result<_, _>>()?;
+        Ok(LatencyReset { events })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_latency_commands() -> Result<()> {
+        let backend = Backend::new();
+        let frame = decode("*2\r\n$7\r\nlatency\r\n$6\r\nLATEST\r\n")?;
+        let latest: LatencyLatest = frame.try_into()?;
+        assert_eq!(latest.execute(&backend), RespArray::new(vec![]).into());
+
+        let frame = decode("*3\r\n$7\r\nlatency\r\n$7\r\nhistory\r\n$7\r\nCommand\r\n")?;
+        let history: LatencyHistory = frame.try_into()?;
+        assert_eq!(history.event, "command");
+        assert_eq!(history.execute(&backend), RespArray::new(vec![]).into());
+
+        let frame =
+            decode("*4\r\n$7\r\nlatency\r\n$5\r\nreset\r\n$7\r\ncommand\r\n$4\r\nfork\r\n")?;
+        let reset: LatencyReset = frame.try_into()?;
+        assert_eq!(reset.events, vec!["command", "fork"]);
+        assert_eq!(reset.execute(&backend), RespFrame::Integer(0));
+
+        let frame = decode("*2\r\n$7\r\nlatency\r\n$7\r\nhistory\r\n")?;
+        assert!(LatencyHistory::try_from(frame).is_err());
+
+        Ok(())
+    }
+}
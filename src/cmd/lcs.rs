@@ -0,0 +1,160 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_variadic_command, CommandError,
+    CommandExecutor, Lcs,
+};
+use crate::{Backend, BulkString, LcsMatch, RespArray, RespFrame, RespMap};
+
+impl CommandExecutor for Lcs {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let lcs = match backend.lcs(&self.key1, &self.key2, self.min_match_len) {
+            Ok(lcs) => lcs,
+            Err(e) => return e.into(),
+        };
+
+        if self.len {
+            return RespFrame::Integer(lcs.string.len() as i64);
+        }
+        if !self.idx {
+            return BulkString::new(lcs.string).into();
+        }
+
+        let matches = lcs
+            .matches
+            .iter()
+            .map(|m| match_frame(m, self.with_match_len))
+            .collect::<Vec<_>>();
+        let mut map = RespMap::new();
+        map.insert("matches".to_string(), RespArray::new(matches).into());
+        map.insert("len".to_string(), (lcs.string.len() as i64).into());
+        map.into()
+    }
+}
+
+// [[a_start, a_end], [b_start, b_end]] with the match length appended for WITHMATCHLEN
+fn match_frame(m: &LcsMatch, with_match_len: bool) -> RespFrame {
+    let range = |(start, end): (usize, usize)| -> RespFrame {
+        RespArray::new(vec![
+            RespFrame::Integer(start as i64),
+            RespFrame::Integer(end as i64),
+        ])
+        .into()
+    };
+
+    let mut frames = vec![range(m.a), range(m.b)];
+    if with_match_len {
+        frames.push(RespFrame::Integer(m.match_len() as i64));
+    }
+    RespArray::new(frames).into()
+}
+
+// LCS key1 key2 [LEN] [IDX] [MINMATCHLEN min-match-len] [WITHMATCHLEN]
+impl TryFrom<RespArray> for Lcs {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["lcs"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut lcs = Lcs {
+            key1: extract_string(args.next(), "key")?,
+            key2: extract_string(args.next(), "key")?,
+            len: false,
+            idx: false,
+            min_match_len: 0,
+            with_match_len: false,
+        };
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"len" => lcs.len = true,
+                b"idx" => lcs.idx = true,
+                b"withmatchlen" => lcs.with_match_len = true,
+                b"minmatchlen" => lcs.min_match_len = extract_int(args.next())?.max(0) as usize,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+
+        if lcs.len && lcs.idx {
+            return Err(CommandError::InvalidArgument(
+                "If you want both the length and indexes, please just use IDX.".to_string(),
+            ));
+        }
+        Ok(lcs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_lcs_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$3\r\nlcs\r\n$2\r\nk1\r\n$2\r\nk2\r\n$3\r\nIDX\r\n$11\r\nMINMATCHLEN\r\n$1\r\n4\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Lcs = frame.try_into()?;
+        assert!(cmd.idx && !cmd.len);
+        assert_eq!(cmd.min_match_len, 4);
+
+        buf.extend_from_slice(
+            b"*5\r\n$3\r\nlcs\r\n$2\r\nk1\r\n$2\r\nk2\r\n$3\r\nidx\r\n$3\r\nlen\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<Lcs, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lcs_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("k1".to_string(), RespFrame::BulkString(b"ohmytext".into()));
+        backend.set("k2".to_string(), RespFrame::BulkString(b"mynewtext".into()));
+
+        let cmd = Lcs {
+            key1: "k1".to_string(),
+            key2: "k2".to_string(),
+            len: false,
+            idx: false,
+            min_match_len: 0,
+            with_match_len: false,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::BulkString(b"mytext".into())
+        );
+
+        let cmd = Lcs {
+            key1: "k1".to_string(),
+            key2: "k2".to_string(),
+            len: false,
+            idx: true,
+            min_match_len: 4,
+            with_match_len: true,
+        };
+        let mut expected = RespMap::new();
+        expected.insert(
+            "matches".to_string(),
+            RespArray::new(vec![RespArray::new(vec![
+                RespArray::new(vec![RespFrame::Integer(4), RespFrame::Integer(7)]).into(),
+                RespArray::new(vec![RespFrame::Integer(5), RespFrame::Integer(8)]).into(),
+                RespFrame::Integer(4),
+            ])
+            .into()])
+            .into(),
+        );
+        expected.insert("len".to_string(), RespFrame::Integer(6));
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        Ok(())
+    }
+}
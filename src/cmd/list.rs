@@ -0,0 +1,943 @@
+use crate::backend::command;
+use crate::cmd::{
+    bpop_wait, extract_args, extract_int, extract_key_and_count, extract_keys_and_timeout,
+    extract_numkeys, extract_string, extract_timeout, validate_command, validate_variadic_command,
+    BLMPop, BLMove, BLPop, BRPop, CommandError, CommandExecutor, LIndex, LInsert, LLen, LMPop,
+    LMove, LPop, LPos, LPush, LRange, LRem, LSet, LTrim, RPop, RPopLPush, RPush, RESP_OK,
+};
+use crate::{
+    Backend, BackendError, BulkString, ListSide, PopFrom, Popped, RespArray, RespFrame, RespNull,
+};
+
+impl CommandExecutor for LPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        len_reply(backend.lpush(&self.key, self.values))
+    }
+}
+
+impl CommandExecutor for RPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        len_reply(backend.rpush(&self.key, self.values))
+    }
+}
+
+impl CommandExecutor for LPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        pop_reply(backend.lpop(&self.key, self.count.unwrap_or(1)), self.count)
+    }
+}
+
+impl CommandExecutor for RPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        pop_reply(backend.rpop(&self.key, self.count.unwrap_or(1)), self.count)
+    }
+}
+
+impl CommandExecutor for LLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        len_reply(backend.llen(&self.key))
+    }
+}
+
+impl CommandExecutor for LRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lrange(&self.key, self.start, self.stop) {
+            Ok(values) => RespArray::new(values).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LIndex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lindex(&self.key, self.index) {
+            Ok(Some(value)) => value,
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lset(&self.key, self.index, self.value) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LInsert {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.linsert(&self.key, self.before, &self.pivot, self.value) {
+            Ok(len) => RespFrame::Integer(len),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        len_reply(backend.lrem(&self.key, self.count, &self.value))
+    }
+}
+
+impl CommandExecutor for LTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.ltrim(&self.key, self.start, self.stop) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let positions = backend.lpos(
+            &self.key,
+            &self.element,
+            self.rank,
+            self.count.unwrap_or(1),
+            self.maxlen,
+        );
+        match (positions, self.count) {
+            (Ok(positions), Some(_)) => RespArray::new(
+                positions
+                    .into_iter()
+                    .map(|i| RespFrame::Integer(i as i64))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            (Ok(positions), None) => match positions.first() {
+                Some(i) => RespFrame::Integer(*i as i64),
+                None => RespFrame::Null(RespNull),
+            },
+            (Err(e), _) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BLPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        bpop_now(backend, &self.keys, true)
+    }
+}
+
+impl CommandExecutor for BRPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        bpop_now(backend, &self.keys, false)
+    }
+}
+
+impl BLPop {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        bpop(backend, &self.keys, true, self.timeout).await
+    }
+}
+
+impl BRPop {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        bpop(backend, &self.keys, false, self.timeout).await
+    }
+}
+
+impl CommandExecutor for LMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        lmove(backend, &self.source, &self.destination, self.from, self.to)
+    }
+}
+
+impl CommandExecutor for RPopLPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        lmove(
+            backend,
+            &self.source,
+            &self.destination,
+            ListSide::Right,
+            ListSide::Left,
+        )
+    }
+}
+
+impl CommandExecutor for BLMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        lmove(backend, &self.source, &self.destination, self.from, self.to)
+    }
+}
+
+impl BLMove {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        // a ready move is atomic and checks the destination type before popping
+        let moved = backend.run_logged(|| {
+            backend.propagate_effects(
+                || backend.lmove(&self.source, &self.destination, self.from, self.to),
+                |moved| match moved {
+                    Ok(Some(_)) => vec![command([
+                        "LMOVE",
+                        &self.source,
+                        &self.destination,
+                        side_name(self.from),
+                        side_name(self.to),
+                    ])],
+                    _ => Vec::new(),
+                },
+            )
+        });
+        match moved {
+            Ok(Some(value)) => return value,
+            Ok(None) => {}
+            Err(e) => return e.into(),
+        }
+
+        let keys = std::slice::from_ref(&self.source);
+        let popped = bpop_wait(
+            backend,
+            keys,
+            PopFrom::list(self.from.is_head()),
+            1,
+            self.timeout,
+        )
+        .await;
+        match popped.map(|popped| popped.and_then(|(_, values)| values.into_iter().next())) {
+            Ok(Some(value)) => backend
+                .lmove_popped(&self.source, &self.destination, value, self.from, self.to)
+                .unwrap_or_else(Into::into),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+fn side_name(side: ListSide) -> &'static str {
+    match side {
+        ListSide::Left => "LEFT",
+        ListSide::Right => "RIGHT",
+    }
+}
+
+fn lmove(
+    backend: &Backend,
+    source: &str,
+    destination: &str,
+    from: ListSide,
+    to: ListSide,
+) -> RespFrame {
+    match backend.lmove(source, destination, from, to) {
+        Ok(Some(value)) => value,
+        Ok(None) => RespFrame::Null(RespNull),
+        Err(e) => e.into(),
+    }
+}
+
+impl CommandExecutor for LMPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lmpop(&self.keys, self.side.is_head(), self.count) {
+            Ok(popped) => mpop_reply(popped),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BLMPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lmpop(&self.keys, self.side.is_head(), self.count) {
+            Ok(popped) => mpop_reply(popped),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl BLMPop {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        let head = self.side.is_head();
+        match bpop_wait(
+            backend,
+            &self.keys,
+            PopFrom::list(head),
+            self.count,
+            self.timeout,
+        )
+        .await
+        {
+            Ok(popped) => mpop_reply(popped),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// pop without blocking, the way blocking pops behave inside a transaction
+fn bpop_now(backend: &Backend, keys: &[String], head: bool) -> RespFrame {
+    match backend.lmpop(keys, head, 1) {
+        Ok(popped) => popped_reply(popped),
+        Err(e) => e.into(),
+    }
+}
+
+async fn bpop(backend: &Backend, keys: &[String], head: bool, timeout: f64) -> RespFrame {
+    match bpop_wait(backend, keys, PopFrom::list(head), 1, timeout).await {
+        Ok(popped) => popped_reply(popped),
+        Err(e) => e.into(),
+    }
+}
+
+// [key, element] for the single element pops
+fn popped_reply(popped: Option<Popped>) -> RespFrame {
+    match popped {
+        Some((key, values)) => {
+            let value = values
+                .into_iter()
+                .next()
+                .unwrap_or(RespFrame::Null(RespNull));
+            RespArray::new([BulkString::new(key).into(), value]).into()
+        }
+        None => RespFrame::Null(RespNull),
+    }
+}
+
+// [key, [elements]] for LMPOP and BLMPOP
+fn mpop_reply(popped: Option<Popped>) -> RespFrame {
+    match popped {
+        Some((key, values)) => {
+            RespArray::new([BulkString::new(key).into(), RespArray::new(values).into()]).into()
+        }
+        None => RespFrame::Null(RespNull),
+    }
+}
+
+fn len_reply(result: Result<usize, BackendError>) -> RespFrame {
+    match result {
+        Ok(len) => RespFrame::Integer(len as i64),
+        Err(e) => e.into(),
+    }
+}
+
+// a single element without count, an array with it, and null for a missing key either way
+fn pop_reply(
+    result: Result<Option<Vec<RespFrame>>, BackendError>,
+    count: Option<usize>,
+) -> RespFrame {
+    match (result, count) {
+        (Ok(None), _) => RespFrame::Null(RespNull),
+        (Ok(Some(popped)), None) => popped
+            .into_iter()
+            .next()
+            .unwrap_or(RespFrame::Null(RespNull)),
+        (Ok(Some(popped)), Some(_)) => RespArray::new(popped).into(),
+        (Err(e), _) => e.into(),
+    }
+}
+
+impl TryFrom<RespArray> for LPush {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = extract_key_and_values(value, "lpush")?;
+        Ok(LPush { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for RPush {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, values) = extract_key_and_values(value, "rpush")?;
+        Ok(RPush { key, values })
+    }
+}
+
+impl TryFrom<RespArray> for LPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "lpop")?;
+        Ok(LPop { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for RPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "rpop")?;
+        Ok(RPop { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for LLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["llen"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(LLen { key })
+    }
+}
+
+impl TryFrom<RespArray> for LRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, stop) = extract_key_and_range(value, "lrange")?;
+        Ok(LRange { key, start, stop })
+    }
+}
+
+impl TryFrom<RespArray> for LIndex {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lindex"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let index = extract_int(args.next())?;
+        Ok(LIndex { key, index })
+    }
+}
+
+impl TryFrom<RespArray> for LSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lset"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let index = extract_int(args.next())?;
+        let value = extract_value(args.next())?;
+        Ok(LSet { key, index, value })
+    }
+}
+
+impl TryFrom<RespArray> for LInsert {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["linsert"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let before = match args.next() {
+            Some(RespFrame::BulkString(pos)) if pos.eq_ignore_ascii_case(b"before") => true,
+            Some(RespFrame::BulkString(pos)) if pos.eq_ignore_ascii_case(b"after") => false,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        let pivot = extract_value(args.next())?;
+        let value = extract_value(args.next())?;
+        Ok(LInsert {
+            key,
+            before,
+            pivot,
+            value,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for LRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lrem"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let count = extract_int(args.next())?;
+        let value = extract_value(args.next())?;
+        Ok(LRem { key, count, value })
+    }
+}
+
+impl TryFrom<RespArray> for LTrim {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, stop) = extract_key_and_range(value, "ltrim")?;
+        Ok(LTrim { key, start, stop })
+    }
+}
+
+impl TryFrom<RespArray> for LPos {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["lpos"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut lpos = LPos {
+            key: extract_string(args.next(), "key")?,
+            element: extract_value(args.next())?,
+            rank: 1,
+            count: None,
+            maxlen: 0,
+        };
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"rank" => {
+                    match extract_int(args.next())? {
+                        0 => return Err(CommandError::InvalidArgument(
+                            "RANK can't be zero: use 1 to start from the first match, 2 from the \
+                             second ... or use negative to start from the end of the list"
+                                .to_string(),
+                        )),
+                        rank => lpos.rank = rank,
+                    }
+                }
+                b"count" => lpos.count = Some(extract_non_negative(args.next(), "COUNT")?),
+                b"maxlen" => lpos.maxlen = extract_non_negative(args.next(), "MAXLEN")?,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(lpos)
+    }
+}
+
+impl TryFrom<RespArray> for BLPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = extract_keys_and_timeout(value, "blpop")?;
+        Ok(BLPop { keys, timeout })
+    }
+}
+
+impl TryFrom<RespArray> for BRPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = extract_keys_and_timeout(value, "brpop")?;
+        Ok(BRPop { keys, timeout })
+    }
+}
+
+impl TryFrom<RespArray> for LMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lmove"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(LMove {
+            source: extract_string(args.next(), "source")?,
+            destination: extract_string(args.next(), "destination")?,
+            from: extract_side(args.next())?,
+            to: extract_side(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for RPopLPush {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["rpoplpush"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(RPopLPush {
+            source: extract_string(args.next(), "source")?,
+            destination: extract_string(args.next(), "destination")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for BLMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["blmove"], 5)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(BLMove {
+            source: extract_string(args.next(), "source")?,
+            destination: extract_string(args.next(), "destination")?,
+            from: extract_side(args.next())?,
+            to: extract_side(args.next())?,
+            timeout: extract_timeout(args.next())?,
+        })
+    }
+}
+
+// LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count]
+impl TryFrom<RespArray> for LMPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["lmpop"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (keys, side, count) = extract_mpop_args(&mut args)?;
+        Ok(LMPop { keys, side, count })
+    }
+}
+
+// BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count]
+impl TryFrom<RespArray> for BLMPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["blmpop"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let timeout = extract_timeout(args.next())?;
+        let (keys, side, count) = extract_mpop_args(&mut args)?;
+        Ok(BLMPop {
+            keys,
+            side,
+            count,
+            timeout,
+        })
+    }
+}
+
+fn extract_mpop_args(
+    args: &mut impl Iterator<Item = RespFrame>,
+) -> Result<(Vec<String>, ListSide, usize), CommandError> {
+    let keys = extract_numkeys(args)?;
+    let side = extract_side(args.next())?;
+    let count = match args.next() {
+        None => 1,
+        Some(RespFrame::BulkString(opt)) if opt.eq_ignore_ascii_case(b"count") => {
+            match extract_int(args.next())? {
+                n if n > 0 => n as usize,
+                _ => {
+                    return Err(CommandError::InvalidArgument(
+                        "count should be greater than 0".to_string(),
+                    ))
+                }
+            }
+        }
+        Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+    };
+    if args.next().is_some() {
+        return Err(CommandError::InvalidArgument("syntax error".to_string()));
+    }
+    Ok((keys, side, count))
+}
+
+// LEFT or RIGHT, in any case
+fn extract_side(frame: Option<RespFrame>) -> Result<ListSide, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(side)) => match side.to_ascii_lowercase().as_slice() {
+            b"left" => Ok(ListSide::Left),
+            b"right" => Ok(ListSide::Right),
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        },
+        _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+    }
+}
+
+fn extract_non_negative(frame: Option<RespFrame>, name: &str) -> Result<usize, CommandError> {
+    match extract_int(frame)? {
+        n if n >= 0 => Ok(n as usize),
+        _ => Err(CommandError::InvalidArgument(format!(
+            "{} can't be negative",
+            name
+        ))),
+    }
+}
+
+fn extract_key_and_values(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Vec<RespFrame>), CommandError> {
+    validate_variadic_command(&value, &[name], 2)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    Ok((key, args.collect()))
+}
+
+fn extract_key_and_range(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, i64, i64), CommandError> {
+    validate_command(&value, &[name], 3)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let start = extract_int(args.next())?;
+    let stop = extract_int(args.next())?;
+    Ok((key, start, stop))
+}
+
+fn extract_value(frame: Option<RespFrame>) -> Result<RespFrame, CommandError> {
+    frame.ok_or_else(|| CommandError::InvalidArgument("Invalid value".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    #[test]
+    fn test_list_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$7\r\nlinsert\r\n$3\r\nkey\r\n$6\r\nBEFORE\r\n$1\r\np\r\n$1\r\nv\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: LInsert = frame.try_into()?;
+        assert!(result.before);
+        assert_eq!(result.pivot, bulk("p"));
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$4\r\nlpop\r\n$3\r\nkey\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(LPop::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lpos_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*7\r\n$4\r\nlpos\r\n$3\r\nkey\r\n$1\r\nc\r\n$4\r\nRANK\r\n$2\r\n-1\r\n$5\r\nCOUNT\r\n$1\r\n0\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: LPos = frame.try_into()?;
+        assert_eq!(cmd.rank, -1);
+        assert_eq!(cmd.count, Some(0));
+
+        let backend = Backend::new();
+        backend.rpush("key", vec![bulk("c"), bulk("a"), bulk("c")])?;
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([RespFrame::Integer(2), RespFrame::Integer(0)]).into()
+        );
+
+        let cmd = LPos {
+            key: "key".to_string(),
+            element: bulk("x"),
+            rank: 1,
+            count: None,
+            maxlen: 0,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$4\r\nlpos\r\n$3\r\nkey\r\n$1\r\nc\r\n$4\r\nRANK\r\n$1\r\n0\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(LPos::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = RPush {
+            key: "l".to_string(),
+            values: vec![bulk("a"), bulk("b"), bulk("a")],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(3));
+
+        let cmd = LRem {
+            key: "l".to_string(),
+            count: 0,
+            value: bulk("a"),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd = LSet {
+            key: "l".to_string(),
+            index: 1,
+            value: bulk("x"),
+        };
+        assert_eq!(cmd.execute(&backend), BackendError::OutOfRange.into());
+
+        let cmd = LPop {
+            key: "l".to_string(),
+            count: None,
+        };
+        assert_eq!(cmd.execute(&backend), bulk("b"));
+        let cmd = LPop {
+            key: "l".to_string(),
+            count: Some(1),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blpop_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nblpop\r\n$2\r\nl1\r\n$2\r\nl2\r\n$3\r\n0.5\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: BLPop = frame.try_into()?;
+        assert_eq!(result.keys, vec!["l1", "l2"]);
+        assert_eq!(result.timeout, 0.5);
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\nbrpop\r\n$2\r\nl1\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(BRPop::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = BLPop {
+            keys: vec!["l".to_string()],
+            timeout: 0.01,
+        };
+        assert_eq!(cmd.block(&backend).await, RespFrame::Null(RespNull));
+
+        let pusher = backend.clone();
+        let waiter = tokio::spawn(async move {
+            let cmd = BLPop {
+                keys: vec!["l".to_string()],
+                timeout: 0.0,
+            };
+            cmd.block(&pusher).await
+        });
+        while !waiter.is_finished() {
+            backend.rpush("l", vec![bulk("v")])?;
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(waiter.await?, RespArray::new([bulk("l"), bulk("v")]).into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lmove_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$5\r\nlmove\r\n$1\r\na\r\n$1\r\nb\r\n$4\r\nLEFT\r\n$5\r\nright\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: LMove = frame.try_into()?;
+        assert_eq!((result.from, result.to), (ListSide::Left, ListSide::Right));
+
+        buf.extend_from_slice(
+            b"*6\r\n$6\r\nblmove\r\n$1\r\na\r\n$1\r\nb\r\n$4\r\nleft\r\n$2\r\nup\r\n$1\r\n0\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(BLMove::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lmove_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.rpush("src", vec![bulk("a"), bulk("b")])?;
+        let cmd = RPopLPush {
+            source: "src".to_string(),
+            destination: "dst".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), bulk("b"));
+
+        let cmd = BLMove {
+            source: "empty".to_string(),
+            destination: "dst".to_string(),
+            from: ListSide::Left,
+            to: ListSide::Right,
+            timeout: 0.01,
+        };
+        assert_eq!(cmd.block(&backend).await, RespFrame::Null(RespNull));
+
+        let pusher = backend.clone();
+        let waiter = tokio::spawn(async move {
+            let cmd = BLMove {
+                source: "empty".to_string(),
+                destination: "dst".to_string(),
+                from: ListSide::Left,
+                to: ListSide::Right,
+                timeout: 0.0,
+            };
+            cmd.block(&pusher).await
+        });
+        while !waiter.is_finished() {
+            backend.rpush("empty", vec![bulk("c")])?;
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(waiter.await?, bulk("c"));
+        assert_eq!(backend.lrange("dst", 0, -1)?, vec![bulk("b"), bulk("c")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lmpop_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*7\r\n$5\r\nlmpop\r\n$1\r\n2\r\n$2\r\nl1\r\n$2\r\nl2\r\n$5\r\nRIGHT\r\n$5\r\nCOUNT\r\n$1\r\n3\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: LMPop = frame.try_into()?;
+        assert_eq!(result.keys, vec!["l1", "l2"]);
+        assert_eq!((result.side, result.count), (ListSide::Right, 3));
+
+        // numkeys larger than the keys given
+        buf.extend_from_slice(b"*4\r\n$5\r\nlmpop\r\n$1\r\n2\r\n$2\r\nl1\r\n$4\r\nleft\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(LMPop::try_from(frame).is_err());
+
+        buf.extend_from_slice(
+            b"*5\r\n$6\r\nblmpop\r\n$1\r\n0\r\n$1\r\n1\r\n$2\r\nl1\r\n$4\r\nleft\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: BLMPop = frame.try_into()?;
+        assert_eq!((result.timeout, result.count), (0.0, 1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lmpop_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.rpush("l2", vec![bulk("a"), bulk("b"), bulk("c")])?;
+        let cmd = LMPop {
+            keys: vec!["l1".to_string(), "l2".to_string()],
+            side: ListSide::Left,
+            count: 2,
+        };
+        let expected = RespArray::new([
+            bulk("l2"),
+            RespArray::new(vec![bulk("a"), bulk("b")]).into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let pusher = backend.clone();
+        let waiter = tokio::spawn(async move {
+            let cmd = BLMPop {
+                keys: vec!["l1".to_string()],
+                side: ListSide::Right,
+                count: 5,
+                timeout: 0.0,
+            };
+            cmd.block(&pusher).await
+        });
+        while !waiter.is_finished() {
+            if backend.llen("l1")? == 0 {
+                backend.rpush("l1", vec![bulk("x"), bulk("y")])?;
+            }
+            tokio::task::yield_now().await;
+        }
+        let expected = RespArray::new([
+            bulk("l1"),
+            RespArray::new(vec![bulk("y"), bulk("x")]).into(),
+        ]);
+        assert_eq!(waiter.await?, expected.into());
+
+        Ok(())
+    }
+}
@@ -1,24 +1,228 @@
+use crate::backend::now_ms;
 use crate::cmd::{
-    extract_args, validate_command, CommandError, CommandExecutor, Get, Set, RESP_OK,
+    extract_args, extract_float, extract_int, extract_string, validate_command,
+    validate_variadic_command, Append, CommandError, CommandExecutor, Decr, DecrBy, Get, GetDel,
+    GetEx, GetExExpire, GetSet, Incr, IncrBy, IncrByFloat, MGet, MSet, MSetNx, PSetEx, Set, SetEx,
+    SetExpire, SetNx, Strlen, RESP_OK,
+};
+use crate::{
+    Backend, BackendError, BulkString, ExpireUpdate, RespArray, RespFrame, RespNull, SetCondition,
+    SimpleError,
 };
-use crate::{Backend, RespArray, RespFrame, RespNull};
 
 impl CommandExecutor for Get {
     fn execute(self, backend: &Backend) -> RespFrame {
         match backend.get(&self.key) {
-            None => RespFrame::Null(RespNull),
-            Some(value) => value,
+            Ok(None) => RespFrame::Null(RespNull),
+            Ok(Some(value)) => value,
+            Err(e) => e.into(),
         }
     }
 }
 
 impl CommandExecutor for Set {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend.set(self.key, self.value);
+        let update = match self.expire {
+            Some(
+                SetExpire::Ex(time)
+                | SetExpire::Px(time)
+                | SetExpire::ExAt(time)
+                | SetExpire::PxAt(time),
+            ) if time <= 0 => None,
+            None => Some(ExpireUpdate::Persist),
+            Some(SetExpire::KeepTtl) => Some(ExpireUpdate::Keep),
+            Some(SetExpire::Ex(seconds)) => seconds
+                .checked_mul(1000)
+                .and_then(|ms| ms.checked_add(now_ms()))
+                .map(ExpireUpdate::At),
+            Some(SetExpire::Px(ms)) => ms.checked_add(now_ms()).map(ExpireUpdate::At),
+            Some(SetExpire::ExAt(timestamp)) => timestamp.checked_mul(1000).map(ExpireUpdate::At),
+            Some(SetExpire::PxAt(timestamp_ms)) => Some(ExpireUpdate::At(timestamp_ms)),
+        };
+        let Some(update) = update else {
+            return SimpleError::new("ERR invalid expire time in 'set' command").into();
+        };
+
+        match backend.set_with(self.key, self.value, self.condition, update, self.get) {
+            Ok((_, old)) if self.get => old.unwrap_or(RespFrame::Null(RespNull)),
+            Ok((true, _)) => RESP_OK.clone(),
+            Ok((false, _)) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SetNx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.setnx(self.key, self.value) as i64)
+    }
+}
+
+impl CommandExecutor for SetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.seconds.checked_mul(1000);
+        set_expire(backend, self.key, self.value, at, "setex")
+    }
+}
+
+impl CommandExecutor for PSetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        set_expire(
+            backend,
+            self.key,
+            self.value,
+            Some(self.milliseconds),
+            "psetex",
+        )
+    }
+}
+
+// SET with a relative TTL in milliseconds, which must be positive
+fn set_expire(
+    backend: &Backend,
+    key: String,
+    value: RespFrame,
+    ttl_ms: Option<i64>,
+    name: &str,
+) -> RespFrame {
+    match ttl_ms
+        .filter(|ms| *ms > 0)
+        .and_then(|ms| ms.checked_add(now_ms()))
+    {
+        Some(at) => {
+            backend.set_expire_at(key, value, at);
+            RESP_OK.clone()
+        }
+        None => SimpleError::new(format!("ERR invalid expire time in '{}' command", name)).into(),
+    }
+}
+
+impl CommandExecutor for GetSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        value_reply(backend.getset(self.key, self.value))
+    }
+}
+
+impl CommandExecutor for GetDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        value_reply(backend.getdel(&self.key))
+    }
+}
+
+impl CommandExecutor for GetEx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let update = match self.expire {
+            None => Some(ExpireUpdate::Keep),
+            Some(GetExExpire::Persist) => Some(ExpireUpdate::Persist),
+            Some(GetExExpire::Ex(seconds)) => seconds
+                .checked_mul(1000)
+                .and_then(|ms| ms.checked_add(now_ms()))
+                .map(ExpireUpdate::At),
+            Some(GetExExpire::Px(ms)) => ms.checked_add(now_ms()).map(ExpireUpdate::At),
+            Some(GetExExpire::ExAt(timestamp)) => timestamp.checked_mul(1000).map(ExpireUpdate::At),
+            Some(GetExExpire::PxAt(timestamp_ms)) => Some(ExpireUpdate::At(timestamp_ms)),
+        };
+
+        match update {
+            Some(update) => value_reply(backend.getex(&self.key, update)),
+            None => SimpleError::new("ERR invalid expire time in 'getex' command").into(),
+        }
+    }
+}
+
+fn value_reply(result: Result<Option<RespFrame>, BackendError>) -> RespFrame {
+    match result {
+        Ok(None) => RespFrame::Null(RespNull),
+        Ok(Some(value)) => value,
+        Err(e) => e.into(),
+    }
+}
+
+impl CommandExecutor for Incr {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        incr_reply(backend.incr_by(&self.key, 1))
+    }
+}
+
+impl CommandExecutor for Decr {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        incr_reply(backend.incr_by(&self.key, -1))
+    }
+}
+
+impl CommandExecutor for IncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        incr_reply(backend.incr_by(&self.key, self.increment))
+    }
+}
+
+impl CommandExecutor for DecrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.decrement.checked_neg() {
+            Some(delta) => incr_reply(backend.incr_by(&self.key, delta)),
+            None => BackendError::Overflow.into(),
+        }
+    }
+}
+
+impl CommandExecutor for IncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.incr_by_float(&self.key, self.increment) {
+            Ok(value) => BulkString::new(value.to_string()).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for MGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let values = backend
+            .mget(&self.keys)
+            .into_iter()
+            .map(|v| v.unwrap_or(RespFrame::Null(RespNull)))
+            .collect::<Vec<_>>();
+        RespArray::new(values).into()
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.mset(self.pairs);
         RESP_OK.clone()
     }
 }
 
+impl CommandExecutor for MSetNx {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.msetnx(self.pairs) as i64)
+    }
+}
+
+impl CommandExecutor for Append {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.append(&self.key, &self.value) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Strlen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.strlen(&self.key) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+fn incr_reply(result: Result<i64, BackendError>) -> RespFrame {
+    match result {
+        Ok(value) => RespFrame::Integer(value),
+        Err(e) => e.into(),
+    }
+}
+
 impl TryFrom<RespArray> for Get {
     type Error = CommandError;
 
@@ -28,25 +232,325 @@ impl TryFrom<RespArray> for Get {
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.0.into())?,
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
 }
 
+// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | KEEPTTL]
 impl TryFrom<RespArray> for Set {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        validate_command(&value, &["set"], 2)?;
+        validate_variadic_command(&value, &["set"], 2)?;
 
         let mut args = extract_args(value, 1)?.into_iter();
-        match (args.next(), args.next()) {
-            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set {
-                key: String::from_utf8(key.0)?,
+        let (key, value) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(value)) => {
+                (String::from_utf8(key.0.into())?, value)
+            }
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid key or value".to_string(),
+                ))
+            }
+        };
+
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        let mut condition = SetCondition::Always;
+        let mut expire = None;
+        let mut get = false;
+        while let Some(arg) = args.next() {
+            let RespFrame::BulkString(opt) = arg else {
+                return Err(syntax_error());
+            };
+            match opt.to_ascii_lowercase().as_slice() {
+                b"nx" if condition != SetCondition::IfExists => condition = SetCondition::IfMissing,
+                b"xx" if condition != SetCondition::IfMissing => condition = SetCondition::IfExists,
+                b"get" => get = true,
+                b"keepttl" if matches!(expire, None | Some(SetExpire::KeepTtl)) => {
+                    expire = Some(SetExpire::KeepTtl)
+                }
+                opt @ (b"ex" | b"px" | b"exat" | b"pxat") if expire.is_none() => {
+                    // a time that's not positive is an error of the execution, like SETEX's
+                    let time = extract_int(args.next())?;
+                    expire = Some(match opt {
+                        b"ex" => SetExpire::Ex(time),
+                        b"px" => SetExpire::Px(time),
+                        b"exat" => SetExpire::ExAt(time),
+                        _ => SetExpire::PxAt(time),
+                    });
+                }
+                _ => return Err(syntax_error()),
+            }
+        }
+
+        Ok(Set {
+            key,
+            value,
+            condition,
+            expire,
+            get,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SetNx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setnx"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        match args.next() {
+            Some(value) => Ok(SetNx { key, value }),
+            None => Err(CommandError::InvalidArgument("Invalid value".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for SetEx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setex"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let seconds = extract_int(args.next())?;
+        match args.next() {
+            Some(value) => Ok(SetEx {
+                key,
+                seconds,
                 value,
             }),
+            None => Err(CommandError::InvalidArgument("Invalid value".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for PSetEx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["psetex"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let milliseconds = extract_int(args.next())?;
+        match args.next() {
+            Some(value) => Ok(PSetEx {
+                key,
+                milliseconds,
+                value,
+            }),
+            None => Err(CommandError::InvalidArgument("Invalid value".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for GetSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getset"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        match args.next() {
+            Some(value) => Ok(GetSet { key, value }),
+            None => Err(CommandError::InvalidArgument("Invalid value".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for GetDel {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getdel"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(GetDel { key })
+    }
+}
+
+// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | PERSIST]
+impl TryFrom<RespArray> for GetEx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["getex"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let opt = match args.next() {
+            None => return Ok(GetEx { key, expire: None }),
+            Some(RespFrame::BulkString(opt)) => opt.to_ascii_lowercase(),
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+
+        let expire = match opt.as_slice() {
+            b"persist" => GetExExpire::Persist,
+            b"ex" | b"px" | b"exat" | b"pxat" => {
+                let time = extract_int(args.next())?;
+                if time <= 0 {
+                    return Err(CommandError::InvalidArgument(
+                        "invalid expire time in 'getex' command".to_string(),
+                    ));
+                }
+                match opt.as_slice() {
+                    b"ex" => GetExExpire::Ex(time),
+                    b"px" => GetExExpire::Px(time),
+                    b"exat" => GetExExpire::ExAt(time),
+                    _ => GetExExpire::PxAt(time),
+                }
+            }
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        Ok(GetEx {
+            key,
+            expire: Some(expire),
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Incr {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incr"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(Incr { key })
+    }
+}
+
+impl TryFrom<RespArray> for Decr {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["decr"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(Decr { key })
+    }
+}
+
+impl TryFrom<RespArray> for IncrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incrby"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let increment = extract_int(args.next())?;
+        Ok(IncrBy { key, increment })
+    }
+}
+
+impl TryFrom<RespArray> for DecrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["decrby"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let decrement = extract_int(args.next())?;
+        Ok(DecrBy { key, decrement })
+    }
+}
+
+impl TryFrom<RespArray> for IncrByFloat {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incrbyfloat"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let increment = extract_float(args.next())?;
+        Ok(IncrByFloat { key, increment })
+    }
+}
+
+impl TryFrom<RespArray> for MGet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["mget"], 1)?;
+
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|key| extract_string(Some(key), "key"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MGet { keys })
+    }
+}
+
+impl TryFrom<RespArray> for MSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["mset"], 2)?;
+        let pairs = extract_pairs(value, "mset")?;
+        Ok(MSet { pairs })
+    }
+}
+
+impl TryFrom<RespArray> for MSetNx {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["msetnx"], 2)?;
+        let pairs = extract_pairs(value, "msetnx")?;
+        Ok(MSetNx { pairs })
+    }
+}
+
+// the key value pairs following the command name
+fn extract_pairs(value: RespArray, name: &str) -> Result<Vec<(String, RespFrame)>, CommandError> {
+    if !(value.len() - 1).is_multiple_of(2) {
+        return Err(CommandError::InvalidArgument(format!(
+            "wrong number of arguments for '{}' command",
+            name
+        )));
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(key), Some(value)) = (args.next(), args.next()) {
+        pairs.push((extract_string(Some(key), "key")?, value));
+    }
+    Ok(pairs)
+}
+
+impl TryFrom<RespArray> for Append {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["append"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(value))) => Ok(Append {
+                key: String::from_utf8(key.0.into())?,
+                value: value.0.into(),
+            }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or value".to_string(),
             )),
@@ -54,11 +558,27 @@ impl TryFrom<RespArray> for Set {
     }
 }
 
+impl TryFrom<RespArray> for Strlen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["strlen"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(Strlen { key })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::cmd::{CommandExecutor, Get, Set, RESP_OK};
+    use crate::backend::command;
+    use crate::cmd::{
+        Append, CommandExecutor, DecrBy, Get, GetDel, GetEx, GetExExpire, GetSet, Incr, IncrBy,
+        IncrByFloat, MGet, MSet, MSetNx, PSetEx, Set, SetEx, SetExpire, SetNx, Strlen, RESP_OK,
+    };
     use crate::RespDecode;
-    use crate::{Backend, RespArray, RespFrame};
+    use crate::{Backend, RespArray, RespFrame, RespNull, SetCondition, SimpleError};
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -93,6 +613,9 @@ mod tests {
         let cmd = Set {
             key: "hello".to_string(),
             value: RespFrame::BulkString(b"world".into()),
+            condition: SetCondition::Always,
+            expire: None,
+            get: false,
         };
 
         let result = cmd.execute(&backend);
@@ -106,4 +629,248 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_incrby_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nincrby\r\n$5\r\nhello\r\n$2\r\n-5\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: IncrBy = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+        assert_eq!(result.increment, -5);
+
+        buf.extend_from_slice(b"*3\r\n$11\r\nincrbyfloat\r\n$5\r\nhello\r\n$3\r\nabc\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<IncrByFloat, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incr_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = Incr {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = DecrBy {
+            key: "hello".to_string(),
+            decrement: i64::MIN,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR increment or decrement would overflow").into()
+        );
+
+        let cmd = IncrByFloat {
+            key: "hello".to_string(),
+            increment: 1.5,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::BulkString(b"2.5".into()));
+
+        let cmd = IncrBy {
+            key: "hello".to_string(),
+            increment: 1,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_strlen_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nappend\r\n$5\r\nhello\r\n$2\r\n\xff\x00\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Append = frame.try_into()?;
+        assert_eq!(cmd.value, b"\xff\x00");
+
+        let backend = Backend::new();
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+        let cmd = Strlen {
+            key: "hello".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mset_mget_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$4\r\nmset\r\n$2\r\nk1\r\n$2\r\nv1\r\n$2\r\nk2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<MSet, _> = frame.try_into();
+        assert!(result.is_err());
+
+        buf.extend_from_slice(
+            b"*5\r\n$4\r\nmset\r\n$2\r\nk1\r\n$2\r\nv1\r\n$2\r\nk2\r\n$2\r\nv2\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: MSet = frame.try_into()?;
+        assert_eq!(cmd.pairs.len(), 2);
+
+        let backend = Backend::new();
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+
+        let cmd = MSetNx {
+            pairs: vec![("k2".to_string(), RespFrame::BulkString(b"v3".into()))],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = MGet {
+            keys: vec!["k1".to_string(), "k3".to_string(), "k2".to_string()],
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new(vec![
+                RespFrame::BulkString(b"v1".into()),
+                RespFrame::Null(crate::RespNull),
+                RespFrame::BulkString(b"v2".into()),
+            ])
+            .into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_setex_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nsetex\r\n$5\r\nhello\r\n$2\r\n10\r\n$5\r\nworld\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: SetEx = frame.try_into()?;
+        assert_eq!(cmd.seconds, 10);
+
+        let backend = Backend::new();
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert!(matches!(backend.expire_time("hello"), Some(Some(_))));
+
+        let cmd = PSetEx {
+            key: "hello".to_string(),
+            milliseconds: 0,
+            value: RespFrame::BulkString(b"world".into()),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR invalid expire time in 'psetex' command").into()
+        );
+
+        let cmd = SetNx {
+            key: "hello".to_string(),
+            value: RespFrame::BulkString(b"again".into()),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_options_command() -> Result<()> {
+        let backend = Backend::new();
+        let set = |args: &[&str]| Set::try_from(command(["SET"].iter().chain(args)));
+
+        let cmd = set(&["k", "v", "nx", "GET", "PX", "100"])?;
+        assert_eq!(cmd.condition, SetCondition::IfMissing);
+        assert_eq!(cmd.expire, Some(SetExpire::Px(100)));
+        assert!(cmd.get);
+        assert!(set(&["k", "v", "NX", "XX"]).is_err());
+        assert!(set(&["k", "v", "EX", "1", "KEEPTTL"]).is_err());
+        assert!(set(&["k", "v", "EX"]).is_err());
+
+        assert_eq!(
+            set(&["k", "v", "EX", "0"])?.execute(&backend),
+            SimpleError::new("ERR invalid expire time in 'set' command").into()
+        );
+        assert_eq!(set(&["k", "v1", "XX"])?.execute(&backend), RespNull.into());
+        assert_eq!(
+            set(&["k", "v1", "NX", "EX", "100"])?.execute(&backend),
+            RESP_OK.clone()
+        );
+        assert_eq!(set(&["k", "v2", "NX"])?.execute(&backend), RespNull.into());
+        assert_eq!(
+            set(&["k", "v2", "XX", "GET", "KEEPTTL"])?.execute(&backend),
+            RespFrame::BulkString(b"v1".into())
+        );
+        assert!(backend.expire_time("k").flatten().is_some());
+        // without KEEPTTL the TTL is dropped
+        set(&["k", "v3"])?.execute(&backend);
+        assert_eq!(backend.expire_time("k"), Some(None));
+
+        backend.sadd("s", vec!["m".to_string()])?;
+        assert!(matches!(
+            set(&["s", "v", "GET"])?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        // without GET any value is overwritten
+        assert_eq!(set(&["s", "v"])?.execute(&backend), RESP_OK.clone());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_getex_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\ngetex\r\n$5\r\nhello\r\n$4\r\nPXAT\r\n$3\r\n100\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: GetEx = frame.try_into()?;
+        assert_eq!(cmd.expire, Some(GetExExpire::PxAt(100)));
+
+        buf.extend_from_slice(b"*3\r\n$5\r\ngetex\r\n$5\r\nhello\r\n$7\r\npersist\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: GetEx = frame.try_into()?;
+        assert_eq!(cmd.expire, Some(GetExExpire::Persist));
+
+        buf.extend_from_slice(b"*4\r\n$5\r\ngetex\r\n$5\r\nhello\r\n$2\r\nex\r\n$2\r\n-1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<GetEx, _> = frame.try_into();
+        assert!(result.is_err());
+
+        buf.extend_from_slice(
+            b"*5\r\n$5\r\ngetex\r\n$5\r\nhello\r\n$2\r\nex\r\n$1\r\n1\r\n$7\r\npersist\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<GetEx, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_getset_getdel_getex_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = GetSet {
+            key: "hello".to_string(),
+            value: RespFrame::BulkString(b"world".into()),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(crate::RespNull));
+
+        let cmd = GetEx {
+            key: "hello".to_string(),
+            expire: Some(GetExExpire::Ex(100)),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::BulkString(b"world".into())
+        );
+        assert!(matches!(backend.expire_time("hello"), Some(Some(_))));
+
+        let cmd = GetDel {
+            key: "hello".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::BulkString(b"world".into())
+        );
+        assert!(!backend.contains_key("hello"));
+
+        Ok(())
+    }
 }
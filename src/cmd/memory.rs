@@ -0,0 +1,214 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    CommandError, CommandExecutor, MemoryDoctor, MemoryPurge, MemoryStats, MemoryUsage, RESP_OK,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespMap, RespNull};
+
+// same default as redis: estimate aggregates from 5 sampled elements
+const DEFAULT_SAMPLES: usize = 5;
+
+impl CommandExecutor for MemoryUsage {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.memory_usage(&self.key, self.samples) {
+            None => RespFrame::Null(RespNull),
+            Some(bytes) => RespFrame::Integer(bytes as i64),
+        }
+    }
+}
+
+impl CommandExecutor for MemoryStats {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let report = backend.memory_stats();
+        let mut map = RespMap::new();
+        map.insert("keys.count".to_string(), (report.keys as i64).into());
+        map.insert(
+            "keys.bytes-per-key".to_string(),
+            (report.bytes_per_key() as i64).into(),
+        );
+        map.insert(
+            "dataset.bytes".to_string(),
+            (report.dataset_bytes as i64).into(),
+        );
+        map.insert(
+            "overhead.total".to_string(),
+            (report.overhead_bytes as i64).into(),
+        );
+        map.insert(
+            "total.allocated".to_string(),
+            (report.total_bytes() as i64).into(),
+        );
+        map.insert("strings.count".to_string(), (report.strings as i64).into());
+        map.insert("hashes.count".to_string(), (report.hashes as i64).into());
+        map.insert("lists.count".to_string(), (report.lists as i64).into());
+        map.insert("sets.count".to_string(), (report.sets as i64).into());
+        map.insert("zsets.count".to_string(), (report.zsets as i64).into());
+        map.insert("streams.count".to_string(), (report.streams as i64).into());
+        if let Some(allocator) = backend.allocator_stats() {
+            map.insert(
+                "allocator.allocated".to_string(),
+                (allocator.allocated as i64).into(),
+            );
+            map.insert(
+                "allocator.active".to_string(),
+                (allocator.active as i64).into(),
+            );
+            map.insert(
+                "allocator.resident".to_string(),
+                (allocator.resident as i64).into(),
+            );
+            map.insert(
+                "allocator-fragmentation.ratio".to_string(),
+                RespFrame::Double(allocator.frag_ratio()),
+            );
+            map.insert(
+                "allocator-fragmentation.bytes".to_string(),
+                allocator.frag_bytes().into(),
+            );
+            map.insert(
+                "allocator.rss-ratio".to_string(),
+                RespFrame::Double(allocator.rss_ratio()),
+            );
+            map.insert(
+                "allocator.rss-bytes".to_string(),
+                allocator.rss_bytes().into(),
+            );
+        }
+        map.into()
+    }
+}
+
+impl CommandExecutor for MemoryDoctor {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        BulkString::new(backend.memory_doctor()).into()
+    }
+}
+
+impl CommandExecutor for MemoryPurge {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.purge_allocator() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// MEMORY USAGE key [SAMPLES count]
+impl TryFrom<RespArray> for MemoryUsage {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["memory", "usage"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let samples = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => DEFAULT_SAMPLES,
+            (Some(RespFrame::BulkString(opt)), count, None)
+                if opt.eq_ignore_ascii_case(b"samples") =>
+            {
+                extract_int(count)?.max(0) as usize
+            }
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+
+        Ok(MemoryUsage { key, samples })
+    }
+}
+
+impl TryFrom<RespArray> for MemoryStats {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["memory", "stats"], 0)?;
+        Ok(MemoryStats)
+    }
+}
+
+impl TryFrom<RespArray> for MemoryDoctor {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["memory", "doctor"], 0)?;
+        Ok(MemoryDoctor)
+    }
+}
+
+impl TryFrom<RespArray> for MemoryPurge {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["memory", "purge"], 0)?;
+        Ok(MemoryPurge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::{CommandExecutor, MemoryDoctor, MemoryPurge, MemoryStats, MemoryUsage};
+    use crate::RespDecode;
+    use crate::{Backend, RespArray, RespFrame, RespNull};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_memory_usage_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nmemory\r\n$5\r\nusage\r\n$5\r\nhello\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: MemoryUsage = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+        assert_eq!(result.samples, 5);
+
+        buf.extend_from_slice(
+            b"*5\r\n$6\r\nmemory\r\n$5\r\nusage\r\n$5\r\nhello\r\n$7\r\nSAMPLES\r\n$1\r\n0\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: MemoryUsage = frame.try_into()?;
+        assert_eq!(result.samples, 0);
+
+        buf.extend_from_slice(
+            b"*4\r\n$6\r\nmemory\r\n$5\r\nusage\r\n$5\r\nhello\r\n$7\r\nsamples\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<MemoryUsage, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = MemoryUsage {
+            key: "hello".to_string(),
+            samples: 5,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        backend.set("hello".to_string(), RespFrame::BulkString(b"world".into()));
+        let cmd = MemoryUsage {
+            key: "hello".to_string(),
+            samples: 5,
+        };
+        assert!(matches!(cmd.execute(&backend), RespFrame::Integer(n) if n > 5));
+
+        match MemoryStats.execute(&backend) {
+            RespFrame::Map(map) => {
+                assert_eq!(map.get("keys.count"), Some(&RespFrame::Integer(1)))
+            }
+            frame => panic!("unexpected reply: {:?}", frame),
+        }
+
+        assert!(matches!(
+            MemoryDoctor.execute(&backend),
+            RespFrame::BulkString(_)
+        ));
+        assert_eq!(
+            MemoryPurge.execute(&backend),
+            RespFrame::SimpleString("OK".into())
+        );
+
+        Ok(())
+    }
+}
@@ -1,70 +1,2243 @@
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::runtime::{Handle, RuntimeFlavor};
 
-use crate::{Backend, RespArray, RespError, RespFrame, SimpleString};
+use crate::{
+    Aggregate, Backend, BackendError, BitFieldOp, BitUnit, BlockingPop, BulkString, ClaimOptions,
+    ExpireOptions, GeoSearchOptions, GeoUnit, LatencyEvent, LexBound, ListSide, MigrateOptions,
+    PendingFilter, PopFrom, Popped, RespArray, RespError, RespFrame, RestoreOptions, RestorePolicy,
+    ScoreBound, SetCondition, SimpleError, SimpleString, SlotState, SortOptions, StreamFields,
+    StreamId, StreamTrim, Subscriptions, TrackingOptions, XAddId, XAddOptions, ZAddOptions,
+    ZRangeOptions,
+};
 
+mod acl;
+mod bitmap;
+mod client;
+mod cluster;
+mod command;
+mod config;
+mod connection;
+mod custom;
+mod debug;
+mod expire;
+mod geo;
 mod hmap;
+mod hyperloglog;
+mod info;
+mod keys;
+mod latency;
+mod lcs;
+mod list;
 mod map;
+mod memory;
+mod object;
+mod persistence;
+mod propagate;
+mod pubsub;
+mod replication;
+mod script;
+mod sentinel;
+mod set;
+mod sort;
+mod stream;
+mod transaction;
+mod zset;
+
+pub use cluster::slot_keys;
+pub use command::{command_keys, key_specs, BeginSearch, FindKeys, KeySpec};
+pub use custom::{CommandFlag, CommandSpec, CustomCommand};
+
+// the commands a server in sentinel mode runs, it has no keyspace
+const SENTINEL_MODE_COMMANDS: &[&str] = &[
+    "sentinel",
+    "ping",
+    "info",
+    "hello",
+    "auth",
+    "client",
+    "acl",
+    "subscribe",
+    "unsubscribe",
+    "psubscribe",
+    "punsubscribe",
+    "publish",
+    "reset",
+    "shutdown",
+    "quit",
+];
 
 lazy_static! {
     static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
 }
 
-#[derive(Error, Debug)]
-pub enum CommandError {
-    #[error("Invalid command: {0}")]
-    InvalidCommand(String),
-    #[error("Invalid argument: {0}")]
-    InvalidArgument(String),
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("Invalid command: {0}")]
+    InvalidCommand(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("{0}")]
+    RespError(#[from] RespError),
+    #[error("Utf8 error: {0}")]
+    Utf8Error(#[from] std::string::FromUtf8Error),
+}
+
+impl From<BackendError> for RespFrame {
+    fn from(e: BackendError) -> Self {
+        SimpleError::new(e.to_string()).into()
+    }
+}
+
+#[enum_dispatch]
+pub trait CommandExecutor {
+    fn execute(self, backend: &Backend) -> RespFrame;
+}
+
+#[enum_dispatch(CommandExecutor)]
+#[derive(Debug, Clone)]
+pub enum Command {
+    Hello(Hello),
+    Auth(Auth),
+    Quit(Quit),
+    Ping(Ping),
+    Select(Select),
+
+    Get(Get),
+    Set(Set),
+    SetNx(SetNx),
+    SetEx(SetEx),
+    PSetEx(PSetEx),
+    GetSet(GetSet),
+    GetDel(GetDel),
+    GetEx(GetEx),
+    Incr(Incr),
+    Decr(Decr),
+    IncrBy(IncrBy),
+    DecrBy(DecrBy),
+    IncrByFloat(IncrByFloat),
+    MGet(MGet),
+    MSet(MSet),
+    MSetNx(MSetNx),
+    Append(Append),
+    Lcs(Lcs),
+    SetBit(SetBit),
+    GetBit(GetBit),
+    BitCount(BitCount),
+    BitPos(BitPos),
+    BitField(BitField),
+    Strlen(Strlen),
+    HGet(HGet),
+    HSet(HSet),
+    HGetAll(HGetAll),
+    HDel(HDel),
+    HExists(HExists),
+    HLen(HLen),
+    HKeys(HKeys),
+    HVals(HVals),
+    HStrLen(HStrLen),
+    HMGet(HMGet),
+    HIncrBy(HIncrBy),
+    HIncrByFloat(HIncrByFloat),
+    HRandField(HRandField),
+    HScan(HScan),
+    HExpire(HExpire),
+    HPExpire(HPExpire),
+    HExpireAt(HExpireAt),
+    HPExpireAt(HPExpireAt),
+    HTtl(HTtl),
+    HPTtl(HPTtl),
+    HPersist(HPersist),
+
+    LPush(LPush),
+    RPush(RPush),
+    LPop(LPop),
+    RPop(RPop),
+    LLen(LLen),
+    LRange(LRange),
+    LIndex(LIndex),
+    LSet(LSet),
+    LInsert(LInsert),
+    LRem(LRem),
+    LTrim(LTrim),
+    LPos(LPos),
+    BLPop(BLPop),
+    BRPop(BRPop),
+    LMove(LMove),
+    RPopLPush(RPopLPush),
+    BLMove(BLMove),
+    LMPop(LMPop),
+    BLMPop(BLMPop),
+
+    SAdd(SAdd),
+    SRem(SRem),
+    SMembers(SMembers),
+    SCard(SCard),
+    SIsMember(SIsMember),
+    SMIsMember(SMIsMember),
+    SInter(SInter),
+    SUnion(SUnion),
+    SDiff(SDiff),
+    SInterStore(SInterStore),
+    SUnionStore(SUnionStore),
+    SDiffStore(SDiffStore),
+    SPop(SPop),
+    SRandMember(SRandMember),
+    SMove(SMove),
+    SScan(SScan),
+
+    ZAdd(ZAdd),
+    ZRem(ZRem),
+    ZScore(ZScore),
+    ZCard(ZCard),
+    ZRange(ZRange),
+    ZRevRange(ZRevRange),
+    ZRangeByScore(ZRangeByScore),
+    ZRevRangeByScore(ZRevRangeByScore),
+    ZRangeByLex(ZRangeByLex),
+    ZRevRangeByLex(ZRevRangeByLex),
+    ZLexCount(ZLexCount),
+    ZIncrBy(ZIncrBy),
+    ZRank(ZRank),
+    ZRevRank(ZRevRank),
+    ZCount(ZCount),
+    ZPopMin(ZPopMin),
+    ZPopMax(ZPopMax),
+    BZPopMin(BZPopMin),
+    BZPopMax(BZPopMax),
+    ZUnion(ZUnion),
+    ZInter(ZInter),
+    ZDiff(ZDiff),
+    ZUnionStore(ZUnionStore),
+    ZInterStore(ZInterStore),
+    ZDiffStore(ZDiffStore),
+    ZRemRangeByRank(ZRemRangeByRank),
+    ZRemRangeByScore(ZRemRangeByScore),
+    ZRemRangeByLex(ZRemRangeByLex),
+    ZRandMember(ZRandMember),
+    ZMScore(ZMScore),
+    ZScan(ZScan),
+
+    GeoAdd(GeoAdd),
+    GeoPos(GeoPos),
+    GeoDist(GeoDist),
+    GeoSearch(GeoSearch),
+
+    PfAdd(PfAdd),
+    PfCount(PfCount),
+    PfMerge(PfMerge),
+
+    XAdd(XAdd),
+    XLen(XLen),
+    XRange(XRange),
+    XRevRange(XRevRange),
+    XRead(XRead),
+    XGroupCreate(XGroupCreate),
+    XGroupDestroy(XGroupDestroy),
+    XGroupSetId(XGroupSetId),
+    XGroupCreateConsumer(XGroupCreateConsumer),
+    XGroupDelConsumer(XGroupDelConsumer),
+    XSetId(XSetId),
+    XInfoStream(XInfoStream),
+    XInfoGroups(XInfoGroups),
+    XInfoConsumers(XInfoConsumers),
+    XReadGroup(XReadGroup),
+    XAck(XAck),
+    XPending(XPending),
+    XClaim(XClaim),
+    XAutoClaim(XAutoClaim),
+    XTrim(XTrim),
+    XDel(XDel),
+
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    PSubscribe(PSubscribe),
+    PUnsubscribe(PUnsubscribe),
+    Publish(Publish),
+    SSubscribe(SSubscribe),
+    SUnsubscribe(SUnsubscribe),
+    SPublish(SPublish),
+
+    ClientId(ClientId),
+    ClientTracking(ClientTracking),
+    ClientCaching(ClientCaching),
+    ClientList(ClientList),
+    ClientNoEvict(ClientNoEvict),
+
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Reset(Reset),
+
+    Eval(Eval),
+    EvalSha(EvalSha),
+    ScriptLoad(ScriptLoad),
+    ScriptExists(ScriptExists),
+    ScriptFlush(ScriptFlush),
+    ScriptKill(ScriptKill),
+    FunctionLoad(FunctionLoad),
+    FunctionDelete(FunctionDelete),
+    FunctionFlush(FunctionFlush),
+    FunctionList(FunctionList),
+    FunctionDump(FunctionDump),
+    FunctionRestore(FunctionRestore),
+    FCall(FCall),
+    FCallRo(FCallRo),
+
+    DebugDigest(DebugDigest),
+    DebugReload(DebugReload),
+    ObjectEncoding(ObjectEncoding),
+    ObjectRefCount(ObjectRefCount),
+    ObjectIdleTime(ObjectIdleTime),
+    ObjectFreq(ObjectFreq),
+    MemoryUsage(MemoryUsage),
+    MemoryStats(MemoryStats),
+    MemoryDoctor(MemoryDoctor),
+    MemoryPurge(MemoryPurge),
+
+    Expire(Expire),
+    PExpire(PExpire),
+    ExpireAt(ExpireAt),
+    PExpireAt(PExpireAt),
+    Ttl(Ttl),
+    PTtl(PTtl),
+    Persist(Persist),
+    ExpireTime(ExpireTime),
+    PExpireTime(PExpireTime),
+
+    Del(Del),
+    Keys(Keys),
+    Scan(Scan),
+    Rename(Rename),
+    RenameNx(RenameNx),
+    Dump(Dump),
+    Restore(Restore),
+    Migrate(Migrate),
+
+    Save(Save),
+    BgSave(BgSave),
+    LastSave(LastSave),
+    BgRewriteAof(BgRewriteAof),
+    WaitAof(WaitAof),
+    Shutdown(Shutdown),
+    ConfigGet(ConfigGet),
+    ConfigSet(ConfigSet),
+    ConfigRewrite(ConfigRewrite),
+    ConfigResetStat(ConfigResetStat),
+    LatencyLatest(LatencyLatest),
+    LatencyHistory(LatencyHistory),
+    LatencyReset(LatencyReset),
+    AclSetUser(AclSetUser),
+    AclGetUser(AclGetUser),
+    AclDelUser(AclDelUser),
+    AclList(AclList),
+    AclWhoAmI(AclWhoAmI),
+    AclSave(AclSave),
+    AclLoad(AclLoad),
+    Info(Info),
+    ReplConf(ReplConf),
+    Wait(Wait),
+    PSync(PSync),
+    Sync(Sync),
+    ReplicaOf(ReplicaOf),
+    ClusterInfo(ClusterInfo),
+    ClusterMyId(ClusterMyId),
+    ClusterSlots(ClusterSlots),
+    ClusterShards(ClusterShards),
+    ClusterNodes(ClusterNodes),
+    ClusterKeySlot(ClusterKeySlot),
+    CommandGetKeys(CommandGetKeys),
+    ClusterSetSlot(ClusterSetSlot),
+    ClusterMeet(ClusterMeet),
+    ClusterForget(ClusterForget),
+    Asking(Asking),
+    SentinelGetMasterAddrByName(SentinelGetMasterAddrByName),
+    SentinelMasters(SentinelMasters),
+    SentinelMaster(SentinelMaster),
+    SentinelReplicas(SentinelReplicas),
+    SentinelSentinels(SentinelSentinels),
+    SentinelIsMasterDownByAddr(SentinelIsMasterDownByAddr),
+    SentinelMonitor(SentinelMonitor),
+    SentinelRemove(SentinelRemove),
+    SentinelSet(SentinelSet),
+    SentinelFailover(SentinelFailover),
+    SentinelMyId(SentinelMyId),
+
+    Sort(Sort),
+
+    Custom(CustomCommand),
+}
+
+#[derive(Debug, Clone)]
+pub struct Hello {
+    /// The protocol to switch to, the connection fills in its current one when omitted.
+    pub protover: Option<u8>,
+    /// The username and password to authenticate with first.
+    pub auth: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Auth {
+    /// None for the default user, of the password set by `requirepass`.
+    pub username: Option<String>,
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Quit;
+
+#[derive(Debug, Clone)]
+pub struct Ping {
+    pub message: Option<BulkString>,
+}
+
+/// Only the database 0 exists, it's the one selected.
+#[derive(Debug, Clone)]
+pub struct Select {
+    pub index: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Get {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Set {
+    pub key: String,
+    pub value: RespFrame,
+    pub condition: SetCondition,
+    pub expire: Option<SetExpire>,
+    pub get: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetNx {
+    pub key: String,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetEx {
+    pub key: String,
+    pub seconds: i64,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct PSetEx {
+    pub key: String,
+    pub milliseconds: i64,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct GetSet {
+    pub key: String,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct GetDel {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GetEx {
+    pub key: String,
+    pub expire: Option<GetExExpire>,
+}
+
+/// The TTL option of SET.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpire {
+    Ex(i64),
+    Px(i64),
+    ExAt(i64),
+    PxAt(i64),
+    KeepTtl,
+}
+
+/// The TTL option of GETEX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GetExExpire {
+    Ex(i64),
+    Px(i64),
+    ExAt(i64),
+    PxAt(i64),
+    Persist,
+}
+
+#[derive(Debug, Clone)]
+pub struct Incr {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Decr {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct IncrBy {
+    pub key: String,
+    pub increment: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecrBy {
+    pub key: String,
+    pub decrement: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct IncrByFloat {
+    pub key: String,
+    pub increment: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MGet {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MSet {
+    pub pairs: Vec<(String, RespFrame)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MSetNx {
+    pub pairs: Vec<(String, RespFrame)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Append {
+    pub key: String,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lcs {
+    pub key1: String,
+    pub key2: String,
+    pub len: bool,
+    pub idx: bool,
+    pub min_match_len: usize,
+    pub with_match_len: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetBit {
+    pub key: String,
+    pub offset: u64,
+    pub bit: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GetBit {
+    pub key: String,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitCount {
+    pub key: String,
+    pub range: Option<(i64, i64, BitUnit)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitPos {
+    pub key: String,
+    pub bit: bool,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitField {
+    pub key: String,
+    pub ops: Vec<BitFieldOp>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Strlen {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HGet {
+    pub key: String,
+    pub field: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HSet {
+    pub key: String,
+    pub fields: Vec<(String, RespFrame)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HMGet {
+    pub key: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HGetAll {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HDel {
+    pub key: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HExists {
+    pub key: String,
+    pub field: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HLen {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HKeys {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HVals {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HStrLen {
+    pub key: String,
+    pub field: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HIncrBy {
+    pub key: String,
+    pub field: String,
+    pub increment: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HIncrByFloat {
+    pub key: String,
+    pub field: String,
+    pub increment: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HRandField {
+    pub key: String,
+    pub count: Option<i64>,
+    pub with_values: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct HScan {
+    pub key: String,
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+    pub no_values: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct HExpire {
+    pub key: String,
+    pub seconds: i64,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HPExpire {
+    pub key: String,
+    pub milliseconds: i64,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HExpireAt {
+    pub key: String,
+    pub timestamp: i64,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HPExpireAt {
+    pub key: String,
+    pub timestamp_ms: i64,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HTtl {
+    pub key: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HPTtl {
+    pub key: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HPersist {
+    pub key: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LPush {
+    pub key: String,
+    pub values: Vec<RespFrame>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RPush {
+    pub key: String,
+    pub values: Vec<RespFrame>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LPop {
+    pub key: String,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RPop {
+    pub key: String,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LLen {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LRange {
+    pub key: String,
+    pub start: i64,
+    pub stop: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LIndex {
+    pub key: String,
+    pub index: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LSet {
+    pub key: String,
+    pub index: i64,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct LInsert {
+    pub key: String,
+    pub before: bool,
+    pub pivot: RespFrame,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct LRem {
+    pub key: String,
+    pub count: i64,
+    pub value: RespFrame,
+}
+
+#[derive(Debug, Clone)]
+pub struct LTrim {
+    pub key: String,
+    pub start: i64,
+    pub stop: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LPos {
+    pub key: String,
+    pub element: RespFrame,
+    pub rank: i64,
+    pub count: Option<usize>,
+    pub maxlen: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BLPop {
+    pub keys: Vec<String>,
+    pub timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BRPop {
+    pub keys: Vec<String>,
+    pub timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LMove {
+    pub source: String,
+    pub destination: String,
+    pub from: ListSide,
+    pub to: ListSide,
+}
+
+#[derive(Debug, Clone)]
+pub struct RPopLPush {
+    pub source: String,
+    pub destination: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BLMove {
+    pub source: String,
+    pub destination: String,
+    pub from: ListSide,
+    pub to: ListSide,
+    pub timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LMPop {
+    pub keys: Vec<String>,
+    pub side: ListSide,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BLMPop {
+    pub keys: Vec<String>,
+    pub side: ListSide,
+    pub count: usize,
+    pub timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SAdd {
+    pub key: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SRem {
+    pub key: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SMembers {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SCard {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SIsMember {
+    pub key: String,
+    pub member: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SMIsMember {
+    pub key: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SInter {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SUnion {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SDiff {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SInterStore {
+    pub destination: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SUnionStore {
+    pub destination: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SDiffStore {
+    pub destination: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SPop {
+    pub key: String,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SRandMember {
+    pub key: String,
+    pub count: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SMove {
+    pub source: String,
+    pub destination: String,
+    pub member: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SScan {
+    pub key: String,
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZAdd {
+    pub key: String,
+    pub members: Vec<(f64, String)>,
+    pub options: ZAddOptions,
+    /// Add the score to the single member's and reply with the new score.
+    pub incr: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRem {
+    pub key: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZScore {
+    pub key: String,
+    pub member: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZCard {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRange {
+    pub key: String,
+    pub options: ZRangeOptions,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRevRange {
+    pub key: String,
+    pub options: ZRangeOptions,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRangeByScore {
+    pub key: String,
+    pub options: ZRangeOptions,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRevRangeByScore {
+    pub key: String,
+    pub options: ZRangeOptions,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRangeByLex {
+    pub key: String,
+    pub options: ZRangeOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRevRangeByLex {
+    pub key: String,
+    pub options: ZRangeOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZLexCount {
+    pub key: String,
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZIncrBy {
+    pub key: String,
+    pub increment: f64,
+    pub member: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRank {
+    pub key: String,
+    pub member: String,
+    pub with_score: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRevRank {
+    pub key: String,
+    pub member: String,
+    pub with_score: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZCount {
+    pub key: String,
+    pub min: ScoreBound,
+    pub max: ScoreBound,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZPopMin {
+    pub key: String,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZPopMax {
+    pub key: String,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BZPopMin {
+    pub keys: Vec<String>,
+    pub timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BZPopMax {
+    pub keys: Vec<String>,
+    pub timeout: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZUnion {
+    pub keys: Vec<String>,
+    pub weights: Vec<f64>,
+    pub aggregate: Aggregate,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZInter {
+    pub keys: Vec<String>,
+    pub weights: Vec<f64>,
+    pub aggregate: Aggregate,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZDiff {
+    pub keys: Vec<String>,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZUnionStore {
+    pub destination: String,
+    pub keys: Vec<String>,
+    pub weights: Vec<f64>,
+    pub aggregate: Aggregate,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZInterStore {
+    pub destination: String,
+    pub keys: Vec<String>,
+    pub weights: Vec<f64>,
+    pub aggregate: Aggregate,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZDiffStore {
+    pub destination: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRemRangeByRank {
+    pub key: String,
+    pub start: i64,
+    pub stop: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRemRangeByScore {
+    pub key: String,
+    pub min: ScoreBound,
+    pub max: ScoreBound,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRemRangeByLex {
+    pub key: String,
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRandMember {
+    pub key: String,
+    pub count: Option<i64>,
+    pub with_scores: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZMScore {
+    pub key: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ZScan {
+    pub key: String,
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoAdd {
+    pub key: String,
+    pub options: ZAddOptions,
+    /// Longitude, latitude and member.
+    pub points: Vec<(f64, f64, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoPos {
+    pub key: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoDist {
+    pub key: String,
+    pub member1: String,
+    pub member2: String,
+    pub unit: GeoUnit,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoSearch {
+    pub key: String,
+    pub options: GeoSearchOptions,
+    /// The unit of the shape, which the distances are replied in.
+    pub unit: GeoUnit,
+    pub with_coord: bool,
+    pub with_dist: bool,
+    pub with_hash: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PfAdd {
+    pub key: String,
+    pub elements: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PfCount {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PfMerge {
+    pub destination: String,
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XAdd {
+    pub key: String,
+    pub id: XAddId,
+    pub fields: StreamFields,
+    pub options: XAddOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct XLen {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XRange {
+    pub key: String,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XRevRange {
+    pub key: String,
+    pub end: StreamId,
+    pub start: StreamId,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XRead {
+    pub keys: Vec<String>,
+    /// The ID to read each stream after, None for `$`: the entries added from now on.
+    pub ids: Vec<Option<StreamId>>,
+    pub count: Option<usize>,
+    /// Milliseconds to block for when there is nothing to read, 0 to block forever.
+    pub block: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XGroupCreate {
+    pub key: String,
+    pub group: String,
+    /// The ID of the last entry delivered to the group, None for `$`: the last one.
+    pub id: Option<StreamId>,
+    pub mkstream: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct XGroupDestroy {
+    pub key: String,
+    pub group: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XGroupSetId {
+    pub key: String,
+    pub group: String,
+    /// The ID of the last entry delivered to the group, None for `$`: the last one.
+    pub id: Option<StreamId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XGroupCreateConsumer {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XGroupDelConsumer {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XSetId {
+    pub key: String,
+    pub id: StreamId,
+}
+
+#[derive(Debug, Clone)]
+pub struct XInfoStream {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XInfoGroups {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XInfoConsumers {
+    pub key: String,
+    pub group: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct XReadGroup {
+    pub group: String,
+    pub consumer: String,
+    pub keys: Vec<String>,
+    /// The ID to read each stream's pending entries after, None for `>`: the entries never
+    /// delivered to the group.
+    pub ids: Vec<Option<StreamId>>,
+    pub count: Option<usize>,
+    /// Milliseconds to block for when there is nothing to read, 0 to block forever.
+    pub block: Option<u64>,
+    pub noack: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct XAck {
+    pub key: String,
+    pub group: String,
+    pub ids: Vec<StreamId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XPending {
+    pub key: String,
+    pub group: String,
+    /// The pending entries to list, the summary if None.
+    pub filter: Option<PendingFilter>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XClaim {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+    pub min_idle: i64,
+    pub ids: Vec<StreamId>,
+    pub options: ClaimOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct XAutoClaim {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+    pub min_idle: i64,
+    pub start: StreamId,
+    pub count: usize,
+    pub justid: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct XTrim {
+    pub key: String,
+    pub trim: StreamTrim,
+}
+
+#[derive(Debug, Clone)]
+pub struct XDel {
+    pub key: String,
+    pub ids: Vec<StreamId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscribe {
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Unsubscribe {
+    /// The channels to unsubscribe from, every subscribed one if empty.
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PSubscribe {
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PUnsubscribe {
+    /// The patterns to unsubscribe from, every subscribed one if empty.
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Publish {
+    pub channel: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SSubscribe {
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SUnsubscribe {
+    /// The shard channels to unsubscribe from, every subscribed one if empty.
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SPublish {
+    pub channel: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientId;
+
+#[derive(Debug, Clone)]
+pub struct ClientTracking {
+    /// The options to turn tracking on with, None to turn it off.
+    pub options: Option<TrackingOptions>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientCaching {
+    pub yes: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientList {
+    /// Only the clients of these IDs, every one if empty.
+    pub ids: Vec<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientNoEvict {
+    pub on: bool,
+}
+
+/// The transaction of a connection: the commands queued since MULTI, if it was called.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    // with the frames they were parsed from, to be propagated
+    queued: Option<Vec<(Command, Option<RespArray>)>>,
+    // a command failed to queue, EXEC discards the transaction
+    failed: bool,
+    // the watched keys, with their versions when watched
+    watched: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Multi;
+
+#[derive(Debug, Clone)]
+pub struct Exec;
+
+#[derive(Debug, Clone)]
+pub struct Discard;
+
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Unwatch;
+
+#[derive(Debug, Clone)]
+pub struct Reset;
+
+#[derive(Debug, Clone)]
+pub struct Eval {
+    pub script: String,
+    pub keys: Vec<String>,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EvalSha {
+    pub sha1: String,
+    pub keys: Vec<String>,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptLoad {
+    pub script: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptExists {
+    pub sha1s: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptFlush;
+
+#[derive(Debug, Clone)]
+pub struct ScriptKill;
+
+#[derive(Debug, Clone)]
+pub struct FunctionLoad {
+    pub code: String,
+    pub replace: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionDelete {
+    pub library: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionFlush;
+
+#[derive(Debug, Clone)]
+pub struct FunctionList {
+    pub pattern: Option<String>,
+    pub with_code: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionDump;
+
+#[derive(Debug, Clone)]
+pub struct FunctionRestore {
+    pub payload: Vec<u8>,
+    pub policy: RestorePolicy,
+}
+
+#[derive(Debug, Clone)]
+pub struct FCall {
+    pub function: String,
+    pub keys: Vec<String>,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FCallRo {
+    pub function: String,
+    pub keys: Vec<String>,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugDigest;
+
+#[derive(Debug, Clone)]
+pub struct DebugReload;
+
+#[derive(Debug, Clone)]
+pub struct ObjectEncoding {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectRefCount {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectIdleTime {
+    pub key: String,
+}
 
-    #[error("{0}")]
-    RespError(#[from] RespError),
-    #[error("Utf8 error: {0}")]
-    Utf8Error(#[from] std::string::FromUtf8Error),
+#[derive(Debug, Clone)]
+pub struct ObjectFreq {
+    pub key: String,
 }
 
-#[enum_dispatch]
-pub trait CommandExecutor {
-    fn execute(self, backend: &Backend) -> RespFrame;
+#[derive(Debug, Clone)]
+pub struct MemoryUsage {
+    pub key: String,
+    pub samples: usize,
 }
 
-#[enum_dispatch(CommandExecutor)]
-pub enum Command {
-    Get(Get),
-    Set(Set),
-    HGet(HGet),
-    HSet(HSet),
-    HGetAll(HGetAll),
+#[derive(Debug, Clone)]
+pub struct MemoryStats;
+
+#[derive(Debug, Clone)]
+pub struct MemoryDoctor;
+
+#[derive(Debug, Clone)]
+pub struct MemoryPurge;
+
+#[derive(Debug, Clone)]
+pub struct Save;
+
+#[derive(Debug, Clone)]
+pub struct BgSave;
+
+#[derive(Debug, Clone)]
+pub struct LastSave;
+
+#[derive(Debug, Clone)]
+pub struct BgRewriteAof;
+
+#[derive(Debug, Clone)]
+pub struct WaitAof {
+    pub numlocal: usize,
+    pub numreplicas: usize,
+    // in milliseconds, 0 to block forever
+    pub timeout: u64,
 }
 
-#[derive(Debug)]
-pub struct Get {
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    // NOSAVE or SAVE, saved as configured otherwise
+    pub save: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigGet {
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigSet {
+    pub pairs: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigRewrite;
+
+#[derive(Debug, Clone)]
+pub struct ConfigResetStat;
+
+#[derive(Debug, Clone)]
+pub struct LatencyLatest;
+
+#[derive(Debug, Clone)]
+pub struct LatencyHistory {
+    pub event: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LatencyReset {
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterInfo;
+
+#[derive(Debug, Clone)]
+pub struct ClusterMyId;
+
+#[derive(Debug, Clone)]
+pub struct ClusterSlots;
+
+#[derive(Debug, Clone)]
+pub struct ClusterShards;
+
+#[derive(Debug, Clone)]
+pub struct ClusterNodes;
+
+#[derive(Debug, Clone)]
+pub struct ClusterKeySlot {
     pub key: String,
 }
 
-#[derive(Debug)]
-pub struct Set {
+#[derive(Debug, Clone)]
+pub struct CommandGetKeys {
+    pub command: RespArray,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterSetSlot {
+    pub slot: u16,
+    pub state: SlotState,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterMeet {
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterForget {
+    pub id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Asking;
+
+#[derive(Debug, Clone)]
+pub struct SentinelGetMasterAddrByName {
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelMasters;
+
+#[derive(Debug, Clone)]
+pub struct SentinelMaster {
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelReplicas {
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelSentinels {
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelIsMasterDownByAddr {
+    pub host: String,
+    pub port: u16,
+    pub epoch: u64,
+    /// The sentinel asking for a vote, `*` to only ask whether the master is down.
+    pub runid: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelMonitor {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub quorum: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelRemove {
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelSet {
+    pub name: String,
+    pub options: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelFailover {
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SentinelMyId;
+
+#[derive(Debug, Clone)]
+pub struct AclSetUser {
+    pub username: String,
+    pub rules: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AclGetUser {
+    pub username: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AclDelUser {
+    pub usernames: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AclList;
+
+#[derive(Debug, Clone)]
+pub struct AclWhoAmI;
+
+#[derive(Debug, Clone)]
+pub struct AclSave;
+
+#[derive(Debug, Clone)]
+pub struct AclLoad;
+
+#[derive(Debug, Clone)]
+pub struct Info {
+    pub sections: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplConf {
+    pub options: Vec<ReplConfOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplConfOption {
+    ListeningPort(u16),
+    IpAddress(String),
+    Capa(String),
+    Ack(u64),
+    FAck(u64),
+    GetAck,
+}
+
+#[derive(Debug, Clone)]
+pub struct Wait {
+    pub numreplicas: usize,
+    // in milliseconds, 0 to block forever
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PSync {
+    pub replid: String,
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sync;
+
+#[derive(Debug, Clone)]
+pub struct ReplicaOf {
+    // host and port, None for NO ONE
+    pub master: Option<(String, u16)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expire {
     pub key: String,
-    pub value: RespFrame,
+    pub seconds: i64,
+    pub options: ExpireOptions,
 }
 
-#[derive(Debug)]
-pub struct HGet {
+#[derive(Debug, Clone)]
+pub struct PExpire {
     pub key: String,
-    pub field: String,
+    pub milliseconds: i64,
+    pub options: ExpireOptions,
 }
 
-#[derive(Debug)]
-pub struct HSet {
+#[derive(Debug, Clone)]
+pub struct ExpireAt {
     pub key: String,
-    pub field: String,
-    pub value: RespFrame,
+    pub timestamp: i64,
+    pub options: ExpireOptions,
 }
 
-#[derive(Debug)]
-pub struct HGetAll {
+#[derive(Debug, Clone)]
+pub struct PExpireAt {
+    pub key: String,
+    pub timestamp_ms: i64,
+    pub options: ExpireOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ttl {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PTtl {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Persist {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExpireTime {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PExpireTime {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Del {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keys {
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rename {
+    pub key: String,
+    pub new_key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameNx {
+    pub key: String,
+    pub new_key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dump {
+    pub key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Restore {
+    pub key: String,
+    pub payload: Vec<u8>,
+    pub options: RestoreOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct Migrate {
+    pub keys: Vec<String>,
+    pub options: MigrateOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sort {
     pub key: String,
+    pub options: SortOptions,
+    pub store: Option<String>,
+}
+
+impl Command {
+    /// Execute the command for a connection. Blocking commands park the connection until they
+    /// can be served, while `execute` always runs them without blocking.
+    pub async fn execute_blocking(
+        self,
+        backend: &Backend,
+        request: Option<RespArray>,
+    ) -> RespFrame {
+        match self {
+            Command::BLPop(cmd) => cmd.block(backend).await,
+            Command::BRPop(cmd) => cmd.block(backend).await,
+            Command::BLMove(cmd) => cmd.block(backend).await,
+            Command::BLMPop(cmd) => cmd.block(backend).await,
+            Command::BZPopMin(cmd) => cmd.block(backend).await,
+            Command::BZPopMax(cmd) => cmd.block(backend).await,
+            Command::XRead(cmd) => cmd.block(backend).await,
+            Command::XReadGroup(cmd) => cmd.block(backend).await,
+            Command::WaitAof(cmd) => cmd.block(backend).await,
+            Command::Wait(cmd) => cmd.block(backend).await,
+            // they run while a script holds the worker back
+            cmd @ (Command::ScriptKill(_) | Command::Shutdown(_)) => {
+                cmd.execute_unblocked(backend, request)
+            }
+            cmd => {
+                let worker_backend = backend.clone();
+                backend
+                    .run_on_worker(move || cmd.execute_unblocked(&worker_backend, request))
+                    .await
+            }
+        }
+    }
+
+    /// Execute the command as `execute_blocking` does, except that blocking commands reply
+    /// right away, as they do in a transaction.
+    pub fn execute_unblocked(self, backend: &Backend, request: Option<RespArray>) -> RespFrame {
+        let started = Instant::now();
+        let reply = match self {
+            // a script runs atomically, with the commands it calls
+            Command::Eval(cmd) => run_script(backend, || cmd.execute(backend)),
+            Command::EvalSha(cmd) => run_script(backend, || cmd.execute(backend)),
+            Command::FCall(cmd) => run_script(backend, || cmd.execute(backend)),
+            Command::FCallRo(cmd) => run_script(backend, || cmd.execute(backend)),
+            // nothing is written between the save and the load
+            Command::DebugReload(cmd) => backend.run_exclusive(|| cmd.execute(backend)),
+            // they run while a script holds every other command back
+            Command::ScriptKill(cmd) => cmd.execute(backend),
+            Command::Shutdown(cmd) => cmd.execute(backend),
+            cmd => backend
+                .run_shared(|| backend.run_logged(|| cmd.execute_propagating(backend, request))),
+        };
+        // what a blocking command spends waiting isn't latency, it's only sampled here
+        backend.latency_sample(LatencyEvent::Command, started.elapsed());
+        reply
+    }
+
+    /// Execute the command for a connection, given its subscriptions and transaction.
+    /// Subscribing commands reply a frame for each channel, every other command a single
+    /// frame. After MULTI, commands are queued until EXEC or DISCARD. request is the frame the
+    /// command was parsed from, propagated to the AOF and the replicas.
+    pub async fn execute_on_connection(
+        self,
+        backend: &Backend,
+        request: Option<RespArray>,
+        subscriptions: &mut Subscriptions,
+        transaction: &mut Transaction,
+    ) -> Vec<RespFrame> {
+        let unqueued = matches!(
+            self,
+            Command::Exec(_) | Command::Discard(_) | Command::Reset(_) | Command::Quit(_)
+        );
+        if transaction.is_active() && !unqueued {
+            return vec![transaction.queue(self, request)];
+        }
+        let replies = match self {
+            Command::Multi(cmd) => vec![cmd.apply(transaction)],
+            Command::Exec(cmd) => vec![cmd.apply(backend, subscriptions, transaction)],
+            Command::Discard(cmd) => vec![cmd.apply(backend, transaction)],
+            Command::Watch(cmd) => vec![cmd.apply(backend, transaction)],
+            Command::Unwatch(cmd) => vec![cmd.apply(backend, transaction)],
+            Command::Reset(cmd) => vec![cmd.apply(backend, subscriptions, transaction)],
+            Command::Subscribe(cmd) => cmd.apply(backend, subscriptions),
+            Command::Unsubscribe(cmd) => cmd.apply(backend, subscriptions),
+            Command::PSubscribe(cmd) => cmd.apply(backend, subscriptions),
+            Command::PUnsubscribe(cmd) => cmd.apply(backend, subscriptions),
+            Command::SSubscribe(cmd) => cmd.apply(backend, subscriptions),
+            Command::SUnsubscribe(cmd) => cmd.apply(backend, subscriptions),
+            Command::Hello(cmd) => vec![cmd.apply(backend, subscriptions.id())],
+            Command::Auth(cmd) => vec![cmd.apply(backend, subscriptions.id())],
+            Command::ReplConf(cmd) => cmd.apply(backend, subscriptions.id()),
+            cmd if cmd.is_about_connection() => {
+                vec![cmd.apply_to_connection(backend, subscriptions)]
+            }
+            cmd => vec![cmd.execute_blocking(backend, request).await],
+        };
+        backend.flush_propagated();
+        backend.sync_aof().await;
+        replies
+    }
+
+    /// Whether the command is about the state of the connection running it, and runs with
+    /// `apply_to_connection`, also when EXEC runs it.
+    pub fn is_about_connection(&self) -> bool {
+        matches!(
+            self,
+            Command::ClientId(_)
+                | Command::Ping(_)
+                | Command::AclWhoAmI(_)
+                | Command::ClientTracking(_)
+                | Command::ClientCaching(_)
+                | Command::ClientNoEvict(_)
+                | Command::Asking(_)
+        )
+    }
+
+    /// Run the command against the connection subscriptions is of, executing it as any other
+    /// if it's not about the connection.
+    pub fn apply_to_connection(
+        self,
+        backend: &Backend,
+        subscriptions: &Subscriptions,
+    ) -> RespFrame {
+        match self {
+            Command::ClientId(cmd) => cmd.apply(subscriptions.id()),
+            Command::Ping(cmd) => cmd.apply(backend, subscriptions.is_subscribed()),
+            Command::AclWhoAmI(cmd) => cmd.apply(backend, subscriptions.id()),
+            Command::ClientTracking(cmd) => cmd.apply(backend, subscriptions.id()),
+            Command::ClientCaching(cmd) => cmd.apply(backend, subscriptions.id()),
+            Command::ClientNoEvict(cmd) => cmd.apply(backend, subscriptions.id()),
+            Command::Asking(cmd) => cmd.apply(backend, subscriptions.id()),
+            cmd => cmd.execute(backend),
+        }
+    }
+
+    /// Whether a script may call the command with `redis.call`. Commands about the
+    /// connection, and those running other commands, can't be.
+    pub fn is_allowed_in_script(&self) -> bool {
+        match self {
+            Command::Custom(cmd) => !cmd.has_flag(CommandFlag::NoScript),
+            cmd => !matches!(
+                cmd,
+                Command::Hello(_)
+                    | Command::Auth(_)
+                    | Command::Quit(_)
+                    | Command::Subscribe(_)
+                    | Command::Unsubscribe(_)
+                    | Command::PSubscribe(_)
+                    | Command::PUnsubscribe(_)
+                    | Command::SSubscribe(_)
+                    | Command::SUnsubscribe(_)
+                    | Command::ClientId(_)
+                    | Command::ClientTracking(_)
+                    | Command::ClientCaching(_)
+                    | Command::ClientList(_)
+                    | Command::ClientNoEvict(_)
+                    | Command::Asking(_)
+                    | Command::Multi(_)
+                    | Command::Exec(_)
+                    | Command::Discard(_)
+                    | Command::Watch(_)
+                    | Command::Unwatch(_)
+                    | Command::Reset(_)
+                    | Command::Eval(_)
+                    | Command::EvalSha(_)
+                    | Command::ScriptLoad(_)
+                    | Command::ScriptExists(_)
+                    | Command::ScriptFlush(_)
+                    | Command::ScriptKill(_)
+                    | Command::FunctionLoad(_)
+                    | Command::FunctionDelete(_)
+                    | Command::FunctionFlush(_)
+                    | Command::FunctionList(_)
+                    | Command::FunctionDump(_)
+                    | Command::FunctionRestore(_)
+                    | Command::FCall(_)
+                    | Command::FCallRo(_)
+                    | Command::Save(_)
+                    | Command::BgSave(_)
+                    | Command::BgRewriteAof(_)
+                    | Command::WaitAof(_)
+                    | Command::Shutdown(_)
+                    | Command::Migrate(_)
+                    | Command::ConfigGet(_)
+                    | Command::ConfigSet(_)
+                    | Command::ConfigRewrite(_)
+                    | Command::ConfigResetStat(_)
+                    | Command::LatencyLatest(_)
+                    | Command::LatencyHistory(_)
+                    | Command::LatencyReset(_)
+                    | Command::AclSetUser(_)
+                    | Command::AclGetUser(_)
+                    | Command::AclDelUser(_)
+                    | Command::AclList(_)
+                    | Command::AclWhoAmI(_)
+                    | Command::AclSave(_)
+                    | Command::AclLoad(_)
+                    | Command::ReplConf(_)
+                    | Command::Wait(_)
+                    | Command::PSync(_)
+                    | Command::Sync(_)
+                    | Command::ReplicaOf(_)
+                    | Command::DebugReload(_)
+                    | Command::SentinelGetMasterAddrByName(_)
+                    | Command::SentinelMasters(_)
+                    | Command::SentinelMaster(_)
+                    | Command::SentinelReplicas(_)
+                    | Command::SentinelSentinels(_)
+                    | Command::SentinelIsMasterDownByAddr(_)
+                    | Command::SentinelMonitor(_)
+                    | Command::SentinelRemove(_)
+                    | Command::SentinelSet(_)
+                    | Command::SentinelFailover(_)
+                    | Command::SentinelMyId(_)
+            ),
+        }
+    }
+
+    /// Whether the command only reads the keyspace, so read-only scripts can call it.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Command::Sort(sort) => sort.store.is_none(),
+            Command::Custom(cmd) => cmd.has_flag(CommandFlag::ReadOnly),
+            cmd => matches!(
+                cmd,
+                Command::Get(_)
+                    | Command::MGet(_)
+                    | Command::Lcs(_)
+                    | Command::GetBit(_)
+                    | Command::BitCount(_)
+                    | Command::BitPos(_)
+                    | Command::Strlen(_)
+                    | Command::HGet(_)
+                    | Command::HGetAll(_)
+                    | Command::HExists(_)
+                    | Command::HLen(_)
+                    | Command::HKeys(_)
+                    | Command::HVals(_)
+                    | Command::HStrLen(_)
+                    | Command::HMGet(_)
+                    | Command::HRandField(_)
+                    | Command::HScan(_)
+                    | Command::HTtl(_)
+                    | Command::HPTtl(_)
+                    | Command::LLen(_)
+                    | Command::LRange(_)
+                    | Command::LIndex(_)
+                    | Command::LPos(_)
+                    | Command::SMembers(_)
+                    | Command::SCard(_)
+                    | Command::SIsMember(_)
+                    | Command::SMIsMember(_)
+                    | Command::SInter(_)
+                    | Command::SUnion(_)
+                    | Command::SDiff(_)
+                    | Command::SRandMember(_)
+                    | Command::SScan(_)
+                    | Command::ZScore(_)
+                    | Command::ZCard(_)
+                    | Command::ZRange(_)
+                    | Command::ZRevRange(_)
+                    | Command::ZRangeByScore(_)
+                    | Command::ZRevRangeByScore(_)
+                    | Command::ZRangeByLex(_)
+                    | Command::ZRevRangeByLex(_)
+                    | Command::ZLexCount(_)
+                    | Command::ZRank(_)
+                    | Command::ZRevRank(_)
+                    | Command::ZCount(_)
+                    | Command::ZUnion(_)
+                    | Command::ZInter(_)
+                    | Command::ZDiff(_)
+                    | Command::ZRandMember(_)
+                    | Command::ZMScore(_)
+                    | Command::ZScan(_)
+                    | Command::GeoPos(_)
+                    | Command::GeoDist(_)
+                    | Command::GeoSearch(_)
+                    | Command::PfCount(_)
+                    | Command::XLen(_)
+                    | Command::XRange(_)
+                    | Command::XRevRange(_)
+                    | Command::XRead(_)
+                    | Command::XPending(_)
+                    | Command::XInfoStream(_)
+                    | Command::XInfoGroups(_)
+                    | Command::XInfoConsumers(_)
+                    | Command::Publish(_)
+                    | Command::SPublish(_)
+                    | Command::DebugDigest(_)
+                    | Command::Dump(_)
+                    | Command::ObjectEncoding(_)
+                    | Command::ObjectRefCount(_)
+                    | Command::ObjectIdleTime(_)
+                    | Command::ObjectFreq(_)
+                    | Command::MemoryUsage(_)
+                    | Command::MemoryStats(_)
+                    | Command::MemoryDoctor(_)
+                    | Command::MemoryPurge(_)
+                    | Command::Ttl(_)
+                    | Command::PTtl(_)
+                    | Command::ExpireTime(_)
+                    | Command::PExpireTime(_)
+                    | Command::Keys(_)
+                    | Command::Scan(_)
+            ),
+        }
+    }
+
+    /// Whether a connection may run the command while a script is busy: only to stop it.
+    pub fn is_allowed_busy(&self) -> bool {
+        matches!(
+            self,
+            Command::ScriptKill(_) | Command::Shutdown(Shutdown { save: Some(false) })
+        )
+    }
+
+    /// Whether a connection in subscriber mode may run the command.
+    pub fn is_allowed_subscribed(&self) -> bool {
+        matches!(
+            self,
+            Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::PSubscribe(_)
+                | Command::PUnsubscribe(_)
+                | Command::SSubscribe(_)
+                | Command::SUnsubscribe(_)
+                | Command::Ping(_)
+                | Command::Reset(_)
+        )
+    }
+
+    /// Whether a connection may run the command before it authenticates, when a password
+    /// is required.
+    pub fn is_allowed_unauthenticated(&self) -> bool {
+        match self {
+            Command::Hello(hello) => hello.auth.is_some(),
+            cmd => matches!(cmd, Command::Auth(_) | Command::Quit(_)),
+        }
+    }
 }
 
 impl TryFrom<RespArray> for Command {
@@ -72,13 +2245,9 @@ impl TryFrom<RespArray> for Command {
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
         match value.first() {
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
-                b"get" => Ok(Get::try_from(value)?.into()),
-                b"set" => Ok(Set::try_from(value)?.into()),
-                b"hget" => Ok(HGet::try_from(value)?.into()),
-                b"hset" => Ok(HSet::try_from(value)?.into()),
-                b"hgetall" => Ok(HGetAll::try_from(value)?.into()),
-                _ => Err(CommandError::InvalidCommand(format!(
+            Some(RespFrame::BulkString(ref cmd)) => match built_in(&cmd.to_ascii_lowercase()) {
+                Some(parse) => parse(value),
+                None => Err(CommandError::InvalidCommand(format!(
                     "Invalid command: {}",
                     String::from_utf8_lossy(cmd.as_ref())
                 ))),
@@ -90,6 +2259,357 @@ impl TryFrom<RespArray> for Command {
     }
 }
 
+type Parse = fn(RespArray) -> Result<Command, CommandError>;
+
+// the parser of the built in command called name, lowercased, the table of the dispatcher
+pub(crate) fn built_in(name: &[u8]) -> Option<Parse> {
+    let parse: Parse = match name {
+        b"hello" => |value| Ok(Hello::try_from(value)?.into()),
+        b"auth" => |value| Ok(Auth::try_from(value)?.into()),
+        b"quit" => |value| Ok(Quit::try_from(value)?.into()),
+        b"ping" => |value| Ok(Ping::try_from(value)?.into()),
+        b"select" => |value| Ok(Select::try_from(value)?.into()),
+        b"get" => |value| Ok(Get::try_from(value)?.into()),
+        b"set" => |value| Ok(Set::try_from(value)?.into()),
+        b"setnx" => |value| Ok(SetNx::try_from(value)?.into()),
+        b"setex" => |value| Ok(SetEx::try_from(value)?.into()),
+        b"psetex" => |value| Ok(PSetEx::try_from(value)?.into()),
+        b"getset" => |value| Ok(GetSet::try_from(value)?.into()),
+        b"getdel" => |value| Ok(GetDel::try_from(value)?.into()),
+        b"getex" => |value| Ok(GetEx::try_from(value)?.into()),
+        b"incr" => |value| Ok(Incr::try_from(value)?.into()),
+        b"decr" => |value| Ok(Decr::try_from(value)?.into()),
+        b"incrby" => |value| Ok(IncrBy::try_from(value)?.into()),
+        b"decrby" => |value| Ok(DecrBy::try_from(value)?.into()),
+        b"incrbyfloat" => |value| Ok(IncrByFloat::try_from(value)?.into()),
+        b"mget" => |value| Ok(MGet::try_from(value)?.into()),
+        b"mset" => |value| Ok(MSet::try_from(value)?.into()),
+        b"msetnx" => |value| Ok(MSetNx::try_from(value)?.into()),
+        b"append" => |value| Ok(Append::try_from(value)?.into()),
+        b"lcs" => |value| Ok(Lcs::try_from(value)?.into()),
+        b"setbit" => |value| Ok(SetBit::try_from(value)?.into()),
+        b"getbit" => |value| Ok(GetBit::try_from(value)?.into()),
+        b"bitcount" => |value| Ok(BitCount::try_from(value)?.into()),
+        b"bitpos" => |value| Ok(BitPos::try_from(value)?.into()),
+        b"bitfield" => |value| Ok(BitField::try_from(value)?.into()),
+        b"strlen" => |value| Ok(Strlen::try_from(value)?.into()),
+        b"hget" => |value| Ok(HGet::try_from(value)?.into()),
+        b"hset" => |value| Ok(HSet::try_from(value)?.into()),
+        b"hgetall" => |value| Ok(HGetAll::try_from(value)?.into()),
+        b"hdel" => |value| Ok(HDel::try_from(value)?.into()),
+        b"hexists" => |value| Ok(HExists::try_from(value)?.into()),
+        b"hlen" => |value| Ok(HLen::try_from(value)?.into()),
+        b"hkeys" => |value| Ok(HKeys::try_from(value)?.into()),
+        b"hvals" => |value| Ok(HVals::try_from(value)?.into()),
+        b"hstrlen" => |value| Ok(HStrLen::try_from(value)?.into()),
+        b"hmget" => |value| Ok(HMGet::try_from(value)?.into()),
+        b"hincrby" => |value| Ok(HIncrBy::try_from(value)?.into()),
+        b"hincrbyfloat" => |value| Ok(HIncrByFloat::try_from(value)?.into()),
+        b"hrandfield" => |value| Ok(HRandField::try_from(value)?.into()),
+        b"hscan" => |value| Ok(HScan::try_from(value)?.into()),
+        b"hexpire" => |value| Ok(HExpire::try_from(value)?.into()),
+        b"hpexpire" => |value| Ok(HPExpire::try_from(value)?.into()),
+        b"hexpireat" => |value| Ok(HExpireAt::try_from(value)?.into()),
+        b"hpexpireat" => |value| Ok(HPExpireAt::try_from(value)?.into()),
+        b"httl" => |value| Ok(HTtl::try_from(value)?.into()),
+        b"hpttl" => |value| Ok(HPTtl::try_from(value)?.into()),
+        b"hpersist" => |value| Ok(HPersist::try_from(value)?.into()),
+        b"lpush" => |value| Ok(LPush::try_from(value)?.into()),
+        b"rpush" => |value| Ok(RPush::try_from(value)?.into()),
+        b"lpop" => |value| Ok(LPop::try_from(value)?.into()),
+        b"rpop" => |value| Ok(RPop::try_from(value)?.into()),
+        b"llen" => |value| Ok(LLen::try_from(value)?.into()),
+        b"lrange" => |value| Ok(LRange::try_from(value)?.into()),
+        b"lindex" => |value| Ok(LIndex::try_from(value)?.into()),
+        b"lset" => |value| Ok(LSet::try_from(value)?.into()),
+        b"linsert" => |value| Ok(LInsert::try_from(value)?.into()),
+        b"lrem" => |value| Ok(LRem::try_from(value)?.into()),
+        b"ltrim" => |value| Ok(LTrim::try_from(value)?.into()),
+        b"lpos" => |value| Ok(LPos::try_from(value)?.into()),
+        b"blpop" => |value| Ok(BLPop::try_from(value)?.into()),
+        b"brpop" => |value| Ok(BRPop::try_from(value)?.into()),
+        b"lmove" => |value| Ok(LMove::try_from(value)?.into()),
+        b"rpoplpush" => |value| Ok(RPopLPush::try_from(value)?.into()),
+        b"blmove" => |value| Ok(BLMove::try_from(value)?.into()),
+        b"lmpop" => |value| Ok(LMPop::try_from(value)?.into()),
+        b"blmpop" => |value| Ok(BLMPop::try_from(value)?.into()),
+        b"sadd" => |value| Ok(SAdd::try_from(value)?.into()),
+        b"srem" => |value| Ok(SRem::try_from(value)?.into()),
+        b"smembers" => |value| Ok(SMembers::try_from(value)?.into()),
+        b"scard" => |value| Ok(SCard::try_from(value)?.into()),
+        b"sismember" => |value| Ok(SIsMember::try_from(value)?.into()),
+        b"smismember" => |value| Ok(SMIsMember::try_from(value)?.into()),
+        b"sinter" => |value| Ok(SInter::try_from(value)?.into()),
+        b"sunion" => |value| Ok(SUnion::try_from(value)?.into()),
+        b"sdiff" => |value| Ok(SDiff::try_from(value)?.into()),
+        b"sinterstore" => |value| Ok(SInterStore::try_from(value)?.into()),
+        b"sunionstore" => |value| Ok(SUnionStore::try_from(value)?.into()),
+        b"sdiffstore" => |value| Ok(SDiffStore::try_from(value)?.into()),
+        b"spop" => |value| Ok(SPop::try_from(value)?.into()),
+        b"srandmember" => |value| Ok(SRandMember::try_from(value)?.into()),
+        b"smove" => |value| Ok(SMove::try_from(value)?.into()),
+        b"sscan" => |value| Ok(SScan::try_from(value)?.into()),
+        b"zadd" => |value| Ok(ZAdd::try_from(value)?.into()),
+        b"zrem" => |value| Ok(ZRem::try_from(value)?.into()),
+        b"zscore" => |value| Ok(ZScore::try_from(value)?.into()),
+        b"zcard" => |value| Ok(ZCard::try_from(value)?.into()),
+        b"zrange" => |value| Ok(ZRange::try_from(value)?.into()),
+        b"zrevrange" => |value| Ok(ZRevRange::try_from(value)?.into()),
+        b"zrangebyscore" => |value| Ok(ZRangeByScore::try_from(value)?.into()),
+        b"zrevrangebyscore" => |value| Ok(ZRevRangeByScore::try_from(value)?.into()),
+        b"zrangebylex" => |value| Ok(ZRangeByLex::try_from(value)?.into()),
+        b"zrevrangebylex" => |value| Ok(ZRevRangeByLex::try_from(value)?.into()),
+        b"zlexcount" => |value| Ok(ZLexCount::try_from(value)?.into()),
+        b"zincrby" => |value| Ok(ZIncrBy::try_from(value)?.into()),
+        b"zrank" => |value| Ok(ZRank::try_from(value)?.into()),
+        b"zrevrank" => |value| Ok(ZRevRank::try_from(value)?.into()),
+        b"zcount" => |value| Ok(ZCount::try_from(value)?.into()),
+        b"zpopmin" => |value| Ok(ZPopMin::try_from(value)?.into()),
+        b"zpopmax" => |value| Ok(ZPopMax::try_from(value)?.into()),
+        b"bzpopmin" => |value| Ok(BZPopMin::try_from(value)?.into()),
+        b"bzpopmax" => |value| Ok(BZPopMax::try_from(value)?.into()),
+        b"zunion" => |value| Ok(ZUnion::try_from(value)?.into()),
+        b"zinter" => |value| Ok(ZInter::try_from(value)?.into()),
+        b"zdiff" => |value| Ok(ZDiff::try_from(value)?.into()),
+        b"zunionstore" => |value| Ok(ZUnionStore::try_from(value)?.into()),
+        b"zinterstore" => |value| Ok(ZInterStore::try_from(value)?.into()),
+        b"zdiffstore" => |value| Ok(ZDiffStore::try_from(value)?.into()),
+        b"zremrangebyrank" => |value| Ok(ZRemRangeByRank::try_from(value)?.into()),
+        b"zremrangebyscore" => |value| Ok(ZRemRangeByScore::try_from(value)?.into()),
+        b"zremrangebylex" => |value| Ok(ZRemRangeByLex::try_from(value)?.into()),
+        b"zrandmember" => |value| Ok(ZRandMember::try_from(value)?.into()),
+        b"zmscore" => |value| Ok(ZMScore::try_from(value)?.into()),
+        b"zscan" => |value| Ok(ZScan::try_from(value)?.into()),
+        b"geoadd" => |value| Ok(GeoAdd::try_from(value)?.into()),
+        b"geopos" => |value| Ok(GeoPos::try_from(value)?.into()),
+        b"geodist" => |value| Ok(GeoDist::try_from(value)?.into()),
+        b"geosearch" => |value| Ok(GeoSearch::try_from(value)?.into()),
+        b"pfadd" => |value| Ok(PfAdd::try_from(value)?.into()),
+        b"pfcount" => |value| Ok(PfCount::try_from(value)?.into()),
+        b"pfmerge" => |value| Ok(PfMerge::try_from(value)?.into()),
+        b"xadd" => |value| Ok(XAdd::try_from(value)?.into()),
+        b"xlen" => |value| Ok(XLen::try_from(value)?.into()),
+        b"xrange" => |value| Ok(XRange::try_from(value)?.into()),
+        b"xrevrange" => |value| Ok(XRevRange::try_from(value)?.into()),
+        b"xread" => |value| Ok(XRead::try_from(value)?.into()),
+        b"xgroup" => |value| match subcommand(&value).as_deref() {
+            Some(b"create") => Ok(XGroupCreate::try_from(value)?.into()),
+            Some(b"destroy") => Ok(XGroupDestroy::try_from(value)?.into()),
+            Some(b"setid") => Ok(XGroupSetId::try_from(value)?.into()),
+            Some(b"createconsumer") => Ok(XGroupCreateConsumer::try_from(value)?.into()),
+            Some(b"delconsumer") => Ok(XGroupDelConsumer::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for xgroup: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"xsetid" => |value| Ok(XSetId::try_from(value)?.into()),
+        b"xinfo" => |value| match subcommand(&value).as_deref() {
+            Some(b"stream") => Ok(XInfoStream::try_from(value)?.into()),
+            Some(b"groups") => Ok(XInfoGroups::try_from(value)?.into()),
+            Some(b"consumers") => Ok(XInfoConsumers::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for xinfo: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"xreadgroup" => |value| Ok(XReadGroup::try_from(value)?.into()),
+        b"xack" => |value| Ok(XAck::try_from(value)?.into()),
+        b"xpending" => |value| Ok(XPending::try_from(value)?.into()),
+        b"xclaim" => |value| Ok(XClaim::try_from(value)?.into()),
+        b"xautoclaim" => |value| Ok(XAutoClaim::try_from(value)?.into()),
+        b"xtrim" => |value| Ok(XTrim::try_from(value)?.into()),
+        b"xdel" => |value| Ok(XDel::try_from(value)?.into()),
+        b"subscribe" => |value| Ok(Subscribe::try_from(value)?.into()),
+        b"unsubscribe" => |value| Ok(Unsubscribe::try_from(value)?.into()),
+        b"psubscribe" => |value| Ok(PSubscribe::try_from(value)?.into()),
+        b"punsubscribe" => |value| Ok(PUnsubscribe::try_from(value)?.into()),
+        b"publish" => |value| Ok(Publish::try_from(value)?.into()),
+        b"ssubscribe" => |value| Ok(SSubscribe::try_from(value)?.into()),
+        b"sunsubscribe" => |value| Ok(SUnsubscribe::try_from(value)?.into()),
+        b"spublish" => |value| Ok(SPublish::try_from(value)?.into()),
+        b"client" => |value| match subcommand(&value).as_deref() {
+            Some(b"id") => Ok(ClientId::try_from(value)?.into()),
+            Some(b"tracking") => Ok(ClientTracking::try_from(value)?.into()),
+            Some(b"caching") => Ok(ClientCaching::try_from(value)?.into()),
+            Some(b"list") => Ok(ClientList::try_from(value)?.into()),
+            Some(b"no-evict") => Ok(ClientNoEvict::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for client: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"multi" => |value| Ok(Multi::try_from(value)?.into()),
+        b"exec" => |value| Ok(Exec::try_from(value)?.into()),
+        b"discard" => |value| Ok(Discard::try_from(value)?.into()),
+        b"watch" => |value| Ok(Watch::try_from(value)?.into()),
+        b"unwatch" => |value| Ok(Unwatch::try_from(value)?.into()),
+        b"reset" => |value| Ok(Reset::try_from(value)?.into()),
+        b"eval" => |value| Ok(Eval::try_from(value)?.into()),
+        b"evalsha" => |value| Ok(EvalSha::try_from(value)?.into()),
+        b"script" => |value| match subcommand(&value).as_deref() {
+            Some(b"load") => Ok(ScriptLoad::try_from(value)?.into()),
+            Some(b"exists") => Ok(ScriptExists::try_from(value)?.into()),
+            Some(b"flush") => Ok(ScriptFlush::try_from(value)?.into()),
+            Some(b"kill") => Ok(ScriptKill::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for script: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"function" => |value| match subcommand(&value).as_deref() {
+            Some(b"load") => Ok(FunctionLoad::try_from(value)?.into()),
+            Some(b"delete") => Ok(FunctionDelete::try_from(value)?.into()),
+            Some(b"flush") => Ok(FunctionFlush::try_from(value)?.into()),
+            Some(b"list") => Ok(FunctionList::try_from(value)?.into()),
+            Some(b"dump") => Ok(FunctionDump::try_from(value)?.into()),
+            Some(b"restore") => Ok(FunctionRestore::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for function: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"fcall" => |value| Ok(FCall::try_from(value)?.into()),
+        b"fcall_ro" => |value| Ok(FCallRo::try_from(value)?.into()),
+        b"expire" => |value| Ok(Expire::try_from(value)?.into()),
+        b"pexpire" => |value| Ok(PExpire::try_from(value)?.into()),
+        b"expireat" => |value| Ok(ExpireAt::try_from(value)?.into()),
+        b"pexpireat" => |value| Ok(PExpireAt::try_from(value)?.into()),
+        b"ttl" => |value| Ok(Ttl::try_from(value)?.into()),
+        b"pttl" => |value| Ok(PTtl::try_from(value)?.into()),
+        b"persist" => |value| Ok(Persist::try_from(value)?.into()),
+        b"del" => |value| Ok(Del::try_from(value)?.into()),
+        b"keys" => |value| Ok(Keys::try_from(value)?.into()),
+        b"scan" => |value| Ok(Scan::try_from(value)?.into()),
+        b"rename" => |value| Ok(Rename::try_from(value)?.into()),
+        b"renamenx" => |value| Ok(RenameNx::try_from(value)?.into()),
+        b"dump" => |value| Ok(Dump::try_from(value)?.into()),
+        b"restore" => |value| Ok(Restore::try_from(value)?.into()),
+        b"migrate" => |value| Ok(Migrate::try_from(value)?.into()),
+        b"expiretime" => |value| Ok(ExpireTime::try_from(value)?.into()),
+        b"pexpiretime" => |value| Ok(PExpireTime::try_from(value)?.into()),
+        b"sort" => |value| Ok(Sort::try_from(value)?.into()),
+        b"debug" => |value| match subcommand(&value).as_deref() {
+            Some(b"digest") => Ok(DebugDigest::try_from(value)?.into()),
+            Some(b"reload") => Ok(DebugReload::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for debug: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"save" => |value| Ok(Save::try_from(value)?.into()),
+        b"bgsave" => |value| Ok(BgSave::try_from(value)?.into()),
+        b"lastsave" => |value| Ok(LastSave::try_from(value)?.into()),
+        b"bgrewriteaof" => |value| Ok(BgRewriteAof::try_from(value)?.into()),
+        b"waitaof" => |value| Ok(WaitAof::try_from(value)?.into()),
+        b"shutdown" => |value| Ok(Shutdown::try_from(value)?.into()),
+        b"info" => |value| Ok(Info::try_from(value)?.into()),
+        b"replconf" => |value| Ok(ReplConf::try_from(value)?.into()),
+        b"wait" => |value| Ok(Wait::try_from(value)?.into()),
+        b"psync" => |value| Ok(PSync::try_from(value)?.into()),
+        b"sync" => |value| Ok(Sync::try_from(value)?.into()),
+        b"replicaof" | b"slaveof" => |value| Ok(ReplicaOf::try_from(value)?.into()),
+        b"config" => |value| match subcommand(&value).as_deref() {
+            Some(b"get") => Ok(ConfigGet::try_from(value)?.into()),
+            Some(b"set") => Ok(ConfigSet::try_from(value)?.into()),
+            Some(b"rewrite") => Ok(ConfigRewrite::try_from(value)?.into()),
+            Some(b"resetstat") => Ok(ConfigResetStat::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for config: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"latency" => |value| match subcommand(&value).as_deref() {
+            Some(b"latest") => Ok(LatencyLatest::try_from(value)?.into()),
+            Some(b"history") => Ok(LatencyHistory::try_from(value)?.into()),
+            Some(b"reset") => Ok(LatencyReset::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for latency: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"command" => |value| match subcommand(&value).as_deref() {
+            Some(b"getkeys") => Ok(CommandGetKeys::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for command: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"asking" => |value| Ok(Asking::try_from(value)?.into()),
+        b"cluster" => |value| match subcommand(&value).as_deref() {
+            Some(b"info") => Ok(ClusterInfo::try_from(value)?.into()),
+            Some(b"myid") => Ok(ClusterMyId::try_from(value)?.into()),
+            Some(b"slots") => Ok(ClusterSlots::try_from(value)?.into()),
+            Some(b"shards") => Ok(ClusterShards::try_from(value)?.into()),
+            Some(b"nodes") => Ok(ClusterNodes::try_from(value)?.into()),
+            Some(b"keyslot") => Ok(ClusterKeySlot::try_from(value)?.into()),
+            Some(b"setslot") => Ok(ClusterSetSlot::try_from(value)?.into()),
+            Some(b"meet") => Ok(ClusterMeet::try_from(value)?.into()),
+            Some(b"forget") => Ok(ClusterForget::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for cluster: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"acl" => |value| match subcommand(&value).as_deref() {
+            Some(b"setuser") => Ok(AclSetUser::try_from(value)?.into()),
+            Some(b"getuser") => Ok(AclGetUser::try_from(value)?.into()),
+            Some(b"deluser") => Ok(AclDelUser::try_from(value)?.into()),
+            Some(b"list") => Ok(AclList::try_from(value)?.into()),
+            Some(b"whoami") => Ok(AclWhoAmI::try_from(value)?.into()),
+            Some(b"save") => Ok(AclSave::try_from(value)?.into()),
+            Some(b"load") => Ok(AclLoad::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for acl: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"object" => |value| match subcommand(&value).as_deref() {
+            Some(b"encoding") => Ok(ObjectEncoding::try_from(value)?.into()),
+            Some(b"refcount") => Ok(ObjectRefCount::try_from(value)?.into()),
+            Some(b"idletime") => Ok(ObjectIdleTime::try_from(value)?.into()),
+            Some(b"freq") => Ok(ObjectFreq::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for object: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"memory" => |value| match subcommand(&value).as_deref() {
+            Some(b"usage") => Ok(MemoryUsage::try_from(value)?.into()),
+            Some(b"stats") => Ok(MemoryStats::try_from(value)?.into()),
+            Some(b"doctor") => Ok(MemoryDoctor::try_from(value)?.into()),
+            Some(b"purge") => Ok(MemoryPurge::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for memory: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        b"sentinel" => |value| match subcommand(&value).as_deref() {
+            Some(b"get-master-addr-by-name") => {
+                Ok(SentinelGetMasterAddrByName::try_from(value)?.into())
+            }
+            Some(b"masters") => Ok(SentinelMasters::try_from(value)?.into()),
+            Some(b"master") => Ok(SentinelMaster::try_from(value)?.into()),
+            Some(b"replicas" | b"slaves") => Ok(SentinelReplicas::try_from(value)?.into()),
+            Some(b"sentinels") => Ok(SentinelSentinels::try_from(value)?.into()),
+            Some(b"is-master-down-by-addr") => {
+                Ok(SentinelIsMasterDownByAddr::try_from(value)?.into())
+            }
+            Some(b"monitor") => Ok(SentinelMonitor::try_from(value)?.into()),
+            Some(b"remove") => Ok(SentinelRemove::try_from(value)?.into()),
+            Some(b"set") => Ok(SentinelSet::try_from(value)?.into()),
+            Some(b"failover") => Ok(SentinelFailover::try_from(value)?.into()),
+            Some(b"myid") => Ok(SentinelMyId::try_from(value)?.into()),
+            _ => Err(CommandError::InvalidCommand(format!(
+                "Invalid command: unknown subcommand for sentinel: {}",
+                subcommand_name(&value)
+            ))),
+        },
+        _ => return None,
+    };
+    Some(parse)
+}
+
 fn validate_command(
     value: &RespArray,
     names: &[&'static str],
@@ -103,6 +2623,26 @@ fn validate_command(
         )));
     }
 
+    validate_names(value, names)
+}
+
+fn validate_variadic_command(
+    value: &RespArray,
+    names: &[&'static str],
+    min_args: usize,
+) -> Result<(), CommandError> {
+    if value.len() < min_args + names.len() {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command must have at least {} arguments",
+            names.join(" "),
+            min_args
+        )));
+    }
+
+    validate_names(value, names)
+}
+
+fn validate_names(value: &RespArray, names: &[&'static str]) -> Result<(), CommandError> {
     for (i, name) in names.iter().enumerate() {
         match value[i] {
             RespFrame::BulkString(ref cmd) => {
@@ -124,16 +2664,290 @@ fn validate_command(
     Ok(())
 }
 
+/// Give the command in value the name it has among the commands when rename-command renamed
+/// it, so it's known, propagated and checked by that name. One called by a name it no longer
+/// has, or disabled, is unknown, as are those of a sentinel to a server not in sentinel mode
+/// and the others to one that is.
+pub fn resolve_command(backend: &Backend, mut value: RespArray) -> Result<RespArray, CommandError> {
+    let sentinel = backend.is_sentinel();
+    let called = match value.first() {
+        Some(RespFrame::BulkString(name)) if sentinel || backend.has_renamed_commands() => {
+            String::from_utf8_lossy(name).to_lowercase()
+        }
+        Some(RespFrame::BulkString(name)) if name.eq_ignore_ascii_case(b"sentinel") => {
+            return Err(CommandError::InvalidCommand(format!(
+                "Invalid command: {}",
+                String::from_utf8_lossy(name)
+            )))
+        }
+        _ => return Ok(value),
+    };
+    let allowed = |name: &str| match sentinel {
+        true => SENTINEL_MODE_COMMANDS.contains(&name),
+        false => name != "sentinel",
+    };
+    let name = match backend.resolve_command_name(&called) {
+        Some(name) if allowed(&name) => name,
+        _ => {
+            return Err(CommandError::InvalidCommand(format!(
+                "Invalid command: {}",
+                called
+            )))
+        }
+    };
+    if name != called {
+        value.0[0] = BulkString::new(name).into();
+    }
+    Ok(value)
+}
+
+// the lowercased second element of a command, e.g. `encoding` for `OBJECT ENCODING key`
+fn subcommand(value: &RespArray) -> Option<Vec<u8>> {
+    match value.get(1) {
+        Some(RespFrame::BulkString(sub)) => Some(sub.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+fn subcommand_name(value: &RespArray) -> String {
+    subcommand(value)
+        .map(|sub| String::from_utf8_lossy(&sub).into_owned())
+        .unwrap_or_default()
+}
+
+fn extract_string(frame: Option<RespFrame>, name: &str) -> Result<String, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(s)) => Ok(String::from_utf8(s.0.into())?),
+        _ => Err(CommandError::InvalidArgument(format!("Invalid {}", name))),
+    }
+}
+
+fn extract_cursor(frame: Option<RespFrame>) -> Result<u64, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(s)) => std::str::from_utf8(&s)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| CommandError::InvalidArgument("invalid cursor".to_string())),
+        Some(RespFrame::Integer(n)) if n >= 0 => Ok(n as u64),
+        _ => Err(CommandError::InvalidArgument("invalid cursor".to_string())),
+    }
+}
+
+// run a script with no other command running, propagating the writes it calls at once. It
+// holds its thread for as long as it runs, so on a multi-threaded runtime the other tasks
+// of the thread are handed to another one, to be told it's busy or to kill it
+fn run_script(backend: &Backend, f: impl FnOnce() -> RespFrame) -> RespFrame {
+    hold_thread(|| backend.run_exclusive(|| backend.run_logged(f)))
+}
+
+// run f, which holds its thread for as long as it runs, handing the other tasks of the thread
+// to another one on a multi-threaded runtime
+fn hold_thread<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+// wait up to timeout seconds for up to count elements, forever if it's 0
+async fn bpop_wait(
+    backend: &Backend,
+    keys: &[String],
+    from: PopFrom,
+    count: usize,
+    timeout: f64,
+) -> Result<Option<Popped>, BackendError> {
+    let mut blocked = match backend.bpop(keys, from, count)? {
+        BlockingPop::Ready(popped) => return Ok(Some(popped)),
+        BlockingPop::Blocked(blocked) => blocked,
+    };
+
+    let popped = if timeout == 0.0 {
+        blocked.wait().await
+    } else {
+        tokio::time::timeout(Duration::from_secs_f64(timeout), blocked.wait())
+            .await
+            .unwrap_or_default()
+    };
+    // served right as the timeout elapsed
+    let late = backend.unblock(blocked);
+    Ok(popped.or(late))
+}
+
+// wait up to timeout milliseconds for acked to hold, forever if it's 0
+async fn wait_acks(backend: &Backend, timeout: u64, acked: impl Fn() -> bool) {
+    let acks = backend.wait_acks(acked);
+    match timeout {
+        0 => acks.await,
+        timeout => {
+            let _ = tokio::time::timeout(Duration::from_millis(timeout), acks).await;
+        }
+    }
+}
+
+fn extract_keys_and_timeout(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(Vec<String>, f64), CommandError> {
+    validate_variadic_command(&value, &[name], 2)?;
+
+    let mut args = extract_args(value, 1)?;
+    let timeout = extract_timeout(args.pop())?;
+    let keys = args
+        .into_iter()
+        .map(|key| extract_string(Some(key), "key"))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((keys, timeout))
+}
+
+// a timeout in seconds, 0 to block forever
+fn extract_timeout(frame: Option<RespFrame>) -> Result<f64, CommandError> {
+    match extract_float(frame) {
+        Ok(timeout) if timeout < 0.0 => Err(CommandError::InvalidArgument(
+            "timeout is negative".to_string(),
+        )),
+        Ok(timeout) if timeout.is_finite() => Ok(timeout),
+        _ => Err(CommandError::InvalidArgument(
+            "timeout is not a float or out of range".to_string(),
+        )),
+    }
+}
+
+// a key and an optional non-negative count, as in LPOP and SPOP
+fn extract_key_and_count(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Option<usize>), CommandError> {
+    validate_variadic_command(&value, &[name], 1)?;
+    if value.len() > 3 {
+        return Err(CommandError::InvalidArgument("syntax error".to_string()));
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let count = match args.next() {
+        None => None,
+        Some(count) => match extract_int(Some(count))? {
+            n if n >= 0 => Some(n as usize),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "value is out of range, must be positive".to_string(),
+                ))
+            }
+        },
+    };
+    Ok((key, count))
+}
+
+// numkeys followed by that many keys, as in LMPOP
+fn extract_numkeys(
+    args: &mut impl Iterator<Item = RespFrame>,
+) -> Result<Vec<String>, CommandError> {
+    let numkeys = extract_int(args.next())?;
+    if numkeys <= 0 {
+        return Err(CommandError::InvalidArgument(
+            "numkeys should be greater than 0".to_string(),
+        ));
+    }
+    (0..numkeys)
+        .map(|_| match args.next() {
+            Some(key) => extract_string(Some(key), "key"),
+            None => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        })
+        .collect()
+}
+
+// the two elements reply of the SCAN family: the next cursor and the page
+fn scan_reply(cursor: u64, elements: Vec<RespFrame>) -> RespFrame {
+    RespArray::new([
+        BulkString::new(cursor.to_string()).into(),
+        RespArray::new(elements).into(),
+    ])
+    .into()
+}
+
+fn extract_int(frame: Option<RespFrame>) -> Result<i64, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(s)) => std::str::from_utf8(&s)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| {
+                CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+            }),
+        Some(RespFrame::Integer(n)) => Ok(n),
+        _ => Err(CommandError::InvalidArgument(
+            "value is not an integer or out of range".to_string(),
+        )),
+    }
+}
+
+fn extract_float(frame: Option<RespFrame>) -> Result<f64, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(s)) => std::str::from_utf8(&s)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|n| !n.is_nan())
+            .ok_or_else(|| CommandError::InvalidArgument("value is not a valid float".to_string())),
+        Some(RespFrame::Integer(n)) => Ok(n as f64),
+        Some(RespFrame::Double(n)) if !n.is_nan() => Ok(n),
+        _ => Err(CommandError::InvalidArgument(
+            "value is not a valid float".to_string(),
+        )),
+    }
+}
+
 fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
     Ok(value.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::RespDecode;
     use anyhow::Result;
+    use bytes::BytesMut;
 
     #[test]
     fn test_command() -> Result<()> {
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.config_load(vec![
+            (
+                "rename-command".to_string(),
+                "CONFIG admin-config".to_string(),
+            ),
+            ("rename-command".to_string(), "flushall ".to_string()),
+        ])?;
+        let resolve = |s: &str| {
+            let mut buf = BytesMut::from(s);
+            resolve_command(&backend, RespArray::decode(&mut buf)?)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| Ok(Command::try_from(value)?))
+        };
+        assert!(matches!(
+            resolve("*3\r\n$12\r\nADMIN-CONFIG\r\n$3\r\nget\r\n$1\r\n*\r\n")?,
+            Command::ConfigGet(_)
+        ));
+        assert!(resolve("*3\r\n$6\r\nconfig\r\n$3\r\nget\r\n$1\r\n*\r\n").is_err());
+        assert!(resolve("*2\r\n$3\r\nget\r\n$1\r\nk\r\n").is_ok());
+        assert_eq!(backend.resolve_command_name("flushall"), None);
+        assert!(backend
+            .config_load(vec![("rename-command".to_string(), "config".to_string())])
+            .is_err());
+
+        // SENTINEL in sentinel mode only, and only the commands of a sentinel there
+        let sentinel = "*2\r\n$8\r\nsentinel\r\n$7\r\nmasters\r\n";
+        assert!(resolve(sentinel).is_err());
+        backend.enable_sentinel();
+        assert!(matches!(resolve(sentinel)?, Command::SentinelMasters(_)));
+        assert!(resolve("*2\r\n$3\r\nget\r\n$1\r\nk\r\n").is_err());
+        assert!(resolve("*1\r\n$4\r\nPING\r\n").is_ok());
+        Ok(())
+    }
 }
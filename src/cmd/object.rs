@@ -0,0 +1,155 @@
+use crate::cmd::{
+    extract_args, validate_command, CommandError, CommandExecutor, ObjectEncoding, ObjectFreq,
+    ObjectIdleTime, ObjectRefCount,
+};
+use crate::{Backend, BulkString, RespArray, RespFrame, RespNull};
+
+impl CommandExecutor for ObjectEncoding {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.object_encoding(&self.key) {
+            None => RespFrame::Null(RespNull),
+            Some(encoding) => BulkString::from(encoding).into(),
+        }
+    }
+}
+
+impl CommandExecutor for ObjectRefCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // values are never shared between keys, so the refcount is always 1
+        match backend.object_encoding(&self.key) {
+            None => RespFrame::Null(RespNull),
+            Some(_) => RespFrame::Integer(1),
+        }
+    }
+}
+
+impl CommandExecutor for ObjectIdleTime {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.object_idletime(&self.key) {
+            None => RespFrame::Null(RespNull),
+            Some(idle) => RespFrame::Integer(idle as i64),
+        }
+    }
+}
+
+impl CommandExecutor for ObjectFreq {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.object_freq(&self.key) {
+            None => RespFrame::Null(RespNull),
+            Some(freq) => RespFrame::Integer(freq as i64),
+        }
+    }
+}
+
+fn extract_key(value: RespArray, subcommand: &'static str) -> Result<String, CommandError> {
+    validate_command(&value, &["object", subcommand], 1)?;
+
+    let mut args = extract_args(value, 2)?.into_iter();
+    match args.next() {
+        Some(RespFrame::BulkString(key)) => Ok(String::from_utf8(key.0.into())?),
+        _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+    }
+}
+
+impl TryFrom<RespArray> for ObjectEncoding {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(ObjectEncoding {
+            key: extract_key(value, "encoding")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ObjectRefCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(ObjectRefCount {
+            key: extract_key(value, "refcount")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ObjectIdleTime {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(ObjectIdleTime {
+            key: extract_key(value, "idletime")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ObjectFreq {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        Ok(ObjectFreq {
+            key: extract_key(value, "freq")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::{
+        Command, CommandExecutor, ObjectEncoding, ObjectFreq, ObjectIdleTime, ObjectRefCount,
+    };
+    use crate::RespDecode;
+    use crate::{Backend, RespArray, RespFrame, RespNull};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_object_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nobject\r\n$8\r\nENCODING\r\n$5\r\nhello\r\n");
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: ObjectEncoding = frame.try_into()?;
+        assert_eq!(result.key, "hello");
+
+        buf.extend_from_slice(b"*3\r\n$6\r\nobject\r\n$4\r\nfreq\r\n$5\r\nhello\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(matches!(Command::try_from(frame)?, Command::ObjectFreq(_)));
+
+        buf.extend_from_slice(b"*3\r\n$6\r\nobject\r\n$3\r\nfoo\r\n$5\r\nhello\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Command::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_command() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("hello".to_string(), RespFrame::BulkString(b"world".into()));
+
+        let result = ObjectEncoding {
+            key: "hello".to_string(),
+        }
+        .execute(&backend);
+        assert_eq!(result, RespFrame::BulkString(b"embstr".into()));
+
+        let result = ObjectRefCount {
+            key: "hello".to_string(),
+        }
+        .execute(&backend);
+        assert_eq!(result, RespFrame::Integer(1));
+
+        let result = ObjectIdleTime {
+            key: "hello".to_string(),
+        }
+        .execute(&backend);
+        assert_eq!(result, RespFrame::Integer(0));
+
+        let result = ObjectFreq {
+            key: "missing".to_string(),
+        }
+        .execute(&backend);
+        assert_eq!(result, RespFrame::Null(RespNull));
+
+        Ok(())
+    }
+}
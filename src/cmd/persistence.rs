@@ -0,0 +1,237 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    wait_acks, BgRewriteAof, BgSave, CommandError, CommandExecutor, LastSave, Save, Shutdown,
+    WaitAof, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame, SimpleString};
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.save() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bgsave() {
+            Ok(()) => SimpleString::new("Background saving started").into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for LastSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.lastsave())
+    }
+}
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bgrewriteaof() {
+            Ok(()) => SimpleString::new("Background append only file rewriting started").into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Shutdown {
+    // the connection is closed, the server stops once the reply is sent
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.shutdown(self.save);
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for WaitAof {
+    // in a transaction, it replies right away
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.aof_target(self.numlocal) {
+            Ok(target) => acks_reply(backend.aof_acks(target)),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl WaitAof {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        let target = match backend.aof_target(self.numlocal) {
+            Ok(target) => target,
+            Err(e) => return e.into(),
+        };
+        if self.numreplicas > 0 {
+            backend.request_replica_acks();
+        }
+        wait_acks(backend, self.timeout, || {
+            let (local, replicas) = backend.aof_acks(target);
+            (local || self.numlocal == 0) && replicas >= self.numreplicas
+        })
+        .await;
+        acks_reply(backend.aof_acks(target))
+    }
+}
+
+// whether the local AOF is fsynced, and how many replicas fsynced theirs
+fn acks_reply((local, replicas): (bool, usize)) -> RespFrame {
+    RespArray::new(vec![
+        RespFrame::Integer(local as i64),
+        RespFrame::Integer(replicas as i64),
+    ])
+    .into()
+}
+
+impl TryFrom<RespArray> for Save {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["save"], 0)?;
+        Ok(Save)
+    }
+}
+
+impl TryFrom<RespArray> for BgSave {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgsave"], 0)?;
+        Ok(BgSave)
+    }
+}
+
+impl TryFrom<RespArray> for LastSave {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lastsave"], 0)?;
+        Ok(LastSave)
+    }
+}
+
+impl TryFrom<RespArray> for BgRewriteAof {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgrewriteaof"], 0)?;
+        Ok(BgRewriteAof)
+    }
+}
+
+// SHUTDOWN [NOSAVE | SAVE]
+impl TryFrom<RespArray> for Shutdown {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["shutdown"], 0)?;
+
+        let mut save = None;
+        for arg in extract_args(value, 1)? {
+            save = match (
+                extract_string(Some(arg), "option")?
+                    .to_ascii_lowercase()
+                    .as_str(),
+                save,
+            ) {
+                ("nosave", None | Some(false)) => Some(false),
+                ("save", None | Some(true)) => Some(true),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+        }
+        Ok(Shutdown { save })
+    }
+}
+
+// WAITAOF numlocal numreplicas timeout
+impl TryFrom<RespArray> for WaitAof {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["waitaof"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut count = || match usize::try_from(extract_int(args.next())?) {
+            Ok(count) => Ok(count),
+            Err(_) => Err(CommandError::InvalidArgument(
+                "value is out of range, must be positive".to_string(),
+            )),
+        };
+        let numlocal = count()?;
+        let numreplicas = count()?;
+        let timeout = match u64::try_from(extract_int(args.next())?) {
+            Ok(timeout) => timeout,
+            Err(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "timeout is negative".to_string(),
+                ))
+            }
+        };
+        Ok(WaitAof {
+            numlocal,
+            numreplicas,
+            timeout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use std::{env, fs};
+
+    #[test]
+    fn test_save_command() -> Result<()> {
+        let mut buf = BytesMut::from("*1\r\n$4\r\nSAVE\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let save: Save = frame.try_into()?;
+
+        let dir = env::temp_dir().join(format!("simple-redis-save-cmd-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        let before = LastSave.execute(&backend);
+        assert_eq!(save.execute(&backend), RESP_OK.clone());
+        assert!(dir.join("dump.rdb").exists());
+        assert!(LastSave.execute(&backend) >= before);
+
+        // the directory is gone
+        fs::remove_dir_all(&dir)?;
+        let RespFrame::Error(e) = Save.execute(&backend) else {
+            panic!("expected an error");
+        };
+        assert!(e.starts_with("ERR "));
+        assert!(!backend.is_saving());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_waitaof_command() -> Result<()> {
+        let mut buf = BytesMut::from("*4\r\n$7\r\nWAITAOF\r\n$1\r\n0\r\n$1\r\n0\r\n$1\r\n0\r\n");
+        let waitaof: WaitAof = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            (waitaof.numlocal, waitaof.numreplicas, waitaof.timeout),
+            (0, 0, 0)
+        );
+
+        let backend = Backend::new();
+        let acks = RespArray::new(vec![RespFrame::Integer(0), RespFrame::Integer(0)]);
+        assert_eq!(waitaof.block(&backend).await, acks.into());
+
+        // no AOF to wait for
+        let waitaof = WaitAof {
+            numlocal: 1,
+            numreplicas: 0,
+            timeout: 10,
+        };
+        assert!(matches!(waitaof.block(&backend).await, RespFrame::Error(_)));
+
+        let mut buf = BytesMut::from("*4\r\n$7\r\nWAITAOF\r\n$1\r\n0\r\n$1\r\n0\r\n$2\r\n-1\r\n");
+        assert!(WaitAof::try_from(RespArray::decode(&mut buf)?).is_err());
+        Ok(())
+    }
+}
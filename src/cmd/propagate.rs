@@ -0,0 +1,510 @@
+use crate::backend::{command, string_bytes, XCLAIM_NO_CLAIM};
+use crate::cmd::{Command, CommandExecutor, CommandFlag, GetExExpire, SetExpire};
+use crate::{Backend, BulkString, PendingFilter, RespArray, RespFrame, StreamId, XAddId};
+
+// what the propagated commands are computed from, taken before the command runs
+enum Rewrite {
+    Verbatim,
+    // nothing changed
+    Nothing,
+    // the popped members are removed
+    SPop(String),
+    // the generated ID is made explicit
+    XAdd,
+    // the TTL is made absolute
+    Expire(String),
+    // the value is set as requested, then its TTL is made absolute
+    SetEx(String),
+    // the TTLs of the fields are made absolute, the fields deleted by a TTL in the past are
+    // deleted
+    HExpire(String, Vec<String>),
+    GetEx(String),
+    // the keys moved away are deleted
+    Migrate(Vec<String>),
+    // the claimed entries are transferred with their new delivery time and count
+    XClaim(XClaimed),
+    XAutoClaim(XClaimed),
+}
+
+struct XClaimed {
+    key: String,
+    group: String,
+    consumer: String,
+    ids: Vec<StreamId>,
+    last_id: Option<StreamId>,
+}
+
+impl Command {
+    /// Whether the command may change the keyspace, so it's propagated to the AOF. Commands
+    /// about the connection and those running other commands aren't: the commands a script
+    /// or a transaction runs are propagated instead.
+    pub fn is_propagated(&self) -> bool {
+        match self {
+            Command::Custom(cmd) => cmd.has_flag(CommandFlag::Write),
+            cmd => {
+                !cmd.is_read_only()
+                    && !matches!(
+                        cmd,
+                        Command::Hello(_)
+                            | Command::Auth(_)
+                            | Command::Quit(_)
+                            | Command::Ping(_)
+                            | Command::Select(_)
+                            | Command::Subscribe(_)
+                            | Command::Unsubscribe(_)
+                            | Command::PSubscribe(_)
+                            | Command::PUnsubscribe(_)
+                            | Command::SSubscribe(_)
+                            | Command::SUnsubscribe(_)
+                            | Command::ClientId(_)
+                            | Command::ClientTracking(_)
+                            | Command::ClientCaching(_)
+                            | Command::ClientList(_)
+                            | Command::ClientNoEvict(_)
+                            | Command::Asking(_)
+                            | Command::Multi(_)
+                            | Command::Exec(_)
+                            | Command::Discard(_)
+                            | Command::Watch(_)
+                            | Command::Unwatch(_)
+                            | Command::Reset(_)
+                            | Command::Eval(_)
+                            | Command::EvalSha(_)
+                            | Command::ScriptLoad(_)
+                            | Command::ScriptExists(_)
+                            | Command::ScriptFlush(_)
+                            | Command::ScriptKill(_)
+                            | Command::FunctionList(_)
+                            | Command::FunctionDump(_)
+                            | Command::FCall(_)
+                            | Command::FCallRo(_)
+                            | Command::Save(_)
+                            | Command::BgSave(_)
+                            | Command::LastSave(_)
+                            | Command::BgRewriteAof(_)
+                            | Command::WaitAof(_)
+                            | Command::Shutdown(_)
+                            | Command::ConfigGet(_)
+                            | Command::ConfigSet(_)
+                            | Command::ConfigRewrite(_)
+                            | Command::ConfigResetStat(_)
+                            | Command::LatencyLatest(_)
+                            | Command::LatencyHistory(_)
+                            | Command::LatencyReset(_)
+                            | Command::AclSetUser(_)
+                            | Command::AclGetUser(_)
+                            | Command::AclDelUser(_)
+                            | Command::AclList(_)
+                            | Command::AclWhoAmI(_)
+                            | Command::AclSave(_)
+                            | Command::AclLoad(_)
+                            | Command::Info(_)
+                            | Command::ClusterInfo(_)
+                            | Command::ClusterMyId(_)
+                            | Command::ClusterSlots(_)
+                            | Command::ClusterShards(_)
+                            | Command::ClusterNodes(_)
+                            | Command::ClusterKeySlot(_)
+                            | Command::CommandGetKeys(_)
+                            | Command::ClusterSetSlot(_)
+                            | Command::ClusterMeet(_)
+                            | Command::ClusterForget(_)
+                            | Command::ReplConf(_)
+                            | Command::Wait(_)
+                            | Command::PSync(_)
+                            | Command::Sync(_)
+                            | Command::ReplicaOf(_)
+                            | Command::DebugReload(_)
+                            | Command::SentinelGetMasterAddrByName(_)
+                            | Command::SentinelMasters(_)
+                            | Command::SentinelMaster(_)
+                            | Command::SentinelReplicas(_)
+                            | Command::SentinelSentinels(_)
+                            | Command::SentinelIsMasterDownByAddr(_)
+                            | Command::SentinelMonitor(_)
+                            | Command::SentinelRemove(_)
+                            | Command::SentinelSet(_)
+                            | Command::SentinelFailover(_)
+                            | Command::SentinelMyId(_)
+                    )
+            }
+        }
+    }
+
+    /// Execute the command, propagating request, the frame it was parsed from, if the writes
+    /// are. What depends on the time or on randomness is propagated as its effect, so replaying
+    /// the commands gives the same keyspace: SPOP as SREM, XADD with the generated ID, relative
+    /// TTLs as PEXPIREAT. The clients a push serves are propagated after it.
+    pub fn execute_propagating(self, backend: &Backend, request: Option<RespArray>) -> RespFrame {
+        let request = match request {
+            Some(request) if backend.is_propagating() && self.is_propagated() => request,
+            _ => return self.execute(backend),
+        };
+        let rewrite = Rewrite::of(&self);
+        backend.propagate_effects(
+            || self.execute(backend),
+            |reply| match reply {
+                RespFrame::Error(_) => Vec::new(),
+                reply => rewrite.commands(backend, request, reply),
+            },
+        )
+    }
+}
+
+impl Rewrite {
+    fn of(cmd: &Command) -> Self {
+        match cmd {
+            Command::SPop(cmd) => Rewrite::SPop(cmd.key.clone()),
+            Command::XAdd(cmd) if matches!(cmd.id, XAddId::Auto | XAddId::AutoSeq(_)) => {
+                Rewrite::XAdd
+            }
+            Command::Expire(cmd) => Rewrite::Expire(cmd.key.clone()),
+            Command::PExpire(cmd) => Rewrite::Expire(cmd.key.clone()),
+            Command::Set(cmd) => match cmd.expire {
+                Some(SetExpire::Ex(_) | SetExpire::Px(_)) => Rewrite::SetEx(cmd.key.clone()),
+                _ => Rewrite::Verbatim,
+            },
+            Command::SetEx(cmd) => Rewrite::SetEx(cmd.key.clone()),
+            Command::HExpire(cmd) => Rewrite::HExpire(cmd.key.clone(), cmd.fields.clone()),
+            Command::HPExpire(cmd) => Rewrite::HExpire(cmd.key.clone(), cmd.fields.clone()),
+            Command::PSetEx(cmd) => Rewrite::SetEx(cmd.key.clone()),
+            Command::Restore(cmd) if cmd.options.ttl != 0 && !cmd.options.absttl => {
+                Rewrite::SetEx(cmd.key.clone())
+            }
+            Command::Migrate(cmd) if cmd.options.copy => Rewrite::Nothing,
+            Command::Migrate(cmd) => Rewrite::Migrate(cmd.keys.clone()),
+            Command::GetEx(cmd) => match cmd.expire {
+                None => Rewrite::Nothing,
+                Some(GetExExpire::Persist) => Rewrite::Verbatim,
+                Some(_) => Rewrite::GetEx(cmd.key.clone()),
+            },
+            Command::XClaim(cmd) => Rewrite::XClaim(XClaimed {
+                key: cmd.key.clone(),
+                group: cmd.group.clone(),
+                consumer: cmd.consumer.clone(),
+                ids: cmd.ids.clone(),
+                last_id: cmd.options.last_id,
+            }),
+            Command::XAutoClaim(cmd) => Rewrite::XAutoClaim(XClaimed {
+                key: cmd.key.clone(),
+                group: cmd.group.clone(),
+                consumer: cmd.consumer.clone(),
+                ids: vec![cmd.start],
+                last_id: None,
+            }),
+            _ => Rewrite::Verbatim,
+        }
+    }
+
+    // the commands to propagate for request, which replied reply
+    fn commands(self, backend: &Backend, request: RespArray, reply: &RespFrame) -> Vec<RespArray> {
+        match self {
+            Rewrite::Verbatim => vec![request],
+            Rewrite::Nothing => Vec::new(),
+            Rewrite::SPop(key) => {
+                let members = match reply {
+                    RespFrame::BulkString(member) => vec![member.to_vec()],
+                    RespFrame::Set(members) => members.iter().map(string_bytes).collect(),
+                    _ => Vec::new(),
+                };
+                if members.is_empty() {
+                    return Vec::new();
+                }
+                let args = [b"SREM".to_vec(), key.into_bytes()];
+                vec![command(args.into_iter().chain(members))]
+            }
+            Rewrite::XAdd => {
+                let RespFrame::BulkString(id) = reply else {
+                    // NOMKSTREAM on a missing key
+                    return Vec::new();
+                };
+                let mut request = request;
+                // the ID is the first argument after the key that's `*` or `ms-*`
+                if let Some(arg) = request.0.iter_mut().skip(2).find(|arg| is_auto_id(arg)) {
+                    *arg = RespFrame::BulkString(id.clone());
+                }
+                vec![request]
+            }
+            Rewrite::Expire(key) => match (reply, backend.expire_time(&key)) {
+                (RespFrame::Integer(0), _) => Vec::new(),
+                (_, Some(Some(at))) => vec![pexpireat(&key, at)],
+                // the key was deleted by a TTL in the past
+                _ => vec![request],
+            },
+            Rewrite::SetEx(key) => match backend.expire_time(&key) {
+                Some(Some(at)) => vec![request, pexpireat(&key, at)],
+                _ => vec![request],
+            },
+            Rewrite::HExpire(key, fields) => {
+                let RespFrame::Array(results) = reply else {
+                    return Vec::new();
+                };
+                let (mut set, mut deleted) = (Vec::new(), Vec::new());
+                for (field, result) in fields.into_iter().zip(results.iter()) {
+                    match result {
+                        RespFrame::Integer(1) => set.push(field),
+                        RespFrame::Integer(2) => deleted.push(field),
+                        _ => {}
+                    }
+                }
+                let mut commands = Vec::new();
+                if !deleted.is_empty() {
+                    let args = ["HDEL".to_string(), key.clone()];
+                    commands.push(command(args.into_iter().chain(deleted)));
+                }
+                // the fields all got the same TTL
+                let at = match set.first() {
+                    Some(field) => backend.hexpire_time(&key, std::slice::from_ref(field)),
+                    None => Ok(Vec::new()),
+                };
+                if let Ok([Some(Some(at))]) = at.as_deref() {
+                    commands.push(hpexpireat(&key, *at, set));
+                }
+                commands
+            }
+            Rewrite::GetEx(key) => match backend.expire_time(&key) {
+                Some(Some(at)) => vec![pexpireat(&key, at)],
+                _ => vec![request],
+            },
+            Rewrite::Migrate(keys) => {
+                // some may have been moved before the target failed
+                let moved: Vec<_> = keys
+                    .into_iter()
+                    .filter(|key| !backend.contains_key(key))
+                    .collect();
+                if moved.is_empty() {
+                    return Vec::new();
+                }
+                vec![command(["DEL".to_string()].into_iter().chain(moved))]
+            }
+            Rewrite::XClaim(claimed) => {
+                let ids = match reply {
+                    RespFrame::Array(entries) => claimed_ids(entries),
+                    _ => Vec::new(),
+                };
+                claimed.commands(backend, &ids)
+            }
+            Rewrite::XAutoClaim(mut claimed) => {
+                let RespFrame::Array(reply) = reply else {
+                    return Vec::new();
+                };
+                let ids = match reply.get(1) {
+                    Some(RespFrame::Array(entries)) => claimed_ids(entries),
+                    _ => Vec::new(),
+                };
+                // the deleted entries found on the way are dropped
+                if let Some(RespFrame::Array(deleted)) = reply.get(2) {
+                    claimed.ids.extend(claimed_ids(deleted));
+                }
+                claimed.commands(backend, &ids)
+            }
+        }
+    }
+}
+
+impl XClaimed {
+    // an XCLAIM with the side effects of the claim, then one setting the state of each of the
+    // entries claimed
+    fn commands(self, backend: &Backend, claimed: &[StreamId]) -> Vec<RespArray> {
+        let (key, group) = (&self.key, &self.group);
+        let last_id = self.last_id.map(|id| id.to_string());
+        let mut args = vec![
+            "XCLAIM".to_string(),
+            key.clone(),
+            group.clone(),
+            self.consumer.clone(),
+            XCLAIM_NO_CLAIM.to_string(),
+        ];
+        args.extend(self.ids.iter().map(StreamId::to_string));
+        if let Some(last_id) = &last_id {
+            args.extend(["LASTID".to_string(), last_id.clone()]);
+        }
+        let mut commands = vec![command(args)];
+
+        for id in claimed {
+            let filter = PendingFilter {
+                min_idle: i64::MIN,
+                start: *id,
+                end: *id,
+                count: 1,
+                consumer: None,
+            };
+            let Some((_, entry)) = backend
+                .xpending_range(key, group, &filter)
+                .ok()
+                .and_then(|mut pending| pending.pop())
+            else {
+                continue;
+            };
+            commands.push(command([
+                "XCLAIM",
+                key,
+                group,
+                &entry.consumer,
+                "0",
+                &id.to_string(),
+                "TIME",
+                &entry.delivered_at.to_string(),
+                "RETRYCOUNT",
+                &entry.deliveries.to_string(),
+                "FORCE",
+                "JUSTID",
+            ]));
+        }
+        commands
+    }
+}
+
+fn pexpireat(key: &str, at: i64) -> RespArray {
+    command(["PEXPIREAT", key, &at.to_string()])
+}
+
+fn hpexpireat(key: &str, at: i64, fields: Vec<String>) -> RespArray {
+    let args = [
+        "HPEXPIREAT".to_string(),
+        key.to_string(),
+        at.to_string(),
+        "FIELDS".to_string(),
+        fields.len().to_string(),
+    ];
+    command(args.into_iter().chain(fields))
+}
+
+fn is_auto_id(arg: &RespFrame) -> bool {
+    let RespFrame::BulkString(arg) = arg else {
+        return false;
+    };
+    match arg.strip_suffix(b"*") {
+        Some(b"") => true,
+        Some(ms) => ms
+            .strip_suffix(b"-")
+            .is_some_and(|ms| !ms.is_empty() && ms.iter().all(u8::is_ascii_digit)),
+        None => false,
+    }
+}
+
+// the IDs of claimed entries, replied as IDs or as [id, fields] pairs
+fn claimed_ids(entries: &[RespFrame]) -> Vec<StreamId> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            RespFrame::BulkString(id) => Some(id),
+            RespFrame::Array(pair) => match pair.first() {
+                Some(RespFrame::BulkString(id)) => Some(id),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter_map(parse_id)
+        .collect()
+}
+
+fn parse_id(id: &BulkString) -> Option<StreamId> {
+    let (ms, seq) = std::str::from_utf8(id).ok()?.split_once('-')?;
+    Some(StreamId {
+        ms: ms.parse().ok()?,
+        seq: seq.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppendFsync, PopFrom};
+    use std::env;
+    use tokio::runtime::Handle;
+
+    // a backend with the AOF on, in a directory of its own
+    fn aof_backend(name: &str) -> Backend {
+        let dir = env::temp_dir().join(format!("simple-redis-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let backend = Backend::new();
+        backend.set_dir(dir);
+        backend
+            .start_aof(AppendFsync::No, &Handle::current())
+            .unwrap();
+        backend
+    }
+
+    fn request(args: &[&str]) -> RespArray {
+        command(args.iter().copied())
+    }
+
+    fn propagated(backend: &Backend, args: &[&str]) -> Vec<RespArray> {
+        let cmd = Command::try_from(request(args)).unwrap();
+        let (_, commands) =
+            backend.capture_propagated(|| cmd.execute_propagating(backend, Some(request(args))));
+        commands
+    }
+
+    #[tokio::test]
+    async fn test_propagation_rewrites() {
+        let backend = aof_backend("propagate");
+
+        assert_eq!(
+            propagated(&backend, &["SET", "k", "v"]),
+            vec![request(&["SET", "k", "v"])]
+        );
+        assert!(propagated(&backend, &["GET", "k"]).is_empty());
+        // a failed command changes nothing
+        assert!(propagated(&backend, &["LPUSH", "k", "v"]).is_empty());
+
+        let expire = propagated(&backend, &["EXPIRE", "k", "100"]);
+        let at = backend.expire_time("k").unwrap().unwrap();
+        assert_eq!(expire, vec![pexpireat("k", at)]);
+        let set = propagated(&backend, &["SET", "k", "v", "PX", "100000"]);
+        let at = backend.expire_time("k").unwrap().unwrap();
+        assert_eq!(
+            set,
+            vec![
+                request(&["SET", "k", "v", "PX", "100000"]),
+                pexpireat("k", at)
+            ]
+        );
+
+        backend
+            .hset("h".to_string(), "f".to_string(), RespFrame::Integer(1))
+            .unwrap();
+        let hexpire = propagated(&backend, &["HEXPIRE", "h", "100", "FIELDS", "2", "f", "g"]);
+        let at = backend.hexpire_time("h", &["f".to_string()]).unwrap()[0]
+            .unwrap()
+            .unwrap();
+        let at = at.to_string();
+        assert_eq!(
+            hexpire,
+            vec![request(&["HPEXPIREAT", "h", &at, "FIELDS", "1", "f"])]
+        );
+        assert_eq!(
+            propagated(&backend, &["HPEXPIRE", "h", "0", "FIELDS", "1", "f"]),
+            vec![request(&["HDEL", "h", "f"])]
+        );
+
+        backend.sadd("s", vec!["a".into()]).unwrap();
+        assert_eq!(
+            propagated(&backend, &["SPOP", "s"]),
+            vec![request(&["SREM", "s", "a"])]
+        );
+        assert!(propagated(&backend, &["SPOP", "s"]).is_empty());
+
+        let xadd = propagated(&backend, &["XADD", "x", "MAXLEN", "10", "5-*", "f", "v"]);
+        assert_eq!(
+            xadd,
+            vec![request(&["XADD", "x", "MAXLEN", "10", "5-0", "f", "v"])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_served_after_push() {
+        let backend = aof_backend("propagate-served");
+        let keys = ["l".to_string()];
+        let _blocked = backend.bpop(&keys, PopFrom::ListHead, 1).unwrap();
+
+        assert_eq!(
+            propagated(&backend, &["RPUSH", "l", "a", "b"]),
+            vec![
+                request(&["RPUSH", "l", "a", "b"]),
+                request(&["LPOP", "l", "1"])
+            ]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use crate::cmd::{
+    extract_args, extract_string, validate_command, validate_variadic_command, CommandError,
+    CommandExecutor, PSubscribe, PUnsubscribe, Publish, SPublish, SSubscribe, SUnsubscribe,
+    Subscribe, Unsubscribe,
+};
+use crate::{
+    Backend, BulkString, Message, RespArray, RespFrame, RespNull, RespPush, SimpleError,
+    Subscriptions,
+};
+
+impl CommandExecutor for Subscribe {
+    // subscribing needs a connection to deliver the messages to
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR SUBSCRIBE is not allowed in this context").into()
+    }
+}
+
+impl Subscribe {
+    /// Subscribe the connection to the channels, replying a confirmation for each.
+    pub fn apply(self, backend: &Backend, subscriptions: &mut Subscriptions) -> Vec<RespFrame> {
+        self.channels
+            .into_iter()
+            .map(|channel| {
+                backend.subscribe_channel(subscriptions, &channel);
+                confirmation("subscribe", Some(channel), subscriptions.count())
+            })
+            .collect()
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR UNSUBSCRIBE is not allowed in this context").into()
+    }
+}
+
+impl Unsubscribe {
+    /// Unsubscribe the connection from the channels, replying a confirmation for each, or
+    /// for none if it had no subscription.
+    pub fn apply(self, backend: &Backend, subscriptions: &mut Subscriptions) -> Vec<RespFrame> {
+        let channels = if self.channels.is_empty() {
+            subscriptions.channels().map(str::to_string).collect()
+        } else {
+            self.channels
+        };
+        if channels.is_empty() {
+            return vec![confirmation("unsubscribe", None, subscriptions.count())];
+        }
+        channels
+            .into_iter()
+            .map(|channel| {
+                backend.unsubscribe_channel(subscriptions, &channel);
+                confirmation("unsubscribe", Some(channel), subscriptions.count())
+            })
+            .collect()
+    }
+}
+
+impl CommandExecutor for PSubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR PSUBSCRIBE is not allowed in this context").into()
+    }
+}
+
+impl PSubscribe {
+    /// Subscribe the connection to the patterns, replying a confirmation for each.
+    pub fn apply(self, backend: &Backend, subscriptions: &mut Subscriptions) -> Vec<RespFrame> {
+        self.patterns
+            .into_iter()
+            .map(|pattern| {
+                backend.subscribe_pattern(subscriptions, &pattern);
+                confirmation("psubscribe", Some(pattern), subscriptions.count())
+            })
+            .collect()
+    }
+}
+
+impl CommandExecutor for PUnsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR PUNSUBSCRIBE is not allowed in this context").into()
+    }
+}
+
+impl PUnsubscribe {
+    /// Unsubscribe the connection from the patterns, like `Unsubscribe::apply`.
+    pub fn apply(self, backend: &Backend, subscriptions: &mut Subscriptions) -> Vec<RespFrame> {
+        let patterns = if self.patterns.is_empty() {
+            subscriptions.patterns().map(str::to_string).collect()
+        } else {
+            self.patterns
+        };
+        if patterns.is_empty() {
+            return vec![confirmation("punsubscribe", None, subscriptions.count())];
+        }
+        patterns
+            .into_iter()
+            .map(|pattern| {
+                backend.unsubscribe_pattern(subscriptions, &pattern);
+                confirmation("punsubscribe", Some(pattern), subscriptions.count())
+            })
+            .collect()
+    }
+}
+
+impl CommandExecutor for SSubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR SSUBSCRIBE is not allowed in this context").into()
+    }
+}
+
+impl SSubscribe {
+    /// Subscribe the connection to the shard channels, replying a confirmation for each with
+    /// the count of shard channel subscriptions.
+    pub fn apply(self, backend: &Backend, subscriptions: &mut Subscriptions) -> Vec<RespFrame> {
+        self.channels
+            .into_iter()
+            .map(|channel| {
+                backend.subscribe_shard_channel(subscriptions, &channel);
+                confirmation("ssubscribe", Some(channel), subscriptions.shard_count())
+            })
+            .collect()
+    }
+}
+
+impl CommandExecutor for SUnsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR SUNSUBSCRIBE is not allowed in this context").into()
+    }
+}
+
+impl SUnsubscribe {
+    /// Unsubscribe the connection from the shard channels, like `Unsubscribe::apply`.
+    pub fn apply(self, backend: &Backend, subscriptions: &mut Subscriptions) -> Vec<RespFrame> {
+        let channels = if self.channels.is_empty() {
+            subscriptions.shard_channels().map(str::to_string).collect()
+        } else {
+            self.channels
+        };
+        if channels.is_empty() {
+            return vec![confirmation(
+                "sunsubscribe",
+                None,
+                subscriptions.shard_count(),
+            )];
+        }
+        channels
+            .into_iter()
+            .map(|channel| {
+                backend.unsubscribe_shard_channel(subscriptions, &channel);
+                confirmation("sunsubscribe", Some(channel), subscriptions.shard_count())
+            })
+            .collect()
+    }
+}
+
+impl CommandExecutor for SPublish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.spublish(&self.channel, &self.message) as i64)
+    }
+}
+
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.publish(&self.channel, &self.message) as i64)
+    }
+}
+
+// [kind, channel, subscription count], pushed to RESP3 connections
+fn confirmation(kind: &'static str, channel: Option<String>, count: usize) -> RespFrame {
+    let channel = match channel {
+        Some(channel) => BulkString::new(channel).into(),
+        None => RespFrame::Null(RespNull),
+    };
+    RespPush::new([
+        BulkString::from(kind).into(),
+        channel,
+        RespFrame::Integer(count as i64),
+    ])
+    .into()
+}
+
+// ["message", channel, payload], ["pmessage", pattern, channel, payload], or
+// ["smessage", channel, payload]. Pushes, which RESP2 connections get as arrays.
+impl From<Message> for RespFrame {
+    fn from(message: Message) -> Self {
+        let mut frames = Vec::with_capacity(4);
+        match message.pattern {
+            Some(pattern) => {
+                frames.push(BulkString::from("pmessage").into());
+                frames.push(BulkString::new(pattern).into());
+            }
+            None if message.sharded => frames.push(BulkString::from("smessage").into()),
+            None => frames.push(BulkString::from("message").into()),
+        }
+        frames.push(BulkString::new(message.channel).into());
+        frames.push(BulkString::new(message.payload).into());
+        RespPush::new(frames).into()
+    }
+}
+
+impl TryFrom<RespArray> for Subscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["subscribe"], 1)?;
+
+        let channels = extract_args(value, 1)?
+            .into_iter()
+            .map(|channel| extract_string(Some(channel), "channel"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Subscribe { channels })
+    }
+}
+
+impl TryFrom<RespArray> for Unsubscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["unsubscribe"], 0)?;
+
+        let channels = extract_args(value, 1)?
+            .into_iter()
+            .map(|channel| extract_string(Some(channel), "channel"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Unsubscribe { channels })
+    }
+}
+
+impl TryFrom<RespArray> for PSubscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["psubscribe"], 1)?;
+
+        let patterns = extract_args(value, 1)?
+            .into_iter()
+            .map(|pattern| extract_string(Some(pattern), "pattern"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PSubscribe { patterns })
+    }
+}
+
+impl TryFrom<RespArray> for PUnsubscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["punsubscribe"], 0)?;
+
+        let patterns = extract_args(value, 1)?
+            .into_iter()
+            .map(|pattern| extract_string(Some(pattern), "pattern"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PUnsubscribe { patterns })
+    }
+}
+
+impl TryFrom<RespArray> for SSubscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["ssubscribe"], 1)?;
+
+        let channels = extract_args(value, 1)?
+            .into_iter()
+            .map(|channel| extract_string(Some(channel), "channel"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SSubscribe { channels })
+    }
+}
+
+impl TryFrom<RespArray> for SUnsubscribe {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["sunsubscribe"], 0)?;
+
+        let channels = extract_args(value, 1)?
+            .into_iter()
+            .map(|channel| extract_string(Some(channel), "channel"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SUnsubscribe { channels })
+    }
+}
+
+impl TryFrom<RespArray> for SPublish {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["spublish"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let channel = extract_string(args.next(), "channel")?;
+        let message = extract_string(args.next(), "message")?;
+        Ok(SPublish { channel, message })
+    }
+}
+
+impl TryFrom<RespArray> for Publish {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["publish"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let channel = extract_string(args.next(), "channel")?;
+        let message = extract_string(args.next(), "message")?;
+        Ok(Publish { channel, message })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(bytes: &[u8]) -> Result<RespArray> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(bytes);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_subscribe_publish_commands() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, mut rx) = backend.subscriptions();
+        let cmd: Subscribe =
+            decode(b"*3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n$1\r\nb\r\n")?.try_into()?;
+        let replies = cmd.apply(&backend, &mut subscriptions);
+        assert_eq!(
+            replies,
+            vec![
+                confirmation("subscribe", Some("a".to_string()), 1),
+                confirmation("subscribe", Some("b".to_string()), 2),
+            ]
+        );
+
+        let cmd: Publish = decode(b"*3\r\n$7\r\npublish\r\n$1\r\nb\r\n$2\r\nhi\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let message: RespFrame = rx.try_recv()?.into();
+        let expected = RespPush::new([
+            BulkString::from("message").into(),
+            BulkString::from("b").into(),
+            BulkString::from("hi").into(),
+        ]);
+        assert_eq!(message, expected.into());
+
+        // from every channel, then from none
+        let cmd: Unsubscribe = decode(b"*1\r\n$11\r\nunsubscribe\r\n")?.try_into()?;
+        let replies = cmd.apply(&backend, &mut subscriptions);
+        assert_eq!(
+            replies[1],
+            confirmation("unsubscribe", Some("b".to_string()), 0)
+        );
+        let cmd: Unsubscribe = decode(b"*1\r\n$11\r\nunsubscribe\r\n")?.try_into()?;
+        let replies = cmd.apply(&backend, &mut subscriptions);
+        assert_eq!(replies, vec![confirmation("unsubscribe", None, 0)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_psubscribe_commands() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, mut rx) = backend.subscriptions();
+        let cmd: Subscribe = decode(b"*2\r\n$9\r\nsubscribe\r\n$1\r\na\r\n")?.try_into()?;
+        cmd.apply(&backend, &mut subscriptions);
+        let cmd: PSubscribe = decode(b"*2\r\n$10\r\npsubscribe\r\n$2\r\nh*\r\n")?.try_into()?;
+        // counting the channel subscription too
+        assert_eq!(
+            cmd.apply(&backend, &mut subscriptions),
+            vec![confirmation("psubscribe", Some("h*".to_string()), 2)]
+        );
+
+        backend.publish("hello", "hi");
+        let message: RespFrame = rx.try_recv()?.into();
+        let expected = RespPush::new([
+            BulkString::from("pmessage").into(),
+            BulkString::from("h*").into(),
+            BulkString::from("hello").into(),
+            BulkString::from("hi").into(),
+        ]);
+        assert_eq!(message, expected.into());
+
+        let cmd: PUnsubscribe = decode(b"*1\r\n$12\r\npunsubscribe\r\n")?.try_into()?;
+        assert_eq!(
+            cmd.apply(&backend, &mut subscriptions),
+            vec![confirmation("punsubscribe", Some("h*".to_string()), 1)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ssubscribe_commands() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, mut rx) = backend.subscriptions();
+        let cmd: Subscribe = decode(b"*2\r\n$9\r\nsubscribe\r\n$1\r\na\r\n")?.try_into()?;
+        cmd.apply(&backend, &mut subscriptions);
+        let cmd: SSubscribe = decode(b"*2\r\n$10\r\nssubscribe\r\n$1\r\na\r\n")?.try_into()?;
+        // shard channels are counted apart
+        assert_eq!(
+            cmd.apply(&backend, &mut subscriptions),
+            vec![confirmation("ssubscribe", Some("a".to_string()), 1)]
+        );
+
+        let cmd: SPublish =
+            decode(b"*3\r\n$8\r\nspublish\r\n$1\r\na\r\n$2\r\nhi\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let message: RespFrame = rx.try_recv()?.into();
+        let expected = RespPush::new([
+            BulkString::from("smessage").into(),
+            BulkString::from("a").into(),
+            BulkString::from("hi").into(),
+        ]);
+        assert_eq!(message, expected.into());
+
+        let cmd: SUnsubscribe = decode(b"*1\r\n$12\r\nsunsubscribe\r\n")?.try_into()?;
+        assert_eq!(
+            cmd.apply(&backend, &mut subscriptions),
+            vec![confirmation("sunsubscribe", Some("a".to_string()), 0)]
+        );
+
+        Ok(())
+    }
+}
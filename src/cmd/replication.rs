@@ -0,0 +1,341 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    wait_acks, CommandError, CommandExecutor, PSync, ReplConf, ReplConfOption, ReplicaOf, Sync,
+    Wait, RESP_OK,
+};
+use crate::{Backend, RespArray, RespFrame, SimpleError, SimpleString};
+use tokio::runtime::Handle;
+
+impl CommandExecutor for ReplConf {
+    // the options are the connection's
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR REPLCONF is not allowed in this context").into()
+    }
+}
+
+impl ReplConf {
+    /// Apply the options for the connection of ID id, about to be a replica or being one. An
+    /// acknowledged offset isn't replied.
+    pub fn apply(self, backend: &Backend, id: u64) -> Vec<RespFrame> {
+        let (mut ip, mut port) = (None, None);
+        let (mut ack, mut fack) = (None, None);
+        for option in self.options {
+            match option {
+                ReplConfOption::ListeningPort(listening) => port = Some(listening),
+                ReplConfOption::IpAddress(address) => ip = Some(address),
+                ReplConfOption::Capa(_) | ReplConfOption::GetAck => {}
+                ReplConfOption::Ack(offset) => ack = Some(offset),
+                ReplConfOption::FAck(offset) => fack = Some(offset),
+            }
+        }
+        if let Some(offset) = ack {
+            backend.replica_ack(id, offset, fack);
+            return vec![];
+        }
+        backend.announce_replica(id, ip, port);
+        vec![RESP_OK.clone()]
+    }
+}
+
+impl CommandExecutor for Wait {
+    // in a transaction, it replies right away
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.wait_offset() {
+            Ok(offset) => RespFrame::Integer(backend.replicas_acked(offset) as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Wait {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        let offset = match backend.wait_offset() {
+            Ok(offset) => offset,
+            Err(e) => return e.into(),
+        };
+        if self.numreplicas > 0 {
+            backend.request_replica_acks();
+        }
+        wait_acks(backend, self.timeout, || {
+            backend.replicas_acked(offset) >= self.numreplicas
+        })
+        .await;
+        RespFrame::Integer(backend.replicas_acked(offset) as i64)
+    }
+}
+
+impl CommandExecutor for PSync {
+    // the connection becomes a replica
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR PSYNC is not allowed in this context").into()
+    }
+}
+
+impl CommandExecutor for Sync {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR SYNC is not allowed in this context").into()
+    }
+}
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // the link to the master is a task of the runtime serving the connection
+        let Ok(handle) = Handle::try_current() else {
+            return SimpleError::new("ERR REPLICAOF is not allowed in this context").into();
+        };
+        let connecting = self.master.is_some();
+        match backend.replicaof(self.master, &handle) {
+            false if connecting => {
+                SimpleString::new("OK Already connected to specified master").into()
+            }
+            _ => RESP_OK.clone(),
+        }
+    }
+}
+
+// REPLCONF option value [option value ...]
+impl TryFrom<RespArray> for ReplConf {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["replconf"], 2)?;
+        if !(value.len() - 1).is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut options = Vec::new();
+        while let (Some(option), Some(value)) = (args.next(), args.next()) {
+            let option = extract_string(Some(option), "option")?;
+            options.push(match option.to_ascii_lowercase().as_str() {
+                "listening-port" => match u16::try_from(extract_int(Some(value))?) {
+                    Ok(port) => ReplConfOption::ListeningPort(port),
+                    Err(_) => {
+                        return Err(CommandError::InvalidArgument(
+                            "value is out of range".to_string(),
+                        ))
+                    }
+                },
+                "ip-address" => ReplConfOption::IpAddress(extract_string(Some(value), "ip")?),
+                "capa" => ReplConfOption::Capa(extract_string(Some(value), "capa")?),
+                "ack" => ReplConfOption::Ack(extract_int(Some(value))?.max(0) as u64),
+                "fack" => ReplConfOption::FAck(extract_int(Some(value))?.max(0) as u64),
+                "getack" => ReplConfOption::GetAck,
+                _ => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unrecognized REPLCONF option: {}",
+                        option
+                    )))
+                }
+            });
+        }
+        Ok(ReplConf { options })
+    }
+}
+
+// WAIT numreplicas timeout
+impl TryFrom<RespArray> for Wait {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["wait"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let numreplicas = usize::try_from(extract_int(args.next())?).unwrap_or(0);
+        let timeout = match u64::try_from(extract_int(args.next())?) {
+            Ok(timeout) => timeout,
+            Err(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "timeout is negative".to_string(),
+                ))
+            }
+        };
+        Ok(Wait {
+            numreplicas,
+            timeout,
+        })
+    }
+}
+
+// PSYNC replicationid offset
+impl TryFrom<RespArray> for PSync {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["psync"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(PSync {
+            replid: extract_string(args.next(), "replicationid")?,
+            offset: extract_int(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Sync {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sync"], 0)?;
+        Ok(Sync)
+    }
+}
+
+// REPLICAOF host port | NO ONE, or SLAVEOF
+impl TryFrom<RespArray> for ReplicaOf {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = match value.first() {
+            Some(RespFrame::BulkString(name)) if name.eq_ignore_ascii_case(b"slaveof") => "slaveof",
+            _ => "replicaof",
+        };
+        validate_command(&value, &[name], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let host = extract_string(args.next(), "host")?;
+        let port = args.next();
+        if host.eq_ignore_ascii_case("no") {
+            if let Ok(one) = extract_string(port.clone(), "port") {
+                if one.eq_ignore_ascii_case("one") {
+                    return Ok(ReplicaOf { master: None });
+                }
+            }
+        }
+        match u16::try_from(extract_int(port)?) {
+            Ok(port) => Ok(ReplicaOf {
+                master: Some((host, port)),
+            }),
+            Err(_) => Err(CommandError::InvalidArgument(
+                "Invalid master port".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<RespArray> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_replconf_from_resp_array() -> Result<()> {
+        let frame = decode("*5\r\n$8\r\nREPLCONF\r\n$14\r\nlistening-port\r\n$4\r\n6380\r\n$4\r\ncapa\r\n$6\r\npsync2\r\n")?;
+        let result: ReplConf = frame.try_into()?;
+        assert_eq!(
+            result.options,
+            vec![
+                ReplConfOption::ListeningPort(6380),
+                ReplConfOption::Capa("psync2".to_string())
+            ]
+        );
+
+        let frame = decode("*3\r\n$8\r\nreplconf\r\n$3\r\nACK\r\n$3\r\n120\r\n")?;
+        let result: ReplConf = frame.try_into()?;
+        assert_eq!(result.options, vec![ReplConfOption::Ack(120)]);
+
+        let frame = decode("*3\r\n$8\r\nreplconf\r\n$14\r\nlistening-port\r\n$5\r\n70000\r\n")?;
+        assert!(ReplConf::try_from(frame).is_err());
+        let frame = decode("*3\r\n$8\r\nreplconf\r\n$4\r\nnope\r\n$1\r\n1\r\n")?;
+        assert!(ReplConf::try_from(frame).is_err());
+        let frame = decode("*2\r\n$8\r\nreplconf\r\n$3\r\nack\r\n")?;
+        assert!(ReplConf::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_psync_from_resp_array() -> Result<()> {
+        let frame = decode("*3\r\n$5\r\npsync\r\n$1\r\n?\r\n$2\r\n-1\r\n")?;
+        let result: PSync = frame.try_into()?;
+        assert_eq!(result.replid, "?");
+        assert_eq!(result.offset, -1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicaof_from_resp_array() -> Result<()> {
+        let frame = decode("*3\r\n$9\r\nreplicaof\r\n$9\r\nlocalhost\r\n$4\r\n6380\r\n")?;
+        let result: ReplicaOf = frame.try_into()?;
+        assert_eq!(result.master, Some(("localhost".to_string(), 6380)));
+
+        let frame = decode("*3\r\n$7\r\nSLAVEOF\r\n$2\r\nNO\r\n$3\r\nOne\r\n")?;
+        let result: ReplicaOf = frame.try_into()?;
+        assert_eq!(result.master, None);
+
+        let frame = decode("*3\r\n$9\r\nreplicaof\r\n$2\r\nno\r\n$3\r\ntwo\r\n")?;
+        assert!(ReplicaOf::try_from(frame).is_err());
+        let frame = decode("*3\r\n$9\r\nreplicaof\r\n$1\r\nh\r\n$5\r\n70000\r\n")?;
+        assert!(ReplicaOf::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replconf_command() {
+        let backend = Backend::new();
+        let replconf = ReplConf {
+            options: vec![ReplConfOption::ListeningPort(6380)],
+        };
+        assert_eq!(replconf.apply(&backend, 1), vec![RESP_OK.clone()]);
+        assert!(backend.is_propagating());
+
+        backend.add_replica(1, "127.0.0.1".to_string(), None);
+        let ack = ReplConf {
+            options: vec![ReplConfOption::Ack(5)],
+        };
+        assert!(ack.apply(&backend, 1).is_empty());
+        let report = backend.replication_report();
+        assert_eq!(report.replicas[0].port, 6380);
+        assert_eq!(report.replicas[0].offset, 5);
+
+        let ack = ReplConf {
+            options: vec![ReplConfOption::Ack(8), ReplConfOption::FAck(6)],
+        };
+        assert!(ack.apply(&backend, 1).is_empty());
+        assert_eq!(backend.replication_report().replicas[0].offset, 8);
+        assert_eq!(backend.replicas_aof_acked(6), 1);
+        assert_eq!(backend.replicas_aof_acked(7), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_command() -> Result<()> {
+        let wait: Wait = decode("*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$3\r\n100\r\n")?.try_into()?;
+        assert_eq!((wait.numreplicas, wait.timeout), (1, 100));
+        let frame = decode("*3\r\n$4\r\nwait\r\n$1\r\n1\r\n$2\r\n-1\r\n")?;
+        assert!(Wait::try_from(frame).is_err());
+
+        let backend = Backend::new();
+        let wait = |numreplicas, timeout| Wait {
+            numreplicas,
+            timeout,
+        };
+        assert_eq!(wait(0, 0).block(&backend).await, RespFrame::Integer(0));
+        assert_eq!(wait(1, 20).block(&backend).await, RespFrame::Integer(0));
+
+        let _replica = backend.add_replica(1, "127.0.0.1".to_string(), None);
+        backend.feed_replicas(b"*1\r\n$4\r\nPING\r\n");
+        let offset = backend.master_repl_offset();
+        let waiting = tokio::spawn({
+            let backend = backend.clone();
+            async move { wait(1, 0).block(&backend).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        backend.replica_ack(1, offset, None);
+        assert_eq!(waiting.await?, RespFrame::Integer(1));
+
+        // a second replica never acknowledges
+        backend.replica_ack(1, backend.master_repl_offset(), None);
+        assert_eq!(wait(2, 20).block(&backend).await, RespFrame::Integer(1));
+        Ok(())
+    }
+}
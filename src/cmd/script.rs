@@ -0,0 +1,992 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, resolve_command, validate_command,
+    validate_variadic_command, CommandError, CommandExecutor, Eval, EvalSha, FCall, FCallRo,
+    FunctionDelete, FunctionDump, FunctionFlush, FunctionList, FunctionLoad, FunctionRestore,
+    ScriptExists, ScriptFlush, ScriptKill, ScriptLoad, RESP_OK,
+};
+use crate::{
+    lua_error_message, script_sha1, Backend, BulkString, RespArray, RespFrame, RespMap, RespNull,
+    RespNullBulkString, RestorePolicy, SimpleError, SimpleString,
+};
+use mlua::{HookTriggers, Lua, Value as LuaValue, Variadic};
+use std::fmt;
+
+impl CommandExecutor for Eval {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.cache_script(&self.script);
+        run_script(backend, &self.script, self.keys, self.args)
+    }
+}
+
+impl CommandExecutor for EvalSha {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.cached_script(&self.sha1) {
+            Some(script) => run_script(backend, &script, self.keys, self.args),
+            None => SimpleError::new("NOSCRIPT No matching script. Please use EVAL.").into(),
+        }
+    }
+}
+
+impl CommandExecutor for ScriptLoad {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // compiled, not run, so a broken script isn't cached
+        let compiled = backend.with_lua(|lua| {
+            lua.load(&self.script)
+                .set_name("@user_script")
+                .into_function()
+                .map(drop)
+        });
+        match compiled {
+            Ok(()) => BulkString::new(backend.cache_script(&self.script)).into(),
+            Err(e) => script_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for ScriptExists {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let exists: Vec<RespFrame> = self
+            .sha1s
+            .iter()
+            .map(|sha1| RespFrame::Integer(backend.cached_script(sha1).is_some() as i64))
+            .collect();
+        RespArray::new(exists).into()
+    }
+}
+
+impl CommandExecutor for ScriptFlush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.flush_scripts();
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for ScriptKill {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.script_kill() {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for FunctionLoad {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.function_load(&self.code, self.replace) {
+            Ok(name) => BulkString::new(name).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for FunctionDelete {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.function_delete(&self.library) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for FunctionFlush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        backend.function_flush();
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for FunctionList {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let libraries: Vec<RespFrame> = backend
+            .function_list(self.pattern.as_deref())
+            .into_iter()
+            .map(|library| {
+                let functions: Vec<RespFrame> = library
+                    .functions
+                    .into_iter()
+                    .map(|(name, meta)| {
+                        let mut function = RespMap::new();
+                        function.insert("name".to_string(), BulkString::new(name).into());
+                        function.insert(
+                            "description".to_string(),
+                            match meta.description {
+                                Some(description) => BulkString::new(description).into(),
+                                None => RespNull.into(),
+                            },
+                        );
+                        let flags = meta
+                            .flags
+                            .into_iter()
+                            .map(|flag| BulkString::new(flag).into());
+                        function.insert(
+                            "flags".to_string(),
+                            RespArray::new(flags.collect::<Vec<_>>()).into(),
+                        );
+                        function.into()
+                    })
+                    .collect();
+
+                let mut map = RespMap::new();
+                map.insert(
+                    "library_name".to_string(),
+                    BulkString::new(library.name).into(),
+                );
+                map.insert("engine".to_string(), BulkString::from("LUA").into());
+                map.insert("functions".to_string(), RespArray::new(functions).into());
+                if self.with_code {
+                    map.insert(
+                        "library_code".to_string(),
+                        BulkString::new(library.code).into(),
+                    );
+                }
+                map.into()
+            })
+            .collect();
+        RespArray::new(libraries).into()
+    }
+}
+
+impl CommandExecutor for FunctionDump {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        BulkString::new(backend.function_dump()).into()
+    }
+}
+
+impl CommandExecutor for FunctionRestore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.function_restore(&self.payload, self.policy) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for FCall {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        run_function(backend, &self.function, self.keys, self.args, false)
+    }
+}
+
+impl CommandExecutor for FCallRo {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        run_function(backend, &self.function, self.keys, self.args, true)
+    }
+}
+
+// the error reply of a command called with redis.call, raised to abort the script
+#[derive(Debug)]
+struct ReplyError(String);
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ReplyError {}
+
+// how many Lua instructions run between two checks for SCRIPT KILL
+const KILL_CHECK_INSTRUCTIONS: u32 = 100_000;
+
+/// Run script with its KEYS and ARGV, replying what it returns.
+fn run_script(backend: &Backend, script: &str, keys: Vec<String>, args: Vec<String>) -> RespFrame {
+    backend.with_lua(|lua| {
+        run_lua(backend, lua, false, |lua| {
+            let globals = lua.globals();
+            globals.set("KEYS", keys)?;
+            globals.set("ARGV", args)?;
+            lua.load(script).set_name("@user_script").eval()
+        })
+    })
+}
+
+/// Run the function called name with its keys and args, replying what it returns. FCALL_RO
+/// only runs the functions flagged `no-writes`.
+fn run_function(
+    backend: &Backend,
+    name: &str,
+    keys: Vec<String>,
+    args: Vec<String>,
+    read_only: bool,
+) -> RespFrame {
+    let reply = backend.with_function(name, |lua, function, meta| {
+        if read_only && !meta.is_read_only() {
+            return SimpleError::new(
+                "ERR Can not execute a script with write flag using *_ro command.",
+            )
+            .into();
+        }
+        run_lua(backend, lua, meta.is_read_only(), |_| {
+            function.call((keys, args))
+        })
+    });
+    reply.unwrap_or_else(Into::into)
+}
+
+/// Run some Lua code, replying the value it evaluates to. The code can run commands with
+/// `redis.call`, which aborts it on an error reply, and `redis.pcall`, which returns the
+/// error as a table. Read-only code can't run commands writing the keyspace. SCRIPT KILL
+/// aborts the code.
+fn run_lua<'lua>(
+    backend: &Backend,
+    lua: &'lua Lua,
+    read_only: bool,
+    code: impl FnOnce(&'lua Lua) -> mlua::Result<LuaValue<'lua>>,
+) -> RespFrame {
+    let killed = backend.clone();
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(KILL_CHECK_INSTRUCTIONS),
+        move |_, _| match killed.is_script_killed() {
+            true => Err(mlua::Error::external(ReplyError(
+                "ERR Script killed by user with SCRIPT KILL...".to_string(),
+            ))),
+            false => Ok(()),
+        },
+    );
+    let result = backend.run_busy_script(|| run_scoped(backend, lua, read_only, code));
+    // the hook holds the backend, which holds the interpreter
+    lua.remove_hook();
+    result.unwrap_or_else(script_error)
+}
+
+// run the code with the redis library, its calls scoped to the code
+fn run_scoped<'lua>(
+    backend: &Backend,
+    lua: &'lua Lua,
+    read_only: bool,
+    code: impl FnOnce(&'lua Lua) -> mlua::Result<LuaValue<'lua>>,
+) -> mlua::Result<RespFrame> {
+    lua.scope(|scope| {
+        let redis = lua.create_table()?;
+        let redis_call = scope.create_function(|lua, args: Variadic<LuaValue>| {
+            match call(backend, args, read_only)? {
+                RespFrame::Error(e) => Err(mlua::Error::external(ReplyError(e.to_string()))),
+                frame => to_lua(lua, frame),
+            }
+        })?;
+        let redis_pcall = scope.create_function(|lua, args: Variadic<LuaValue>| {
+            to_lua(lua, call(backend, args, read_only)?)
+        })?;
+        let status_reply = lua
+            .create_function(|lua, status: mlua::String| lua.create_table_from([("ok", status)]))?;
+        let error_reply = lua
+            .create_function(|lua, error: mlua::String| lua.create_table_from([("err", error)]))?;
+        let sha1hex =
+            lua.create_function(|_, body: mlua::String| Ok(script_sha1(body.to_str()?)))?;
+        redis.set("call", redis_call)?;
+        redis.set("pcall", redis_pcall)?;
+        redis.set("status_reply", status_reply)?;
+        redis.set("error_reply", error_reply)?;
+        redis.set("sha1hex", sha1hex)?;
+
+        lua.globals().set("redis", redis)?;
+        Ok(from_lua(code(lua)?))
+    })
+}
+
+// run the command of a redis.call, replying an error for one a script can't call
+fn call(backend: &Backend, args: Variadic<LuaValue>, read_only: bool) -> mlua::Result<RespFrame> {
+    if args.is_empty() {
+        return Err(mlua::Error::runtime(
+            "Please specify at least one argument for this redis lib call",
+        ));
+    }
+    let args = args
+        .into_iter()
+        .map(|arg| match arg {
+            LuaValue::String(s) => Ok(BulkString::from(s.as_bytes()).into()),
+            LuaValue::Integer(n) => Ok(BulkString::new(n.to_string()).into()),
+            LuaValue::Number(n) => Ok(BulkString::new(n.to_string()).into()),
+            _ => Err(mlua::Error::runtime(
+                "Lua redis lib command arguments must be strings or integers",
+            )),
+        })
+        .collect::<mlua::Result<Vec<RespFrame>>>()?;
+
+    let request = match resolve_command(backend, RespArray::new(args)) {
+        Ok(request) => request,
+        Err(e) => return Ok(SimpleError::new(format!("ERR {}", e)).into()),
+    };
+    // the commands a script calls are propagated, not the script
+    let propagated = backend.is_propagating().then(|| request.clone());
+    let reply = match backend.parse_command(request) {
+        Ok(cmd) if !cmd.is_allowed_in_script() => {
+            SimpleError::new("ERR This Redis command is not allowed from script").into()
+        }
+        Ok(cmd) if read_only && !cmd.is_read_only() => {
+            SimpleError::new("ERR Write commands are not allowed from read-only scripts.").into()
+        }
+        Ok(cmd) if backend.is_replica() && cmd.is_propagated() => {
+            SimpleError::new("READONLY You can't write against a read only replica.").into()
+        }
+        Ok(cmd) => {
+            if cmd.is_propagated() {
+                backend.script_wrote();
+            }
+            cmd.execute_propagating(backend, propagated)
+        }
+        Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+    };
+    Ok(reply)
+}
+
+// a reply as scripts see it: a status or an error is a table with an `ok` or an `err`
+// field, a null is false
+fn to_lua(lua: &Lua, frame: RespFrame) -> mlua::Result<LuaValue<'_>> {
+    let value = match frame.into_resp2() {
+        RespFrame::Integer(n) => LuaValue::Integer(n),
+        RespFrame::BulkString(s) => LuaValue::String(lua.create_string(&*s)?),
+        RespFrame::SimpleString(s) => {
+            LuaValue::Table(lua.create_table_from([("ok", s.to_string())])?)
+        }
+        RespFrame::Error(e) => LuaValue::Table(lua.create_table_from([("err", e.to_string())])?),
+        RespFrame::Array(array) => {
+            let values = array
+                .iter()
+                .map(|frame| to_lua(lua, frame.clone()))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            LuaValue::Table(lua.create_sequence_from(values)?)
+        }
+        RespFrame::NullBulkString(_) | RespFrame::NullArray(_) => LuaValue::Boolean(false),
+        frame => unreachable!("not a RESP2 frame: {:?}", frame),
+    };
+    Ok(value)
+}
+
+// the reply of what a script returns: numbers are truncated to integers, and an array
+// stops at its first nil
+fn from_lua(value: LuaValue) -> RespFrame {
+    match value {
+        LuaValue::Boolean(true) => RespFrame::Integer(1),
+        LuaValue::Integer(n) => RespFrame::Integer(n),
+        LuaValue::Number(n) => RespFrame::Integer(n as i64),
+        LuaValue::String(s) => BulkString::from(s.as_bytes()).into(),
+        LuaValue::Table(table) => {
+            if let Ok(LuaValue::String(error)) = table.raw_get("err") {
+                return SimpleError::new(error.to_string_lossy()).into();
+            }
+            if let Ok(LuaValue::String(status)) = table.raw_get("ok") {
+                return SimpleString::new(status.to_string_lossy()).into();
+            }
+            let frames: Vec<RespFrame> = table
+                .sequence_values::<LuaValue>()
+                .map_while(Result::ok)
+                .map(from_lua)
+                .collect();
+            RespArray::new(frames).into()
+        }
+        _ => RespNullBulkString.into(),
+    }
+}
+
+// the error of a command called with redis.call as is, any other one as a script error
+fn script_error(e: mlua::Error) -> RespFrame {
+    fn reply_error(e: &mlua::Error) -> Option<&ReplyError> {
+        match e {
+            mlua::Error::CallbackError { cause, .. } => reply_error(cause),
+            mlua::Error::ExternalError(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    match reply_error(&e) {
+        Some(ReplyError(message)) => SimpleError::new(message.clone()).into(),
+        None => SimpleError::new(format!(
+            "ERR Error running script: {}",
+            lua_error_message(&e)
+        ))
+        .into(),
+    }
+}
+
+// EVAL script numkeys [key [key ...]] [arg [arg ...]]
+impl TryFrom<RespArray> for Eval {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["eval"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let script = extract_string(args.next(), "script")?;
+        let (keys, args) = extract_keys_and_args(args)?;
+        Ok(Eval { script, keys, args })
+    }
+}
+
+// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+impl TryFrom<RespArray> for EvalSha {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["evalsha"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sha1 = extract_string(args.next(), "sha1")?;
+        let (keys, args) = extract_keys_and_args(args)?;
+        Ok(EvalSha { sha1, keys, args })
+    }
+}
+
+// SCRIPT LOAD script
+impl TryFrom<RespArray> for ScriptLoad {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["script", "load"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let script = extract_string(args.next(), "script")?;
+        Ok(ScriptLoad { script })
+    }
+}
+
+// SCRIPT EXISTS sha1 [sha1 ...]
+impl TryFrom<RespArray> for ScriptExists {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["script", "exists"], 1)?;
+
+        let sha1s = extract_args(value, 2)?
+            .into_iter()
+            .map(|sha1| extract_string(Some(sha1), "sha1"))
+            .collect::<Result<_, _>>()?;
+        Ok(ScriptExists { sha1s })
+    }
+}
+
+// SCRIPT FLUSH [ASYNC | SYNC], flushing synchronously either way
+impl TryFrom<RespArray> for ScriptFlush {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["script", "flush"], 0)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        match args.next() {
+            None => {}
+            Some(mode) => match extract_string(Some(mode), "mode")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "async" | "sync" => {}
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            },
+        }
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(ScriptFlush)
+    }
+}
+
+// SCRIPT KILL
+impl TryFrom<RespArray> for ScriptKill {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["script", "kill"], 0)?;
+        Ok(ScriptKill)
+    }
+}
+
+// FUNCTION LOAD [REPLACE] function-code
+impl TryFrom<RespArray> for FunctionLoad {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["function", "load"], 1)?;
+
+        let mut args = extract_args(value, 2)?;
+        let code = extract_string(args.pop(), "function code")?;
+        let replace = match args.len() {
+            0 => false,
+            1 if extract_string(args.pop(), "option")?.eq_ignore_ascii_case("replace") => true,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(FunctionLoad { code, replace })
+    }
+}
+
+// FUNCTION DELETE library-name
+impl TryFrom<RespArray> for FunctionDelete {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["function", "delete"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let library = extract_string(args.next(), "library name")?;
+        Ok(FunctionDelete { library })
+    }
+}
+
+// FUNCTION FLUSH [ASYNC | SYNC], flushing synchronously either way
+impl TryFrom<RespArray> for FunctionFlush {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["function", "flush"], 0)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        match args.next() {
+            None => {}
+            Some(mode) => match extract_string(Some(mode), "mode")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "async" | "sync" => {}
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            },
+        }
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(FunctionFlush)
+    }
+}
+
+// FUNCTION LIST [LIBRARYNAME library-name-pattern] [WITHCODE]
+impl TryFrom<RespArray> for FunctionList {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["function", "list"], 0)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let mut pattern = None;
+        let mut with_code = false;
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "libraryname" if pattern.is_none() => {
+                    pattern = Some(extract_string(args.next(), "library name pattern")?)
+                }
+                "withcode" if !with_code => with_code = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(FunctionList { pattern, with_code })
+    }
+}
+
+// FUNCTION DUMP
+impl TryFrom<RespArray> for FunctionDump {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["function", "dump"], 0)?;
+        Ok(FunctionDump)
+    }
+}
+
+// FUNCTION RESTORE serialized-value [FLUSH | APPEND | REPLACE]
+impl TryFrom<RespArray> for FunctionRestore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["function", "restore"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let payload = match args.next() {
+            Some(RespFrame::BulkString(payload)) => payload.to_vec(),
+            _ => return Err(CommandError::InvalidArgument("Invalid payload".to_string())),
+        };
+        let policy = match args.next() {
+            None => RestorePolicy::default(),
+            Some(policy) => match extract_string(Some(policy), "policy")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "append" => RestorePolicy::Append,
+                "replace" => RestorePolicy::Replace,
+                "flush" => RestorePolicy::Flush,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            },
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(FunctionRestore { payload, policy })
+    }
+}
+
+// FCALL function numkeys [key [key ...]] [arg [arg ...]]
+impl TryFrom<RespArray> for FCall {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["fcall"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let function = extract_string(args.next(), "function")?;
+        let (keys, args) = extract_keys_and_args(args)?;
+        Ok(FCall {
+            function,
+            keys,
+            args,
+        })
+    }
+}
+
+// FCALL_RO function numkeys [key [key ...]] [arg [arg ...]]
+impl TryFrom<RespArray> for FCallRo {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["fcall_ro"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let function = extract_string(args.next(), "function")?;
+        let (keys, args) = extract_keys_and_args(args)?;
+        Ok(FCallRo {
+            function,
+            keys,
+            args,
+        })
+    }
+}
+
+fn extract_keys_and_args(
+    mut args: impl ExactSizeIterator<Item = RespFrame>,
+) -> Result<(Vec<String>, Vec<String>), CommandError> {
+    let numkeys = extract_int(args.next())?;
+    if numkeys < 0 {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be negative".to_string(),
+        ));
+    }
+    if numkeys as usize > args.len() {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be greater than number of args".to_string(),
+        ));
+    }
+
+    let mut strings = args.map(|arg| extract_string(Some(arg), "argument"));
+    let keys = strings
+        .by_ref()
+        .take(numkeys as usize)
+        .collect::<Result<_, _>>()?;
+    let args = strings.collect::<Result<_, _>>()?;
+    Ok((keys, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Shutdown;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn eval(script: &str, keys: &[&str], args: &[&str]) -> Eval {
+        Eval {
+            script: script.to_string(),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_eval_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::from(
+            "*5\r\n$4\r\neval\r\n$8\r\nreturn 1\r\n$1\r\n1\r\n$1\r\nk\r\n$1\r\na\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Eval = frame.try_into()?;
+        assert_eq!(result.script, "return 1");
+        assert_eq!(result.keys, vec!["k"]);
+        assert_eq!(result.args, vec!["a"]);
+
+        let mut buf =
+            BytesMut::from("*4\r\n$4\r\neval\r\n$8\r\nreturn 1\r\n$1\r\n2\r\n$1\r\nk\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Eval::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_conversions() {
+        let backend = Backend::new();
+        let script = "return {1, 'two', 3.99, true, false, {ok='fine'}, nil, 'lost'}";
+        assert_eq!(
+            eval(script, &[], &[]).execute(&backend),
+            RespArray::new(vec![
+                RespFrame::Integer(1),
+                BulkString::from("two").into(),
+                RespFrame::Integer(3),
+                RespFrame::Integer(1),
+                RespNullBulkString.into(),
+                SimpleString::new("fine").into(),
+            ])
+            .into()
+        );
+        assert_eq!(
+            eval("return redis.error_reply('MY err')", &[], &[]).execute(&backend),
+            SimpleError::new("MY err").into()
+        );
+        assert_eq!(
+            eval("return {KEYS[1], ARGV[2]}", &["k"], &["a", "b"]).execute(&backend),
+            RespArray::new(vec![
+                BulkString::from("k").into(),
+                BulkString::from("b").into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_eval_call() {
+        let backend = Backend::new();
+        let script = "redis.call('SET', KEYS[1], ARGV[1]); return redis.call('INCRBY', KEYS[1], 5)";
+        assert_eq!(
+            eval(script, &["k"], &["10"]).execute(&backend),
+            RespFrame::Integer(15)
+        );
+
+        // redis.call aborts with the error reply, redis.pcall returns it
+        backend
+            .lpush("list", vec![BulkString::from("a").into()])
+            .unwrap();
+        assert_eq!(
+            eval("redis.call('INCR', 'list'); return 1", &[], &[]).execute(&backend),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+        let script = "local reply = redis.pcall('INCR', 'list'); return reply['err'] ~= nil";
+        assert_eq!(
+            eval(script, &[], &[]).execute(&backend),
+            RespFrame::Integer(1)
+        );
+
+        let reply = eval("return redis.call('EVAL', 'return 1', 0)", &[], &[]).execute(&backend);
+        assert_eq!(
+            reply,
+            SimpleError::new("ERR This Redis command is not allowed from script").into()
+        );
+        let RespFrame::Error(e) = eval("return +", &[], &[]).execute(&backend) else {
+            panic!("expected a script error");
+        };
+        assert!(e.starts_with("ERR Error running script"));
+    }
+
+    #[test]
+    fn test_evalsha() {
+        let backend = Backend::new();
+        let sha1 = "e0e1f9fabfc9d4800c877a703b823ac0578ff8db".to_string();
+        let evalsha = || EvalSha {
+            sha1: sha1.clone(),
+            keys: vec![],
+            args: vec![],
+        };
+        assert_eq!(
+            evalsha().execute(&backend),
+            SimpleError::new("NOSCRIPT No matching script. Please use EVAL.").into()
+        );
+        eval("return 1", &[], &[]).execute(&backend);
+        assert_eq!(evalsha().execute(&backend), RespFrame::Integer(1));
+    }
+
+    #[test]
+    fn test_script_commands() {
+        let backend = Backend::new();
+        let sha1 = "e0e1f9fabfc9d4800c877a703b823ac0578ff8db";
+        let exists = || {
+            ScriptExists {
+                sha1s: vec![sha1.to_string(), "ffff".to_string()],
+            }
+            .execute(&backend)
+        };
+        let load = ScriptLoad {
+            script: "return 1".to_string(),
+        };
+        assert_eq!(load.execute(&backend), BulkString::from(sha1).into());
+        assert_eq!(
+            exists(),
+            RespArray::new(vec![RespFrame::Integer(1), RespFrame::Integer(0)]).into()
+        );
+
+        // not cached when it doesn't compile
+        let load = ScriptLoad {
+            script: "return +".to_string(),
+        };
+        assert!(matches!(load.execute(&backend), RespFrame::Error(_)));
+
+        assert_eq!(ScriptFlush.execute(&backend), RESP_OK.clone());
+        assert_eq!(
+            exists(),
+            RespArray::new(vec![RespFrame::Integer(0), RespFrame::Integer(0)]).into()
+        );
+    }
+
+    const LIBRARY: &str = "#!lua name=mylib\n\
+        redis.register_function('set', function(keys, args) return redis.call('SET', keys[1], args[1]) end)\n\
+        redis.register_function{function_name='get', callback=function(keys) return redis.call('GET', keys[1]) end, flags={'no-writes'}}";
+
+    fn load(backend: &Backend) -> RespFrame {
+        FunctionLoad {
+            code: LIBRARY.to_string(),
+            replace: false,
+        }
+        .execute(backend)
+    }
+
+    #[test]
+    fn test_function_load_from_resp_array() -> Result<()> {
+        let mut buf =
+            BytesMut::from("*4\r\n$8\r\nfunction\r\n$4\r\nload\r\n$7\r\nREPLACE\r\n$4\r\ncode\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: FunctionLoad = frame.try_into()?;
+        assert_eq!(result.code, "code");
+        assert!(result.replace);
+
+        let mut buf = BytesMut::from(
+            "*4\r\n$8\r\nfunction\r\n$4\r\nlist\r\n$11\r\nlibraryname\r\n$8\r\nwithcode\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: FunctionList = frame.try_into()?;
+        assert_eq!(result.pattern.as_deref(), Some("withcode"));
+        assert!(!result.with_code);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fcall() {
+        let backend = Backend::new();
+        assert_eq!(load(&backend), BulkString::from("mylib").into());
+        assert_eq!(
+            load(&backend),
+            SimpleError::new("ERR Library 'mylib' already exists").into()
+        );
+
+        let fcall = FCall {
+            function: "set".to_string(),
+            keys: vec!["k".to_string()],
+            args: vec!["v".to_string()],
+        };
+        assert_eq!(fcall.execute(&backend), SimpleString::new("OK").into());
+        let fcall_ro = |function: &str| FCallRo {
+            function: function.to_string(),
+            keys: vec!["k".to_string()],
+            args: vec!["w".to_string()],
+        };
+        assert_eq!(
+            fcall_ro("get").execute(&backend),
+            BulkString::from("v").into()
+        );
+        assert_eq!(
+            fcall_ro("set").execute(&backend),
+            SimpleError::new("ERR Can not execute a script with write flag using *_ro command.")
+                .into()
+        );
+        assert_eq!(
+            fcall_ro("missing").execute(&backend),
+            SimpleError::new("ERR Function not found").into()
+        );
+
+        // functions outlive the script cache
+        ScriptFlush.execute(&backend);
+        assert_eq!(
+            fcall_ro("get").execute(&backend),
+            BulkString::from("v").into()
+        );
+    }
+
+    #[test]
+    fn test_function_commands() {
+        let backend = Backend::new();
+        load(&backend);
+
+        let list = FunctionList {
+            pattern: Some("my*".to_string()),
+            with_code: true,
+        };
+        let RespFrame::Array(libraries) = list.execute(&backend) else {
+            panic!("expected an array");
+        };
+        let RespFrame::Map(library) = &libraries[0] else {
+            panic!("expected a map");
+        };
+        assert_eq!(library["library_name"], BulkString::from("mylib").into());
+        assert_eq!(library["library_code"], BulkString::from(LIBRARY).into());
+        let RespFrame::Array(functions) = &library["functions"] else {
+            panic!("expected an array");
+        };
+        assert_eq!(functions.len(), 2);
+
+        let RespFrame::BulkString(payload) = FunctionDump.execute(&backend) else {
+            panic!("expected a bulk string");
+        };
+        let delete = || {
+            FunctionDelete {
+                library: "mylib".to_string(),
+            }
+            .execute(&backend)
+        };
+        assert_eq!(delete(), RESP_OK.clone());
+        assert_eq!(delete(), SimpleError::new("ERR Library not found").into());
+
+        let restore = FunctionRestore {
+            payload: payload.to_vec(),
+            policy: RestorePolicy::Append,
+        };
+        assert_eq!(restore.execute(&backend), RESP_OK.clone());
+        assert_eq!(FunctionFlush.execute(&backend), RESP_OK.clone());
+        assert_eq!(
+            FunctionList {
+                pattern: None,
+                with_code: false,
+            }
+            .execute(&backend),
+            RespArray::new(vec![]).into()
+        );
+    }
+
+    // run script while another thread waits for it to be busy, then stops it with stop
+    fn run_stopped(script: &str, stop: fn(&Backend) -> RespFrame) -> (RespFrame, RespFrame) {
+        let backend = Backend::new();
+        backend.set_busy_script_time_limit(0);
+        let stopper = backend.clone();
+        let stopped = std::thread::spawn(move || {
+            while !stopper.is_script_busy() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            stop(&stopper)
+        });
+        let reply = eval(script, &["k"], &[]).execute(&backend);
+        (reply, stopped.join().unwrap())
+    }
+
+    #[test]
+    fn test_script_kill() {
+        let killed = SimpleError::new("ERR Script killed by user with SCRIPT KILL...").into();
+        let kill = |backend: &Backend| ScriptKill.execute(backend);
+        assert_eq!(
+            run_stopped("while true do end", kill),
+            (killed, RESP_OK.clone())
+        );
+        assert!(matches!(
+            ScriptKill.execute(&Backend::new()),
+            RespFrame::Error(e) if e.starts_with("NOTBUSY")
+        ));
+
+        // only SHUTDOWN NOSAVE stops a script that wrote
+        let script = "redis.call('SET', KEYS[1], 'v') while true do end";
+        let kill_then_shutdown = |backend: &Backend| {
+            // once it wrote, the script may be busy before
+            while !backend.contains_key("k") {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            let reply = ScriptKill.execute(backend);
+            Shutdown { save: Some(false) }.execute(backend);
+            reply
+        };
+        let (reply, killed) = run_stopped(script, kill_then_shutdown);
+        assert!(matches!(reply, RespFrame::Error(_)));
+        assert!(matches!(killed, RespFrame::Error(e) if e.starts_with("UNKILLABLE")));
+    }
+}
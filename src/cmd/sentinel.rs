@@ -0,0 +1,449 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    CommandError, CommandExecutor, SentinelFailover, SentinelGetMasterAddrByName,
+    SentinelIsMasterDownByAddr, SentinelMaster, SentinelMasters, SentinelMonitor, SentinelMyId,
+    SentinelRemove, SentinelReplicas, SentinelSentinels, SentinelSet, RESP_OK,
+};
+use crate::{
+    Backend, BackendError, BulkString, RespArray, RespFrame, RespMap, RespNullArray,
+    SentinelInstanceReport, SentinelMasterReport,
+};
+
+impl CommandExecutor for SentinelGetMasterAddrByName {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sentinel_master_addr(&self.name) {
+            Some(addr) => RespArray::new(vec![
+                BulkString::from(addr.host.as_str()).into(),
+                BulkString::from(addr.port.to_string().as_str()).into(),
+            ])
+            .into(),
+            None => RespFrame::NullArray(RespNullArray),
+        }
+    }
+}
+
+impl CommandExecutor for SentinelMasters {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let masters: Vec<RespFrame> = backend.sentinel_masters().iter().map(master).collect();
+        RespArray::new(masters).into()
+    }
+}
+
+impl CommandExecutor for SentinelMaster {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let masters = backend.sentinel_masters();
+        match masters.iter().find(|report| report.name == self.name) {
+            Some(report) => master(report),
+            None => BackendError::NoSuchMaster.into(),
+        }
+    }
+}
+
+fn master(report: &SentinelMasterReport) -> RespFrame {
+    let mut map = RespMap::new();
+    let mut bulk = |name: &str, value: &str| {
+        map.insert(name.to_string(), BulkString::from(value).into());
+    };
+    bulk("name", &report.name);
+    bulk("ip", &report.addr.host);
+    bulk("port", &report.addr.port.to_string());
+    bulk("flags", &report.flags);
+    bulk("last-ok-ping-reply", &report.last_ok_ping_reply.to_string());
+    bulk("quorum", &report.quorum.to_string());
+    bulk("down-after-milliseconds", &report.down_after.to_string());
+    bulk("failover-timeout", &report.failover_timeout.to_string());
+    bulk("config-epoch", &report.config_epoch.to_string());
+    bulk("num-slaves", &report.num_replicas.to_string());
+    bulk(
+        "num-other-sentinels",
+        &report.num_other_sentinels.to_string(),
+    );
+    map.into()
+}
+
+impl CommandExecutor for SentinelReplicas {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sentinel_replicas(&self.name) {
+            Some(replicas) => {
+                RespArray::new(replicas.iter().map(instance).collect::<Vec<_>>()).into()
+            }
+            None => BackendError::NoSuchMaster.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SentinelSentinels {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sentinel_sentinels(&self.name) {
+            Some(sentinels) => {
+                RespArray::new(sentinels.iter().map(instance).collect::<Vec<_>>()).into()
+            }
+            None => BackendError::NoSuchMaster.into(),
+        }
+    }
+}
+
+// a replica, with its offset, or a sentinel, with its run ID
+fn instance(report: &SentinelInstanceReport) -> RespFrame {
+    let mut map = RespMap::new();
+    let mut bulk = |name: &str, value: &str| {
+        map.insert(name.to_string(), BulkString::from(value).into());
+    };
+    bulk("name", &report.addr.to_string());
+    bulk("ip", &report.addr.host);
+    bulk("port", &report.addr.port.to_string());
+    bulk("flags", &report.flags);
+    match report.runid.is_empty() {
+        true => {
+            bulk("last-ok-ping-reply", &report.last_seen.to_string());
+            bulk("slave-repl-offset", &report.offset.to_string());
+        }
+        false => {
+            bulk("runid", &report.runid);
+            bulk("last-hello-message", &report.last_seen.to_string());
+        }
+    }
+    map.into()
+}
+
+impl CommandExecutor for SentinelIsMasterDownByAddr {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let (down, leader, epoch) =
+            backend.sentinel_is_master_down_by_addr(&self.host, self.port, self.epoch, &self.runid);
+        let leader = leader.unwrap_or_else(|| "*".to_string());
+        RespArray::new(vec![
+            RespFrame::Integer(down as i64),
+            BulkString::from(leader.as_str()).into(),
+            RespFrame::Integer(epoch as i64),
+        ])
+        .into()
+    }
+}
+
+impl CommandExecutor for SentinelMonitor {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sentinel_monitor(&self.name, &self.host, self.port, self.quorum) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SentinelRemove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sentinel_remove(&self.name) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SentinelSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        for (option, value) in &self.options {
+            if let Err(e) = backend.sentinel_set(&self.name, option, value) {
+                return e.into();
+            }
+        }
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for SentinelFailover {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sentinel_failover(&self.name) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SentinelMyId {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        BulkString::from(backend.sentinel_myid().as_str()).into()
+    }
+}
+
+// SENTINEL GET-MASTER-ADDR-BY-NAME name
+impl TryFrom<RespArray> for SentinelGetMasterAddrByName {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "get-master-addr-by-name"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(SentinelGetMasterAddrByName {
+            name: extract_string(args.next(), "master name")?,
+        })
+    }
+}
+
+// SENTINEL MASTERS
+impl TryFrom<RespArray> for SentinelMasters {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "masters"], 0)?;
+        Ok(SentinelMasters)
+    }
+}
+
+// SENTINEL MASTER name
+impl TryFrom<RespArray> for SentinelMaster {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "master"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(SentinelMaster {
+            name: extract_string(args.next(), "master name")?,
+        })
+    }
+}
+
+// SENTINEL REPLICAS name, or SENTINEL SLAVES name
+impl TryFrom<RespArray> for SentinelReplicas {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() != 3 {
+            return Err(CommandError::InvalidArgument(
+                "sentinel replicas command must have exactly 1 arguments".to_string(),
+            ));
+        }
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(SentinelReplicas {
+            name: extract_string(args.next(), "master name")?,
+        })
+    }
+}
+
+// SENTINEL SENTINELS name
+impl TryFrom<RespArray> for SentinelSentinels {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "sentinels"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(SentinelSentinels {
+            name: extract_string(args.next(), "master name")?,
+        })
+    }
+}
+
+// SENTINEL IS-MASTER-DOWN-BY-ADDR ip port current-epoch runid
+impl TryFrom<RespArray> for SentinelIsMasterDownByAddr {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "is-master-down-by-addr"], 4)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        let host = extract_string(args.next(), "ip")?;
+        let port = extract_port(args.next())?;
+        let epoch = match extract_int(args.next())? {
+            epoch if epoch >= 0 => epoch as u64,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid current epoch".to_string(),
+                ))
+            }
+        };
+        Ok(SentinelIsMasterDownByAddr {
+            host,
+            port,
+            epoch,
+            runid: extract_string(args.next(), "runid")?,
+        })
+    }
+}
+
+// SENTINEL MONITOR name ip port quorum
+impl TryFrom<RespArray> for SentinelMonitor {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "monitor"], 4)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        let name = extract_string(args.next(), "master name")?;
+        let host = extract_string(args.next(), "ip")?;
+        let port = extract_port(args.next())?;
+        let quorum = match extract_int(args.next())? {
+            quorum if quorum > 0 => quorum as usize,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Quorum must be 1 or greater.".to_string(),
+                ))
+            }
+        };
+        Ok(SentinelMonitor {
+            name,
+            host,
+            port,
+            quorum,
+        })
+    }
+}
+
+// SENTINEL REMOVE name
+impl TryFrom<RespArray> for SentinelRemove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "remove"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(SentinelRemove {
+            name: extract_string(args.next(), "master name")?,
+        })
+    }
+}
+
+// SENTINEL SET name option value [option value ...]
+impl TryFrom<RespArray> for SentinelSet {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["sentinel", "set"], 3)?;
+        if value.len().is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for SENTINEL SET".to_string(),
+            ));
+        }
+        let mut args = extract_args(value, 2)?.into_iter();
+        let name = extract_string(args.next(), "master name")?;
+        let mut options = Vec::new();
+        while let Some(option) = args.next() {
+            let option = extract_string(Some(option), "option")?;
+            options.push((option, extract_string(args.next(), "value")?));
+        }
+        Ok(SentinelSet { name, options })
+    }
+}
+
+// SENTINEL FAILOVER name
+impl TryFrom<RespArray> for SentinelFailover {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "failover"], 1)?;
+        let mut args = extract_args(value, 2)?.into_iter();
+        Ok(SentinelFailover {
+            name: extract_string(args.next(), "master name")?,
+        })
+    }
+}
+
+// SENTINEL MYID
+impl TryFrom<RespArray> for SentinelMyId {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sentinel", "myid"], 0)?;
+        Ok(SentinelMyId)
+    }
+}
+
+fn extract_port(frame: Option<RespFrame>) -> Result<u16, CommandError> {
+    match extract_int(frame) {
+        Ok(port) if (1..=u16::MAX as i64).contains(&port) => Ok(port as u16),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid port number".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn run(backend: &Backend, s: &str) -> Result<RespFrame> {
+        let mut buf = BytesMut::from(s);
+        let cmd = Command::try_from(RespArray::decode(&mut buf)?)?;
+        Ok(cmd.execute(backend))
+    }
+
+    fn get(frame: &RespFrame, name: &str) -> String {
+        match frame {
+            RespFrame::Map(map) => match &map.0[name] {
+                RespFrame::BulkString(s) => String::from_utf8_lossy(s).into_owned(),
+                frame => panic!("not a bulk string: {:?}", frame),
+            },
+            frame => panic!("not a map: {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_sentinel_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.enable_sentinel();
+
+        let monitor = "*6\r\n$8\r\nsentinel\r\n$7\r\nmonitor\r\n$8\r\nmymaster\r\n$9\r\n127.0.0.1\r\n$4\r\n6380\r\n$1\r\n2\r\n";
+        assert_eq!(run(&backend, monitor)?, RESP_OK.clone());
+        assert_eq!(
+            run(&backend, monitor)?,
+            BackendError::DuplicatedMaster.into()
+        );
+
+        let addr = run(
+            &backend,
+            "*3\r\n$8\r\nsentinel\r\n$23\r\nget-master-addr-by-name\r\n$8\r\nmymaster\r\n",
+        )?;
+        assert_eq!(
+            addr,
+            RespArray::new(vec![
+                BulkString::from("127.0.0.1").into(),
+                BulkString::from("6380").into(),
+            ])
+            .into()
+        );
+        let unknown = run(
+            &backend,
+            "*3\r\n$8\r\nsentinel\r\n$23\r\nget-master-addr-by-name\r\n$5\r\nother\r\n",
+        )?;
+        assert_eq!(unknown, RespFrame::NullArray(RespNullArray));
+
+        let set = "*5\r\n$8\r\nsentinel\r\n$3\r\nset\r\n$8\r\nmymaster\r\n$23\r\ndown-after-milliseconds\r\n$4\r\n5000\r\n";
+        assert_eq!(run(&backend, set)?, RESP_OK.clone());
+        let master = run(
+            &backend,
+            "*3\r\n$8\r\nsentinel\r\n$6\r\nmaster\r\n$8\r\nmymaster\r\n",
+        )?;
+        assert_eq!(get(&master, "down-after-milliseconds"), "5000");
+        assert_eq!(get(&master, "quorum"), "2");
+        assert_eq!(get(&master, "flags"), "master");
+
+        // nobody to promote
+        let failover = "*3\r\n$8\r\nsentinel\r\n$8\r\nfailover\r\n$8\r\nmymaster\r\n";
+        assert_eq!(run(&backend, failover)?, BackendError::NoGoodReplica.into());
+
+        // a vote per epoch, for the first to ask
+        let ask = |runid: &str, epoch: u64| {
+            format!(
+                "*6\r\n$8\r\nsentinel\r\n$22\r\nis-master-down-by-addr\r\n$9\r\n127.0.0.1\r\n$4\r\n6380\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+                epoch.to_string().len(),
+                epoch,
+                runid.len(),
+                runid
+            )
+        };
+        let vote = |leader: &str, epoch: i64| -> RespFrame {
+            RespArray::new(vec![
+                RespFrame::Integer(0),
+                BulkString::from(leader).into(),
+                RespFrame::Integer(epoch),
+            ])
+            .into()
+        };
+        assert_eq!(run(&backend, &ask("*", 0))?, vote("*", 0));
+        assert_eq!(run(&backend, &ask("a", 1))?, vote("a", 1));
+        assert_eq!(run(&backend, &ask("b", 1))?, vote("a", 1));
+        assert_eq!(run(&backend, &ask("b", 2))?, vote("b", 2));
+
+        let remove = "*3\r\n$8\r\nsentinel\r\n$6\r\nremove\r\n$8\r\nmymaster\r\n";
+        assert_eq!(run(&backend, remove)?, RESP_OK.clone());
+        assert_eq!(run(&backend, remove)?, BackendError::NoSuchMaster.into());
+        Ok(())
+    }
+}
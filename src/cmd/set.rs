@@ -0,0 +1,550 @@
+use crate::cmd::{
+    extract_args, extract_cursor, extract_int, extract_key_and_count, extract_string, scan_reply,
+    validate_command, validate_variadic_command, CommandError, CommandExecutor, SAdd, SCard, SDiff,
+    SDiffStore, SInter, SInterStore, SIsMember, SMIsMember, SMembers, SMove, SPop, SRandMember,
+    SRem, SScan, SUnion, SUnionStore,
+};
+use crate::{
+    glob_match, Backend, BackendError, BulkString, RespArray, RespFrame, RespNull, RespSet, SetOp,
+};
+
+impl CommandExecutor for SAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.sadd(&self.key, self.members))
+    }
+}
+
+impl CommandExecutor for SRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.srem(&self.key, &self.members))
+    }
+}
+
+impl CommandExecutor for SMembers {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.smembers(&self.key) {
+            Ok(members) => RespSet::new(members_reply(members)).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.scard(&self.key))
+    }
+}
+
+impl CommandExecutor for SIsMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.sismember(&self.key, &self.member) {
+            Ok(found) => RespFrame::Integer(found as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SMIsMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.smismember(&self.key, &self.members) {
+            Ok(found) => RespArray::new(
+                found
+                    .into_iter()
+                    .map(|found| RespFrame::Integer(found as i64))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SInter {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        set_op_reply(backend.set_op(SetOp::Inter, &self.keys))
+    }
+}
+
+impl CommandExecutor for SUnion {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        set_op_reply(backend.set_op(SetOp::Union, &self.keys))
+    }
+}
+
+impl CommandExecutor for SDiff {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        set_op_reply(backend.set_op(SetOp::Diff, &self.keys))
+    }
+}
+
+impl CommandExecutor for SInterStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.set_op_store(SetOp::Inter, &self.destination, &self.keys))
+    }
+}
+
+impl CommandExecutor for SUnionStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.set_op_store(SetOp::Union, &self.destination, &self.keys))
+    }
+}
+
+impl CommandExecutor for SDiffStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.set_op_store(SetOp::Diff, &self.destination, &self.keys))
+    }
+}
+
+impl CommandExecutor for SPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match (backend.spop(&self.key, self.count.unwrap_or(1)), self.count) {
+            (Ok(popped), None) => single_member_reply(popped),
+            (Ok(popped), Some(_)) => RespSet::new(members_reply(popped)).into(),
+            (Err(e), _) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SRandMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match (
+            backend.srandmember(&self.key, self.count.unwrap_or(1)),
+            self.count,
+        ) {
+            (Ok(picked), None) => single_member_reply(picked),
+            (Ok(picked), Some(_)) => RespArray::new(members_reply(picked)).into(),
+            (Err(e), _) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SMove {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.smove(&self.source, &self.destination, &self.member) {
+            Ok(moved) => RespFrame::Integer(moved as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for SScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let (next, page) = match backend.sscan(&self.key, self.cursor, self.count) {
+            Ok(scanned) => scanned,
+            Err(e) => return e.into(),
+        };
+
+        let members = page
+            .into_iter()
+            .filter(|member| match &self.pattern {
+                Some(pattern) => glob_match(pattern.as_bytes(), member.as_bytes()),
+                None => true,
+            })
+            .collect();
+        scan_reply(next, members_reply(members))
+    }
+}
+
+fn single_member_reply(members: Vec<String>) -> RespFrame {
+    match members.into_iter().next() {
+        Some(member) => BulkString::new(member).into(),
+        None => RespFrame::Null(RespNull),
+    }
+}
+
+fn set_op_reply(result: Result<Vec<String>, BackendError>) -> RespFrame {
+    match result {
+        Ok(members) => RespSet::new(members_reply(members)).into(),
+        Err(e) => e.into(),
+    }
+}
+
+fn count_reply(result: Result<usize, BackendError>) -> RespFrame {
+    match result {
+        Ok(count) => RespFrame::Integer(count as i64),
+        Err(e) => e.into(),
+    }
+}
+
+fn members_reply(members: Vec<String>) -> Vec<RespFrame> {
+    members
+        .into_iter()
+        .map(|member| BulkString::new(member).into())
+        .collect()
+}
+
+impl TryFrom<RespArray> for SAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = extract_key_and_members(value, "sadd")?;
+        Ok(SAdd { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for SRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = extract_key_and_members(value, "srem")?;
+        Ok(SRem { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for SMembers {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smembers"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(SMembers { key })
+    }
+}
+
+impl TryFrom<RespArray> for SCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["scard"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(SCard { key })
+    }
+}
+
+impl TryFrom<RespArray> for SIsMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["sismember"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let member = extract_string(args.next(), "member")?;
+        Ok(SIsMember { key, member })
+    }
+}
+
+impl TryFrom<RespArray> for SMIsMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, members) = extract_key_and_members(value, "smismember")?;
+        Ok(SMIsMember { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for SInter {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let keys = extract_keys(value, "sinter", 1)?;
+        Ok(SInter { keys })
+    }
+}
+
+impl TryFrom<RespArray> for SUnion {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let keys = extract_keys(value, "sunion", 1)?;
+        Ok(SUnion { keys })
+    }
+}
+
+impl TryFrom<RespArray> for SDiff {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let keys = extract_keys(value, "sdiff", 1)?;
+        Ok(SDiff { keys })
+    }
+}
+
+impl TryFrom<RespArray> for SInterStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut keys = extract_keys(value, "sinterstore", 2)?;
+        let destination = keys.remove(0);
+        Ok(SInterStore { destination, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SUnionStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut keys = extract_keys(value, "sunionstore", 2)?;
+        let destination = keys.remove(0);
+        Ok(SUnionStore { destination, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SDiffStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut keys = extract_keys(value, "sdiffstore", 2)?;
+        let destination = keys.remove(0);
+        Ok(SDiffStore { destination, keys })
+    }
+}
+
+impl TryFrom<RespArray> for SPop {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "spop")?;
+        Ok(SPop { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for SRandMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["srandmember"], 1)?;
+        if value.len() > 3 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let count = args
+            .next()
+            .map(|count| extract_int(Some(count)))
+            .transpose()?;
+        Ok(SRandMember { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for SMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smove"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let source = extract_string(args.next(), "source")?;
+        let destination = extract_string(args.next(), "destination")?;
+        let member = extract_string(args.next(), "member")?;
+        Ok(SMove {
+            source,
+            destination,
+            member,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SScan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["sscan"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut scan = SScan {
+            key: extract_string(args.next(), "key")?,
+            cursor: extract_cursor(args.next())?,
+            pattern: None,
+            count: 10,
+        };
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"match" => scan.pattern = Some(extract_string(args.next(), "pattern")?),
+                b"count" => match extract_int(args.next())? {
+                    count if count >= 1 => scan.count = count as usize,
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                },
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+fn extract_keys(
+    value: RespArray,
+    name: &'static str,
+    min: usize,
+) -> Result<Vec<String>, CommandError> {
+    validate_variadic_command(&value, &[name], min)?;
+
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|key| extract_string(Some(key), "key"))
+        .collect()
+}
+
+fn extract_key_and_members(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, Vec<String>), CommandError> {
+    validate_variadic_command(&value, &[name], 2)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let members = args
+        .map(|member| extract_string(Some(member), "member"))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((key, members))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_set_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$4\r\nsadd\r\n$1\r\ns\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: SAdd = frame.try_into()?;
+        assert_eq!(result.key, "s");
+        assert_eq!(result.members, vec!["a", "b"]);
+
+        buf.extend_from_slice(b"*2\r\n$4\r\nsrem\r\n$1\r\ns\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(SRem::try_from(frame).is_err());
+
+        buf.extend_from_slice(b"*3\r\n$9\r\nsismember\r\n$1\r\ns\r\n$1\r\na\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: SIsMember = frame.try_into()?;
+        assert_eq!(result.member, "a");
+
+        buf.extend_from_slice(b"*4\r\n$10\r\nsdiffstore\r\n$1\r\nd\r\n$2\r\ns1\r\n$2\r\ns2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: SDiffStore = frame.try_into()?;
+        assert_eq!(result.destination, "d");
+        assert_eq!(result.keys, vec!["s1", "s2"]);
+
+        buf.extend_from_slice(b"*2\r\n$11\r\nsunionstore\r\n$1\r\nd\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(SUnionStore::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = SAdd {
+            key: "s".to_string(),
+            members: vec!["a".to_string(), "a".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = SMembers {
+            key: "s".to_string(),
+        };
+        let expected = RespSet::new(vec![BulkString::from("a").into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd = SIsMember {
+            key: "s".to_string(),
+            member: "b".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = SMIsMember {
+            key: "s".to_string(),
+            members: vec!["b".to_string(), "a".to_string()],
+        };
+        let expected = RespArray::new([RespFrame::Integer(0), RespFrame::Integer(1)]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd = SInterStore {
+            destination: "d".to_string(),
+            keys: vec!["s".to_string(), "s".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd = SUnion {
+            keys: vec!["d".to_string(), "missing".to_string()],
+        };
+        let expected = RespSet::new(vec![BulkString::from("a").into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd = SRandMember {
+            key: "s".to_string(),
+            count: None,
+        };
+        assert_eq!(cmd.execute(&backend), BulkString::from("a").into());
+        let cmd = SRandMember {
+            key: "s".to_string(),
+            count: Some(-2),
+        };
+        let expected = RespArray::new(vec![BulkString::from("a").into(); 2]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd = SMove {
+            source: "d".to_string(),
+            destination: "e".to_string(),
+            member: "a".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd = SPop {
+            key: "e".to_string(),
+            count: None,
+        };
+        assert_eq!(cmd.execute(&backend), BulkString::from("a").into());
+        let cmd = SPop {
+            key: "e".to_string(),
+            count: Some(1),
+        };
+        assert_eq!(cmd.execute(&backend), RespSet::new(vec![]).into());
+
+        let cmd = SRem {
+            key: "s".to_string(),
+            members: vec!["a".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd = SCard {
+            key: "s".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sscan_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$5\r\nsscan\r\n$1\r\ns\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$2\r\na*\r\n$5\r\ncount\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        // COUNT without its value
+        assert!(SScan::try_from(frame).is_err());
+
+        buf.extend_from_slice(
+            b"*5\r\n$5\r\nsscan\r\n$1\r\ns\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$2\r\na*\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: SScan = frame.try_into()?;
+        assert_eq!(cmd.pattern.as_deref(), Some("a*"));
+
+        let backend = Backend::new();
+        backend.sadd("s", vec!["a1".to_string(), "b1".to_string()])?;
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([
+                RespFrame::BulkString(b"0".into()),
+                RespArray::new([RespFrame::BulkString(b"a1".into())]).into(),
+            ])
+            .into()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_variadic_command, CommandError,
+    CommandExecutor, Sort,
+};
+use crate::{Backend, RespArray, RespFrame, RespNull, SortOptions};
+
+impl CommandExecutor for Sort {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.store {
+            Some(dest) => match backend.sort_store(&self.key, &self.options, dest) {
+                Ok(len) => RespFrame::Integer(len as i64),
+                Err(e) => e.into(),
+            },
+            None => match backend.sort(&self.key, &self.options) {
+                Ok(result) => RespArray::new(
+                    result
+                        .into_iter()
+                        .map(|v| v.unwrap_or(RespFrame::Null(RespNull)))
+                        .collect::<Vec<_>>(),
+                )
+                .into(),
+                Err(e) => e.into(),
+            },
+        }
+    }
+}
+
+// SORT key [BY pattern] [LIMIT offset count] [GET pattern ...] [ASC | DESC] [ALPHA]
+//   [STORE destination]
+impl TryFrom<RespArray> for Sort {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["sort"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let mut options = SortOptions::default();
+        let mut store = None;
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"asc" => options.desc = false,
+                b"desc" => options.desc = true,
+                b"alpha" => options.alpha = true,
+                b"by" => options.by = Some(extract_string(args.next(), "pattern")?),
+                b"get" => options.get.push(extract_string(args.next(), "pattern")?),
+                b"limit" => {
+                    options.limit = Some((extract_int(args.next())?, extract_int(args.next())?))
+                }
+                b"store" => store = Some(extract_string(args.next(), "destination")?),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+
+        Ok(Sort {
+            key,
+            options,
+            store,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RespDecode, Value};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_sort_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*9\r\n$4\r\nsort\r\n$1\r\nl\r\n$5\r\nLIMIT\r\n$1\r\n0\r\n$2\r\n10\r\n$3\r\nGET\r\n$1\r\n#\r\n$4\r\ndesc\r\n$5\r\nalpha\r\n",
+        );
+
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Sort = frame.try_into()?;
+        assert_eq!(result.key, "l");
+        assert_eq!(result.options.limit, Some((0, 10)));
+        assert_eq!(result.options.get, vec!["#".to_string()]);
+        assert!(result.options.desc && result.options.alpha);
+        assert!(result.store.is_none());
+
+        buf.extend_from_slice(b"*3\r\n$4\r\nsort\r\n$1\r\nl\r\n$5\r\nlimit\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Result<Sort, _> = frame.try_into();
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_command() -> Result<()> {
+        let backend = Backend::new();
+        let list = ["2", "1"].iter().collect();
+        backend
+            .write("l")
+            .insert("l".to_string(), Value::List(list));
+
+        let cmd = Sort {
+            key: "l".to_string(),
+            options: SortOptions {
+                get: vec!["missing_*".to_string()],
+                ..Default::default()
+            },
+            store: None,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new(vec![RespFrame::Null(RespNull), RespFrame::Null(RespNull)]).into()
+        );
+
+        let cmd = Sort {
+            key: "l".to_string(),
+            options: SortOptions::default(),
+            store: Some("dest".to_string()),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+        assert!(backend.contains_key("dest"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1541 @@
+use crate::backend::{command, now_ms};
+use crate::cmd::{
+    extract_args, extract_int, extract_string, validate_command, validate_variadic_command,
+    CommandError, CommandExecutor, XAck, XAdd, XAutoClaim, XClaim, XDel, XGroupCreate,
+    XGroupCreateConsumer, XGroupDelConsumer, XGroupDestroy, XGroupSetId, XInfoConsumers,
+    XInfoGroups, XInfoStream, XLen, XPending, XRange, XRead, XReadGroup, XRevRange, XSetId, XTrim,
+    RESP_OK,
+};
+use crate::{
+    Backend, BackendError, BlockingRead, BulkString, ClaimOptions, GroupReads, PendingFilter,
+    RespArray, RespFrame, RespMap, RespNull, StreamFields, StreamId, StreamReads, StreamTrim,
+    TrimStrategy, XAddId, XAddOptions, STREAM_NODE_MAX_ENTRIES,
+};
+use std::iter::Peekable;
+use std::time::Duration;
+use tokio::time::Instant;
+
+impl CommandExecutor for XAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xadd_with(&self.key, self.id, self.fields, &self.options) {
+            Ok(Some(id)) => BulkString::new(id.to_string()).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xtrim(&self.key, &self.trim) {
+            Ok(trimmed) => RespFrame::Integer(trimmed as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xdel(&self.key, &self.ids) {
+            Ok(deleted) => RespFrame::Integer(deleted as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xlen(&self.key) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        entries_reply(backend.xrange(&self.key, self.start, self.end, false, self.count))
+    }
+}
+
+impl CommandExecutor for XRevRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        entries_reply(backend.xrange(&self.key, self.start, self.end, true, self.count))
+    }
+}
+
+impl CommandExecutor for XRead {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let after = match resolve_ids(backend, &self.keys, &self.ids) {
+            Ok(after) => after,
+            Err(e) => return e.into(),
+        };
+        reads_reply(backend.xread(&self.keys, &after, self.count))
+    }
+}
+
+impl XRead {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        let Some(block) = self.block else {
+            return self.execute(backend);
+        };
+        // `$` is the last ID when the command is run, not when it is woken
+        let after = match resolve_ids(backend, &self.keys, &self.ids) {
+            Ok(after) => after,
+            Err(e) => return e.into(),
+        };
+        let deadline = (block > 0).then(|| Instant::now() + Duration::from_millis(block));
+
+        loop {
+            let mut blocked = match backend.xread_or_block(&self.keys, &after, self.count) {
+                Ok(BlockingRead::Ready(reads)) => return reads_reply(Ok(reads)),
+                Ok(BlockingRead::Blocked(blocked)) => blocked,
+                Err(e) => return e.into(),
+            };
+            let woken = match deadline {
+                None => blocked.wait().await,
+                Some(deadline) => tokio::time::timeout_at(deadline, blocked.wait())
+                    .await
+                    .unwrap_or_default(),
+            };
+            // woken right as the timeout elapsed
+            let late = backend.unblock(blocked);
+            if woken.or(late).is_none() {
+                return RespFrame::Null(RespNull);
+            }
+        }
+    }
+}
+
+impl CommandExecutor for XGroupCreate {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xgroup_create(&self.key, &self.group, self.id, self.mkstream) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XGroupDestroy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xgroup_destroy(&self.key, &self.group) {
+            Ok(destroyed) => RespFrame::Integer(destroyed as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XGroupSetId {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xgroup_setid(&self.key, &self.group, self.id) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XGroupCreateConsumer {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xgroup_createconsumer(&self.key, &self.group, &self.consumer) {
+            Ok(created) => RespFrame::Integer(created as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XGroupDelConsumer {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xgroup_delconsumer(&self.key, &self.group, &self.consumer) {
+            Ok(pending) => RespFrame::Integer(pending as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XSetId {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xsetid(&self.key, self.id) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XInfoStream {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let info = match backend.xinfo_stream(&self.key) {
+            Ok(info) => info,
+            Err(e) => return e.into(),
+        };
+        let entry = |entry: Option<(StreamId, StreamFields)>| match entry {
+            Some((id, fields)) => entry_frame(id, fields),
+            None => RespFrame::Null(RespNull),
+        };
+        let mut map = RespMap::new();
+        map.insert("length".to_string(), (info.length as i64).into());
+        map.insert(
+            "radix-tree-keys".to_string(),
+            (info.length.div_ceil(STREAM_NODE_MAX_ENTRIES) as i64).into(),
+        );
+        map.insert(
+            "last-generated-id".to_string(),
+            BulkString::new(info.last_generated.to_string()).into(),
+        );
+        map.insert("groups".to_string(), (info.groups as i64).into());
+        map.insert("first-entry".to_string(), entry(info.first));
+        map.insert("last-entry".to_string(), entry(info.last));
+        map.into()
+    }
+}
+
+impl CommandExecutor for XInfoGroups {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let groups = match backend.xinfo_groups(&self.key) {
+            Ok(groups) => groups,
+            Err(e) => return e.into(),
+        };
+        let groups: Vec<RespFrame> = groups
+            .into_iter()
+            .map(|group| {
+                let mut map = RespMap::new();
+                map.insert("name".to_string(), BulkString::new(group.name).into());
+                map.insert("consumers".to_string(), (group.consumers as i64).into());
+                map.insert("pending".to_string(), (group.pending as i64).into());
+                map.insert(
+                    "last-delivered-id".to_string(),
+                    BulkString::new(group.last_delivered.to_string()).into(),
+                );
+                map.insert("lag".to_string(), (group.lag as i64).into());
+                map.into()
+            })
+            .collect();
+        RespArray::new(groups).into()
+    }
+}
+
+impl CommandExecutor for XInfoConsumers {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let consumers = match backend.xinfo_consumers(&self.key, &self.group) {
+            Ok(consumers) => consumers,
+            Err(e) => return e.into(),
+        };
+        let consumers: Vec<RespFrame> = consumers
+            .into_iter()
+            .map(|consumer| {
+                let mut map = RespMap::new();
+                map.insert("name".to_string(), BulkString::new(consumer.name).into());
+                map.insert("pending".to_string(), (consumer.pending as i64).into());
+                map.insert("idle".to_string(), consumer.idle.into());
+                map.into()
+            })
+            .collect();
+        RespArray::new(consumers).into()
+    }
+}
+
+impl CommandExecutor for XReadGroup {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        group_reads_reply(backend.xreadgroup(
+            &self.group,
+            &self.consumer,
+            &self.keys,
+            &self.ids,
+            self.count,
+            self.noack,
+        ))
+    }
+}
+
+impl XReadGroup {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        // propagated without BLOCK, when it ran
+        let request = backend.is_propagating().then(|| self.request());
+        let propagated = |failed: bool| match &request {
+            Some(request) if !failed => vec![request.clone()],
+            _ => Vec::new(),
+        };
+
+        // reading the pending entries never blocks
+        let block = match self.block {
+            Some(block) if self.ids.iter().all(Option::is_none) => block,
+            _ => {
+                return backend.run_logged(|| {
+                    backend.propagate_effects(
+                        || self.execute(backend),
+                        |reply| propagated(matches!(reply, RespFrame::Error(_))),
+                    )
+                })
+            }
+        };
+        let deadline = (block > 0).then(|| Instant::now() + Duration::from_millis(block));
+
+        let mut first = true;
+        loop {
+            let read = backend.run_logged(|| {
+                backend.propagate_effects(
+                    || {
+                        backend.xreadgroup_or_block(
+                            &self.group,
+                            &self.consumer,
+                            &self.keys,
+                            &self.ids,
+                            self.count,
+                            self.noack,
+                        )
+                    },
+                    // the first read creates the consumer even if it blocks
+                    |read| match read {
+                        Ok(BlockingRead::Blocked(_)) if !first => Vec::new(),
+                        read => propagated(read.is_err()),
+                    },
+                )
+            });
+            first = false;
+            let mut blocked = match read {
+                Ok(BlockingRead::Ready(reads)) => return group_reads_reply(Ok(reads)),
+                Ok(BlockingRead::Blocked(blocked)) => blocked,
+                Err(e) => return e.into(),
+            };
+            let woken = match deadline {
+                None => blocked.wait().await,
+                Some(deadline) => tokio::time::timeout_at(deadline, blocked.wait())
+                    .await
+                    .unwrap_or_default(),
+            };
+            // woken right as the timeout elapsed
+            let late = backend.unblock(blocked);
+            if woken.or(late).is_none() {
+                return RespFrame::Null(RespNull);
+            }
+        }
+    }
+}
+
+impl XReadGroup {
+    // the command reading right away what this one reads
+    fn request(&self) -> RespArray {
+        let mut args = vec![
+            "XREADGROUP".to_string(),
+            "GROUP".to_string(),
+            self.group.clone(),
+            self.consumer.clone(),
+        ];
+        if let Some(count) = self.count {
+            args.extend(["COUNT".to_string(), count.to_string()]);
+        }
+        if self.noack {
+            args.push("NOACK".to_string());
+        }
+        args.push("STREAMS".to_string());
+        args.extend(self.keys.iter().cloned());
+        args.extend(self.ids.iter().map(|id| match id {
+            Some(id) => id.to_string(),
+            None => ">".to_string(),
+        }));
+        command(args)
+    }
+}
+
+impl CommandExecutor for XAck {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xack(&self.key, &self.group, &self.ids) {
+            Ok(acked) => RespFrame::Integer(acked as i64),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XPending {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let Some(filter) = self.filter else {
+            return match backend.xpending(&self.key, &self.group) {
+                Ok(summary) => {
+                    let (first, last) = match summary.bounds {
+                        Some((first, last)) => (
+                            BulkString::new(first.to_string()).into(),
+                            BulkString::new(last.to_string()).into(),
+                        ),
+                        None => (RespFrame::Null(RespNull), RespFrame::Null(RespNull)),
+                    };
+                    let consumers = if summary.consumers.is_empty() {
+                        RespFrame::Null(RespNull)
+                    } else {
+                        RespArray::new(
+                            summary
+                                .consumers
+                                .into_iter()
+                                .map(|(name, count)| {
+                                    RespArray::new([
+                                        BulkString::new(name).into(),
+                                        BulkString::new(count.to_string()).into(),
+                                    ])
+                                    .into()
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                        .into()
+                    };
+                    RespArray::new([
+                        RespFrame::Integer(summary.count as i64),
+                        first,
+                        last,
+                        consumers,
+                    ])
+                    .into()
+                }
+                Err(e) => e.into(),
+            };
+        };
+        match backend.xpending_range(&self.key, &self.group, &filter) {
+            Ok(pending) => {
+                let now = now_ms();
+                RespArray::new(
+                    pending
+                        .into_iter()
+                        .map(|(id, entry)| {
+                            RespArray::new([
+                                BulkString::new(id.to_string()).into(),
+                                BulkString::new(entry.consumer).into(),
+                                RespFrame::Integer((now - entry.delivered_at).max(0)),
+                                RespFrame::Integer(entry.deliveries as i64),
+                            ])
+                            .into()
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .into()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XClaim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let justid = self.options.justid;
+        match backend.xclaim(
+            &self.key,
+            &self.group,
+            &self.consumer,
+            self.min_idle,
+            &self.ids,
+            &self.options,
+        ) {
+            Ok(claimed) => claimed_reply(claimed, justid),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for XAutoClaim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.xautoclaim(
+            &self.key,
+            &self.group,
+            &self.consumer,
+            self.min_idle,
+            self.start,
+            self.count,
+            self.justid,
+        ) {
+            Ok(claimed) => RespArray::new([
+                BulkString::new(claimed.next.to_string()).into(),
+                claimed_reply(claimed.claimed, self.justid),
+                ids_reply(claimed.deleted),
+            ])
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+fn resolve_ids(
+    backend: &Backend,
+    keys: &[String],
+    ids: &[Option<StreamId>],
+) -> Result<Vec<StreamId>, BackendError> {
+    keys.iter()
+        .zip(ids)
+        .map(|(key, id)| match id {
+            Some(id) => Ok(*id),
+            None => backend.xlast_id(key),
+        })
+        .collect()
+}
+
+// [[key, [entry, ...]], ...], Null if there is nothing new
+fn reads_reply(result: Result<StreamReads, BackendError>) -> RespFrame {
+    match result {
+        Ok(reads) if reads.is_empty() => RespFrame::Null(RespNull),
+        Ok(reads) => RespArray::new(
+            reads
+                .into_iter()
+                .map(|(key, entries)| {
+                    let entries = entries
+                        .into_iter()
+                        .map(|(id, fields)| entry_frame(id, fields))
+                        .collect::<Vec<_>>();
+                    RespArray::new([BulkString::new(key).into(), RespArray::new(entries).into()])
+                        .into()
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into(),
+        Err(e) => e.into(),
+    }
+}
+
+// like reads_reply, a pending entry deleted since it was delivered having Null fields
+fn group_reads_reply(result: Result<GroupReads, BackendError>) -> RespFrame {
+    match result {
+        Ok(reads) if reads.is_empty() => RespFrame::Null(RespNull),
+        Ok(reads) => RespArray::new(
+            reads
+                .into_iter()
+                .map(|(key, entries)| {
+                    let entries = entries
+                        .into_iter()
+                        .map(|(id, fields)| match fields {
+                            Some(fields) => entry_frame(id, fields),
+                            None => RespArray::new([
+                                BulkString::new(id.to_string()).into(),
+                                RespFrame::Null(RespNull),
+                            ])
+                            .into(),
+                        })
+                        .collect::<Vec<_>>();
+                    RespArray::new([BulkString::new(key).into(), RespArray::new(entries).into()])
+                        .into()
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into(),
+        Err(e) => e.into(),
+    }
+}
+
+fn entries_reply(result: Result<Vec<(StreamId, StreamFields)>, BackendError>) -> RespFrame {
+    match result {
+        Ok(entries) => RespArray::new(
+            entries
+                .into_iter()
+                .map(|(id, fields)| entry_frame(id, fields))
+                .collect::<Vec<_>>(),
+        )
+        .into(),
+        Err(e) => e.into(),
+    }
+}
+
+// the claimed entries, only their IDs if justid
+fn claimed_reply(claimed: Vec<(StreamId, StreamFields)>, justid: bool) -> RespFrame {
+    if justid {
+        return ids_reply(claimed.into_iter().map(|(id, _)| id).collect());
+    }
+    entries_reply(Ok(claimed))
+}
+
+fn ids_reply(ids: Vec<StreamId>) -> RespFrame {
+    RespArray::new(
+        ids.into_iter()
+            .map(|id| BulkString::new(id.to_string()).into())
+            .collect::<Vec<_>>(),
+    )
+    .into()
+}
+
+// [id, [field, value, ...]]
+fn entry_frame(id: StreamId, fields: StreamFields) -> RespFrame {
+    let mut flat = Vec::with_capacity(fields.len() * 2);
+    for (field, value) in fields {
+        flat.push(BulkString::new(field).into());
+        flat.push(value);
+    }
+    RespArray::new([
+        BulkString::new(id.to_string()).into(),
+        RespArray::new(flat).into(),
+    ])
+    .into()
+}
+
+// XADD key [NOMKSTREAM] [<MAXLEN | MINID> [= | ~] threshold [LIMIT count]] <* | id> field value
+//   [field value ...]
+impl TryFrom<RespArray> for XAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xadd"], 4)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let mut options = XAddOptions::default();
+        loop {
+            if args.next_if(|arg| is_option(arg, b"nomkstream")).is_some() {
+                options.nomkstream = true;
+            } else if args.peek().is_some_and(is_trim_strategy) {
+                options.trim = Some(extract_trim(&mut args)?);
+            } else {
+                break;
+            }
+        }
+        let id = extract_xadd_id(args.next())?;
+        let args: Vec<_> = args.collect();
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(CommandError::InvalidArgument(
+                "wrong number of arguments for 'xadd' command".to_string(),
+            ));
+        }
+        let mut fields = Vec::with_capacity(args.len() / 2);
+        let mut args = args.into_iter();
+        while let (Some(field), Some(value)) = (args.next(), args.next()) {
+            fields.push((extract_string(Some(field), "field")?, value));
+        }
+        Ok(XAdd {
+            key,
+            id,
+            fields,
+            options,
+        })
+    }
+}
+
+// XTRIM key <MAXLEN | MINID> [= | ~] threshold [LIMIT count]
+impl TryFrom<RespArray> for XTrim {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xtrim"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        if !args.peek().is_some_and(is_trim_strategy) {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let trim = extract_trim(&mut args)?;
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(XTrim { key, trim })
+    }
+}
+
+impl TryFrom<RespArray> for XDel {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xdel"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let ids = args
+            .map(|id| parse_stream_id(&extract_string(Some(id), "id")?, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XDel { key, ids })
+    }
+}
+
+impl TryFrom<RespArray> for XLen {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xlen"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(XLen { key })
+    }
+}
+
+impl TryFrom<RespArray> for XRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, end, count) = extract_range(value, "xrange", false)?;
+        Ok(XRange {
+            key,
+            start,
+            end,
+            count,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XRevRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, start, end, count) = extract_range(value, "xrevrange", true)?;
+        Ok(XRevRange {
+            key,
+            end,
+            start,
+            count,
+        })
+    }
+}
+
+// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+impl TryFrom<RespArray> for XRead {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xread"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (count, block, _) = extract_read_options(&mut args, false)?;
+        let (keys, ids) = extract_streams(args, "xread", "$")?;
+        Ok(XRead {
+            keys,
+            ids,
+            count,
+            block,
+        })
+    }
+}
+
+// XGROUP CREATE key group <id | $> [MKSTREAM]
+impl TryFrom<RespArray> for XGroupCreate {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xgroup", "create"], 3)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        let id = match extract_string(args.next(), "id")?.as_str() {
+            "$" => None,
+            id => Some(parse_stream_id(id, 0)?),
+        };
+        let mkstream = match args.next() {
+            None => false,
+            Some(arg) if is_option(&arg, b"mkstream") && args.next().is_none() => true,
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(XGroupCreate {
+            key,
+            group,
+            id,
+            mkstream,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XGroupDestroy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xgroup", "destroy"], 2)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        Ok(XGroupDestroy { key, group })
+    }
+}
+
+// XGROUP SETID key group <id | $>
+impl TryFrom<RespArray> for XGroupSetId {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xgroup", "setid"], 3)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        let id = match extract_string(args.next(), "id")?.as_str() {
+            "$" => None,
+            id => Some(parse_stream_id(id, 0)?),
+        };
+        Ok(XGroupSetId { key, group, id })
+    }
+}
+
+// XGROUP CREATECONSUMER key group consumer
+impl TryFrom<RespArray> for XGroupCreateConsumer {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, group, consumer) = extract_group_consumer(value, "createconsumer")?;
+        Ok(XGroupCreateConsumer {
+            key,
+            group,
+            consumer,
+        })
+    }
+}
+
+// XGROUP DELCONSUMER key group consumer
+impl TryFrom<RespArray> for XGroupDelConsumer {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, group, consumer) = extract_group_consumer(value, "delconsumer")?;
+        Ok(XGroupDelConsumer {
+            key,
+            group,
+            consumer,
+        })
+    }
+}
+
+// XSETID key last-id
+impl TryFrom<RespArray> for XSetId {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xsetid"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let id = parse_stream_id(&extract_string(args.next(), "id")?, 0)?;
+        Ok(XSetId { key, id })
+    }
+}
+
+// XINFO STREAM key
+impl TryFrom<RespArray> for XInfoStream {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xinfo", "stream"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(XInfoStream { key })
+    }
+}
+
+// XINFO GROUPS key
+impl TryFrom<RespArray> for XInfoGroups {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xinfo", "groups"], 1)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(XInfoGroups { key })
+    }
+}
+
+// XINFO CONSUMERS key group
+impl TryFrom<RespArray> for XInfoConsumers {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xinfo", "consumers"], 2)?;
+
+        let mut args = extract_args(value, 2)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        Ok(XInfoConsumers { key, group })
+    }
+}
+
+// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK]
+//   STREAMS key [key ...] id [id ...]
+impl TryFrom<RespArray> for XReadGroup {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xreadgroup"], 6)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(arg) if is_option(&arg, b"group") => {}
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+        let group = extract_string(args.next(), "group")?;
+        let consumer = extract_string(args.next(), "consumer")?;
+        let (count, block, noack) = extract_read_options(&mut args, true)?;
+        let (keys, ids) = extract_streams(args, "xreadgroup", ">")?;
+        Ok(XReadGroup {
+            group,
+            consumer,
+            keys,
+            ids,
+            count,
+            block,
+            noack,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for XAck {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xack"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        let ids = args
+            .map(|id| parse_stream_id(&extract_string(Some(id), "id")?, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XAck { key, group, ids })
+    }
+}
+
+// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+impl TryFrom<RespArray> for XPending {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xpending"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        if args.peek().is_none() {
+            return Ok(XPending {
+                key,
+                group,
+                filter: None,
+            });
+        }
+
+        let min_idle = match args.next_if(|arg| is_option(arg, b"idle")) {
+            Some(_) => extract_int(args.next())?.max(0),
+            None => 0,
+        };
+        let start = extract_range_id(args.next(), false)?;
+        let end = extract_range_id(args.next(), true)?;
+        // a negative count lists nothing
+        let count = usize::try_from(extract_int(args.next())?).unwrap_or(0);
+        let consumer = args
+            .next()
+            .map(|consumer| extract_string(Some(consumer), "consumer"))
+            .transpose()?;
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let filter = PendingFilter {
+            min_idle,
+            start,
+            end,
+            count,
+            consumer,
+        };
+        Ok(XPending {
+            key,
+            group,
+            filter: Some(filter),
+        })
+    }
+}
+
+// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+//   [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]
+impl TryFrom<RespArray> for XClaim {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xclaim"], 5)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        let consumer = extract_string(args.next(), "consumer")?;
+        let min_idle = extract_int(args.next())?.max(0);
+
+        // the IDs go on up to the first option
+        let mut ids = Vec::new();
+        while let Some(id) = args.next_if(|arg| !is_claim_option(arg)) {
+            ids.push(parse_stream_id(&extract_string(Some(id), "id")?, 0)?);
+        }
+        if ids.is_empty() {
+            return Err(invalid_id());
+        }
+
+        let mut options = ClaimOptions::default();
+        while let Some(opt) = args.next() {
+            let RespFrame::BulkString(opt) = opt else {
+                return Err(CommandError::InvalidArgument("syntax error".to_string()));
+            };
+            match opt.to_ascii_lowercase().as_slice() {
+                b"idle" => {
+                    let idle = extract_int(args.next())?.max(0);
+                    options.delivered_at = Some(now_ms() - idle);
+                }
+                b"time" => options.delivered_at = Some(extract_int(args.next())?),
+                b"retrycount" => match u64::try_from(extract_int(args.next())?) {
+                    Ok(count) => options.retry_count = Some(count),
+                    Err(_) => {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid RETRYCOUNT option argument for XCLAIM".to_string(),
+                        ))
+                    }
+                },
+                b"force" => options.force = true,
+                b"justid" => options.justid = true,
+                b"lastid" => {
+                    let id = extract_string(args.next(), "id")?;
+                    options.last_id = Some(parse_stream_id(&id, 0)?);
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(XClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            ids,
+            options,
+        })
+    }
+}
+
+// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+impl TryFrom<RespArray> for XAutoClaim {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["xautoclaim"], 5)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let group = extract_string(args.next(), "group")?;
+        let consumer = extract_string(args.next(), "consumer")?;
+        let min_idle = extract_int(args.next())?.max(0);
+        let start = extract_range_id(args.next(), false)?;
+        let mut count = 100;
+        let mut justid = false;
+        while let Some(opt) = args.next() {
+            match opt {
+                opt if is_option(&opt, b"count") => match extract_int(args.next())? {
+                    n if n > 0 => count = n as usize,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "COUNT must be > 0".to_string(),
+                        ))
+                    }
+                },
+                opt if is_option(&opt, b"justid") => justid = true,
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(XAutoClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            start,
+            count,
+            justid,
+        })
+    }
+}
+
+// the key, group and consumer of an XGROUP subcommand
+fn extract_group_consumer(
+    value: RespArray,
+    subcommand: &'static str,
+) -> Result<(String, String, String), CommandError> {
+    validate_command(&value, &["xgroup", subcommand], 3)?;
+
+    let mut args = extract_args(value, 2)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let group = extract_string(args.next(), "group")?;
+    let consumer = extract_string(args.next(), "consumer")?;
+    Ok((key, group, consumer))
+}
+
+fn is_trim_strategy(frame: &RespFrame) -> bool {
+    is_option(frame, b"maxlen") || is_option(frame, b"minid")
+}
+
+// `<MAXLEN | MINID> [= | ~] threshold [LIMIT count]`
+fn extract_trim(
+    args: &mut Peekable<impl Iterator<Item = RespFrame>>,
+) -> Result<StreamTrim, CommandError> {
+    let maxlen = args.next().is_some_and(|arg| is_option(&arg, b"maxlen"));
+    let approx = args
+        .next_if(|arg| is_option(arg, b"~") || is_option(arg, b"="))
+        .is_some_and(|arg| is_option(&arg, b"~"));
+
+    let strategy = if maxlen {
+        match usize::try_from(extract_int(args.next())?) {
+            Ok(len) => TrimStrategy::MaxLen(len),
+            Err(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "The MAXLEN argument must be >= 0.".to_string(),
+                ))
+            }
+        }
+    } else {
+        let id = extract_string(args.next(), "id")?;
+        TrimStrategy::MinId(parse_stream_id(&id, 0)?)
+    };
+
+    let limit = match args.next_if(|arg| is_option(arg, b"limit")) {
+        None => None,
+        Some(_) if !approx => {
+            return Err(CommandError::InvalidArgument(
+                "syntax error, LIMIT cannot be used without the special ~ option".to_string(),
+            ))
+        }
+        Some(_) => match usize::try_from(extract_int(args.next())?) {
+            Ok(limit) => Some(limit),
+            Err(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "The LIMIT argument must be >= 0.".to_string(),
+                ))
+            }
+        },
+    };
+    Ok(StreamTrim {
+        strategy,
+        approx,
+        limit,
+    })
+}
+
+fn is_claim_option(frame: &RespFrame) -> bool {
+    [
+        b"idle".as_slice(),
+        b"time",
+        b"retrycount",
+        b"force",
+        b"justid",
+        b"lastid",
+    ]
+    .iter()
+    .any(|name| is_option(frame, name))
+}
+
+// the options of XREAD and XREADGROUP up to STREAMS: the count, the block timeout and, if
+// allowed, NOACK
+fn extract_read_options(
+    args: &mut impl Iterator<Item = RespFrame>,
+    allow_noack: bool,
+) -> Result<(Option<usize>, Option<u64>, bool), CommandError> {
+    let mut count = None;
+    let mut block = None;
+    let mut noack = false;
+    loop {
+        let opt = match args.next() {
+            Some(RespFrame::BulkString(opt)) => opt.to_ascii_lowercase(),
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        match opt.as_slice() {
+            b"count" => count = Some(usize::try_from(extract_int(args.next())?).unwrap_or(0)),
+            b"block" => match extract_int(args.next())? {
+                ms if ms >= 0 => block = Some(ms as u64),
+                _ => {
+                    return Err(CommandError::InvalidArgument(
+                        "timeout is negative".to_string(),
+                    ))
+                }
+            },
+            b"noack" if allow_noack => noack = true,
+            b"streams" => return Ok((count, block, noack)),
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+// `key [key ...] id [id ...]` after STREAMS, special standing for the ID the command resolves
+// itself
+fn extract_streams(
+    args: impl Iterator<Item = RespFrame>,
+    name: &str,
+    special: &str,
+) -> Result<(Vec<String>, Vec<Option<StreamId>>), CommandError> {
+    let args: Vec<_> = args.collect();
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(CommandError::InvalidArgument(format!(
+            "Unbalanced '{}' list of streams: for each stream key an ID or '{}' must be specified.",
+            name, special
+        )));
+    }
+    let streams = args.len() / 2;
+    let mut args = args.into_iter();
+    let keys = args
+        .by_ref()
+        .take(streams)
+        .map(|key| extract_string(Some(key), "key"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ids = args
+        .map(|id| match extract_string(Some(id), "id")? {
+            id if id == special => Ok(None),
+            id => parse_stream_id(&id, 0).map(Some),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((keys, ids))
+}
+
+// `key start end [COUNT count]`, end coming first if rev. Returns the key, the inclusive start
+// and end IDs and the count.
+fn extract_range(
+    value: RespArray,
+    name: &'static str,
+    rev: bool,
+) -> Result<(String, StreamId, StreamId, Option<usize>), CommandError> {
+    validate_variadic_command(&value, &[name], 3)?;
+    if value.len() != 4 && value.len() != 6 {
+        return Err(CommandError::InvalidArgument("syntax error".to_string()));
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let (first, second) = (args.next(), args.next());
+    let (start, end) = if rev {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    let start = extract_range_id(start, false)?;
+    let end = extract_range_id(end, true)?;
+    let count = match args.next() {
+        None => None,
+        Some(arg) if is_option(&arg, b"count") => {
+            // a negative count returns nothing
+            Some(usize::try_from(extract_int(args.next())?).unwrap_or(0))
+        }
+        Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+    };
+    Ok((key, start, end, count))
+}
+
+fn is_option(frame: &RespFrame, name: &[u8]) -> bool {
+    matches!(frame, RespFrame::BulkString(opt) if opt.eq_ignore_ascii_case(name))
+}
+
+fn invalid_id() -> CommandError {
+    CommandError::InvalidArgument(
+        "Invalid stream ID specified as stream command argument".to_string(),
+    )
+}
+
+// `ms-seq`, or `ms` with missing_seq as the sequence number
+fn parse_stream_id(s: &str, missing_seq: u64) -> Result<StreamId, CommandError> {
+    let (ms, seq) = match s.split_once('-') {
+        Some((ms, seq)) => (ms, seq.parse().map_err(|_| invalid_id())?),
+        None => (s, missing_seq),
+    };
+    Ok(StreamId::new(ms.parse().map_err(|_| invalid_id())?, seq))
+}
+
+// `*`, `ms-*` or an explicit ID
+fn extract_xadd_id(frame: Option<RespFrame>) -> Result<XAddId, CommandError> {
+    let id = extract_string(frame, "id")?;
+    if id == "*" {
+        return Ok(XAddId::Auto);
+    }
+    match id.strip_suffix("-*") {
+        Some(ms) => Ok(XAddId::AutoSeq(ms.parse().map_err(|_| invalid_id())?)),
+        None => Ok(XAddId::Explicit(parse_stream_id(&id, 0)?)),
+    }
+}
+
+// `-`, `+`, or an ID, exclusive when prefixed with `(`. A missing sequence number makes the
+// range cover the whole millisecond.
+fn extract_range_id(frame: Option<RespFrame>, end: bool) -> Result<StreamId, CommandError> {
+    let id = extract_string(frame, "id")?;
+    let (exclusive, id) = match id.strip_prefix('(') {
+        Some(id) => (true, id),
+        None => (false, id.as_str()),
+    };
+    let parsed = match id {
+        "-" if !exclusive => StreamId::MIN,
+        "+" if !exclusive => StreamId::MAX,
+        id => parse_stream_id(id, if end { u64::MAX } else { 0 })?,
+    };
+    if !exclusive {
+        return Ok(parsed);
+    }
+    let id = if end { parsed.prev() } else { parsed.next() };
+    id.ok_or_else(|| {
+        let side = if end { "end" } else { "start" };
+        CommandError::InvalidArgument(format!("invalid {} ID for the interval", side))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(bytes: &[u8]) -> Result<RespArray> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(bytes);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_xadd_xrange_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd: XAdd =
+            decode(b"*5\r\n$4\r\nxadd\r\n$1\r\ns\r\n$3\r\n1-1\r\n$1\r\nf\r\n$1\r\nv\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), BulkString::from("1-1").into());
+        let cmd: XAdd =
+            decode(b"*5\r\n$4\r\nxadd\r\n$1\r\ns\r\n$3\r\n1-*\r\n$1\r\nf\r\n$1\r\nw\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), BulkString::from("1-2").into());
+
+        // an ID not above the top item
+        let cmd: XAdd =
+            decode(b"*5\r\n$4\r\nxadd\r\n$1\r\ns\r\n$1\r\n1\r\n$1\r\nf\r\n$1\r\nv\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), BackendError::StreamIdTooSmall.into());
+
+        let cmd: XLen = decode(b"*2\r\n$4\r\nxlen\r\n$1\r\ns\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd: XRevRange = decode(
+            b"*6\r\n$9\r\nxrevrange\r\n$1\r\ns\r\n$1\r\n+\r\n$1\r\n-\r\n$5\r\nCOUNT\r\n$1\r\n1\r\n",
+        )?
+        .try_into()?;
+        let entry = RespArray::new([
+            BulkString::from("1-2").into(),
+            RespArray::new([BulkString::from("f").into(), BulkString::from("w").into()]).into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), RespArray::new([entry.into()]).into());
+
+        // `(1-1` starts right after 1-1, `1` ends after every 1-* ID
+        let cmd: XRange =
+            decode(b"*4\r\n$6\r\nxrange\r\n$1\r\ns\r\n$4\r\n(1-1\r\n$1\r\n1\r\n")?.try_into()?;
+        assert_eq!(
+            (cmd.start, cmd.end),
+            (StreamId::new(1, 2), StreamId::new(1, u64::MAX))
+        );
+
+        let frame = decode(b"*4\r\n$6\r\nxrange\r\n$1\r\ns\r\n$3\r\nx-1\r\n$1\r\n+\r\n")?;
+        assert!(XRange::try_from(frame).is_err());
+        let frame = decode(b"*4\r\n$4\r\nxadd\r\n$1\r\ns\r\n$1\r\n*\r\n$1\r\nf\r\n")?;
+        assert!(XAdd::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xread_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd: XRead = decode(
+            b"*6\r\n$5\r\nxread\r\n$5\r\nCOUNT\r\n$1\r\n1\r\n$7\r\nstreams\r\n$1\r\ns\r\n$1\r\n0\r\n",
+        )?
+        .try_into()?;
+        assert_eq!(cmd.ids, vec![Some(StreamId::MIN)]);
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        // BLOCK with `$` waits for the next entry
+        let cmd: XRead = decode(
+            b"*6\r\n$5\r\nxread\r\n$5\r\nblock\r\n$1\r\n0\r\n$7\r\nSTREAMS\r\n$1\r\ns\r\n$1\r\n$\r\n",
+        )?
+        .try_into()?;
+        let reader = backend.clone();
+        let read = tokio::spawn(async move { cmd.block(&reader).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let id = XAddId::Explicit(StreamId::new(5, 0));
+        let fields = vec![("f".to_string(), BulkString::from("v").into())];
+        backend.xadd("s", id, fields.clone(), false)?;
+        let entries = RespArray::new([entry_frame(StreamId::new(5, 0), fields)]);
+        let expected =
+            RespArray::new([RespArray::new([BulkString::from("s").into(), entries.into()]).into()]);
+        assert_eq!(read.await?, expected.into());
+
+        // a short block times out
+        let cmd: XRead = decode(
+            b"*6\r\n$5\r\nxread\r\n$5\r\nblock\r\n$2\r\n10\r\n$7\r\nSTREAMS\r\n$1\r\ns\r\n$1\r\n$\r\n",
+        )?
+        .try_into()?;
+        assert_eq!(cmd.block(&backend).await, RespFrame::Null(RespNull));
+
+        let frame =
+            decode(b"*5\r\n$5\r\nxread\r\n$7\r\nstreams\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\n0\r\n")?;
+        assert!(XRead::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xreadgroup_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd: XGroupCreate = decode(
+            b"*6\r\n$6\r\nxgroup\r\n$6\r\ncreate\r\n$1\r\ns\r\n$1\r\ng\r\n$1\r\n$\r\n$8\r\nMKSTREAM\r\n",
+        )?
+        .try_into()?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+
+        // a blocked `>` read is served by the next entry
+        let cmd: XReadGroup = decode(
+            b"*9\r\n$10\r\nxreadgroup\r\n$5\r\nGROUP\r\n$1\r\ng\r\n$1\r\nc\r\n$5\r\nblock\r\n$1\r\n0\r\n$7\r\nstreams\r\n$1\r\ns\r\n$1\r\n>\r\n",
+        )?
+        .try_into()?;
+        let reader = backend.clone();
+        let read = tokio::spawn(async move { cmd.block(&reader).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let id = XAddId::Explicit(StreamId::new(5, 0));
+        let fields = vec![("f".to_string(), BulkString::from("v").into())];
+        backend.xadd("s", id, fields.clone(), false)?;
+        let entries = RespArray::new([entry_frame(StreamId::new(5, 0), fields)]);
+        let expected =
+            RespArray::new([RespArray::new([BulkString::from("s").into(), entries.into()]).into()]);
+        assert_eq!(read.await?, expected.into());
+
+        let cmd: XAck =
+            decode(b"*4\r\n$4\r\nxack\r\n$1\r\ns\r\n$1\r\ng\r\n$3\r\n5-0\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd: XInfoGroups =
+            decode(b"*3\r\n$5\r\nxinfo\r\n$6\r\ngroups\r\n$1\r\ns\r\n")?.try_into()?;
+        let RespFrame::Array(groups) = cmd.execute(&backend) else {
+            panic!("XINFO GROUPS isn't an array");
+        };
+        let RespFrame::Map(group) = &groups[0] else {
+            panic!("XINFO GROUPS doesn't list maps");
+        };
+        assert_eq!(group.get("pending"), Some(&RespFrame::Integer(0)));
+        assert_eq!(group.get("lag"), Some(&RespFrame::Integer(0)));
+        let cmd: XInfoConsumers =
+            decode(b"*4\r\n$5\r\nxinfo\r\n$9\r\nconsumers\r\n$1\r\ns\r\n$1\r\nx\r\n")?
+                .try_into()?;
+        assert!(matches!(cmd.execute(&backend), RespFrame::Error(_)));
+        let cmd: XInfoStream =
+            decode(b"*3\r\n$5\r\nxinfo\r\n$6\r\nstream\r\n$1\r\ns\r\n")?.try_into()?;
+        let RespFrame::Map(info) = cmd.execute(&backend) else {
+            panic!("XINFO STREAM isn't a map");
+        };
+        assert_eq!(info.get("groups"), Some(&RespFrame::Integer(1)));
+        assert_eq!(
+            info.get("last-generated-id"),
+            Some(&BulkString::from("5-0").into())
+        );
+
+        let cmd: XGroupCreateConsumer = decode(
+            b"*5\r\n$6\r\nxgroup\r\n$14\r\ncreateconsumer\r\n$1\r\ns\r\n$1\r\ng\r\n$1\r\nd\r\n",
+        )?
+        .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd: XGroupSetId =
+            decode(b"*5\r\n$6\r\nxgroup\r\n$5\r\nsetid\r\n$1\r\ns\r\n$1\r\ng\r\n$1\r\n$\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.id, None);
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        let cmd: XSetId = decode(b"*3\r\n$6\r\nxsetid\r\n$1\r\ns\r\n$3\r\n9-0\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+
+        let cmd: XGroupDestroy =
+            decode(b"*4\r\n$6\r\nxgroup\r\n$7\r\ndestroy\r\n$1\r\ns\r\n$1\r\ng\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let frame = decode(
+            b"*7\r\n$10\r\nxreadgroup\r\n$5\r\ngroup\r\n$1\r\ng\r\n$1\r\nc\r\n$7\r\nstreams\r\n$1\r\ns\r\n$1\r\ns\r\n",
+        )?;
+        assert!(XReadGroup::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xpending_xclaim_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.xgroup_create("s", "g", None, true)?;
+        let fields = vec![("f".to_string(), BulkString::from("v").into())];
+        backend.xadd("s", XAddId::Explicit(StreamId::new(1, 0)), fields, false)?;
+        backend.xreadgroup("g", "alice", &["s".to_string()], &[None], None, false)?;
+
+        let cmd: XPending =
+            decode(b"*3\r\n$8\r\nxpending\r\n$1\r\ns\r\n$1\r\ng\r\n")?.try_into()?;
+        let consumers = RespArray::new([RespArray::new([
+            BulkString::from("alice").into(),
+            BulkString::from("1").into(),
+        ])
+        .into()]);
+        let expected = RespArray::new([
+            RespFrame::Integer(1),
+            BulkString::from("1-0").into(),
+            BulkString::from("1-0").into(),
+            consumers.into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        // not idle long enough
+        let cmd: XClaim = decode(
+            b"*7\r\n$6\r\nxclaim\r\n$1\r\ns\r\n$1\r\ng\r\n$3\r\nbob\r\n$5\r\n60000\r\n$3\r\n1-0\r\n$6\r\nJUSTID\r\n",
+        )?
+        .try_into()?;
+        assert!(cmd.options.justid);
+        assert_eq!(cmd.execute(&backend), RespArray::new(vec![]).into());
+
+        let cmd: XAutoClaim = decode(
+            b"*7\r\n$10\r\nxautoclaim\r\n$1\r\ns\r\n$1\r\ng\r\n$3\r\nbob\r\n$1\r\n0\r\n$1\r\n-\r\n$6\r\njustid\r\n",
+        )?
+        .try_into()?;
+        let expected = RespArray::new([
+            BulkString::from("0-0").into(),
+            RespArray::new([BulkString::from("1-0").into()]).into(),
+            RespArray::new(vec![]).into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: XPending = decode(
+            b"*6\r\n$8\r\nxpending\r\n$1\r\ns\r\n$1\r\ng\r\n$1\r\n-\r\n$1\r\n+\r\n$2\r\n10\r\n",
+        )?
+        .try_into()?;
+        let RespFrame::Array(pending) = cmd.execute(&backend) else {
+            panic!("expected an array");
+        };
+        let RespFrame::Array(entry) = &pending[0] else {
+            panic!("expected an array");
+        };
+        assert_eq!(entry[1], BulkString::from("bob").into());
+        // JUSTID doesn't count as a delivery
+        assert_eq!(entry[3], RespFrame::Integer(1));
+
+        let cmd: XPending =
+            decode(b"*3\r\n$8\r\nxpending\r\n$1\r\ns\r\n$4\r\nnope\r\n")?.try_into()?;
+        assert_eq!(
+            cmd.execute(&backend),
+            BackendError::NoGroup("s".to_string(), "nope".to_string()).into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xtrim_xdel_commands() -> Result<()> {
+        let backend = Backend::new();
+        for id in [b"1", b"2", b"3"] {
+            let frame = [
+                b"*8\r\n$4\r\nxadd\r\n$1\r\ns\r\n$6\r\nMAXLEN\r\n$1\r\n=\r\n$1\r\n2\r\n$1\r\n"
+                    .as_slice(),
+                id,
+                b"\r\n$1\r\nf\r\n$1\r\nv\r\n",
+            ]
+            .concat();
+            let cmd: XAdd = decode(&frame)?.try_into()?;
+            cmd.execute(&backend);
+        }
+        assert_eq!(backend.xlen("s"), Ok(2));
+
+        let cmd: XTrim =
+            decode(b"*4\r\n$5\r\nxtrim\r\n$1\r\ns\r\n$5\r\nminid\r\n$1\r\n3\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd: XDel =
+            decode(b"*4\r\n$4\r\nxdel\r\n$1\r\ns\r\n$3\r\n3-0\r\n$1\r\n9\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        // LIMIT needs `~`
+        let frame = decode(
+            b"*6\r\n$5\r\nxtrim\r\n$1\r\ns\r\n$6\r\nmaxlen\r\n$1\r\n0\r\n$5\r\nlimit\r\n$1\r\n5\r\n",
+        )?;
+        assert!(XTrim::try_from(frame).is_err());
+        let frame = decode(
+            b"*7\r\n$5\r\nxtrim\r\n$1\r\ns\r\n$6\r\nmaxlen\r\n$1\r\n~\r\n$1\r\n0\r\n$5\r\nlimit\r\n$1\r\n5\r\n",
+        )?;
+        let cmd = XTrim::try_from(frame)?;
+        assert!(cmd.trim.approx);
+        assert_eq!(cmd.trim.limit, Some(5));
+
+        Ok(())
+    }
+}
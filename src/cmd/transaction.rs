@@ -0,0 +1,529 @@
+use crate::cmd::{
+    extract_args, extract_string, hold_thread, validate_command, validate_variadic_command,
+    Command, CommandError, CommandExecutor, Discard, Exec, Multi, Reset, Transaction, Unwatch,
+    Watch, RESP_OK,
+};
+use crate::{
+    Backend, RespArray, RespFrame, RespNullArray, SimpleError, SimpleString, Subscriptions,
+};
+
+impl Transaction {
+    /// Whether MULTI was called and the commands are being queued.
+    pub fn is_active(&self) -> bool {
+        self.queued.is_some()
+    }
+
+    /// Queue cmd for EXEC. MULTI can't be nested, and a command that can't run in a
+    /// transaction, one authenticating, subscribing or replicating the connection or DEBUG
+    /// RELOAD, fails it. Blocking commands are queued, they reply right away in EXEC.
+    pub fn queue(&mut self, cmd: Command, request: Option<RespArray>) -> RespFrame {
+        let Some(queued) = &mut self.queued else {
+            return SimpleError::new("ERR no transaction to queue to").into();
+        };
+        match cmd {
+            Command::Multi(_) => SimpleError::new("ERR MULTI calls can not be nested").into(),
+            Command::Hello(_)
+            | Command::Auth(_)
+            | Command::Watch(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::PSubscribe(_)
+            | Command::PUnsubscribe(_)
+            | Command::SSubscribe(_)
+            | Command::SUnsubscribe(_)
+            | Command::ReplConf(_)
+            | Command::PSync(_)
+            | Command::Sync(_)
+            | Command::DebugReload(_) => {
+                self.failed = true;
+                SimpleError::new("ERR Command not allowed inside a transaction").into()
+            }
+            cmd => {
+                queued.push((cmd, request));
+                SimpleString::new("QUEUED").into()
+            }
+        }
+    }
+
+    /// Fail the transaction, if any, for a command that couldn't be parsed.
+    pub fn fail(&mut self) {
+        if self.is_active() {
+            self.failed = true;
+        }
+    }
+
+    /// Discard the transaction and drop the watches, when the connection is reset or closed.
+    pub fn reset(&mut self, backend: &Backend) {
+        self.discard();
+        self.unwatch(backend);
+    }
+
+    fn discard(&mut self) -> Option<Vec<(Command, Option<RespArray>)>> {
+        self.failed = false;
+        self.queued.take()
+    }
+
+    fn unwatch(&mut self, backend: &Backend) {
+        for (key, _) in self.watched.drain(..) {
+            backend.unwatch(&key);
+        }
+    }
+}
+
+impl CommandExecutor for Multi {
+    // the transaction is the connection's
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR MULTI is not allowed in this context").into()
+    }
+}
+
+impl Multi {
+    pub fn apply(self, transaction: &mut Transaction) -> RespFrame {
+        if transaction.is_active() {
+            return SimpleError::new("ERR MULTI calls can not be nested").into();
+        }
+        transaction.queued = Some(Vec::new());
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Exec {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR EXEC is not allowed in this context").into()
+    }
+}
+
+impl Exec {
+    /// Run the queued commands one after the other with no other command interleaved,
+    /// replying their replies, or a null array if a watched key was modified. Blocking
+    /// commands don't block, those about the connection run against the one subscriptions is
+    /// of. The keys are unwatched either way. The writes are propagated
+    /// as a transaction.
+    pub fn apply(
+        self,
+        backend: &Backend,
+        subscriptions: &Subscriptions,
+        transaction: &mut Transaction,
+    ) -> RespFrame {
+        let failed = transaction.failed;
+        let Some(queued) = transaction.discard() else {
+            return SimpleError::new("ERR EXEC without MULTI").into();
+        };
+        let reply = if failed {
+            SimpleError::new("EXECABORT Transaction discarded because of previous errors.").into()
+        } else {
+            // it holds its thread like a script
+            hold_thread(|| {
+                backend.run_exclusive(|| {
+                    backend.run_logged(|| {
+                        let watched = &transaction.watched;
+                        if watched
+                            .iter()
+                            .any(|(key, version)| backend.watch_version(key) != Some(*version))
+                        {
+                            return RespFrame::NullArray(RespNullArray);
+                        }
+                        let replies: Vec<RespFrame> = queued
+                            .into_iter()
+                            .map(|(cmd, request)| match cmd.is_about_connection() {
+                                true => cmd.apply_to_connection(backend, subscriptions),
+                                false => cmd.execute_propagating(backend, request),
+                            })
+                            .collect();
+                        RespArray::new(replies).into()
+                    })
+                })
+            })
+        };
+        transaction.unwatch(backend);
+        reply
+    }
+}
+
+impl CommandExecutor for Discard {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR DISCARD is not allowed in this context").into()
+    }
+}
+
+impl Discard {
+    pub fn apply(self, backend: &Backend, transaction: &mut Transaction) -> RespFrame {
+        if transaction.discard().is_none() {
+            return SimpleError::new("ERR DISCARD without MULTI").into();
+        }
+        transaction.unwatch(backend);
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Watch {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR WATCH is not allowed in this context").into()
+    }
+}
+
+impl Watch {
+    /// Watch the keys for the next EXEC of the connection.
+    pub fn apply(self, backend: &Backend, transaction: &mut Transaction) -> RespFrame {
+        for key in self.keys {
+            if transaction
+                .watched
+                .iter()
+                .all(|(watched, _)| *watched != key)
+            {
+                let version = backend.watch(&key);
+                transaction.watched.push((key, version));
+            }
+        }
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Unwatch {
+    // queued in a transaction, whose EXEC unwatches anyway
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        RESP_OK.clone()
+    }
+}
+
+impl Unwatch {
+    pub fn apply(self, backend: &Backend, transaction: &mut Transaction) -> RespFrame {
+        transaction.unwatch(backend);
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Reset {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        SimpleError::new("ERR RESET is not allowed in this context").into()
+    }
+}
+
+impl Reset {
+    /// Bring the connection back to its state when it connected: no transaction, watch,
+    /// subscription or tracking, and authenticated as the default user only if it needs no
+    /// password. The protocol is switched back by the connection.
+    pub fn apply(
+        self,
+        backend: &Backend,
+        subscriptions: &mut Subscriptions,
+        transaction: &mut Transaction,
+    ) -> RespFrame {
+        transaction.reset(backend);
+        backend.unsubscribe_all(subscriptions);
+        let _ = backend.client_tracking(subscriptions.id(), None);
+        backend.acl_connect(subscriptions.id());
+        SimpleString::new("RESET").into()
+    }
+}
+
+impl TryFrom<RespArray> for Multi {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["multi"], 0)?;
+        Ok(Multi)
+    }
+}
+
+impl TryFrom<RespArray> for Exec {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exec"], 0)?;
+        Ok(Exec)
+    }
+}
+
+impl TryFrom<RespArray> for Discard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["discard"], 0)?;
+        Ok(Discard)
+    }
+}
+
+// WATCH key [key ...]
+impl TryFrom<RespArray> for Watch {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["watch"], 1)?;
+
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|key| extract_string(Some(key), "key"))
+            .collect::<Result<_, _>>()?;
+        Ok(Watch { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Unwatch {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unwatch"], 0)?;
+        Ok(Unwatch)
+    }
+}
+
+impl TryFrom<RespArray> for Reset {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["reset"], 0)?;
+        Ok(Reset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespDecode, RespNull, Subscriptions};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn decode(s: &str) -> Result<Command> {
+        let mut buf = BytesMut::from(s);
+        Ok(RespArray::decode(&mut buf)?.try_into()?)
+    }
+
+    async fn run(
+        backend: &Backend,
+        subscriptions: &mut Subscriptions,
+        transaction: &mut Transaction,
+        s: &str,
+    ) -> Result<RespFrame> {
+        let mut frames = decode(s)?
+            .execute_on_connection(backend, None, subscriptions, transaction)
+            .await;
+        Ok(frames.remove(0))
+    }
+
+    #[tokio::test]
+    async fn test_transaction() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let (subs, tx) = (&mut subscriptions, &mut transaction);
+
+        let exec = "*1\r\n$4\r\nexec\r\n";
+        assert_eq!(
+            run(&backend, subs, tx, exec).await?,
+            SimpleError::new("ERR EXEC without MULTI").into()
+        );
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?,
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?,
+            SimpleError::new("ERR MULTI calls can not be nested").into()
+        );
+
+        // queued, not run
+        let set = "*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n";
+        let get = "*2\r\n$3\r\nget\r\n$1\r\nk\r\n";
+        assert_eq!(
+            run(&backend, subs, tx, set).await?,
+            SimpleString::new("QUEUED").into()
+        );
+        assert_eq!(
+            run(&backend, subs, tx, get).await?,
+            SimpleString::new("QUEUED").into()
+        );
+        assert_eq!(backend.get("k")?, None);
+
+        assert_eq!(
+            run(&backend, subs, tx, exec).await?,
+            RespArray::new(vec![RESP_OK.clone(), BulkString::from("v").into()]).into()
+        );
+        assert!(!tx.is_active());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transaction_discard() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let (subs, tx) = (&mut subscriptions, &mut transaction);
+
+        let discard = "*1\r\n$7\r\ndiscard\r\n";
+        assert_eq!(
+            run(&backend, subs, tx, discard).await?,
+            SimpleError::new("ERR DISCARD without MULTI").into()
+        );
+        run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+        run(
+            &backend,
+            subs,
+            tx,
+            "*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n",
+        )
+        .await?;
+        assert_eq!(run(&backend, subs, tx, discard).await?, RESP_OK.clone());
+        assert_eq!(backend.get("k")?, None);
+
+        // a command that failed to queue aborts EXEC
+        run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+        tx.fail();
+        run(&backend, subs, tx, "*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$4\r\nexec\r\n").await?,
+            SimpleError::new("EXECABORT Transaction discarded because of previous errors.").into()
+        );
+
+        // blocking commands don't block
+        run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+        run(
+            &backend,
+            subs,
+            tx,
+            "*3\r\n$5\r\nblpop\r\n$1\r\nl\r\n$1\r\n0\r\n",
+        )
+        .await?;
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$4\r\nexec\r\n").await?,
+            RespArray::new(vec![RespFrame::Null(RespNull)]).into()
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_transaction_not_allowed() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let (subs, tx) = (&mut subscriptions, &mut transaction);
+        let not_allowed: RespFrame =
+            SimpleError::new("ERR Command not allowed inside a transaction").into();
+
+        // subscribing and reloading are rejected, and abort EXEC
+        for cmd in [
+            "*2\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n",
+            "*2\r\n$10\r\npsubscribe\r\n$2\r\nc*\r\n",
+            "*2\r\n$10\r\nssubscribe\r\n$2\r\nch\r\n",
+            "*1\r\n$11\r\nunsubscribe\r\n",
+            "*2\r\n$5\r\ndebug\r\n$6\r\nreload\r\n",
+        ] {
+            run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+            assert_eq!(run(&backend, subs, tx, cmd).await?, not_allowed);
+            assert_eq!(
+                run(&backend, subs, tx, "*1\r\n$4\r\nexec\r\n").await?,
+                SimpleError::new("EXECABORT Transaction discarded because of previous errors.")
+                    .into()
+            );
+        }
+        assert!(!subs.is_subscribed());
+
+        // blocking pops reply right away, on a thread EXEC holds
+        run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+        run(
+            &backend,
+            subs,
+            tx,
+            "*3\r\n$8\r\nbzpopmin\r\n$1\r\nz\r\n$1\r\n0\r\n",
+        )
+        .await?;
+        run(
+            &backend,
+            subs,
+            tx,
+            "*3\r\n$5\r\nbrpop\r\n$1\r\nl\r\n$1\r\n0\r\n",
+        )
+        .await?;
+        let RespFrame::Array(replies) = run(&backend, subs, tx, "*1\r\n$4\r\nexec\r\n").await?
+        else {
+            panic!("EXEC didn't reply an array");
+        };
+        assert_eq!(replies.len(), 2);
+        assert!(replies
+            .iter()
+            .all(|reply| !matches!(reply, RespFrame::Error(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transaction_connection_commands() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let id = subscriptions.id();
+        let (subs, tx) = (&mut subscriptions, &mut transaction);
+
+        // run against the connection by EXEC
+        run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+        assert_eq!(
+            run(&backend, subs, tx, "*2\r\n$6\r\nclient\r\n$2\r\nid\r\n").await?,
+            SimpleString::new("QUEUED").into()
+        );
+        run(&backend, subs, tx, "*1\r\n$4\r\nping\r\n").await?;
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$4\r\nexec\r\n").await?,
+            RespArray::new(vec![
+                RespFrame::Integer(id as i64),
+                SimpleString::new("PONG").into()
+            ])
+            .into()
+        );
+
+        // replicating the connection can't be queued
+        run(&backend, subs, tx, "*1\r\n$5\r\nmulti\r\n").await?;
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$4\r\nsync\r\n").await?,
+            SimpleError::new("ERR Command not allowed inside a transaction").into()
+        );
+        assert!(matches!(
+            run(&backend, subs, tx, "*1\r\n$4\r\nexec\r\n").await?,
+            RespFrame::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch() -> Result<()> {
+        let backend = Backend::new();
+        let (mut subscriptions, _rx) = backend.subscriptions();
+        let mut transaction = Transaction::default();
+        let (subs, tx) = (&mut subscriptions, &mut transaction);
+        let multi = "*1\r\n$5\r\nmulti\r\n";
+        let incr = "*2\r\n$4\r\nincr\r\n$1\r\nk\r\n";
+        let exec = "*1\r\n$4\r\nexec\r\n";
+
+        let watch = "*2\r\n$5\r\nwatch\r\n$1\r\nk\r\n";
+        assert_eq!(run(&backend, subs, tx, watch).await?, RESP_OK.clone());
+        run(&backend, subs, tx, multi).await?;
+        run(&backend, subs, tx, incr).await?;
+        // modified by another connection before EXEC
+        backend.incr_by("k", 10)?;
+        assert_eq!(
+            run(&backend, subs, tx, exec).await?,
+            RespFrame::NullArray(RespNullArray)
+        );
+        assert_eq!(backend.watch_version("k"), None);
+
+        // not modified
+        run(&backend, subs, tx, watch).await?;
+        run(&backend, subs, tx, multi).await?;
+        run(&backend, subs, tx, incr).await?;
+        assert_eq!(
+            run(&backend, subs, tx, exec).await?,
+            RespArray::new(vec![RespFrame::Integer(11)]).into()
+        );
+
+        // WATCH can't be queued
+        run(&backend, subs, tx, multi).await?;
+        assert_eq!(
+            run(&backend, subs, tx, watch).await?,
+            SimpleError::new("ERR Command not allowed inside a transaction").into()
+        );
+        assert_eq!(
+            run(&backend, subs, tx, "*1\r\n$5\r\nreset\r\n").await?,
+            SimpleString::new("RESET").into()
+        );
+        assert!(!tx.is_active());
+        Ok(())
+    }
+}
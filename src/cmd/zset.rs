@@ -0,0 +1,1337 @@
+use crate::cmd::{
+    bpop_wait, extract_args, extract_cursor, extract_float, extract_int, extract_key_and_count,
+    extract_keys_and_timeout, extract_numkeys, extract_string, scan_reply, validate_command,
+    validate_variadic_command, BZPopMax, BZPopMin, CommandError, CommandExecutor, ZAdd, ZCard,
+    ZCount, ZDiff, ZDiffStore, ZIncrBy, ZInter, ZInterStore, ZLexCount, ZMScore, ZPopMax, ZPopMin,
+    ZRandMember, ZRange, ZRangeByLex, ZRangeByScore, ZRank, ZRem, ZRemRangeByLex, ZRemRangeByRank,
+    ZRemRangeByScore, ZRevRange, ZRevRangeByLex, ZRevRangeByScore, ZRevRank, ZScan, ZScore, ZUnion,
+    ZUnionStore,
+};
+use crate::{
+    glob_match, Aggregate, Backend, BackendError, BulkString, LexBound, PopFrom, Popped, RespArray,
+    RespFrame, RespNull, ScoreBound, SetOp, ZAddOptions, ZRangeBy, ZRangeOptions,
+};
+
+impl CommandExecutor for ZAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !self.incr {
+            return count_reply(backend.zadd_with(&self.key, self.members, &self.options));
+        }
+        let Some((increment, member)) = self.members.into_iter().next() else {
+            return RespFrame::Null(RespNull);
+        };
+        match backend.zadd_incr(&self.key, increment, &member, &self.options) {
+            Ok(Some(score)) => RespFrame::Double(score),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zrem(&self.key, &self.members))
+    }
+}
+
+impl CommandExecutor for ZScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zscore(&self.key, &self.member) {
+            Ok(Some(score)) => RespFrame::Double(score),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zcard(&self.key))
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        range_reply(backend.zrange(&self.key, &self.options), self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZRevRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        range_reply(backend.zrange(&self.key, &self.options), self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZRangeByScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        range_reply(backend.zrange(&self.key, &self.options), self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZRevRangeByScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        range_reply(backend.zrange(&self.key, &self.options), self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZRangeByLex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        range_reply(backend.zrange(&self.key, &self.options), false)
+    }
+}
+
+impl CommandExecutor for ZRevRangeByLex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        range_reply(backend.zrange(&self.key, &self.options), false)
+    }
+}
+
+impl CommandExecutor for ZLexCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zlexcount(&self.key, &self.min, &self.max))
+    }
+}
+
+impl CommandExecutor for ZIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zincrby(&self.key, self.increment, &self.member) {
+            Ok(score) => RespFrame::Double(score),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZRank {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        rank_reply(
+            backend.zrank(&self.key, &self.member, false),
+            self.with_score,
+        )
+    }
+}
+
+impl CommandExecutor for ZRevRank {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        rank_reply(
+            backend.zrank(&self.key, &self.member, true),
+            self.with_score,
+        )
+    }
+}
+
+impl CommandExecutor for ZCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zcount(&self.key, self.min, self.max))
+    }
+}
+
+impl CommandExecutor for ZPopMin {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        range_reply(backend.zpop(&self.key, count, false), true)
+    }
+}
+
+impl CommandExecutor for ZPopMax {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let count = self.count.unwrap_or(1);
+        range_reply(backend.zpop(&self.key, count, true), true)
+    }
+}
+
+impl CommandExecutor for BZPopMin {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        popped_reply(backend.zmpop(&self.keys, false, 1))
+    }
+}
+
+impl CommandExecutor for BZPopMax {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        popped_reply(backend.zmpop(&self.keys, true, 1))
+    }
+}
+
+impl BZPopMin {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        popped_reply(bpop_wait(backend, &self.keys, PopFrom::ZSetMin, 1, self.timeout).await)
+    }
+}
+
+impl BZPopMax {
+    pub async fn block(self, backend: &Backend) -> RespFrame {
+        popped_reply(bpop_wait(backend, &self.keys, PopFrom::ZSetMax, 1, self.timeout).await)
+    }
+}
+
+impl CommandExecutor for ZUnion {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let result = backend.zset_op(SetOp::Union, &self.keys, &self.weights, self.aggregate);
+        range_reply(result, self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZInter {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let result = backend.zset_op(SetOp::Inter, &self.keys, &self.weights, self.aggregate);
+        range_reply(result, self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZDiff {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let result = backend.zset_op(SetOp::Diff, &self.keys, &[], Aggregate::Sum);
+        range_reply(result, self.with_scores)
+    }
+}
+
+impl CommandExecutor for ZUnionStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zset_op_store(
+            SetOp::Union,
+            &self.destination,
+            &self.keys,
+            &self.weights,
+            self.aggregate,
+        ))
+    }
+}
+
+impl CommandExecutor for ZInterStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zset_op_store(
+            SetOp::Inter,
+            &self.destination,
+            &self.keys,
+            &self.weights,
+            self.aggregate,
+        ))
+    }
+}
+
+impl CommandExecutor for ZDiffStore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        count_reply(backend.zset_op_store(
+            SetOp::Diff,
+            &self.destination,
+            &self.keys,
+            &[],
+            Aggregate::Sum,
+        ))
+    }
+}
+
+impl CommandExecutor for ZRemRangeByRank {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let by = ZRangeBy::Rank(self.start, self.stop);
+        count_reply(backend.zremrange(&self.key, &by))
+    }
+}
+
+impl CommandExecutor for ZRemRangeByScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let by = ZRangeBy::Score(self.min, self.max);
+        count_reply(backend.zremrange(&self.key, &by))
+    }
+}
+
+impl CommandExecutor for ZRemRangeByLex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let by = ZRangeBy::Lex(self.min, self.max);
+        count_reply(backend.zremrange(&self.key, &by))
+    }
+}
+
+impl CommandExecutor for ZRandMember {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let picked = backend.zrandmember(&self.key, self.count.unwrap_or(1));
+        if self.count.is_some() {
+            return range_reply(picked, self.with_scores);
+        }
+        match picked.map(|picked| picked.into_iter().next()) {
+            Ok(Some((member, _))) => BulkString::new(member).into(),
+            Ok(None) => RespFrame::Null(RespNull),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZMScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zmscore(&self.key, &self.members) {
+            Ok(scores) => RespArray::new(
+                scores
+                    .into_iter()
+                    .map(|score| match score {
+                        Some(score) => RespFrame::Double(score),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for ZScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let (next, page) = match backend.zscan(&self.key, self.cursor, self.count) {
+            Ok(scanned) => scanned,
+            Err(e) => return e.into(),
+        };
+
+        let mut elements = Vec::with_capacity(page.len() * 2);
+        for (member, score) in page {
+            if let Some(pattern) = &self.pattern {
+                if !glob_match(pattern.as_bytes(), member.as_bytes()) {
+                    continue;
+                }
+            }
+            elements.push(BulkString::new(member).into());
+            elements.push(RespFrame::Double(score));
+        }
+        scan_reply(next, elements)
+    }
+}
+
+// [key, member, score] for BZPOPMIN and BZPOPMAX
+fn popped_reply(result: Result<Option<Popped>, BackendError>) -> RespFrame {
+    match result {
+        Ok(Some((key, values))) => {
+            let mut reply = vec![BulkString::new(key).into()];
+            reply.extend(values);
+            RespArray::new(reply).into()
+        }
+        Ok(None) => RespFrame::Null(RespNull),
+        Err(e) => e.into(),
+    }
+}
+
+fn rank_reply(result: Result<Option<(usize, f64)>, BackendError>, with_score: bool) -> RespFrame {
+    match result {
+        Ok(None) => RespFrame::Null(RespNull),
+        Ok(Some((rank, _))) if !with_score => RespFrame::Integer(rank as i64),
+        Ok(Some((rank, score))) => {
+            RespArray::new([RespFrame::Integer(rank as i64), RespFrame::Double(score)]).into()
+        }
+        Err(e) => e.into(),
+    }
+}
+
+// the members, each followed by its score WITHSCORES
+fn range_reply(result: Result<Vec<(String, f64)>, BackendError>, with_scores: bool) -> RespFrame {
+    let members = match result {
+        Ok(members) => members,
+        Err(e) => return e.into(),
+    };
+    let mut reply = Vec::with_capacity(members.len() * if with_scores { 2 } else { 1 });
+    for (member, score) in members {
+        reply.push(BulkString::new(member).into());
+        if with_scores {
+            reply.push(RespFrame::Double(score));
+        }
+    }
+    RespArray::new(reply).into()
+}
+
+fn count_reply(result: Result<usize, BackendError>) -> RespFrame {
+    match result {
+        Ok(count) => RespFrame::Integer(count as i64),
+        Err(e) => e.into(),
+    }
+}
+
+impl TryFrom<RespArray> for ZAdd {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zadd"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let mut options = ZAddOptions::default();
+        let mut incr = false;
+        // the flags come first, the pairs start at the first argument that isn't one
+        while let Some(RespFrame::BulkString(opt)) = args.peek() {
+            match opt.to_ascii_lowercase().as_slice() {
+                b"nx" => options.nx = true,
+                b"xx" => options.xx = true,
+                b"gt" => options.gt = true,
+                b"lt" => options.lt = true,
+                b"ch" => options.ch = true,
+                b"incr" => incr = true,
+                _ => break,
+            }
+            args.next();
+        }
+        if options.nx && options.xx {
+            return Err(CommandError::InvalidArgument(
+                "XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if [options.nx, options.gt, options.lt]
+            .iter()
+            .filter(|set| **set)
+            .count()
+            > 1
+        {
+            return Err(CommandError::InvalidArgument(
+                "GT, LT, and/or NX options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        let args: Vec<_> = args.collect();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        if incr && args.len() > 2 {
+            return Err(CommandError::InvalidArgument(
+                "INCR option supports a single increment-element pair".to_string(),
+            ));
+        }
+        let mut members = Vec::with_capacity(args.len() / 2);
+        let mut args = args.into_iter();
+        while let Some(score) = args.next() {
+            let score = extract_float(Some(score))?;
+            members.push((score, extract_string(args.next(), "member")?));
+        }
+        Ok(ZAdd {
+            key,
+            members,
+            options,
+            incr,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRem {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zrem"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let members = args
+            .map(|member| extract_string(Some(member), "member"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ZRem { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for ZScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zscore"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let member = extract_string(args.next(), "member")?;
+        Ok(ZScore { key, member })
+    }
+}
+
+impl TryFrom<RespArray> for ZCard {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcard"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        Ok(ZCard { key })
+    }
+}
+
+impl TryFrom<RespArray> for ZRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, options, with_scores) = extract_zrange(value, "zrange", None, false)?;
+        Ok(ZRange {
+            key,
+            options,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRevRange {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, options, with_scores) =
+            extract_zrange(value, "zrevrange", Some(RangeKind::Rank), true)?;
+        Ok(ZRevRange {
+            key,
+            options,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRangeByScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, options, with_scores) =
+            extract_zrange(value, "zrangebyscore", Some(RangeKind::Score), false)?;
+        Ok(ZRangeByScore {
+            key,
+            options,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRevRangeByScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, options, with_scores) =
+            extract_zrange(value, "zrevrangebyscore", Some(RangeKind::Score), true)?;
+        Ok(ZRevRangeByScore {
+            key,
+            options,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRangeByLex {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, options, _) = extract_zrange(value, "zrangebylex", Some(RangeKind::Lex), false)?;
+        Ok(ZRangeByLex { key, options })
+    }
+}
+
+impl TryFrom<RespArray> for ZRevRangeByLex {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, options, _) =
+            extract_zrange(value, "zrevrangebylex", Some(RangeKind::Lex), true)?;
+        Ok(ZRevRangeByLex { key, options })
+    }
+}
+
+impl TryFrom<RespArray> for ZLexCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zlexcount"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let min = extract_lex_bound(args.next())?;
+        let max = extract_lex_bound(args.next())?;
+        Ok(ZLexCount { key, min, max })
+    }
+}
+
+impl TryFrom<RespArray> for ZIncrBy {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zincrby"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let increment = extract_float(args.next())?;
+        let member = extract_string(args.next(), "member")?;
+        Ok(ZIncrBy {
+            key,
+            increment,
+            member,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRank {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, member, with_score) = extract_rank_args(value, "zrank")?;
+        Ok(ZRank {
+            key,
+            member,
+            with_score,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZRevRank {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, member, with_score) = extract_rank_args(value, "zrevrank")?;
+        Ok(ZRevRank {
+            key,
+            member,
+            with_score,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZCount {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcount"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let min = extract_score_bound(args.next())?;
+        let max = extract_score_bound(args.next())?;
+        Ok(ZCount { key, min, max })
+    }
+}
+
+impl TryFrom<RespArray> for ZPopMin {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "zpopmin")?;
+        Ok(ZPopMin { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for ZPopMax {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (key, count) = extract_key_and_count(value, "zpopmax")?;
+        Ok(ZPopMax { key, count })
+    }
+}
+
+impl TryFrom<RespArray> for BZPopMin {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = extract_keys_and_timeout(value, "bzpopmin")?;
+        Ok(BZPopMin { keys, timeout })
+    }
+}
+
+impl TryFrom<RespArray> for BZPopMax {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (keys, timeout) = extract_keys_and_timeout(value, "bzpopmax")?;
+        Ok(BZPopMax { keys, timeout })
+    }
+}
+
+impl TryFrom<RespArray> for ZUnion {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zunion"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (keys, weights, aggregate, with_scores) = extract_zset_op_args(&mut args, true, true)?;
+        Ok(ZUnion {
+            keys,
+            weights,
+            aggregate,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZInter {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zinter"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (keys, weights, aggregate, with_scores) = extract_zset_op_args(&mut args, true, true)?;
+        Ok(ZInter {
+            keys,
+            weights,
+            aggregate,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZDiff {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zdiff"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (keys, _, _, with_scores) = extract_zset_op_args(&mut args, false, true)?;
+        Ok(ZDiff { keys, with_scores })
+    }
+}
+
+impl TryFrom<RespArray> for ZUnionStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zunionstore"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let destination = extract_string(args.next(), "destination")?;
+        let (keys, weights, aggregate, _) = extract_zset_op_args(&mut args, true, false)?;
+        Ok(ZUnionStore {
+            destination,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZInterStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zinterstore"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let destination = extract_string(args.next(), "destination")?;
+        let (keys, weights, aggregate, _) = extract_zset_op_args(&mut args, true, false)?;
+        Ok(ZInterStore {
+            destination,
+            keys,
+            weights,
+            aggregate,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZDiffStore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zdiffstore"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let destination = extract_string(args.next(), "destination")?;
+        let (keys, _, _, _) = extract_zset_op_args(&mut args, false, false)?;
+        Ok(ZDiffStore { destination, keys })
+    }
+}
+
+impl TryFrom<RespArray> for ZRemRangeByRank {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zremrangebyrank"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let start = extract_int(args.next())?;
+        let stop = extract_int(args.next())?;
+        Ok(ZRemRangeByRank { key, start, stop })
+    }
+}
+
+impl TryFrom<RespArray> for ZRemRangeByScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zremrangebyscore"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let min = extract_score_bound(args.next())?;
+        let max = extract_score_bound(args.next())?;
+        Ok(ZRemRangeByScore { key, min, max })
+    }
+}
+
+impl TryFrom<RespArray> for ZRemRangeByLex {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zremrangebylex"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let min = extract_lex_bound(args.next())?;
+        let max = extract_lex_bound(args.next())?;
+        Ok(ZRemRangeByLex { key, min, max })
+    }
+}
+
+impl TryFrom<RespArray> for ZRandMember {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zrandmember"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let count = args
+            .next()
+            .map(|count| extract_int(Some(count)))
+            .transpose()?;
+        let with_scores = match args.next() {
+            None => false,
+            Some(RespFrame::BulkString(opt)) if opt.eq_ignore_ascii_case(b"withscores") => true,
+            Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        if args.next().is_some() {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        Ok(ZRandMember {
+            key,
+            count,
+            with_scores,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ZMScore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zmscore"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let members = args
+            .map(|member| extract_string(Some(member), "member"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ZMScore { key, members })
+    }
+}
+
+impl TryFrom<RespArray> for ZScan {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_variadic_command(&value, &["zscan"], 2)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut scan = ZScan {
+            key: extract_string(args.next(), "key")?,
+            cursor: extract_cursor(args.next())?,
+            pattern: None,
+            count: 10,
+        };
+        while let Some(arg) = args.next() {
+            let opt = match arg {
+                RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            };
+            match opt.as_slice() {
+                b"match" => scan.pattern = Some(extract_string(args.next(), "pattern")?),
+                b"count" => match extract_int(args.next())? {
+                    count if count >= 1 => scan.count = count as usize,
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                },
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+// `numkeys key [key ...] [WEIGHTS weight [weight ...]] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]`,
+// WEIGHTS and AGGREGATE only if weighted and WITHSCORES only if scored
+fn extract_zset_op_args(
+    args: &mut impl Iterator<Item = RespFrame>,
+    weighted: bool,
+    scored: bool,
+) -> Result<(Vec<String>, Vec<f64>, Aggregate, bool), CommandError> {
+    let keys = extract_numkeys(args)?;
+    let mut weights = Vec::new();
+    let mut aggregate = Aggregate::Sum;
+    let mut with_scores = false;
+    while let Some(arg) = args.next() {
+        let opt = match arg {
+            RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        match opt.as_slice() {
+            b"weights" if weighted => {
+                weights = (0..keys.len())
+                    .map(|_| {
+                        extract_float(args.next()).map_err(|_| {
+                            CommandError::InvalidArgument("weight value is not a float".to_string())
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            b"aggregate" if weighted => {
+                aggregate = match args.next() {
+                    Some(RespFrame::BulkString(agg)) => match agg.to_ascii_lowercase().as_slice() {
+                        b"sum" => Aggregate::Sum,
+                        b"min" => Aggregate::Min,
+                        b"max" => Aggregate::Max,
+                        _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                    },
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                }
+            }
+            b"withscores" if scored => with_scores = true,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+    Ok((keys, weights, aggregate, with_scores))
+}
+
+// `key member [WITHSCORE]`
+fn extract_rank_args(
+    value: RespArray,
+    name: &'static str,
+) -> Result<(String, String, bool), CommandError> {
+    validate_variadic_command(&value, &[name], 2)?;
+    if value.len() > 4 {
+        return Err(CommandError::InvalidArgument("syntax error".to_string()));
+    }
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let member = extract_string(args.next(), "member")?;
+    let with_score = match args.next() {
+        None => false,
+        Some(RespFrame::BulkString(opt)) if opt.eq_ignore_ascii_case(b"withscore") => true,
+        Some(_) => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+    };
+    Ok((key, member, with_score))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+// `key start stop` and the options of a range command. The legacy commands come with their
+// kind of range, ZRANGE picks it with BYSCORE or BYLEX and also takes REV.
+fn extract_zrange(
+    value: RespArray,
+    name: &'static str,
+    kind: Option<RangeKind>,
+    rev: bool,
+) -> Result<(String, ZRangeOptions, bool), CommandError> {
+    validate_variadic_command(&value, &[name], 3)?;
+
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = extract_string(args.next(), "key")?;
+    let (start, stop) = (args.next(), args.next());
+    let unified = kind.is_none();
+    let mut kind = kind.unwrap_or(RangeKind::Rank);
+    let mut rev = rev;
+    let mut limit = None;
+    let mut with_scores = false;
+    while let Some(arg) = args.next() {
+        let opt = match arg {
+            RespFrame::BulkString(opt) => opt.to_ascii_lowercase(),
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        match opt.as_slice() {
+            b"withscores" => with_scores = true,
+            b"limit" if unified || kind != RangeKind::Rank => {
+                limit = Some((extract_int(args.next())?, extract_int(args.next())?))
+            }
+            b"byscore" if unified => kind = RangeKind::Score,
+            b"bylex" if unified => kind = RangeKind::Lex,
+            b"rev" if unified => rev = true,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+    if limit.is_some() && kind == RangeKind::Rank {
+        return Err(CommandError::InvalidArgument(
+            "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                .to_string(),
+        ));
+    }
+    if with_scores && kind == RangeKind::Lex {
+        return Err(CommandError::InvalidArgument(
+            "syntax error, WITHSCORES not supported in combination with BYLEX".to_string(),
+        ));
+    }
+
+    // ranks always come as start and stop, bounds come max first when walking down
+    let (first, second) = match kind {
+        RangeKind::Score | RangeKind::Lex if rev => (stop, start),
+        _ => (start, stop),
+    };
+    let by = match kind {
+        RangeKind::Rank => ZRangeBy::Rank(extract_int(first)?, extract_int(second)?),
+        RangeKind::Score => {
+            ZRangeBy::Score(extract_score_bound(first)?, extract_score_bound(second)?)
+        }
+        RangeKind::Lex => ZRangeBy::Lex(extract_lex_bound(first)?, extract_lex_bound(second)?),
+    };
+    let options = ZRangeOptions { by, rev, limit };
+    Ok((key, options, with_scores))
+}
+
+// a score, `(` prefixed when exclusive
+fn extract_score_bound(frame: Option<RespFrame>) -> Result<ScoreBound, CommandError> {
+    let err = || CommandError::InvalidArgument("min or max is not a float".to_string());
+    let bound = extract_string(frame, "score").map_err(|_| err())?;
+    let (exclusive, score) = match bound.strip_prefix('(') {
+        Some(score) => (true, score),
+        None => (false, bound.as_str()),
+    };
+    let score = score
+        .parse::<f64>()
+        .ok()
+        .filter(|score| !score.is_nan())
+        .ok_or_else(err)?;
+    Ok(if exclusive {
+        ScoreBound::Exclusive(score)
+    } else {
+        ScoreBound::Inclusive(score)
+    })
+}
+
+fn extract_lex_bound(frame: Option<RespFrame>) -> Result<LexBound, CommandError> {
+    let bound = extract_string(frame, "member")?;
+    match bound.as_bytes().first() {
+        Some(b'-') if bound.len() == 1 => Ok(LexBound::Min),
+        Some(b'+') if bound.len() == 1 => Ok(LexBound::Max),
+        Some(b'[') => Ok(LexBound::Inclusive(bound[1..].to_string())),
+        Some(b'(') => Ok(LexBound::Exclusive(bound[1..].to_string())),
+        _ => Err(CommandError::InvalidArgument(
+            "min or max not valid string range item".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_zadd_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$4\r\nzadd\r\n$1\r\nz\r\n$3\r\n1.5\r\n$1\r\na\r\n$4\r\n-inf\r\n$1\r\nb\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: ZAdd = frame.try_into()?;
+        assert_eq!(result.key, "z");
+        assert_eq!(
+            result.members,
+            vec![(1.5, "a".to_string()), (f64::NEG_INFINITY, "b".to_string())]
+        );
+
+        // a score without its member
+        buf.extend_from_slice(b"*5\r\n$4\r\nzadd\r\n$1\r\nz\r\n$1\r\n1\r\n$1\r\na\r\n$1\r\n2\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(ZAdd::try_from(frame).is_err());
+
+        buf.extend_from_slice(b"*4\r\n$4\r\nzadd\r\n$1\r\nz\r\n$3\r\nnan\r\n$1\r\na\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(ZAdd::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = ZAdd {
+            key: "z".to_string(),
+            members: vec![(1.0, "a".to_string()), (2.5, "b".to_string())],
+            options: ZAddOptions::default(),
+            incr: false,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd = ZScore {
+            key: "z".to_string(),
+            member: "b".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Double(2.5));
+        let cmd = ZScore {
+            key: "z".to_string(),
+            member: "x".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        let cmd = ZRem {
+            key: "z".to_string(),
+            members: vec!["a".to_string(), "x".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd = ZCard {
+            key: "z".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        Ok(())
+    }
+
+    fn decode(cmd: &[u8]) -> Result<RespArray> {
+        let mut buf = BytesMut::from(cmd);
+        Ok(RespArray::decode(&mut buf)?)
+    }
+
+    #[test]
+    fn test_zrange_from_resp_array() -> Result<()> {
+        let frame = decode(b"*8\r\n$6\r\nzrange\r\n$1\r\nz\r\n$4\r\n+inf\r\n$2\r\n(1\r\n$7\r\nBYSCORE\r\n$3\r\nrev\r\n$10\r\nWITHSCORES\r\n$5\r\nlimit\r\n")?;
+        // LIMIT without its arguments
+        assert!(ZRange::try_from(frame).is_err());
+
+        let frame = decode(b"*7\r\n$6\r\nzrange\r\n$1\r\nz\r\n$4\r\n+inf\r\n$2\r\n(1\r\n$7\r\nBYSCORE\r\n$3\r\nrev\r\n$10\r\nWITHSCORES\r\n")?;
+        let cmd: ZRange = frame.try_into()?;
+        assert_eq!(
+            cmd.options.by,
+            ZRangeBy::Score(
+                ScoreBound::Exclusive(1.0),
+                ScoreBound::Inclusive(f64::INFINITY)
+            )
+        );
+        assert!(cmd.options.rev && cmd.with_scores);
+
+        let frame = decode(b"*6\r\n$6\r\nzrange\r\n$1\r\nz\r\n$1\r\n-\r\n$2\r\n[c\r\n$5\r\nBYLEX\r\n$10\r\nWITHSCORES\r\n")?;
+        assert!(ZRange::try_from(frame).is_err());
+
+        let frame = decode(b"*7\r\n$9\r\nzrevrange\r\n$1\r\nz\r\n$1\r\n0\r\n$2\r\n-1\r\n$5\r\nLIMIT\r\n$1\r\n0\r\n$1\r\n1\r\n")?;
+        assert!(ZRevRange::try_from(frame).is_err());
+
+        let frame = decode(b"*4\r\n$13\r\nzrangebyscore\r\n$1\r\nz\r\n$1\r\na\r\n$1\r\n1\r\n")?;
+        assert!(ZRangeByScore::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.zadd(
+            "z",
+            vec![
+                (1.0, "a".to_string()),
+                (2.0, "b".to_string()),
+                (3.0, "c".to_string()),
+            ],
+        )?;
+
+        let cmd: ZRevRangeByScore = decode(b"*7\r\n$16\r\nzrevrangebyscore\r\n$1\r\nz\r\n$4\r\n+inf\r\n$4\r\n-inf\r\n$5\r\nLIMIT\r\n$1\r\n1\r\n$1\r\n5\r\n")?.try_into()?;
+        let expected = RespArray::new([BulkString::from("b").into(), BulkString::from("a").into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: ZRange = decode(
+            b"*5\r\n$6\r\nzrange\r\n$1\r\nz\r\n$1\r\n0\r\n$1\r\n0\r\n$10\r\nwithscores\r\n",
+        )?
+        .try_into()?;
+        let expected = RespArray::new([BulkString::from("a").into(), RespFrame::Double(1.0)]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrank_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd: ZIncrBy =
+            decode(b"*4\r\n$7\r\nzincrby\r\n$1\r\nz\r\n$3\r\n1.5\r\n$1\r\na\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Double(1.5));
+        backend.zadd("z", vec![(2.0, "b".to_string())])?;
+
+        let cmd: ZRevRank =
+            decode(b"*4\r\n$8\r\nzrevrank\r\n$1\r\nz\r\n$1\r\na\r\n$9\r\nWITHSCORE\r\n")?
+                .try_into()?;
+        let expected = RespArray::new([RespFrame::Integer(1), RespFrame::Double(1.5)]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: ZRank = decode(b"*3\r\n$5\r\nzrank\r\n$1\r\nz\r\n$1\r\nx\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        let cmd: ZCount =
+            decode(b"*4\r\n$6\r\nzcount\r\n$1\r\nz\r\n$2\r\n(1\r\n$4\r\n+inf\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrangebylex_commands() -> Result<()> {
+        let backend = Backend::new();
+        let members = ["a", "b", "c", "d"].iter().map(|m| (0.0, m.to_string()));
+        backend.zadd("z", members.collect())?;
+
+        let cmd: ZRevRangeByLex = decode(b"*7\r\n$14\r\nzrevrangebylex\r\n$1\r\nz\r\n$2\r\n(d\r\n$1\r\n-\r\n$5\r\nLIMIT\r\n$1\r\n0\r\n$1\r\n2\r\n")?.try_into()?;
+        let expected = RespArray::new([BulkString::from("c").into(), BulkString::from("b").into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: ZLexCount =
+            decode(b"*4\r\n$9\r\nzlexcount\r\n$1\r\nz\r\n$2\r\n[b\r\n$1\r\n+\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(3));
+
+        // a bound without `[` or `(`
+        let frame = decode(b"*4\r\n$11\r\nzrangebylex\r\n$1\r\nz\r\n$1\r\nb\r\n$1\r\n+\r\n")?;
+        assert!(ZRangeByLex::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zpop_commands() -> Result<()> {
+        let backend = Backend::new();
+        let members = [(1.0, "a"), (2.0, "b"), (3.0, "c")];
+        backend.zadd("z", members.map(|(s, m)| (s, m.to_string())).to_vec())?;
+
+        let cmd: ZPopMax = decode(b"*3\r\n$7\r\nzpopmax\r\n$1\r\nz\r\n$1\r\n2\r\n")?.try_into()?;
+        let expected = RespArray::new([
+            BulkString::from("c").into(),
+            RespFrame::Double(3.0),
+            BulkString::from("b").into(),
+            RespFrame::Double(2.0),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        // BZPOPMIN doesn't block when run by `execute`, as in a transaction
+        let cmd: BZPopMin =
+            decode(b"*4\r\n$8\r\nbzpopmin\r\n$1\r\nx\r\n$1\r\nz\r\n$1\r\n0\r\n")?.try_into()?;
+        let expected = RespArray::new([
+            BulkString::from("z").into(),
+            BulkString::from("a").into(),
+            RespFrame::Double(1.0),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: ZPopMin = decode(b"*2\r\n$7\r\nzpopmin\r\n$1\r\nz\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespArray::new([]).into());
+
+        let frame = decode(b"*3\r\n$8\r\nbzpopmax\r\n$1\r\nz\r\n$2\r\n-1\r\n")?;
+        assert!(BZPopMax::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_op_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.zadd("z1", vec![(1.0, "a".to_string()), (2.0, "b".to_string())])?;
+        backend.zadd("z2", vec![(3.0, "b".to_string())])?;
+
+        let cmd: ZUnion = decode(b"*9\r\n$6\r\nzunion\r\n$1\r\n2\r\n$2\r\nz1\r\n$2\r\nz2\r\n$7\r\nWEIGHTS\r\n$1\r\n2\r\n$1\r\n1\r\n$9\r\naggregate\r\n$3\r\nmin\r\n")?.try_into()?;
+        let expected = RespArray::new([BulkString::from("a").into(), BulkString::from("b").into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: ZInterStore = decode(
+            b"*5\r\n$11\r\nzinterstore\r\n$3\r\ndst\r\n$1\r\n2\r\n$2\r\nz1\r\n$2\r\nz2\r\n",
+        )?
+        .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        assert_eq!(backend.zscore("dst", "b"), Ok(Some(5.0)));
+
+        let cmd: ZDiff = decode(
+            b"*5\r\n$5\r\nzdiff\r\n$1\r\n2\r\n$2\r\nz1\r\n$2\r\nz2\r\n$10\r\nwithscores\r\n",
+        )?
+        .try_into()?;
+        let expected = RespArray::new([BulkString::from("a").into(), RespFrame::Double(1.0)]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        // ZDIFF takes no weights, and there must be a weight per key
+        let frame =
+            decode(b"*5\r\n$5\r\nzdiff\r\n$1\r\n1\r\n$2\r\nz1\r\n$7\r\nweights\r\n$1\r\n1\r\n")?;
+        assert!(ZDiff::try_from(frame).is_err());
+        let frame = decode(b"*6\r\n$6\r\nzinter\r\n$1\r\n2\r\n$2\r\nz1\r\n$2\r\nz2\r\n$7\r\nweights\r\n$1\r\n1\r\n")?;
+        assert!(ZInter::try_from(frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zremrange_commands() -> Result<()> {
+        let backend = Backend::new();
+        let members = ["a", "b", "c", "d"].iter().enumerate();
+        backend.zadd(
+            "z",
+            members.map(|(i, m)| (i as f64, m.to_string())).collect(),
+        )?;
+
+        let cmd: ZRemRangeByRank =
+            decode(b"*4\r\n$15\r\nzremrangebyrank\r\n$1\r\nz\r\n$1\r\n0\r\n$1\r\n0\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd: ZRemRangeByScore =
+            decode(b"*4\r\n$16\r\nzremrangebyscore\r\n$1\r\nz\r\n$2\r\n(1\r\n$1\r\n2\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd: ZRemRangeByLex =
+            decode(b"*4\r\n$14\r\nzremrangebylex\r\n$1\r\nz\r\n$1\r\n-\r\n$1\r\n+\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+        assert_eq!(backend.zcard("z"), Ok(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrandmember_zmscore_zscan_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.zadd("z", vec![(1.0, "a".to_string()), (2.0, "b".to_string())])?;
+
+        let cmd: ZMScore =
+            decode(b"*4\r\n$7\r\nzmscore\r\n$1\r\nz\r\n$1\r\nb\r\n$1\r\nx\r\n")?.try_into()?;
+        let expected = RespArray::new([RespFrame::Double(2.0), RespFrame::Null(RespNull)]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd: ZRandMember =
+            decode(b"*4\r\n$11\r\nzrandmember\r\n$1\r\nz\r\n$2\r\n-3\r\n$10\r\nWITHSCORES\r\n")?
+                .try_into()?;
+        let RespFrame::Array(reply) = cmd.execute(&backend) else {
+            panic!("expected an array");
+        };
+        assert_eq!(reply.len(), 6);
+
+        let cmd: ZRandMember = decode(b"*2\r\n$11\r\nzrandmember\r\n$1\r\nx\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+
+        let cmd: ZScan =
+            decode(b"*5\r\n$5\r\nzscan\r\n$1\r\nz\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$1\r\nb\r\n")?
+                .try_into()?;
+        let expected = RespArray::new([
+            BulkString::from("0").into(),
+            RespArray::new([BulkString::from("b").into(), RespFrame::Double(2.0)]).into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zadd_options_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.zadd("z", vec![(1.0, "a".to_string())])?;
+
+        let cmd: ZAdd = decode(b"*8\r\n$4\r\nzadd\r\n$1\r\nz\r\n$2\r\nGT\r\n$2\r\nch\r\n$1\r\n3\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n")?.try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd: ZAdd = decode(
+            b"*6\r\n$4\r\nzadd\r\n$1\r\nz\r\n$2\r\nnx\r\n$4\r\nincr\r\n$1\r\n1\r\n$1\r\na\r\n",
+        )?
+        .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(RespNull));
+        let cmd: ZAdd =
+            decode(b"*5\r\n$4\r\nzadd\r\n$1\r\nz\r\n$4\r\nINCR\r\n$1\r\n2\r\n$1\r\nb\r\n")?
+                .try_into()?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Double(3.0));
+
+        // illegal combinations
+        let frame = decode(
+            b"*6\r\n$4\r\nzadd\r\n$1\r\nz\r\n$2\r\ngt\r\n$2\r\nnx\r\n$1\r\n1\r\n$1\r\na\r\n",
+        )?;
+        assert!(ZAdd::try_from(frame).is_err());
+        let frame = decode(b"*7\r\n$4\r\nzadd\r\n$1\r\nz\r\n$4\r\nincr\r\n$1\r\n1\r\n$1\r\na\r\n$1\r\n2\r\n$1\r\nb\r\n")?;
+        assert!(ZAdd::try_from(frame).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use crate::client::{bulk, bulk_value, command, elements, field_values, integer, ok, push_args};
+use crate::cmd::Command;
+use crate::{Backend, RespArray, RespFrame, SimpleError};
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+
+/// Runs commands on a backend in the same process, for applications embedding it as a store
+/// without going through a connection and RESP.
+///
+/// Commands run as they do for a connection of the default user, except that blocking ones
+/// reply right away and those needing a connection, such as MULTI or SUBSCRIBE, reply an
+/// error. The typed methods turn error replies into errors, like those of `Client`.
+#[derive(Debug, Clone)]
+pub struct BackendHandle {
+    backend: Backend,
+}
+
+impl BackendHandle {
+    pub fn new(backend: Backend) -> Self {
+        Self { backend }
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    /// Run a command and return its reply. It isn't written to the AOF nor sent to the
+    /// replicas, which need the request it was parsed from: `request` runs one that is.
+    pub fn execute(&self, cmd: Command) -> RespFrame {
+        self.run(cmd, None)
+    }
+
+    /// Run a command given as its arguments, e.g. `["SET", "k", "v"]`, and return its reply,
+    /// an error reply too.
+    pub fn request<I, A>(&self, args: I) -> RespFrame
+    where
+        I: IntoIterator<Item = A>,
+        A: AsRef<[u8]>,
+    {
+        let request = command(args);
+        let propagated = self.backend.is_propagating().then(|| request.clone());
+        match self.backend.parse_command(request) {
+            Ok(cmd) => self.run(cmd, propagated),
+            Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        bulk(self.call(["GET", key])?)
+    }
+
+    pub fn set(&self, key: &str, value: impl AsRef<[u8]>) -> Result<()> {
+        ok(self.call([b"SET", key.as_bytes(), value.as_ref()])?)
+    }
+
+    pub fn del(&self, keys: &[&str]) -> Result<i64> {
+        integer(self.call(["DEL"].iter().chain(keys))?)
+    }
+
+    pub fn incr(&self, key: &str) -> Result<i64> {
+        integer(self.call(["INCR", key])?)
+    }
+
+    /// Set the TTL of key in seconds, returns whether the key exists.
+    pub fn expire(&self, key: &str, seconds: i64) -> Result<bool> {
+        let seconds = seconds.to_string();
+        Ok(integer(self.call(["EXPIRE", key, &seconds])?)? == 1)
+    }
+
+    /// The TTL of key in seconds, -1 if it has none and -2 if it doesn't exist.
+    pub fn ttl(&self, key: &str) -> Result<i64> {
+        integer(self.call(["TTL", key])?)
+    }
+
+    /// Set a field of a hash, returns how many fields were added.
+    pub fn hset(&self, key: &str, field: &str, value: impl AsRef<[u8]>) -> Result<i64> {
+        integer(self.call([b"HSET", key.as_bytes(), field.as_bytes(), value.as_ref()])?)
+    }
+
+    pub fn hget(&self, key: &str, field: &str) -> Result<Option<Bytes>> {
+        bulk(self.call(["HGET", key, field])?)
+    }
+
+    pub fn hgetall(&self, key: &str) -> Result<Vec<(String, Bytes)>> {
+        field_values(self.call(["HGETALL", key])?)
+    }
+
+    /// Push values to the head of a list, returns its length.
+    pub fn lpush<V: AsRef<[u8]>>(&self, key: &str, values: &[V]) -> Result<i64> {
+        integer(self.call(push_args("LPUSH", key, values))?)
+    }
+
+    /// Push values to the tail of a list, returns its length.
+    pub fn rpush<V: AsRef<[u8]>>(&self, key: &str, values: &[V]) -> Result<i64> {
+        integer(self.call(push_args("RPUSH", key, values))?)
+    }
+
+    pub fn lrange(&self, key: &str, start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let (start, stop) = (start.to_string(), stop.to_string());
+        let reply = self.call(["LRANGE", key, &start, &stop])?;
+        elements(reply)?.into_iter().map(bulk_value).collect()
+    }
+
+    /// Add members to a set, returns how many weren't members already.
+    pub fn sadd<M: AsRef<[u8]>>(&self, key: &str, members: &[M]) -> Result<i64> {
+        integer(self.call(push_args("SADD", key, members))?)
+    }
+
+    pub fn smembers(&self, key: &str) -> Result<Vec<Bytes>> {
+        let reply = self.call(["SMEMBERS", key])?;
+        elements(reply)?.into_iter().map(bulk_value).collect()
+    }
+
+    /// Publish a message to a channel, returns how many subscribers received it.
+    pub fn publish(&self, channel: &str, message: impl AsRef<[u8]>) -> Result<i64> {
+        integer(self.call([b"PUBLISH", channel.as_bytes(), message.as_ref()])?)
+    }
+
+    // run a command given as its arguments, an error reply is an error
+    fn call<I, A>(&self, args: I) -> Result<RespFrame>
+    where
+        I: IntoIterator<Item = A>,
+        A: AsRef<[u8]>,
+    {
+        match self.request(args) {
+            RespFrame::Error(e) => Err(anyhow!("{}", e.as_str())),
+            reply => Ok(reply),
+        }
+    }
+
+    fn run(&self, cmd: Command, request: Option<RespArray>) -> RespFrame {
+        let backend = &self.backend;
+        if backend.is_replica() && cmd.is_propagated() {
+            return SimpleError::new("READONLY You can't write against a read only replica.")
+                .into();
+        }
+        let reply = cmd.execute_unblocked(backend, request);
+        backend.flush_propagated();
+        reply
+    }
+}
+
+impl From<Backend> for BackendHandle {
+    fn from(backend: Backend) -> Self {
+        Self::new(backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{Get, Set};
+    use crate::{BulkString, SetCondition};
+
+    #[test]
+    fn test_handle_commands() -> Result<()> {
+        let handle = BackendHandle::new(Backend::new());
+
+        assert_eq!(handle.get("k")?, None);
+        handle.set("k", "v")?;
+        assert_eq!(handle.get("k")?, Some(Bytes::from("v")));
+        assert_eq!(handle.incr("n")?, 1);
+        assert!(handle.expire("n", 100)?);
+        assert!(handle.ttl("n")? > 0);
+        assert_eq!(handle.del(&["k", "n", "missing"])?, 2);
+
+        assert_eq!(handle.hset("h", "f", "1")?, 1);
+        assert_eq!(handle.hget("h", "f")?, Some(Bytes::from("1")));
+        assert_eq!(
+            handle.hgetall("h")?,
+            vec![("f".to_string(), Bytes::from("1"))]
+        );
+        assert_eq!(handle.rpush("l", &["a", "b"])?, 2);
+        assert_eq!(handle.lpush("l", &["c"])?, 3);
+        assert_eq!(handle.lrange("l", 0, -1)?, ["c", "a", "b"].map(Bytes::from));
+        assert_eq!(handle.sadd("s", &["x"])?, 1);
+        assert_eq!(handle.smembers("s")?, vec![Bytes::from("x")]);
+        assert_eq!(handle.publish("c", "m")?, 0);
+
+        let err = handle.incr("l").unwrap_err();
+        assert!(err.to_string().starts_with("WRONGTYPE"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle_execute() {
+        let handle = BackendHandle::from(Backend::new());
+        let set = Set {
+            key: "k".to_string(),
+            value: BulkString::from("v").into(),
+            condition: SetCondition::Always,
+            expire: None,
+            get: false,
+        };
+        assert_eq!(handle.execute(set.into()), RespFrame::from("OK"));
+        let get = Get {
+            key: "k".to_string(),
+        };
+        assert_eq!(handle.execute(get.into()), BulkString::from("v").into());
+        // the shared backend sees the write
+        assert!(handle.backend().contains_key("k"));
+
+        // blocking commands don't wait, those of a connection can't run
+        let reply = handle.request(["BLPOP", "l", "0"]);
+        assert!(matches!(
+            reply,
+            RespFrame::NullArray(_) | RespFrame::Null(_)
+        ));
+        assert!(matches!(handle.request(["MULTI"]), RespFrame::Error(_)));
+        assert!(matches!(handle.request(["NOPE"]), RespFrame::Error(_)));
+    }
+}
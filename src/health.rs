@@ -0,0 +1,97 @@
+use crate::{http, Backend};
+use anyhow::Result;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tracing::{info, warn};
+
+// how long a task spawned by /healthz may take to run before the runtime is deemed stuck
+const RESPONSIVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Serve the probes of an orchestrator such as Kubernetes over HTTP at addr, until an accept
+/// error occurs: `GET /healthz` answers 200 while the runtime runs its tasks, `GET /readyz`
+/// 200 while the backend `is_ready`, and both 503 otherwise.
+pub async fn serve(addr: impl ToSocketAddrs, backend: Backend) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("serving health checks on http://{}", listener.local_addr()?);
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            if let Err(e) = answer(stream, &backend).await {
+                warn!("health check from {} failed: {}", raddr, e);
+            }
+        });
+    }
+}
+
+// answer a single request, then close the connection
+async fn answer(mut stream: TcpStream, backend: &Backend) -> Result<()> {
+    let Some((method, path)) = http::read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let (status, body) = match (method.as_str(), path.as_str()) {
+        ("GET", "/healthz") => probe(is_responsive().await),
+        ("GET", "/readyz") => probe(backend.is_ready()),
+        ("GET", _) => ("404 Not Found", "not found\n"),
+        _ => ("405 Method Not Allowed", "method not allowed\n"),
+    };
+    http::respond(&mut stream, status, "text/plain", body).await
+}
+
+fn probe(ok: bool) -> (&'static str, &'static str) {
+    match ok {
+        true => ("200 OK", "ok\n"),
+        false => ("503 Service Unavailable", "unavailable\n"),
+    }
+}
+
+// whether a new task gets to run in time, which it doesn't when the workers are all stuck
+async fn is_responsive() -> bool {
+    let task = tokio::spawn(async {});
+    matches!(
+        tokio::time::timeout(RESPONSIVE_TIMEOUT, task).await,
+        Ok(Ok(()))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get(addr: std::net::SocketAddr, path: &str) -> Result<String> {
+        let mut stream = loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoints() -> Result<()> {
+        let backend = Backend::new();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        drop(listener);
+        tokio::spawn(serve(addr, backend.clone()));
+
+        assert!(get(addr, "/healthz")
+            .await?
+            .starts_with("HTTP/1.1 200 OK\r\n"));
+        // not ready until the server accepts connections
+        let response = get(addr, "/readyz").await?;
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        backend.set_accepting(true);
+        assert!(get(addr, "/readyz")
+            .await?
+            .starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(get(addr, "/nope").await?.starts_with("HTTP/1.1 404"));
+        Ok(())
+    }
+}
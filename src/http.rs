@@ -0,0 +1,41 @@
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// the longest request head read, in bytes
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// Read the head of a request, returns its method and path, None if the connection was
+/// closed, or the head got too long, before it was complete.
+pub(crate) async fn read_request(stream: &mut TcpStream) -> Result<Option<(String, String)>> {
+    let mut request = Vec::new();
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_LEN || stream.read_buf(&mut request).await? == 0 {
+            return Ok(None);
+        }
+    }
+    let line = String::from_utf8_lossy(&request);
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    Ok(Some((method, path)))
+}
+
+/// Write the response, then close the connection.
+pub(crate) async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
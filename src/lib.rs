@@ -1,7 +1,23 @@
 mod backend;
+pub mod client;
 pub mod cmd;
+mod handle;
+pub mod health;
+mod http;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod network;
 mod resp;
+mod server;
+pub mod systemd;
+#[cfg(feature = "tls")]
+pub mod tls;
 
 pub use backend::*;
+pub use handle::BackendHandle;
 pub use resp::*;
+pub use server::{Server, ServerBuilder};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
@@ -1,22 +1,423 @@
-use anyhow::Result;
-use tokio::net::TcpListener;
-use tracing::info;
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
-    let addr = "0.0.0.0:6379";
-    info!("Simple-Redis-Server is listening on {}", addr);
-    let listener = TcpListener::bind(addr).await?;
-
-    loop {
-        let (_socket, raddr) = listener.accept().await?;
-        tokio::spawn(async move {
-            // if let Err(e) = crate::connection::process(socket).await {
-            //  info!("connection error: {:?}", e)
-            // }
-            info!("connection from: {:?}", raddr);
+use anyhow::{Context, Result};
+use clap::Parser;
+use simple_redis::{
+    parse_config, Backend, Server, StorageEngine, DEFAULT_SHARDS, SENTINEL_DEFAULT_PORT,
+};
+use std::path::PathBuf;
+use tracing::{info, warn};
+use tracing_subscriber::filter::dynamic_filter_fn;
+#[cfg(feature = "otlp")]
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+/// A Redis-compatible server. Parameters are read from the config file, if any, then from the
+/// options, which take precedence.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// A config file in the format of redis.conf
+    config: Option<PathBuf>,
+    /// The port to listen on, none if it's 0, e.g. to serve TLS connections only
+    #[arg(long)]
+    port: Option<u16>,
+    /// The addresses to listen on, as "127.0.0.1 ::1", * for every IPv4 one and ::* for every
+    /// IPv6 one
+    #[arg(long)]
+    bind: Option<String>,
+    /// A memory limit, in bytes or with a unit as 100mb
+    #[arg(long)]
+    maxmemory: Option<String>,
+    /// The file to log to, stdout if empty
+    #[arg(long)]
+    logfile: Option<String>,
+    #[arg(long)]
+    loglevel: Option<String>,
+    #[arg(long)]
+    requirepass: Option<String>,
+    /// The file ACL SAVE and ACL LOAD keep the users in, loaded at startup
+    #[arg(long)]
+    aclfile: Option<String>,
+    /// no, systemd, or auto to notify systemd if it started the server
+    #[arg(long)]
+    supervised: Option<String>,
+    /// The port of the TLS listener, none if it's 0, with the tls feature
+    #[arg(long)]
+    tls_port: Option<u16>,
+    #[arg(long)]
+    tls_cert_file: Option<String>,
+    #[arg(long)]
+    tls_key_file: Option<String>,
+    /// The CA the certificates of the clients must be signed by
+    #[arg(long)]
+    tls_ca_cert_file: Option<String>,
+    /// yes, no, or optional for clients to present a certificate if they have one
+    #[arg(long)]
+    tls_auth_clients: Option<String>,
+    #[arg(long)]
+    timeout: Option<String>,
+    /// how many times per second expired keys are looked for, from 1 to 500
+    #[arg(long)]
+    hz: Option<String>,
+    /// from 1 to 10, how much CPU is spent to leave fewer expired keys in memory
+    #[arg(long)]
+    active_expire_effort: Option<String>,
+    /// The TTL in seconds of the keys created without one, 0 for none
+    #[arg(long)]
+    default_ttl: Option<String>,
+    /// The TTLs of the keys created with a prefix instead, as "session: 60 user: 0"
+    #[arg(long)]
+    default_ttl_prefixes: Option<String>,
+    /// How many threads run the commands apart from the connections' IO, 0 for none
+    #[arg(long)]
+    worker_threads: Option<String>,
+    #[arg(long)]
+    dir: Option<String>,
+    #[arg(long)]
+    dbfilename: Option<String>,
+    #[arg(long)]
+    save: Option<String>,
+    #[arg(long)]
+    appendonly: Option<String>,
+    #[arg(long)]
+    appendfilename: Option<String>,
+    #[arg(long)]
+    appendfsync: Option<String>,
+    /// yes to rewrite the AOF as a dump of the keyspace followed by the commands since
+    #[arg(long)]
+    aof_use_rdb_preamble: Option<String>,
+    #[arg(long)]
+    auto_aof_rewrite_percentage: Option<String>,
+    #[arg(long)]
+    auto_aof_rewrite_min_size: Option<String>,
+    #[arg(long)]
+    repl_backlog_size: Option<String>,
+    #[arg(long)]
+    notify_keyspace_events: Option<String>,
+    #[arg(long)]
+    list_max_listpack_size: Option<String>,
+    #[arg(long)]
+    set_max_intset_entries: Option<String>,
+    #[arg(long)]
+    hash_max_listpack_entries: Option<String>,
+    #[arg(long)]
+    hash_max_listpack_value: Option<String>,
+    /// yes to run in cluster mode, redirecting the clients to the node serving their keys
+    #[arg(long)]
+    cluster_enabled: Option<String>,
+    /// The slots served in cluster mode, as 0-5460,5462
+    #[arg(long)]
+    cluster_slots: Option<String>,
+    /// The other nodes with the slots they serve, as "ip:port=5461-10922 ip:port=10923-16383"
+    #[arg(long)]
+    cluster_peers: Option<String>,
+    /// The master to replicate, as "host port"
+    #[arg(long)]
+    replicaof: Option<String>,
+    #[arg(long)]
+    storage: Option<StorageEngine>,
+    /// Where to serve Prometheus metrics over HTTP, as host:port
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_addr: Option<String>,
+    /// Where to serve the /healthz and /readyz probes over HTTP, as host:port
+    #[arg(long)]
+    health_addr: Option<String>,
+    /// Where to export the spans over OTLP/HTTP, as http://host:4318/v1/traces
+    #[cfg(feature = "otlp")]
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+    /// A library to load custom commands from, repeated for every other one
+    #[cfg(feature = "modules")]
+    #[arg(long)]
+    loadmodule: Vec<String>,
+    /// Fix the inconsistencies of the keyspace loaded
+    #[arg(long)]
+    repair: bool,
+    /// Run as a sentinel, monitoring the masters of the sentinel monitor directives and
+    /// failing them over, on port 26379 unless another is given
+    #[arg(long)]
+    sentinel: bool,
+}
+
+impl Args {
+    // the parameters set by the options, as directives of the config file
+    fn overrides(&self) -> Vec<(String, String)> {
+        [
+            ("port", self.port.map(|port| port.to_string())),
+            ("bind", self.bind.clone()),
+            ("maxmemory", self.maxmemory.clone()),
+            ("logfile", self.logfile.clone()),
+            ("aclfile", self.aclfile.clone()),
+            ("supervised", self.supervised.clone()),
+            ("loglevel", self.loglevel.clone()),
+            ("requirepass", self.requirepass.clone()),
+            ("tls-port", self.tls_port.map(|port| port.to_string())),
+            ("tls-cert-file", self.tls_cert_file.clone()),
+            ("tls-key-file", self.tls_key_file.clone()),
+            ("tls-ca-cert-file", self.tls_ca_cert_file.clone()),
+            ("tls-auth-clients", self.tls_auth_clients.clone()),
+            ("timeout", self.timeout.clone()),
+            ("hz", self.hz.clone()),
+            ("active-expire-effort", self.active_expire_effort.clone()),
+            ("default-ttl", self.default_ttl.clone()),
+            ("default-ttl-prefixes", self.default_ttl_prefixes.clone()),
+            ("worker-threads", self.worker_threads.clone()),
+            ("dir", self.dir.clone()),
+            ("dbfilename", self.dbfilename.clone()),
+            ("save", self.save.clone()),
+            ("appendonly", self.appendonly.clone()),
+            ("appendfilename", self.appendfilename.clone()),
+            ("appendfsync", self.appendfsync.clone()),
+            ("aof-use-rdb-preamble", self.aof_use_rdb_preamble.clone()),
+            (
+                "auto-aof-rewrite-percentage",
+                self.auto_aof_rewrite_percentage.clone(),
+            ),
+            (
+                "auto-aof-rewrite-min-size",
+                self.auto_aof_rewrite_min_size.clone(),
+            ),
+            ("repl-backlog-size", self.repl_backlog_size.clone()),
+            (
+                "notify-keyspace-events",
+                self.notify_keyspace_events.clone(),
+            ),
+            (
+                "list-max-listpack-size",
+                self.list_max_listpack_size.clone(),
+            ),
+            (
+                "set-max-intset-entries",
+                self.set_max_intset_entries.clone(),
+            ),
+            (
+                "hash-max-listpack-entries",
+                self.hash_max_listpack_entries.clone(),
+            ),
+            (
+                "hash-max-listpack-value",
+                self.hash_max_listpack_value.clone(),
+            ),
+            ("cluster-enabled", self.cluster_enabled.clone()),
+            ("cluster-slots", self.cluster_slots.clone()),
+            ("cluster-peers", self.cluster_peers.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value?)))
+        .collect()
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut pairs = match &args.config {
+        Some(path) => {
+            let config = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            parse_config(&config)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("bad config file {}", path.display()))?
+        }
+        None => Vec::new(),
+    };
+    pairs.extend(args.overrides());
+    if args.sentinel && !pairs.iter().any(|(name, _)| name == "port") {
+        pairs.insert(0, ("port".to_string(), SENTINEL_DEFAULT_PORT.to_string()));
+    }
+
+    // colored, unless it goes to a file
+    let ansi = !pairs
+        .iter()
+        .any(|(name, logfile)| name == "logfile" && !logfile.is_empty());
+    let backend = Backend::with_engine(DEFAULT_SHARDS, args.storage.unwrap_or_default());
+    init_tracing(&backend, ansi, &args)?;
+    if args.sentinel {
+        backend.enable_sentinel();
+    }
+    backend
+        .config_load(pairs)
+        .map_err(anyhow::Error::msg)
+        .context("invalid configuration")?;
+    backend
+        .open_log()
+        .with_context(|| format!("failed to open {}", backend.logfile()))?;
+    if let Some(path) = &args.config {
+        backend.set_config_file(path);
+    }
+    if !backend.aclfile().is_empty() {
+        backend
+            .acl_load()
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("failed to load {}", backend.aclfile()))?;
+    }
+    // before the AOF loads, it may have their commands
+    #[cfg(feature = "modules")]
+    for path in &args.loadmodule {
+        let names = backend.load_module(path)?;
+        info!("loaded {} with the commands {}", path, names.join(", "));
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    // up while the keyspace loads, not ready until the server listens
+    if let Some(addr) = args.health_addr.clone() {
+        let backend = backend.clone();
+        runtime.spawn(async move {
+            if let Err(e) = simple_redis::health::serve(addr, backend).await {
+                warn!("serving health checks failed: {}", e);
+            }
         });
     }
+    // a sentinel has no keyspace
+    if !args.sentinel {
+        load_keyspace(&backend)?;
+        check_keyspace(&backend, args.repair);
+    }
+
+    #[cfg(unix)]
+    backend.reopen_log_on_sighup(runtime.handle())?;
+    if let Some(master) = &args.replicaof {
+        let (host, port) = master
+            .split_once(' ')
+            .context("--replicaof must be \"host port\"")?;
+        backend.replicaof(Some((host.to_string(), port.parse()?)), runtime.handle());
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = args.metrics_addr.clone() {
+        let backend = backend.clone();
+        runtime.spawn(async move {
+            if let Err(e) = simple_redis::metrics::serve(addr, backend).await {
+                warn!("serving metrics failed: {}", e);
+            }
+        });
+    }
+    let server = Server::builder().backend(backend).build()?;
+    runtime.block_on(server.run(shutdown_signal()))
+}
+
+// wait for SIGTERM, or ctrl-c
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                warn!("failed to listen for SIGTERM: {}", e);
+                return std::future::pending().await;
+            }
+        };
+        tokio::select! {
+            _ = terminate.recv() => info!("received SIGTERM, shutting down"),
+            _ = tokio::signal::ctrl_c() => info!("received SIGINT, shutting down"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => info!("received ctrl-c, shutting down"),
+            Err(e) => {
+                warn!("failed to listen for ctrl-c: {}", e);
+                std::future::pending().await
+            }
+        }
+    }
+}
+
+// log to logfile, stdout if it's empty, and export the spans to an OpenTelemetry collector if
+// one is given
+fn init_tracing(
+    backend: &Backend,
+    ansi: bool,
+    #[cfg_attr(not(feature = "otlp"), allow(unused_variables))] args: &Args,
+) -> Result<()> {
+    // the level and the file are the backend's, set at runtime
+    let logged = backend.clone();
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(backend.log_writer())
+        .with_ansi(ansi)
+        .with_filter(dynamic_filter_fn(move |metadata, _| {
+            logged.is_logged(metadata)
+        }));
+    let registry = tracing_subscriber::registry().with(fmt);
+    #[cfg(feature = "otlp")]
+    if let Some(endpoint) = &args.otlp_endpoint {
+        registry
+            .with(otlp_layer(endpoint)?.with_filter(LevelFilter::INFO))
+            .init();
+        return Ok(());
+    }
+    registry.init();
+    Ok(())
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(endpoint: &str) -> Result<impl tracing_subscriber::Layer<S>>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name("simple-redis")
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer("simple-redis");
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+// from the AOF, which has every write, or the dump, which only has those up to the last save
+fn load_keyspace(backend: &Backend) -> Result<()> {
+    if backend.appendonly() {
+        let replayed = backend
+            .load_aof()
+            .with_context(|| format!("failed to load {}", backend.aof_path().display()))?;
+        if replayed > 0 {
+            info!(
+                "replayed {} commands from {}",
+                replayed,
+                backend.aof_path().display()
+            );
+        }
+    } else {
+        let loaded = backend
+            .load_dump()
+            .with_context(|| format!("failed to load {}", backend.dump_path().display()))?;
+        if loaded > 0 {
+            info!(
+                "loaded {} keys from {}",
+                loaded,
+                backend.dump_path().display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_keyspace(backend: &Backend, repair: bool) {
+    let found = if repair {
+        backend.repair()
+    } else {
+        backend.check()
+    };
+
+    for inconsistency in found.iter() {
+        warn!("keyspace inconsistency: {}", inconsistency);
+    }
+    if repair {
+        info!("keyspace repair fixed {} inconsistencies", found.len());
+    } else if !found.is_empty() {
+        warn!("restart with --repair to fix the inconsistencies above");
+    }
 }
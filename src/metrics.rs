@@ -0,0 +1,182 @@
+use crate::{http, Backend, COMMAND_DURATION_BUCKETS};
+use anyhow::Result;
+use std::fmt::Write;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tracing::{info, warn};
+
+/// Serve the metrics of backend in the Prometheus text format on `GET /metrics` at addr,
+/// until an accept error occurs.
+pub async fn serve(addr: impl ToSocketAddrs, backend: Backend) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            if let Err(e) = answer(stream, &backend).await {
+                warn!("metrics request from {} failed: {}", raddr, e);
+            }
+        });
+    }
+}
+
+// answer a single request, then close the connection
+async fn answer(mut stream: TcpStream, backend: &Backend) -> Result<()> {
+    let Some((method, path)) = http::read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let (status, body) = match (method.as_str(), path.as_str()) {
+        ("GET", "/metrics") => ("200 OK", render(backend)),
+        ("GET", _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    http::respond(&mut stream, status, "text/plain; version=0.0.4", &body).await
+}
+
+/// The metrics of backend in the Prometheus text format.
+pub fn render(backend: &Backend) -> String {
+    let stats = backend.stats_report();
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP simple_redis_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE simple_redis_{} {}", name, kind);
+        let _ = writeln!(out, "simple_redis_{} {}", name, value);
+    };
+    metric(
+        "connected_clients",
+        "gauge",
+        "Clients connected.",
+        backend.connected_clients() as u64,
+    );
+    metric(
+        "connections_received_total",
+        "counter",
+        "Connections accepted.",
+        stats.total_connections_received,
+    );
+    metric(
+        "commands_processed_total",
+        "counter",
+        "Commands processed.",
+        stats.total_commands_processed,
+    );
+    metric(
+        "keyspace_keys",
+        "gauge",
+        "Keys in the keyspace.",
+        backend.dbsize() as u64,
+    );
+    metric(
+        "keyspace_hits_total",
+        "counter",
+        "Key reads that found the key.",
+        stats.keyspace_hits,
+    );
+    metric(
+        "keyspace_misses_total",
+        "counter",
+        "Key reads that didn't find the key.",
+        stats.keyspace_misses,
+    );
+    metric(
+        "memory_used_bytes",
+        "gauge",
+        "Estimated memory used by the keyspace.",
+        backend.memory_stats().total_bytes() as u64,
+    );
+    metric(
+        "expired_keys_total",
+        "counter",
+        "Keys deleted for being expired.",
+        stats.expired_keys,
+    );
+    metric(
+        "evicted_keys_total",
+        "counter",
+        "Keys evicted for maxmemory.",
+        stats.evicted_keys,
+    );
+
+    let command_stats = backend.command_stats();
+    out.push_str("# HELP simple_redis_commands_total Calls of each command.\n");
+    out.push_str("# TYPE simple_redis_commands_total counter\n");
+    for (name, stats) in &command_stats {
+        let _ = writeln!(
+            out,
+            "simple_redis_commands_total{{cmd=\"{}\"}} {}",
+            name, stats.calls
+        );
+    }
+    out.push_str(
+        "# HELP simple_redis_command_duration_seconds How long the calls of each command took.\n",
+    );
+    out.push_str("# TYPE simple_redis_command_duration_seconds histogram\n");
+    for (name, stats) in &command_stats {
+        let mut cumulative = 0;
+        for (le, calls) in COMMAND_DURATION_BUCKETS.iter().zip(stats.buckets) {
+            cumulative += calls;
+            let _ = writeln!(
+                out,
+                "simple_redis_command_duration_seconds_bucket{{cmd=\"{}\",le=\"{}\"}} {}",
+                name,
+                *le as f64 / 1e6,
+                cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "simple_redis_command_duration_seconds_bucket{{cmd=\"{}\",le=\"+Inf\"}} {}",
+            name, stats.calls
+        );
+        let _ = writeln!(
+            out,
+            "simple_redis_command_duration_seconds_sum{{cmd=\"{}\"}} {}",
+            name,
+            stats.usec as f64 / 1e6
+        );
+        let _ = writeln!(
+            out,
+            "simple_redis_command_duration_seconds_count{{cmd=\"{}\"}} {}",
+            name, stats.calls
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("k".to_string(), RespFrame::Integer(1));
+        backend.get("k")?;
+        backend.get("nope")?;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        drop(listener);
+        tokio::spawn(serve(addr, backend.clone()));
+
+        let mut stream = loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\nsimple_redis_keyspace_keys 1\n"));
+        assert!(response.contains("\nsimple_redis_keyspace_hits_total 1\n"));
+        assert!(response.contains("\nsimple_redis_keyspace_misses_total 1\n"));
+        Ok(())
+    }
+}
@@ -1,13 +1,577 @@
-use crate::RespFrame;
+use crate::cmd::{command_keys, resolve_command, slot_keys, Command, Transaction};
+use crate::{
+    Backend, BackendError, BulkString, RespArray, RespDecode, RespEncode, RespError, RespFrame,
+    SimpleError, SimpleString, Stat, Subscriptions,
+};
 use anyhow::Result;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+use tracing::{debug, field, info, info_span, warn, Instrument};
 
-#[allow(dead_code)]
-async fn stream_handler(_stream: TcpStream) -> Result<()> {
-    todo!()
+// the connections accepted by the OS but not yet by the server, tcp-backlog's default
+const LISTEN_BACKLOG: i32 = 511;
+// connections speak RESP2 until they switch with HELLO
+const DEFAULT_PROTOCOL: u8 = 2;
+// bulk strings at least this long are written from the bytes they're stored in, rather than
+// copied to the write buffer first
+const ZERO_COPY_MIN_LEN: usize = 16 * 1024;
+// aggregates with at least this many elements are encoded and written element by element
+const STREAMED_MIN_LEN: usize = 1024;
+// the most bytes of encoded replies buffered before they're written to the socket
+const OUTPUT_BUFFER_LIMIT: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub(crate) struct RespFrameCodec {
+    protocol: u8,
+    // whether the frames decoded are requests, which may be sent inline, rather than replies
+    requests: bool,
+}
+
+impl RespFrameCodec {
+    /// The codec of the client end of a connection: requests are encoded as they are and
+    /// replies of any type decoded.
+    pub(crate) fn client() -> Self {
+        Self {
+            protocol: 3,
+            requests: false,
+        }
+    }
+}
+
+// the header of an aggregate reply whose elements are sent one by one after it
+#[derive(Debug)]
+enum AggregateHeader {
+    Array(usize),
+    Set(usize),
+    Map(usize),
+}
+
+#[derive(Debug)]
+struct RedisRequest {
+    frame: RespFrame,
+    backend: Backend,
+    protocol: u8,
+}
+
+#[derive(Debug)]
+struct RedisResponse {
+    frames: Vec<RespFrame>,
+    protocol: u8,
+    // PSYNC or SYNC, the connection is now a replica's
+    sync: Option<Command>,
+    // QUIT, the connection is closed once the frames are sent
+    quit: bool,
+}
+
+/// Bind a listener on addr. IPv6 listeners only accept IPv6 connections, so the same port may
+/// be listened on for IPv4 with another.
+pub fn listen(addr: SocketAddr) -> std::io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// Close a connection refused by the limits of its address, with the error as the reply to
+/// whatever it sends first, if its socket takes it without waiting.
+pub fn reject(stream: TcpStream, raddr: SocketAddr, e: BackendError) {
+    warn!("Rejected connection from {}: {}", raddr, e);
+    // written to the socket directly, tokio doesn't know yet whether it's writable
+    if let Ok(mut stream) = stream.into_std() {
+        let _ = std::io::Write::write(&mut stream, &RespFrame::from(e).encode());
+    }
+}
+
+pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+    let addr = stream.peer_addr()?;
+    connection_handler(stream, addr, None, backend).await
+}
+
+/// Serve the connection of a client at addr over stream, e.g. one established over TLS. The
+/// connection is authenticated as the user named by the common name of its client's
+/// certificate, if there's one and the user exists.
+pub async fn connection_handler<S>(
+    stream: S,
+    addr: SocketAddr,
+    common_name: Option<String>,
+    backend: Backend,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let ip = addr.ip().to_string();
+    let codec = RespFrameCodec {
+        protocol: DEFAULT_PROTOCOL,
+        requests: true,
+    };
+    let mut framed = Framed::new(stream, codec);
+    framed.set_backpressure_boundary(OUTPUT_BUFFER_LIMIT);
+    // published messages and invalidations are queued here and sent between replies
+    let (mut subscriptions, mut messages) = backend.subscriptions();
+    let id = subscriptions.id();
+    let mut invalidations = backend.connect_client(id);
+    let mut transaction = Transaction::default();
+    // as the default user, unless it needs a password
+    backend.acl_connect(id);
+    if let Some(common_name) = &common_name {
+        backend.auth_client_certificate(id, common_name);
+    }
+    backend.count(Stat::Connection);
+    // closed by the cron once idle for longer than the timeout
+    let idle = backend.idle_timer(id);
+    // and by the cron too once the clients use more memory than maxmemory-clients
+    let client = backend.register_client(id, addr);
+    let result = async {
+        loop {
+            tokio::select! {
+                frame = framed.next() => match frame {
+                    Some(Ok(frame)) => {
+                        debug!("Received frame: {:?}", frame);
+                        let request = RedisRequest {
+                            frame,
+                            backend: backend.clone(),
+                            protocol: framed.codec().protocol,
+                        };
+                        idle.busy();
+                        let response =
+                            request_handler(request, &mut subscriptions, &mut transaction)
+                                .await?;
+                        // a subscriber waits for messages however long it takes
+                        idle.idle(subscriptions.is_subscribed());
+                        if let Some(sync) = response.sync {
+                            SinkExt::<RespFrame>::flush(&mut framed).await?;
+                            return serve_replica(&mut framed, &backend, id, &ip, sync).await;
+                        }
+                        debug!("Sending response: {:?}", response.frames);
+                        // HELLO is answered in the protocol it switches to
+                        framed.codec_mut().protocol = response.protocol;
+                        for frame in response.frames {
+                            write_reply(&mut framed, frame).await?;
+                        }
+                        // the replies to pipelined requests are written together
+                        if framed.read_buffer().is_empty() || response.quit {
+                            SinkExt::<RespFrame>::flush(&mut framed).await?;
+                        }
+                        if response.quit {
+                            return Ok(());
+                        }
+                        client.buffers(
+                            framed.read_buffer().len(),
+                            framed.read_buffer().capacity(),
+                            framed.write_buffer().capacity(),
+                        );
+                    }
+                    Some(Err(e)) => return Err(e),
+                    None => return Ok(()),
+                },
+                Some(message) = messages.recv() => framed.send(RespFrame::from(message)).await?,
+                Some(invalidation) = invalidations.recv() => {
+                    // RESP2 has no pushes, only redirected invalidations get there
+                    if framed.codec().protocol >= 3 || invalidation.redirected {
+                        framed.send(RespFrame::from(invalidation)).await?;
+                    }
+                }
+                _ = idle.timed_out() => return Ok(()),
+                _ = client.evicted() => return Ok(()),
+            }
+        }
+    }
+    .instrument(info_span!("connection", client_id = id, peer = %ip))
+    .await;
+    transaction.reset(&backend);
+    backend.disconnect_client(id);
+    backend.acl_disconnect(id);
+    backend.cluster_disconnect(id);
+    backend.remove_replica(id);
+    result
+}
+
+// feed a reply to be written. The elements of a large aggregate are encoded one by one rather
+// than all together, so the encoded reply is written while it's encoded, no more than
+// OUTPUT_BUFFER_LIMIT bytes of it buffered at once.
+async fn write_reply<S>(framed: &mut Framed<S, RespFrameCodec>, frame: RespFrame) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match frame {
+        RespFrame::Array(array) if array.len() >= STREAMED_MIN_LEN => {
+            framed.feed(AggregateHeader::Array(array.len())).await?;
+            for frame in array.0 {
+                write_element(framed, frame).await?;
+            }
+        }
+        RespFrame::Set(set) if set.len() >= STREAMED_MIN_LEN => {
+            framed.feed(AggregateHeader::Set(set.len())).await?;
+            for frame in set.0 {
+                write_element(framed, frame).await?;
+            }
+        }
+        RespFrame::Map(map) if map.len() >= STREAMED_MIN_LEN => {
+            framed.feed(AggregateHeader::Map(map.len())).await?;
+            for (key, value) in map.0 {
+                // the keys of a map are simple strings, bulk strings in RESP2 where it's an
+                // array
+                let key: RespFrame = match framed.codec().protocol {
+                    protocol if protocol < 3 => BulkString::new(key).into(),
+                    _ => SimpleString::new(key).into(),
+                };
+                framed.feed(key).await?;
+                write_element(framed, value).await?;
+            }
+        }
+        frame => write_element(framed, frame).await?,
+    }
+    Ok(())
 }
 
-#[allow(dead_code)]
-async fn request_handler() -> Result<RespFrame> {
-    todo!()
+// feed a reply, or an element of one, to be written, a long bulk string written right away
+async fn write_element<S>(framed: &mut Framed<S, RespFrameCodec>, frame: RespFrame) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match frame {
+        RespFrame::BulkString(s) if s.len() >= ZERO_COPY_MIN_LEN => {
+            // after what was fed before it
+            SinkExt::<RespFrame>::flush(framed).await?;
+            write_bulk_string(framed.get_mut(), s).await
+        }
+        frame => framed.feed(frame).await,
+    }
+}
+
+// write s to the stream with a vectored write of its header, the bytes it's stored in and
+// the trailing CRLF
+async fn write_bulk_string<S>(stream: &mut S, s: BulkString) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let header = Bytes::from(format!("${}\r\n", s.len()));
+    let mut buf = header.chain(s.0).chain(&b"\r\n"[..]);
+    stream.write_all_buf(&mut buf).await?;
+    Ok(())
+}
+
+// send the keyspace then the stream of writes to the replica on the connection, or only the
+// stream from where it left it, its acknowledgements being the only thing read from then on
+async fn serve_replica<S>(
+    framed: &mut Framed<S, RespFrameCodec>,
+    backend: &Backend,
+    id: u64,
+    ip: &str,
+    sync: Command,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let psync = match &sync {
+        Command::PSync(psync) => Some((psync.replid.as_str(), psync.offset)),
+        _ => None,
+    };
+    let mut replica_sync = backend.add_replica(id, ip.to_string(), psync);
+    match replica_sync.rdb.take() {
+        Some(rdb) => {
+            info!(
+                "replica {}:{} asked for a synchronization, sending the keyspace at offset {}",
+                ip, id, replica_sync.offset
+            );
+            if let Command::PSync(_) = sync {
+                let reply = format!("FULLRESYNC {} {}", replica_sync.replid, replica_sync.offset);
+                framed
+                    .send(RespFrame::from(SimpleString::new(reply)))
+                    .await?;
+            }
+            // a bulk string without the trailing CRLF
+            let mut data = format!("${}\r\n", rdb.len()).into_bytes();
+            data.extend(rdb);
+            // what's sent from now on is already encoded
+            framed.get_mut().write_all(&data).await?;
+        }
+        None => {
+            info!(
+                "replica {}:{} continues the stream of writes where it left it",
+                ip, id
+            );
+            let reply = format!("CONTINUE {}", replica_sync.replid);
+            framed
+                .send(RespFrame::from(SimpleString::new(reply)))
+                .await?;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            data = replica_sync.stream.recv() => match data {
+                Some(data) => framed.get_mut().write_all(&data).await?,
+                // dropped for being too slow
+                None => return Ok(()),
+            },
+            frame = framed.next() => match frame {
+                Some(Ok(RespFrame::Array(array))) => {
+                    if let Ok(Command::ReplConf(replconf)) = Command::try_from(array) {
+                        replconf.apply(backend, id);
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+async fn request_handler(
+    request: RedisRequest,
+    subscriptions: &mut Subscriptions,
+    transaction: &mut Transaction,
+) -> Result<RedisResponse> {
+    let (frame, backend) = (request.frame, request.backend);
+    let mut protocol = request.protocol;
+    let mut quit = false;
+    let frame = match frame {
+        RespFrame::Array(array) => match resolve_command(&backend, array) {
+            Ok(array) => RespFrame::Array(array),
+            Err(e) => {
+                transaction.fail();
+                return Ok(RedisResponse {
+                    frames: vec![SimpleError::new(format!("ERR {}", e)).into()],
+                    protocol,
+                    sync: None,
+                    quit,
+                });
+            }
+        },
+        frame => frame,
+    };
+    let frames = match frame {
+        RespFrame::Array(array) => {
+            let name = command_name(&array);
+            backend.count(Stat::Command);
+            // kept to be propagated as is, queued ones in case a replica shows up before EXEC
+            let request =
+                (backend.is_propagating() || transaction.is_active()).then(|| array.clone());
+            let keys = command_keys(&backend, &name, &array);
+            // the first key to trace, none for a command without keys, whose arguments may be
+            // credentials
+            let key = keys.first().map(String::as_str).unwrap_or_default();
+            let slot_keys = match backend.is_cluster_enabled() {
+                true => slot_keys(&backend, &name, &array),
+                false => Vec::new(),
+            };
+            let command = backend.parse_command(array);
+            // the connection must authenticate first, then only run what its user is allowed
+            let denied = match &command {
+                Ok(cmd) if !cmd.is_allowed_unauthenticated() => backend
+                    .acl_check(
+                        subscriptions.id(),
+                        &name,
+                        |category| cmd.in_acl_category(category),
+                        &keys,
+                    )
+                    .err(),
+                _ => None,
+            };
+            // a script holds every other command back, those stopping it aside
+            let busy = match &command {
+                Ok(cmd) if !cmd.is_allowed_busy() => backend.wait_script_busy().await,
+                _ => false,
+            };
+            // in cluster mode, the keys must be in a slot this node serves
+            let redirect = match &command {
+                Ok(_) => backend.cluster_check(subscriptions.id(), &slot_keys).err(),
+                Err(_) => None,
+            };
+            // what the embedding application replies instead of running the command
+            let hooked = match (&command, &denied, &redirect) {
+                (Ok(cmd), None, None) => backend.before_command(subscriptions.id(), cmd),
+                _ => None,
+            };
+            match command {
+                Ok(_) if denied.is_some() => {
+                    transaction.fail();
+                    denied.into_iter().map(RespFrame::from).collect()
+                }
+                Ok(_) if redirect.is_some() => {
+                    transaction.fail();
+                    redirect.into_iter().map(RespFrame::from).collect()
+                }
+                // RESP3 tells pushes from replies, so it can run anything while subscribed
+                Ok(cmd)
+                    if protocol < 3
+                        && subscriptions.is_subscribed()
+                        && !cmd.is_allowed_subscribed() =>
+                {
+                    vec![SimpleError::new(format!(
+                        "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                        name
+                    ))
+                    .into()]
+                }
+                // once it runs for too long, they're replied so
+                Ok(_) if busy => {
+                    transaction.fail();
+                    vec![BackendError::Busy.into()]
+                }
+                Ok(_) if hooked.is_some() => hooked.into_iter().collect(),
+                Ok(cmd) if backend.is_replica() && cmd.is_propagated() => {
+                    transaction.fail();
+                    vec![
+                        SimpleError::new("READONLY You can't write against a read only replica.")
+                            .into(),
+                    ]
+                }
+                // the stream of a replica is its master's, replicas replicate the master
+                Ok(Command::PSync(_) | Command::Sync(_)) if backend.is_replica() => {
+                    vec![SimpleError::new("ERR a replica can't have replicas").into()]
+                }
+                Ok(cmd @ (Command::PSync(_) | Command::Sync(_))) if !transaction.is_active() => {
+                    return Ok(RedisResponse {
+                        frames: vec![],
+                        protocol,
+                        sync: Some(cmd),
+                        quit,
+                    });
+                }
+                Ok(mut cmd) => {
+                    // queued, HELLO fails the transaction instead
+                    match (&mut cmd, transaction.is_active()) {
+                        (Command::Hello(hello), false) => {
+                            // wrong credentials fail HELLO as a whole
+                            let authenticates =
+                                hello.auth.as_ref().is_none_or(|(username, password)| {
+                                    backend.authenticate(Some(username), password).is_ok()
+                                });
+                            if authenticates {
+                                protocol = *hello.protover.get_or_insert(protocol);
+                            }
+                        }
+                        (Command::Reset(_), _) => protocol = DEFAULT_PROTOCOL,
+                        (Command::Quit(_), _) | (Command::Shutdown(_), false) => quit = true,
+                        _ => {}
+                    }
+                    let id = subscriptions.id();
+                    let span = info_span!(
+                        "command",
+                        name = %name,
+                        key = %key,
+                        client_id = id,
+                        duration_us = field::Empty
+                    );
+                    let hooked = backend.has_after_command_hooks().then(|| cmd.clone());
+                    let started = Instant::now();
+                    // on the worker of its first key, the commands on a key run in order
+                    let execute = backend.track(
+                        id,
+                        cmd.execute_on_connection(&backend, request, subscriptions, transaction),
+                    );
+                    let frames = backend
+                        .route(keys.first().map(String::as_str), id, execute)
+                        .instrument(span.clone())
+                        .await;
+                    let elapsed = started.elapsed();
+                    span.record("duration_us", elapsed.as_micros() as u64);
+                    backend.count_command(&name, elapsed);
+                    if let Some(cmd) = hooked {
+                        backend.after_command(&cmd, &frames, elapsed);
+                    }
+                    frames
+                }
+                Err(e) => {
+                    transaction.fail();
+                    vec![SimpleError::new(format!("ERR {}", e)).into()]
+                }
+            }
+        }
+        _ => vec![SimpleError::new("ERR Protocol error: expected a command array").into()],
+    };
+    Ok(RedisResponse {
+        frames,
+        protocol,
+        sync: None,
+        quit,
+    })
+}
+
+fn command_name(array: &RespArray) -> String {
+    match array.first() {
+        Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name).to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+impl Encoder<RespFrame> for RespFrameCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: RespFrame, dst: &mut BytesMut) -> Result<()> {
+        let item = if self.protocol < 3 {
+            item.into_resp2()
+        } else {
+            item
+        };
+        match item {
+            // straight from the bytes it's stored in
+            RespFrame::BulkString(s) => {
+                dst.reserve(s.len() + 16);
+                dst.extend_from_slice(format!("${}\r\n", s.len()).as_bytes());
+                dst.extend_from_slice(&s);
+                dst.extend_from_slice(b"\r\n");
+            }
+            item => match item.shared_encoding() {
+                Some(encoded) => dst.extend_from_slice(encoded),
+                None => dst.extend_from_slice(&item.encode()),
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Encoder<AggregateHeader> for RespFrameCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: AggregateHeader, dst: &mut BytesMut) -> Result<()> {
+        // RESP2 has only arrays, with the keys and values of a map one after the other
+        let header = match (item, self.protocol) {
+            (AggregateHeader::Array(len), _) => format!("*{}\r\n", len),
+            (AggregateHeader::Set(len), protocol) if protocol < 3 => format!("*{}\r\n", len),
+            (AggregateHeader::Map(len), protocol) if protocol < 3 => format!("*{}\r\n", len * 2),
+            (AggregateHeader::Set(len), _) => format!("~{}\r\n", len),
+            (AggregateHeader::Map(len), _) => format!("%{}\r\n", len),
+        };
+        dst.extend_from_slice(header.as_bytes());
+        Ok(())
+    }
+}
+
+impl Decoder for RespFrameCodec {
+    type Item = RespFrame;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<RespFrame>> {
+        loop {
+            // anything else than an array is a command sent inline
+            let frame = match src.first() {
+                None => return Ok(None),
+                Some(b'*') => RespFrame::decode(src),
+                Some(_) if self.requests => RespArray::decode_inline(src).map(RespFrame::from),
+                Some(_) => RespFrame::decode(src),
+            };
+            match frame {
+                // empty lines are skipped
+                Ok(RespFrame::Array(array)) if self.requests && array.is_empty() => continue,
+                Ok(frame) => return Ok(Some(frame)),
+                Err(RespError::NotComplete) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 }
@@ -1,8 +1,8 @@
 use crate::{
     BulkString, RespArray, RespDecode, RespError, RespFrame, RespMap, RespNull, RespNullArray,
-    RespNullBulkString, RespSet, SimpleError, SimpleString,
+    RespNullBulkString, RespPush, RespSet, SimpleError, SimpleString,
 };
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
@@ -22,6 +22,7 @@ const CRLF_LEN: usize = CRLF.len();
  - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
  - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
  - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
+ - push: "><number-of-elements>\r\n<element-1>...<element-n>"
 */
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
@@ -77,6 +78,10 @@ impl RespDecode for RespFrame {
                 let frame = RespSet::decode(buf)?;
                 Ok(frame.into())
             }
+            Some(b'>') => {
+                let frame = RespPush::decode(buf)?;
+                Ok(frame.into())
+            }
             _ => Err(RespError::InvalidFrameType(format!(
                 "expect length: unknown frame type: {:?}",
                 buf
@@ -90,13 +95,17 @@ impl RespDecode for RespFrame {
             Some(b'+') => SimpleString::expect_length(buf),
             Some(b'-') => SimpleError::expect_length(buf),
             Some(b':') => i64::expect_length(buf),
+            // the null elements of an aggregate, e.g. the channel of an UNSUBSCRIBE reply
+            Some(b'$') if buf.starts_with(b"$-1\r\n") => Ok(5),
             Some(b'$') => BulkString::expect_length(buf),
+            Some(b'*') if buf.starts_with(b"*-1\r\n") => Ok(5),
             Some(b'*') => RespArray::expect_length(buf),
             Some(b'_') => RespNull::expect_length(buf),
             Some(b'#') => bool::expect_length(buf),
             Some(b',') => f64::expect_length(buf),
             Some(b'%') => RespMap::expect_length(buf),
             Some(b'~') => RespSet::expect_length(buf),
+            Some(b'>') => RespPush::expect_length(buf),
             _ => Err(RespError::NotComplete),
         }
     }
@@ -178,7 +187,7 @@ impl RespDecode for BulkString {
         buf.advance(end + CRLF_LEN);
 
         let data = buf.split_to(len + CRLF_LEN);
-        Ok(BulkString::new(data[..len].to_vec()))
+        Ok(BulkString(Bytes::copy_from_slice(&data[..len])))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -338,6 +347,128 @@ impl RespDecode for RespSet {
     }
 }
 
+impl RespDecode for RespPush {
+    const PREFIX: &'static str = ">";
+
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+
+        if buf.len() < total_len {
+            return Err(RespError::NotComplete);
+        }
+
+        buf.advance(end + CRLF_LEN);
+
+        let mut frames = Vec::with_capacity(len);
+        for _ in 0..len {
+            let frame = RespFrame::decode(buf)?;
+            frames.push(frame);
+        }
+
+        Ok(RespPush::new(frames))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        calc_total_length(buf, end, len, Self::PREFIX)
+    }
+}
+
+// like Redis, a line of an inline command without its end is at most 64KB
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+impl RespArray {
+    /// Decode a command sent inline, as a line of space separated arguments the way
+    /// redis-cli or telnet send them, quoted arguments included. An empty line is an empty
+    /// array.
+    pub fn decode_inline(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let end = match buf.iter().position(|&b| b == b'\n') {
+            Some(end) => end,
+            None if buf.len() > MAX_INLINE_LEN => {
+                return Err(RespError::InvalidFrame(
+                    "too big inline request".to_string(),
+                ))
+            }
+            None => return Err(RespError::NotComplete),
+        };
+        let line = buf.split_to(end + 1);
+        let args = split_inline_args(&line[..end])
+            .ok_or_else(|| RespError::InvalidFrame("unbalanced quotes in request".to_string()))?;
+        Ok(RespArray::new(
+            args.into_iter()
+                .map(|arg| BulkString::new(arg).into())
+                .collect::<Vec<_>>(),
+        ))
+    }
+}
+
+// split line into arguments as Redis does, where double quoted ones take escapes like \n
+// or \x41 and single quoted ones \'; None if a quote isn't closed, or is followed by
+// something else than a space
+fn split_inline_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let is_space = |b: u8| matches!(b, b' ' | b'\n' | b'\r' | b'\t' | b'\0');
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && is_space(line[i]) {
+            i += 1;
+        }
+        if i == line.len() {
+            return Some(args);
+        }
+        let mut arg = Vec::new();
+        let mut quote = None;
+        loop {
+            match (quote, line.get(i).copied()) {
+                (Some(_), None) => return None,
+                (None, None) => break,
+                (None, Some(b)) if is_space(b) => break,
+                (None, Some(b @ (b'"' | b'\''))) => quote = Some(b),
+                (None, Some(b)) => arg.push(b),
+                (Some(q), Some(b)) if b == q => {
+                    // the closing quote ends the argument
+                    if line.get(i + 1).is_some_and(|&b| !is_space(b)) {
+                        return None;
+                    }
+                    i += 1;
+                    break;
+                }
+                (Some(b'"'), Some(b'\\')) if i + 3 < line.len() && line[i + 1] == b'x' => {
+                    match std::str::from_utf8(&line[i + 2..i + 4])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    {
+                        Some(b) => {
+                            arg.push(b);
+                            i += 3;
+                        }
+                        None => arg.push(b'\\'),
+                    }
+                }
+                (Some(b'"'), Some(b'\\')) if i + 1 < line.len() => {
+                    i += 1;
+                    arg.push(match line[i] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        b => b,
+                    });
+                }
+                (Some(b'\''), Some(b'\\')) if line.get(i + 1) == Some(&b'\'') => {
+                    i += 1;
+                    arg.push(b'\'');
+                }
+                (Some(_), Some(b)) => arg.push(b),
+            }
+            i += 1;
+        }
+        args.push(arg);
+    }
+}
+
 fn extract_fixed_data(
     buf: &mut BytesMut,
     except: &str,
@@ -362,10 +493,11 @@ fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
     match prefix {
-        "*" | "~" => {
+        "*" | "~" | ">" => {
             for _ in 0..len {
                 let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
+                // an element cut short
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
                 total += len;
             }
             Ok(total)
@@ -374,11 +506,11 @@ fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result
             for _ in 0..len {
                 let len = SimpleString::expect_length(data)?;
 
-                data = &data[len..];
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
                 total += len;
 
                 let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
+                data = data.get(len..).ok_or(RespError::NotComplete)?;
                 total += len;
             }
             Ok(total)
@@ -427,8 +559,8 @@ fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
 mod tests {
     use crate::resp::RespDecode;
     use crate::{
-        BulkString, RespArray, RespError, RespMap, RespNull, RespNullArray, RespNullBulkString,
-        RespSet, SimpleError, SimpleString,
+        BulkString, RespArray, RespError, RespFrame, RespMap, RespNull, RespNullArray,
+        RespNullBulkString, RespPush, RespSet, SimpleError, SimpleString,
     };
     use anyhow::Result;
     use bytes::{BufMut, BytesMut};
@@ -526,10 +658,21 @@ mod tests {
         let ret = RespArray::decode(&mut buf);
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
-        buf.extend_from_slice(b"$5\r\nhello\r\n");
+        buf.extend_from_slice(b"$5\r\nhel");
+        let ret = RespArray::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        buf.extend_from_slice(b"lo\r\n");
         let frame = RespArray::decode(&mut buf)?;
         assert_eq!(frame, RespArray::new([b"set".into(), b"hello".into()]));
 
+        buf.extend_from_slice(b"*3\r\n$-1\r\n*-1\r\n:0\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespArray::new([RespNullBulkString.into(), RespNullArray.into(), 0.into()])
+        );
+
         Ok(())
     }
 
@@ -589,4 +732,50 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_push_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b">2\r\n$7\r\nmessage\r\n:1\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespPush::new([BulkString::new("message").into(), RespFrame::Integer(1)]).into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_decode() -> Result<()> {
+        let mut buf =
+            BytesMut::from(&b"PING\r\nset  k \"a b\\x41\\n\"\r\nget 'it\\'s'\n\r\nset k"[..]);
+        let array = |args: &[&[u8]]| {
+            RespArray::new(
+                args.iter()
+                    .map(|arg| BulkString::new(arg.to_vec()).into())
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        assert_eq!(RespArray::decode_inline(&mut buf)?, array(&[b"PING"]));
+        assert_eq!(
+            RespArray::decode_inline(&mut buf)?,
+            array(&[b"set", b"k", b"a bA\n"])
+        );
+        assert_eq!(
+            RespArray::decode_inline(&mut buf)?,
+            array(&[b"get", b"it's"])
+        );
+        assert_eq!(RespArray::decode_inline(&mut buf)?, array(&[]));
+        // the line isn't over yet
+        let ret = RespArray::decode_inline(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+        assert_eq!(&buf[..], b"set k");
+
+        let mut buf = BytesMut::from(&b"get \"k\r\n"[..]);
+        let ret = RespArray::decode_inline(&mut buf);
+        assert!(matches!(ret, Err(RespError::InvalidFrame(_))));
+        Ok(())
+    }
 }
@@ -0,0 +1,126 @@
+use crate::RespFrame;
+use std::fmt;
+
+// Frames are displayed as redis-cli prints replies: strings quoted and escaped, the type of
+// the other scalars in parentheses, and the elements of aggregates numbered one per line,
+// nested ones indented under their number.
+impl fmt::Display for RespFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render(self))
+    }
+}
+
+fn render(frame: &RespFrame) -> String {
+    match frame {
+        RespFrame::SimpleString(s) => s.to_string(),
+        RespFrame::Error(e) => format!("(error) {}", e.as_str()),
+        RespFrame::Integer(n) => format!("(integer) {}", n),
+        RespFrame::BulkString(s) => quoted(s),
+        RespFrame::NullBulkString(_) | RespFrame::NullArray(_) | RespFrame::Null(_) => {
+            "(nil)".to_string()
+        }
+        RespFrame::Boolean(b) => format!("({})", b),
+        RespFrame::Double(d) => format!("(double) {}", d),
+        RespFrame::Array(array) => elements(array.iter(), ")", "(empty array)"),
+        RespFrame::Push(push) => elements(push.iter(), ")", "(empty array)"),
+        RespFrame::Set(set) => elements(set.iter(), "~", "(empty set)"),
+        RespFrame::Map(map) => {
+            let entries = map
+                .iter()
+                .map(|(key, value)| pair(&quoted(key.as_bytes()), &render(value)));
+            numbered(entries.collect(), "#", "(empty hash)")
+        }
+    }
+}
+
+fn elements<'a>(frames: impl Iterator<Item = &'a RespFrame>, mark: &str, empty: &str) -> String {
+    numbered(frames.map(render).collect(), mark, empty)
+}
+
+// the key on the first line of a map entry, its value after it and indented under it
+fn pair(key: &str, value: &str) -> String {
+    let prefix = format!("{} => ", key);
+    indent(&prefix, value)
+}
+
+fn numbered(rendered: Vec<String>, mark: &str, empty: &str) -> String {
+    if rendered.is_empty() {
+        return empty.to_string();
+    }
+    let width = rendered.len().to_string().len();
+    rendered
+        .iter()
+        .enumerate()
+        .map(|(i, element)| indent(&format!("{:>width$}{} ", i + 1, mark), element))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// the first line after the prefix, the others aligned with it
+fn indent(prefix: &str, rendered: &str) -> String {
+    let padding = " ".repeat(prefix.len());
+    let mut lines = rendered.lines();
+    let mut out = format!("{}{}", prefix, lines.next().unwrap_or_default());
+    for line in lines {
+        out.push('\n');
+        out.push_str(&padding);
+        out.push_str(line);
+    }
+    out
+}
+
+// double quoted, with the quotes, backslashes and unprintable bytes escaped
+fn quoted(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('"');
+    for &b in bytes {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BulkString, RespArray, RespFrame, RespMap, RespNull, SimpleError};
+
+    #[test]
+    fn test_scalar_display() {
+        assert_eq!(RespFrame::from("OK").to_string(), "OK");
+        let frame: RespFrame = SimpleError::new("ERR nope").into();
+        assert_eq!(frame.to_string(), "(error) ERR nope");
+        assert_eq!(RespFrame::Integer(-3).to_string(), "(integer) -3");
+        let frame: RespFrame = BulkString::from("a\"b\n\x01").into();
+        assert_eq!(frame.to_string(), r#""a\"b\n\x01""#);
+        assert_eq!(RespFrame::from(RespNull).to_string(), "(nil)");
+    }
+
+    #[test]
+    fn test_aggregate_display() {
+        let elements = (1..=10).map(|n| n.into()).collect::<Vec<_>>();
+        let nested = RespArray::new([
+            BulkString::from("a").into(),
+            RespArray::new([b"b".into(), b"c".into()]).into(),
+            RespArray::new(elements).into(),
+        ]);
+        let frame: RespFrame = nested.into();
+        let expected = "1) \"a\"\n2) 1) \"b\"\n   2) \"c\"\n3)  1) (integer) 1\n";
+        assert!(frame.to_string().starts_with(expected));
+        assert!(frame.to_string().ends_with("\n   10) (integer) 10"));
+
+        let mut map = RespMap::new();
+        map.insert("k".to_string(), RespArray::new([b"v".into()]).into());
+        let frame: RespFrame = map.into();
+        assert_eq!(frame.to_string(), "1# \"k\" => 1) \"v\"");
+        let frame: RespFrame = RespArray::new([]).into();
+        assert_eq!(frame.to_string(), "(empty array)");
+    }
+}
@@ -1,8 +1,42 @@
 use crate::{
-    BulkString, RespArray, RespEncode, RespMap, RespNull, RespNullArray, RespNullBulkString,
-    RespSet, SimpleError, SimpleString,
+    BulkString, RespArray, RespEncode, RespFrame, RespMap, RespNull, RespNullArray,
+    RespNullBulkString, RespPush, RespSet, SimpleError, SimpleString,
 };
 
+impl RespFrame {
+    /// Downgrade the RESP3 only frames for a RESP2 connection: maps become flat arrays of
+    /// keys and values, sets and pushes arrays, nulls null bulk strings, booleans integers and
+    /// doubles bulk strings.
+    pub fn into_resp2(self) -> RespFrame {
+        match self {
+            RespFrame::Array(array) => resp2_array(array.0),
+            RespFrame::Set(set) => resp2_array(set.0),
+            RespFrame::Push(push) => resp2_array(push.0),
+            RespFrame::Map(map) => RespArray::new(
+                map.0
+                    .into_iter()
+                    .flat_map(|(key, value)| [BulkString::new(key).into(), value.into_resp2()])
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Null(_) => RespNullBulkString.into(),
+            RespFrame::Boolean(b) => RespFrame::Integer(b as i64),
+            RespFrame::Double(d) => BulkString::new(d.to_string()).into(),
+            frame => frame,
+        }
+    }
+}
+
+fn resp2_array(frames: Vec<RespFrame>) -> RespFrame {
+    RespArray::new(
+        frames
+            .into_iter()
+            .map(RespFrame::into_resp2)
+            .collect::<Vec<_>>(),
+    )
+    .into()
+}
+
 impl RespEncode for SimpleString {
     fn encode(self) -> Vec<u8> {
         format!("+{}\r\n", self.0).into_bytes()
@@ -117,10 +151,23 @@ impl RespEncode for RespSet {
     }
 }
 
+// push: "><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespEncode for RespPush {
+    fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(4096);
+        let len_prefix = format!(">{}\r\n", self.len()).into_bytes();
+        buf.extend_from_slice(&len_prefix);
+
+        for frame in self.0 {
+            buf.extend_from_slice(&frame.encode());
+        }
+        buf
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::RespFrame;
 
     #[test]
     fn test_simple_string_encode() {
@@ -228,4 +275,30 @@ mod tests {
         .into();
         assert_eq!(frame.encode(), b"~2\r\n+foo\r\n$6\r\nfoobar\r\n");
     }
+
+    #[test]
+    fn test_push_encode() {
+        let frame: RespFrame = RespPush::new(vec![
+            BulkString::new("message".to_string()).into(),
+            BulkString::new("ch".to_string()).into(),
+        ])
+        .into();
+        assert_eq!(frame.encode(), b">2\r\n$7\r\nmessage\r\n$2\r\nch\r\n");
+    }
+
+    #[test]
+    fn test_into_resp2() {
+        let mut map = RespMap::new();
+        map.insert("k".to_string(), RespFrame::Double(1.5));
+        let frame: RespFrame = RespArray::new(vec![
+            map.into(),
+            RespSet::new(vec![RespFrame::Null(RespNull)]).into(),
+            RespFrame::Boolean(true),
+        ])
+        .into();
+        assert_eq!(
+            frame.into_resp2().encode(),
+            b"*3\r\n*2\r\n$1\r\nk\r\n$3\r\n1.5\r\n*1\r\n$-1\r\n:+1\r\n"
+        );
+    }
 }
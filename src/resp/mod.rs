@@ -1,11 +1,13 @@
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use enum_dispatch::enum_dispatch;
 use std::collections::BTreeMap;
 use std::ops::{Deref, DerefMut};
 use thiserror::Error;
 
 mod decode;
+mod display;
 mod encode;
+pub mod shared;
 
 // Resp is RESP (Redis Serialization Protocol)
 
@@ -55,6 +57,7 @@ pub enum RespFrame {
     Double(f64),
     Map(RespMap),
     Set(RespSet),
+    Push(RespPush),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
@@ -62,7 +65,7 @@ pub struct SimpleString(String);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct SimpleError(String);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct BulkString(pub(crate) Vec<u8>);
+pub struct BulkString(pub(crate) Bytes);
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct RespNullBulkString;
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -72,9 +75,12 @@ pub struct RespNullArray;
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct RespNull;
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct RespMap(BTreeMap<String, RespFrame>);
+pub struct RespMap(pub(crate) BTreeMap<String, RespFrame>);
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct RespSet(Vec<RespFrame>);
+pub struct RespSet(pub(crate) Vec<RespFrame>);
+/// Out-of-band data the server sends a RESP3 connection, such as pub/sub messages.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RespPush(pub(crate) Vec<RespFrame>);
 
 impl Deref for SimpleString {
     type Target = String;
@@ -93,7 +99,7 @@ impl Deref for SimpleError {
 }
 
 impl Deref for BulkString {
-    type Target = Vec<u8>;
+    type Target = Bytes;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -130,6 +136,14 @@ impl Deref for RespSet {
     }
 }
 
+impl Deref for RespPush {
+    type Target = Vec<RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl SimpleString {
     pub fn new(s: impl Into<String>) -> Self {
         SimpleString(s.into())
@@ -144,7 +158,7 @@ impl SimpleError {
 
 impl BulkString {
     pub fn new(s: impl Into<Vec<u8>>) -> Self {
-        BulkString(s.into())
+        BulkString(Bytes::from(s.into()))
     }
 }
 
@@ -172,6 +186,12 @@ impl RespSet {
     }
 }
 
+impl RespPush {
+    pub fn new(frames: impl Into<Vec<RespFrame>>) -> Self {
+        RespPush(frames.into())
+    }
+}
+
 impl From<&str> for RespFrame {
     fn from(s: &str) -> Self {
         SimpleString(s.to_string()).into()
@@ -186,31 +206,31 @@ impl From<&str> for SimpleString {
 
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(s.as_bytes().to_vec())
+        BulkString(Bytes::copy_from_slice(s.as_bytes()))
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString(Bytes::copy_from_slice(s)).into()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for RespFrame {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString(Bytes::copy_from_slice(s)).into()
     }
 }
 
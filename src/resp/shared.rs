@@ -0,0 +1,76 @@
+use crate::RespFrame;
+
+// The encodings of the most common replies, written as they are rather than encoded again
+// for every request.
+pub const OK: &[u8] = b"+OK\r\n";
+pub const QUEUED: &[u8] = b"+QUEUED\r\n";
+pub const PONG: &[u8] = b"+PONG\r\n";
+pub const ZERO: &[u8] = b":+0\r\n";
+pub const ONE: &[u8] = b":+1\r\n";
+pub const MINUS_ONE: &[u8] = b":-1\r\n";
+pub const MINUS_TWO: &[u8] = b":-2\r\n";
+pub const NULL_BULK_STRING: &[u8] = b"$-1\r\n";
+pub const NULL: &[u8] = b"_\r\n";
+pub const EMPTY_ARRAY: &[u8] = b"*0\r\n";
+pub const WRONGTYPE: &[u8] =
+    b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+
+impl RespFrame {
+    /// The encoding of the frame if it's one of the common replies, which is shared instead
+    /// of being encoded.
+    pub fn shared_encoding(&self) -> Option<&'static [u8]> {
+        let encoded = match self {
+            RespFrame::SimpleString(s) => match s.as_str() {
+                "OK" => OK,
+                "QUEUED" => QUEUED,
+                "PONG" => PONG,
+                _ => return None,
+            },
+            RespFrame::Integer(0) => ZERO,
+            RespFrame::Integer(1) => ONE,
+            RespFrame::Integer(-1) => MINUS_ONE,
+            RespFrame::Integer(-2) => MINUS_TWO,
+            RespFrame::NullBulkString(_) => NULL_BULK_STRING,
+            RespFrame::Null(_) => NULL,
+            RespFrame::Array(array) if array.is_empty() => EMPTY_ARRAY,
+            RespFrame::Error(e) if e.as_bytes() == &WRONGTYPE[1..WRONGTYPE.len() - 2] => WRONGTYPE,
+            _ => return None,
+        };
+        Some(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RespArray, RespEncode, RespNull, RespNullBulkString, SimpleError, SimpleString};
+
+    #[test]
+    fn test_shared_encoding() {
+        let frames: Vec<RespFrame> = vec![
+            SimpleString::new("OK").into(),
+            SimpleString::new("QUEUED").into(),
+            SimpleString::new("PONG").into(),
+            RespFrame::Integer(0),
+            RespFrame::Integer(1),
+            RespFrame::Integer(-1),
+            RespFrame::Integer(-2),
+            RespNullBulkString.into(),
+            RespFrame::Null(RespNull),
+            RespArray::new([]).into(),
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into(),
+        ];
+        // the same bytes as they're encoded
+        for frame in frames {
+            let shared = frame.shared_encoding();
+            assert_eq!(shared, Some(&frame.encode()[..]));
+        }
+
+        assert_eq!(RespFrame::Integer(2).shared_encoding(), None);
+        let frame: RespFrame = SimpleString::new("FOO").into();
+        assert_eq!(frame.shared_encoding(), None);
+        let frame: RespFrame = SimpleError::new("ERR no").into();
+        assert_eq!(frame.shared_encoding(), None);
+    }
+}
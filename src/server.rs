@@ -0,0 +1,1105 @@
+use crate::cmd::CommandSpec;
+#[cfg(feature = "tls")]
+use crate::tls::TlsListener;
+use crate::{network, systemd, Backend, CLUSTER_PORT_INCR};
+use anyhow::{bail, Context, Result};
+use futures::future;
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use tokio::net::TcpListener;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// A simple-redis server serving commands from a backend, built with `Server::builder()`.
+///
+/// The server doesn't own a runtime: `run` serves on whatever runtime polls it, `run_on`
+/// spawns it on a caller-provided one, so it can be embedded next to other services.
+#[derive(Debug)]
+pub struct Server {
+    // one accept loop each, none if only TLS connections are served
+    listeners: Vec<std::net::TcpListener>,
+    // the cluster bus, in cluster mode
+    bus_listener: Option<std::net::TcpListener>,
+    backend: Backend,
+}
+
+/// Configures the addresses and the backend of a `Server`.
+#[derive(Debug, Default)]
+pub struct ServerBuilder {
+    addrs: Vec<String>,
+    cluster_addr: Option<String>,
+    backend: Option<Backend>,
+    commands: Vec<CommandSpec>,
+}
+
+impl ServerBuilder {
+    /// An address to listen on, e.g. 127.0.0.1:0 for a port chosen by the OS or [::1]:6379,
+    /// called again for every other one. By default the bind addresses of the backend on its
+    /// port, none if it's 0.
+    pub fn bind(mut self, addr: impl Into<String>) -> Self {
+        self.addrs.push(addr.into());
+        self
+    }
+
+    /// The address of the cluster bus, in cluster mode. By default the first IP listened on,
+    /// with the port offset by `CLUSTER_PORT_INCR`.
+    pub fn cluster_bind(mut self, addr: impl Into<String>) -> Self {
+        self.cluster_addr = Some(addr.into());
+        self
+    }
+
+    /// The backend serving the commands, a new one by default.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// A custom command, registered with the backend on `build`, called again for every other
+    /// one.
+    pub fn command(mut self, spec: CommandSpec) -> Self {
+        self.commands.push(spec);
+        self
+    }
+
+    /// Bind the listeners, so the addresses are taken, and known with `local_addrs`, before
+    /// the server runs. Fails if a custom command can't be registered, or if there's nothing
+    /// to listen on, neither an address nor a tls-port.
+    pub fn build(self) -> Result<Server> {
+        let backend = self.backend.unwrap_or_default();
+        for spec in self.commands {
+            let name = spec.name().to_string();
+            backend
+                .register_command(spec)
+                .with_context(|| format!("failed to register {}", name))?;
+        }
+        let addrs = match (self.addrs.is_empty(), backend.port()) {
+            (false, _) => self
+                .addrs
+                .iter()
+                .map(|addr| resolve(addr))
+                .collect::<Result<Vec<_>>>()?,
+            (true, 0) => Vec::new(),
+            (true, port) => backend
+                .bind()
+                .0
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, port))
+                .collect(),
+        };
+        let listeners = addrs
+            .iter()
+            .map(|&addr| {
+                network::listen(addr).with_context(|| format!("failed to listen on {}", addr))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if listeners.is_empty() && !(cfg!(feature = "tls") && backend.tls_port() != 0) {
+            bail!("configured to not listen anywhere, with port 0 and no tls-port");
+        }
+        let bus_listener = match backend.is_cluster_enabled() {
+            true => Some(bind_cluster_bus(self.cluster_addr, listeners.first())?),
+            false => None,
+        };
+        Ok(Server {
+            listeners,
+            bus_listener,
+            backend,
+        })
+    }
+}
+
+// the first address addr resolves to, e.g. localhost:6379
+fn resolve(addr: &str) -> Result<SocketAddr> {
+    addr.to_socket_addrs()
+        .with_context(|| format!("failed to resolve {}", addr))?
+        .next()
+        .with_context(|| format!("no address for {}", addr))
+}
+
+fn bind_cluster_bus(
+    addr: Option<String>,
+    listener: Option<&std::net::TcpListener>,
+) -> Result<std::net::TcpListener> {
+    let addr = match addr {
+        Some(addr) => addr,
+        None => {
+            let local_addr = listener
+                .context("cluster mode needs a port, the cluster bus port is derived from it")?
+                .local_addr()?;
+            let port = local_addr
+                .port()
+                .checked_add(CLUSTER_PORT_INCR)
+                .context("the port is too high for a cluster bus port")?;
+            SocketAddr::new(local_addr.ip(), port).to_string()
+        }
+    };
+    let listener = std::net::TcpListener::bind(&addr)
+        .with_context(|| format!("failed to listen on {} for the cluster bus", addr))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    /// The address the server listens on, the first one if there are several.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        let listener = self
+            .listeners
+            .first()
+            .context("the server doesn't listen on TCP, its port is 0")?;
+        Ok(listener.local_addr()?)
+    }
+
+    /// Every address the server listens on, in the order they were bound.
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>> {
+        let addrs = self
+            .listeners
+            .iter()
+            .map(|listener| listener.local_addr())
+            .collect::<io::Result<_>>()?;
+        Ok(addrs)
+    }
+
+    /// The address of the cluster bus, in cluster mode.
+    pub fn cluster_addr(&self) -> Result<Option<SocketAddr>> {
+        match &self.bus_listener {
+            Some(listener) => Ok(Some(listener.local_addr()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Start the background tasks of the backend (AOF, cron, cluster bus, sentinel), bind the TLS
+    /// listeners if there's a tls-port, and accept connections on every address until
+    /// shutdown completes, SHUTDOWN is called or an accept error occurs. On shutdown, the
+    /// keyspace is saved if snapshots are configured, or as SHUTDOWN asks. Connection tasks
+    /// are spawned on the runtime the returned future is polled on.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let handle = Handle::current();
+        if self.backend.appendonly() {
+            self.backend
+                .start_aof(self.backend.appendfsync(), &handle)
+                .with_context(|| format!("failed to open {}", self.backend.aof_path().display()))?;
+        }
+        self.backend.start_cron(&handle);
+        self.backend.start_workers(&handle);
+
+        let listeners = self
+            .listeners
+            .into_iter()
+            .map(TcpListener::from_std)
+            .collect::<io::Result<Vec<_>>>()?;
+        for listener in &listeners {
+            info!(
+                "Simple-Redis-Server is listening on {}",
+                listener.local_addr()?
+            );
+        }
+        // the port announced to the master and the cluster, the first one's
+        let port = match listeners.first() {
+            Some(listener) => listener.local_addr()?.port(),
+            None => 0,
+        };
+        self.backend.set_listening_port(port);
+        if self.backend.is_sentinel() {
+            self.backend.start_sentinel(&handle);
+        }
+        if let Some(bus_listener) = self.bus_listener {
+            let bus_listener = TcpListener::from_std(bus_listener)?;
+            let bus_addr = bus_listener.local_addr()?;
+            info!("Cluster bus is listening on {}", bus_addr);
+            self.backend.set_cluster_ports(port, bus_addr.port());
+            self.backend.start_cluster_bus(bus_listener, &handle);
+        }
+        #[cfg(feature = "tls")]
+        let tls = match self.backend.tls_port() {
+            0 => None,
+            _ => Some(TlsListener::bind(&self.backend).await?),
+        };
+        #[cfg(not(feature = "tls"))]
+        if self.backend.tls_port() != 0 {
+            warn!("tls-port is ignored, the server was built without the tls feature");
+        }
+        // what's to be loaded was, the server is ready once it listens
+        self.backend.set_accepting(true);
+        if self.backend.supervised().is_systemd() {
+            if let Err(e) = systemd::notify("READY=1\nSTATUS=Ready to accept connections") {
+                warn!("failed to notify systemd: {}", e);
+            }
+        }
+
+        // the listener supervisor: one accept loop per endpoint, the first to fail stops the
+        // server
+        let accept_loops = listeners
+            .into_iter()
+            .map(|listener| accept(listener, self.backend.clone()));
+        let accepted = async { future::try_join_all(accept_loops).await.map(|_| ()) };
+        #[cfg(feature = "tls")]
+        let accepted = async {
+            match tls {
+                Some(tls) => tokio::try_join!(accepted, tls.run(self.backend.clone())).map(|_| ()),
+                None => accepted.await,
+            }
+        };
+        tokio::select! {
+            result = accepted => result,
+            _ = shutdown => {
+                stop(&self.backend, None);
+                Ok(())
+            }
+            save = self.backend.shutdown_requested() => {
+                stop(&self.backend, save);
+                Ok(())
+            }
+        }
+    }
+
+    /// Spawn the server on an existing runtime, e.g. one shared with other services.
+    pub fn run_on(
+        self,
+        handle: &Handle,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> JoinHandle<Result<()>> {
+        handle.spawn(self.run(shutdown))
+    }
+}
+
+async fn accept(listener: TcpListener, backend: Backend) -> Result<()> {
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        let permit = match backend.admit_connection(raddr.ip()) {
+            Ok(permit) => permit,
+            Err(e) => {
+                network::reject(stream, raddr, e);
+                continue;
+            }
+        };
+        info!("Accepted connection from: {}", raddr);
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            // counted as open from the address until it closes, or it's denied
+            let result = tokio::select! {
+                result = network::stream_handler(stream, backend) => result,
+                _ = permit.denied() => Ok(()),
+            };
+            match result {
+                Ok(_) => info!("Connection from {} exited", raddr),
+                Err(e) => warn!("Handle error for {}: {:?}", raddr, e),
+            }
+        });
+    }
+}
+
+// no longer ready, tell systemd the server is stopping, then save the keyspace if save, or
+// if it's None and snapshots are configured
+fn stop(backend: &Backend, save: Option<bool>) {
+    backend.set_accepting(false);
+    if backend.supervised().is_systemd() {
+        if let Err(e) = systemd::notify("STOPPING=1") {
+            warn!("failed to notify systemd: {}", e);
+        }
+    }
+    if save.unwrap_or(!backend.save_rules().0.is_empty()) {
+        match backend.save() {
+            Ok(()) => info!("DB saved on disk"),
+            Err(e) => warn!("failed to save the DB on shutdown: {}", e),
+        }
+    }
+    info!("Simple-Redis-Server is now ready to exit, bye bye...");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{Command, CommandError, CommandExecutor, CommandSpec};
+    use crate::{BulkString, RespArray, RespFrame, SimpleError, SimpleString};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+
+    // a server on a port the OS picks, listening before it returns, so it can be connected to
+    // right away
+    fn spawn_server(builder: ServerBuilder) -> Result<(SocketAddr, JoinHandle<Result<()>>)> {
+        let server = builder.bind("127.0.0.1:0").build()?;
+        let addr = server.local_addr()?;
+        Ok((addr, tokio::spawn(server.run(std::future::pending()))))
+    }
+
+    #[test]
+    fn test_run_on_caller_runtime() -> Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        // on a port chosen by the OS, saving to dir on shutdown
+        let dir = std::env::temp_dir().join(format!("simple-redis-server-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let backend = Backend::new();
+        backend.set_dir(&dir);
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .backend(backend.clone())
+            .build()?;
+        let addr = server.local_addr()?;
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let handle = server.run_on(runtime.handle(), async {
+            let _ = stopped.await;
+        });
+
+        runtime.block_on(async {
+            // accepted once it runs, the listener is already bound
+            let mut stream = TcpStream::connect(addr).await?;
+
+            stream
+                .write_all(b"*3\r\n$3\r\nSET\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
+                .await?;
+            let mut buf = [0u8; 64];
+            let n = stream.read(&mut buf).await?;
+            assert_eq!(&buf[..n], b"+OK\r\n");
+
+            stream
+                .write_all(b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n")
+                .await?;
+            let n = stream.read(&mut buf).await?;
+            assert_eq!(&buf[..n], b"$5\r\nworld\r\n");
+
+            stream.write_all(b"*1\r\n$7\r\nunknown\r\n").await?;
+            let n = stream.read(&mut buf).await?;
+            assert!(buf[..n].starts_with(b"-ERR"));
+
+            // sets are arrays until the connection switches to RESP3
+            stream
+                .write_all(b"*3\r\n$4\r\nsadd\r\n$1\r\ns\r\n$1\r\na\r\n")
+                .await?;
+            let n = stream.read(&mut buf).await?;
+            assert_eq!(&buf[..n], b":+1\r\n");
+            let smembers = b"*2\r\n$8\r\nsmembers\r\n$1\r\ns\r\n";
+            stream.write_all(smembers).await?;
+            let n = stream.read(&mut buf).await?;
+            assert_eq!(&buf[..n], b"*1\r\n$1\r\na\r\n");
+
+            let mut hello = [0u8; 256];
+            stream
+                .write_all(b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n")
+                .await?;
+            let n = stream.read(&mut hello).await?;
+            assert!(hello[..n].starts_with(b"%6\r\n"));
+            stream.write_all(smembers).await?;
+            let n = stream.read(&mut buf).await?;
+            assert_eq!(&buf[..n], b"~1\r\n$1\r\na\r\n");
+
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        assert!(backend.get("hello")?.is_some());
+        let _ = stop.send(());
+        runtime.block_on(handle)??;
+        assert!(dir.join("dump.rdb").exists());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_connection() -> Result<()> {
+        let (addr, handle) = spawn_server(Server::builder())?;
+        let mut subscriber = TcpStream::connect(addr).await?;
+        let mut publisher = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 128];
+
+        subscriber
+            .write_all(b"*2\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n")
+            .await?;
+        let n = subscriber.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"*3\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n:+1\r\n");
+
+        publisher
+            .write_all(b"*3\r\n$7\r\npublish\r\n$2\r\nch\r\n$2\r\nhi\r\n")
+            .await?;
+        let n = publisher.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b":+1\r\n");
+        let n = subscriber.read(&mut buf).await?;
+        assert_eq!(
+            &buf[..n],
+            b"*3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n"
+        );
+
+        // only pub/sub commands until it unsubscribes
+        subscriber
+            .write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n")
+            .await?;
+        let n = subscriber.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"-ERR Can't execute 'get'"));
+
+        // RESP3 gets messages as pushes, and can run any command
+        let mut resp3 = TcpStream::connect(addr).await?;
+        let mut hello = [0u8; 256];
+        resp3.write_all(b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n").await?;
+        let n = resp3.read(&mut hello).await?;
+        assert!(hello[..n].starts_with(b"%"));
+        resp3
+            .write_all(b"*2\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n")
+            .await?;
+        let n = resp3.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b">3\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n:+1\r\n");
+        resp3.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = resp3.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"_\r\n");
+        publisher
+            .write_all(b"*3\r\n$7\r\npublish\r\n$2\r\nch\r\n$2\r\nhi\r\n")
+            .await?;
+        let n = publisher.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b":+2\r\n");
+        let n = resp3.read(&mut buf).await?;
+        assert_eq!(
+            &buf[..n],
+            b">3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n"
+        );
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline_pipeline() -> Result<()> {
+        let (addr, handle) = spawn_server(Server::builder())?;
+        let mut client = TcpStream::connect(addr).await?;
+
+        // what redis-benchmark sends first, then its inline and pipelined requests
+        client
+            .write_all(b"*3\r\n$6\r\nconfig\r\n$3\r\nget\r\n$10\r\nappendonly\r\n")
+            .await?;
+        let mut buf = [0u8; 256];
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"*2\r\n$10\r\nappendonly\r\n$2\r\nno\r\n");
+        client
+            .write_all(b"PING\r\nPING\r\n\r\nselect 0\r\nSET key:1 \"a b\"\r\nGET key:1\r\n")
+            .await?;
+        let expected = b"+PONG\r\n+PONG\r\n+OK\r\n+OK\r\n$3\r\na b\r\n";
+        let mut replies = Vec::new();
+        while replies.len() < expected.len() {
+            let n = client.read(&mut buf).await?;
+            assert!(n > 0);
+            replies.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(replies, expected);
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_large_bulk_string_reply() -> Result<()> {
+        let backend = Backend::new();
+        let value = (0..100_000)
+            .map(|i| b'a' + (i % 26) as u8)
+            .collect::<Vec<_>>();
+        backend.set("big".to_string(), BulkString::new(value.clone()).into());
+        let (addr, handle) = spawn_server(Server::builder().backend(backend))?;
+        let mut client = TcpStream::connect(addr).await?;
+
+        // written apart from the write buffer, in the order of the requests
+        client.write_all(b"GET k\r\nGET big\r\nPING\r\n").await?;
+        let mut expected = b"$-1\r\n$100000\r\n".to_vec();
+        expected.extend_from_slice(&value);
+        expected.extend_from_slice(b"\r\n+PONG\r\n");
+        let mut replies = vec![0u8; expected.len()];
+        client.read_exact(&mut replies).await?;
+        assert_eq!(replies, expected);
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamed_aggregate_reply() -> Result<()> {
+        let backend = Backend::new();
+        let values = (0..5000)
+            .map(|i| BulkString::new(format!("v{}", i)).into())
+            .collect::<Vec<RespFrame>>();
+        backend.rpush("list", values)?;
+        for i in 0..2000 {
+            backend.hset("h".to_string(), format!("f{:04}", i), RespFrame::Integer(i))?;
+        }
+        let (addr, handle) = spawn_server(Server::builder().backend(backend))?;
+        let mut client = TcpStream::connect(addr).await?;
+
+        // the same bytes as the replies encoded at once, maps as arrays in RESP2
+        client
+            .write_all(b"LRANGE list 0 -1\r\nHGETALL h\r\nPING\r\n")
+            .await?;
+        let mut expected = b"*5000\r\n".to_vec();
+        for i in 0..5000 {
+            let value = format!("v{}", i);
+            expected.extend_from_slice(format!("${}\r\n{}\r\n", value.len(), value).as_bytes());
+        }
+        expected.extend_from_slice(b"*4000\r\n");
+        for i in 0..2000 {
+            expected.extend_from_slice(format!("$5\r\nf{:04}\r\n:+{}\r\n", i, i).as_bytes());
+        }
+        expected.extend_from_slice(b"+PONG\r\n");
+        let mut replies = vec![0u8; expected.len()];
+        client.read_exact(&mut replies).await?;
+        assert_eq!(replies, expected);
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tracking_connection() -> Result<()> {
+        let (addr, handle) = spawn_server(Server::builder())?;
+        let mut client = TcpStream::connect(addr).await?;
+        let mut writer = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 256];
+
+        client
+            .write_all(b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"%"));
+        client
+            .write_all(b"*3\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$2\r\non\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        client.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"_\r\n");
+
+        // the key it read is invalidated when another client writes it
+        writer
+            .write_all(b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n")
+            .await?;
+        let n = writer.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b">2\r\n$10\r\ninvalidate\r\n*1\r\n$1\r\nk\r\n");
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_worker_threads_connection() -> Result<()> {
+        let backend = Backend::new();
+        backend.config_load(vec![("worker-threads".to_string(), "2".to_string())])?;
+        let (addr, handle) = spawn_server(Server::builder().backend(backend))?;
+        let mut client = TcpStream::connect(addr).await?;
+        let mut writer = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 256];
+
+        client
+            .write_all(b"*2\r\n$5\r\nhello\r\n$1\r\n3\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"%"));
+        client
+            .write_all(b"*3\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$2\r\non\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        // the keys read on a worker are tracked for the client
+        client.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"_\r\n");
+
+        writer
+            .write_all(b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n")
+            .await?;
+        let n = writer.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b">2\r\n$10\r\ninvalidate\r\n*1\r\n$1\r\nk\r\n");
+        writer.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = writer.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"$1\r\nv\r\n");
+
+        handle.abort();
+        Ok(())
+    }
+
+    // SERVERTEST.ECHO message
+    #[derive(Debug, Clone)]
+    struct Echo(RespFrame);
+
+    impl TryFrom<RespArray> for Echo {
+        type Error = CommandError;
+
+        fn try_from(mut value: RespArray) -> std::result::Result<Self, Self::Error> {
+            Ok(Echo(value.0.pop().unwrap()))
+        }
+    }
+
+    impl CommandExecutor for Echo {
+        fn execute(self, _backend: &Backend) -> RespFrame {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_command_connection() -> Result<()> {
+        let builder = Server::builder().command(CommandSpec::new::<Echo>("servertest.echo", 2));
+        let (addr, handle) = spawn_server(builder)?;
+        let mut stream = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 128];
+        stream
+            .write_all(b"*2\r\n$15\r\nSERVERTEST.ECHO\r\n$2\r\nhi\r\n")
+            .await?;
+        let n = stream.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"$2\r\nhi\r\n");
+        handle.abort();
+
+        // the commands are the server's own: another one knows none of them
+        let (addr, handle) = spawn_server(Server::builder())?;
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"*2\r\n$15\r\nSERVERTEST.ECHO\r\n$2\r\nhi\r\n")
+            .await?;
+        let n = stream.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"-ERR"));
+        handle.abort();
+        // and may register its own under the same name
+        let built = Server::builder()
+            .bind("127.0.0.1:0")
+            .command(CommandSpec::new::<Echo>("servertest.echo", 2))
+            .build();
+        assert!(built.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_hooks_connection() -> Result<()> {
+        let backend = Backend::new();
+        backend.add_before_command_hook(|client, cmd| match cmd {
+            Command::Del(_) if client.addr.is_some() => {
+                Some(SimpleError::new("ERR DEL is audited away").into())
+            }
+            _ => None,
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        backend.add_after_command_hook(move |cmd, reply, _| {
+            let _ = tx.send((matches!(cmd, Command::Set(_)), reply.clone()));
+        });
+        let (addr, handle) = spawn_server(Server::builder().backend(backend))?;
+        let mut stream = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 128];
+
+        stream
+            .write_all(b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1\r\nv\r\n")
+            .await?;
+        let n = stream.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        assert_eq!(
+            rx.recv().await,
+            Some((true, SimpleString::new("OK").into()))
+        );
+
+        // stopped before it runs, so not seen after
+        stream.write_all(b"*2\r\n$3\r\ndel\r\n$1\r\nk\r\n").await?;
+        let n = stream.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"-ERR DEL is audited away\r\n");
+        stream.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = stream.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"$1\r\nv\r\n");
+        let (set, reply) = rx.recv().await.unwrap();
+        assert!(!set);
+        assert_eq!(reply, BulkString::new("v").into());
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth_connection() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_requirepass("secret");
+        let rules = ["on", ">pw", "+get", "~cache:*"].map(String::from);
+        backend.acl_setuser("reader", &rules)?;
+        let (addr, handle) = spawn_server(Server::builder().backend(backend))?;
+        let mut client = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 256];
+
+        client.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"-NOAUTH Authentication required.\r\n");
+        client
+            .write_all(b"*2\r\n$4\r\nauth\r\n$5\r\nwrong\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"-WRONGPASS"));
+        client
+            .write_all(b"*2\r\n$4\r\nauth\r\n$6\r\nsecret\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        client.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"$-1\r\n");
+
+        // RESET asks for the password again, QUIT closes the connection
+        client.write_all(b"*1\r\n$5\r\nreset\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+RESET\r\n");
+        client.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"-NOAUTH"));
+
+        // a user only runs the commands, on the keys, it's allowed
+        client
+            .write_all(b"*3\r\n$4\r\nauth\r\n$6\r\nreader\r\n$2\r\npw\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        client
+            .write_all(b"*2\r\n$3\r\nget\r\n$7\r\ncache:1\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"$-1\r\n");
+        client.write_all(b"*2\r\n$3\r\nget\r\n$1\r\nk\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"-NOPERM No permissions to access a key\r\n");
+        client
+            .write_all(b"*3\r\n$3\r\nset\r\n$7\r\ncache:1\r\n$1\r\nv\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"-NOPERM User reader has no permissions to run the 'set'"));
+
+        client.write_all(b"*1\r\n$4\r\nquit\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        assert_eq!(client.read(&mut buf).await?, 0);
+
+        handle.abort();
+        Ok(())
+    }
+
+    // the fields of every span, as they're recorded
+    #[derive(Clone, Default)]
+    struct SpanFields(Arc<Mutex<Vec<String>>>);
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl tracing::field::Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanFields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            attrs.record(&mut Fields(&mut self.0.lock().unwrap()));
+        }
+
+        fn on_record(&self, _span: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut Fields(&mut self.0.lock().unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_spans_hide_credentials() -> Result<()> {
+        let fields = SpanFields::default();
+        let _default =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+        let backend = Backend::new();
+        backend.set_requirepass("secret-pw");
+        let (addr, handle) = spawn_server(Server::builder().backend(backend))?;
+        let mut client = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 256];
+
+        client.write_all(b"AUTH secret-pw\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        client
+            .write_all(b"HELLO 2 AUTH default secret-pw\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"*"));
+        client.write_all(b"SET k v\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+
+        // the keys are traced, the other arguments aren't
+        let fields = fields.0.lock().unwrap();
+        assert!(fields.iter().any(|field| field == "key=k"));
+        assert!(fields.iter().any(|field| field == "name=auth"));
+        assert!(!fields.iter().any(|field| field.contains("secret-pw")));
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_limits() -> Result<()> {
+        let backend = Backend::new();
+        backend.set_max_connections_per_ip(1);
+        let (addr, handle) = spawn_server(Server::builder().backend(backend.clone()))?;
+        let mut buf = [0u8; 128];
+
+        let mut client = TcpStream::connect(addr).await?;
+        client.write_all(b"PING\r\n").await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+PONG\r\n");
+
+        // told why, then closed
+        let mut rejected = TcpStream::connect(addr).await?;
+        let n = rejected.read(&mut buf).await?;
+        assert_eq!(
+            &buf[..n],
+            b"-ERR max number of connections from your address reached\r\n"
+        );
+        assert_eq!(rejected.read(&mut buf).await?, 0);
+        assert_eq!(backend.stats_report().rejected_connections, 1);
+
+        // once the first is closed, another may connect
+        drop(client);
+        let mut client = loop {
+            let mut client = TcpStream::connect(addr).await?;
+            client.write_all(b"PING\r\n").await?;
+            match client.read(&mut buf).await {
+                Ok(n) if &buf[..n] == b"+PONG\r\n" => break client,
+                Ok(_) | Err(_) => tokio::task::yield_now().await,
+            }
+        };
+
+        // denied, even the connection setting it is closed
+        client
+            .write_all(b"CONFIG SET deny-clients 127.0.0.0/8\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(n == 0 || &buf[..n] == b"+OK\r\n");
+        assert_eq!(client.read(&mut buf).await?, 0);
+        let mut rejected = TcpStream::connect(addr).await?;
+        let n = rejected.read(&mut buf).await?;
+        assert_eq!(
+            &buf[..n],
+            b"-ERR connections from your address are not allowed\r\n"
+        );
+
+        handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_addresses() -> Result<()> {
+        // every IPv4 and IPv6 address on the same port, each listener only taking its own
+        let port = std::net::TcpListener::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        let backend = Backend::new();
+        backend.config_load(vec![
+            ("bind".to_string(), "* ::*".to_string()),
+            ("port".to_string(), port.to_string()),
+        ])?;
+        let server = Server::builder().backend(backend).build()?;
+        let addrs = server.local_addrs()?;
+        assert_eq!(
+            addrs,
+            [
+                SocketAddr::from(([0, 0, 0, 0], port)),
+                SocketAddr::from(([0u16; 8], port))
+            ]
+        );
+        assert_eq!(server.local_addr()?, addrs[0]);
+        let handle = tokio::spawn(server.run(std::future::pending()));
+
+        let mut buf = [0u8; 16];
+        for addr in ["127.0.0.1", "::1"] {
+            let mut client = TcpStream::connect((addr, port)).await?;
+            client.write_all(b"PING\r\n").await?;
+            let n = client.read(&mut buf).await?;
+            assert_eq!(&buf[..n], b"+PONG\r\n");
+        }
+        handle.abort();
+
+        // port 0 listens on TCP nowhere, which needs a tls-port
+        let backend = Backend::new();
+        backend.config_load(vec![("port".to_string(), "0".to_string())])?;
+        let e = Server::builder().backend(backend).build().unwrap_err();
+        assert!(e.to_string().contains("not listen anywhere"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replica_connection() -> Result<()> {
+        let (addr, handle) = spawn_server(Server::builder())?;
+        let mut replica = TcpStream::connect(addr).await?;
+        let mut client = TcpStream::connect(addr).await?;
+        let mut buf = [0u8; 1024];
+
+        replica
+            .write_all(b"*3\r\n$8\r\nREPLCONF\r\n$14\r\nlistening-port\r\n$4\r\n6380\r\n")
+            .await?;
+        let n = replica.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        let set = b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n";
+        client.write_all(set).await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+
+        // +FULLRESYNC replid offset, then the dump as a bulk string without the trailing CRLF
+        replica
+            .write_all(b"*3\r\n$5\r\nPSYNC\r\n$1\r\n?\r\n$2\r\n-1\r\n")
+            .await?;
+        let mut received = Vec::new();
+        let line = |data: &[u8], from: usize| {
+            let end = from + data[from..].windows(2).position(|w| w == b"\r\n")?;
+            Some((
+                String::from_utf8_lossy(&data[from..end]).into_owned(),
+                end + 2,
+            ))
+        };
+        let (fullresync, rdb) = loop {
+            let n = replica.read(&mut buf).await?;
+            received.extend_from_slice(&buf[..n]);
+            let Some((fullresync, next)) = line(&received, 0) else {
+                continue;
+            };
+            let Some((len, start)) = line(&received, next) else {
+                continue;
+            };
+            let len: usize = len[1..].parse()?;
+            if received.len() >= start + len {
+                assert_eq!(received.len(), start + len);
+                break (fullresync, received[start..].to_vec());
+            }
+        };
+        assert!(fullresync.starts_with("+FULLRESYNC "));
+        assert!(fullresync.ends_with(&format!(" {}", set.len())));
+        assert!(rdb.starts_with(b"REDIS"));
+
+        // then the writes, as they're made
+        let set = b"*3\r\n$3\r\nSET\r\n$1\r\nb\r\n$1\r\n2\r\n";
+        client.write_all(set).await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        let n = replica.read(&mut buf).await?;
+        assert_eq!(&buf[..n], set);
+
+        client
+            .write_all(b"*2\r\n$4\r\nINFO\r\n$11\r\nreplication\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        let info = String::from_utf8_lossy(&buf[..n]);
+        assert!(info.contains("connected_slaves:1\r\n"));
+        assert!(info.contains("slave0:ip=127.0.0.1,port=6380,state=online"));
+        assert!(info.contains(&format!("master_repl_offset:{}\r\n", 2 * set.len())));
+
+        // reconnecting, the replica only gets what it missed
+        drop(replica);
+        let missed = b"*3\r\n$3\r\nSET\r\n$1\r\nc\r\n$1\r\n3\r\n";
+        client.write_all(missed).await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        let replid = fullresync.split(' ').nth(1).unwrap_or_default();
+        let next = (2 * set.len() + 1).to_string();
+        let psync = format!(
+            "*3\r\n$5\r\nPSYNC\r\n$40\r\n{}\r\n${}\r\n{}\r\n",
+            replid,
+            next.len(),
+            next
+        );
+        let mut replica = TcpStream::connect(addr).await?;
+        replica.write_all(psync.as_bytes()).await?;
+        let mut expected = format!("+CONTINUE {}\r\n", replid).into_bytes();
+        expected.extend(missed);
+        let mut received = Vec::new();
+        while received.len() < expected.len() {
+            let n = replica.read(&mut buf).await?;
+            assert!(n > 0);
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(received, expected);
+
+        handle.abort();
+        Ok(())
+    }
+
+    // whether key shows up in backend within a few seconds
+    async fn replicated(backend: &Backend, key: &str) -> bool {
+        for _ in 0..500 {
+            if backend.contains_key(key) {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_replicaof_connection() -> Result<()> {
+        let (master, replica) = (Backend::new(), Backend::new());
+        let (master_addr, master_handle) = spawn_server(Server::builder().backend(master.clone()))?;
+        let (replica_addr, replica_handle) =
+            spawn_server(Server::builder().backend(replica.clone()))?;
+        let mut client = TcpStream::connect(master_addr).await?;
+        let mut replica_client = TcpStream::connect(replica_addr).await?;
+        let mut buf = [0u8; 1024];
+
+        master.set("a".to_string(), RespFrame::BulkString(b"1".into()));
+        let port = master_addr.port().to_string();
+        let replicaof = format!(
+            "*3\r\n$9\r\nREPLICAOF\r\n$9\r\n127.0.0.1\r\n${}\r\n{}\r\n",
+            port.len(),
+            port
+        );
+        replica_client.write_all(replicaof.as_bytes()).await?;
+        let n = replica_client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        assert!(replicated(&replica, "a").await);
+
+        // then the writes, as they're made
+        client
+            .write_all(b"*3\r\n$3\r\nSET\r\n$1\r\nb\r\n$1\r\n2\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        assert!(replicated(&replica, "b").await);
+        assert_eq!(replica.master_repl_offset(), master.master_repl_offset());
+
+        let set = b"*3\r\n$3\r\nSET\r\n$1\r\nc\r\n$1\r\n3\r\n";
+        replica_client.write_all(set).await?;
+        let n = replica_client.read(&mut buf).await?;
+        assert!(buf[..n].starts_with(b"-READONLY"));
+
+        replica_client
+            .write_all(b"*2\r\n$4\r\nINFO\r\n$11\r\nreplication\r\n")
+            .await?;
+        let n = replica_client.read(&mut buf).await?;
+        let info = String::from_utf8_lossy(&buf[..n]);
+        assert!(info.contains("role:slave\r\n"));
+        assert!(info.contains(&format!("master_port:{}\r\n", port)));
+        assert!(info.contains("master_link_status:up\r\n"));
+        assert!(info.contains(&format!("master_replid:{}\r\n", master.replid())));
+        let slave = format!("slave0:ip=127.0.0.1,port={},", replica_addr.port());
+        assert_eq!(master.replication_report().replicas.len(), 1);
+        client
+            .write_all(b"*2\r\n$4\r\nINFO\r\n$11\r\nreplication\r\n")
+            .await?;
+        let n = client.read(&mut buf).await?;
+        assert!(String::from_utf8_lossy(&buf[..n]).contains(&slave));
+
+        // promoted, it takes writes again
+        replica_client
+            .write_all(b"*3\r\n$9\r\nREPLICAOF\r\n$2\r\nNO\r\n$3\r\nONE\r\n")
+            .await?;
+        let n = replica_client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+        assert_ne!(replica.replid(), master.replid());
+        replica_client.write_all(set).await?;
+        let n = replica_client.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+
+        master_handle.abort();
+        replica_handle.abort();
+        Ok(())
+    }
+}
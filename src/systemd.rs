@@ -0,0 +1,107 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Who supervises the server, as the `supervised` of redis.conf: with systemd, the server
+/// tells it when it's ready and when it stops, for units of `Type=notify`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Supervised {
+    #[default]
+    No,
+    Systemd,
+    // systemd if it started the server, as told by NOTIFY_SOCKET
+    Auto,
+}
+
+impl Supervised {
+    pub fn is_systemd(self) -> bool {
+        match self {
+            Supervised::No => false,
+            Supervised::Systemd => true,
+            Supervised::Auto => std::env::var_os("NOTIFY_SOCKET").is_some(),
+        }
+    }
+}
+
+impl FromStr for Supervised {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "no" => Ok(Supervised::No),
+            "systemd" => Ok(Supervised::Systemd),
+            "auto" => Ok(Supervised::Auto),
+            _ => Err("argument(s) must be one of the following: no, systemd, auto".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Supervised {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Supervised::No => "no",
+            Supervised::Systemd => "systemd",
+            Supervised::Auto => "auto",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Send state, as `READY=1`, to systemd on the socket of NOTIFY_SOCKET. Returns false if
+/// there's none, the server wasn't started by systemd.
+pub fn notify(state: &str) -> io::Result<bool> {
+    match std::env::var("NOTIFY_SOCKET") {
+        Ok(socket) => notify_socket(&socket, state).map(|()| true),
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(unix)]
+fn notify_socket(socket: &str, state: &str) -> io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    // a name in the abstract namespace
+    #[cfg(target_os = "linux")]
+    if let Some(name) = socket.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+
+        let addr = SocketAddr::from_abstract_name(name)?;
+        datagram.send_to_addr(state.as_bytes(), &addr)?;
+        return Ok(());
+    }
+    datagram.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn notify_socket(_socket: &str, _state: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "systemd notifications need unix sockets",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn test_notify_socket() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.notify", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path)?;
+        notify_socket(path.to_str().unwrap(), "READY=1")?;
+        let mut buf = [0; 64];
+        let n = systemd.recv(&mut buf)?;
+        assert_eq!(&buf[..n], b"READY=1");
+        std::fs::remove_file(&path)?;
+
+        assert_eq!("SYSTEMD".parse(), Ok(Supervised::Systemd));
+        assert!("upstart".parse::<Supervised>().is_err());
+        assert!(!Supervised::No.is_systemd());
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{network, Backend, TlsAuthClients};
+use anyhow::{Context, Result};
+use futures::future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ServerConnection, WebPkiClientVerifier};
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+use x509_parser::parse_x509_certificate;
+
+/// The listeners of TLS connections, on every address of the server and tls-port.
+#[derive(Debug)]
+pub struct TlsListener {
+    listeners: Vec<TcpListener>,
+    config: Arc<ServerConfig>,
+}
+
+impl TlsListener {
+    /// Load the certificate and the key of the server, and the CA of the clients unless
+    /// they don't present certificates, then bind the listeners.
+    pub async fn bind(backend: &Backend) -> Result<Self> {
+        let config = server_config(backend)?;
+        let mut listeners = Vec::new();
+        for ip in backend.bind().0 {
+            let addr = SocketAddr::new(ip, backend.tls_port());
+            let listener = network::listen(addr)
+                .with_context(|| format!("failed to listen for TLS on {}", addr))?;
+            let listener = TcpListener::from_std(listener)?;
+            info!("Simple-Redis-Server is listening for TLS on {}", addr);
+            listeners.push(listener);
+        }
+        Ok(Self { listeners, config })
+    }
+
+    /// Accept connections on every listener until an accept error occurs. Those whose
+    /// handshake fails, e.g. for a certificate that isn't accepted, are closed.
+    pub async fn run(self, backend: Backend) -> Result<()> {
+        let accept_loops = self
+            .listeners
+            .iter()
+            .map(|listener| accept(listener, &self.config, &backend));
+        future::try_join_all(accept_loops).await?;
+        Ok(())
+    }
+}
+
+// accept connections on listener, handshaking with config in a task each
+async fn accept(
+    listener: &TcpListener,
+    config: &Arc<ServerConfig>,
+    backend: &Backend,
+) -> Result<()> {
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        // before the handshake, which costs more than the connection
+        let permit = match backend.admit_connection(raddr.ip()) {
+            Ok(permit) => permit,
+            Err(e) => {
+                network::reject(stream, raddr, e);
+                continue;
+            }
+        };
+        info!("Accepted TLS connection from: {}", raddr);
+        let acceptor = TlsAcceptor::from(config.clone());
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            let connection = async {
+                let stream = acceptor.accept(stream).await?;
+                let common_name = common_name(stream.get_ref().1);
+                network::connection_handler(stream, raddr, common_name, backend).await
+            };
+            let result = tokio::select! {
+                result = connection => result,
+                _ = permit.denied() => Ok(()),
+            };
+            match result {
+                Ok(_) => info!("TLS connection from {} exited", raddr),
+                Err(e) => warn!("Handle error for {}: {:?}", raddr, e),
+            }
+        });
+    }
+}
+
+fn server_config(backend: &Backend) -> Result<Arc<ServerConfig>> {
+    let provider = Arc::new(ring::default_provider());
+    let cert_file = backend.tls_cert_file();
+    let certs = CertificateDer::pem_file_iter(&cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to load tls-cert-file {:?}", cert_file))?;
+    let key_file = backend.tls_key_file();
+    let key = PrivateKeyDer::from_pem_file(&key_file)
+        .with_context(|| format!("failed to load tls-key-file {:?}", key_file))?;
+
+    let verifier = match backend.tls_auth_clients() {
+        TlsAuthClients::No => WebPkiClientVerifier::no_client_auth(),
+        auth_clients => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(
+                Arc::new(client_roots(backend)?),
+                provider.clone(),
+            );
+            match auth_clients {
+                TlsAuthClients::Optional => verifier.allow_unauthenticated().build()?,
+                _ => verifier.build()?,
+            }
+        }
+    };
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)?;
+    Ok(Arc::new(config))
+}
+
+// the CAs of tls-ca-cert-file, which the certificates of the clients must be signed by
+fn client_roots(backend: &Backend) -> Result<RootCertStore> {
+    let ca_cert_file = backend.tls_ca_cert_file();
+    let mut roots = RootCertStore::empty();
+    let certs = CertificateDer::pem_file_iter(&ca_cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to load tls-ca-cert-file {:?}", ca_cert_file))?;
+    for cert in certs {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+// the common name of the subject of the certificate the client presented, if it did
+fn common_name(connection: &ServerConnection) -> Option<String> {
+    let cert = connection.peer_certificates()?.first()?;
+    let (_, cert) = parse_x509_certificate(cert).ok()?;
+    let name = cert.subject().iter_common_name().next()?;
+    name.as_str().ok().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Server;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose,
+        IsCa, KeyPair,
+    };
+    use std::fs;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+
+    #[tokio::test]
+    async fn test_client_certificates() -> Result<()> {
+        // a CA signing the certificate of the server and the one of alice
+        let ca_key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::<String>::new())?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&ca_key)?;
+        let server_key = KeyPair::generate()?;
+        let server = CertificateParams::new(vec!["localhost".to_string()])?.signed_by(
+            &server_key,
+            &ca,
+            &ca_key,
+        )?;
+        let client_key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(Vec::<String>::new())?;
+        params.distinguished_name = DistinguishedName::new();
+        params.distinguished_name.push(DnType::CommonName, "alice");
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client = params.signed_by(&client_key, &ca, &ca_key)?;
+
+        let dir = std::env::temp_dir().join(format!("simple-redis-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("ca.crt"), ca.pem())?;
+        fs::write(dir.join("server.crt"), server.pem())?;
+        fs::write(dir.join("server.key"), server_key.serialize_pem())?;
+
+        let tls_port = std::net::TcpListener::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        let backend = Backend::new();
+        let file = |name: &str| dir.join(name).display().to_string();
+        backend.config_load(vec![
+            ("bind".to_string(), "127.0.0.1".to_string()),
+            // TLS connections only
+            ("port".to_string(), "0".to_string()),
+            ("tls-port".to_string(), tls_port.to_string()),
+            ("tls-cert-file".to_string(), file("server.crt")),
+            ("tls-key-file".to_string(), file("server.key")),
+            ("tls-ca-cert-file".to_string(), file("ca.crt")),
+            ("requirepass".to_string(), "secret".to_string()),
+        ])?;
+        let rules = ["on", "+@all", "~*"].map(String::from);
+        backend.acl_setuser("alice", &rules)?;
+        let handle = tokio::spawn(
+            Server::builder()
+                .backend(backend)
+                .build()?
+                .run(std::future::pending()),
+        );
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone())?;
+        let provider = Arc::new(ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots.clone());
+        let connect = |config: ClientConfig| async move {
+            let stream = loop {
+                match TcpStream::connect(("127.0.0.1", tls_port)).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::task::yield_now().await,
+                }
+            };
+            let connector = TlsConnector::from(Arc::new(config));
+            connector
+                .connect(ServerName::try_from("localhost")?, stream)
+                .await
+                .map_err(anyhow::Error::from)
+        };
+
+        // authenticated as the user its certificate names
+        let config = config.with_client_auth_cert(
+            vec![client.der().clone()],
+            PrivateKeyDer::Pkcs8(client_key.serialize_der().into()),
+        )?;
+        let mut alice = connect(config).await?;
+        let mut buf = [0u8; 64];
+        alice
+            .write_all(b"*2\r\n$3\r\nacl\r\n$6\r\nwhoami\r\n")
+            .await?;
+        let n = alice.read(&mut buf).await?;
+        assert_eq!(&buf[..n], b"$5\r\nalice\r\n");
+
+        // without a certificate, the connection is refused
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        if let Ok(mut anonymous) = connect(config).await {
+            let _ = anonymous.write_all(b"*1\r\n$4\r\nping\r\n").await;
+            assert!(!matches!(anonymous.read(&mut buf).await, Ok(n) if n > 0));
+        }
+
+        handle.abort();
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+// Runs redis-benchmark against a server, when it's installed, to check the server answers
+// everything it sends and to print the throughput of each test.
+//
+// Run with `cargo test --release --test benchmark -- --nocapture` to see the numbers, e.g.
+// with the default tests and `-P 16` to measure pipelined requests. Without redis-benchmark
+// on the PATH, the test passes without running anything.
+
+use anyhow::Result;
+use simple_redis::{Backend, Server};
+use std::process::Command;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_redis_benchmark() -> Result<()> {
+    if Command::new("redis-benchmark")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("redis-benchmark isn't installed, skipping");
+        return Ok(());
+    }
+    let server = Server::builder()
+        .bind("127.0.0.1:0")
+        .backend(Backend::new())
+        .build()?;
+    let addr = server.local_addr()?;
+    let handle = tokio::spawn(server.run(std::future::pending()));
+
+    // the inline PING_INLINE, every other test with multi bulk requests, one by one then
+    // pipelined
+    for pipeline in ["1", "16"] {
+        let port = addr.port().to_string();
+        let args = [
+            "-h",
+            "127.0.0.1",
+            "-p",
+            &port,
+            "-n",
+            "10000",
+            "-c",
+            "8",
+            "-P",
+            pipeline,
+            "-r",
+            "1000",
+            "--csv",
+        ];
+        let output = tokio::task::spawn_blocking({
+            let args = args.map(String::from);
+            move || Command::new("redis-benchmark").args(args).output()
+        })
+        .await??;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            output.status.success(),
+            "redis-benchmark failed: {}",
+            stderr
+        );
+        // a reply it didn't expect is reported and ends the run
+        assert!(
+            !stderr.contains("Error"),
+            "redis-benchmark errors: {}",
+            stderr
+        );
+
+        // "test","rps",... with a line per test
+        let results = stdout
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let mut fields = line.split(',').map(|f| f.trim_matches('"'));
+                Some((
+                    fields.next()?.to_string(),
+                    fields.next()?.parse::<f64>().ok()?,
+                ))
+            })
+            .collect::<Vec<_>>();
+        assert!(results.iter().any(|(test, _)| test == "PING_INLINE"));
+        for (test, rps) in results {
+            assert!(rps > 0.0, "no request of {} was answered", test);
+            println!(
+                "{:<24} {:>12.0} requests per second, -P {}",
+                test, rps, pipeline
+            );
+        }
+    }
+
+    handle.abort();
+    Ok(())
+}
@@ -0,0 +1,698 @@
+// Boots a server on a port chosen by the OS and drives it over TCP with the redis crate, as
+// a client library would: every command family, pipelines and transactions, RESP2 and RESP3,
+// expiry and pub/sub. When redis-cli is installed, it's checked to talk to the server too.
+
+use anyhow::Result;
+use redis::{Connection, RedisResult, Value};
+use simple_redis::{Backend, Server};
+use std::net::SocketAddr;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+struct TestServer {
+    addr: SocketAddr,
+    // the cluster bus, in cluster mode
+    cluster_addr: Option<SocketAddr>,
+    // dropped first, stopping the server before its runtime goes away
+    stop: Option<oneshot::Sender<()>>,
+    _runtime: Runtime,
+}
+
+impl TestServer {
+    fn start() -> Result<Self> {
+        Self::with_backend(Backend::new())
+    }
+
+    fn with_backend(backend: Backend) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()?;
+        let server = Server::builder()
+            .bind("127.0.0.1:0")
+            .cluster_bind("127.0.0.1:0")
+            .backend(backend)
+            .build()?;
+        let addr = server.local_addr()?;
+        let cluster_addr = server.cluster_addr()?;
+        let (stop, stopped) = oneshot::channel::<()>();
+        server.run_on(runtime.handle(), async {
+            let _ = stopped.await;
+        });
+        Ok(Self {
+            addr,
+            cluster_addr,
+            stop: Some(stop),
+            _runtime: runtime,
+        })
+    }
+
+    // a connection speaking RESP2, or RESP3 after a HELLO 3
+    fn connect(&self, resp3: bool) -> Result<Connection> {
+        let protocol = if resp3 { "resp3" } else { "resp2" };
+        let url = format!("redis://{}/?protocol={}", self.addr, protocol);
+        Ok(redis::Client::open(url)?.get_connection()?)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+fn query(con: &mut Connection, command: &str) -> RedisResult<Value> {
+    let mut args = command.split_whitespace();
+    let mut cmd = redis::cmd(args.next().unwrap_or_default());
+    for arg in args {
+        cmd.arg(arg);
+    }
+    cmd.query(con)
+}
+
+// a reply as a short string: scalars as they are, aggregates in brackets, errors by their code
+fn show(reply: RedisResult<Value>) -> String {
+    match reply {
+        Ok(value) => show_value(&value),
+        Err(e) => format!("(error) {}", e.code().unwrap_or("?")),
+    }
+}
+
+fn show_value(value: &Value) -> String {
+    let list = |values: &[Value]| {
+        let values = values.iter().map(show_value).collect::<Vec<_>>();
+        format!("[{}]", values.join(", "))
+    };
+    match value {
+        Value::Nil => "nil".to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::BulkString(s) => String::from_utf8_lossy(s).into_owned(),
+        Value::SimpleString(s) => s.clone(),
+        Value::Okay => "OK".to_string(),
+        Value::Double(d) => d.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Array(values) | Value::Set(values) => list(values),
+        Value::Map(pairs) => {
+            let pairs = pairs
+                .iter()
+                .map(|(k, v)| format!("{}: {}", show_value(k), show_value(v)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", pairs.join(", "))
+        }
+        value => format!("{:?}", value),
+    }
+}
+
+// run the commands one after the other, checking each reply
+fn check(con: &mut Connection, cases: &[(&str, &str)]) {
+    for (command, expected) in cases {
+        assert_eq!(show(query(con, command)), *expected, "{}", command);
+    }
+}
+
+#[test]
+fn test_string_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("PING", "PONG"),
+            ("PING hi", "hi"),
+            ("SELECT 0", "OK"),
+            ("GET k", "nil"),
+            ("SET k v", "OK"),
+            ("GET k", "v"),
+            ("SETNX k w", "0"),
+            ("GETSET k w", "v"),
+            ("APPEND k xy", "3"),
+            ("STRLEN k", "3"),
+            ("GETDEL k", "wxy"),
+            ("MSET a 1 b 2", "OK"),
+            ("MSETNX b 3 c 3", "0"),
+            ("MGET a b c", "[1, 2, nil]"),
+            ("INCR a", "2"),
+            ("DECR a", "1"),
+            ("INCRBY a 10", "11"),
+            ("DECRBY a 5", "6"),
+            ("INCRBYFLOAT a 0.5", "6.5"),
+            ("INCR b x", "(error) ERR"),
+            ("SETEX e 100 v", "OK"),
+            ("PSETEX p 100000 v", "OK"),
+            ("GETEX e PERSIST", "v"),
+            ("TTL e", "-1"),
+            ("MSET s1 ohmytext s2 mynewtext", "OK"),
+            ("LCS s1 s2", "mytext"),
+            ("SETBIT bits 7 1", "0"),
+            ("GETBIT bits 7", "1"),
+            ("BITCOUNT bits", "1"),
+            ("BITPOS bits 1", "7"),
+            ("BITFIELD bf INCRBY u8 0 200", "[200]"),
+            ("PFADD hll a b c", "1"),
+            ("PFADD hll2 c d", "1"),
+            ("PFCOUNT hll hll2", "4"),
+            ("PFMERGE hll3 hll hll2", "OK"),
+            ("PFCOUNT hll3", "4"),
+            ("PFCOUNT hll s1", "(error) WRONGTYPE"),
+            ("DEL a b c missing", "2"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_hash_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("HSET h f1 v1 f2 v2", "2"),
+            ("HGET h f1", "v1"),
+            ("HMGET h f1 nope", "[v1, nil]"),
+            ("HEXISTS h f2", "1"),
+            ("HLEN h", "2"),
+            ("HSTRLEN h f2", "2"),
+            ("HKEYS h", "[f1, f2]"),
+            ("HVALS h", "[v1, v2]"),
+            ("HGETALL h", "[f1, v1, f2, v2]"),
+            ("HINCRBY h n 5", "5"),
+            ("HINCRBYFLOAT h n 1.5", "6.5"),
+            ("HDEL h n nope", "1"),
+            ("HSET one f v", "1"),
+            ("HRANDFIELD one", "f"),
+            ("HSCAN h 0", "[0, [f1, v1, f2, v2]]"),
+            ("HEXPIRE h 100 FIELDS 1 f1", "[1]"),
+            ("HTTL h FIELDS 2 f1 f2", "[100, -1]"),
+            ("HPEXPIRE h 100000 FIELDS 1 f2", "[1]"),
+            ("HPERSIST h FIELDS 1 f1", "[1]"),
+            ("HPTTL h FIELDS 1 f1", "[-1]"),
+            ("SET s v", "OK"),
+            ("HGET s f", "(error) WRONGTYPE"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_list_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("RPUSH l a b c", "3"),
+            ("LPUSH l z", "4"),
+            ("LRANGE l 0 -1", "[z, a, b, c]"),
+            ("LLEN l", "4"),
+            ("LINDEX l 1", "a"),
+            ("LSET l 0 y", "OK"),
+            ("LINSERT l BEFORE a x", "5"),
+            ("LPOS l b", "3"),
+            ("LREM l 1 x", "1"),
+            ("LTRIM l 0 2", "OK"),
+            ("LPOP l", "y"),
+            ("RPOP l", "b"),
+            ("RPUSH l d", "2"),
+            ("LMOVE l m LEFT RIGHT", "a"),
+            ("RPOPLPUSH l m", "d"),
+            ("LMPOP 2 l m LEFT", "[m, [d]]"),
+            ("BLPOP m 1", "[m, a]"),
+            ("BRPOP m 0.1", "nil"),
+            ("RPUSH l e", "1"),
+            ("BLMOVE l m RIGHT LEFT 1", "e"),
+            ("BLMPOP 1 1 m RIGHT", "[m, [e]]"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_set_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            // the order of the members isn't defined, the replies have a single one
+            ("SADD s1 a b c", "3"),
+            ("SADD s2 c d e", "3"),
+            ("SREM s2 e", "1"),
+            ("SCARD s1", "3"),
+            ("SISMEMBER s1 a", "1"),
+            ("SMISMEMBER s1 a z", "[1, 0]"),
+            ("SINTER s1 s2", "[c]"),
+            ("SDIFF s2 s1", "[d]"),
+            ("SUNIONSTORE u s1 s2", "4"),
+            ("SINTERSTORE i s1 s2", "1"),
+            ("SDIFFSTORE d s2 s1", "1"),
+            ("SMEMBERS d", "[d]"),
+            ("SUNION d missing", "[d]"),
+            ("SSCAN d 0", "[0, [d]]"),
+            ("SRANDMEMBER d", "d"),
+            ("SMOVE d i d", "1"),
+            ("SPOP d", "nil"),
+            ("SCARD i", "2"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_sorted_set_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("ZADD z 1 a 2 b 3 c", "3"),
+            ("ZSCORE z b", "2"),
+            ("ZCARD z", "3"),
+            ("ZRANGE z 0 -1", "[a, b, c]"),
+            ("ZREVRANGE z 0 0", "[c]"),
+            ("ZRANGEBYSCORE z 2 +inf", "[b, c]"),
+            ("ZREVRANGEBYSCORE z 2 -inf", "[b, a]"),
+            ("ZCOUNT z 1 2", "2"),
+            ("ZINCRBY z 10 a", "11"),
+            ("ZRANK z a", "2"),
+            ("ZREVRANK z a", "0"),
+            ("ZMSCORE z a nope", "[11, nil]"),
+            ("ZREM z a", "1"),
+            ("ZADD lex 0 a 0 b 0 c", "3"),
+            ("ZRANGEBYLEX lex [b +", "[b, c]"),
+            ("ZREVRANGEBYLEX lex + (b", "[c]"),
+            ("ZLEXCOUNT lex - +", "3"),
+            ("ZUNION 2 z lex", "[a, b, c]"),
+            ("ZINTER 2 z lex", "[b, c]"),
+            ("ZDIFF 2 lex z", "[a]"),
+            ("ZUNIONSTORE u 2 z lex", "3"),
+            ("ZINTERSTORE i 2 z lex", "2"),
+            ("ZDIFFSTORE d 2 lex z", "1"),
+            ("ZREMRANGEBYRANK u 0 0", "1"),
+            ("ZREMRANGEBYSCORE u 0 2", "1"),
+            ("ZREMRANGEBYLEX lex [a [a", "1"),
+            ("ZRANDMEMBER d", "a"),
+            ("ZSCAN d 0", "[0, [a, 0]]"),
+            ("ZPOPMIN z", "[b, 2]"),
+            ("ZPOPMAX z", "[c, 3]"),
+            ("BZPOPMIN lex 1", "[lex, b, 0]"),
+            ("BZPOPMAX lex 1", "[lex, c, 0]"),
+        ],
+    );
+    // geo members are in a zset, scored by their geohash
+    check(
+        &mut con,
+        &[
+            (
+                "GEOADD Sicily 13.361389 38.115556 Palermo 15.087269 37.502669 Catania",
+                "2",
+            ),
+            ("ZSCORE Sicily Palermo", "3479099956230698"),
+            ("GEODIST Sicily Palermo Catania km", "166.2742"),
+            ("GEODIST Sicily Palermo nope", "nil"),
+            (
+                "GEOSEARCH Sicily FROMLONLAT 15 37 BYRADIUS 100 km WITHDIST",
+                "[[Catania, 56.4413]]",
+            ),
+            (
+                "GEOSEARCH Sicily FROMMEMBER Palermo BYBOX 400 400 km DESC",
+                "[Catania, Palermo]",
+            ),
+            ("GEOADD Sicily 200 0 nope", "(error) ERR"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_stream_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("XADD x 1-1 f v", "1-1"),
+            ("XADD x 2-1 f w", "2-1"),
+            ("XLEN x", "2"),
+            ("XRANGE x - +", "[[1-1, [f, v]], [2-1, [f, w]]]"),
+            ("XREVRANGE x + - COUNT 1", "[[2-1, [f, w]]]"),
+            ("XREAD STREAMS x 1-1", "[[x, [[2-1, [f, w]]]]]"),
+            ("XGROUP CREATE x g 0", "OK"),
+            (
+                "XREADGROUP GROUP g c COUNT 1 STREAMS x >",
+                "[[x, [[1-1, [f, v]]]]]",
+            ),
+            ("XPENDING x g", "[1, 1-1, 1-1, [[c, 1]]]"),
+            ("XCLAIM x g d 0 1-1 JUSTID", "[1-1]"),
+            ("XAUTOCLAIM x g c 0 0 JUSTID", "[0-0, [1-1], []]"),
+            ("XACK x g 1-1", "1"),
+            (
+                "XINFO GROUPS x",
+                "[[consumers, 2, lag, 1, last-delivered-id, 1-1, name, g, pending, 0]]",
+            ),
+            ("XINFO CONSUMERS x nope", "(error) NOGROUP"),
+            ("XGROUP CREATECONSUMER x g e", "1"),
+            ("XGROUP DELCONSUMER x g d", "0"),
+            ("XGROUP SETID x g $", "OK"),
+            ("XSETID x 1-0", "(error) ERR"),
+            ("XSETID x 5-0", "OK"),
+            ("XDEL x 1-1", "1"),
+            ("XTRIM x MAXLEN 0", "1"),
+            ("XGROUP DESTROY x g", "1"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_keyspace_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("SET k v", "OK"),
+            ("EXPIRE k 100", "1"),
+            ("TTL k", "100"),
+            ("PERSIST k", "1"),
+            ("PTTL k", "-1"),
+            ("PEXPIRE k 100000", "1"),
+            ("EXPIREAT k 4102444800", "1"),
+            ("EXPIRETIME k", "4102444800"),
+            ("PEXPIREAT k 4102444800000", "1"),
+            ("PEXPIRETIME k", "4102444800000"),
+            ("RENAME k k2", "OK"),
+            ("SET k v", "OK"),
+            ("RENAMENX k k2", "0"),
+            ("RPUSH l 3 1 2", "3"),
+            ("SORT l", "[1, 2, 3]"),
+            ("SORT l DESC STORE sorted", "3"),
+            ("OBJECT ENCODING k", "embstr"),
+            ("OBJECT REFCOUNT k", "1"),
+            ("DEL k k2 l sorted", "4"),
+            ("TTL k", "-2"),
+            ("DUMP k", "nil"),
+            ("MIGRATE 127.0.0.1 1 k 0 100", "NOKEY"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_server_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("EVAL return(1) 0", "1"),
+            (
+                "SCRIPT LOAD return'x'",
+                "e3dac64e665973fab5c5a16c7378011a9a847dcf",
+            ),
+            (
+                "SCRIPT EXISTS e3dac64e665973fab5c5a16c7378011a9a847dcf",
+                "[1]",
+            ),
+            ("EVALSHA e3dac64e665973fab5c5a16c7378011a9a847dcf 0", "x"),
+            ("SCRIPT FLUSH", "OK"),
+            ("CONFIG GET maxmemory", "[maxmemory, 0]"),
+            ("CONFIG SET maxmemory 0", "OK"),
+            ("ACL WHOAMI", "default"),
+            ("ACL SETUSER alice on >pw +get ~*", "OK"),
+            ("ACL DELUSER alice", "1"),
+            ("CLIENT TRACKING off", "OK"),
+            ("CLIENT NO-EVICT on", "OK"),
+            ("CONFIG SET maxmemory-clients 10%", "OK"),
+            ("LATENCY RESET", "0"),
+            ("MEMORY USAGE missing", "nil"),
+            ("MEMORY PURGE", "OK"),
+            ("DEBUG DIGEST", "0000000000000000000000000000000000000000"),
+            ("WAITAOF 0 0 0", "[0, 0]"),
+            ("WAIT 0 0", "0"),
+            ("WAITAOF 1 0 0", "(error) ERR"),
+            ("UNKNOWN", "(error) ERR"),
+            ("SENTINEL MASTERS", "(error) ERR"),
+            ("COMMAND GETKEYS ZUNIONSTORE d 2 a b", "[d, a, b]"),
+            ("COMMAND GETKEYS PING", "(error) ERR"),
+        ],
+    );
+    let info = show(query(&mut con, "INFO stats"));
+    assert!(info.contains("# Stats"), "{}", info);
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_and_transaction() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+
+    let (a, b, missing): (i64, i64, Option<String>) = redis::pipe()
+        .incr("n", 1)
+        .incr("n", 2)
+        .get("missing")
+        .query(&mut con)?;
+    assert_eq!((a, b, missing), (1, 3, None));
+
+    // MULTI/EXEC around the commands, their replies those of EXEC
+    let (n, items): (i64, Vec<String>) = redis::pipe()
+        .atomic()
+        .incr("n", 1)
+        .rpush("l", &["a", "b"])
+        .ignore()
+        .lrange("l", 0, -1)
+        .query(&mut con)?;
+    assert_eq!((n, items), (4, vec!["a".to_string(), "b".to_string()]));
+
+    // a failed command doesn't stop the others of a pipeline
+    let failed: RedisResult<Value> = redis::pipe()
+        .cmd("INCR")
+        .arg("l")
+        .set("after", 1)
+        .query(&mut con);
+    assert_eq!(failed.unwrap_err().code(), Some("WRONGTYPE"));
+    check(&mut con, &[("GET after", "1")]);
+    Ok(())
+}
+
+#[test]
+fn test_protocol_negotiation() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut resp2 = server.connect(false)?;
+    let mut resp3 = server.connect(true)?;
+    check(&mut resp2, &[("HSET h f v", "1"), ("SADD s m", "1")]);
+
+    // maps, sets and doubles have types of their own in RESP3
+    let hash = query(&mut resp2, "HGETALL h")?;
+    assert!(matches!(hash, Value::Array(_)), "{:?}", hash);
+    let hash = query(&mut resp3, "HGETALL h")?;
+    assert!(matches!(hash, Value::Map(_)), "{:?}", hash);
+    let set = query(&mut resp3, "SMEMBERS s")?;
+    assert!(matches!(set, Value::Set(_)), "{:?}", set);
+    let score = query(&mut resp3, "ZINCRBY z 1.5 m")?;
+    assert_eq!(score, Value::Double(1.5));
+    assert_eq!(query(&mut resp3, "GET missing")?, Value::Nil);
+
+    let hello = query(&mut resp2, "HELLO 3")?;
+    let Value::Map(fields) = hello else {
+        panic!("unexpected HELLO reply: {:?}", hello);
+    };
+    let proto = fields
+        .iter()
+        .find(|(k, _)| show_value(k) == "proto")
+        .map(|(_, v)| v.clone());
+    assert_eq!(proto, Some(Value::Int(3)));
+    Ok(())
+}
+
+#[test]
+fn test_expiry() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("PSETEX k 100 v", "OK"),
+            ("SETEX kept 100 v", "OK"),
+            ("HSET h f v", "1"),
+            ("HPEXPIRE h 100 FIELDS 1 f", "[1]"),
+            ("EXPIRE kept 50 GT", "0"),
+            ("EXPIRE kept 200 XX GT", "1"),
+            ("EXPIRE kept 300 NX", "0"),
+            ("TTL kept", "200"),
+            ("EXPIRE kept 100 NX GT", "(error) ERR"),
+        ],
+    );
+    thread::sleep(Duration::from_millis(250));
+    check(
+        &mut con,
+        &[
+            ("GET k", "nil"),
+            ("GET kept", "v"),
+            ("HGET h f", "nil"),
+            ("TTL k", "-2"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_busy_script() -> Result<()> {
+    let backend = Backend::new();
+    backend.set_busy_script_time_limit(0);
+    let server = TestServer::with_backend(backend.clone())?;
+    let mut con = server.connect(false)?;
+    let mut scripted = server.connect(false)?;
+    let script = thread::spawn(move || {
+        let reply = redis::cmd("EVAL")
+            .arg("while true do end")
+            .arg(0)
+            .query(&mut scripted);
+        show(reply)
+    });
+
+    // the other connections are told the server is busy, until the script is killed
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !backend.is_script_busy() {
+        assert!(Instant::now() < deadline, "the script never got busy");
+        thread::sleep(Duration::from_millis(10));
+    }
+    check(
+        &mut con,
+        &[("GET k", "(error) BUSY"), ("SCRIPT KILL", "OK")],
+    );
+    assert_eq!(script.join().unwrap(), "(error) ERR");
+    check(
+        &mut con,
+        &[("GET k", "nil"), ("SCRIPT KILL", "(error) NOTBUSY")],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_pubsub() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut subscriber = server.connect(false)?;
+    let mut publisher = server.connect(false)?;
+
+    let mut pubsub = subscriber.as_pubsub();
+    pubsub.subscribe("news")?;
+    pubsub.psubscribe("sport.*")?;
+    pubsub.set_read_timeout(Some(Duration::from_secs(5)))?;
+    check(
+        &mut publisher,
+        &[
+            ("PUBLISH news hello", "1"),
+            ("PUBLISH sport.ball goal", "1"),
+            ("PUBLISH other x", "0"),
+            ("SPUBLISH news x", "0"),
+        ],
+    );
+    let message = pubsub.get_message()?;
+    assert_eq!(message.get_channel_name(), "news");
+    assert_eq!(message.get_payload::<String>()?, "hello");
+    let message = pubsub.get_message()?;
+    assert_eq!(message.get_pattern::<String>()?, "sport.*");
+    assert_eq!(message.get_payload::<String>()?, "goal");
+    Ok(())
+}
+
+#[test]
+fn test_cluster_redirects() -> Result<()> {
+    // two nodes splitting the slots, the first meeting the second over the cluster bus
+    let config = |slots: &str| -> Result<Backend> {
+        let backend = Backend::new();
+        backend.config_load(vec![
+            ("cluster-enabled".to_string(), "yes".to_string()),
+            ("cluster-slots".to_string(), slots.to_string()),
+        ])?;
+        Ok(backend)
+    };
+    let (first, second) = (config("0-8191")?, config("8192-16383")?);
+    let first_server = TestServer::with_backend(first.clone())?;
+    let second_server = TestServer::with_backend(second.clone())?;
+    let cluster_port = second_server.cluster_addr.map(|addr| addr.port());
+    let meet = format!(
+        "CLUSTER MEET 127.0.0.1 {} {}",
+        second_server.addr.port(),
+        cluster_port.unwrap_or_default()
+    );
+    check(&mut first_server.connect(false)?, &[(&meet, "OK")]);
+
+    // each learns the ID of the other, and the slots it serves
+    let (first_id, second_id) = (first.node_id(), second.node_id());
+    let knows = |backend: &Backend, id: &str| {
+        let nodes = backend.cluster_nodes();
+        nodes.len() == 2 && nodes[1].id == id && nodes[1].slots.count() == 8192
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !(knows(&first, &second_id) && knows(&second, &first_id)) {
+        assert!(Instant::now() < deadline, "the nodes didn't meet");
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // bar is in slot 5061, foo in 12182
+    let mut con = first_server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            ("SET bar 1", "OK"),
+            ("SET foo 1", "(error) MOVED"),
+            ("MSET {bar}1 1 {bar}2 2", "OK"),
+            ("MGET foo bar", "(error) CROSSSLOT"),
+            ("CLUSTER KEYSLOT foo", "12182"),
+            // a shard channel is served by the node of its slot
+            ("SPUBLISH bar m", "0"),
+            ("SPUBLISH foo m", "(error) MOVED"),
+        ],
+    );
+    let moved = query(&mut con, "GET foo").unwrap_err();
+    assert_eq!(
+        moved.redirect_node(),
+        Some((second_server.addr.to_string().as_str(), 12182))
+    );
+
+    // foo is moving to the first node, which serves it once asked
+    let mut con = second_server.connect(false)?;
+    let migrating = format!("CLUSTER SETSLOT 12182 MIGRATING {}", first_id);
+    let importing = format!("CLUSTER SETSLOT 12182 IMPORTING {}", second_id);
+    check(&mut con, &[(&migrating, "OK"), ("GET foo", "(error) ASK")]);
+    let mut con = first_server.connect(false)?;
+    check(
+        &mut con,
+        &[
+            (&importing, "OK"),
+            ("GET foo", "(error) MOVED"),
+            ("ASKING", "OK"),
+            ("SET foo 2", "OK"),
+            ("GET foo", "(error) MOVED"),
+        ],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_redis_cli() -> Result<()> {
+    if Command::new("redis-cli").arg("--version").output().is_err() {
+        eprintln!("redis-cli isn't installed, skipping");
+        return Ok(());
+    }
+    let server = TestServer::start()?;
+    let port = server.addr.port().to_string();
+    let cli = |args: &[&str]| -> Result<String> {
+        let output = Command::new("redis-cli")
+            .args(["-h", "127.0.0.1", "-p", &port])
+            .args(args)
+            .output()?;
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    };
+    assert_eq!(cli(&["SET", "k", "v"])?, "OK");
+    assert_eq!(cli(&["GET", "k"])?, "v");
+    assert_eq!(cli(&["-3", "HSET", "h", "f", "v"])?, "1");
+    Ok(())
+}
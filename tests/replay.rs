@@ -0,0 +1,75 @@
+// Replays the captured command journals in tests/journals against a fresh backend and checks
+// the final DEBUG DIGEST against the recorded value in the sibling `.digest` file.
+//
+// Run with `UPDATE_JOURNAL_DIGESTS=1 cargo test --test replay` to record new digests after an
+// intended change of command semantics.
+
+use anyhow::Result;
+use bytes::BytesMut;
+use simple_redis::cmd::{Command, CommandExecutor};
+use simple_redis::{Backend, RespArray, RespDecode, RespFrame};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn replay(backend: &Backend, journal: &[u8]) -> Result<()> {
+    let mut buf = BytesMut::from(journal);
+    while !buf.is_empty() {
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd = Command::try_from(frame)?;
+        cmd.execute(backend);
+    }
+    Ok(())
+}
+
+fn digest(backend: &Backend) -> Result<String> {
+    let frame = RespArray::new([b"debug".into(), b"digest".into()]);
+    match Command::try_from(frame)?.execute(backend) {
+        RespFrame::SimpleString(s) => Ok(s.to_string()),
+        frame => anyhow::bail!("unexpected DEBUG DIGEST reply: {:?}", frame),
+    }
+}
+
+fn journals() -> Result<Vec<PathBuf>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/journals");
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "resp"));
+    paths.sort();
+    Ok(paths)
+}
+
+#[test]
+fn test_replay_journals() -> Result<()> {
+    let update = std::env::var_os("UPDATE_JOURNAL_DIGESTS").is_some();
+    let paths = journals()?;
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let backend = Backend::new();
+        replay(&backend, &fs::read(&path)?)?;
+        let actual = digest(&backend)?;
+
+        let digest_path = path.with_extension("digest");
+        if update {
+            fs::write(&digest_path, format!("{}\n", actual))?;
+            continue;
+        }
+        let expected = fs::read_to_string(&digest_path)?;
+        assert_eq!(actual, expected.trim(), "digest mismatch for {:?}", path);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_replay_is_deterministic() -> Result<()> {
+    for path in journals()? {
+        let journal = fs::read(&path)?;
+        let first = Backend::new();
+        replay(&first, &journal)?;
+        let second = Backend::new();
+        replay(&second, &journal)?;
+        assert_eq!(digest(&first)?, digest(&second)?);
+    }
+    Ok(())
+}